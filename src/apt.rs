@@ -0,0 +1,512 @@
+//! APT backend shared by every target that installs or removes Debian packages.
+//!
+//! Before running `apt-get` it checks the dpkg and apt list locks. When another
+//! process such as `unattended-upgrades` holds one, it says which process that is
+//! and waits with backoff instead of letting `apt-get` fail with a bare exit status.
+//! An `apt-get` run that fails because the lock was taken in the meantime is retried.
+//! Under `--offline` nothing is downloaded; see [`crate::offline`]. Downloads go
+//! through the proxy from [`crate::network`], if one is set.
+
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+
+use crate::output::say;
+use crate::{exec, network, offline, privileged};
+
+const LOCKS: &[&str] = &[
+    "/var/lib/dpkg/lock-frontend",
+    "/var/lib/dpkg/lock",
+    "/var/lib/apt/lists/lock",
+];
+const LOCK_TIMEOUT: Duration = Duration::from_secs(600);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_ATTEMPTS: usize = 3;
+
+pub fn install_missing(packages: &[&str]) -> anyhow::Result<Vec<String>> {
+    let missing = missing_packages(packages)?;
+    if missing.is_empty() {
+        return Ok(missing);
+    }
+
+    let refs = missing.iter().map(String::as_str).collect::<Vec<_>>();
+    install(&refs)?;
+    Ok(missing)
+}
+
+/// Refreshes the package lists and installs `packages`, whether or not they are
+/// already installed.
+pub fn install(packages: &[&str]) -> anyhow::Result<()> {
+    update()?;
+    let mut args = vec!["install", "-y"];
+    args.extend(packages.iter().copied());
+    run(&args)
+}
+
+/// Installs a local `.deb` file, letting apt pull in its dependencies.
+pub fn install_local(file: &Path, allow_downgrade: bool) -> anyhow::Result<()> {
+    install_file(
+        file,
+        if allow_downgrade {
+            &["--allow-downgrades"]
+        } else {
+            &[]
+        },
+    )
+}
+
+/// Installs a `.deb` that was just built, even when the same or a newer version is
+/// installed, so its new contents always land.
+pub fn reinstall_local(file: &Path) -> anyhow::Result<()> {
+    install_file(file, &["--reinstall", "--allow-downgrades"])
+}
+
+fn install_file(file: &Path, flags: &[&str]) -> anyhow::Result<()> {
+    if file.extension().and_then(|ext| ext.to_str()) != Some("deb") {
+        bail!("{} is not a .deb file", file.display());
+    }
+    if !file.is_file() {
+        bail!("{} does not exist", file.display());
+    }
+    // apt only treats the argument as a file when it contains a slash.
+    let file =
+        fs::canonicalize(file).with_context(|| format!("failed to resolve {}", file.display()))?;
+    let file = file.to_string_lossy();
+    update()?;
+    let mut args = vec!["install", "-y"];
+    args.extend(flags);
+    args.push(&file);
+    run(&args)
+}
+
+pub fn update() -> anyhow::Result<()> {
+    if offline::is_enabled() {
+        say!("Offline: skipping apt-get update.");
+        return Ok(());
+    }
+    run(&["update"])
+}
+
+/// Refreshes the package lists of the one deb822 `.sources` file at `path`, leaving
+/// the lists of every other source alone.
+pub fn update_source(path: &Path) -> anyhow::Result<()> {
+    if offline::is_enabled() {
+        say!("Offline: skipping apt-get update for {}.", path.display());
+        return Ok(());
+    }
+    run(&[
+        "update",
+        "-o",
+        &format!("Dir::Etc::sourcelist={}", path.display()),
+        "-o",
+        "Dir::Etc::sourceparts=-",
+        "-o",
+        "APT::Get::List-Cleanup=0",
+    ])
+}
+
+pub fn remove(packages: &[&str]) -> anyhow::Result<()> {
+    let mut args = vec!["remove", "-y"];
+    args.extend(packages.iter().copied());
+    run(&args)
+}
+
+/// Removes `packages` together with their system-wide configuration files.
+pub fn purge(packages: &[&str]) -> anyhow::Result<()> {
+    let mut args = vec!["purge", "-y"];
+    args.extend(packages.iter().copied());
+    run(&args)
+}
+
+/// Removes packages that were only installed as dependencies and are no longer needed.
+pub fn autoremove() -> anyhow::Result<()> {
+    run(&["autoremove", "-y"])
+}
+
+pub fn package_installed(package: &str) -> anyhow::Result<bool> {
+    let status = exec::query(Command::new("dpkg-query").args(["-W", "-f=${Status}", package]))
+        .with_context(|| format!("failed to query package `{package}`"))?;
+
+    if !status.status.success() {
+        return Ok(false);
+    }
+
+    let stdout = String::from_utf8_lossy(&status.stdout);
+    Ok(stdout.trim().eq_ignore_ascii_case("install ok installed"))
+}
+
+/// Installed version of `package` as dpkg reports it.
+pub fn package_version(package: &str) -> Option<String> {
+    dpkg_query(package, "${Version}")
+}
+
+/// The version and status of `package` as dpkg reports them, such as
+/// `1.2.0 install ok installed`.
+pub fn package_status(package: &str) -> Option<String> {
+    dpkg_query(package, "${Version} ${Status}")
+}
+
+fn dpkg_query(package: &str, format: &str) -> Option<String> {
+    let output =
+        exec::query(Command::new("dpkg-query").args(["-W", &format!("-f={format}"), package]))
+            .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if value.is_empty() { None } else { Some(value) }
+}
+
+/// An installed package with another candidate version in the package lists.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Upgrade {
+    pub package: String,
+    pub installed: String,
+    pub candidate: String,
+}
+
+/// The installed `packages` that `apt upgrade` would change, going by `apt-cache
+/// policy` and the package lists as of the last `apt update`. Packages that are not
+/// installed are left out.
+pub fn upgradable(packages: &[&str]) -> anyhow::Result<Vec<Upgrade>> {
+    if packages.is_empty() {
+        return Ok(Vec::new());
+    }
+    let output = exec::query(Command::new("apt-cache").arg("policy").args(packages))
+        .context("failed to run `apt-cache policy`")?;
+    if !output.status.success() {
+        bail!(
+            "`apt-cache policy` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(parse_policy(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Reads the `Installed:` and `Candidate:` lines under each `package:` heading of
+/// `apt-cache policy`, where `(none)` stands for no version.
+fn parse_policy(policy: &str) -> Vec<Upgrade> {
+    let mut upgrades = Vec::new();
+    let mut package = None;
+    let mut installed = None;
+    for line in policy.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            package = line.strip_suffix(':').map(str::to_string);
+            installed = None;
+        } else if let Some(version) = line.trim().strip_prefix("Installed:") {
+            installed = Some(version.trim()).filter(|version| *version != "(none)");
+        } else if let Some(candidate) = line.trim().strip_prefix("Candidate:") {
+            let candidate = candidate.trim();
+            if let (Some(package), Some(installed)) = (&package, installed.take())
+                && candidate != "(none)"
+                && candidate != installed
+            {
+                upgrades.push(Upgrade {
+                    package: package.clone(),
+                    installed: installed.to_string(),
+                    candidate: candidate.to_string(),
+                });
+            }
+        }
+    }
+    upgrades
+}
+
+fn missing_packages(packages: &[&str]) -> anyhow::Result<Vec<String>> {
+    let mut missing = Vec::new();
+    for package in packages {
+        if !package_installed(package)? {
+            missing.push((*package).to_string());
+        }
+    }
+    Ok(missing)
+}
+
+fn run(args: &[&str]) -> anyhow::Result<()> {
+    let full_args = apt_get_args(
+        args,
+        offline::is_enabled(),
+        offline::apt_cache(),
+        network::proxy(),
+    );
+    let mut command = privileged::command("apt-get")?;
+    command
+        .args(&full_args)
+        .env("DEBIAN_FRONTEND", "noninteractive");
+
+    let mut attempt = 1;
+    loop {
+        if !exec::is_dry_run() {
+            wait_for_locks()?;
+        }
+        let status = exec::status_step(&mut command, &step_name(args))
+            .context("failed to launch apt-get")?;
+        if status.success() {
+            return Ok(());
+        }
+        if attempt < MAX_ATTEMPTS
+            && let Some(holder) = lock_holder()
+        {
+            say!(
+                "apt-get {} lost the race for {holder}; retrying",
+                args.join(" ")
+            );
+            attempt += 1;
+            continue;
+        }
+        if offline::is_enabled() && args.first() == Some(&"install") {
+            bail!(
+                "apt-get {} failed with status {}; in offline mode every package and its \
+                 dependencies must already be downloaded (see --apt-cache)",
+                args.join(" "),
+                status
+            );
+        }
+        bail!("apt-get {} failed with status {}", args.join(" "), status);
+    }
+}
+
+/// Adds the options for the proxy and `--apt-cache` and, for installs in offline
+/// mode, `--no-download` in front of `args`.
+fn apt_get_args(
+    args: &[&str],
+    offline: bool,
+    cache: Option<&Path>,
+    proxy: Option<&str>,
+) -> Vec<String> {
+    let mut full = Vec::new();
+    if let Some(proxy) = proxy {
+        for scheme in ["http", "https"] {
+            full.push("-o".to_string());
+            full.push(format!("Acquire::{scheme}::Proxy={proxy}"));
+        }
+    }
+    if let Some(cache) = cache {
+        full.push("-o".to_string());
+        full.push(format!("Dir::Cache={}", cache.display()));
+    }
+    if offline && args.first() == Some(&"install") {
+        full.push("--no-download".to_string());
+    }
+    full.extend(args.iter().map(|arg| arg.to_string()));
+    full
+}
+
+/// What an `apt-get` run is doing, for its progress line.
+fn step_name(args: &[&str]) -> String {
+    let packages = args[1..]
+        .iter()
+        .filter(|arg| !arg.starts_with('-') && !arg.contains('='))
+        .map(|arg| arg.rsplit('/').next().unwrap_or(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    match args.first() {
+        Some(&"update") => "Updating package lists".to_string(),
+        Some(&"install") => format!("Installing {packages}"),
+        Some(&"remove") => format!("Removing {packages}"),
+        Some(&"purge") => format!("Purging {packages}"),
+        Some(&"autoremove") => "Removing unused dependencies".to_string(),
+        _ => format!("apt-get {}", args.join(" ")),
+    }
+}
+
+/// Waits until no other process holds a dpkg or apt lock, backing off between checks.
+fn wait_for_locks() -> anyhow::Result<()> {
+    let started = Instant::now();
+    let mut delay = Duration::from_secs(1);
+    let mut reported = None;
+    while let Some(holder) = lock_holder() {
+        if started.elapsed() >= LOCK_TIMEOUT {
+            bail!(
+                "gave up after {}s waiting for {holder}; finish or stop that process and retry",
+                LOCK_TIMEOUT.as_secs()
+            );
+        }
+        if reported.as_ref() != Some(&holder) {
+            say!("Waiting for {holder}...");
+            reported = Some(holder);
+        }
+        thread::sleep(delay);
+        delay = (delay * 2).min(MAX_BACKOFF);
+    }
+    Ok(())
+}
+
+/// Describes the first held lock, e.g. `/var/lib/dpkg/lock-frontend (held by
+/// unattended-upgr, pid 812)`.
+fn lock_holder() -> Option<String> {
+    let locks = fs::read_to_string("/proc/locks").ok()?;
+    LOCKS.iter().find_map(|path| {
+        let meta = fs::metadata(path).ok()?;
+        let pid = holder_pid(&locks, meta.dev(), meta.ino())?;
+        let name = fs::read_to_string(format!("/proc/{pid}/comm"))
+            .map(|comm| comm.trim().to_string())
+            .unwrap_or_else(|_| "unknown process".to_string());
+        Some(format!("{path} (held by {name}, pid {pid})"))
+    })
+}
+
+/// Finds the pid holding a lock on the file with `dev` and `inode` in the contents of
+/// `/proc/locks`, whose lines look like `1: POSIX  ADVISORY  WRITE 812 08:02:131090 0 EOF`.
+fn holder_pid(locks: &str, dev: u64, inode: u64) -> Option<u32> {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    let wanted = format!("{major:02x}:{minor:02x}:{inode}");
+    locks.lines().find_map(|line| {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        // Blocked waiters are listed as `1: -> POSIX ...`; only the holder counts.
+        if fields.get(1) == Some(&"->") || fields.get(5) != Some(&wanted.as_str()) {
+            return None;
+        }
+        fields.get(4)?.parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn installed_package_status_is_detected() {
+        assert!(package_installed("definitely-not-a-real-debkit-package-name").is_ok());
+    }
+
+    #[test]
+    fn offline_installs_use_only_the_cache() {
+        assert_eq!(
+            apt_get_args(
+                &["install", "-y", "git"],
+                true,
+                Some(Path::new("/srv/debs")),
+                None
+            ),
+            [
+                "-o",
+                "Dir::Cache=/srv/debs",
+                "--no-download",
+                "install",
+                "-y",
+                "git"
+            ]
+        );
+        assert_eq!(
+            apt_get_args(&["remove", "-y", "git"], true, None, None),
+            ["remove", "-y", "git"]
+        );
+        assert_eq!(
+            apt_get_args(&["install", "git"], false, None, None),
+            ["install", "git"]
+        );
+    }
+
+    #[test]
+    fn proxy_is_passed_as_acquire_options() {
+        assert_eq!(
+            apt_get_args(&["update"], false, None, Some("http://proxy.corp:3128")),
+            [
+                "-o",
+                "Acquire::http::Proxy=http://proxy.corp:3128",
+                "-o",
+                "Acquire::https::Proxy=http://proxy.corp:3128",
+                "update"
+            ]
+        );
+    }
+
+    #[test]
+    fn names_steps_after_the_packages() {
+        assert_eq!(
+            step_name(&["install", "-y", "git", "curl"]),
+            "Installing git curl"
+        );
+        assert_eq!(
+            step_name(&[
+                "install",
+                "-y",
+                "--allow-downgrades",
+                "/tmp/debkit_0.2.0_amd64.deb"
+            ]),
+            "Installing debkit_0.2.0_amd64.deb"
+        );
+        assert_eq!(
+            step_name(&[
+                "update",
+                "-o",
+                "Dir::Etc::sourcelist=/etc/apt/sources.list.d/x.sources"
+            ]),
+            "Updating package lists"
+        );
+        assert_eq!(step_name(&["purge", "-y", "sl"]), "Purging sl");
+    }
+
+    #[test]
+    fn finds_packages_with_newer_candidates() {
+        let policy = "git:\n  Installed: 1:2.39.2-1.1\n  Candidate: 1:2.39.5-0+deb12u2\n  Version table:\n \
+                      *** 1:2.39.2-1.1 500\n        500 http://deb.debian.org/debian bookworm/main amd64 Packages\n\
+                      curl:\n  Installed: (none)\n  Candidate: 7.88.1-10+deb12u8\n\
+                      zsh:\n  Installed: 5.9-4+b5\n  Candidate: 5.9-4+b5\n";
+        let recorder = Arc::new(Recorder::new().reply("apt-cache policy git curl zsh", 0, policy));
+        let upgrades = runner::with(recorder, || upgradable(&["git", "curl", "zsh"])).unwrap();
+        assert_eq!(
+            upgrades,
+            [Upgrade {
+                package: "git".to_string(),
+                installed: "1:2.39.2-1.1".to_string(),
+                candidate: "1:2.39.5-0+deb12u2".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn installs_only_the_missing_packages() {
+        let recorder = Arc::new(Recorder::new().reply(
+            "dpkg-query -W '-f=${Status}' git",
+            0,
+            "install ok installed",
+        ));
+        let missing = runner::with(recorder.clone(), || install_missing(&["git", "curl"])).unwrap();
+        assert_eq!(missing, ["curl"]);
+        // Loading the proxy setting may run other commands, and a proxy adds options.
+        let calls = recorder.calls();
+        let queries = calls
+            .iter()
+            .filter(|call| call.starts_with("dpkg-query"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            queries,
+            [
+                "dpkg-query -W '-f=${Status}' git",
+                "dpkg-query -W '-f=${Status}' curl"
+            ]
+        );
+        let apt_get = calls
+            .iter()
+            .filter(|call| call.contains("apt-get"))
+            .collect::<Vec<_>>();
+        assert_eq!(apt_get.len(), 2, "{apt_get:?}");
+        assert!(apt_get[0].ends_with(" update"), "{apt_get:?}");
+        assert!(apt_get[1].ends_with(" install -y curl"), "{apt_get:?}");
+    }
+
+    #[test]
+    fn finds_lock_holder_in_proc_locks() {
+        let locks = "\
+1: POSIX  ADVISORY  WRITE 812 08:02:131090 0 EOF
+1: -> POSIX  ADVISORY  WRITE 901 08:02:131090 0 EOF
+2: FLOCK  ADVISORY  WRITE 77 00:19:42 0 EOF
+";
+        let dev = (8 << 8) | 2;
+        assert_eq!(holder_pid(locks, dev, 131090), Some(812));
+        assert_eq!(holder_pid(locks, 0x19, 42), Some(77));
+        assert_eq!(holder_pid(locks, dev, 1), None);
+    }
+}
@@ -0,0 +1,383 @@
+//! Append-only audit log of what DebKit did to the system.
+//!
+//! Outside dry runs, every command run and file changed through [`crate::exec`] is
+//! appended as one JSON line to `audit.jsonl` in the state directory, tagged with the
+//! invocation it belongs to. File changes carry SHA-256 hashes of the contents before
+//! and after, and text written into a file also the diff. `debkit logs` reads the file back grouped by invocation. Invocations
+//! that change nothing leave no trace.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{exec, state};
+
+static SESSION: OnceLock<Session> = OnceLock::new();
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+struct Session {
+    id: String,
+    args: Vec<String>,
+    path: PathBuf,
+    /// Whether the `start` entry has been written.
+    started: Mutex<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub run: String,
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Event {
+    Start {
+        args: Vec<String>,
+    },
+    Command {
+        command: String,
+        /// Exit code; `None` when the command could not start or was killed.
+        status: Option<i32>,
+    },
+    File {
+        op: String,
+        path: PathBuf,
+        before: Option<String>,
+        after: Option<String>,
+        /// What a write changed, as a unified diff.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        diff: Option<String>,
+    },
+}
+
+/// One `debkit` invocation as shown by `debkit logs`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunLog {
+    pub run: String,
+    pub started: u64,
+    pub args: Vec<String>,
+    pub entries: Vec<Entry>,
+}
+
+pub fn path() -> anyhow::Result<PathBuf> {
+    Ok(state::dir()?.join("audit.jsonl"))
+}
+
+/// Starts logging this invocation. Nothing is written until the first change.
+pub fn start(args: Vec<String>) {
+    let Ok(path) = path() else {
+        return;
+    };
+    let _ = SESSION.set(Session {
+        id: format!("{}-{}", state::now(), std::process::id()),
+        args,
+        path,
+        started: Mutex::new(false),
+    });
+}
+
+pub fn is_enabled() -> bool {
+    SESSION.get().is_some()
+}
+
+pub fn command(command: &Command, status: Option<i32>) {
+    record(Event::Command {
+        command: exec::describe(command),
+        status,
+    });
+}
+
+/// Records a change to `path`, given the hash of its contents beforehand and, for a
+/// write, its diff.
+pub fn file(op: &str, path: &Path, before: Option<String>, diff: Option<String>) {
+    record(Event::File {
+        op: op.to_string(),
+        path: path.to_path_buf(),
+        before,
+        after: hash_file(path),
+        diff,
+    });
+}
+
+/// SHA-256 of a regular file's contents, or `None` when it is missing or unreadable.
+pub fn hash_file(path: &Path) -> Option<String> {
+    if !path.is_file() {
+        return None;
+    }
+    let contents = fs::read(path).ok()?;
+    Some(hex(&Sha256::digest(contents)))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn record(event: Event) {
+    let Some(session) = SESSION.get() else {
+        return;
+    };
+    let mut started = session.started.lock().unwrap();
+    let mut entries = Vec::new();
+    if !*started {
+        entries.push(Event::Start {
+            args: session.args.clone(),
+        });
+    }
+    entries.push(event);
+
+    let now = state::now();
+    let lines = entries
+        .into_iter()
+        .map(|event| Entry {
+            run: session.id.clone(),
+            time: now,
+            event,
+        })
+        .map(|entry| serde_json::to_string(&entry).map(|line| line + "\n"))
+        .collect::<Result<String, _>>();
+    let written = lines
+        .map_err(anyhow::Error::from)
+        .and_then(|lines| append(&session.path, &lines));
+    match written {
+        Ok(()) => *started = true,
+        Err(err) => {
+            if !WARNED.swap(true, Ordering::Relaxed) {
+                warn!("failed to write the audit log: {err:#}");
+            }
+        }
+    }
+}
+
+fn append(path: &Path, lines: &str) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        state::create_dir(parent)?;
+    }
+    let created = !path.exists();
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(lines.as_bytes()))
+        .with_context(|| format!("failed to append to {}", path.display()))?;
+    if created {
+        state::hand_over(path)?;
+    }
+    Ok(())
+}
+
+/// Reads the audit log; a missing file is an empty log.
+pub fn read(path: &Path) -> anyhow::Result<Vec<Entry>> {
+    let raw = match fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", path.display())),
+    };
+    raw.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("{}:{}: invalid audit entry", path.display(), index + 1))
+        })
+        .collect()
+}
+
+/// Groups entries by invocation and keeps the `last` most recent ones.
+pub fn runs(entries: Vec<Entry>, last: usize) -> Vec<RunLog> {
+    let mut runs: Vec<RunLog> = Vec::new();
+    for entry in entries {
+        if let Event::Start { args } = &entry.event {
+            runs.push(RunLog {
+                run: entry.run.clone(),
+                started: entry.time,
+                args: args.clone(),
+                entries: Vec::new(),
+            });
+            continue;
+        }
+        match runs.iter_mut().rev().find(|run| run.run == entry.run) {
+            Some(run) => run.entries.push(entry),
+            None => runs.push(RunLog {
+                run: entry.run.clone(),
+                started: entry.time,
+                args: Vec::new(),
+                entries: vec![entry],
+            }),
+        }
+    }
+    let skip = runs.len().saturating_sub(last);
+    runs.split_off(skip)
+}
+
+pub fn render(runs: &[RunLog]) -> String {
+    let mut out = String::new();
+    for run in runs {
+        out.push_str(&format!(
+            "== {} UTC  {}\n",
+            format_time(run.started),
+            run.args.join(" ")
+        ));
+        for entry in &run.entries {
+            let line = match &entry.event {
+                Event::Start { .. } => continue,
+                Event::Command { command, status } => {
+                    let status =
+                        status.map_or("no exit code".to_string(), |code| format!("exit {code}"));
+                    format!("run {command} ({status})")
+                }
+                Event::File {
+                    op,
+                    path,
+                    before,
+                    after,
+                    diff,
+                } => {
+                    let line = format!(
+                        "{op} {}  {} -> {}",
+                        path.display(),
+                        short_hash(before),
+                        short_hash(after)
+                    );
+                    match diff {
+                        Some(diff) => format!("{line}\n{}", diff.trim_end_matches('\n')),
+                        None => line,
+                    }
+                }
+            };
+            out.push_str(&format!("  {}  {line}\n", &format_time(entry.time)[11..]));
+        }
+    }
+    out
+}
+
+fn short_hash(hash: &Option<String>) -> &str {
+    hash.as_deref()
+        .map_or("-", |hash| &hash[..hash.len().min(12)])
+}
+
+/// `YYYY-MM-DD HH:MM:SS` in UTC.
+pub fn format_time(secs: u64) -> String {
+    let (days, rest) = (secs / 86_400, secs % 86_400);
+    // Civil-from-days, after Howard Hinnant's date algorithms.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        rest / 3_600,
+        rest % 3_600 / 60,
+        rest % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(run: &str, time: u64, event: Event) -> Entry {
+        Entry {
+            run: run.to_string(),
+            time,
+            event,
+        }
+    }
+
+    #[test]
+    fn formats_utc_times() {
+        assert_eq!(format_time(0), "1970-01-01 00:00:00");
+        assert_eq!(format_time(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(format_time(1_792_166_716), "2026-10-16 16:05:16");
+    }
+
+    #[test]
+    fn groups_runs_and_renders_the_last_ones() {
+        let start = |run: &str, time, args: &[&str]| {
+            entry(
+                run,
+                time,
+                Event::Start {
+                    args: args.iter().map(|arg| arg.to_string()).collect(),
+                },
+            )
+        };
+        let entries = vec![
+            start("1-1", 60, &["debkit", "install", "git"]),
+            entry(
+                "1-1",
+                61,
+                Event::Command {
+                    command: "apt-get install -y git".to_string(),
+                    status: Some(0),
+                },
+            ),
+            start("2-2", 120, &["debkit", "configure", "git-prompt"]),
+            entry(
+                "2-2",
+                121,
+                Event::File {
+                    op: "write".to_string(),
+                    path: PathBuf::from("/home/me/.git-prompt.sh"),
+                    before: None,
+                    after: Some("0123456789abcdef".to_string()),
+                    diff: Some("    + export PS1\n".to_string()),
+                },
+            ),
+        ];
+
+        let runs = runs(entries.clone(), 10);
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].entries.len(), 1);
+
+        let last = super::runs(entries, 1);
+        assert_eq!(
+            render(&last),
+            "\
+== 1970-01-01 00:02:00 UTC  debkit configure git-prompt
+  00:02:01  write /home/me/.git-prompt.sh  - -> 0123456789ab
+    + export PS1
+"
+        );
+    }
+
+    #[test]
+    fn entries_are_json_lines() {
+        let line = serde_json::to_string(&entry(
+            "1-1",
+            5,
+            Event::Command {
+                command: "apt-get update".to_string(),
+                status: Some(100),
+            },
+        ))
+        .unwrap();
+        assert_eq!(
+            line,
+            r#"{"run":"1-1","time":5,"kind":"command","command":"apt-get update","status":100}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Entry>(&line).unwrap().event,
+            Event::Command {
+                command: "apt-get update".to_string(),
+                status: Some(100),
+            }
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Copies of user files taken before DebKit rewrites them, and `debkit restore`.
+//!
+//! Before changing a file such as `variety.conf` or `.bashrc`, DebKit copies it to
+//! `~/.local/state/debkit/backups/<its absolute path>/<UTC time>`, for example
+//! `backups/home/me/.bashrc/2026-10-16T09:30:00`. `restore` puts the newest copy back.
+//! Like the state file this is DebKit's own bookkeeping, so taking a backup bypasses
+//! [`crate::exec`], and dry runs never take one.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+
+use crate::{audit, exec, state};
+
+pub fn dir() -> anyhow::Result<PathBuf> {
+    Ok(state::dir()?.join("backups"))
+}
+
+/// Copies `file` to a new backup before DebKit changes it. A missing file has nothing
+/// to back up.
+pub fn save(file: &Path) -> anyhow::Result<Option<PathBuf>> {
+    if exec::is_dry_run() || !file.is_file() {
+        return Ok(None);
+    }
+    save_in(&dir()?, &absolute(file)?, state::now()).map(Some)
+}
+
+/// The backups of `file`, oldest first.
+pub fn list(file: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    list_in(&dir()?, &absolute(file)?)
+}
+
+/// Puts the newest backup of `file` back and returns it. The current contents are
+/// backed up first, so running `restore` again undoes it.
+pub fn restore(file: &Path) -> anyhow::Result<PathBuf> {
+    let file = absolute(file)?;
+    let Some(backup) = list(&file)?.pop() else {
+        bail!(
+            "there are no backups of {} in {}",
+            file.display(),
+            dir()?.display()
+        );
+    };
+    let contents =
+        fs::read(&backup).with_context(|| format!("failed to read {}", backup.display()))?;
+    if fs::read(&file).is_ok_and(|current| current != contents) {
+        save(&file)?;
+    }
+    exec::write(&file, contents).with_context(|| format!("failed to write {}", file.display()))?;
+    Ok(backup)
+}
+
+fn absolute(file: &Path) -> anyhow::Result<PathBuf> {
+    std::path::absolute(file).with_context(|| format!("failed to resolve {}", file.display()))
+}
+
+/// The directory holding the backups of the absolute path `file`.
+fn backups_of(root: &Path, file: &Path) -> PathBuf {
+    root.join(file.strip_prefix("/").unwrap_or(file))
+}
+
+/// Copies `file` into `root`, numbering the copy `.1`, `.2`, ... when one was already
+/// taken in the same second. A copy of a file root owns, such as `/etc/fstab`, stays
+/// root's; other copies go to the target user with the rest of the state directory.
+fn save_in(root: &Path, file: &Path, now: u64) -> anyhow::Result<PathBuf> {
+    let dir = backups_of(root, file);
+    state::create_dir(&dir)?;
+    let time = audit::format_time(now).replace(' ', "T");
+    let backup = (0..)
+        .map(|n| match n {
+            0 => dir.join(&time),
+            n => dir.join(format!("{time}.{n}")),
+        })
+        .find(|backup| !backup.exists())
+        .context("no free backup name")?;
+    fs::copy(file, &backup).with_context(|| {
+        format!(
+            "failed to back up {} to {}",
+            file.display(),
+            backup.display()
+        )
+    })?;
+    if fs::metadata(file).is_ok_and(|metadata| metadata.uid() != 0) {
+        state::hand_over(&backup)?;
+    }
+    Ok(backup)
+}
+
+fn list_in(root: &Path, file: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let dir = backups_of(root, file);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", dir.display())),
+    };
+    let mut backups = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() {
+            backups.push(path);
+        }
+    }
+    // The UTC times in the names sort chronologically.
+    backups.sort();
+    Ok(backups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_timestamped_copies_under_the_file_path() {
+        let root = std::env::temp_dir().join(format!("debkit-backups-{}", std::process::id()));
+        let file = root.join("home").join(".bashrc");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        let backups = root.join("backups");
+
+        fs::write(&file, "first\n").unwrap();
+        let first = save_in(&backups, &file, 0).unwrap();
+        assert!(first.ends_with("home/.bashrc/1970-01-01T00:00:00"));
+        assert!(first.starts_with(&backups));
+        fs::write(&file, "second\n").unwrap();
+        let same_second = save_in(&backups, &file, 0).unwrap();
+        assert!(same_second.ends_with("1970-01-01T00:00:00.1"));
+        let later = save_in(&backups, &file, 61).unwrap();
+
+        assert_eq!(
+            list_in(&backups, &file).unwrap(),
+            [first.clone(), same_second, later]
+        );
+        assert_eq!(fs::read_to_string(first).unwrap(), "first\n");
+        assert!(list_in(&backups, &root.join("missing")).unwrap().is_empty());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+//! Shell completion.
+//!
+//! The scripts printed by `debkit completions <shell>` call back into `debkit` with
+//! `COMPLETE=<shell>` set on every <TAB>, so the candidates always come from the
+//! target registry of the installed binary and from the current config rather than
+//! from a list frozen when the script was generated.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::CommandFactory;
+use clap_complete::CompleteEnv;
+use clap_complete::env::Shells;
+
+const ENV_VAR: &str = "COMPLETE";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    fn name(self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+
+    /// The file name each shell looks for in its completion directory.
+    fn file_name(self) -> &'static str {
+        match self {
+            Shell::Bash => "debkit",
+            Shell::Zsh => "_debkit",
+            Shell::Fish => "debkit.fish",
+        }
+    }
+}
+
+/// Answers a completion request and exits when `COMPLETE` is set; otherwise returns
+/// so the command runs normally. Must run before anything is written to stdout.
+pub fn complete_from_env() {
+    CompleteEnv::with_factory(crate::Cli::command)
+        .var(ENV_VAR)
+        .complete();
+}
+
+pub fn write_script(shell: Shell, out: &mut dyn Write) -> anyhow::Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(shell.name())
+        .with_context(|| format!("unsupported shell `{}`", shell.name()))?;
+    completer
+        .write_registration(ENV_VAR, "debkit", "debkit", "debkit", out)
+        .context("failed to write completion script")
+}
+
+/// Writes the script for every shell into `dir`, as `<shell>/<file name>`, for
+/// shipping in release archives.
+pub fn write_all(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+        let path = dir.join(shell.name()).join(shell.file_name());
+        let mut script = Vec::new();
+        write_script(shell, &mut script)?;
+        debkit::exec::create_dir_all(path.parent().unwrap_or(dir))
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        debkit::exec::write(&path, script)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(args: &[&str]) -> Vec<String> {
+        let args = args.iter().map(Into::into).collect::<Vec<_>>();
+        let index = args.len() - 1;
+        clap_complete::engine::complete(&mut crate::Cli::command(), args, index, None)
+            .unwrap()
+            .into_iter()
+            .filter(|candidate| !candidate.is_hide_set())
+            .map(|candidate| candidate.get_value().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn completes_target_names_from_registry() {
+        assert_eq!(candidates(&["debkit", "install", "ri"]), ["ripgrep"]);
+        assert_eq!(candidates(&["debkit", "configure", "git"]), ["git-prompt"]);
+    }
+
+    #[test]
+    fn writes_registration_for_each_shell() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let mut script = Vec::new();
+            write_script(shell, &mut script).unwrap();
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains("COMPLETE"), "{shell:?}: {script}");
+        }
+    }
+}
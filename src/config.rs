@@ -1,14 +1,36 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 use anyhow::{Context, bail};
-use toml_edit::{Array, DocumentMut, Item, Table, Value, value};
+use serde::{Deserialize, Serialize};
+use toml_edit::{Array, DocumentMut, ImDocument, Item, Table, TableLike, Value};
+
+use crate::{exec, keyring, network};
 
 pub const DEFAULT_WALLPAPERS_FOLDER: &str = "";
 pub const DEFAULT_INTERVAL_MINUTES: u32 = 10;
+pub const DEFAULT_VARIETY_QUOTA_MB: u32 = 500;
+pub const DEFAULT_VARIETY_FILTER: &str = "Keep original";
+/// The online sources Variety can download from; the ones in
+/// [`VARIETY_SOURCES_WITH_LOCATION`] need a search or URL after a `:`.
+pub const VARIETY_ONLINE_SOURCES: &[&str] = &[
+    "unsplash",
+    "bing",
+    "apod",
+    "desktoppr",
+    "chromecast",
+    "wallhaven",
+    "reddit",
+    "flickr",
+    "mediarss",
+];
+pub const VARIETY_SOURCES_WITH_LOCATION: &[&str] = &["wallhaven", "reddit", "flickr", "mediarss"];
 pub const DEFAULT_FOUNDATION_INSTALL: &[&str] = &[
     "essentials",
     "git",
+    "ssh",
     "ripgrep",
     "rust",
     "npm",
@@ -29,45 +51,81 @@ pub const DEFAULT_ESSENTIAL_PACKAGES: &[&str] = &[
     "neovim",
 ];
 pub const DEFAULT_NPM_VERSION: &str = "latest";
+pub const DEFAULT_RUST_TOOLCHAIN: &str = "stable";
+pub const DEFAULT_RUSTUP_VERSION: &str = "1.29.0";
 pub const DEFAULT_WOL_MODE: &str = "magic";
 pub const DEFAULT_WOL_BACKEND: &str = "network_manager";
 pub const DEFAULT_WOL_REFERENCE_HOST: &str = "";
+pub const DEFAULT_NETWORK_PROXY: &str = "";
+pub const DEFAULT_SELF_UPDATE_SOURCE: &str = "github";
+pub const DEFAULT_SELF_UPDATE_GITHUB_REPO: &str = "johnjoeallen/debkit";
+pub const DEFAULT_PACKAGING_SIGNING_KEY: &str = "";
+pub const DEFAULT_PUBLISH_BACKEND: &str = "reprepro";
+pub const DEFAULT_PUBLISH_DISTRIBUTION: &str = "stable";
+pub const DEFAULT_PUBLISH_COMPONENT: &str = "main";
 pub const DEFAULT_HOST_NAME: &str = "unknown";
 pub const DEFAULT_NIS_ROLE: &str = "slave";
 pub const DEFAULT_NIS_DOMAIN: &str = "";
 pub const DEFAULT_NIS_MASTER: &str = "";
 pub const DEFAULT_NIS_SERVER: &str = "";
 pub const DEFAULT_NIS_LOCAL_ADMIN_GROUPS: &[&str] = &[];
+/// The archive formats `fonts.archives` may point at.
+pub const FONT_ARCHIVE_SUFFIXES: &[&str] = &[".zip", ".tar.xz", ".tar.gz", ".tgz", ".tar"];
+pub const DEFAULT_ZSH_PLUGINS: &[&str] = &[
+    "zsh-users/zsh-autosuggestions",
+    "zsh-users/zsh-syntax-highlighting",
+];
+pub const DEFAULT_NEOVIM_MIN_VERSION: &str = "0.9.0";
+pub const DEFAULT_NEOVIM_RELEASE: &str = "stable";
+pub const DEFAULT_TAILSCALE_AUTH_KEY_ENV: &str = "TS_AUTHKEY";
+/// Security updates only, in the patterns Debian's and Ubuntu's security archives
+/// match.
+pub const DEFAULT_AUTO_UPDATES_ORIGINS: &[&str] = &[
+    "origin=${distro_id},codename=${distro_codename}-security,label=${distro_id}-Security",
+    "origin=${distro_id},archive=${distro_codename}-security",
+];
+pub const DEFAULT_AUTO_UPDATES_REBOOT_TIME: &str = "02:00";
+pub const DEFAULT_NFS_MOUNTS_OPTIONS: &str = "rw,soft";
 pub const DEFAULT_SUDO_NOPASS_GROUP: &str = "superuser";
 pub const DEFAULT_SUDO_NOPASS_NIS_MANAGED: bool = false;
+pub const PATH_ENV: &str = "DEBKIT_CONFIG";
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DebkitConfig {
+    /// Always the detected hostname; never read from or written to the files.
+    #[serde(skip)]
     pub host: HostConfig,
     pub wallpapers: WallpapersConfig,
     pub variety: VarietyConfig,
     pub foundation: FoundationConfig,
     pub essentials: EssentialsConfig,
     pub npm: NpmConfig,
+    pub rust: RustConfig,
+    pub cargo_tools: CargoToolsConfig,
+    pub docker: DockerConfig,
+    pub fonts: FontsConfig,
+    pub zsh: ZshConfig,
+    pub ssh: SshConfig,
+    pub flatpak: FlatpakConfig,
+    pub neovim: NeovimConfig,
+    pub python: PythonConfig,
+    pub tailscale: TailscaleConfig,
+    pub auto_updates: AutoUpdatesConfig,
+    pub nfs_mounts: NfsMountsConfig,
+    pub firewall: FirewallConfig,
+    pub gnome_settings: GnomeSettingsConfig,
     pub sudo_nopass: SudoNopassConfig,
     pub nis: NisConfig,
     pub wake_on_lan: WakeOnLanConfig,
-}
-
-impl Default for DebkitConfig {
-    fn default() -> Self {
-        Self {
-            host: HostConfig::default(),
-            wallpapers: WallpapersConfig::default(),
-            variety: VarietyConfig::default(),
-            foundation: FoundationConfig::default(),
-            essentials: EssentialsConfig::default(),
-            npm: NpmConfig::default(),
-            sudo_nopass: SudoNopassConfig::default(),
-            nis: NisConfig::default(),
-            wake_on_lan: WakeOnLanConfig::default(),
-        }
-    }
+    pub network: NetworkConfig,
+    pub self_update: SelfUpdateConfig,
+    pub packaging: PackagingConfig,
+    /// Expected fingerprints of APT signing keys, by keyring name.
+    pub keys: BTreeMap<String, String>,
+    pub upload: UploadConfig,
+    /// Local APT repositories `debkit publish --repo` adds packages to, by name.
+    pub publish: BTreeMap<String, PublishRepo>,
 }
 
 impl DebkitConfig {
@@ -92,33 +150,138 @@ impl Default for HostConfig {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct WallpapersConfig {
     pub folder: String,
+    /// Where `install wallpapers-sync` mirrors `folder` from: a directory such as an NFS
+    /// mount, an rsync `host:path`, or a git repository URL; empty for none.
+    pub source: String,
+    /// Globs of the files to copy from `source`, such as `*.jpg`; empty for all.
+    pub include: Vec<String>,
+    /// Globs of the files to leave out, checked before `include`.
+    pub exclude: Vec<String>,
+    /// Files larger than this many MB are not copied; 0 for no limit.
+    pub max_file_mb: u32,
+    /// A sync that would copy more than this many MB in total is refused; 0 for no limit.
+    pub max_total_mb: u32,
 }
 
 impl Default for WallpapersConfig {
     fn default() -> Self {
         Self {
             folder: DEFAULT_WALLPAPERS_FOLDER.to_string(),
+            source: String::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            max_file_mb: 0,
+            max_total_mb: 0,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// How `install variety` changes the wallpaper.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VarietyMode {
+    /// Variety, started with the session.
+    #[default]
+    Variety,
+    /// GNOME's own slideshow from an XML file, without Variety.
+    GnomeSlideshow,
+}
+
+impl VarietyMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Variety => "variety",
+            Self::GnomeSlideshow => "gnome_slideshow",
+        }
+    }
+}
+
+impl std::fmt::Display for VarietyMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How `install variety` starts Variety with the session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VarietyAutostart {
+    /// An XDG autostart entry.
+    #[default]
+    Desktop,
+    /// A `variety.service` user unit, for sessions without XDG autostart.
+    Systemd,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct VarietyConfig {
+    pub mode: VarietyMode,
+    /// Version to pin the package to, such as `0.8.4-1` or `0.8.*`; empty for any.
+    pub version: String,
+    /// Keep apt and unattended-upgrades from changing the installed version.
+    pub hold: bool,
     pub interval_minutes: u32,
+    /// How Variety starts with the session.
+    pub autostart: VarietyAutostart,
+    /// Let Variety download wallpapers from `online_sources`.
+    pub internet_enabled: bool,
+    /// Where Variety keeps downloaded wallpapers; empty for its default.
+    pub download_folder: String,
+    /// Size limit of `download_folder` in MB, beyond which Variety deletes the oldest
+    /// downloads; 0 for none.
+    pub quota_mb: u32,
+    /// Sources such as `unsplash`, or `wallhaven:nature` for those that take a search
+    /// or URL.
+    pub online_sources: Vec<String>,
+    /// The filters Variety picks from, by their name in the `[filters]` section of
+    /// variety.conf, such as `Keep original` or `Grayscale`; the others are turned off.
+    pub filters: Vec<String>,
+    /// Draw a clock on the wallpaper.
+    pub clock_enabled: bool,
+    /// Show quotes on the desktop.
+    pub quotes_enabled: bool,
+    pub safe_mode: bool,
+    pub smart_enabled: bool,
+    pub sync_enabled: bool,
+    pub stats_enabled: bool,
+    pub smart_notice_shown: bool,
+    pub smart_register_shown: bool,
+    pub stats_notice_shown: bool,
 }
 
 impl Default for VarietyConfig {
     fn default() -> Self {
         Self {
+            mode: VarietyMode::Variety,
+            version: String::new(),
+            hold: false,
             interval_minutes: DEFAULT_INTERVAL_MINUTES,
+            autostart: VarietyAutostart::Desktop,
+            internet_enabled: false,
+            download_folder: String::new(),
+            quota_mb: DEFAULT_VARIETY_QUOTA_MB,
+            online_sources: Vec::new(),
+            filters: vec![DEFAULT_VARIETY_FILTER.to_string()],
+            clock_enabled: false,
+            quotes_enabled: false,
+            safe_mode: false,
+            smart_enabled: false,
+            sync_enabled: false,
+            stats_enabled: false,
+            smart_notice_shown: true,
+            smart_register_shown: true,
+            stats_notice_shown: true,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FoundationConfig {
     pub install: Vec<String>,
 }
@@ -134,7 +297,8 @@ impl Default for FoundationConfig {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct EssentialsConfig {
     pub packages: Vec<String>,
 }
@@ -150,7 +314,8 @@ impl Default for EssentialsConfig {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NpmConfig {
     pub version: String,
 }
@@ -163,7 +328,393 @@ impl Default for NpmConfig {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RustConfig {
+    /// The default toolchain: a channel such as `stable`, or an exact version such as
+    /// `1.79.0` to pin it.
+    pub toolchain: String,
+    /// rustup components, such as `rust-analyzer` or `rust-src`, added to every
+    /// installed toolchain.
+    pub components: Vec<String>,
+    /// Toolchains installed next to the default one, such as `nightly` or `1.79.0`.
+    pub extra_toolchains: Vec<String>,
+    /// Cross-compilation targets, such as `wasm32-unknown-unknown`, added to every
+    /// installed toolchain.
+    pub targets: Vec<String>,
+    /// The rustup release `install rust` downloads `rustup-init` from.
+    pub rustup_version: String,
+    /// The SHA-256 `rustup-init` must have; empty for the one DebKit pins for
+    /// `DEFAULT_RUSTUP_VERSION`.
+    pub rustup_init_sha256: String,
+}
+
+impl Default for RustConfig {
+    fn default() -> Self {
+        Self {
+            toolchain: DEFAULT_RUST_TOOLCHAIN.to_string(),
+            components: Vec::new(),
+            extra_toolchains: Vec::new(),
+            targets: Vec::new(),
+            rustup_version: DEFAULT_RUSTUP_VERSION.to_string(),
+            rustup_init_sha256: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CargoToolsConfig {
+    /// Crates to `cargo install --locked`, such as `cargo-deb`, or `name@version` to
+    /// pin one.
+    pub install: Vec<String>,
+}
+
+/// Which container engine `install docker` sets up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DockerFlavor {
+    /// Docker from download.docker.com.
+    #[default]
+    Docker,
+    /// The distribution's Podman.
+    Podman,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DockerConfig {
+    pub flavor: DockerFlavor,
+    /// Adds the target user to the `docker` group.
+    pub add_user: bool,
+}
+
+impl Default for DockerConfig {
+    fn default() -> Self {
+        Self {
+            flavor: DockerFlavor::Docker,
+            add_user: true,
+        }
+    }
+}
+
+/// Where `install fonts` puts the fonts from archives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FontScope {
+    /// `~/.local/share/fonts` of the target user.
+    #[default]
+    User,
+    /// `/usr/local/share/fonts`, for everyone.
+    System,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FontsConfig {
+    /// APT font packages, such as `fonts-firacode`.
+    pub packages: Vec<String>,
+    /// HTTPS URLs of font archives, such as a Nerd Fonts release `.tar.xz` or `.zip`.
+    pub archives: Vec<String>,
+    pub scope: FontScope,
+}
+
+impl Default for FontsConfig {
+    fn default() -> Self {
+        Self {
+            packages: Vec::new(),
+            archives: Vec::new(),
+            scope: FontScope::User,
+        }
+    }
+}
+
+/// The zsh plugin manager `install zsh` clones into the user's home.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ZshPluginManager {
+    #[default]
+    Zinit,
+    Antidote,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZshConfig {
+    /// Makes zsh the target user's login shell.
+    pub login_shell: bool,
+    /// Loads `plugins`.
+    pub plugin_manager: ZshPluginManager,
+    /// GitHub repositories of zsh plugins, such as `zsh-users/zsh-autosuggestions`.
+    pub plugins: Vec<String>,
+}
+
+impl Default for ZshConfig {
+    fn default() -> Self {
+        Self {
+            login_shell: false,
+            plugin_manager: ZshPluginManager::Zinit,
+            plugins: DEFAULT_ZSH_PLUGINS
+                .iter()
+                .map(|plugin| (*plugin).to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SshConfig {
+    /// Generates `~/.ssh/id_ed25519` when the target user has no such key.
+    pub generate_key: bool,
+    /// The comment of a generated key; empty for ssh-keygen's `user@host`.
+    pub key_comment: String,
+    /// Installs openssh-server and enables `ssh.service`.
+    pub server: bool,
+    /// `Host` blocks written to `~/.ssh/config`, by host alias.
+    pub hosts: BTreeMap<String, SshHost>,
+}
+
+impl Default for SshConfig {
+    fn default() -> Self {
+        Self {
+            generate_key: true,
+            key_comment: String::new(),
+            server: false,
+            hosts: BTreeMap::new(),
+        }
+    }
+}
+
+/// One `Host` block of `~/.ssh/config`. Empty fields are left out of the block.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SshHost {
+    pub hostname: String,
+    pub user: String,
+    /// 0 leaves the port to ssh, normally 22.
+    pub port: u16,
+    pub identity_file: String,
+    pub forward_agent: bool,
+    /// Other options by their ssh_config name, such as `ProxyJump = "bastion"`.
+    pub options: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlatpakConfig {
+    /// Flathub application IDs, such as `org.mozilla.firefox`.
+    pub apps: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NeovimConfig {
+    /// The oldest `nvim` accepted; an older APT package gives way to the upstream
+    /// release.
+    pub min_version: String,
+    /// The upstream release tag to install when APT's is too old, such as `v0.10.4`,
+    /// or `stable`.
+    pub release: String,
+    /// A git repository cloned into `~/.config/nvim`; empty leaves the config alone.
+    pub config_repo: String,
+}
+
+impl Default for NeovimConfig {
+    fn default() -> Self {
+        Self {
+            min_version: DEFAULT_NEOVIM_MIN_VERSION.to_string(),
+            release: DEFAULT_NEOVIM_RELEASE.to_string(),
+            config_repo: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PythonConfig {
+    /// Packages to `pipx install`, such as `httpie`, or `name==version` to pin one.
+    pub tools: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TailscaleConfig {
+    /// Runs `tailscale up` when the node is not connected yet.
+    pub up: bool,
+    /// The environment variable holding the auth key for `tailscale up`.
+    pub auth_key_env: String,
+    /// A file holding the auth key, used when the variable is unset. `tailscale up`
+    /// reads it as root, so it can be private to root.
+    pub auth_key_file: String,
+    /// More `tailscale up` flags, such as `--ssh` or `--accept-routes`.
+    pub up_args: Vec<String>,
+}
+
+impl Default for TailscaleConfig {
+    fn default() -> Self {
+        Self {
+            up: false,
+            auth_key_env: DEFAULT_TAILSCALE_AUTH_KEY_ENV.to_string(),
+            auth_key_file: String::new(),
+            up_args: Vec::new(),
+        }
+    }
+}
+
+/// When unattended-upgrades mails a report, as `Unattended-Upgrade::MailReport`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MailReport {
+    Always,
+    OnlyOnError,
+    #[default]
+    OnChange,
+}
+
+impl MailReport {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Always => "always",
+            Self::OnlyOnError => "only-on-error",
+            Self::OnChange => "on-change",
+        }
+    }
+}
+
+impl std::fmt::Display for MailReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoUpdatesConfig {
+    /// `Unattended-Upgrade::Origins-Pattern` entries, such as
+    /// `origin=Debian,codename=${distro_codename},label=Debian`.
+    pub origins: Vec<String>,
+    /// Reboots on its own when an upgrade needs it.
+    pub automatic_reboot: bool,
+    /// When to reboot, as `HH:MM`.
+    pub reboot_time: String,
+    /// Where to mail reports; empty sends none.
+    pub mail: String,
+    pub mail_report: MailReport,
+}
+
+impl Default for AutoUpdatesConfig {
+    fn default() -> Self {
+        Self {
+            origins: DEFAULT_AUTO_UPDATES_ORIGINS
+                .iter()
+                .map(|origin| origin.to_string())
+                .collect(),
+            automatic_reboot: false,
+            reboot_time: DEFAULT_AUTO_UPDATES_REBOOT_TIME.to_string(),
+            mail: String::new(),
+            mail_report: MailReport::OnChange,
+        }
+    }
+}
+
+/// How `install nfs-mounts` mounts the shares.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NfsMountMethod {
+    /// On first access, through autofs.
+    #[default]
+    Autofs,
+    /// At boot, from `/etc/fstab`.
+    Fstab,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NfsMountsConfig {
+    pub method: NfsMountMethod,
+    /// Browse every NFS server's exports under `/net/<host>`; needs autofs.
+    pub net: bool,
+    /// Mount points and the `host:/export` mounted on each.
+    pub mounts: BTreeMap<String, String>,
+    /// NFS mount options, such as `ro,hard`.
+    pub options: String,
+}
+
+impl Default for NfsMountsConfig {
+    fn default() -> Self {
+        Self {
+            method: NfsMountMethod::Autofs,
+            net: false,
+            mounts: BTreeMap::new(),
+            options: DEFAULT_NFS_MOUNTS_OPTIONS.to_string(),
+        }
+    }
+}
+
+/// A ufw default policy, as `ufw default <policy>` takes it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FirewallPolicy {
+    /// Drops packets silently.
+    #[default]
+    Deny,
+    /// Refuses connections, so the other side learns at once.
+    Reject,
+    Allow,
+}
+
+impl FirewallPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Deny => "deny",
+            Self::Reject => "reject",
+            Self::Allow => "allow",
+        }
+    }
+}
+
+impl std::fmt::Display for FirewallPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FirewallConfig {
+    /// The policy for incoming connections.
+    pub incoming: FirewallPolicy,
+    /// The policy for outgoing connections.
+    pub outgoing: FirewallPolicy,
+    /// Allows SSH in on the port `sshd -T` reports (22/tcp when it cannot be read), so
+    /// enabling the firewall keeps remote sessions open.
+    pub allow_ssh: bool,
+    /// More ports to allow in, as `80`, `443/tcp` or a range such as `60000:61000/udp`.
+    pub allow: Vec<String>,
+}
+
+impl Default for FirewallConfig {
+    fn default() -> Self {
+        Self {
+            incoming: FirewallPolicy::Deny,
+            outgoing: FirewallPolicy::Allow,
+            allow_ssh: true,
+            allow: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GnomeSettingsConfig {
+    /// gsettings keys by schema, with values in GVariant text such as `'prefer-dark'`,
+    /// `true` or `uint32 300`.
+    pub settings: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SudoNopassConfig {
     pub enabled: bool,
     pub group: String,
@@ -184,7 +735,8 @@ impl Default for SudoNopassConfig {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NisConfig {
     pub enabled: bool,
     pub role: String,
@@ -222,7 +774,114 @@ impl Default for NisConfig {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// `http://` or `https://` proxy URL for apt, curl, rustup and npm; empty for none.
+    pub proxy: String,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            proxy: DEFAULT_NETWORK_PROXY.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelfUpdateConfig {
+    /// Where `debkit self update` looks for new versions: `github` or `apt`.
+    pub source: String,
+    /// `owner/name` of the GitHub repository whose releases carry the .deb.
+    pub github_repo: String,
+}
+
+impl Default for SelfUpdateConfig {
+    fn default() -> Self {
+        Self {
+            source: DEFAULT_SELF_UPDATE_SOURCE.to_string(),
+            github_repo: DEFAULT_SELF_UPDATE_GITHUB_REPO.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PackagingConfig {
+    /// The OpenPGP key ID or fingerprint `debkit package deb --sign` signs with when
+    /// `--key` is not given; empty for the signer's default key.
+    pub signing_key: String,
+}
+
+impl Default for PackagingConfig {
+    fn default() -> Self {
+        Self {
+            signing_key: DEFAULT_PACKAGING_SIGNING_KEY.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PublishRepo {
+    /// The tool that manages the repository: `aptly` or `reprepro`.
+    pub backend: String,
+    /// reprepro's base directory, or aptly's local repository name; by default the
+    /// repository's name in the config.
+    pub path: String,
+    pub distribution: String,
+    pub component: String,
+    /// The key that signs the `Release` file under `debkit publish --sign`; by default
+    /// `packaging.signing_key`.
+    pub signing_key: String,
+}
+
+impl Default for PublishRepo {
+    fn default() -> Self {
+        Self {
+            backend: DEFAULT_PUBLISH_BACKEND.to_string(),
+            path: String::new(),
+            distribution: DEFAULT_PUBLISH_DISTRIBUTION.to_string(),
+            component: DEFAULT_PUBLISH_COMPONENT.to_string(),
+            signing_key: String::new(),
+        }
+    }
+}
+
+/// Where `debkit publish --backend` uploads packages. Tokens can also come from the
+/// `PACKAGECLOUD_TOKEN` and `FURY_PUSH_TOKEN` environment variables, which win.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UploadConfig {
+    pub s3_bucket: String,
+    /// The key prefix packages go under in the bucket, such as `pool/main`.
+    pub s3_prefix: String,
+    /// `user/repo` on packagecloud.
+    pub packagecloud_repo: String,
+    /// packagecloud's numeric ID for the distribution version, such as bookworm's.
+    pub packagecloud_distro_version_id: String,
+    pub packagecloud_token: String,
+    /// `owner/repo` on Cloudsmith. The cloudsmith CLI reads its own API key.
+    pub cloudsmith_repo: String,
+    /// The distribution and release, such as `debian/bookworm`.
+    pub cloudsmith_distribution: String,
+    pub gemfury_account: String,
+    pub gemfury_token: String,
+}
+
+/// The keys a `[publish.<name>]` table may set.
+const PUBLISH_REPO_KEYS: &[&str] = &[
+    "backend",
+    "path",
+    "distribution",
+    "component",
+    "signing_key",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "WakeOnLanToml", into = "WakeOnLanToml")]
 pub struct WakeOnLanConfig {
     pub enabled: bool,
     pub interfaces_auto: bool,
@@ -245,19 +904,99 @@ impl Default for WakeOnLanConfig {
     }
 }
 
-pub fn load_or_init() -> anyhow::Result<DebkitConfig> {
-    let home = home_dir()?;
-    load_or_init_for_home(&home)
+/// `[wake_on_lan]` as written in the file, where `interfaces` is `"auto"` or a list.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct WakeOnLanToml {
+    enabled: bool,
+    interfaces: Interfaces,
+    mode: String,
+    #[serde(alias = "persistence")]
+    backend: String,
+    reference_host: String,
 }
 
-pub fn configure_complete_for_current_host() -> anyhow::Result<PathBuf> {
-    let home = home_dir()?;
-    configure_complete_for_home(&home)
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Interfaces {
+    Keyword(String),
+    List(Vec<String>),
 }
 
-pub fn add_nis_slave_to_host(master_host: &str, slave: &str) -> anyhow::Result<AddNisSlaveResult> {
-    let home = home_dir()?;
-    add_nis_slave_to_host_for_home(&home, master_host, slave)
+impl Default for WakeOnLanToml {
+    fn default() -> Self {
+        WakeOnLanConfig::default().into()
+    }
+}
+
+impl TryFrom<WakeOnLanToml> for WakeOnLanConfig {
+    type Error = String;
+
+    fn try_from(raw: WakeOnLanToml) -> Result<Self, Self::Error> {
+        let (interfaces_auto, interfaces) = match raw.interfaces {
+            Interfaces::Keyword(keyword) if keyword == "auto" => (true, Vec::new()),
+            Interfaces::Keyword(other) => {
+                return Err(format!(
+                    "`wake_on_lan.interfaces` must be \"auto\" or an array of strings, got `{other}`"
+                ));
+            }
+            Interfaces::List(interfaces) => (false, interfaces),
+        };
+        Ok(Self {
+            enabled: raw.enabled,
+            interfaces_auto,
+            interfaces,
+            mode: raw.mode,
+            backend: raw.backend,
+            reference_host: raw.reference_host,
+        })
+    }
+}
+
+impl From<WakeOnLanConfig> for WakeOnLanToml {
+    fn from(config: WakeOnLanConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            interfaces: if config.interfaces_auto {
+                Interfaces::Keyword("auto".to_string())
+            } else {
+                Interfaces::List(config.interfaces)
+            },
+            mode: config.mode,
+            backend: config.backend,
+            reference_host: config.reference_host,
+        }
+    }
+}
+
+static PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Uses `path` instead of the default config file for the rest of the process.
+pub fn set_path(path: PathBuf) {
+    let _ = PATH_OVERRIDE.set(path);
+}
+
+/// The config file to use: `--config`, then `$DEBKIT_CONFIG`, then
+/// `~/.config/debkit/config.toml`. Host overrides live in `hosts/` next to it.
+pub fn path() -> anyhow::Result<PathBuf> {
+    if let Some(path) = PATH_OVERRIDE.get() {
+        return Ok(path.clone());
+    }
+    if let Some(path) = std::env::var_os(PATH_ENV).filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+    Ok(config_path_for_home(&home_dir()?))
+}
+
+/// Loads the existing config without creating or backfilling anything.
+pub fn load(path: &Path) -> anyhow::Result<DebkitConfig> {
+    let hostname = current_hostname().unwrap_or_else(|_| DEFAULT_HOST_NAME.to_string());
+    load_for_hostname(path, &hostname)
+}
+
+pub fn check(path: &Path, is_target: impl Fn(&str) -> bool) -> anyhow::Result<Vec<Diagnostic>> {
+    let hostname = current_hostname().unwrap_or_else(|_| DEFAULT_HOST_NAME.to_string());
+    check_for_hostname(path, &hostname, is_target)
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -266,8 +1005,8 @@ pub struct AddNisSlaveResult {
     pub added: bool,
 }
 
-pub fn add_nis_slave_to_host_for_home(
-    home: &Path,
+pub fn add_nis_slave_to_host(
+    path: &Path,
     master_host: &str,
     slave: &str,
 ) -> anyhow::Result<AddNisSlaveResult> {
@@ -276,7 +1015,7 @@ pub fn add_nis_slave_to_host_for_home(
         bail!("slave hostname must not be empty");
     }
 
-    let host_path = host_config_path_for_home(home, master_host);
+    let host_path = host_config_path(path, master_host);
     if !host_path.exists() {
         bail!(
             "host config {} does not exist; run `debkit host-config` on that host or create it first",
@@ -284,7 +1023,7 @@ pub fn add_nis_slave_to_host_for_home(
         );
     }
 
-    let config = load_for_home_and_hostname(home, master_host)?;
+    let config = load_for_hostname(path, master_host)?;
     if !config.nis.enabled || config.nis.role != "master" {
         bail!("host `{master_host}` must have `nis.enabled = true` and `nis.role = \"master\"`");
     }
@@ -293,7 +1032,7 @@ pub fn add_nis_slave_to_host_for_home(
         .with_context(|| format!("failed to read {}", host_path.display()))?;
     let (updated, added) = add_nis_slave_to_raw_config(&raw, slave)?;
     if added {
-        fs::write(&host_path, updated)
+        exec::write(&host_path, updated)
             .with_context(|| format!("failed to write {}", host_path.display()))?;
     }
     Ok(AddNisSlaveResult {
@@ -302,247 +1041,721 @@ pub fn add_nis_slave_to_host_for_home(
     })
 }
 
-fn load_for_home_and_hostname(home: &Path, hostname: &str) -> anyhow::Result<DebkitConfig> {
-    let path = config_path_for_home(home);
+/// Every `section.key` that config.toml understands, in file order.
+pub fn keys() -> Vec<String> {
+    let defaults = default_document(DEFAULT_HOST_NAME);
+    defaults
+        .iter()
+        .filter_map(|(section, item)| Some((section, item.as_table()?)))
+        .flat_map(|(section, table)| table.iter().map(move |(key, _)| format!("{section}.{key}")))
+        .collect()
+}
+
+/// Returns the value of a dotted key such as `variety.interval_minutes` from config.toml,
+/// falling back to the default when the file does not set it.
+pub fn get(path: &Path, key: &str) -> anyhow::Result<Value> {
+    let raw = if path.exists() {
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?
+    } else {
+        String::new()
+    };
+    let hostname = current_hostname().unwrap_or_else(|_| DEFAULT_HOST_NAME.to_string());
+    get_key_from_raw_config(&raw, key, &hostname)
+}
+
+/// Sets a dotted key in config.toml, keeping comments and everything else in the file.
+pub fn set(path: &Path, key: &str, input: &str) -> anyhow::Result<()> {
+    let raw = if path.exists() {
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?
+    } else {
+        let hostname = current_hostname().unwrap_or_else(|_| DEFAULT_HOST_NAME.to_string());
+        if let Some(parent) = path.parent() {
+            exec::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        serialize_config(&DebkitConfig::for_hostname(&hostname))
+    };
+    let updated = set_key_in_raw_config(&raw, key, input)?;
+    exec::write(path, updated).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Removes a dotted key from config.toml so the default applies again. Returns whether
+/// the file set it.
+pub fn unset(path: &Path, key: &str) -> anyhow::Result<bool> {
+    if !path.exists() {
+        split_key(key)?;
+        return Ok(false);
+    }
     let raw =
-        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
-    let (mut config, missing_keys) = parse_config(&raw)?;
-    if missing_keys.host_name {
-        config.host.name = hostname.to_string();
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let (updated, removed) = unset_key_in_raw_config(&raw, key)?;
+    if removed {
+        exec::write(path, updated)
+            .with_context(|| format!("failed to write {}", path.display()))?;
     }
-    if missing_keys.wake_on_lan_reference_host {
-        config.wake_on_lan.reference_host = hostname.to_string();
+    Ok(removed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found by [`check`], located as precisely as the file allows.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, ": {severity}: {}", self.message)
     }
+}
 
-    let host_path = host_config_path_for_home(home, hostname);
+/// Checks config.toml and the host override for `hostname` without changing either.
+///
+/// Syntax errors, wrong value types and values that loading would reject are errors;
+/// unknown sections and keys, ignored or deprecated keys, relative wallpaper folders and
+/// `foundation.install` entries that `is_target` does not recognise are warnings.
+fn check_for_hostname(
+    path: &Path,
+    hostname: &str,
+    is_target: impl Fn(&str) -> bool,
+) -> anyhow::Result<Vec<Diagnostic>> {
+    let base_path = path.to_path_buf();
+    if !base_path.exists() {
+        return Ok(vec![Diagnostic {
+            severity: Severity::Error,
+            path: base_path,
+            line: None,
+            key: None,
+            message: "config file does not exist; run `debkit configure host-config` to create it"
+                .to_string(),
+        }]);
+    }
+
+    let mut files = vec![base_path];
+    let host_path = host_config_path(path, hostname);
     if host_path.exists() {
-        let host_raw = fs::read_to_string(&host_path)
-            .with_context(|| format!("failed to read {}", host_path.display()))?;
-        let (host_config, host_missing_keys) = parse_config(&host_raw)?;
-        apply_host_overlay(&mut config, host_config, host_missing_keys);
+        files.push(host_path);
+    }
+
+    let mut diagnostics = Vec::new();
+    let mut parsed = Vec::new();
+    for path in &files {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let file_diagnostics = check_raw_config(path, &raw);
+        let has_errors = file_diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error);
+        diagnostics.extend(file_diagnostics);
+        if !has_errors {
+            parsed.push((path, raw));
+        }
     }
-    config.host.name = hostname.to_string();
+    if parsed.len() != files.len() {
+        return Ok(diagnostics);
+    }
+
+    let layers = parsed
+        .iter()
+        .map(|(path, raw)| (path.as_path(), raw.as_str()))
+        .collect::<Vec<_>>();
+    let config = match merged_config(hostname, &layers) {
+        Ok(config) => config,
+        Err(err) => {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                path: parsed[0].0.clone(),
+                line: None,
+                key: None,
+                message: format!("{err:#}"),
+            });
+            return Ok(diagnostics);
+        }
+    };
+
+    // Point at the file that decides the value: the host override when it sets the key.
+    let locate = |key: &str| {
+        let (section, name) = key.split_once('.').expect("dotted key");
+        parsed
+            .iter()
+            .rev()
+            .find_map(|(path, raw)| {
+                let line = key_line(raw, section, name)?;
+                Some(((*path).clone(), Some(line)))
+            })
+            .unwrap_or_else(|| (parsed[0].0.clone(), None))
+    };
+    let mut semantic = |severity, key: &str, message: String| {
+        let (path, line) = locate(key);
+        diagnostics.push(Diagnostic {
+            severity,
+            path,
+            line,
+            key: Some(key.to_string()),
+            message,
+        });
+    };
+
+    for (key, message) in problems(&config) {
+        semantic(Severity::Error, &key, message);
+    }
+    let folder = &config.wallpapers.folder;
+    if !folder.is_empty() && !Path::new(folder).is_absolute() {
+        semantic(
+            Severity::Warning,
+            "wallpapers.folder",
+            format!("`wallpapers.folder` should be an absolute path, got `{folder}`"),
+        );
+    }
+    for name in &config.foundation.install {
+        if !is_target(name) {
+            semantic(
+                Severity::Warning,
+                "foundation.install",
+                format!("`foundation.install` lists unknown target `{name}`; it will be skipped"),
+            );
+        }
+    }
+
+    Ok(diagnostics)
+}
+
+fn load_for_hostname(path: &Path, hostname: &str) -> anyhow::Result<DebkitConfig> {
+    let raw =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    load_layers(hostname, path, &raw)
+}
+
+/// Builds the effective config from the base file's contents and the host override.
+fn load_layers(hostname: &str, path: &Path, raw: &str) -> anyhow::Result<DebkitConfig> {
+    let host_path = host_config_path(path, hostname);
+    let host_raw = if host_path.exists() {
+        Some(
+            fs::read_to_string(&host_path)
+                .with_context(|| format!("failed to read {}", host_path.display()))?,
+        )
+    } else {
+        None
+    };
+
+    let mut layers = vec![(path, raw)];
+    if let Some(host_raw) = &host_raw {
+        layers.push((host_path.as_path(), host_raw.as_str()));
+    }
+    let config = merged_config(hostname, &layers)?;
     validate_config(&config)?;
     Ok(config)
 }
 
-pub fn configure_complete_for_home(home: &Path) -> anyhow::Result<PathBuf> {
+/// Makes sure the base config exists and creates the host override file for the current
+/// host, returning the override's path.
+pub fn configure_complete(base_path: &Path) -> anyhow::Result<PathBuf> {
     let hostname = current_hostname().unwrap_or_else(|_| DEFAULT_HOST_NAME.to_string());
-    let base_path = config_path_for_home(home);
     if let Some(parent) = base_path.parent() {
-        fs::create_dir_all(parent)
+        exec::create_dir_all(parent)
             .with_context(|| format!("failed to create {}", parent.display()))?;
     }
     if !base_path.exists() {
         let default_cfg = DebkitConfig::for_hostname(&hostname);
-        fs::write(&base_path, serialize_config(&default_cfg))
+        exec::write(base_path, serialize_config(&default_cfg))
             .with_context(|| format!("failed to write {}", base_path.display()))?;
     }
 
-    let path = host_config_path_for_home(home, &hostname);
+    let path = host_config_path(base_path, &hostname);
     if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
+        exec::create_dir_all(parent)
             .with_context(|| format!("failed to create {}", parent.display()))?;
     }
 
     if !path.exists() {
         let content = format!(
-            "# DebKit host overrides for {hostname}\n# This file supplements ~/.config/debkit/config.toml.\n# Add only values that differ for this host.\n\n"
+            "# DebKit host overrides for {hostname}\n# This file supplements {}.\n# Add only values that differ for this host.\n\n",
+            base_path.display()
         );
-        fs::write(&path, content).with_context(|| format!("failed to write {}", path.display()))?;
+        exec::write(&path, content)
+            .with_context(|| format!("failed to write {}", path.display()))?;
     }
     Ok(path)
 }
 
-pub fn load_or_init_for_home(home: &Path) -> anyhow::Result<DebkitConfig> {
+/// Loads the config at `path`, creating it with defaults or adding keys it lacks first.
+pub fn load_or_init(path: &Path) -> anyhow::Result<DebkitConfig> {
     let hostname = current_hostname().unwrap_or_else(|_| DEFAULT_HOST_NAME.to_string());
-    let path = config_path_for_home(home);
     if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
+        exec::create_dir_all(parent)
             .with_context(|| format!("failed to create {}", parent.display()))?;
     }
 
-    if !path.exists() {
-        let default_cfg = DebkitConfig::for_hostname(&hostname);
-        fs::write(&path, serialize_config(&default_cfg))
+    let raw = if path.exists() {
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?
+    } else {
+        let default_raw = serialize_config(&DebkitConfig::for_hostname(&hostname));
+        exec::write(path, &default_raw)
             .with_context(|| format!("failed to write {}", path.display()))?;
-    }
-
-    let raw =
-        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
-    let (mut config, missing_keys) = parse_config(&raw)?;
-
-    if missing_keys.host_name {
-        config.host.name = hostname.clone();
-    }
-    if missing_keys.wake_on_lan_reference_host {
-        config.wake_on_lan.reference_host = hostname.clone();
-    }
-
-    if missing_keys.any_missing() {
-        fs::write(&path, serialize_config(&config))
+        default_raw
+    };
+    parse_config(&raw).with_context(|| format!("invalid config {}", path.display()))?;
+    let mut document = parse_toml_document(&raw)?;
+    let raw = if backfill(&mut document, &default_document(&hostname))? {
+        let updated = ensure_trailing_newline(document.to_string());
+        exec::write(path, &updated)
             .with_context(|| format!("failed to update {}", path.display()))?;
-    }
-
-    config.host.name = hostname.clone();
-    let host_path = host_config_path_for_home(home, &hostname);
-    if host_path.exists() {
-        let host_raw = fs::read_to_string(&host_path)
-            .with_context(|| format!("failed to read {}", host_path.display()))?;
-        let (host_config, host_missing_keys) = parse_config(&host_raw)?;
-        apply_host_overlay(&mut config, host_config, host_missing_keys);
-        config.host.name = hostname;
-    }
-
-    validate_config(&config)?;
+        updated
+    } else {
+        raw
+    };
 
-    Ok(config)
+    load_layers(&hostname, path, &raw)
 }
 
 pub fn config_path_for_home(home: &Path) -> PathBuf {
     home.join(".config").join("debkit").join("config.toml")
 }
 
-pub fn host_config_path_for_home(home: &Path, hostname: &str) -> PathBuf {
-    home.join(".config")
-        .join("debkit")
+/// The host override for `hostname` that accompanies the config file at `path`.
+pub fn host_config_path(path: &Path, hostname: &str) -> PathBuf {
+    path.parent()
+        .unwrap_or(Path::new(""))
         .join("hosts")
         .join(format!("{}.toml", sanitize_hostname_for_path(hostname)))
 }
 
-pub fn home_dir() -> anyhow::Result<PathBuf> {
+fn home_dir() -> anyhow::Result<PathBuf> {
     std::env::var_os("HOME")
         .map(PathBuf::from)
         .context("HOME environment variable is not set")
 }
 
-fn apply_host_overlay(base: &mut DebkitConfig, overlay: DebkitConfig, missing: MissingKeys) {
-    if !missing.wallpapers_folder {
-        base.wallpapers.folder = overlay.wallpapers.folder;
-    }
-    if !missing.variety_interval_minutes {
-        base.variety.interval_minutes = overlay.variety.interval_minutes;
+fn validate_config(config: &DebkitConfig) -> anyhow::Result<()> {
+    if let Some((_, message)) = problems(config).into_iter().next() {
+        bail!(message);
     }
-    if !missing.foundation_install {
-        base.foundation.install = overlay.foundation.install;
+    Ok(())
+}
+
+/// Semantic problems with a parsed config, each with the key it is about.
+fn problems(config: &DebkitConfig) -> Vec<(String, String)> {
+    let mut problems = Vec::new();
+    let mut problem =
+        |key: &str, message: &str| problems.push((key.to_string(), message.to_string()));
+    let wallpapers = &config.wallpapers;
+    if !wallpapers.source.trim().is_empty() {
+        if wallpapers.folder.trim().is_empty() {
+            problem(
+                "wallpapers.folder",
+                "`wallpapers.folder` must name the local copy when `wallpapers.source` is set",
+            );
+        } else if Path::new(wallpapers.source.trim_end_matches('/'))
+            == Path::new(wallpapers.folder.trim_end_matches('/'))
+        {
+            problem(
+                "wallpapers.source",
+                "`wallpapers.source` must differ from `wallpapers.folder`, which is its local copy",
+            );
+        }
     }
-    if !missing.essentials_packages {
-        base.essentials.packages = overlay.essentials.packages;
+    if config.variety.interval_minutes == 0 {
+        problem(
+            "variety.interval_minutes",
+            "`variety.interval_minutes` must be greater than 0",
+        );
     }
-    if !missing.npm_version {
-        base.npm.version = overlay.npm.version;
+    if config.variety.version.contains(char::is_whitespace) {
+        problem(
+            "variety.version",
+            "`variety.version` must be a single version such as `0.8.4-1` or `0.8.*`",
+        );
     }
-    if !missing.sudo_nopass_enabled {
-        base.sudo_nopass.enabled = overlay.sudo_nopass.enabled;
+    if config
+        .variety
+        .filters
+        .iter()
+        .all(|filter| filter.trim().is_empty())
+    {
+        problem(
+            "variety.filters",
+            "`variety.filters` must name at least one filter, such as `Keep original`",
+        );
     }
-    if !missing.sudo_nopass_group {
-        base.sudo_nopass.group = overlay.sudo_nopass.group;
+    for source in &config.variety.online_sources {
+        let (kind, location) = source
+            .split_once(':')
+            .map_or((source.as_str(), ""), |(kind, location)| (kind, location));
+        if !VARIETY_ONLINE_SOURCES.contains(&kind) {
+            problem(
+                "variety.online_sources",
+                &format!(
+                    "`variety.online_sources` has unknown source `{kind}`; use one of {}",
+                    VARIETY_ONLINE_SOURCES.join(", ")
+                ),
+            );
+        } else if VARIETY_SOURCES_WITH_LOCATION.contains(&kind) && location.trim().is_empty() {
+            problem(
+                "variety.online_sources",
+                &format!(
+                    "`variety.online_sources` needs a search or URL for `{kind}`, as in `{kind}:<search>`"
+                ),
+            );
+        }
     }
-    if !missing.sudo_nopass_add_current_user {
-        base.sudo_nopass.add_current_user = overlay.sudo_nopass.add_current_user;
+    if config.npm.version.trim().is_empty() {
+        problem("npm.version", "`npm.version` must not be empty");
     }
-    if !missing.sudo_nopass_users {
-        base.sudo_nopass.users = overlay.sudo_nopass.users;
+    let toolchain = &config.rust.toolchain;
+    if toolchain.is_empty() || toolchain.contains(char::is_whitespace) {
+        problem(
+            "rust.toolchain",
+            "`rust.toolchain` must be a single toolchain such as `stable` or `1.79.0`",
+        );
     }
-    if !missing.sudo_nopass_nis_managed {
-        base.sudo_nopass.nis_managed = overlay.sudo_nopass.nis_managed;
+    let sha256 = &config.rust.rustup_init_sha256;
+    if !sha256.is_empty() && (sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        problem(
+            "rust.rustup_init_sha256",
+            "`rust.rustup_init_sha256` must be empty or a SHA-256 of 64 hex digits",
+        );
     }
-    if !missing.nis_enabled {
-        base.nis.enabled = overlay.nis.enabled;
+    if config
+        .cargo_tools
+        .install
+        .iter()
+        .any(|tool| tool.is_empty() || tool.ends_with('@') || tool.contains(char::is_whitespace))
+    {
+        problem(
+            "cargo_tools.install",
+            "`cargo_tools.install` entries must be crate names such as `cargo-deb` or `cargo-deb@2.7.0`",
+        );
     }
-    if !missing.nis_role {
-        base.nis.role = overlay.nis.role;
+    if config.fonts.archives.iter().any(|url| {
+        !url.starts_with("https://")
+            || !FONT_ARCHIVE_SUFFIXES
+                .iter()
+                .any(|suffix| url.ends_with(suffix))
+    }) {
+        problem(
+            "fonts.archives",
+            "`fonts.archives` entries must be https:// URLs of .zip, .tar.xz, .tar.gz or .tar files",
+        );
     }
-    if !missing.nis_domain {
-        base.nis.domain = overlay.nis.domain;
+    if config.zsh.plugins.iter().any(|plugin| {
+        plugin.split('/').count() != 2
+            || plugin.split('/').any(str::is_empty)
+            || plugin.contains(char::is_whitespace)
+    }) {
+        problem(
+            "zsh.plugins",
+            "`zsh.plugins` entries must be GitHub repositories such as `zsh-users/zsh-autosuggestions`",
+        );
     }
-    if !missing.nis_admin_user {
-        base.nis.admin_user = overlay.nis.admin_user;
+    for (alias, host) in &config.ssh.hosts {
+        let values = [alias, &host.hostname, &host.user, &host.identity_file];
+        if alias.trim().is_empty()
+            || values
+                .into_iter()
+                .chain(host.options.values())
+                .any(|value| value.contains(['\n', '\r']))
+            || host
+                .options
+                .keys()
+                .any(|key| key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric()))
+        {
+            problem(
+                &format!("ssh.hosts.{alias}"),
+                &format!(
+                    "`ssh.hosts.{alias}` needs a host alias, single-line values and option names such as `ProxyJump`"
+                ),
+            );
+        }
     }
-    if !missing.nis_local_admin_groups {
-        base.nis.local_admin_groups = overlay.nis.local_admin_groups;
+    if config.flatpak.apps.iter().any(|app| {
+        app.split('.').count() < 3
+            || app
+                .split('.')
+                .any(|part| part.is_empty() || part.contains(char::is_whitespace))
+    }) {
+        problem(
+            "flatpak.apps",
+            "`flatpak.apps` entries must be application IDs such as `org.mozilla.firefox`",
+        );
     }
-    if !missing.nis_master {
-        base.nis.master = overlay.nis.master;
+    if config
+        .neovim
+        .min_version
+        .split('.')
+        .any(|part| part.parse::<u32>().is_err())
+    {
+        problem(
+            "neovim.min_version",
+            "`neovim.min_version` must be a version such as `0.9.0`",
+        );
     }
-    if !missing.nis_server {
-        base.nis.server = overlay.nis.server;
+    if config.neovim.release != "stable" && !config.neovim.release.starts_with('v') {
+        problem(
+            "neovim.release",
+            "`neovim.release` must be `stable` or a release tag such as `v0.10.4`",
+        );
     }
-    if !missing.nis_prefer_local {
-        base.nis.prefer_local = overlay.nis.prefer_local;
+    if config.python.tools.iter().any(|tool| {
+        let (name, version) = tool.split_once("==").unwrap_or((tool, "x"));
+        name.is_empty() || version.is_empty() || tool.contains(char::is_whitespace)
+    }) {
+        problem(
+            "python.tools",
+            "`python.tools` entries must be package names such as `httpie` or `httpie==3.2.4`",
+        );
     }
-    if !missing.nis_push_to_slaves {
-        base.nis.push_to_slaves = overlay.nis.push_to_slaves;
+    if config.tailscale.auth_key_env.is_empty()
+        || !config
+            .tailscale
+            .auth_key_env
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        problem(
+            "tailscale.auth_key_env",
+            "`tailscale.auth_key_env` must be an environment variable name such as `TS_AUTHKEY`",
+        );
     }
-    if !missing.nis_force_refresh_maps {
-        base.nis.force_refresh_maps = overlay.nis.force_refresh_maps;
+    if !config.tailscale.auth_key_file.is_empty()
+        && !Path::new(&config.tailscale.auth_key_file).is_absolute()
+    {
+        problem(
+            "tailscale.auth_key_file",
+            "`tailscale.auth_key_file` must be an absolute path",
+        );
     }
-    if !missing.nis_slaves {
-        base.nis.slaves = overlay.nis.slaves;
+    if config.tailscale.up_args.iter().any(|arg| {
+        !arg.starts_with("--") || arg.starts_with("--authkey") || arg.starts_with("--auth-key")
+    }) {
+        problem(
+            "tailscale.up_args",
+            "`tailscale.up_args` entries must be flags such as `--ssh`; give the auth key with `auth_key_env` or `auth_key_file`",
+        );
     }
-    if !missing.nis_servers {
-        base.nis.servers = overlay.nis.servers;
+    if config.auto_updates.origins.is_empty()
+        || config
+            .auto_updates
+            .origins
+            .iter()
+            .any(|origin| origin.trim().is_empty() || origin.contains(['"', '\n']))
+    {
+        problem(
+            "auto_updates.origins",
+            "`auto_updates.origins` must list origin patterns such as `origin=Debian,label=Debian-Security`",
+        );
     }
-    if !missing.wake_on_lan_enabled {
-        base.wake_on_lan.enabled = overlay.wake_on_lan.enabled;
+    let clock_time = config
+        .auto_updates
+        .reboot_time
+        .split_once(':')
+        .and_then(|(hour, minute)| Some((hour.parse::<u8>().ok()?, minute.parse::<u8>().ok()?)))
+        .filter(|_| config.auto_updates.reboot_time.len() == 5);
+    if !clock_time.is_some_and(|(hour, minute)| hour < 24 && minute < 60) {
+        problem(
+            "auto_updates.reboot_time",
+            "`auto_updates.reboot_time` must be a time such as `02:00`",
+        );
     }
-    if !missing.wake_on_lan_interfaces {
-        base.wake_on_lan.interfaces_auto = overlay.wake_on_lan.interfaces_auto;
-        base.wake_on_lan.interfaces = overlay.wake_on_lan.interfaces;
+    if config.auto_updates.mail.contains(['"', '\n'])
+        || config.auto_updates.mail.contains(char::is_whitespace)
+    {
+        problem(
+            "auto_updates.mail",
+            "`auto_updates.mail` must be an address such as `root` or `me@example.com`",
+        );
     }
-    if !missing.wake_on_lan_mode {
-        base.wake_on_lan.mode = overlay.wake_on_lan.mode;
+    if config.nfs_mounts.method == NfsMountMethod::Fstab && config.nfs_mounts.net {
+        problem(
+            "nfs_mounts.net",
+            "`nfs_mounts.net` needs `nfs_mounts.method = \"autofs\"`",
+        );
     }
-    if !missing.wake_on_lan_backend {
-        base.wake_on_lan.backend = overlay.wake_on_lan.backend;
+    for (mount_point, source) in &config.nfs_mounts.mounts {
+        let valid_source = source
+            .split_once(':')
+            .is_some_and(|(host, export)| !host.is_empty() && export.starts_with('/'));
+        if !mount_point.starts_with('/')
+            || mount_point == "/"
+            || mount_point.contains(char::is_whitespace)
+            || !valid_source
+            || source.contains(char::is_whitespace)
+        {
+            problem(
+                &format!("nfs_mounts.mounts.{mount_point}"),
+                &format!(
+                    "`nfs_mounts.mounts` needs absolute mount points and `host:/export` sources (got `{mount_point}` = `{source}`)"
+                ),
+            );
+        }
     }
-    if !missing.wake_on_lan_reference_host {
-        base.wake_on_lan.reference_host = overlay.wake_on_lan.reference_host;
+    if config.nfs_mounts.options.is_empty()
+        || config.nfs_mounts.options.contains(char::is_whitespace)
+    {
+        problem(
+            "nfs_mounts.options",
+            "`nfs_mounts.options` must be comma-separated mount options such as `rw,soft`",
+        );
     }
-}
-
-fn validate_config(config: &DebkitConfig) -> anyhow::Result<()> {
-    if config.variety.interval_minutes == 0 {
-        bail!("`variety.interval_minutes` must be greater than 0");
+    if config.firewall.allow.iter().any(|rule| {
+        let (ports, protocol) = rule.split_once('/').unwrap_or((rule, ""));
+        let port = |port: &str| port.parse::<u16>().is_ok_and(|port| port > 0);
+        let valid_ports = match ports.split_once(':') {
+            // ufw only takes ranges with a protocol.
+            Some((first, last)) => port(first) && port(last) && !protocol.is_empty(),
+            None => port(ports),
+        };
+        !valid_ports || !["", "tcp", "udp"].contains(&protocol)
+    }) {
+        problem(
+            "firewall.allow",
+            "`firewall.allow` entries must be ports such as `80`, `443/tcp` or `60000:61000/udp`",
+        );
     }
-    if config.npm.version.trim().is_empty() {
-        bail!("`npm.version` must not be empty");
+    for (schema, keys) in &config.gnome_settings.settings {
+        if schema.is_empty() || schema.contains(char::is_whitespace) {
+            problem(
+                "gnome_settings.settings",
+                &format!("`gnome_settings.settings` has an invalid schema `{schema}`"),
+            );
+        }
+        for (key, value) in keys {
+            let valid_key = !key.is_empty()
+                && key
+                    .chars()
+                    .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-');
+            if !valid_key || value.trim().is_empty() {
+                problem(
+                    &format!("gnome_settings.settings.{schema}"),
+                    &format!(
+                        "`gnome_settings.settings.{schema}` needs keys such as `color-scheme` with GVariant values such as `\"'prefer-dark'\"` (got `{key}` = `{value}`)"
+                    ),
+                );
+            }
+        }
     }
     if config.sudo_nopass.group.trim().is_empty() {
-        bail!("`sudo_nopass.group` must not be empty");
+        problem("sudo_nopass.group", "`sudo_nopass.group` must not be empty");
     }
     if config.nis.enabled && config.nis.domain.trim().is_empty() {
-        bail!("`nis.domain` must be set when `nis.enabled = true`");
+        problem(
+            "nis.domain",
+            "`nis.domain` must be set when `nis.enabled = true`",
+        );
     }
     if config.nis.enabled && config.nis.admin_user.trim().is_empty() {
-        bail!("`nis.admin_user` must be set when `nis.enabled = true`");
+        problem(
+            "nis.admin_user",
+            "`nis.admin_user` must be set when `nis.enabled = true`",
+        );
     }
     if config.nis.enabled && !matches!(config.nis.role.as_str(), "master" | "slave" | "client") {
-        bail!("`nis.role` must be one of `master`, `slave`, or `client`");
+        problem(
+            "nis.role",
+            "`nis.role` must be one of `master`, `slave`, or `client`",
+        );
     }
     if config.nis.enabled
         && matches!(config.nis.role.as_str(), "slave" | "slave-client")
         && config.nis.master.trim().is_empty()
     {
-        bail!("`nis.master` must be set when `nis.role = \"slave\"`");
+        problem(
+            "nis.master",
+            "`nis.master` must be set when `nis.role = \"slave\"`",
+        );
     }
     if config.nis.enabled
         && config.nis.role == "client"
         && config.nis.server.trim().is_empty()
         && config.nis.servers.is_empty()
     {
-        bail!("`nis.server` must be set when `nis.role = \"client\"`");
+        problem(
+            "nis.server",
+            "`nis.server` must be set when `nis.role = \"client\"`",
+        );
     }
     if config.wake_on_lan.enabled && config.wake_on_lan.mode != "magic" {
-        bail!("`wake_on_lan.mode` currently supports only `magic`");
+        problem(
+            "wake_on_lan.mode",
+            "`wake_on_lan.mode` currently supports only `magic`",
+        );
     }
     if !matches!(
         config.wake_on_lan.backend.as_str(),
         "auto" | "network_manager" | "networkmanager" | "ethtool"
     ) {
-        bail!("`wake_on_lan.backend` must be one of `network_manager`, `ethtool`, or `auto`");
+        problem(
+            "wake_on_lan.backend",
+            "`wake_on_lan.backend` must be one of `network_manager`, `ethtool`, or `auto`",
+        );
     }
-    Ok(())
+    let proxy = &config.network.proxy;
+    if !proxy.is_empty() && !network::is_valid_proxy(proxy) {
+        problem(
+            "network.proxy",
+            &format!("`network.proxy` must be an http:// or https:// URL, got `{proxy}`"),
+        );
+    }
+    if !matches!(config.self_update.source.as_str(), "github" | "apt") {
+        problem(
+            "self_update.source",
+            "`self_update.source` must be `github` or `apt`",
+        );
+    }
+    let repo = config
+        .self_update
+        .github_repo
+        .split('/')
+        .collect::<Vec<_>>();
+    if repo.len() != 2 || repo.iter().any(|part| part.trim().is_empty()) {
+        problem(
+            "self_update.github_repo",
+            "`self_update.github_repo` must look like `owner/name`",
+        );
+    }
+    for (name, fingerprint) in &config.keys {
+        if keyring::normalize_fingerprint(fingerprint).is_none() {
+            problem(
+                &format!("keys.{name}"),
+                &format!(
+                    "`keys.{name}` must be a 40 or 64 digit hex key fingerprint, got `{fingerprint}`"
+                ),
+            );
+        }
+    }
+    for (name, repo) in &config.publish {
+        if !matches!(repo.backend.as_str(), "aptly" | "reprepro") {
+            problem(
+                &format!("publish.{name}"),
+                &format!("`publish.{name}.backend` must be `aptly` or `reprepro`"),
+            );
+        }
+        if repo.distribution.trim().is_empty() || repo.component.trim().is_empty() {
+            problem(
+                &format!("publish.{name}"),
+                &format!("`publish.{name}` needs a distribution and a component"),
+            );
+        }
+    }
+    problems
 }
 
 fn current_user() -> Option<String> {
@@ -555,8 +1768,7 @@ fn current_user() -> Option<String> {
 }
 
 fn current_hostname() -> anyhow::Result<String> {
-    let raw = std::process::Command::new("hostname")
-        .output()
+    let raw = exec::query(&mut std::process::Command::new("hostname"))
         .context("failed to run hostname")?;
     if !raw.status.success() {
         bail!("hostname failed with status {}", raw.status);
@@ -584,11 +1796,7 @@ fn sanitize_hostname_for_path(hostname: &str) -> String {
 
 fn add_nis_slave_to_raw_config(raw: &str, slave: &str) -> anyhow::Result<(String, bool)> {
     let mut document = parse_toml_document(raw)?;
-    ensure_table(&mut document, "nis")?;
-
-    let nis = document["nis"]
-        .as_table_mut()
-        .context("`nis` must be a TOML table")?;
+    let nis = ensure_table(&mut document, "nis")?;
 
     let mut slaves = match nis.get("slaves") {
         Some(item) => string_array_item(item, "nis.slaves")?,
@@ -599,245 +1807,342 @@ fn add_nis_slave_to_raw_config(raw: &str, slave: &str) -> anyhow::Result<(String
     }
 
     slaves.push(slave.to_string());
-    nis["slaves"] = array_item(&slaves);
+    nis.insert("slaves", array_item(&slaves));
     Ok((ensure_trailing_newline(document.to_string()), true))
 }
 
-fn ensure_trailing_newline(mut raw: String) -> String {
-    if !raw.ends_with('\n') {
-        raw.push('\n');
+fn default_document(hostname: &str) -> DocumentMut {
+    serialize_config(&DebkitConfig::for_hostname(hostname))
+        .parse()
+        .expect("serialized default config is valid TOML")
+}
+
+fn split_key(key: &str) -> anyhow::Result<(&str, &str)> {
+    let unknown = || {
+        format!(
+            "unknown config key `{key}`; expected one of: {}",
+            keys().join(", ")
+        )
+    };
+    let (section, name) = key.split_once('.').with_context(unknown)?;
+    let defaults = default_document(DEFAULT_HOST_NAME);
+    if defaults
+        .get(section)
+        .and_then(|table| table.get(name))
+        .is_none()
+    {
+        bail!(unknown());
     }
-    raw
+    Ok((section, name))
 }
 
-#[derive(Debug, Clone, Copy)]
-struct MissingKeys {
-    host_name: bool,
-    wallpapers_folder: bool,
-    variety_interval_minutes: bool,
-    foundation_install: bool,
-    essentials_packages: bool,
-    npm_version: bool,
-    sudo_nopass_enabled: bool,
-    sudo_nopass_group: bool,
-    sudo_nopass_add_current_user: bool,
-    sudo_nopass_users: bool,
-    sudo_nopass_nis_managed: bool,
-    nis_enabled: bool,
-    nis_role: bool,
-    nis_domain: bool,
-    nis_admin_user: bool,
-    nis_local_admin_groups: bool,
-    nis_master: bool,
-    nis_server: bool,
-    nis_prefer_local: bool,
-    nis_push_to_slaves: bool,
-    nis_force_refresh_maps: bool,
-    nis_slaves: bool,
-    nis_servers: bool,
-    wake_on_lan_enabled: bool,
-    wake_on_lan_interfaces: bool,
-    wake_on_lan_mode: bool,
-    wake_on_lan_backend: bool,
-    wake_on_lan_reference_host: bool,
-}
-
-impl MissingKeys {
-    fn any_missing(self) -> bool {
-        self.host_name
-            || self.wallpapers_folder
-            || self.variety_interval_minutes
-            || self.foundation_install
-            || self.essentials_packages
-            || self.npm_version
-            || self.sudo_nopass_enabled
-            || self.sudo_nopass_group
-            || self.sudo_nopass_add_current_user
-            || self.sudo_nopass_users
-            || self.sudo_nopass_nis_managed
-            || self.nis_enabled
-            || self.nis_role
-            || self.nis_domain
-            || self.nis_admin_user
-            || self.nis_local_admin_groups
-            || self.nis_master
-            || self.nis_server
-            || self.nis_prefer_local
-            || self.nis_push_to_slaves
-            || self.nis_force_refresh_maps
-            || self.nis_slaves
-            || self.nis_servers
-            || self.wake_on_lan_enabled
-            || self.wake_on_lan_interfaces
-            || self.wake_on_lan_mode
-            || self.wake_on_lan_backend
-            || self.wake_on_lan_reference_host
-    }
-}
-
-fn parse_config(raw: &str) -> anyhow::Result<(DebkitConfig, MissingKeys)> {
-    let mut config = DebkitConfig::default();
-    let document = parse_toml_document(raw)?;
+/// Reports syntax errors, unknown sections and keys, and values of the wrong type.
+fn check_raw_config(path: &Path, raw: &str) -> Vec<Diagnostic> {
+    let diagnostic =
+        |severity, span: Option<std::ops::Range<usize>>, key: Option<String>, message| Diagnostic {
+            severity,
+            path: path.to_path_buf(),
+            line: span.map(|span| line_at(raw, span.start)),
+            key,
+            message,
+        };
+
+    let document = match ImDocument::parse(raw) {
+        Ok(document) => document,
+        Err(err) => {
+            let message = format!("invalid TOML: {}", err.message().trim().replace('\n', "; "));
+            return vec![diagnostic(Severity::Error, err.span(), None, message)];
+        }
+    };
 
-    let host = table(&document, "host")?;
-    if let Some(item) = item(host, "name") {
-        config.host.name = string_item(item, "host.name")?;
-    }
+    let defaults = default_document(DEFAULT_HOST_NAME);
+    let mut diagnostics = Vec::new();
+    let root = document.as_table();
+    for (section, section_item) in root.iter() {
+        let section_span = root.key(section).and_then(|key| key.span());
+        let Some(table) = section_item.as_table_like() else {
+            let message = format!("`{section}` must be a TOML table");
+            diagnostics.push(diagnostic(
+                Severity::Error,
+                section_span.clone(),
+                None,
+                message,
+            ));
+            continue;
+        };
+        let known = defaults.get(section).and_then(Item::as_table);
+        if known.is_none() && section != "host" {
+            let message = format!("unknown section `[{section}]`");
+            diagnostics.push(diagnostic(Severity::Warning, section_span, None, message));
+            continue;
+        }
 
-    let wallpapers = table(&document, "wallpapers")?;
-    if let Some(item) = item(wallpapers, "folder") {
-        config.wallpapers.folder = string_item(item, "wallpapers.folder")?;
+        for (key, item) in table.iter() {
+            let dotted = format!("{section}.{key}");
+            let span = table.key(key).and_then(|key| key.span());
+            if section == "keys" {
+                if let Err(err) = string_item(item, &dotted) {
+                    let span = item.span().or(span);
+                    diagnostics.push(diagnostic(
+                        Severity::Error,
+                        span,
+                        Some(dotted),
+                        err.to_string(),
+                    ));
+                }
+                continue;
+            }
+            if section == "publish" {
+                let Some(repo) = item.as_table_like() else {
+                    let message = format!("`{dotted}` must be a table");
+                    diagnostics.push(diagnostic(Severity::Error, span, Some(dotted), message));
+                    continue;
+                };
+                for (field, value) in repo.iter() {
+                    let field_span = repo.key(field).and_then(|key| key.span());
+                    let dotted = format!("{dotted}.{field}");
+                    let (severity, message) = if !PUBLISH_REPO_KEYS.contains(&field) {
+                        (Severity::Warning, format!("unknown key `{dotted}`"))
+                    } else if let Err(err) = string_item(value, &dotted) {
+                        (Severity::Error, err.to_string())
+                    } else {
+                        continue;
+                    };
+                    diagnostics.push(diagnostic(severity, field_span, Some(dotted), message));
+                }
+                continue;
+            }
+            let warning = match dotted.as_str() {
+                "host.name" => Some(format!(
+                    "`{dotted}` is ignored; the hostname is detected when DebKit runs"
+                )),
+                "wake_on_lan.persistence" => Some(format!(
+                    "`{dotted}` is deprecated; use `wake_on_lan.backend`"
+                )),
+                _ => None,
+            };
+            if let Some(message) = warning {
+                diagnostics.push(diagnostic(Severity::Warning, span, Some(dotted), message));
+                continue;
+            }
+            let Some(default) = known
+                .and_then(|known| known.get(key))
+                .and_then(Item::as_value)
+            else {
+                let message = format!("unknown key `{dotted}`");
+                diagnostics.push(diagnostic(Severity::Warning, span, Some(dotted), message));
+                continue;
+            };
+
+            let checked = match default {
+                _ if dotted == "wake_on_lan.interfaces" => match item.as_str() {
+                    Some("auto") => Ok(()),
+                    _ => string_array_item(item, &dotted).map(drop),
+                },
+                Value::String(_) => string_item(item, &dotted).map(drop),
+                Value::Boolean(_) => bool_item(item, &dotted).map(drop),
+                Value::Integer(_) => integer_item(item, &dotted).map(drop),
+                // Maps such as `ssh.hosts`, whose entries serde checks on load.
+                Value::InlineTable(_) => table_item(item, &dotted).map(drop),
+                _ => string_array_item(item, &dotted).map(drop),
+            };
+            if let Err(err) = checked {
+                let span = item.span().or(span);
+                diagnostics.push(diagnostic(
+                    Severity::Error,
+                    span,
+                    Some(dotted),
+                    err.to_string(),
+                ));
+            }
+        }
     }
+    diagnostics
+}
 
-    let variety = table(&document, "variety")?;
-    if let Some(item) = item(variety, "interval_minutes") {
-        config.variety.interval_minutes = integer_item(item, "variety.interval_minutes")?;
-    }
+fn key_line(raw: &str, section: &str, name: &str) -> Option<usize> {
+    let document = ImDocument::parse(raw).ok()?;
+    let key = document.get(section)?.as_table()?.key(name)?;
+    Some(line_at(raw, key.span()?.start))
+}
 
-    let foundation = table(&document, "foundation")?;
-    if let Some(item) = item(foundation, "install") {
-        config.foundation.install = string_array_item(item, "foundation.install")?;
-    }
+fn line_at(raw: &str, offset: usize) -> usize {
+    raw[..offset.min(raw.len())].matches('\n').count() + 1
+}
 
-    let essentials = table(&document, "essentials")?;
-    if let Some(item) = item(essentials, "packages") {
-        config.essentials.packages = string_array_item(item, "essentials.packages")?;
-    }
+fn get_key_from_raw_config(raw: &str, key: &str, hostname: &str) -> anyhow::Result<Value> {
+    let (section, name) = split_key(key)?;
+    let document = parse_toml_document(raw)?;
+    if let Some(item) = item(table(&document, section)?, name) {
+        return item
+            .as_value()
+            .cloned()
+            .with_context(|| format!("`{key}` must be a value, not a table"));
+    }
+    let defaults = default_document(hostname);
+    Ok(defaults[section][name]
+        .as_value()
+        .cloned()
+        .expect("default config keys are values"))
+}
 
-    let npm = table(&document, "npm")?;
-    if let Some(item) = item(npm, "version") {
-        config.npm.version = string_item(item, "npm.version")?;
+fn set_key_in_raw_config(raw: &str, key: &str, input: &str) -> anyhow::Result<String> {
+    let (section, name) = split_key(key)?;
+    let mut document = parse_toml_document(raw)?;
+    let defaults = default_document(DEFAULT_HOST_NAME);
+    let mut new_value = parse_input_value(defaults[section][name].as_value(), input);
+    let table = ensure_table(&mut document, section)?;
+    if let Some(old) = table.get(name).and_then(Item::as_value) {
+        *new_value.decor_mut() = old.decor().clone();
     }
+    table.insert(name, Item::Value(new_value));
 
-    let sudo_nopass = table(&document, "sudo_nopass")?;
-    if let Some(item) = item(sudo_nopass, "enabled") {
-        config.sudo_nopass.enabled = bool_item(item, "sudo_nopass.enabled")?;
-    }
-    if let Some(item) = item(sudo_nopass, "group") {
-        config.sudo_nopass.group = string_item(item, "sudo_nopass.group")?;
-    }
-    if let Some(item) = item(sudo_nopass, "add_current_user") {
-        config.sudo_nopass.add_current_user = bool_item(item, "sudo_nopass.add_current_user")?;
-    }
-    if let Some(item) = item(sudo_nopass, "users") {
-        config.sudo_nopass.users = string_array_item(item, "sudo_nopass.users")?;
-    }
-    if let Some(item) = item(sudo_nopass, "nis_managed") {
-        config.sudo_nopass.nis_managed = bool_item(item, "sudo_nopass.nis_managed")?;
-    }
+    let updated = ensure_trailing_newline(document.to_string());
+    let config = parse_config(&updated)?;
+    validate_config(&config)?;
+    Ok(updated)
+}
 
-    let nis = table(&document, "nis")?;
-    if let Some(item) = item(nis, "enabled") {
-        config.nis.enabled = bool_item(item, "nis.enabled")?;
-    }
-    if let Some(item) = item(nis, "role") {
-        config.nis.role = string_item(item, "nis.role")?;
-    }
-    if let Some(item) = item(nis, "domain") {
-        config.nis.domain = string_item(item, "nis.domain")?;
-    }
-    if let Some(item) = item(nis, "admin_user") {
-        config.nis.admin_user = string_item(item, "nis.admin_user")?;
-    }
-    if let Some(item) = item(nis, "local_admin_groups") {
-        config.nis.local_admin_groups = string_array_item(item, "nis.local_admin_groups")?;
-    }
-    if let Some(item) = item(nis, "master") {
-        config.nis.master = string_item(item, "nis.master")?;
-    }
-    if let Some(item) = item(nis, "server") {
-        config.nis.server = string_item(item, "nis.server")?;
-    }
-    if let Some(item) = item(nis, "prefer_local") {
-        config.nis.prefer_local = bool_item(item, "nis.prefer_local")?;
-    }
-    if let Some(item) = item(nis, "push_to_slaves") {
-        config.nis.push_to_slaves = bool_item(item, "nis.push_to_slaves")?;
+/// Interprets command-line input using the type of the key's default: strings are taken
+/// verbatim, arrays also accept `a,b,c`, everything else is parsed as a TOML value.
+fn parse_input_value(default: Option<&Value>, input: &str) -> Value {
+    let literal_array = input.trim_start().starts_with('[');
+    match default {
+        Some(Value::String(_)) if !literal_array => Value::from(input),
+        Some(Value::Array(_)) if !literal_array => {
+            let mut array = Array::default();
+            for entry in input.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                array.push(entry);
+            }
+            Value::Array(array)
+        }
+        _ => input
+            .trim()
+            .parse::<Value>()
+            .unwrap_or_else(|_| Value::from(input)),
     }
-    if let Some(item) = item(nis, "force_refresh_maps") {
-        config.nis.force_refresh_maps = bool_item(item, "nis.force_refresh_maps")?;
+}
+
+fn unset_key_in_raw_config(raw: &str, key: &str) -> anyhow::Result<(String, bool)> {
+    let (section, name) = split_key(key)?;
+    let mut document = parse_toml_document(raw)?;
+    let removed = match document.get_mut(section) {
+        Some(item) => item
+            .as_table_like_mut()
+            .with_context(|| format!("`{section}` must be a TOML table"))?
+            .remove(name)
+            .is_some(),
+        None => false,
+    };
+    if !removed {
+        return Ok((raw.to_string(), false));
     }
-    if let Some(item) = item(nis, "slaves") {
-        config.nis.slaves = string_array_item(item, "nis.slaves")?;
+    Ok((ensure_trailing_newline(document.to_string()), true))
+}
+
+fn ensure_trailing_newline(mut raw: String) -> String {
+    if !raw.ends_with('\n') {
+        raw.push('\n');
     }
-    if let Some(item) = item(nis, "servers") {
-        config.nis.servers = string_array_item(item, "nis.servers")?;
+    raw
+}
+
+/// Deserializes a single config file over the built-in defaults.
+fn parse_config(raw: &str) -> anyhow::Result<DebkitConfig> {
+    toml_edit::de::from_str(raw).map_err(|err| anyhow::anyhow!("{}", err.to_string().trim_end()))
+}
+
+/// Dotted keys that `defaults` sets but `document` does not.
+fn missing_keys(document: &DocumentMut, defaults: &DocumentMut) -> Vec<String> {
+    let mut missing = Vec::new();
+    for (section, default_table) in defaults.iter() {
+        let Some(default_table) = default_table.as_table() else {
+            continue;
+        };
+        let table = document.get(section).and_then(Item::as_table_like);
+        for (key, _) in default_table.iter() {
+            let set = table.is_some_and(|table| {
+                table.contains_key(key)
+                    || (section == "wake_on_lan"
+                        && key == "backend"
+                        && table.contains_key("persistence"))
+            });
+            if !set {
+                missing.push(format!("{section}.{key}"));
+            }
+        }
     }
+    missing
+}
 
-    let wake_on_lan = table(&document, "wake_on_lan")?;
-    if let Some(item) = item(wake_on_lan, "enabled") {
-        config.wake_on_lan.enabled = bool_item(item, "wake_on_lan.enabled")?;
+/// Adds the keys `document` lacks from `defaults`, leaving everything already in it alone.
+fn backfill(document: &mut DocumentMut, defaults: &DocumentMut) -> anyhow::Result<bool> {
+    let missing = missing_keys(document, defaults);
+    for key in &missing {
+        let (section, name) = key.split_once('.').expect("dotted key");
+        ensure_table(document, section)?.insert(name, defaults[section][name].clone());
     }
-    if let Some(item) = item(wake_on_lan, "interfaces") {
-        if item.as_str() == Some("auto") {
-            config.wake_on_lan.interfaces_auto = true;
-            config.wake_on_lan.interfaces.clear();
-        } else {
-            config.wake_on_lan.interfaces = string_array_item(item, "wake_on_lan.interfaces")?;
-            config.wake_on_lan.interfaces_auto = false;
+    Ok(!missing.is_empty())
+}
+
+/// Layers `files` over the defaults for `hostname`, key by key, so a host override only
+/// replaces the values it sets.
+fn merged_config(hostname: &str, files: &[(&Path, &str)]) -> anyhow::Result<DebkitConfig> {
+    let mut merged = default_document(hostname);
+    for (path, raw) in files {
+        // Deserialize each file on its own first so errors point at a line in that file.
+        parse_config(raw).with_context(|| format!("invalid config {}", path.display()))?;
+        let mut document = parse_toml_document(raw)?;
+        if let Some(wake_on_lan) = document
+            .get_mut("wake_on_lan")
+            .and_then(Item::as_table_like_mut)
+            && !wake_on_lan.contains_key("backend")
+            && let Some(persistence) = wake_on_lan.remove("persistence")
+        {
+            wake_on_lan.insert("backend", persistence);
         }
+        merge_table(merged.as_table_mut(), document.as_table());
     }
-    if let Some(item) = item(wake_on_lan, "mode") {
-        config.wake_on_lan.mode = string_item(item, "wake_on_lan.mode")?;
-    }
-    if let Some(item) = item(wake_on_lan, "backend").or_else(|| item(wake_on_lan, "persistence")) {
-        config.wake_on_lan.backend = string_item(item, "wake_on_lan.backend")?;
-    }
-    if let Some(item) = item(wake_on_lan, "reference_host") {
-        config.wake_on_lan.reference_host = string_item(item, "wake_on_lan.reference_host")?;
-    }
-
-    let missing = MissingKeys {
-        host_name: item(host, "name").is_none(),
-        wallpapers_folder: item(wallpapers, "folder").is_none(),
-        variety_interval_minutes: item(variety, "interval_minutes").is_none(),
-        foundation_install: item(foundation, "install").is_none(),
-        essentials_packages: item(essentials, "packages").is_none(),
-        npm_version: item(npm, "version").is_none(),
-        sudo_nopass_enabled: item(sudo_nopass, "enabled").is_none(),
-        sudo_nopass_group: item(sudo_nopass, "group").is_none(),
-        sudo_nopass_add_current_user: item(sudo_nopass, "add_current_user").is_none(),
-        sudo_nopass_users: item(sudo_nopass, "users").is_none(),
-        sudo_nopass_nis_managed: item(sudo_nopass, "nis_managed").is_none(),
-        nis_enabled: item(nis, "enabled").is_none(),
-        nis_role: item(nis, "role").is_none(),
-        nis_domain: item(nis, "domain").is_none(),
-        nis_admin_user: item(nis, "admin_user").is_none(),
-        nis_local_admin_groups: item(nis, "local_admin_groups").is_none(),
-        nis_master: item(nis, "master").is_none(),
-        nis_server: item(nis, "server").is_none(),
-        nis_prefer_local: item(nis, "prefer_local").is_none(),
-        nis_push_to_slaves: item(nis, "push_to_slaves").is_none(),
-        nis_force_refresh_maps: item(nis, "force_refresh_maps").is_none(),
-        nis_slaves: item(nis, "slaves").is_none(),
-        nis_servers: item(nis, "servers").is_none(),
-        wake_on_lan_enabled: item(wake_on_lan, "enabled").is_none(),
-        wake_on_lan_interfaces: item(wake_on_lan, "interfaces").is_none(),
-        wake_on_lan_mode: item(wake_on_lan, "mode").is_none(),
-        wake_on_lan_backend: item(wake_on_lan, "backend")
-            .or_else(|| item(wake_on_lan, "persistence"))
-            .is_none(),
-        wake_on_lan_reference_host: item(wake_on_lan, "reference_host").is_none(),
-    };
 
-    Ok((config, missing))
+    let mut config: DebkitConfig = toml_edit::de::from_document(merged)
+        .map_err(|err| anyhow::anyhow!("{}", err.to_string().trim_end()))?;
+    config.host.name = hostname.to_string();
+    Ok(config)
+}
+
+fn merge_table(base: &mut dyn TableLike, overlay: &dyn TableLike) {
+    for (key, item) in overlay.iter() {
+        let base_item = base.get_mut(key);
+        match (
+            base_item.and_then(Item::as_table_like_mut),
+            item.as_table_like(),
+        ) {
+            (Some(base_table), Some(overlay_table)) => merge_table(base_table, overlay_table),
+            _ => {
+                base.insert(key, item.clone());
+            }
+        }
+    }
 }
 
 fn parse_toml_document(raw: &str) -> anyhow::Result<DocumentMut> {
     raw.parse::<DocumentMut>().context("invalid TOML config")
 }
 
-fn table<'a>(document: &'a DocumentMut, section: &str) -> anyhow::Result<Option<&'a Table>> {
+fn table<'a>(
+    document: &'a DocumentMut,
+    section: &str,
+) -> anyhow::Result<Option<&'a dyn TableLike>> {
     match document.get(section) {
         Some(item) => item
-            .as_table()
+            .as_table_like()
             .map(Some)
             .with_context(|| format!("`{section}` must be a TOML table")),
         None => Ok(None),
     }
 }
 
-fn item<'a>(table: Option<&'a Table>, key: &str) -> Option<&'a Item> {
+fn item<'a>(table: Option<&'a dyn TableLike>, key: &str) -> Option<&'a Item> {
     table.and_then(|table| table.get(key))
 }
 
@@ -859,6 +2164,11 @@ fn integer_item(item: &Item, key: &str) -> anyhow::Result<u32> {
     u32::try_from(value).with_context(|| format!("`{key}` must be a non-negative u32"))
 }
 
+fn table_item<'a>(item: &'a Item, key: &str) -> anyhow::Result<&'a dyn TableLike> {
+    item.as_table_like()
+        .with_context(|| format!("`{key}` must be a table"))
+}
+
 fn string_array_item(item: &Item, key: &str) -> anyhow::Result<Vec<String>> {
     let array = item
         .as_array()
@@ -875,171 +2185,29 @@ fn string_array_item(item: &Item, key: &str) -> anyhow::Result<Vec<String>> {
     Ok(values)
 }
 
-fn ensure_table(document: &mut DocumentMut, section: &str) -> anyhow::Result<()> {
+fn ensure_table<'a>(
+    document: &'a mut DocumentMut,
+    section: &str,
+) -> anyhow::Result<&'a mut dyn TableLike> {
     if document.get(section).is_none() {
         document[section] = Item::Table(Table::new());
     }
-    if document[section].as_table().is_none() {
-        bail!("`{section}` must be a TOML table");
-    }
-    Ok(())
+    document[section]
+        .as_table_like_mut()
+        .with_context(|| format!("`{section}` must be a TOML table"))
 }
 
 fn serialize_config(config: &DebkitConfig) -> String {
-    let mut document = DocumentMut::new();
-
-    set_config_item(
-        &mut document,
-        "wallpapers",
-        "folder",
-        value(&config.wallpapers.folder),
-    );
-    set_config_item(
-        &mut document,
-        "variety",
-        "interval_minutes",
-        value(config.variety.interval_minutes as i64),
-    );
-    set_config_item(
-        &mut document,
-        "foundation",
-        "install",
-        array_item(&config.foundation.install),
-    );
-    set_config_item(
-        &mut document,
-        "essentials",
-        "packages",
-        array_item(&config.essentials.packages),
-    );
-    set_config_item(&mut document, "npm", "version", value(&config.npm.version));
-
-    set_config_item(
-        &mut document,
-        "sudo_nopass",
-        "enabled",
-        value(config.sudo_nopass.enabled),
-    );
-    set_config_item(
-        &mut document,
-        "sudo_nopass",
-        "group",
-        value(&config.sudo_nopass.group),
-    );
-    set_config_item(
-        &mut document,
-        "sudo_nopass",
-        "add_current_user",
-        value(config.sudo_nopass.add_current_user),
-    );
-    set_config_item(
-        &mut document,
-        "sudo_nopass",
-        "users",
-        array_item(&config.sudo_nopass.users),
-    );
-    set_config_item(
-        &mut document,
-        "sudo_nopass",
-        "nis_managed",
-        value(config.sudo_nopass.nis_managed),
-    );
-
-    set_config_item(&mut document, "nis", "enabled", value(config.nis.enabled));
-    set_config_item(&mut document, "nis", "role", value(&config.nis.role));
-    set_config_item(&mut document, "nis", "domain", value(&config.nis.domain));
-    set_config_item(
-        &mut document,
-        "nis",
-        "admin_user",
-        value(&config.nis.admin_user),
-    );
-    set_config_item(
-        &mut document,
-        "nis",
-        "local_admin_groups",
-        array_item(&config.nis.local_admin_groups),
-    );
-    set_config_item(&mut document, "nis", "master", value(&config.nis.master));
-    set_config_item(&mut document, "nis", "server", value(&config.nis.server));
-    set_config_item(
-        &mut document,
-        "nis",
-        "prefer_local",
-        value(config.nis.prefer_local),
-    );
-    set_config_item(
-        &mut document,
-        "nis",
-        "push_to_slaves",
-        value(config.nis.push_to_slaves),
-    );
-    set_config_item(
-        &mut document,
-        "nis",
-        "force_refresh_maps",
-        value(config.nis.force_refresh_maps),
-    );
-    set_config_item(
-        &mut document,
-        "nis",
-        "slaves",
-        array_item(&config.nis.slaves),
-    );
-    set_config_item(
-        &mut document,
-        "nis",
-        "servers",
-        array_item(&config.nis.servers),
-    );
-
-    set_config_item(
-        &mut document,
-        "wake_on_lan",
-        "enabled",
-        value(config.wake_on_lan.enabled),
-    );
-    if config.wake_on_lan.interfaces_auto {
-        set_config_item(&mut document, "wake_on_lan", "interfaces", value("auto"));
-    } else {
-        set_config_item(
-            &mut document,
-            "wake_on_lan",
-            "interfaces",
-            array_item(&config.wake_on_lan.interfaces),
-        );
+    let mut document =
+        toml_edit::ser::to_document(config).expect("config structs serialize to TOML");
+    for (_, item) in document.iter_mut() {
+        if let Some(table) = item.as_inline_table_mut().map(std::mem::take) {
+            *item = Item::Table(table.into_table());
+        }
     }
-    set_config_item(
-        &mut document,
-        "wake_on_lan",
-        "mode",
-        value(&config.wake_on_lan.mode),
-    );
-    set_config_item(
-        &mut document,
-        "wake_on_lan",
-        "backend",
-        value(&config.wake_on_lan.backend),
-    );
-    set_config_item(
-        &mut document,
-        "wake_on_lan",
-        "reference_host",
-        value(&config.wake_on_lan.reference_host),
-    );
-
     ensure_trailing_newline(document.to_string())
 }
 
-fn set_config_item(document: &mut DocumentMut, section: &str, key: &str, item: Item) {
-    if document.get(section).is_none() {
-        document[section] = Item::Table(Table::new());
-    }
-    document[section]
-        .as_table_mut()
-        .expect("section was just created as table")[key] = item;
-}
-
 fn array_item(items: &[String]) -> Item {
     let mut array = Array::default();
     for item in items {
@@ -1050,6 +2218,7 @@ fn array_item(items: &[String]) -> Item {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeSet;
     use std::path::PathBuf;
 
     use super::*;
@@ -1057,7 +2226,7 @@ mod tests {
     #[test]
     fn initializes_default_config() {
         let home = temp_home("default_init");
-        let config = load_or_init_for_home(&home).unwrap();
+        let config = load_or_init(&config_path_for_home(&home)).unwrap();
 
         assert_ne!(config.host.name, DEFAULT_HOST_NAME);
         assert_eq!(config.wallpapers.folder, DEFAULT_WALLPAPERS_FOLDER);
@@ -1067,6 +2236,7 @@ mod tests {
             vec![
                 "essentials",
                 "git",
+                "ssh",
                 "ripgrep",
                 "rust",
                 "npm",
@@ -1123,7 +2293,7 @@ mod tests {
         )
         .unwrap();
 
-        let config = load_or_init_for_home(&home).unwrap();
+        let config = load_or_init(&config_path_for_home(&home)).unwrap();
         assert_eq!(config.wallpapers.folder, "/tmp/walls");
         assert_eq!(config.variety.interval_minutes, DEFAULT_INTERVAL_MINUTES);
         assert_eq!(
@@ -1131,6 +2301,7 @@ mod tests {
             vec![
                 "essentials",
                 "git",
+                "ssh",
                 "ripgrep",
                 "rust",
                 "npm",
@@ -1155,7 +2326,7 @@ mod tests {
         assert!(rewritten.contains("interval_minutes"));
         assert!(rewritten.contains("/tmp/walls"));
         assert!(rewritten.contains(
-            "install = [\"essentials\", \"git\", \"ripgrep\", \"rust\", \"npm\", \"codex\", \"variety\", \"nis\", \"wake-on-lan\"]"
+            "install = [\"essentials\", \"git\", \"ssh\", \"ripgrep\", \"rust\", \"npm\", \"codex\", \"variety\", \"nis\", \"wake-on-lan\"]"
         ));
         assert!(rewritten.contains("[essentials]"));
         assert!(rewritten.contains("packages = [\"curl\", \"wget\", \"zip\", \"unzip\", \"rsync\", \"ca-certificates\", \"gnupg\", \"apt-transport-https\", \"neovim\"]"));
@@ -1171,46 +2342,159 @@ mod tests {
     #[test]
     fn parses_foundation_install_array() {
         let raw = "[foundation]\ninstall = [\"variety\", \"rust\"]\n";
-        let (config, missing) = parse_config(raw).unwrap();
+        let config = parse_config(raw).unwrap();
+        let missing = missing(raw);
         assert_eq!(config.foundation.install, vec!["variety", "rust"]);
-        assert!(missing.wallpapers_folder);
-        assert!(missing.variety_interval_minutes);
-        assert!(!missing.foundation_install);
-        assert!(missing.npm_version);
+        assert!(missing.contains("wallpapers.folder"));
+        assert!(missing.contains("variety.interval_minutes"));
+        assert!(!missing.contains("foundation.install"));
+        assert!(missing.contains("npm.version"));
+    }
+
+    #[test]
+    fn parses_variety_privacy_toggles() {
+        let raw = "[variety]\nsafe_mode = true\nstats_enabled = true\nsmart_notice_shown = false\n";
+        let config = parse_config(raw).unwrap();
+        let missing = missing(raw);
+        assert!(config.variety.safe_mode);
+        assert!(config.variety.stats_enabled);
+        assert!(!config.variety.smart_enabled);
+        assert!(!config.variety.smart_notice_shown);
+        assert!(config.variety.stats_notice_shown);
+        assert!(!missing.contains("variety.safe_mode"));
+        assert!(missing.contains("variety.smart_enabled"));
+        assert_eq!(config.variety.mode, VarietyMode::Variety);
+        assert!(missing.contains("variety.mode"));
+    }
+
+    #[test]
+    fn rejects_wallpapers_source_without_a_local_copy() {
+        let config = parse_config("[wallpapers]\nsource = \"/net/walls\"\n").unwrap();
+        assert_eq!(problems(&config)[0].0, "wallpapers.folder");
+        let config =
+            parse_config("[wallpapers]\nsource = \"/net/walls/\"\nfolder = \"/net/walls\"\n")
+                .unwrap();
+        assert_eq!(problems(&config)[0].0, "wallpapers.source");
+        let config = parse_config(
+            "[wallpapers]\nsource = \"host:/srv/walls\"\nfolder = \"/home/me/walls\"\n",
+        )
+        .unwrap();
+        assert!(problems(&config).is_empty());
+    }
+
+    #[test]
+    fn parses_docker_flavors() {
+        let config = parse_config("[docker]\nflavor = \"podman\"\n").unwrap();
+        assert_eq!(config.docker.flavor, DockerFlavor::Podman);
+        assert_eq!(DebkitConfig::default().docker.flavor, DockerFlavor::Docker);
+        assert!(parse_config("[docker]\nflavor = \"Docker\"\n").is_err());
+    }
+
+    #[test]
+    fn parses_font_scopes() {
+        let config = parse_config("[fonts]\nscope = \"system\"\n").unwrap();
+        assert_eq!(config.fonts.scope, FontScope::System);
+        assert!(parse_config("[fonts]\nscope = \"global\"\n").is_err());
+    }
+
+    #[test]
+    fn parses_zsh_plugin_managers() {
+        let config = parse_config("[zsh]\nplugin_manager = \"antidote\"\n").unwrap();
+        assert_eq!(config.zsh.plugin_manager, ZshPluginManager::Antidote);
+        assert!(parse_config("[zsh]\nplugin_manager = \"oh-my-zsh\"\n").is_err());
+    }
+
+    #[test]
+    fn parses_mail_reports() {
+        let config = parse_config("[auto_updates]\nmail_report = \"only-on-error\"\n").unwrap();
+        assert_eq!(config.auto_updates.mail_report, MailReport::OnlyOnError);
+        assert_eq!(MailReport::OnlyOnError.to_string(), "only-on-error");
+        assert!(parse_config("[auto_updates]\nmail_report = \"never\"\n").is_err());
+    }
+
+    #[test]
+    fn parses_firewall_policies() {
+        let config = parse_config("[firewall]\nincoming = \"reject\"\n").unwrap();
+        assert_eq!(config.firewall.incoming, FirewallPolicy::Reject);
+        assert_eq!(config.firewall.outgoing, FirewallPolicy::Allow);
+        assert!(parse_config("[firewall]\noutgoing = \"drop\"\n").is_err());
+    }
+
+    #[test]
+    fn parses_nfs_mount_methods() {
+        let config = parse_config("[nfs_mounts]\nmethod = \"fstab\"\n").unwrap();
+        assert_eq!(config.nfs_mounts.method, NfsMountMethod::Fstab);
+        assert!(validate_config(&config).is_ok());
+        assert!(parse_config("[nfs_mounts]\nmethod = \"nfs\"\n").is_err());
+
+        let config = parse_config("[nfs_mounts]\nmethod = \"fstab\"\nnet = true\n").unwrap();
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_variety_mode() {
+        assert!(parse_config("[variety]\nmode = \"slideshow\"\n").is_err());
+        let config = parse_config("[variety]\nmode = \"gnome_slideshow\"\n").unwrap();
+        assert!(validate_config(&config).is_ok());
+
+        let config = parse_config(
+            "[variety]\ninternet_enabled = true\nonline_sources = [\"unsplash\", \"wallhaven:nature\"]\n",
+        )
+        .unwrap();
+        assert!(validate_config(&config).is_ok());
+        assert_eq!(config.variety.quota_mb, DEFAULT_VARIETY_QUOTA_MB);
+        let config = parse_config("[variety]\nfilters = []\n").unwrap();
+        assert_eq!(problems(&config)[0].0, "variety.filters");
+        let config = parse_config("[variety]\nautostart = \"systemd\"\n").unwrap();
+        assert_eq!(config.variety.autostart, VarietyAutostart::Systemd);
+        assert!(parse_config("[variety]\nautostart = \"cron\"\n").is_err());
+        for sources in ["[\"pexels\"]", "[\"reddit\"]"] {
+            let raw = format!("[variety]\nonline_sources = {sources}\n");
+            let config = parse_config(&raw).unwrap();
+            assert_eq!(problems(&config)[0].0, "variety.online_sources");
+        }
     }
 
     #[test]
     fn parses_essentials_packages_array() {
         let raw = "[essentials]\npackages = [\"curl\", \"jq\"]\n";
-        let (config, missing) = parse_config(raw).unwrap();
+        let config = parse_config(raw).unwrap();
+        let missing = missing(raw);
         assert_eq!(config.essentials.packages, vec!["curl", "jq"]);
-        assert!(!missing.essentials_packages);
+        assert!(!missing.contains("essentials.packages"));
     }
 
     #[test]
     fn parses_multiline_arrays() {
         let raw = "[foundation]\ninstall = [\n    \"git\",\n    \"ripgrep\",\n]\n\n[nis]\nlocal_admin_groups = [\n    \"superuser\",\n]\nslaves = [\n    \"node-a.example.lan\",\n    \"node-b.example.lan\",\n]\n";
-        let (config, missing) = parse_config(raw).unwrap();
+        let config = parse_config(raw).unwrap();
+        let missing = missing(raw);
         assert_eq!(config.foundation.install, vec!["git", "ripgrep"]);
         assert_eq!(config.nis.local_admin_groups, vec!["superuser"]);
         assert_eq!(
             config.nis.slaves,
             vec!["node-a.example.lan", "node-b.example.lan"]
         );
-        assert!(!missing.foundation_install);
-        assert!(!missing.nis_local_admin_groups);
-        assert!(!missing.nis_slaves);
+        assert!(!missing.contains("foundation.install"));
+        assert!(!missing.contains("nis.local_admin_groups"));
+        assert!(!missing.contains("nis.slaves"));
     }
 
     #[test]
     fn configures_complete_host_config() {
         let home = temp_home("complete_host");
-        let path = configure_complete_for_home(&home).unwrap();
+        let path = configure_complete(&config_path_for_home(&home)).unwrap();
         let hostname = current_hostname().unwrap();
-        assert_eq!(path, host_config_path_for_home(&home, &hostname));
+        assert_eq!(
+            path,
+            host_config_path(&config_path_for_home(&home), &hostname)
+        );
         let raw = fs::read_to_string(path).unwrap();
         assert!(raw.contains(&format!("DebKit host overrides for {hostname}")));
-        assert!(raw.contains("supplements ~/.config/debkit/config.toml"));
+        assert!(raw.contains(&format!(
+            "supplements {}",
+            config_path_for_home(&home).display()
+        )));
 
         let base_raw = fs::read_to_string(config_path_for_home(&home)).unwrap();
         assert!(base_raw.contains("[wake_on_lan]"));
@@ -1228,11 +2512,11 @@ mod tests {
         )
         .unwrap();
 
-        let path = host_config_path_for_home(&home, &hostname);
+        let path = host_config_path(&config_path_for_home(&home), &hostname);
         fs::create_dir_all(path.parent().unwrap()).unwrap();
         fs::write(&path, "[wake_on_lan]\nenabled = true\n").unwrap();
 
-        let config = load_or_init_for_home(&home).unwrap();
+        let config = load_or_init(&config_path_for_home(&home)).unwrap();
         assert_eq!(config.foundation.install, vec!["git"]);
         assert_eq!(config.host.name, hostname);
         assert!(config.wake_on_lan.enabled);
@@ -1243,40 +2527,85 @@ mod tests {
     fn host_config_path_uses_sanitized_hostname() {
         let home = PathBuf::from("/tmp/home");
         assert_eq!(
-            host_config_path_for_home(&home, "bad/name"),
+            host_config_path(&config_path_for_home(&home), "bad/name"),
             PathBuf::from("/tmp/home/.config/debkit/hosts/bad_name.toml")
         );
     }
 
+    #[test]
+    fn host_config_lives_next_to_an_explicit_config_file() {
+        assert_eq!(
+            host_config_path(Path::new("/srv/debkit/lab.toml"), "box"),
+            PathBuf::from("/srv/debkit/hosts/box.toml")
+        );
+        assert_eq!(
+            host_config_path(Path::new("lab.toml"), "box"),
+            PathBuf::from("hosts/box.toml")
+        );
+    }
+
+    #[test]
+    fn parses_rust_toolchains_and_cargo_tools() {
+        let raw = "[rust]\ntoolchain = \"1.79.0\"\ncomponents = [\"rust-analyzer\", \"rust-src\"]\nextra_toolchains = [\"nightly\"]\ntargets = [\"wasm32-unknown-unknown\"]\n";
+        let config = parse_config(raw).unwrap();
+        assert_eq!(config.rust.toolchain, "1.79.0");
+        assert_eq!(config.rust.components, vec!["rust-analyzer", "rust-src"]);
+        assert_eq!(config.rust.extra_toolchains, vec!["nightly"]);
+        assert_eq!(config.rust.targets, vec!["wasm32-unknown-unknown"]);
+        assert_eq!(
+            DebkitConfig::default().rust.toolchain,
+            DEFAULT_RUST_TOOLCHAIN
+        );
+
+        let pinned = parse_config("[rust]\ntoolchain = \"1.79 .0\"\n").unwrap();
+        assert_eq!(problems(&pinned)[0].0, "rust.toolchain");
+        let checksum = parse_config("[rust]\nrustup_init_sha256 = \"abc\"\n").unwrap();
+        assert_eq!(problems(&checksum)[0].0, "rust.rustup_init_sha256");
+
+        let tools =
+            parse_config("[cargo_tools]\ninstall = [\"cargo-deb\", \"cargo-nextest@0.9.72\"]\n")
+                .unwrap();
+        assert_eq!(
+            tools.cargo_tools.install,
+            ["cargo-deb", "cargo-nextest@0.9.72"]
+        );
+        assert!(problems(&tools).is_empty());
+        let tools = parse_config("[cargo_tools]\ninstall = [\"cargo-deb@\"]\n").unwrap();
+        assert_eq!(problems(&tools)[0].0, "cargo_tools.install");
+    }
+
     #[test]
     fn parses_npm_version() {
         let raw = "[npm]\nversion = \"24.12.0\"\n";
-        let (config, missing) = parse_config(raw).unwrap();
+        let config = parse_config(raw).unwrap();
+        let missing = missing(raw);
         assert_eq!(config.npm.version, "24.12.0");
-        assert!(missing.wallpapers_folder);
-        assert!(missing.variety_interval_minutes);
-        assert!(missing.foundation_install);
-        assert!(!missing.npm_version);
+        assert!(missing.contains("wallpapers.folder"));
+        assert!(missing.contains("variety.interval_minutes"));
+        assert!(missing.contains("foundation.install"));
+        assert!(!missing.contains("npm.version"));
     }
 
     #[test]
     fn parses_sudo_nopass_config() {
         let raw = "[sudo_nopass]\nenabled = true\ngroup = \"superuser\"\nadd_current_user = false\nusers = [\"alice\", \"bob\"]\n";
-        let (config, missing) = parse_config(raw).unwrap();
+        let config = parse_config(raw).unwrap();
+        let missing = missing(raw);
         assert!(config.sudo_nopass.enabled);
         assert_eq!(config.sudo_nopass.group, "superuser");
         assert!(!config.sudo_nopass.add_current_user);
         assert_eq!(config.sudo_nopass.users, vec!["alice", "bob"]);
-        assert!(!missing.sudo_nopass_enabled);
-        assert!(!missing.sudo_nopass_group);
-        assert!(!missing.sudo_nopass_add_current_user);
-        assert!(!missing.sudo_nopass_users);
+        assert!(!missing.contains("sudo_nopass.enabled"));
+        assert!(!missing.contains("sudo_nopass.group"));
+        assert!(!missing.contains("sudo_nopass.add_current_user"));
+        assert!(!missing.contains("sudo_nopass.users"));
     }
 
     #[test]
     fn parses_nis_config() {
         let raw = "[nis]\nenabled = true\nrole = \"master\"\ndomain = \"example.internal\"\nadmin_user = \"admin\"\nlocal_admin_groups = [\"sudo\", \"wheel\"]\nmaster = \"iris.example.internal\"\nserver = \"iris.example.internal\"\nprefer_local = false\npush_to_slaves = true\nforce_refresh_maps = true\nslaves = [\"spitfire.example.internal\", \"laptop.example.internal\"]\nservers = [\"legacy1\", \"legacy2\"]\n";
-        let (config, missing) = parse_config(raw).unwrap();
+        let config = parse_config(raw).unwrap();
+        let missing = missing(raw);
         assert!(config.nis.enabled);
         assert_eq!(config.nis.role, "master");
         assert_eq!(config.nis.domain, "example.internal");
@@ -1292,38 +2621,39 @@ mod tests {
             vec!["spitfire.example.internal", "laptop.example.internal"]
         );
         assert_eq!(config.nis.servers, vec!["legacy1", "legacy2"]);
-        assert!(!missing.nis_enabled);
-        assert!(!missing.nis_role);
-        assert!(!missing.nis_domain);
-        assert!(!missing.nis_admin_user);
-        assert!(!missing.nis_local_admin_groups);
-        assert!(!missing.nis_master);
-        assert!(!missing.nis_server);
-        assert!(!missing.nis_prefer_local);
-        assert!(!missing.nis_push_to_slaves);
-        assert!(!missing.nis_force_refresh_maps);
-        assert!(!missing.nis_slaves);
-        assert!(!missing.nis_servers);
+        assert!(!missing.contains("nis.enabled"));
+        assert!(!missing.contains("nis.role"));
+        assert!(!missing.contains("nis.domain"));
+        assert!(!missing.contains("nis.admin_user"));
+        assert!(!missing.contains("nis.local_admin_groups"));
+        assert!(!missing.contains("nis.master"));
+        assert!(!missing.contains("nis.server"));
+        assert!(!missing.contains("nis.prefer_local"));
+        assert!(!missing.contains("nis.push_to_slaves"));
+        assert!(!missing.contains("nis.force_refresh_maps"));
+        assert!(!missing.contains("nis.slaves"));
+        assert!(!missing.contains("nis.servers"));
     }
 
     #[test]
     fn parses_wake_on_lan_config() {
         let raw = "[wake_on_lan]\nenabled = true\ninterfaces = [\"enp5s0\"]\nmode = \"magic\"\nbackend = \"ethtool\"\nreference_host = \"workstation\"\n";
-        let (config, missing) = parse_config(raw).unwrap();
+        let config = parse_config(raw).unwrap();
+        let missing = missing(raw);
         assert!(config.wake_on_lan.enabled);
         assert!(!config.wake_on_lan.interfaces_auto);
         assert_eq!(config.wake_on_lan.interfaces, vec!["enp5s0"]);
         assert_eq!(config.wake_on_lan.mode, "magic");
         assert_eq!(config.wake_on_lan.backend, "ethtool");
         assert_eq!(config.wake_on_lan.reference_host, "workstation");
-        assert!(!missing.wake_on_lan_enabled);
-        assert!(!missing.wake_on_lan_interfaces);
+        assert!(!missing.contains("wake_on_lan.enabled"));
+        assert!(!missing.contains("wake_on_lan.interfaces"));
     }
 
     #[test]
     fn parses_wake_on_lan_auto_interfaces() {
         let raw = "[wake_on_lan]\ninterfaces = \"auto\"\nbackend = \"network_manager\"\n";
-        let (config, _) = parse_config(raw).unwrap();
+        let config = parse_config(raw).unwrap();
         assert!(config.wake_on_lan.interfaces_auto);
         assert!(config.wake_on_lan.interfaces.is_empty());
         assert_eq!(config.wake_on_lan.backend, "network_manager");
@@ -1350,12 +2680,230 @@ mod tests {
         let raw = "[nis]\nenabled = true\nrole = \"master\"\n\n[wake_on_lan]\nenabled = true\n";
         let (updated, added) = add_nis_slave_to_raw_config(raw, "node-a.example.lan").unwrap();
         assert!(added);
-        let (config, missing) = parse_config(&updated).unwrap();
+        let config = parse_config(&updated).unwrap();
+        let missing = missing(&updated);
         assert_eq!(config.nis.slaves, vec!["node-a.example.lan"]);
-        assert!(!missing.nis_slaves);
+        assert!(!missing.contains("nis.slaves"));
         assert!(config.wake_on_lan.enabled);
     }
 
+    #[test]
+    fn set_key_preserves_comments_and_other_keys() {
+        let raw = "# my settings\n[variety]\nmode = \"variety\" # keep me\ninterval_minutes = 10\n\n[npm]\nversion = \"22\"\n";
+        let updated = set_key_in_raw_config(raw, "variety.interval_minutes", "30").unwrap();
+        assert_eq!(
+            updated,
+            "# my settings\n[variety]\nmode = \"variety\" # keep me\ninterval_minutes = 30\n\n[npm]\nversion = \"22\"\n"
+        );
+
+        let updated = set_key_in_raw_config(&updated, "foundation.install", "git, rust").unwrap();
+        assert!(updated.contains("[foundation]\ninstall = [\"git\", \"rust\"]"));
+        let updated = set_key_in_raw_config(&updated, "wallpapers.folder", "2024").unwrap();
+        assert!(updated.contains("folder = \"2024\""));
+    }
+
+    #[test]
+    fn set_key_rejects_unknown_keys_and_invalid_values() {
+        let err = set_key_in_raw_config("", "variety.interval", "5").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("unknown config key `variety.interval`")
+        );
+        let err = set_key_in_raw_config("", "variety.interval_minutes", "0").unwrap_err();
+        assert!(err.to_string().contains("must be greater than 0"));
+        let err = set_key_in_raw_config("", "variety.safe_mode", "yes").unwrap_err();
+        assert!(err.to_string().contains("expected a boolean"));
+    }
+
+    #[test]
+    fn get_key_falls_back_to_default_and_unset_restores_it() {
+        let raw = "[variety]\ninterval_minutes = 30\n";
+        let value = get_key_from_raw_config(raw, "variety.interval_minutes", "host").unwrap();
+        assert_eq!(value.as_integer(), Some(30));
+        let value = get_key_from_raw_config(raw, "wake_on_lan.reference_host", "host").unwrap();
+        assert_eq!(value.as_str(), Some("host"));
+
+        let (updated, removed) = unset_key_in_raw_config(raw, "variety.interval_minutes").unwrap();
+        assert!(removed);
+        assert_eq!(updated, "[variety]\n");
+        let (_, removed) = unset_key_in_raw_config(&updated, "variety.interval_minutes").unwrap();
+        assert!(!removed);
+    }
+
+    #[test]
+    fn check_reports_types_and_unknown_keys_with_lines() {
+        let raw =
+            "[variety]\nsafe_mode = \"yes\"\ncolour = 3\n\n[extras]\n[host]\nname = \"box\"\n";
+        let diagnostics = check_raw_config(Path::new("config.toml"), raw);
+        let lines = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                "config.toml:2: error: `variety.safe_mode` must be a boolean",
+                "config.toml:3: warning: unknown key `variety.colour`",
+                "config.toml:5: warning: unknown section `[extras]`",
+                "config.toml:7: warning: `host.name` is ignored; the hostname is detected when DebKit runs",
+            ]
+        );
+
+        let raw = "[ssh.hosts.bastion]\nhostname = \"203.0.113.7\"\n";
+        assert!(check_raw_config(Path::new("config.toml"), raw).is_empty());
+        let diagnostics = check_raw_config(Path::new("config.toml"), "[ssh]\nhosts = 3\n");
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "config.toml:2: error: `ssh.hosts` must be a table"
+        );
+
+        let diagnostics = check_raw_config(Path::new("config.toml"), "[npm]\nversion = [\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].line, Some(3));
+    }
+
+    #[test]
+    fn check_reports_semantic_problems_in_the_deciding_file() {
+        let home = temp_home("check");
+        let base_path = config_path_for_home(&home);
+        fs::create_dir_all(base_path.parent().unwrap()).unwrap();
+        fs::write(
+            &base_path,
+            "[wallpapers]\nfolder = \"walls\"\n\n[variety]\ninterval_minutes = 5\n\n[foundation]\ninstall = [\"git\", \"gti\"]\n",
+        )
+        .unwrap();
+        let host_path = host_config_path(&config_path_for_home(&home), "box");
+        fs::create_dir_all(host_path.parent().unwrap()).unwrap();
+        fs::write(&host_path, "[variety]\ninterval_minutes = 0\n").unwrap();
+
+        let diagnostics =
+            check_for_hostname(&config_path_for_home(&home), "box", |name| name == "git").unwrap();
+        let summary = diagnostics
+            .iter()
+            .map(|d| (d.severity, d.path.clone(), d.line, d.key.clone().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    Severity::Error,
+                    host_path,
+                    Some(2),
+                    "variety.interval_minutes".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    base_path.clone(),
+                    Some(2),
+                    "wallpapers.folder".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    base_path,
+                    Some(8),
+                    "foundation.install".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn keys_map_names_to_fingerprints() {
+        let raw =
+            "[keys]\nvscode = \"BC52 8686 B50D 79E3 39D3 721C EB3E 94AD BE12 29CF\"\nbad = 3\n";
+        let diagnostics = check_raw_config(Path::new("config.toml"), raw);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["config.toml:3: error: `keys.bad` must be a string"]
+        );
+
+        let config = parse_config("[keys]\nvscode = \"EB3E94ADBE1229CF\"\n").unwrap();
+        assert_eq!(
+            problems(&config),
+            vec![(
+                "keys.vscode".to_string(),
+                "`keys.vscode` must be a 40 or 64 digit hex key fingerprint, got `EB3E94ADBE1229CF`"
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_full_toml_syntax() {
+        let raw = r#"
+wallpapers = { folder = '/srv/walls' }
+variety.mode = "gnome_slideshow"
+
+[npm]
+version = """
+22.1.0"""
+
+[nis]
+slaves = [
+    "node-a", # first
+    'node-b',
+]
+
+[wake_on_lan]
+persistence = "ethtool"
+"#;
+        let config = parse_config(raw).unwrap();
+        assert_eq!(config.wallpapers.folder, "/srv/walls");
+        assert_eq!(config.variety.mode, VarietyMode::GnomeSlideshow);
+        assert_eq!(config.variety.interval_minutes, DEFAULT_INTERVAL_MINUTES);
+        assert_eq!(config.npm.version, "22.1.0");
+        assert_eq!(config.nis.slaves, vec!["node-a", "node-b"]);
+        assert_eq!(config.wake_on_lan.backend, "ethtool");
+
+        let missing = missing(raw);
+        assert!(!missing.contains("wallpapers.folder"));
+        assert!(!missing.contains("variety.mode"));
+        assert!(!missing.contains("wake_on_lan.backend"));
+        assert!(missing.contains("variety.interval_minutes"));
+    }
+
+    #[test]
+    fn backfill_keeps_comments_and_unknown_keys() {
+        let home = temp_home("backfill_comments");
+        let config_path = config_path_for_home(&home);
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        let original = "# my laptop\n[variety]\ninterval_minutes = 30 # slower\nextra = \"kept\"\n";
+        fs::write(&config_path, original).unwrap();
+
+        let config = load_or_init(&config_path_for_home(&home)).unwrap();
+        assert_eq!(config.variety.interval_minutes, 30);
+
+        let rewritten = fs::read_to_string(&config_path).unwrap();
+        assert!(rewritten.starts_with(
+            "# my laptop\n[variety]\ninterval_minutes = 30 # slower\nextra = \"kept\"\n"
+        ));
+        assert!(rewritten.contains("mode = \"variety\""));
+        assert!(rewritten.contains("[wake_on_lan]"));
+        assert!(missing(&rewritten).is_empty());
+    }
+
+    #[test]
+    fn reports_type_errors_with_location() {
+        let err = parse_config("[variety]\nsafe_mode = \"yes\"\n").unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("line 2"), "{message}");
+        assert!(message.contains("expected a boolean"), "{message}");
+
+        let err = parse_config("[wake_on_lan]\ninterfaces = \"eth0\"\n").unwrap_err();
+        assert!(err.to_string().contains("must be \"auto\" or an array"));
+    }
+
+    fn missing(raw: &str) -> BTreeSet<String> {
+        let document = parse_toml_document(raw).unwrap();
+        missing_keys(&document, &default_document(DEFAULT_HOST_NAME))
+            .into_iter()
+            .collect()
+    }
+
     fn temp_home(label: &str) -> PathBuf {
         let dir = std::env::temp_dir().join(format!(
             "debkit_test_config_{}_{}_{}",
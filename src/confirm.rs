@@ -0,0 +1,170 @@
+//! Confirmation before DebKit changes the system with root privileges.
+//!
+//! [`ask`] runs an action as a silent dry run to collect what it would do; downloads
+//! are only listed then, so nothing is fetched before the user agrees.
+//! When any of that needs root (a `sudo` command, or anything at all when DebKit
+//! itself runs as root, which covers writing into another user's home), the plan is
+//! listed and the user is asked before the action runs for real. `--yes` skips the
+//! question, as do dry runs and a stdin that is not a terminal, so scripts and remote
+//! runs behave as before. [`prompt`] asks for a value under the same rules, taking its
+//! default whenever [`ask`] would not ask.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::bail;
+
+use crate::exec::{self, Planned};
+use crate::system;
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+/// Set when the user answered yes to the last question [`ask`] or [`agree`] put.
+static ANSWERED_YES: AtomicBool = AtomicBool::new(false);
+
+pub fn set_assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+}
+
+/// Dry-runs `action` and, when it would make privileged changes, lists them and asks
+/// whether to go ahead. Returns an error if the user says no; the caller then runs
+/// the action for real. `what` names the operation, e.g. `install variety`.
+pub fn ask<T>(what: &str, action: impl FnOnce() -> anyhow::Result<T>) -> anyhow::Result<()> {
+    ANSWERED_YES.store(false, Ordering::Relaxed);
+    if exec::is_dry_run() || ASSUME_YES.load(Ordering::Relaxed) || !io::stdin().is_terminal() {
+        return Ok(());
+    }
+    let (planned, result) = exec::plan(action);
+    let root = system::is_root();
+    if !needs_confirmation(&planned, root) {
+        return Ok(());
+    }
+
+    eprint!("{}", render(what, &planned, root));
+    if let Err(err) = result {
+        eprintln!("  (the dry run stopped early: {err:#})");
+    }
+    if !read_yes("Proceed?")? {
+        bail!("cancelled; nothing was changed");
+    }
+    Ok(())
+}
+
+/// Whether privileged changes were agreed to, with `--yes` or by answering yes to the
+/// question [`ask`] put on a terminal. A terminal alone is not enough: [`ask`] does
+/// not ask when it finds nothing privileged in its dry run.
+pub fn confirmed() -> bool {
+    ASSUME_YES.load(Ordering::Relaxed) || ANSWERED_YES.load(Ordering::Relaxed)
+}
+
+/// For a change that is hard to undo from a script, such as turning on a firewall:
+/// `true` when it was [`confirmed`] already, and otherwise asks `question` on a
+/// terminal. Without a terminal the answer is no.
+pub fn agree(question: &str) -> anyhow::Result<bool> {
+    if confirmed() {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+    read_yes(question)
+}
+
+fn read_yes(question: &str) -> anyhow::Result<bool> {
+    eprint!("{question} [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    let yes = is_yes(&answer);
+    ANSWERED_YES.store(yes, Ordering::Relaxed);
+    Ok(yes)
+}
+
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+/// Asks for a value, showing `default` and returning it for an empty answer.
+pub fn prompt(question: &str, default: &str) -> anyhow::Result<String> {
+    if exec::is_dry_run() || ASSUME_YES.load(Ordering::Relaxed) || !io::stdin().is_terminal() {
+        return Ok(default.to_string());
+    }
+    if default.is_empty() {
+        eprint!("{question}: ");
+    } else {
+        eprint!("{question} [{default}]: ");
+    }
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+fn needs_confirmation(planned: &[Planned], root: bool) -> bool {
+    if root {
+        !planned.is_empty()
+    } else {
+        planned.iter().any(|change| change.sudo)
+    }
+}
+
+fn render(what: &str, planned: &[Planned], root: bool) -> String {
+    let mut out = format!("`debkit {what}` will make these changes");
+    out.push_str(if root { " as root:\n" } else { ":\n" });
+    for change in planned {
+        out.push_str(&format!("  - {}\n", change.description));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(description: &str, sudo: bool) -> Planned {
+        Planned {
+            description: description.to_string(),
+            sudo,
+        }
+    }
+
+    #[test]
+    fn asks_only_for_privileged_changes() {
+        let own_files = [change(
+            "create /home/me/.config/variety/variety.conf",
+            false,
+        )];
+        assert!(!needs_confirmation(&own_files, false));
+        assert!(needs_confirmation(&own_files, true));
+        assert!(!needs_confirmation(&[], true));
+        assert!(needs_confirmation(
+            &[change("run: sudo apt-get update", true)],
+            false
+        ));
+    }
+
+    #[test]
+    fn lists_every_planned_change() {
+        let planned = [
+            change("run: sudo apt-get install -y variety", true),
+            change("create /home/me/.config/autostart/variety.desktop", false),
+        ];
+        assert_eq!(
+            render("install variety", &planned, false),
+            "\
+`debkit install variety` will make these changes:
+  - run: sudo apt-get install -y variety
+  - create /home/me/.config/autostart/variety.desktop
+"
+        );
+    }
+
+    #[test]
+    fn only_yes_agrees() {
+        assert!(is_yes("y\n"));
+        assert!(is_yes(" YES "));
+        assert!(!is_yes("\n"));
+        assert!(!is_yes("no"));
+        assert!(!is_yes("yess"));
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::Path;
+use std::process::Stdio;
+
+use anyhow::{Context, bail};
+
+use crate::exec;
+use crate::install::user::{UserContext, command_as, target_user_context};
+use crate::output::say;
+
+pub fn dump(dir: &str) -> anyhow::Result<String> {
+    dump_as(&target_user_context()?, dir)
+}
+
+/// `dconf dump` of `dir` for `user`: a keyfile with a `[section]` per directory below
+/// it, holding the keys that are not at their defaults.
+pub fn dump_as(user: &UserContext, dir: &str) -> anyhow::Result<String> {
+    let dir = normalize_dir(dir)?;
+    let output = exec::query(
+        dconf_command(user)?
+            .args(["dump", &dir])
+            .stderr(Stdio::inherit()),
+    )
+    .context("failed to run `dconf dump`; is the `dconf-cli` package installed?")?;
+    if !output.status.success() {
+        bail!("dconf dump {dir} failed with status {}", output.status);
+    }
+
+    String::from_utf8(output.stdout).context("`dconf dump` returned non-UTF-8 output")
+}
+
+pub fn load(path: &Path, dir: &str) -> anyhow::Result<()> {
+    let dir = normalize_dir(dir)?;
+    let profile =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    validate_profile(&profile)
+        .with_context(|| format!("invalid dconf profile {}", path.display()))?;
+
+    let user = target_user_context()?;
+    load_as(&user, &dir, &profile)?;
+
+    say!(
+        "Loaded {} into dconf {dir} for {}",
+        path.display(),
+        user.name.as_deref().unwrap_or("the current user")
+    );
+    Ok(())
+}
+
+/// `dconf load` of a keyfile such as [`dump_as`] returns into `dir` for `user`. Keys
+/// the profile does not mention are left alone.
+pub fn load_as(user: &UserContext, dir: &str, profile: &str) -> anyhow::Result<()> {
+    let dir = normalize_dir(dir)?;
+    let status = exec::status_with_input(
+        dconf_command(user)?.args(["load", &dir]),
+        profile.as_bytes(),
+    )
+    .context("failed to run `dconf load`; is the `dconf-cli` package installed?")?;
+    if !status.success() {
+        bail!("dconf load {dir} failed with status {status}");
+    }
+    Ok(())
+}
+
+/// The `(section, key, value)` entries of a dconf keyfile, in order.
+pub fn entries(profile: &str) -> Vec<(String, String, String)> {
+    let mut section = None;
+    let mut entries = Vec::new();
+    for line in profile.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            section = Some(name.to_string());
+        } else if let (Some(section), Some((key, value))) = (&section, trimmed.split_once('=')) {
+            entries.push((
+                section.clone(),
+                key.trim().to_string(),
+                value.trim().to_string(),
+            ));
+        }
+    }
+    entries
+}
+
+fn dconf_command(user: &UserContext) -> anyhow::Result<std::process::Command> {
+    command_as(user, "dconf")
+}
+
+fn normalize_dir(dir: &str) -> anyhow::Result<String> {
+    let trimmed = dir.trim();
+    if !trimmed.starts_with('/') {
+        bail!("dconf directory `{trimmed}` must start with `/`");
+    }
+    if trimmed.contains("//") {
+        bail!("dconf directory `{trimmed}` must not contain empty path segments");
+    }
+    if trimmed.ends_with('/') {
+        Ok(trimmed.to_string())
+    } else {
+        Ok(format!("{trimmed}/"))
+    }
+}
+
+fn validate_profile(profile: &str) -> anyhow::Result<()> {
+    let mut in_section = false;
+    for (idx, line) in profile.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            in_section = true;
+            continue;
+        }
+        if !in_section {
+            bail!("line {}: key outside of a [section]", idx + 1);
+        }
+        if !trimmed.contains('=') {
+            bail!("line {}: expected `key=value`", idx + 1);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_dconf_dirs() {
+        assert_eq!(normalize_dir("/").unwrap(), "/");
+        assert_eq!(
+            normalize_dir("/org/gnome/desktop").unwrap(),
+            "/org/gnome/desktop/"
+        );
+        assert!(normalize_dir("org/gnome/").is_err());
+        assert!(normalize_dir("/org//gnome/").is_err());
+    }
+
+    #[test]
+    fn validates_profile_keyfile() {
+        assert!(
+            validate_profile("[org/gnome/desktop/background]\npicture-uri='file:///x.png'\n")
+                .is_ok()
+        );
+        assert!(validate_profile("picture-uri='x'\n").is_err());
+        assert!(validate_profile("[section]\nnot a key\n").is_err());
+    }
+}
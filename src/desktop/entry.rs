@@ -0,0 +1,301 @@
+use std::collections::HashSet;
+
+use anyhow::bail;
+
+const BOOLEAN_KEYS: &[&str] = &[
+    "NoDisplay",
+    "Hidden",
+    "DBusActivatable",
+    "Terminal",
+    "StartupNotify",
+    "PrefersNonDefaultGPU",
+    "SingleMainWindow",
+    "X-GNOME-Autostart-enabled",
+];
+
+const EXEC_FIELD_CODES: &[char] = &[
+    'f', 'F', 'u', 'U', 'd', 'D', 'n', 'N', 'i', 'c', 'k', 'v', 'm', '%',
+];
+
+/// Checks a desktop entry against the parts of the freedesktop.org Desktop Entry
+/// Specification that break launching: group and key syntax, required keys, boolean
+/// values, and the quoting and field-code rules of `Exec`.
+pub fn validate(content: &str) -> anyhow::Result<()> {
+    let problems = problems(content);
+    if !problems.is_empty() {
+        bail!("invalid desktop entry:\n- {}", problems.join("\n- "));
+    }
+    Ok(())
+}
+
+fn problems(content: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut group: Option<String> = None;
+    let mut groups = HashSet::new();
+    let mut keys = HashSet::new();
+    let mut main_entries: Vec<(String, String)> = Vec::new();
+
+    for (index, raw) in content.lines().enumerate() {
+        let line_no = index + 1;
+        let line = raw.trim_end();
+        if line.trim_start().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[') {
+            let Some(name) = name.strip_suffix(']') else {
+                problems.push(format!("line {line_no}: malformed group header `{line}`"));
+                continue;
+            };
+            if name.is_empty() || name.contains(['[', ']']) || name.chars().any(char::is_control) {
+                problems.push(format!("line {line_no}: invalid group name `{name}`"));
+            }
+            if group.is_none() && name != "Desktop Entry" {
+                problems.push(format!(
+                    "line {line_no}: first group must be [Desktop Entry], found [{name}]"
+                ));
+            }
+            if !groups.insert(name.to_string()) {
+                problems.push(format!("line {line_no}: duplicate group [{name}]"));
+            }
+            group = Some(name.to_string());
+            keys.clear();
+            continue;
+        }
+
+        let Some(current) = group.as_deref() else {
+            problems.push(format!("line {line_no}: key outside of any group"));
+            continue;
+        };
+
+        let Some((key, value)) = line.split_once('=') else {
+            problems.push(format!(
+                "line {line_no}: expected `key=value`, found `{line}`"
+            ));
+            continue;
+        };
+        let key = key.trim();
+        let value = value.trim_start();
+        if !valid_key(key) {
+            problems.push(format!("line {line_no}: invalid key name `{key}`"));
+            continue;
+        }
+        if !keys.insert(key.to_string()) {
+            problems.push(format!(
+                "line {line_no}: duplicate key `{key}` in [{current}]"
+            ));
+        }
+        if current == "Desktop Entry" {
+            main_entries.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    if group.is_none() {
+        problems.push("missing [Desktop Entry] group".to_string());
+        return problems;
+    }
+
+    let lookup = |wanted: &str| {
+        main_entries
+            .iter()
+            .find(|(key, _)| key == wanted)
+            .map(|(_, value)| value.as_str())
+    };
+
+    for (key, value) in &main_entries {
+        if BOOLEAN_KEYS.contains(&key.as_str()) && value != "true" && value != "false" {
+            problems.push(format!("{key} must be `true` or `false`, found `{value}`"));
+        }
+    }
+
+    match lookup("Type") {
+        None => problems.push("missing required key `Type`".to_string()),
+        Some("Application" | "Link" | "Directory") => {}
+        Some(other) => problems.push(format!("unknown Type `{other}`")),
+    }
+    if lookup("Name").is_none_or(str::is_empty) {
+        problems.push("missing required key `Name`".to_string());
+    }
+    if lookup("Type") == Some("Link") && lookup("URL").is_none() {
+        problems.push("Type=Link requires `URL`".to_string());
+    }
+    if lookup("Type") == Some("Application") && lookup("DBusActivatable") != Some("true") {
+        match lookup("Exec") {
+            None | Some("") => problems.push("Type=Application requires `Exec`".to_string()),
+            Some(exec) => {
+                if let Err(err) = split_exec(&unescape_string(exec)) {
+                    problems.push(format!("Exec: {err}"));
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+fn valid_key(key: &str) -> bool {
+    let (name, locale) = match key.split_once('[') {
+        Some((name, rest)) => match rest.strip_suffix(']') {
+            Some(locale) => (name, Some(locale)),
+            None => return false,
+        },
+        None => (key, None),
+    };
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && locale.is_none_or(|locale| {
+            !locale.is_empty()
+                && locale
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_@.-".contains(c))
+        })
+}
+
+/// Applies the general string escapes (`\s`, `\n`, `\t`, `\r`, `\\`) that precede
+/// `Exec` quoting; unknown escapes are kept as-is, matching GLib's lenient reader.
+fn unescape_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Splits an `Exec` value into arguments, enforcing the spec's quoting rules:
+/// reserved characters must sit inside double quotes, and inside quotes only
+/// `"`, `` ` ``, `$` and `\` may be (and must be) backslash-escaped.
+fn split_exec(exec: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        None => return Err("unterminated double quote".to_string()),
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '`' | '$' | '\\')) => current.push(escaped),
+                            Some(other) => {
+                                return Err(format!(
+                                    "invalid escape `\\{other}` inside quoted argument"
+                                ));
+                            }
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(quoted @ ('`' | '$')) => {
+                            return Err(format!(
+                                "`{quoted}` must be escaped inside quoted argument"
+                            ));
+                        }
+                        Some(other) => current.push(other),
+                    }
+                }
+            }
+            '%' => {
+                in_arg = true;
+                match chars.next() {
+                    Some(code) if EXEC_FIELD_CODES.contains(&code) => {
+                        current.push('%');
+                        current.push(code);
+                    }
+                    Some(code) => return Err(format!("unknown field code `%{code}`")),
+                    None => return Err("dangling `%` at end of line".to_string()),
+                }
+            }
+            '\'' | '\\' | '>' | '<' | '~' | '|' | '&' | ';' | '$' | '*' | '?' | '#' | '(' | ')'
+            | '`' => {
+                return Err(format!("reserved character `{c}` must be quoted"));
+            }
+            other => {
+                in_arg = true;
+                current.push(other);
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+
+    match args.first() {
+        None => Err("empty command".to_string()),
+        Some(program) if program.contains('=') => {
+            Err(format!("program `{program}` must not contain `=`"))
+        }
+        Some(_) => Ok(args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_entry() {
+        let entry = r#"[Desktop Entry]
+Type=Application
+Name=Variety
+Name[de]=Variety
+Exec=variety --profile "\\$HOME/a b" %U
+Hidden=false
+X-GNOME-Autostart-enabled=true
+
+[Desktop Action Next]
+Name=Next
+Exec=variety --next
+"#;
+        assert!(problems(entry).is_empty(), "{:?}", problems(entry));
+    }
+
+    #[test]
+    fn rejects_malformed_exec_lines() {
+        for exec in [
+            "variety \"--profile",
+            "variety --profile ~/pics",
+            "variety %z",
+            "variety \"$HOME\"",
+            "variety \"\\\\a\"",
+            "FOO=1 variety",
+        ] {
+            let entry = format!("[Desktop Entry]\nType=Application\nName=Variety\nExec={exec}\n");
+            assert!(validate(&entry).is_err(), "accepted Exec={exec}");
+        }
+    }
+
+    #[test]
+    fn rejects_structural_problems() {
+        assert!(validate("Type=Application\n").is_err());
+        assert!(validate("[Desktop Entry]\nName=Variety\nExec=variety\n").is_err());
+        assert!(
+            validate("[Desktop Entry]\nType=Application\nName=V\nExec=v\nHidden=yes\n").is_err()
+        );
+        assert!(validate("[Desktop Entry]\nType=Application\nName=V\nName=W\nExec=v\n").is_err());
+        assert!(validate("[Other]\n[Desktop Entry]\nType=Application\nName=V\nExec=v\n").is_err());
+    }
+}
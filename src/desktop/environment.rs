@@ -0,0 +1,166 @@
+//! Which desktop environment the target user runs, and where it keeps its wallpaper
+//! settings.
+//!
+//! `XDG_CURRENT_DESKTOP` names it, such as `ubuntu:GNOME`, `X-Cinnamon` or `KDE`, with
+//! `XDG_SESSION_DESKTOP` and `DESKTOP_SESSION` as fallbacks. sudo drops all three, so
+//! without them the `Desktop` of the user's graphical login session is asked from
+//! `loginctl`.
+
+use std::env;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::process::Command;
+
+use serde::Serialize;
+
+use crate::exec;
+use crate::install::user::UserContext;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    /// GNOME and the desktops built on its settings, such as Unity and Budgie.
+    Gnome,
+    Cinnamon,
+    Mate,
+    Xfce,
+    Kde,
+    Unknown,
+}
+
+/// A gsettings schema holding the desktop background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackgroundSchema {
+    pub schema: &'static str,
+    /// The key with the picture, or a background slideshow XML.
+    pub picture_key: &'static str,
+    /// Whether `picture_key` takes a `file://` URI rather than a path.
+    pub picture_is_uri: bool,
+}
+
+impl Environment {
+    /// Reads a `:`-separated desktop list such as `ubuntu:GNOME`; the first name it
+    /// recognizes wins.
+    pub fn parse(names: &str) -> Self {
+        names
+            .split(':')
+            .map(|name| {
+                let name = name.trim().to_ascii_lowercase();
+                match name.strip_prefix("x-").unwrap_or(&name) {
+                    "gnome" | "gnome-classic" | "gnome-flashback" | "unity" | "budgie" => {
+                        Self::Gnome
+                    }
+                    "cinnamon" => Self::Cinnamon,
+                    "mate" => Self::Mate,
+                    "xfce" | "xubuntu" => Self::Xfce,
+                    "kde" | "plasma" | "plasmawayland" | "plasmax11" => Self::Kde,
+                    _ => Self::Unknown,
+                }
+            })
+            .find(|desktop| *desktop != Self::Unknown)
+            .unwrap_or(Self::Unknown)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gnome => "GNOME",
+            Self::Cinnamon => "Cinnamon",
+            Self::Mate => "MATE",
+            Self::Xfce => "Xfce",
+            Self::Kde => "KDE Plasma",
+            Self::Unknown => "an unknown desktop",
+        }
+    }
+
+    /// The schema with the background, for the desktops that keep it in gsettings.
+    pub fn background_schema(self) -> Option<BackgroundSchema> {
+        let (schema, picture_key, picture_is_uri) = match self {
+            Self::Gnome => ("org.gnome.desktop.background", "picture-uri", true),
+            Self::Cinnamon => ("org.cinnamon.desktop.background", "picture-uri", true),
+            Self::Mate => ("org.mate.background", "picture-filename", false),
+            Self::Xfce | Self::Kde | Self::Unknown => return None,
+        };
+        Some(BackgroundSchema {
+            schema,
+            picture_key,
+            picture_is_uri,
+        })
+    }
+}
+
+/// The desktop `user` runs, from the environment or else their login session.
+pub fn detect(user: &UserContext) -> Environment {
+    let from_env = [
+        "XDG_CURRENT_DESKTOP",
+        "XDG_SESSION_DESKTOP",
+        "DESKTOP_SESSION",
+    ]
+    .into_iter()
+    .filter_map(|var| env::var(var).ok())
+    .map(|names| Environment::parse(&names))
+    .find(|desktop| *desktop != Environment::Unknown);
+    from_env
+        .or_else(|| session_desktop(user))
+        .unwrap_or(Environment::Unknown)
+}
+
+fn session_desktop(user: &UserContext) -> Option<Environment> {
+    let uid = user.uid?.to_string();
+    let session = loginctl(&["show-user", &uid, "--property=Display", "--value"])?;
+    let desktop = loginctl(&["show-session", &session, "--property=Desktop", "--value"])?;
+    Some(Environment::parse(&desktop)).filter(|desktop| *desktop != Environment::Unknown)
+}
+
+/// The `file://` URI for `path`, for keys that take one. Bytes other than letters,
+/// digits, `/` and `-._~` are percent-encoded, so spaces and `#` survive.
+pub fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            uri.push(char::from(byte));
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+fn loginctl(args: &[&str]) -> Option<String> {
+    let output = exec::query(Command::new("loginctl").args(args)).ok()?;
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !value.is_empty()).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_desktop_names() {
+        assert_eq!(Environment::parse("ubuntu:GNOME"), Environment::Gnome);
+        assert_eq!(Environment::parse("X-Cinnamon"), Environment::Cinnamon);
+        assert_eq!(Environment::parse("MATE"), Environment::Mate);
+        assert_eq!(Environment::parse("XFCE"), Environment::Xfce);
+        assert_eq!(Environment::parse("KDE"), Environment::Kde);
+        assert_eq!(Environment::parse("plasmawayland"), Environment::Kde);
+        assert_eq!(Environment::parse("i3"), Environment::Unknown);
+        assert_eq!(Environment::parse(""), Environment::Unknown);
+
+        let mate = Environment::Mate.background_schema().unwrap();
+        assert_eq!(mate.picture_key, "picture-filename");
+        assert!(!mate.picture_is_uri);
+        assert!(Environment::Xfce.background_schema().is_none());
+    }
+
+    #[test]
+    fn percent_encodes_file_uris() {
+        assert_eq!(
+            file_uri(Path::new("/home/me/Pictures/slideshow.xml")),
+            "file:///home/me/Pictures/slideshow.xml"
+        );
+        assert_eq!(
+            file_uri(Path::new("/srv/My Walls #2/café")),
+            "file:///srv/My%20Walls%20%232/caf%C3%A9"
+        );
+    }
+}
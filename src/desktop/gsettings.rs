@@ -0,0 +1,143 @@
+//! Reading and writing gsettings keys as the target user.
+//!
+//! Values are GVariant text, as `gsettings get` prints them and `gsettings set` and
+//! dconf profiles take them: `'prefer-dark'`, `true`, `uint32 300` or `['<Super>q']`.
+//! A schema may carry a path for relocatable schemas, as in
+//! `org.gnome.settings-daemon.plugins.media-keys.custom-keybinding:/org/gnome/settings-daemon/plugins/media-keys/custom-keybindings/custom0/`.
+
+use anyhow::{Context, bail};
+
+use crate::exec;
+use crate::install::user::{UserContext, command_as};
+use crate::system;
+
+/// GVariant type annotations `gsettings get` puts before numbers it would otherwise
+/// read as `int32` or `double`.
+const TYPE_ANNOTATIONS: &[&str] = &[
+    "byte", "int16", "uint16", "int32", "uint32", "int64", "uint64", "handle", "double",
+];
+
+pub fn available() -> bool {
+    system::command_available("gsettings")
+}
+
+/// The value of `key`, or `None` when the schema is not installed or has no such key.
+pub fn get(user: &UserContext, schema: &str, key: &str) -> anyhow::Result<Option<String>> {
+    let output = exec::query(command_as(user, "gsettings")?.args(["get", schema, key]))
+        .context("failed to run `gsettings`; is the desktop installed?")?;
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string()))
+}
+
+/// Whether the schema is installed and has `key`.
+pub fn has_key(user: &UserContext, schema: &str, key: &str) -> bool {
+    command_as(user, "gsettings")
+        .ok()
+        .and_then(|mut command| exec::query(command.args(["range", schema, key])).ok())
+        .is_some_and(|output| output.status.success())
+}
+
+/// Whether `key` exists and is not locked down by a system-wide dconf profile.
+pub fn writable(user: &UserContext, schema: &str, key: &str) -> bool {
+    command_as(user, "gsettings")
+        .ok()
+        .and_then(|mut command| exec::query(command.args(["writable", schema, key])).ok())
+        .is_some_and(|output| {
+            output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "true"
+        })
+}
+
+pub fn set(user: &UserContext, schema: &str, key: &str, value: &str) -> anyhow::Result<()> {
+    let status = exec::status(command_as(user, "gsettings")?.args(["set", schema, key, value]))
+        .context("failed to run `gsettings`; is the desktop installed?")?;
+    if !status.success() {
+        bail!("gsettings set {schema} {key} failed with status {status}");
+    }
+    Ok(())
+}
+
+/// Puts `key` back to its default.
+pub fn reset(user: &UserContext, schema: &str, key: &str) -> anyhow::Result<()> {
+    let status = exec::status(command_as(user, "gsettings")?.args(["reset", schema, key]))
+        .context("failed to run `gsettings`; is the desktop installed?")?;
+    if !status.success() {
+        bail!("gsettings reset {schema} {key} failed with status {status}");
+    }
+    Ok(())
+}
+
+/// Whether two GVariant texts hold the same value, ignoring the type annotation
+/// `gsettings get` adds and the spacing and quoting it normalizes, so a configured
+/// `300` matches `uint32 300` and `"dark"` matches `'dark'`.
+pub fn same_value(a: &str, b: &str) -> bool {
+    normalize(a) == normalize(b)
+}
+
+fn normalize(value: &str) -> String {
+    let value = value.trim();
+    let value = value
+        .split_once(' ')
+        .filter(|(annotation, _)| {
+            TYPE_ANNOTATIONS.contains(annotation) || annotation.starts_with('@')
+        })
+        .map_or(value, |(_, rest)| rest.trim());
+    let mut out = String::new();
+    let mut quote = None;
+    for ch in value.chars() {
+        match quote {
+            Some(open) if ch == open => {
+                quote = None;
+                out.push('\'');
+            }
+            Some(_) => out.push(ch),
+            None if ch == '\'' || ch == '"' => {
+                quote = Some(ch);
+                out.push('\'');
+            }
+            None if ch.is_whitespace() => {}
+            None => out.push(ch),
+        }
+    }
+    out
+}
+
+/// The dconf directory a schema keeps its keys in: the path after `:` for a
+/// relocatable schema, and otherwise the schema id with `.` as `/`, which is where
+/// GNOME's own schemas live.
+pub fn dconf_dir(schema: &str) -> String {
+    match schema.split_once(':') {
+        Some((_, path)) => path.to_string(),
+        None => format!("/{}/", schema.replace('.', "/")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_gvariant_text() {
+        assert!(same_value("uint32 300", "300"));
+        assert!(same_value("'prefer-dark'", "\"prefer-dark\""));
+        assert!(same_value(
+            "['<Super>q', '<Alt>F4']",
+            "['<Super>q','<Alt>F4']"
+        ));
+        assert!(same_value("@as []", "[]"));
+        assert!(!same_value("'a b'", "'ab'"));
+        assert!(!same_value("true", "false"));
+
+        assert_eq!(
+            dconf_dir("org.gnome.desktop.interface"),
+            "/org/gnome/desktop/interface/"
+        );
+        assert_eq!(
+            dconf_dir(
+                "org.gnome.Terminal.Legacy.Profile:/org/gnome/terminal/legacy/profiles:/:b1/"
+            ),
+            "/org/gnome/terminal/legacy/profiles:/:b1/"
+        );
+    }
+}
@@ -0,0 +1,5 @@
+pub mod dconf;
+pub mod entry;
+pub mod environment;
+pub mod gsettings;
+pub mod slideshow;
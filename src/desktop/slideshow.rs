@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Serialize;
+
+/// The file extensions both Variety and the GNOME slideshow show, compared case-insensitively.
+pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "bmp", "gif", "tif", "tiff"];
+
+/// Seconds GNOME spends cross-fading between two slides.
+pub const TRANSITION_SECONDS: u64 = 5;
+
+/// How many levels of subfolders the slideshow and [`inventory`] look into.
+pub const INVENTORY_DEPTH: usize = 4;
+
+/// What a wallpapers folder holds.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Inventory {
+    pub images: usize,
+    pub image_bytes: u64,
+    /// How many files of each other extension there are, such as `heic` or `txt`;
+    /// files without one count under `""`.
+    pub unsupported: BTreeMap<String, usize>,
+}
+
+impl Inventory {
+    pub fn unsupported_files(&self) -> usize {
+        self.unsupported.values().sum()
+    }
+}
+
+/// Counts the images in `folder` and its subfolders, down to `depth` levels below
+/// it, skipping hidden files and folders.
+pub fn inventory(folder: &Path, depth: usize) -> anyhow::Result<Inventory> {
+    let mut inventory = Inventory::default();
+    walk(folder, depth, |path, metadata| {
+        if is_image(&path) {
+            inventory.images += 1;
+            inventory.image_bytes += metadata.len();
+        } else if metadata.is_file() {
+            let extension = path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+                .unwrap_or_default();
+            *inventory.unsupported.entry(extension).or_default() += 1;
+        }
+    })?;
+    Ok(inventory)
+}
+
+/// Hands every entry below `folder` that is not a folder to `visit`, down to `depth`
+/// levels of subfolders, skipping hidden files and folders as Variety does.
+fn walk(
+    folder: &Path,
+    depth: usize,
+    mut visit: impl FnMut(PathBuf, fs::Metadata),
+) -> anyhow::Result<()> {
+    let mut pending = vec![(folder.to_path_buf(), 0)];
+    while let Some((dir, level)) = pending.pop() {
+        let entries =
+            fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                if level < depth {
+                    pending.push((path, level + 1));
+                }
+            } else {
+                visit(path, metadata);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Lists the images in `folder` and its subfolders, down to `depth` levels as
+/// [`inventory`] counts them, sorted so the slideshow order is stable.
+pub fn collect_images(folder: &Path, depth: usize) -> anyhow::Result<Vec<PathBuf>> {
+    let mut images = Vec::new();
+    walk(folder, depth, |path, metadata| {
+        if is_image(&path) && metadata.is_file() {
+            images.push(path);
+        }
+    })
+    .with_context(|| format!("failed to read wallpapers folder {}", folder.display()))?;
+    images.sort();
+    Ok(images)
+}
+
+/// Renders a GNOME background slideshow that shows each image for `interval_seconds`
+/// and then cross-fades to the next one, looping back to the first image at the end.
+pub fn render(images: &[PathBuf], interval_seconds: u64) -> String {
+    let mut out = String::from("<background>\n");
+    out.push_str("  <starttime>\n");
+    out.push_str("    <year>2000</year>\n    <month>1</month>\n    <day>1</day>\n");
+    out.push_str("    <hour>0</hour>\n    <minute>0</minute>\n    <second>0</second>\n");
+    out.push_str("  </starttime>\n");
+
+    let still = interval_seconds.saturating_sub(TRANSITION_SECONDS).max(1);
+    for (index, image) in images.iter().enumerate() {
+        let current = xml_escape(&image.to_string_lossy());
+        out.push_str(&format!(
+            "  <static>\n    <duration>{still}.0</duration>\n    <file>{current}</file>\n  </static>\n"
+        ));
+        if images.len() > 1 {
+            let next = xml_escape(&images[(index + 1) % images.len()].to_string_lossy());
+            out.push_str(&format!(
+                "  <transition type=\"overlay\">\n    <duration>{TRANSITION_SECONDS}.0</duration>\n    <from>{current}</from>\n    <to>{next}</to>\n  </transition>\n"
+            ));
+        }
+    }
+
+    out.push_str("</background>\n");
+    out
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_looping_slideshow() {
+        let images = vec![PathBuf::from("/pics/a.jpg"), PathBuf::from("/pics/b&c.png")];
+        let xml = render(&images, 600);
+        assert_eq!(xml.matches("<static>").count(), 2);
+        assert_eq!(xml.matches("<transition").count(), 2);
+        assert!(xml.contains("<duration>595.0</duration>"));
+        assert!(xml.contains("<from>/pics/b&amp;c.png</from>\n    <to>/pics/a.jpg</to>"));
+    }
+
+    #[test]
+    fn inventories_images_down_to_the_depth_limit() {
+        let root = std::env::temp_dir().join(format!("debkit-inventory-{}", std::process::id()));
+        let deep = root.join("a").join("b");
+        fs::create_dir_all(&deep).unwrap();
+        fs::create_dir_all(root.join(".thumbnails")).unwrap();
+        fs::write(root.join("one.JPG"), [0; 10]).unwrap();
+        fs::write(root.join("a").join("two.png"), [0; 5]).unwrap();
+        fs::write(root.join("a").join("three.heic"), []).unwrap();
+        fs::write(deep.join("four.jpg"), []).unwrap();
+        fs::write(root.join(".thumbnails").join("hidden.png"), []).unwrap();
+
+        let inventory = inventory(&root, 1).unwrap();
+        assert_eq!(inventory.images, 2);
+        assert_eq!(inventory.image_bytes, 15);
+        assert_eq!(
+            inventory.unsupported,
+            BTreeMap::from([("heic".to_string(), 1)])
+        );
+        assert_eq!(super::inventory(&root, 2).unwrap().images, 3);
+        assert_eq!(
+            collect_images(&root, 1).unwrap(),
+            [root.join("a").join("two.png"), root.join("one.JPG")]
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn single_image_has_no_transition() {
+        let xml = render(&[PathBuf::from("/pics/a.jpg")], 60);
+        assert_eq!(xml.matches("<static>").count(), 1);
+        assert!(!xml.contains("<transition"));
+    }
+}
@@ -0,0 +1,53 @@
+//! Downloads over HTTPS for DebKit's own use, such as repository signing keys.
+//! Fetching into memory is read-only, so it also happens in dry runs; [`save`] writes
+//! to disk and only announces the download in a dry run.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+
+use crate::{exec, network, offline};
+
+pub fn fetch(url: &str) -> anyhow::Result<Vec<u8>> {
+    if !url.starts_with("https://") {
+        bail!("refusing to download `{url}`: only https:// URLs are allowed");
+    }
+    if offline::is_enabled() {
+        bail!("cannot download {url} with --offline");
+    }
+    let output = exec::query(network::apply(&mut Command::new("curl")).args([
+        "-fsSL",
+        "--proto",
+        "=https",
+        "--tlsv1.2",
+        url,
+    ]))
+    .context("failed to launch curl")?;
+    if !output.status.success() {
+        bail!(
+            "downloading {url} failed with status {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Downloads `url` into `path`, creating its directory. A dry run announces the
+/// download instead, leaving `path` missing, so callers skip checking or unpacking it.
+pub fn save(url: &str, path: &Path) -> anyhow::Result<()> {
+    if exec::is_dry_run() {
+        exec::announce_change(
+            &format!("would download {url} to {}", path.display()),
+            false,
+        );
+        return Ok(());
+    }
+    let contents = fetch(url)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))
+}
@@ -0,0 +1,697 @@
+//! Execution layer for everything that changes the system.
+//!
+//! Targets route mutating commands and file changes through these wrappers instead
+//! of calling `Command::status` or `std::fs` directly. With `--dry-run` the wrappers
+//! print what would happen (including a diff for file contents) and report success
+//! without touching anything. Read-only probes such as `dpkg-query` go through
+//! [`probe`] and [`query`], which run even in a dry run so it still sees the real
+//! system state. Every process is started by the [`crate::runner`]. With `--diff`, file
+//! writes print the same diff before they are made, and the audit log keeps it.
+
+use std::cell::Cell;
+use std::ffi::OsStr;
+use std::fs::{self, OpenOptions, Permissions};
+use std::io::{self, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::audit;
+use crate::logging;
+use crate::output::{self, say};
+use crate::progress::{self, Step};
+use crate::runner::{self, Stream};
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+static SHOW_DIFF: AtomicBool = AtomicBool::new(false);
+static CHANGES: AtomicUsize = AtomicUsize::new(0);
+static CREATED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static PLAN: Mutex<Option<Vec<Planned>>> = Mutex::new(None);
+
+thread_local! {
+    static THREAD_DRY_RUN: Cell<bool> = const { Cell::new(false) };
+}
+
+const DIFF_CONTEXT: usize = 3;
+
+pub fn set_dry_run(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed) || THREAD_DRY_RUN.with(Cell::get)
+}
+
+/// Runs `action` as a dry run on this thread only, leaving tests that run alongside
+/// it unaffected.
+#[cfg(test)]
+pub(crate) fn dry_run<T>(action: impl FnOnce() -> T) -> T {
+    let previous = THREAD_DRY_RUN.with(|dry_run| dry_run.replace(true));
+    let result = action();
+    THREAD_DRY_RUN.with(|dry_run| dry_run.set(previous));
+    result
+}
+
+/// Prints the diff of every file write before it is made, as dry runs do.
+pub fn set_show_diff(enabled: bool) {
+    SHOW_DIFF.store(enabled, Ordering::Relaxed);
+}
+
+/// Number of commands and file changes made (or, in a dry run, announced) so far.
+/// Writes that would leave a file unchanged are not counted.
+pub fn changes() -> usize {
+    CHANGES.load(Ordering::Relaxed)
+}
+
+fn record() {
+    CHANGES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Files, directories and links created (rather than modified) so far, in order.
+/// Dry runs create nothing.
+pub fn created() -> Vec<PathBuf> {
+    CREATED.lock().unwrap().clone()
+}
+
+fn record_created(path: &Path, existed: bool, result: io::Result<()>) -> io::Result<()> {
+    if result.is_ok() && !existed {
+        CREATED.lock().unwrap().push(path.to_path_buf());
+    }
+    result
+}
+
+/// A change collected by [`plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Planned {
+    pub description: String,
+    /// Whether the change is a command run through `sudo`.
+    pub sudo: bool,
+}
+
+/// Runs `action` as a silent dry run and returns the changes it would make along with
+/// its result. Nothing is printed through [`say!`] meanwhile, and the change counter is
+/// left as it was.
+pub fn plan<T>(action: impl FnOnce() -> T) -> (Vec<Planned>, T) {
+    let dry_run = is_dry_run();
+    let quiet = output::is_quiet();
+    let changes = changes();
+    set_dry_run(true);
+    output::set_quiet(true);
+    *PLAN.lock().unwrap() = Some(Vec::new());
+    let result = action();
+    let planned = PLAN.lock().unwrap().take().unwrap_or_default();
+    CHANGES.store(changes, Ordering::Relaxed);
+    output::set_quiet(quiet);
+    set_dry_run(dry_run);
+    (planned, result)
+}
+
+/// Logs a command that is about to run.
+fn log_run(command: &Command) {
+    tracing::info!("run: {}", describe(command));
+}
+
+/// Logs a finished command to the audit log.
+fn audit_command(command: &Command, status: io::Result<ExitStatus>) -> io::Result<ExitStatus> {
+    match &status {
+        Ok(status) if !status.success() => {
+            tracing::debug!("{} exited with {status}", describe(command))
+        }
+        Ok(_) => {}
+        Err(err) => tracing::debug!("{} failed to start: {err}", describe(command)),
+    }
+    audit::command(command, status.as_ref().ok().and_then(ExitStatus::code));
+    status
+}
+
+/// Applies a file change and logs it to the audit log with the hashes of the
+/// contents before and after.
+fn audit_file<T>(op: &str, path: &Path, change: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+    audit_change(op, path, None, change)
+}
+
+/// Applies a write that leaves `path` holding `contents`. Its diff is printed under
+/// `--diff` and kept in the audit log along with the hashes.
+fn audit_write<T>(
+    op: &str,
+    path: &Path,
+    contents: &[u8],
+    change: impl FnOnce() -> io::Result<T>,
+) -> io::Result<T> {
+    if !audit::is_enabled() && !SHOW_DIFF.load(Ordering::Relaxed) {
+        return audit_change(op, path, None, change);
+    }
+    let diff = text_diff(path, contents);
+    if SHOW_DIFF.load(Ordering::Relaxed) {
+        let verb = if path.exists() {
+            "modifying"
+        } else {
+            "creating"
+        };
+        say!("{verb} {}:", path.display());
+        if let Some(diff) = &diff {
+            say!("{}", diff.trim_end_matches('\n'));
+        }
+    }
+    audit_change(op, path, diff, change)
+}
+
+fn audit_change<T>(
+    op: &str,
+    path: &Path,
+    diff: Option<String>,
+    change: impl FnOnce() -> io::Result<T>,
+) -> io::Result<T> {
+    tracing::info!("{op} {}", path.display());
+    if !audit::is_enabled() {
+        return change();
+    }
+    let before = audit::hash_file(path);
+    let result = change();
+    if result.is_ok() {
+        audit::file(op, path, before, diff);
+    }
+    result
+}
+
+/// Runs a mutating command with inherited stdio.
+pub fn status(command: &mut Command) -> io::Result<ExitStatus> {
+    record();
+    if is_dry_run() {
+        announce_command(command, "");
+        return Ok(ExitStatus::from_raw(0));
+    }
+    log_run(command);
+    let status = runner::current().status(keep_stdout_clean(command));
+    audit_command(command, status)
+}
+
+/// Runs a read-only command with inherited stdio, even in a dry run.
+pub fn probe(command: &mut Command) -> io::Result<ExitStatus> {
+    runner::current().status(keep_stdout_clean(command))
+}
+
+/// Runs a read-only command and captures its output, even in a dry run.
+pub fn query(command: &mut Command) -> io::Result<Output> {
+    runner::current().output(command, &[])
+}
+
+/// Like [`query`], with `input` on the command's stdin.
+pub fn query_with_input(command: &mut Command, input: &[u8]) -> io::Result<Output> {
+    runner::current().output(command, input)
+}
+
+/// Runs a long mutating command as the step `name`, with a progress indicator; see
+/// [`crate::progress`]. Its output goes to the log file. On a terminal it is otherwise
+/// only shown next to the indicator, and printed in full if the command fails;
+/// elsewhere it is passed through line by line, except under `--quiet`, where it is
+/// likewise kept back unless the command fails.
+pub fn status_step(command: &mut Command, name: &str) -> io::Result<ExitStatus> {
+    record();
+    if is_dry_run() {
+        announce_command(command, "");
+        return Ok(ExitStatus::from_raw(0));
+    }
+    if progress::is_animated() {
+        authenticate_sudo(command);
+    }
+    log_run(command);
+    let step = Step::start(name);
+    let animated = step.is_animated();
+    let echo = !animated && !output::is_quiet();
+    let output = capture_for_step(command, &step, echo);
+    let ok = output.as_ref().is_ok_and(|output| output.status.success());
+    step.finish(ok);
+    let status = output.map(|output| {
+        if !ok && !echo {
+            io::stderr().write_all(&output.stdout).ok();
+            io::stderr().write_all(&output.stderr).ok();
+        }
+        output.status
+    });
+    audit_command(command, status)
+}
+
+/// Runs a long mutating command as the step `name` and captures its output.
+pub fn output_step(command: &mut Command, name: &str) -> io::Result<Output> {
+    record();
+    if is_dry_run() {
+        announce_command(command, "");
+        return Ok(Output {
+            status: ExitStatus::from_raw(0),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        });
+    }
+    log_run(command);
+    let step = Step::start(name);
+    let output = capture_for_step(command, &step, false);
+    step.finish(output.as_ref().is_ok_and(|output| output.status.success()));
+    audit::command(
+        command,
+        output.as_ref().ok().and_then(|output| output.status.code()),
+    );
+    output
+}
+
+/// Runs `command` with its stdout and stderr captured, logging each line and showing
+/// it on `step` as it arrives. With `echo` the lines are printed as well.
+fn capture_for_step(command: &mut Command, step: &Step, echo: bool) -> io::Result<Output> {
+    runner::current().stream(command, &|stream, line| {
+        tracing::debug!(target: logging::FILE_ONLY, "{line}");
+        step.update(line);
+        if !echo {
+            return;
+        }
+        match stream {
+            Stream::Stdout => say!("{line}"),
+            Stream::Stderr => eprintln!("{line}"),
+        }
+    })
+}
+
+/// Lets `sudo` ask for a password up front, before a spinner would draw over its
+/// prompt.
+fn authenticate_sudo(command: &Command) {
+    if command.get_program() == "sudo" {
+        let _ = runner::current().status(Command::new("sudo").arg("-v"));
+    }
+}
+
+/// Runs a mutating command with `input` on its stdin.
+pub fn status_with_input(command: &mut Command, input: &[u8]) -> io::Result<ExitStatus> {
+    record();
+    if is_dry_run() {
+        announce_command(command, &format!(" (with {} bytes on stdin)", input.len()));
+        return Ok(ExitStatus::from_raw(0));
+    }
+    log_run(command);
+    let status = runner::current().pipe(keep_stdout_clean(command), input);
+    audit_command(command, status)
+}
+
+/// Runs a mutating command, printing each line of its stdout and stderr with
+/// `prefix` in front so output from several commands at once stays readable.
+pub fn status_prefixed(command: &mut Command, prefix: &str) -> io::Result<ExitStatus> {
+    record();
+    if is_dry_run() {
+        announce_command(command, "");
+        return Ok(ExitStatus::from_raw(0));
+    }
+    log_run(command);
+    let status = stream_prefixed(command, prefix);
+    audit_command(command, status)
+}
+
+fn stream_prefixed(command: &mut Command, prefix: &str) -> io::Result<ExitStatus> {
+    let output = runner::current().stream(command, &|stream, line| match stream {
+        Stream::Stdout => say!("{prefix}{line}"),
+        Stream::Stderr => eprintln!("{prefix}{line}"),
+    })?;
+    Ok(output.status)
+}
+
+/// Runs a command such as `sudo tee <path>` that writes `contents` into `path`.
+/// Dry runs show the diff against the current file instead of the command line.
+pub fn write_via(command: &mut Command, path: &Path, contents: &[u8]) -> io::Result<ExitStatus> {
+    if unchanged(path, contents) {
+        return Ok(ExitStatus::from_raw(0));
+    }
+    record();
+    if is_dry_run() {
+        preview_write(path, contents, command.get_program() == "sudo");
+        return Ok(ExitStatus::from_raw(0));
+    }
+    let existed = path.exists();
+    let status = audit_write("write", path, contents, || {
+        let status = runner::current().pipe(command, contents);
+        audit_command(command, status)
+    })?;
+    if status.success() {
+        record_created(path, existed, Ok(()))?;
+    }
+    Ok(status)
+}
+
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let (path, contents) = (path.as_ref(), contents.as_ref());
+    if unchanged(path, contents) {
+        return Ok(());
+    }
+    record();
+    if is_dry_run() {
+        preview_write(path, contents, false);
+        return Ok(());
+    }
+    let existed = path.exists();
+    let result = audit_write("write", path, contents, || fs::write(path, contents));
+    record_created(path, existed, result)
+}
+
+/// Appends `contents` to `path`, creating the file if it does not exist yet.
+pub fn append(path: impl AsRef<Path>, contents: &str) -> io::Result<()> {
+    let path = path.as_ref();
+    if contents.is_empty() {
+        return Ok(());
+    }
+    record();
+    let mut updated = fs::read(path).unwrap_or_default();
+    updated.extend_from_slice(contents.as_bytes());
+    if is_dry_run() {
+        preview_write(path, &updated, false);
+        return Ok(());
+    }
+    let existed = path.exists();
+    let result = audit_write("append", path, &updated, || {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(contents.as_bytes())
+    });
+    record_created(path, existed, result)
+}
+
+pub fn create_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    if path.is_dir() {
+        return Ok(());
+    }
+    record();
+    if is_dry_run() {
+        announce(&format!("would create directory {}", path.display()));
+        return Ok(());
+    }
+    let result = audit_file("create_dir", path, || fs::create_dir_all(path));
+    record_created(path, false, result)
+}
+
+pub fn remove_file(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    record();
+    if is_dry_run() {
+        announce(&format!("would remove {}", path.display()));
+        return Ok(());
+    }
+    audit_file("remove", path, || fs::remove_file(path))
+}
+
+pub fn remove_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    record();
+    if is_dry_run() {
+        announce(&format!("would remove directory {}", path.display()));
+        return Ok(());
+    }
+    audit_file("remove_dir", path, || fs::remove_dir_all(path))
+}
+
+pub fn set_permissions(path: impl AsRef<Path>, permissions: Permissions) -> io::Result<()> {
+    let path = path.as_ref();
+    let mode = permissions.mode() & 0o7777;
+    let current = fs::metadata(path).map(|meta| meta.permissions().mode() & 0o7777);
+    if current.ok() == Some(mode) {
+        return Ok(());
+    }
+    record();
+    if is_dry_run() {
+        announce(&format!("would set mode {mode:o} on {}", path.display()));
+        return Ok(());
+    }
+    audit_file("chmod", path, || fs::set_permissions(path, permissions))
+}
+
+pub fn chown(path: impl AsRef<Path>, uid: u32, gid: u32) -> io::Result<()> {
+    let path = path.as_ref();
+    let current = fs::metadata(path).map(|meta| (meta.uid(), meta.gid()));
+    if current.ok() == Some((uid, gid)) {
+        return Ok(());
+    }
+    record();
+    if is_dry_run() {
+        announce(&format!(
+            "would set owner {uid}:{gid} on {}",
+            path.display()
+        ));
+        return Ok(());
+    }
+    audit_file("chown", path, || {
+        std::os::unix::fs::chown(path, Some(uid), Some(gid))
+    })
+}
+
+pub fn symlink(target: impl AsRef<Path>, link: impl AsRef<Path>) -> io::Result<()> {
+    let (target, link) = (target.as_ref(), link.as_ref());
+    record();
+    if is_dry_run() {
+        announce(&format!(
+            "would link {} -> {}",
+            link.display(),
+            target.display()
+        ));
+        return Ok(());
+    }
+    let result = audit_file("symlink", link, || std::os::unix::fs::symlink(target, link));
+    record_created(link, false, result)
+}
+
+pub fn copy(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<u64> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    record();
+    if is_dry_run() {
+        announce(&format!(
+            "would copy {} to {}",
+            from.display(),
+            to.display()
+        ));
+        return Ok(0);
+    }
+    let existed = to.exists();
+    let copied = audit_file("copy", to, || fs::copy(from, to))?;
+    record_created(to, existed, Ok(()))?;
+    Ok(copied)
+}
+
+fn unchanged(path: &Path, contents: &[u8]) -> bool {
+    fs::read(path).is_ok_and(|existing| existing == contents)
+}
+
+/// Sends a child's stdout to stderr under `--format json`, where stdout is reserved
+/// for the JSON result, and discards it under `--quiet`.
+fn keep_stdout_clean(command: &mut Command) -> &mut Command {
+    if output::is_quiet() {
+        command.stdout(Stdio::null());
+    } else if output::is_json() {
+        command.stdout(io::stderr());
+    }
+    command
+}
+
+fn announce(message: &str) {
+    announce_change(message, false);
+}
+
+fn announce_command(command: &Command, suffix: &str) {
+    announce_change(
+        &format!("would run: {}{suffix}", describe(command)),
+        command.get_program() == "sudo",
+    );
+}
+
+/// Reports a change made outside this module, such as a download, in a dry run or
+/// [`plan`]; `sudo` marks it as privileged. The caller then skips the change itself.
+pub(crate) fn announce_change(message: &str, sudo: bool) {
+    if let Some(plan) = PLAN.lock().unwrap().as_mut() {
+        let description = message.strip_prefix("would ").unwrap_or(message);
+        plan.push(Planned {
+            description: description.trim_end_matches(':').to_string(),
+            sudo,
+        });
+        return;
+    }
+    say!("[dry-run] {message}");
+}
+
+/// `sudo` marks a write made through `sudo tee` for [`plan`].
+fn preview_write(path: &Path, contents: &[u8], sudo: bool) {
+    let verb = if path.exists() { "modify" } else { "create" };
+    let Some(diff) = text_diff(path, contents) else {
+        announce_change(
+            &format!(
+                "would {verb} {} ({} bytes of binary data)",
+                path.display(),
+                contents.len()
+            ),
+            sudo,
+        );
+        return;
+    };
+    announce_change(&format!("would {verb} {}:", path.display()), sudo);
+    say!("{}", diff.trim_end_matches('\n'));
+}
+
+/// The unified diff from the current contents of `path`, which may not exist yet, to
+/// `contents`, with its `---`/`+++` header; `None` when `contents` is not text.
+fn text_diff(path: &Path, contents: &[u8]) -> Option<String> {
+    let new = std::str::from_utf8(contents).ok()?;
+    let old = fs::read(path).ok();
+    let from = match old {
+        Some(_) => path.display().to_string(),
+        None => "/dev/null".to_string(),
+    };
+    let hunks = diff(&String::from_utf8_lossy(&old.unwrap_or_default()), new);
+    Some(format!("--- {from}\n+++ {}\n{hunks}", path.display()))
+}
+
+/// Renders a command line the way a user would type it.
+pub fn describe(command: &Command) -> String {
+    let mut parts = vec![shell_quote(command.get_program())];
+    parts.extend(command.get_args().map(shell_quote));
+    let line = parts.join(" ");
+    match command.get_current_dir() {
+        Some(dir) => format!("{line} (in {})", dir.display()),
+        None => line,
+    }
+}
+
+fn shell_quote(value: &OsStr) -> String {
+    let value = value.to_string_lossy();
+    let plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+    if plain {
+        value.into_owned()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+/// Unified diff from `old` to `new`, as `diff -u` prints it but without the file
+/// header: `@@ -a,b +c,d @@` hunks with three lines of context. Empty when
+/// nothing changed.
+pub fn diff(old: &str, new: &str) -> String {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(('-', old[i]));
+            i += 1;
+        } else {
+            ops.push(('+', new[j]));
+            j += 1;
+        }
+    }
+
+    // Changes closer than twice the context share a hunk, as in `diff -u`.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (index, _) in ops.iter().enumerate().filter(|(_, (kind, _))| *kind != ' ') {
+        let start = index.saturating_sub(DIFF_CONTEXT);
+        let end = (index + DIFF_CONTEXT + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    // Lines of one side among `ops[range]`: old lines are not `+`, new lines not `-`.
+    let count = |range: std::ops::Range<usize>, skip: char| {
+        ops[range].iter().filter(|(kind, _)| *kind != skip).count()
+    };
+    let mut out = String::new();
+    for (start, end) in hunks {
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(count(0..start, '+'), count(start..end, '+')),
+            hunk_range(count(0..start, '-'), count(start..end, '-'))
+        ));
+        for (kind, line) in &ops[start..end] {
+            out.push_str(&format!("{kind}{line}\n"));
+        }
+    }
+    out
+}
+
+/// One side of a hunk header for `len` lines after the first `before`: `3,4`, just
+/// `3` for a single line, and `2,0` (the line before) for none.
+fn hunk_range(before: usize, len: usize) -> String {
+    match len {
+        0 => format!("{before},0"),
+        1 => format!("{}", before + 1),
+        len => format!("{},{len}", before + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_marks_changed_lines_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+        let expected = [
+            "@@ -2,7 +2,7 @@",
+            " 2",
+            " 3",
+            " 4",
+            "-5",
+            "+five",
+            " 6",
+            " 7",
+            " 8",
+        ];
+        assert_eq!(diff(old, new), format!("{}\n", expected.join("\n")));
+        assert_eq!(diff(old, old), "");
+    }
+
+    #[test]
+    fn diff_splits_distant_changes_into_hunks() {
+        let old = (1..=20).map(|n| format!("{n}\n")).collect::<String>();
+        let new = old.replacen("2\n", "", 1).replace("19\n", "19\nnew\n");
+        let diff = diff(&old, &new);
+        let headers = diff.lines().filter(|line| line.starts_with("@@"));
+        assert_eq!(
+            headers.collect::<Vec<_>>(),
+            ["@@ -1,5 +1,4 @@", "@@ -17,4 +16,5 @@"]
+        );
+    }
+
+    #[test]
+    fn diff_of_new_file_is_all_additions() {
+        assert_eq!(diff("", "x\ny\n"), "@@ -0,0 +1,2 @@\n+x\n+y\n");
+        let path = Path::new("/nonexistent/debkit-diff/new.conf");
+        assert_eq!(
+            text_diff(path, b"x\n").unwrap(),
+            "--- /dev/null\n+++ /nonexistent/debkit-diff/new.conf\n@@ -0,0 +1 @@\n+x\n"
+        );
+    }
+
+    #[test]
+    fn describes_commands_with_quoting() {
+        let mut command = Command::new("sudo");
+        command.args(["tee", "/etc/yp.conf", "a b", "it's"]);
+        assert_eq!(describe(&command), r"sudo tee /etc/yp.conf 'a b' 'it'\''s'");
+    }
+}
@@ -0,0 +1,204 @@
+//! `install appindicator`: tray icons on GNOME, whose shell has no system tray of its
+//! own, through the AppIndicator extension packaged as
+//! `gnome-shell-extension-appindicator`.
+//!
+//! The package only puts the extension on disk; it is switched on per user with
+//! `gnome-extensions enable`, or by adding it to `org.gnome.shell enabled-extensions`
+//! when the running shell does not know the extension yet. A shell picks up newly
+//! installed extensions at the next login.
+
+use anyhow::{Context, bail};
+
+use super::user::{UserContext, command_as, target_user_context};
+use super::{Action, Target};
+use crate::desktop::environment::{self, Environment};
+use crate::desktop::gsettings;
+use crate::output::say;
+use crate::{apt, exec};
+
+const PACKAGE: &str = "gnome-shell-extension-appindicator";
+const EXTENSION_UUID: &str = "appindicatorsupport@rgcjonas.gmail.com";
+
+pub fn run() -> anyhow::Result<()> {
+    run_for(&target_user_context()?)
+}
+
+/// Installs the extension and enables it for `user`. Other desktops show tray icons
+/// on their own and are left alone.
+pub fn run_for(user: &UserContext) -> anyhow::Result<()> {
+    let desktop = environment::detect(user);
+    if !matches!(desktop, Environment::Gnome | Environment::Unknown) {
+        say!(
+            "{} shows tray icons without an extension; skipping AppIndicator.",
+            desktop.name()
+        );
+        return Ok(());
+    }
+
+    apt::install_missing(&[PACKAGE])?;
+    enable(user)?;
+    if exec::is_dry_run() {
+        return Ok(());
+    }
+    match extension_state(user).as_deref() {
+        Some("ACTIVE" | "ENABLED") => say!("AppIndicator extension is active."),
+        Some(state) => say!(
+            "AppIndicator extension is enabled but {}; it becomes active at the next login.",
+            state.to_ascii_lowercase()
+        ),
+        None => say!("AppIndicator extension is enabled; it becomes active at the next login."),
+    }
+    Ok(())
+}
+
+pub fn uninstall() -> anyhow::Result<()> {
+    let user = target_user_context()?;
+    if enabled_extensions(&user).is_some_and(|list| with_extension(&list, EXTENSION_UUID).is_none())
+    {
+        let status =
+            exec::status(command_as(&user, "gnome-extensions")?.args(["disable", EXTENSION_UUID]))
+                .context("failed to start `gnome-extensions`")?;
+        if !status.success() {
+            bail!("gnome-extensions disable {EXTENSION_UUID} failed with status {status}");
+        }
+    }
+    if apt::package_installed(PACKAGE)? {
+        apt::remove(&[PACKAGE])?;
+    } else {
+        say!("{PACKAGE} is not installed.");
+    }
+    Ok(())
+}
+
+fn enable(user: &UserContext) -> anyhow::Result<()> {
+    // A dry run may not have installed GNOME Shell's schemas yet.
+    let Some(list) = enabled_extensions(user).or_else(|| exec::is_dry_run().then(String::new))
+    else {
+        bail!("could not read org.gnome.shell enabled-extensions; is GNOME Shell installed?");
+    };
+    let Some(updated) = with_extension(&list, EXTENSION_UUID) else {
+        return Ok(());
+    };
+    let enabled =
+        exec::status(command_as(user, "gnome-extensions")?.args(["enable", EXTENSION_UUID]))
+            .is_ok_and(|status| status.success());
+    if enabled {
+        return Ok(());
+    }
+    // The running shell only knows the extensions that were there when it started.
+    gsettings::set(user, "org.gnome.shell", "enabled-extensions", &updated)
+}
+
+/// `org.gnome.shell enabled-extensions` as gsettings prints it, such as
+/// `['a@example.com']` or `@as []`.
+fn enabled_extensions(user: &UserContext) -> Option<String> {
+    gsettings::get(user, "org.gnome.shell", "enabled-extensions")
+        .ok()
+        .flatten()
+}
+
+/// `list` with `uuid` appended, or `None` when it is already there.
+fn with_extension(list: &str, uuid: &str) -> Option<String> {
+    let entries = list
+        .trim()
+        .trim_start_matches("@as")
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(|entry| entry.trim().trim_matches('\'').to_string())
+        .filter(|entry| !entry.is_empty())
+        .collect::<Vec<_>>();
+    if entries.iter().any(|entry| entry == uuid) {
+        return None;
+    }
+    let quoted = entries
+        .iter()
+        .map(String::as_str)
+        .chain([uuid])
+        .map(|entry| format!("'{entry}'"))
+        .collect::<Vec<_>>();
+    Some(format!("[{}]", quoted.join(", ")))
+}
+
+/// The `State:` `gnome-extensions info` reports, such as `ACTIVE`, or `None` without a
+/// running shell to ask.
+fn extension_state(user: &UserContext) -> Option<String> {
+    let mut command = command_as(user, "gnome-extensions").ok()?;
+    let output = exec::query(command.args(["info", EXTENSION_UUID])).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("State:"))
+        .map(|state| state.trim().to_string())
+}
+
+pub struct AppindicatorTarget;
+
+impl Target for AppindicatorTarget {
+    fn name(&self) -> &'static str {
+        "appindicator"
+    }
+
+    fn description(&self) -> &'static str {
+        "GNOME Shell AppIndicator extension for tray icons"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall)
+    }
+
+    fn install(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        run()
+    }
+
+    fn uninstall(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall()
+    }
+
+    fn version(&self) -> Option<String> {
+        apt::package_version(PACKAGE)
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(&[PACKAGE])
+    }
+
+    fn summary(&self, _ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let status = super::TargetStatus::new(self.name(), self.version());
+        if !status.installed {
+            return Ok(status);
+        }
+        let user = target_user_context()?;
+        let mut drift = Vec::new();
+        if enabled_extensions(&user)
+            .is_some_and(|list| with_extension(&list, EXTENSION_UUID).is_some())
+        {
+            drift.push(format!("{EXTENSION_UUID} is not enabled"));
+        }
+        Ok(status.with_drift(drift))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_the_extension_to_the_enabled_list() {
+        assert_eq!(
+            with_extension("@as []", EXTENSION_UUID).unwrap(),
+            format!("['{EXTENSION_UUID}']")
+        );
+        assert_eq!(
+            with_extension("['ding@rastersoft.com']", EXTENSION_UUID).unwrap(),
+            format!("['ding@rastersoft.com', '{EXTENSION_UUID}']")
+        );
+        assert_eq!(
+            with_extension(&format!("['{EXTENSION_UUID}']"), EXTENSION_UUID),
+            None
+        );
+    }
+}
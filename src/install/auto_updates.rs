@@ -0,0 +1,191 @@
+//! `install auto-updates`: unattended-upgrades, configured from `[auto_updates]`.
+//!
+//! `/etc/apt/apt.conf.d/50unattended-upgrades` is rendered from the origins, reboot
+//! and mail settings, and `20auto-upgrades` turns on the daily package list refresh
+//! and upgrade run. The stock `50unattended-upgrades` is backed up before DebKit first
+//! replaces it. `status all` reports drift when either file no longer matches the
+//! config.
+
+use std::fs;
+use std::path::Path;
+
+use super::{Action, Target};
+use crate::config::AutoUpdatesConfig;
+use crate::output::say;
+use crate::{apt, backup, privileged};
+
+const UNATTENDED_UPGRADES_PATH: &str = "/etc/apt/apt.conf.d/50unattended-upgrades";
+const AUTO_UPGRADES_PATH: &str = "/etc/apt/apt.conf.d/20auto-upgrades";
+const MARKER: &str = "// Managed by debkit; `debkit install auto-updates` rewrites this file.";
+
+pub fn run(config: &AutoUpdatesConfig) -> anyhow::Result<()> {
+    apt::install_missing(&["unattended-upgrades"])?;
+    let mut changed = false;
+    for (path, contents) in files(config) {
+        let path = Path::new(path);
+        if fs::read_to_string(path).is_ok_and(|existing| existing == contents) {
+            continue;
+        }
+        backup::save(path)?;
+        privileged::write(path, contents.as_bytes())?;
+        say!("Wrote {}", path.display());
+        changed = true;
+    }
+    if !changed {
+        say!("unattended-upgrades is already configured.");
+    }
+    if config.automatic_reboot {
+        say!(
+            "Upgrades that need it reboot the machine at {}.",
+            config.reboot_time
+        );
+    }
+    Ok(())
+}
+
+/// Removes `20auto-upgrades`, which stops the daily runs, and the package. dpkg keeps
+/// `50unattended-upgrades` as the package's configuration.
+pub fn uninstall() -> anyhow::Result<()> {
+    let path = Path::new(AUTO_UPGRADES_PATH);
+    if fs::read_to_string(path).is_ok_and(|existing| existing.starts_with(MARKER)) {
+        privileged::remove_file(path)?;
+        say!("Removed {}", path.display());
+    }
+    if apt::package_installed("unattended-upgrades")? {
+        apt::remove(&["unattended-upgrades"])?;
+    } else {
+        say!("unattended-upgrades is not installed.");
+    }
+    Ok(())
+}
+
+/// The files DebKit writes and their contents for `config`.
+fn files(config: &AutoUpdatesConfig) -> [(&'static str, String); 2] {
+    [
+        (UNATTENDED_UPGRADES_PATH, render_unattended_upgrades(config)),
+        (AUTO_UPGRADES_PATH, render_auto_upgrades()),
+    ]
+}
+
+fn render_unattended_upgrades(config: &AutoUpdatesConfig) -> String {
+    let mut lines = vec![
+        MARKER.to_string(),
+        "Unattended-Upgrade::Origins-Pattern {".to_string(),
+    ];
+    lines.extend(
+        config
+            .origins
+            .iter()
+            .map(|origin| format!("        \"{origin}\";")),
+    );
+    lines.push("};".to_string());
+    if !config.mail.is_empty() {
+        lines.push(format!("Unattended-Upgrade::Mail \"{}\";", config.mail));
+        lines.push(format!(
+            "Unattended-Upgrade::MailReport \"{}\";",
+            config.mail_report
+        ));
+    }
+    lines.push(format!(
+        "Unattended-Upgrade::Automatic-Reboot \"{}\";",
+        config.automatic_reboot
+    ));
+    if config.automatic_reboot {
+        lines.push(format!(
+            "Unattended-Upgrade::Automatic-Reboot-Time \"{}\";",
+            config.reboot_time
+        ));
+    }
+    lines.join("\n") + "\n"
+}
+
+fn render_auto_upgrades() -> String {
+    [
+        MARKER,
+        "APT::Periodic::Update-Package-Lists \"1\";",
+        "APT::Periodic::Unattended-Upgrade \"1\";",
+    ]
+    .join("\n")
+        + "\n"
+}
+
+pub struct AutoUpdatesTarget;
+
+impl Target for AutoUpdatesTarget {
+    fn name(&self) -> &'static str {
+        "auto-updates"
+    }
+
+    fn description(&self) -> &'static str {
+        "unattended-upgrades with origins, reboot window and mail from the config"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["unattended-upgrades"]
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall)
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        run(&ctx.config()?.auto_updates)
+    }
+
+    fn uninstall(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall()
+    }
+
+    fn version(&self) -> Option<String> {
+        apt::package_version("unattended-upgrades")
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(&["unattended-upgrades"])
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let config = &ctx.config()?.auto_updates;
+        let status = super::TargetStatus::new(self.name(), self.version());
+        if !status.installed {
+            return Ok(status);
+        }
+        let drift = files(config)
+            .into_iter()
+            .filter(|(path, contents)| fs::read_to_string(path).ok().as_ref() != Some(contents))
+            .map(|(path, _)| format!("{path} does not match the config"))
+            .collect();
+        Ok(status.with_drift(drift))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_origins_reboot_and_mail() {
+        let config = AutoUpdatesConfig {
+            origins: vec!["origin=Debian,codename=${distro_codename},label=Debian".to_string()],
+            automatic_reboot: true,
+            mail: "root".to_string(),
+            ..AutoUpdatesConfig::default()
+        };
+        assert_eq!(
+            render_unattended_upgrades(&config),
+            format!(
+                "{MARKER}\n\
+                 Unattended-Upgrade::Origins-Pattern {{\n\
+                 \x20       \"origin=Debian,codename=${{distro_codename}},label=Debian\";\n\
+                 }};\n\
+                 Unattended-Upgrade::Mail \"root\";\n\
+                 Unattended-Upgrade::MailReport \"on-change\";\n\
+                 Unattended-Upgrade::Automatic-Reboot \"true\";\n\
+                 Unattended-Upgrade::Automatic-Reboot-Time \"02:00\";\n"
+            )
+        );
+        let quiet = render_unattended_upgrades(&AutoUpdatesConfig::default());
+        assert!(quiet.contains("Unattended-Upgrade::Automatic-Reboot \"false\";\n"));
+        assert!(!quiet.contains("Mail"));
+    }
+}
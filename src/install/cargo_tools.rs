@@ -0,0 +1,327 @@
+//! `install cargo-tools`: binaries from crates.io, such as `cargo-deb` or
+//! `cargo-nextest`, installed with `cargo install --locked`.
+//!
+//! Each entry of `cargo_tools.install` is a crate name, or `name@version` to pin it.
+//! `cargo install --list` says which versions are installed; a tool is skipped when
+//! it has the pinned version or, unpinned, the newest one `cargo search` knows of.
+//! `package deb --reinstall` installs cargo-deb through [`install`] too.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+
+use super::rust::resolve_program;
+use super::{Action, Target};
+use crate::output::say;
+use crate::{exec, network, offline, system};
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Crate names, optionally pinned as `name@version`.
+    pub tools: Vec<String>,
+    /// Installs every tool again with `--force`, even when it is up to date.
+    pub reinstall: bool,
+}
+
+pub fn run(options: Options) -> anyhow::Result<()> {
+    if options.tools.is_empty() {
+        say!("No cargo tools to install; list them in `cargo_tools.install`.");
+        return Ok(());
+    }
+    install(&options.tools, options.reinstall)
+}
+
+/// Runs `cargo install --locked` for each of `tools` that is missing or out of date,
+/// or for all of them with `reinstall`.
+pub fn install(tools: &[String], reinstall: bool) -> anyhow::Result<()> {
+    let tools = tools
+        .iter()
+        .map(|tool| Tool::parse(tool))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    // A dry run of `install foundation` on a fresh machine has no cargo yet.
+    let installed = match installed_versions() {
+        Err(_) if exec::is_dry_run() => BTreeMap::new(),
+        installed => installed?,
+    };
+    for tool in tools {
+        let current = installed.get(&tool.name);
+        if !reinstall && let Some(current) = current {
+            let wanted = match &tool.version {
+                Some(version) => Some(version.clone()),
+                None => latest_version(&tool.name),
+            };
+            if wanted.is_none_or(|wanted| &wanted == current) {
+                say!("{} {current} is up to date", tool.name);
+                continue;
+            }
+        }
+
+        let spec = tool.spec();
+        let mut args = vec!["install", "--locked"];
+        if reinstall {
+            args.push("--force");
+        }
+        if offline::is_enabled() {
+            args.push("--offline");
+        }
+        args.push(&spec);
+        run_cargo(&args)?;
+        if exec::is_dry_run() {
+            continue;
+        }
+        match installed_versions()?.get(&tool.name) {
+            Some(version) => say!("Installed {} {version}", tool.name),
+            None => bail!("`cargo install` did not install {}", tool.name),
+        }
+    }
+    Ok(())
+}
+
+/// Runs `cargo uninstall` for each of `tools` that is installed.
+pub fn uninstall(tools: &[String]) -> anyhow::Result<()> {
+    let installed = installed_versions()?;
+    let mut removed_any = false;
+    for tool in tools {
+        let tool = Tool::parse(tool)?;
+        if installed.contains_key(&tool.name) {
+            run_cargo(&["uninstall", &tool.name])?;
+            removed_any = true;
+        }
+    }
+    if !removed_any {
+        say!("No configured cargo tools are installed.");
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Tool {
+    name: String,
+    /// The pinned version, if any.
+    version: Option<String>,
+}
+
+impl Tool {
+    fn parse(entry: &str) -> anyhow::Result<Self> {
+        let (name, version) = match entry.trim().split_once('@') {
+            Some((name, version)) => (name, Some(version.trim_start_matches('v'))),
+            None => (entry.trim(), None),
+        };
+        if name.is_empty()
+            || version.is_some_and(str::is_empty)
+            || name.contains(char::is_whitespace)
+        {
+            bail!("`{entry}` is not a crate name or `name@version`");
+        }
+        Ok(Self {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+        })
+    }
+
+    fn spec(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}@{version}", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// The installed unpinned `tools` with a newer release on crates.io, going by `cargo
+/// search`; offline this finds none. A pinned tool is at the version it should be.
+fn available_updates(tools: &[String]) -> anyhow::Result<Vec<super::Update>> {
+    if tools.is_empty() || resolve_program("cargo").is_none() {
+        return Ok(Vec::new());
+    }
+    let installed = installed_versions()?;
+    let mut updates = Vec::new();
+    for tool in tools {
+        let tool = Tool::parse(tool)?;
+        let Some(current) = installed.get(&tool.name) else {
+            continue;
+        };
+        if tool.version.is_some() {
+            continue;
+        }
+        if let Some(latest) = latest_version(&tool.name)
+            && &latest != current
+        {
+            updates.push(super::Update {
+                name: tool.name,
+                installed: current.clone(),
+                available: latest,
+            });
+        }
+    }
+    Ok(updates)
+}
+
+/// The installed version of each crate `cargo install --list` knows of.
+fn installed_versions() -> anyhow::Result<BTreeMap<String, String>> {
+    let output = exec::query(Command::new(cargo()?).args(["install", "--list"]))
+        .context("failed to start `cargo install --list`")?;
+    if !output.status.success() {
+        bail!(
+            "`cargo install --list` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(parse_install_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Reads the `name vVERSION:` or `name vVERSION (source):` lines of `cargo install
+/// --list`, skipping the indented binaries under each.
+fn parse_install_list(list: &str) -> BTreeMap<String, String> {
+    list.lines()
+        .filter(|line| !line.starts_with(char::is_whitespace))
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let name = words.next()?;
+            let version = words.next()?.trim_end_matches(':').strip_prefix('v')?;
+            Some((name.to_string(), version.to_string()))
+        })
+        .collect()
+}
+
+/// The newest version of `name` on crates.io, or `None` offline or when `cargo
+/// search` cannot tell.
+fn latest_version(name: &str) -> Option<String> {
+    if offline::is_enabled() {
+        return None;
+    }
+    let output = exec::query(
+        network::apply(&mut Command::new(cargo().ok()?)).args(["search", "--limit", "1", name]),
+    )
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let prefix = format!("{name} = \"");
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix(&prefix)?.split_once('"'))
+        .map(|(version, _)| version.to_string())
+}
+
+/// `cargo`, or `~/.cargo/bin/cargo` before the cargo env is sourced.
+fn cargo() -> anyhow::Result<PathBuf> {
+    if system::command_available("cargo") {
+        return Ok(PathBuf::from("cargo"));
+    }
+    resolve_program("cargo")
+        .context("`cargo` was not found in PATH or ~/.cargo/bin; run `debkit install rust`")
+}
+
+fn run_cargo(args: &[&str]) -> anyhow::Result<()> {
+    let step = format!("cargo {}", args.join(" "));
+    let status = exec::status_step(
+        network::apply(&mut Command::new(cargo()?)).args(args),
+        &step,
+    )
+    .context("failed to start `cargo`")?;
+    if !status.success() {
+        bail!("command `{step}` failed with status {status}");
+    }
+    Ok(())
+}
+
+pub struct CargoToolsTarget;
+
+impl Target for CargoToolsTarget {
+    fn name(&self) -> &'static str {
+        "cargo-tools"
+    }
+
+    fn description(&self) -> &'static str {
+        "Rust tools installed with cargo install"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["rust"]
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall)
+    }
+
+    fn args(&self, action: Action, command: clap::Command) -> clap::Command {
+        match action {
+            Action::Install => command.arg(
+                clap::Arg::new("reinstall")
+                    .long("reinstall")
+                    .action(clap::ArgAction::SetTrue)
+                    .help("Install every tool again, even when it is up to date"),
+            ),
+            _ => command,
+        }
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        run(Options {
+            tools: ctx.config()?.cargo_tools.install.clone(),
+            reinstall: ctx.flag("reinstall"),
+        })
+    }
+
+    fn uninstall(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall(&ctx.config()?.cargo_tools.install)
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let tools = &ctx.config()?.cargo_tools.install;
+        let installed = if tools.is_empty() || resolve_program("cargo").is_none() {
+            BTreeMap::new()
+        } else {
+            installed_versions()?
+        };
+        let mut versions = Vec::new();
+        let mut drift = Vec::new();
+        for tool in tools {
+            let tool = Tool::parse(tool)?;
+            match (installed.get(&tool.name), &tool.version) {
+                (None, _) => drift.push(format!("{} is not installed", tool.name)),
+                (Some(current), Some(pinned)) if current != pinned => {
+                    versions.push(format!("{} {current}", tool.name));
+                    drift.push(format!(
+                        "{} is {current} but config pins {pinned}",
+                        tool.name
+                    ));
+                }
+                (Some(current), _) => versions.push(format!("{} {current}", tool.name)),
+            }
+        }
+        let version = (!versions.is_empty()).then(|| versions.join(", "));
+        let status = super::TargetStatus::new(self.name(), version);
+        if !status.installed {
+            return Ok(status);
+        }
+        Ok(status.with_drift(drift))
+    }
+
+    fn updates(&self, ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        available_updates(&ctx.config()?.cargo_tools.install)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_installed_versions_and_pins() {
+        let list = "cargo-deb v2.7.0:\n    cargo-deb\nripgrep v14.1.0 (/src/ripgrep):\n    rg\n";
+        let installed = parse_install_list(list);
+        assert_eq!(installed.len(), 2);
+        assert_eq!(installed["cargo-deb"], "2.7.0");
+        assert_eq!(installed["ripgrep"], "14.1.0");
+
+        let pinned = Tool::parse("cargo-nextest@v0.9.72").unwrap();
+        assert_eq!(pinned.version.as_deref(), Some("0.9.72"));
+        assert_eq!(pinned.spec(), "cargo-nextest@0.9.72");
+        assert_eq!(Tool::parse("cargo-audit").unwrap().spec(), "cargo-audit");
+        assert!(Tool::parse("cargo-audit@").is_err());
+        assert!(Tool::parse("cargo audit").is_err());
+    }
+}
@@ -3,6 +3,11 @@ use std::process::Command;
 
 use anyhow::{Context, bail};
 
+use super::{Action, Target};
+use crate::exec;
+use crate::network;
+use crate::output::say;
+
 pub fn run(node_version: String) -> anyhow::Result<()> {
     super::npm::run(super::npm::Options {
         version: node_version,
@@ -10,18 +15,21 @@ pub fn run(node_version: String) -> anyhow::Result<()> {
     .context("failed to install prerequisite target `npm`")?;
 
     if let Some(codex) = managed_program("codex") {
-        println!("Codex already installed:");
+        say!("Codex already installed:");
         run_command(&codex, &["--version"])?;
         return Ok(());
     }
 
     install_codex_package()?;
+    if exec::is_dry_run() {
+        return Ok(());
+    }
 
     let Some(codex) = managed_program("codex") else {
         bail!("`codex` was not found on PATH after installation");
     };
 
-    println!("Codex installation complete:");
+    say!("Codex installation complete:");
     run_command(&codex, &["--version"])?;
 
     Ok(())
@@ -29,39 +37,43 @@ pub fn run(node_version: String) -> anyhow::Result<()> {
 
 pub fn uninstall() -> anyhow::Result<()> {
     let Some(npm) = managed_program("npm") else {
-        println!("Codex is not installed: managed npm was not found.");
+        say!("Codex is not installed: managed npm was not found.");
         return Ok(());
     };
 
     if managed_program("codex").is_none() {
-        println!("Codex is not installed.");
+        say!("Codex is not installed.");
         return Ok(());
     }
 
     let prefix = codex_prefix_dir().context("failed to determine per-user npm prefix")?;
-    let status = Command::new(&npm)
-        .args(["uninstall", "-g", "@openai/codex"])
-        .env("NPM_CONFIG_PREFIX", &prefix)
-        .status()
-        .with_context(|| format!("failed to start `{}`", npm.display()))?;
+    let status = exec::status(
+        Command::new(&npm)
+            .args(["uninstall", "-g", "@openai/codex"])
+            .env("NPM_CONFIG_PREFIX", &prefix),
+    )
+    .with_context(|| format!("failed to start `{}`", npm.display()))?;
     if !status.success() {
         bail!(
-            "command `{}` failed with status {}",
-            format!("{} uninstall -g @openai/codex", npm.display()),
+            "command `{} uninstall -g @openai/codex` failed with status {}",
+            npm.display(),
             status
         );
     }
+    if exec::is_dry_run() {
+        return Ok(());
+    }
 
-    if let Some(codex) = managed_program("codex") {
-        if codex.exists() {
-            bail!(
-                "`codex` still exists after uninstall at {}",
-                codex.display()
-            );
-        }
+    if let Some(codex) = managed_program("codex")
+        && codex.exists()
+    {
+        bail!(
+            "`codex` still exists after uninstall at {}",
+            codex.display()
+        );
     }
 
-    println!("Codex uninstalled.");
+    say!("Codex uninstalled.");
     Ok(())
 }
 
@@ -70,15 +82,16 @@ fn install_codex_package() -> anyhow::Result<()> {
         .context("`npm` was not found after installing Node.js")?;
     let prefix = codex_prefix_dir().context("failed to determine per-user npm prefix")?;
 
-    let status = Command::new(&npm)
-        .args(["install", "-g", "@openai/codex"])
-        .env("NPM_CONFIG_PREFIX", &prefix)
-        .status()
-        .with_context(|| format!("failed to start `{}`", npm.display()))?;
+    let status = exec::status(
+        network::apply(&mut Command::new(&npm))
+            .args(["install", "-g", "@openai/codex"])
+            .env("NPM_CONFIG_PREFIX", &prefix),
+    )
+    .with_context(|| format!("failed to start `{}`", npm.display()))?;
     if !status.success() {
         bail!(
-            "command `{}` failed with status {}",
-            format!("{} install -g @openai/codex", npm.display()),
+            "command `{} install -g @openai/codex` failed with status {}",
+            npm.display(),
             status
         );
     }
@@ -87,14 +100,13 @@ fn install_codex_package() -> anyhow::Result<()> {
 }
 
 fn run_command(program: &PathBuf, args: &[&str]) -> anyhow::Result<()> {
-    let status = Command::new(program)
-        .args(args)
-        .status()
+    let status = exec::probe(Command::new(program).args(args))
         .with_context(|| format!("failed to start `{}`", program.display()))?;
     if !status.success() {
         bail!(
-            "command `{}` failed with status {}",
-            format!("{} {}", program.display(), args.join(" ")),
+            "command `{} {}` failed with status {}",
+            program.display(),
+            args.join(" "),
             status
         );
     }
@@ -109,3 +121,51 @@ fn managed_program(program: &str) -> Option<PathBuf> {
 fn codex_prefix_dir() -> anyhow::Result<PathBuf> {
     super::npm::managed_bin_dir().map(|bin_dir| bin_dir.parent().unwrap().to_path_buf())
 }
+
+pub struct CodexTarget;
+
+impl Target for CodexTarget {
+    fn name(&self) -> &'static str {
+        "codex"
+    }
+
+    fn description(&self) -> &'static str {
+        "OpenAI Codex CLI via npm"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["npm"]
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall)
+    }
+
+    fn args(&self, action: Action, command: clap::Command) -> clap::Command {
+        if action != Action::Install {
+            return command;
+        }
+        command.arg(
+            clap::Arg::new("node-version")
+                .long("node-version")
+                .value_name("VERSION")
+                .help("Node.js version to install first [default: `npm.version` from config]"),
+        )
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let node_version = match ctx.value("node-version") {
+            Some(version) => version.to_string(),
+            None => ctx.config()?.npm.version.clone(),
+        };
+        run(node_version)
+    }
+
+    fn uninstall(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall()
+    }
+
+    fn version(&self) -> Option<String> {
+        super::program_version("codex", &["--version"])
+    }
+}
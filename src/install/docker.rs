@@ -0,0 +1,257 @@
+//! `install docker`: a container engine, Docker from its upstream repository or
+//! Podman from the distribution.
+//!
+//! For Docker, `download.docker.com` is added as the `docker` APT source with its
+//! signing key, the engine, CLI, containerd and the buildx and compose plugins are
+//! installed, `docker.service` is enabled and started, and the target user joins the
+//! `docker` group so they can use it without sudo. Podman runs rootless without a
+//! daemon, so that flavor only installs the package.
+
+use std::fs;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+
+use super::user::target_user_context;
+use super::{Action, Target};
+use crate::config::{DockerConfig, DockerFlavor};
+use crate::output::say;
+use crate::{apt, exec, keyring, privileged, repo, system};
+
+const REPO_NAME: &str = "docker";
+const DOCKER_GROUP: &str = "docker";
+const DOCKER_SERVICE: &str = "docker.service";
+/// The key Docker signs its Debian and Ubuntu repositories with.
+const DOCKER_KEY_FINGERPRINT: &str = "9DC858229FC7DD38854AE2D88D81803C0EBFCD88";
+const DOCKER_PACKAGES: &[&str] = &[
+    "docker-ce",
+    "docker-ce-cli",
+    "containerd.io",
+    "docker-buildx-plugin",
+    "docker-compose-plugin",
+];
+const PODMAN_PACKAGES: &[&str] = &["podman"];
+
+pub fn run(config: &DockerConfig) -> anyhow::Result<()> {
+    if config.flavor == DockerFlavor::Podman {
+        apt::install_missing(PODMAN_PACKAGES)?;
+        say!("Podman installed; it runs containers rootless, without a daemon.");
+        return Ok(());
+    }
+
+    if !repo::path(REPO_NAME).exists() {
+        add_docker_repo()?;
+    }
+    apt::install_missing(DOCKER_PACKAGES)?;
+    if !system::systemctl_succeeds("is-enabled", DOCKER_SERVICE)
+        || !system::systemctl_succeeds("is-active", DOCKER_SERVICE)
+    {
+        privileged::run("systemctl", &["enable", "--now", DOCKER_SERVICE])?;
+    }
+
+    if config.add_user {
+        add_user_to_docker_group()?;
+    }
+    say!("Docker installed and {DOCKER_SERVICE} enabled.");
+    Ok(())
+}
+
+pub fn uninstall(config: &DockerConfig) -> anyhow::Result<()> {
+    let packages = match config.flavor {
+        DockerFlavor::Docker => DOCKER_PACKAGES,
+        DockerFlavor::Podman => PODMAN_PACKAGES,
+    };
+    let installed = packages
+        .iter()
+        .copied()
+        .filter(|package| apt::package_installed(package).unwrap_or(false))
+        .collect::<Vec<_>>();
+    if installed.is_empty() {
+        say!("{} is not installed.", flavor_name(config));
+    } else {
+        apt::remove(&installed)?;
+    }
+    if config.flavor == DockerFlavor::Docker && repo::path(REPO_NAME).exists() {
+        repo::remove(REPO_NAME)?;
+    }
+    say!(
+        "Images, containers and volumes are kept in {}.",
+        match config.flavor {
+            DockerFlavor::Docker => "/var/lib/docker",
+            DockerFlavor::Podman => "~/.local/share/containers",
+        }
+    );
+    Ok(())
+}
+
+fn flavor_name(config: &DockerConfig) -> &'static str {
+    match config.flavor {
+        DockerFlavor::Docker => "Docker",
+        DockerFlavor::Podman => "Podman",
+    }
+}
+
+/// Adds `download.docker.com` for this distribution and release as the `docker`
+/// source. A fingerprint under `[keys] docker` replaces the built-in one.
+fn add_docker_repo() -> anyhow::Result<()> {
+    let os_release =
+        fs::read_to_string("/etc/os-release").context("failed to read /etc/os-release")?;
+    let Some((distro, codename)) = docker_distro(&os_release) else {
+        bail!("Docker publishes packages for Debian and Ubuntu; this system is neither");
+    };
+    let architecture = dpkg_architecture()?;
+    let fingerprint = keyring::declared_fingerprint(REPO_NAME)?
+        .unwrap_or_else(|| DOCKER_KEY_FINGERPRINT.to_string());
+    repo::add(
+        &format!(
+            "deb [arch={architecture}] https://download.docker.com/linux/{distro} {codename} stable"
+        ),
+        repo::AddOptions {
+            name: Some(REPO_NAME),
+            key_url: Some(&format!("https://download.docker.com/linux/{distro}/gpg")),
+            fingerprint: Some(&fingerprint),
+            suite: None,
+        },
+    )?;
+    Ok(())
+}
+
+/// The `linux/<distro>` directory of download.docker.com and the release codename to
+/// use from `/etc/os-release`. Derivatives such as Linux Mint use the release of the
+/// Ubuntu or Debian they are based on.
+fn docker_distro(os_release: &str) -> Option<(&'static str, String)> {
+    let value = |key: &str| {
+        os_release
+            .lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+            .map(|value| value.trim_matches('"').to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let id = value("ID").unwrap_or_default();
+    let like = value("ID_LIKE").unwrap_or_default();
+    let family = |name: &str| id == name || like.split_whitespace().any(|like| like == name);
+    if family("ubuntu") {
+        Some((
+            "ubuntu",
+            value("UBUNTU_CODENAME").or(value("VERSION_CODENAME"))?,
+        ))
+    } else if family("debian") {
+        Some((
+            "debian",
+            value("DEBIAN_CODENAME").or(value("VERSION_CODENAME"))?,
+        ))
+    } else {
+        None
+    }
+}
+
+fn dpkg_architecture() -> anyhow::Result<String> {
+    let output = exec::query(Command::new("dpkg").arg("--print-architecture"))
+        .context("failed to run `dpkg --print-architecture`")?;
+    if !output.status.success() {
+        bail!(
+            "`dpkg --print-architecture` failed with status {}",
+            output.status
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn add_user_to_docker_group() -> anyhow::Result<()> {
+    let user = target_user_context()?;
+    let Some(name) = user.name.as_deref().filter(|name| *name != "root") else {
+        return Ok(());
+    };
+    if in_docker_group(name) {
+        return Ok(());
+    }
+    privileged::run("usermod", &["-aG", DOCKER_GROUP, name])?;
+    say!(
+        "Added {name} to the `{DOCKER_GROUP}` group; log out and back in to use docker without sudo."
+    );
+    Ok(())
+}
+
+fn in_docker_group(user: &str) -> bool {
+    system::user_groups(user).is_ok_and(|groups| groups.iter().any(|group| group == DOCKER_GROUP))
+}
+
+pub struct DockerTarget;
+
+impl Target for DockerTarget {
+    fn name(&self) -> &'static str {
+        "docker"
+    }
+
+    fn description(&self) -> &'static str {
+        "Docker engine from download.docker.com, or Podman"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall)
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        run(&ctx.config()?.docker)
+    }
+
+    fn uninstall(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall(&ctx.config()?.docker)
+    }
+
+    fn version(&self) -> Option<String> {
+        apt::package_version("docker-ce").or_else(|| apt::package_version("podman"))
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(&[DOCKER_PACKAGES, PODMAN_PACKAGES].concat())
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let config = &ctx.config()?.docker;
+        let package = match config.flavor {
+            DockerFlavor::Docker => "docker-ce",
+            DockerFlavor::Podman => "podman",
+        };
+        let status = super::TargetStatus::new(self.name(), apt::package_version(package));
+        if !status.installed || config.flavor == DockerFlavor::Podman {
+            return Ok(status);
+        }
+
+        let mut drift = Vec::new();
+        if !repo::path(REPO_NAME).exists() {
+            drift.push(format!("{} is missing", repo::path(REPO_NAME).display()));
+        }
+        if !system::systemctl_succeeds("is-enabled", DOCKER_SERVICE) {
+            drift.push(format!("{DOCKER_SERVICE} is not enabled"));
+        }
+        if !system::systemctl_succeeds("is-active", DOCKER_SERVICE) {
+            drift.push(format!("{DOCKER_SERVICE} is not running"));
+        }
+        if config.add_user
+            && let Some(name) = target_user_context()?.name
+            && name != "root"
+            && !in_docker_group(&name)
+        {
+            drift.push(format!("{name} is not in group `{DOCKER_GROUP}`"));
+        }
+        Ok(status.with_drift(drift))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_docker_repository_for_the_release() {
+        let debian = "ID=debian\nVERSION_CODENAME=bookworm\n";
+        assert_eq!(
+            docker_distro(debian),
+            Some(("debian", "bookworm".to_string()))
+        );
+        let mint = "ID=linuxmint\nID_LIKE=\"ubuntu debian\"\nVERSION_CODENAME=wilma\nUBUNTU_CODENAME=noble\n";
+        assert_eq!(docker_distro(mint), Some(("ubuntu", "noble".to_string())));
+        assert_eq!(docker_distro("ID=fedora\nVERSION_CODENAME=\n"), None);
+    }
+}
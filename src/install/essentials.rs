@@ -1,18 +1,20 @@
+use super::{Context, Target, TargetStatus};
 use crate::config::{DEFAULT_ESSENTIAL_PACKAGES, EssentialsConfig};
+use crate::output::say;
 
 pub fn run(config: &EssentialsConfig) -> anyhow::Result<()> {
     let packages = selected_packages(config);
     if packages.is_empty() {
-        println!("No essentials packages configured (`essentials.packages` is empty).");
+        say!("No essentials packages configured (`essentials.packages` is empty).");
         return Ok(());
     }
 
     let package_refs = packages.iter().map(String::as_str).collect::<Vec<_>>();
-    let installed = super::apt::install_missing(&package_refs)?;
+    let installed = crate::apt::install_missing(&package_refs)?;
     if installed.is_empty() {
-        println!("Essential packages already installed.");
+        say!("Essential packages already installed.");
     } else {
-        println!("Installed essential packages: {}", installed.join(", "));
+        say!("Installed essential packages: {}", installed.join(", "));
     }
 
     Ok(())
@@ -35,6 +37,44 @@ fn selected_packages(config: &EssentialsConfig) -> Vec<String> {
         .collect()
 }
 
+pub struct EssentialsTarget;
+
+impl Target for EssentialsTarget {
+    fn name(&self) -> &'static str {
+        "essentials"
+    }
+
+    fn description(&self) -> &'static str {
+        "Baseline CLI packages required for provisioning"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["base", "dev-base", "dev_base"]
+    }
+
+    fn install(&self, ctx: &Context) -> anyhow::Result<()> {
+        run(&ctx.config()?.essentials)
+    }
+
+    fn summary(&self, ctx: &Context) -> anyhow::Result<TargetStatus> {
+        let mut drift = Vec::new();
+        for package in selected_packages(&ctx.config()?.essentials) {
+            if !crate::apt::package_installed(&package)? {
+                drift.push(format!("package `{package}` is not installed"));
+            }
+        }
+        let mut status = TargetStatus::new(self.name(), None);
+        status.installed = drift.is_empty();
+        status.drift = drift;
+        Ok(status)
+    }
+
+    fn updates(&self, ctx: &Context) -> anyhow::Result<Vec<super::Update>> {
+        let packages = selected_packages(&ctx.config()?.essentials);
+        super::apt_updates(&packages.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,385 @@
+//! `install firewall`: a ufw baseline from `[firewall]`.
+//!
+//! The default policies are set, SSH and the ports in `firewall.allow` are allowed in,
+//! and ufw is enabled. Rules DebKit adds carry the comment `debkit`, which is how it
+//! finds and deletes the ones dropped from the config later; rules added by hand are
+//! left alone. Only what differs from `ufw show added` is changed, so running it
+//! again changes nothing.
+//!
+//! Turning on a firewall can cut off the session that runs it, so ufw is only enabled
+//! once the user answered yes on a terminal or passed `--yes`, and dropping the SSH
+//! rule from an SSH session is refused.
+
+use std::fs;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+
+use super::{Action, Target};
+use crate::config::{FirewallConfig, FirewallPolicy};
+use crate::output::say;
+use crate::{apt, confirm, exec, privileged, system};
+
+const DEFAULT_SSH_PORT: &str = "22";
+const RULE_COMMENT: &str = "debkit";
+const UFW_CONF_PATH: &str = "/etc/ufw/ufw.conf";
+const UFW_DEFAULTS_PATH: &str = "/etc/default/ufw";
+
+#[derive(Debug, Serialize)]
+pub struct FirewallReport {
+    pub incoming: FirewallPolicy,
+    pub outgoing: FirewallPolicy,
+    pub added: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+/// A rule in `ufw show added`.
+#[derive(Debug, PartialEq, Eq)]
+struct AddedRule {
+    rule: String,
+    /// Whether it carries DebKit's comment.
+    managed: bool,
+}
+
+pub fn run(config: &FirewallConfig) -> anyhow::Result<FirewallReport> {
+    let desired = desired_rules(config, &sshd_port());
+    if config.incoming != FirewallPolicy::Allow
+        && let Some(port) = session_port()
+        && !allows_port(&desired, &port)
+    {
+        bail!(
+            "this is an SSH session on port {port}, which the firewall config does not allow and would lock out; allow {port}/tcp or run it from the console"
+        );
+    }
+    apt::install_missing(&["ufw"])?;
+
+    for (direction, policy) in [("incoming", config.incoming), ("outgoing", config.outgoing)] {
+        if default_policy(direction) != Some(policy) {
+            ufw(&["default", policy.as_str(), direction])?;
+        }
+    }
+
+    let current = added_rules()?;
+    let mut added = Vec::new();
+    for rule in &desired {
+        if !current.iter().any(|current| &current.rule == rule) {
+            ufw(&["allow", rule, "comment", RULE_COMMENT])?;
+            added.push(rule.clone());
+        }
+    }
+    let mut deleted = Vec::new();
+    for current in current.iter().filter(|current| current.managed) {
+        if !desired.contains(&current.rule) {
+            ufw(&["delete", "allow", &current.rule])?;
+            deleted.push(current.rule.clone());
+        }
+    }
+    if added.is_empty() && deleted.is_empty() {
+        say!("ufw already allows {}.", desired.join(", "));
+    }
+
+    if !enabled() {
+        if !exec::is_dry_run() && !confirm::agree("Enable the firewall now?")? {
+            bail!("the firewall was not enabled; confirm on a terminal or pass --yes");
+        }
+        // --force skips ufw's own question, which `confirm` has asked already.
+        ufw(&["--force", "enable"])?;
+        say!(
+            "Firewall enabled: incoming {}, outgoing {}.",
+            config.incoming,
+            config.outgoing
+        );
+    }
+    Ok(FirewallReport {
+        incoming: config.incoming,
+        outgoing: config.outgoing,
+        added,
+        deleted,
+    })
+}
+
+/// Disables ufw and removes it. Its rules stay in `/etc/ufw` with the package's
+/// configuration.
+pub fn uninstall() -> anyhow::Result<()> {
+    if !apt::package_installed("ufw")? {
+        say!("ufw is not installed.");
+        return Ok(());
+    }
+    if enabled() {
+        ufw(&["--force", "disable"])?;
+    }
+    apt::remove(&["ufw"])
+}
+
+/// The rules `config` asks for, in ufw's own spelling, with SSH on `ssh_port`.
+fn desired_rules(config: &FirewallConfig, ssh_port: &str) -> Vec<String> {
+    let mut rules = Vec::new();
+    if config.allow_ssh {
+        rules.push(format!("{ssh_port}/tcp"));
+    }
+    for rule in &config.allow {
+        if !rules.contains(rule) {
+            rules.push(rule.clone());
+        }
+    }
+    rules
+}
+
+/// Whether `rules`, spelled as in `firewall.allow`, let TCP connections to `port` in:
+/// a rule for the port or for a range such as `1000:2000/tcp` that holds it, for TCP
+/// or any protocol. `firewall.allow` takes no app profiles such as `OpenSSH`, and
+/// rules added to ufw by hand are not counted.
+fn allows_port(rules: &[String], port: &str) -> bool {
+    let Ok(port) = port.parse::<u16>() else {
+        return false;
+    };
+    rules.iter().any(|rule| {
+        let (ports, protocol) = rule.split_once('/').unwrap_or((rule, ""));
+        let (first, last) = ports.split_once(':').unwrap_or((ports, ports));
+        matches!(protocol, "" | "tcp")
+            && match (first.parse::<u16>(), last.parse::<u16>()) {
+                (Ok(first), Ok(last)) => (first..=last).contains(&port),
+                _ => false,
+            }
+    })
+}
+
+/// The port sshd listens on according to `sshd -T`, which needs root; 22 when it
+/// cannot tell.
+fn sshd_port() -> String {
+    exec::query(Command::new("sshd").arg("-T"))
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| parse_sshd_port(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_else(|| DEFAULT_SSH_PORT.to_string())
+}
+
+/// The first `port` of `sshd -T`'s settings.
+fn parse_sshd_port(settings: &str) -> Option<String> {
+    settings
+        .lines()
+        .find_map(|line| line.strip_prefix("port "))
+        .map(|port| port.trim().to_string())
+}
+
+/// The server port of the SSH session DebKit runs in, the fourth field of
+/// `SSH_CONNECTION`; `None` outside SSH.
+fn session_port() -> Option<String> {
+    parse_session_port(&std::env::var("SSH_CONNECTION").ok()?)
+}
+
+fn parse_session_port(connection: &str) -> Option<String> {
+    connection.split_whitespace().nth(3).map(str::to_string)
+}
+
+fn ufw(args: &[&str]) -> anyhow::Result<()> {
+    privileged::run("ufw", args)
+}
+
+/// The rules from `ufw show added`, which lists them whether or not ufw is enabled.
+/// Needs root; a dry run without ufw installed yet has none.
+fn added_rules() -> anyhow::Result<Vec<AddedRule>> {
+    let output = match exec::query(privileged::command("ufw")?.args(["show", "added"])) {
+        Ok(output) => output,
+        Err(_) if exec::is_dry_run() => return Ok(Vec::new()),
+        Err(err) => return Err(err).context("failed to run `ufw show added`"),
+    };
+    if !output.status.success() {
+        if exec::is_dry_run() {
+            return Ok(Vec::new());
+        }
+        bail!(
+            "`ufw show added` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(parse_added(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Reads the `ufw allow ...` lines of `ufw show added`. Other rules, such as `deny` or
+/// `limit`, are not DebKit's to manage.
+fn parse_added(listing: &str) -> Vec<AddedRule> {
+    let comment = format!(" comment '{RULE_COMMENT}'");
+    listing
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("ufw allow "))
+        .map(|rule| match rule.strip_suffix(&comment) {
+            Some(rule) => AddedRule {
+                rule: rule.to_string(),
+                managed: true,
+            },
+            None => AddedRule {
+                rule: rule.to_string(),
+                managed: false,
+            },
+        })
+        .collect()
+}
+
+fn enabled() -> bool {
+    fs::read_to_string(UFW_CONF_PATH)
+        .is_ok_and(|conf| setting(&conf, "ENABLED").as_deref() == Some("yes"))
+}
+
+/// The default policy for `direction` from `/etc/default/ufw`.
+fn default_policy(direction: &str) -> Option<FirewallPolicy> {
+    let key = match direction {
+        "incoming" => "DEFAULT_INPUT_POLICY",
+        _ => "DEFAULT_OUTPUT_POLICY",
+    };
+    let defaults = fs::read_to_string(UFW_DEFAULTS_PATH).ok()?;
+    match setting(&defaults, key)?.as_str() {
+        "DROP" => Some(FirewallPolicy::Deny),
+        "REJECT" => Some(FirewallPolicy::Reject),
+        "ACCEPT" => Some(FirewallPolicy::Allow),
+        _ => None,
+    }
+}
+
+/// The value of a `KEY=value` line of a shell-style settings file.
+fn setting(contents: &str, key: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let value = line.trim().strip_prefix(key)?.strip_prefix('=')?;
+        Some(value.trim().trim_matches('"').to_string())
+    })
+}
+
+pub struct FirewallTarget;
+
+impl Target for FirewallTarget {
+    fn name(&self) -> &'static str {
+        "firewall"
+    }
+
+    fn description(&self) -> &'static str {
+        "ufw with default policies, SSH and the ports in firewall.allow"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["ufw"]
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall)
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let report = run(&ctx.config()?.firewall)?;
+        ctx.report(&report)
+    }
+
+    fn uninstall(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall()
+    }
+
+    fn version(&self) -> Option<String> {
+        apt::package_version("ufw")
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(&["ufw"])
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let config = &ctx.config()?.firewall;
+        let status = super::TargetStatus::new(self.name(), self.version());
+        if !status.installed {
+            return Ok(status);
+        }
+        let mut drift = Vec::new();
+        if !enabled() {
+            drift.push("ufw is not enabled".to_string());
+        }
+        for (direction, policy) in [("incoming", config.incoming), ("outgoing", config.outgoing)] {
+            if default_policy(direction) != Some(policy) {
+                drift.push(format!("the default {direction} policy is not {policy}"));
+            }
+        }
+        // Reading the rules needs root; without it only the above is checked.
+        if system::is_root() {
+            let current = added_rules()?;
+            let desired = desired_rules(config, &sshd_port());
+            drift.extend(
+                desired
+                    .iter()
+                    .filter(|rule| !current.iter().any(|current| &&current.rule == rule))
+                    .map(|rule| format!("{rule} is not allowed")),
+            );
+            drift.extend(
+                current
+                    .iter()
+                    .filter(|current| current.managed && !desired.contains(&current.rule))
+                    .map(|current| format!("{} is allowed but not configured", current.rule)),
+            );
+        }
+        Ok(status.with_drift(drift))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_added_rules_and_policies() {
+        let listing = "Added user rules (see 'ufw status' for running firewall):\n\
+                       ufw allow 22/tcp comment 'debkit'\n\
+                       ufw allow from 192.168.1.0/24\n\
+                       ufw limit 2222/tcp\n\
+                       ufw allow 8080 comment 'debkit'\n";
+        assert_eq!(
+            parse_added(listing),
+            [
+                AddedRule {
+                    rule: "22/tcp".to_string(),
+                    managed: true
+                },
+                AddedRule {
+                    rule: "from 192.168.1.0/24".to_string(),
+                    managed: false
+                },
+                AddedRule {
+                    rule: "8080".to_string(),
+                    managed: true
+                },
+            ]
+        );
+
+        let config = FirewallConfig {
+            allow: vec!["443/tcp".to_string(), "22/tcp".to_string()],
+            ..FirewallConfig::default()
+        };
+        assert_eq!(desired_rules(&config, "22"), ["22/tcp", "443/tcp"]);
+        assert_eq!(
+            desired_rules(&config, "2222"),
+            ["2222/tcp", "443/tcp", "22/tcp"]
+        );
+
+        assert_eq!(
+            parse_session_port("192.168.1.20 51234 192.168.1.5 2222").as_deref(),
+            Some("2222")
+        );
+        assert_eq!(parse_session_port("192.168.1.20 51234"), None);
+        assert_eq!(
+            parse_sshd_port("port 2222\nport 22\naddressfamily any\n").as_deref(),
+            Some("2222")
+        );
+        let rules = ["443/tcp".to_string(), "2222".to_string()];
+        assert!(allows_port(&rules, "2222"));
+        assert!(!allows_port(&rules, "22"));
+        assert!(!allows_port(&["22/udp".to_string()], "22"));
+        let ranges = ["1000:2000/tcp".to_string(), "3000:4000/udp".to_string()];
+        assert!(allows_port(&ranges, "1000"));
+        assert!(allows_port(&ranges, "2000"));
+        assert!(!allows_port(&ranges, "2001"));
+        assert!(!allows_port(&ranges, "3022"));
+
+        let defaults = "IPV6=yes\nDEFAULT_INPUT_POLICY=\"DROP\"\n";
+        assert_eq!(
+            setting(defaults, "DEFAULT_INPUT_POLICY").as_deref(),
+            Some("DROP")
+        );
+        assert_eq!(setting(defaults, "DEFAULT_OUTPUT_POLICY"), None);
+    }
+}
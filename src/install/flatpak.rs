@@ -0,0 +1,281 @@
+//! `install flatpak`: Flatpak with the Flathub remote, and the apps in
+//! `flatpak.apps` installed from it.
+//!
+//! Remote and apps are installed system-wide, for every user, through root or sudo.
+//! `status flatpak` lists each configured app with its installed version.
+
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use anyhow::bail;
+use serde::Serialize;
+
+use super::{Action, Target};
+use crate::config::FlatpakConfig;
+use crate::output::{self, say};
+use crate::{apt, exec, offline, privileged};
+
+const FLATHUB: &str = "flathub";
+const FLATHUB_URL: &str = "https://dl.flathub.org/repo/flathub.flatpakrepo";
+
+#[derive(Debug, Serialize)]
+pub struct FlatpakStatus {
+    pub version: Option<String>,
+    pub flathub: bool,
+    pub apps: Vec<AppStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AppStatus {
+    pub id: String,
+    pub installed: bool,
+    pub version: Option<String>,
+}
+
+impl FlatpakStatus {
+    fn drift(&self) -> Vec<String> {
+        let mut drift = Vec::new();
+        if !self.flathub {
+            drift.push(format!("the {FLATHUB} remote is missing"));
+        }
+        drift.extend(
+            self.apps
+                .iter()
+                .filter(|app| !app.installed)
+                .map(|app| format!("{} is not installed", app.id)),
+        );
+        drift
+    }
+}
+
+pub fn run(config: &FlatpakConfig) -> anyhow::Result<FlatpakStatus> {
+    apt::install_missing(&["flatpak"])?;
+    if !has_flathub() {
+        if offline::is_enabled() {
+            bail!("adding the {FLATHUB} remote needs network access");
+        }
+        privileged::run(
+            "flatpak",
+            &[
+                "remote-add",
+                "--system",
+                "--if-not-exists",
+                FLATHUB,
+                FLATHUB_URL,
+            ],
+        )?;
+    }
+
+    let installed = installed_apps();
+    let missing = config
+        .apps
+        .iter()
+        .map(String::as_str)
+        .filter(|app| !installed.contains_key(*app))
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        say!("All {} Flathub apps are installed.", config.apps.len());
+    } else {
+        if offline::is_enabled() {
+            bail!(
+                "installing {} from {FLATHUB} needs network access",
+                missing.join(", ")
+            );
+        }
+        let mut args = vec!["install", "--system", "--noninteractive", "-y", FLATHUB];
+        args.extend(&missing);
+        privileged::run("flatpak", &args)?;
+        say!("Installed from {FLATHUB}: {}", missing.join(", "));
+    }
+    Ok(collect_status(config))
+}
+
+/// Uninstalls the configured apps. Flatpak and the Flathub remote stay for apps
+/// installed by hand.
+pub fn uninstall(config: &FlatpakConfig) -> anyhow::Result<()> {
+    let installed = installed_apps();
+    let present = config
+        .apps
+        .iter()
+        .map(String::as_str)
+        .filter(|app| installed.contains_key(*app))
+        .collect::<Vec<_>>();
+    if present.is_empty() {
+        say!("None of the apps in `flatpak.apps` are installed.");
+    } else {
+        let mut args = vec!["uninstall", "--system", "--noninteractive", "-y"];
+        args.extend(&present);
+        privileged::run("flatpak", &args)?;
+        say!("Uninstalled {}", present.join(", "));
+    }
+    say!("Kept flatpak and the {FLATHUB} remote.");
+    Ok(())
+}
+
+pub fn collect_status(config: &FlatpakConfig) -> FlatpakStatus {
+    let installed = installed_apps();
+    FlatpakStatus {
+        version: apt::package_version("flatpak"),
+        flathub: has_flathub(),
+        apps: config
+            .apps
+            .iter()
+            .map(|id| AppStatus {
+                id: id.clone(),
+                installed: installed.contains_key(id),
+                version: installed.get(id).cloned().flatten(),
+            })
+            .collect(),
+    }
+}
+
+fn print_status_report(status: &FlatpakStatus) {
+    say!(
+        "flatpak: {}",
+        status.version.as_deref().unwrap_or("not installed")
+    );
+    say!(
+        "{FLATHUB}: {}",
+        if status.flathub { "added" } else { "missing" }
+    );
+    if status.apps.is_empty() {
+        say!("No apps listed in `flatpak.apps`.");
+        return;
+    }
+    let width = status
+        .apps
+        .iter()
+        .map(|app| app.id.len())
+        .max()
+        .unwrap_or(0)
+        .max("APP".len());
+    say!("{:<width$}  {:<9} VERSION", "APP", "STATUS");
+    for app in &status.apps {
+        let state = if app.installed {
+            "installed"
+        } else {
+            "missing"
+        };
+        say!(
+            "{:<width$}  {state:<9} {}",
+            app.id,
+            app.version.as_deref().unwrap_or("-")
+        );
+    }
+}
+
+fn has_flathub() -> bool {
+    flatpak_lines(&["remotes", "--system", "--columns=name"])
+        .iter()
+        .any(|remote| remote.trim() == FLATHUB)
+}
+
+/// The system-wide apps by ID, with their version when the app declares one.
+fn installed_apps() -> BTreeMap<String, Option<String>> {
+    parse_apps(
+        &flatpak_lines(&["list", "--app", "--system", "--columns=application,version"]).join("\n"),
+    )
+}
+
+fn flatpak_lines(args: &[&str]) -> Vec<String> {
+    exec::query(Command::new("flatpak").args(args))
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Reads the tab-separated `application` and `version` columns of `flatpak list`.
+fn parse_apps(list: &str) -> BTreeMap<String, Option<String>> {
+    list.lines()
+        .filter_map(|line| {
+            let mut columns = line.split('\t').map(str::trim);
+            let id = columns.next().filter(|id| !id.is_empty())?;
+            if id == "Application ID" {
+                return None;
+            }
+            let version = columns
+                .next()
+                .filter(|version| !version.is_empty())
+                .map(str::to_string);
+            Some((id.to_string(), version))
+        })
+        .collect()
+}
+
+pub struct FlatpakTarget;
+
+impl Target for FlatpakTarget {
+    fn name(&self) -> &'static str {
+        "flatpak"
+    }
+
+    fn description(&self) -> &'static str {
+        "Flatpak with Flathub and the apps in flatpak.apps"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall | Action::Status)
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let status = run(&ctx.config()?.flatpak)?;
+        ctx.report(&status)
+    }
+
+    fn uninstall(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall(&ctx.config()?.flatpak)
+    }
+
+    fn status(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let status = collect_status(&ctx.config()?.flatpak);
+        if !output::is_json() {
+            print_status_report(&status);
+        }
+        ctx.report(&status)?;
+        if !status.drift().is_empty() {
+            return Err(output::Drift.into());
+        }
+        Ok(())
+    }
+
+    fn version(&self) -> Option<String> {
+        apt::package_version("flatpak")
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(&["flatpak"])
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let status = collect_status(&ctx.config()?.flatpak);
+        let summary = super::TargetStatus::new(self.name(), status.version.clone());
+        if !summary.installed {
+            return Ok(summary);
+        }
+        Ok(summary.with_drift(status.drift()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_installed_apps() {
+        let apps = parse_apps(
+            "Application ID\tVersion\norg.mozilla.firefox\t131.0\ncom.valvesoftware.Steam\t\n",
+        );
+        assert_eq!(
+            apps.get("org.mozilla.firefox"),
+            Some(&Some("131.0".to_string()))
+        );
+        assert_eq!(apps.get("com.valvesoftware.Steam"), Some(&None));
+        assert_eq!(apps.len(), 2);
+    }
+}
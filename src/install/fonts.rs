@@ -0,0 +1,410 @@
+//! `install fonts`: font packages from APT and font archives from the web.
+//!
+//! `fonts.packages` are installed with apt. Each archive in `fonts.archives`, such as
+//! a Nerd Fonts release, is downloaded and unpacked in a private scratch directory, and its
+//! `.ttf`, `.otf` and `.ttc` files are copied into a folder named after the archive
+//! under `~/.local/share/fonts`, or `/usr/local/share/fonts` with `scope = "system"`.
+//! An archive whose folder already has files is not downloaded again. Afterwards
+//! `fc-cache` rebuilds the font cache and the font families that became available are
+//! listed.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+
+use super::user::{
+    UserContext, command_as, ensure_owned_writable_dir, ensure_owned_writable_file,
+    target_user_context,
+};
+use super::{Action, Target};
+use crate::config::{FONT_ARCHIVE_SUFFIXES, FontScope, FontsConfig};
+use crate::output::say;
+use crate::{apt, download, exec, privileged, scratch, system};
+
+const SYSTEM_FONTS_DIR: &str = "/usr/local/share/fonts";
+const FONT_EXTENSIONS: &[&str] = &["ttf", "otf", "ttc"];
+
+#[derive(Debug, Serialize)]
+pub struct FontsReport {
+    pub directory: PathBuf,
+    /// Families `fc-list` shows now that it did not before.
+    pub new_families: Vec<String>,
+}
+
+pub fn run(config: &FontsConfig) -> anyhow::Result<FontsReport> {
+    if config.packages.is_empty() && config.archives.is_empty() {
+        bail!("list the fonts to install in `fonts.packages` or `fonts.archives`");
+    }
+    let user = target_user_context()?;
+    let mut packages = vec!["fontconfig"];
+    if config.archives.iter().any(|url| url.ends_with(".zip")) {
+        packages.push("unzip");
+    }
+    if config.archives.iter().any(|url| url.ends_with(".tar.xz")) {
+        packages.push("xz-utils");
+    }
+    packages.extend(config.packages.iter().map(String::as_str));
+    apt::install_missing(&packages)?;
+
+    let before = font_families(&user);
+    let directory = fonts_dir(config, &user);
+    for url in &config.archives {
+        install_archive(config, &user, url, &directory)?;
+    }
+    refresh_cache(config, &user)?;
+
+    let new_families = font_families(&user)
+        .difference(&before)
+        .cloned()
+        .collect::<Vec<_>>();
+    // A dry run installs nothing for fc-list to show.
+    if new_families.is_empty() && !exec::is_dry_run() {
+        say!("No new font families; the configured fonts were already available.");
+    } else if !new_families.is_empty() {
+        say!("New font families:");
+        for family in &new_families {
+            say!("  {family}");
+        }
+    }
+    Ok(FontsReport {
+        directory,
+        new_families,
+    })
+}
+
+/// Deletes the fonts installed from `fonts.archives`. Font packages stay: other
+/// packages, such as the desktop, often depend on them.
+pub fn uninstall(config: &FontsConfig) -> anyhow::Result<()> {
+    let user = target_user_context()?;
+    let directory = fonts_dir(config, &user);
+    let mut removed = false;
+    for url in &config.archives {
+        let Some(name) = archive_name(url) else {
+            continue;
+        };
+        let dest = directory.join(name);
+        if !dest.exists() {
+            continue;
+        }
+        if config.scope == FontScope::System {
+            privileged::run("rm", &["-rf", &dest.to_string_lossy()])?;
+        } else {
+            exec::remove_dir_all(&dest)
+                .with_context(|| format!("failed to remove {}", dest.display()))?;
+        }
+        say!("Removed {}", dest.display());
+        removed = true;
+    }
+    if removed {
+        refresh_cache(config, &user)?;
+    } else {
+        say!("No fonts from `fonts.archives` are installed.");
+    }
+    if !config.packages.is_empty() {
+        say!(
+            "Kept the font packages {}; remove them with apt if nothing else needs them.",
+            config.packages.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn fonts_dir(config: &FontsConfig, user: &UserContext) -> PathBuf {
+    if config.scope == FontScope::System {
+        PathBuf::from(SYSTEM_FONTS_DIR)
+    } else {
+        user.home.join(".local").join("share").join("fonts")
+    }
+}
+
+/// The folder name for the fonts of `url`: its file name without the archive suffix,
+/// such as `JetBrainsMono` for `.../JetBrainsMono.tar.xz`.
+fn archive_name(url: &str) -> Option<&str> {
+    let file = url.rsplit('/').next()?;
+    FONT_ARCHIVE_SUFFIXES
+        .iter()
+        .find_map(|suffix| file.strip_suffix(suffix))
+        .filter(|name| !name.is_empty() && !name.starts_with('.'))
+}
+
+fn install_archive(
+    config: &FontsConfig,
+    user: &UserContext,
+    url: &str,
+    directory: &Path,
+) -> anyhow::Result<()> {
+    let name = archive_name(url).with_context(|| format!("{url} is not a font archive"))?;
+    let dest = directory.join(name);
+    if fs::read_dir(&dest).is_ok_and(|mut entries| entries.next().is_some()) {
+        say!("Fonts from {url} are already in {}", dest.display());
+        return Ok(());
+    }
+    let tool = if url.ends_with(".zip") {
+        "unzip"
+    } else {
+        "tar"
+    };
+    if exec::is_dry_run() {
+        download::save(url, &archive_path(url, &std::env::temp_dir()))?;
+        let pending = if system::command_available(tool) {
+            String::new()
+        } else {
+            format!(" once {tool} is installed")
+        };
+        exec::announce_change(
+            &format!(
+                "would unpack the fonts of {url} into {}{pending}",
+                dest.display()
+            ),
+            // System fonts are copied with sudo, which the confirmation prompt asks about.
+            config.scope == FontScope::System,
+        );
+        return Ok(());
+    }
+
+    let scratch = scratch::create("fonts")?;
+    let fonts = unpack(url, tool, &scratch);
+    let written = fonts.and_then(|fonts| {
+        if fonts.is_empty() {
+            bail!("{url} holds no .ttf, .otf or .ttc fonts");
+        }
+        copy_fonts(config, user, &fonts, &dest)?;
+        Ok(fonts.len())
+    });
+    let _ = fs::remove_dir_all(&scratch);
+    say!(
+        "Installed {} fonts from {url} into {}",
+        written?,
+        dest.display()
+    );
+    Ok(())
+}
+
+/// Where the archive at `url` is downloaded to under `scratch`.
+fn archive_path(url: &str, scratch: &Path) -> PathBuf {
+    scratch.join(url.rsplit('/').next().unwrap_or("fonts"))
+}
+
+/// Downloads the archive at `url` and unpacks it under `scratch`, returning the font
+/// files it holds. Dry runs stop before this in [`install_archive`].
+fn unpack(url: &str, tool: &str, scratch: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let file = archive_path(url, scratch);
+    download::save(url, &file)?;
+    let unpacked = scratch.join("fonts");
+    fs::create_dir_all(&unpacked)
+        .with_context(|| format!("failed to create {}", unpacked.display()))?;
+
+    let mut command = Command::new(tool);
+    if tool == "unzip" {
+        command.arg("-qo").arg(&file).arg("-d").arg(&unpacked);
+    } else {
+        command.arg("-xf").arg(&file).arg("-C").arg(&unpacked);
+    }
+    let output = exec::output_step(&mut command, &format!("Unpacking {url}"))
+        .with_context(|| format!("failed to run `{tool}`"))?;
+    if !output.status.success() {
+        bail!(
+            "could not unpack {url}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let mut fonts = Vec::new();
+    collect_fonts(&unpacked, &mut fonts)?;
+    fonts.sort();
+    Ok(fonts)
+}
+
+fn collect_fonts(dir: &Path, fonts: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_fonts(&path, fonts)?;
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| FONT_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        {
+            fonts.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn copy_fonts(
+    config: &FontsConfig,
+    user: &UserContext,
+    fonts: &[PathBuf],
+    dest: &Path,
+) -> anyhow::Result<()> {
+    let system = config.scope == FontScope::System;
+    if system {
+        privileged::create_dir_all(dest)?;
+    } else {
+        exec::create_dir_all(dest)
+            .with_context(|| format!("failed to create {}", dest.display()))?;
+        for dir in dest
+            .ancestors()
+            .take_while(|dir| *dir != user.home && dir.starts_with(&user.home))
+        {
+            ensure_owned_writable_dir(dir, user)?;
+        }
+    }
+    for font in fonts {
+        let target = dest.join(font.file_name().context("font without a file name")?);
+        let contents =
+            fs::read(font).with_context(|| format!("failed to read {}", font.display()))?;
+        if system {
+            privileged::write(&target, &contents)?;
+        } else {
+            exec::write(&target, contents)
+                .with_context(|| format!("failed to write {}", target.display()))?;
+            ensure_owned_writable_file(&target, user)?;
+        }
+    }
+    Ok(())
+}
+
+fn refresh_cache(config: &FontsConfig, user: &UserContext) -> anyhow::Result<()> {
+    if config.scope == FontScope::System {
+        return privileged::run("fc-cache", &["-f"]);
+    }
+    let status = exec::status(command_as(user, "fc-cache")?.arg("-f"))
+        .context("failed to run `fc-cache`")?;
+    if !status.success() {
+        bail!("fc-cache -f failed with status {status}");
+    }
+    Ok(())
+}
+
+/// The font families the target user sees, as `fc-list` lists them.
+fn font_families(user: &UserContext) -> BTreeSet<String> {
+    command_as(user, "fc-list")
+        .ok()
+        .and_then(|mut command| exec::query(command.args([":", "family"])).ok())
+        .filter(|output| output.status.success())
+        .map(|output| parse_families(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default()
+}
+
+/// One family per name in `fc-list : family` output, where a line may list several,
+/// such as `DejaVu Sans,DejaVu Sans Condensed`.
+fn parse_families(list: &str) -> BTreeSet<String> {
+    list.lines()
+        .flat_map(|line| line.split(','))
+        .map(|family| family.trim().replace("\\-", "-"))
+        .filter(|family| !family.is_empty())
+        .collect()
+}
+
+pub struct FontsTarget;
+
+impl Target for FontsTarget {
+    fn name(&self) -> &'static str {
+        "fonts"
+    }
+
+    fn description(&self) -> &'static str {
+        "Font packages and font archives such as Nerd Fonts"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall)
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let report = run(&ctx.config()?.fonts)?;
+        ctx.report(&report)
+    }
+
+    fn uninstall(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall(&ctx.config()?.fonts)
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let config = &ctx.config()?.fonts;
+        if config.packages.is_empty() && config.archives.is_empty() {
+            return Ok(super::TargetStatus::disabled(self.name()));
+        }
+        let directory = fonts_dir(config, &target_user_context()?);
+        let mut drift = Vec::new();
+        for package in &config.packages {
+            if !apt::package_installed(package)? {
+                drift.push(format!("{package} is not installed"));
+            }
+        }
+        for url in &config.archives {
+            let dest = archive_name(url).map(|name| directory.join(name));
+            if !dest.is_some_and(|dest| dest.is_dir()) {
+                drift.push(format!("fonts from {url} are not installed"));
+            }
+        }
+        let mut status = super::TargetStatus::new(self.name(), None);
+        status.installed = drift.is_empty();
+        Ok(status.with_drift(drift))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn names_archives_and_lists_families() {
+        assert_eq!(
+            archive_name(
+                "https://github.com/ryanoasis/nerd-fonts/releases/download/v3.2.1/JetBrainsMono.tar.xz"
+            ),
+            Some("JetBrainsMono")
+        );
+        assert_eq!(
+            archive_name("https://example.com/fonts/FiraCode.zip"),
+            Some("FiraCode")
+        );
+        assert_eq!(archive_name("https://example.com/fonts/.zip"), None);
+        assert_eq!(archive_name("https://example.com/font.ttf"), None);
+
+        let families = parse_families(
+            "DejaVu Sans,DejaVu Sans Condensed\nJetBrainsMono Nerd Font\nDejaVu Sans\n",
+        );
+        assert_eq!(
+            families.into_iter().collect::<Vec<_>>(),
+            [
+                "DejaVu Sans",
+                "DejaVu Sans Condensed",
+                "JetBrainsMono Nerd Font"
+            ]
+        );
+    }
+
+    #[test]
+    fn dry_run_announces_archives_without_downloading_them() {
+        let user = UserContext {
+            name: None,
+            home: PathBuf::from("/nonexistent/debkit-fonts-home"),
+            uid: None,
+            gid: None,
+        };
+        let directory = user.home.join(".local/share/fonts");
+        let recorder = Arc::new(Recorder::new());
+        runner::with(recorder.clone(), || {
+            exec::dry_run(|| {
+                install_archive(
+                    &FontsConfig::default(),
+                    &user,
+                    "https://example.com/fonts/FiraCode.zip",
+                    &directory,
+                )
+            })
+        })
+        .unwrap();
+        assert!(recorder.calls().is_empty());
+    }
+}
@@ -1,80 +1,489 @@
-use anyhow::Context;
+use anyhow::{Context as _, anyhow, bail};
+use clap_complete::{ArgValueCandidates, CompletionCandidate};
+use serde::Serialize;
+use tracing::{error, warn};
 
-use crate::config::DebkitConfig;
+use super::{Action, Context, Target, TargetStatus};
+use crate::config::{self, DebkitConfig};
+use crate::output::say;
+use crate::{exec, remote};
 
-pub fn run(config: &DebkitConfig) -> anyhow::Result<()> {
-    if config.foundation.install.is_empty() {
-        println!("No foundation install targets configured (`foundation.install` is empty).");
-        return Ok(());
+pub struct FoundationTarget;
+
+impl Target for FoundationTarget {
+    fn name(&self) -> &'static str {
+        "foundation"
     }
 
-    for target in &config.foundation.install {
-        match target.as_str() {
-            "codex" => {
-                println!("Installing foundation target: codex");
-                super::codex::run(config.npm.version.clone())
-                    .context("failed to install foundation target `codex`")?;
-            }
-            "essentials" | "base" | "dev-base" | "dev_base" => {
-                println!("Installing foundation target: essentials");
-                super::essentials::run(&config.essentials)
-                    .context("failed to install foundation target `essentials`")?;
-            }
-            "git" => {
-                println!("Installing foundation target: git");
-                super::git::run().context("failed to install foundation target `git`")?;
-            }
-            "npm" => {
-                println!("Installing foundation target: npm");
-                super::npm::run(super::npm::Options {
-                    version: config.npm.version.clone(),
-                })
-                .context("failed to install foundation target `npm`")?;
-            }
-            "sudo-nopass" | "sudo_nopass" | "admin-group-nopass" | "admin_group_nopass" => {
-                println!("Installing foundation target: sudo-nopass");
-                super::sudo_nopass::run(&config.sudo_nopass)
-                    .context("failed to install foundation target `sudo-nopass`")?;
-            }
-            "nis" => {
-                println!("Installing foundation target: nis");
-                super::nis::run(super::nis::Role::Configured, &config.nis)
-                    .context("failed to install foundation target `nis`")?;
-            }
-            "nis-client" | "nis_client" => {
-                println!("Installing foundation target: nis-client");
-                super::nis::run(super::nis::Role::Client, &config.nis)
-                    .context("failed to install foundation target `nis-client`")?;
-            }
-            "nis-server" | "nis_server" => {
-                println!("Installing foundation target: nis-server");
-                super::nis::run(super::nis::Role::Server, &config.nis)
-                    .context("failed to install foundation target `nis-server`")?;
-            }
-            "ripgrep" => {
-                println!("Installing foundation target: ripgrep");
-                super::ripgrep::run().context("failed to install foundation target `ripgrep`")?;
-            }
-            "rust" => {
-                println!("Installing foundation target: rust");
-                super::rust::run(super::rust::Options { reinstall: false })
-                    .context("failed to install foundation target `rust`")?;
-            }
-            "variety" => {
-                println!("Installing foundation target: variety");
-                super::variety::run(config)
-                    .context("failed to install foundation target `variety`")?;
-            }
-            "wake-on-lan" | "wake_on_lan" | "wol" => {
-                println!("Installing foundation target: wake-on-lan");
-                super::wake_on_lan::run(config)
-                    .context("failed to install foundation target `wake-on-lan`")?;
+    fn description(&self) -> &'static str {
+        "Installs configured base targets from debkit config"
+    }
+
+    fn args(&self, action: Action, command: clap::Command) -> clap::Command {
+        if action != Action::Install {
+            return command;
+        }
+        command
+            .arg(
+                clap::Arg::new("only")
+                    .value_name("TARGET")
+                    .num_args(0..)
+                    .help("Only install these entries from `foundation.install`")
+                    .add(ArgValueCandidates::new(configured_entries)),
+            )
+            .arg(
+                clap::Arg::new("keep-going")
+                    .long("keep-going")
+                    .action(clap::ArgAction::SetTrue)
+                    .help("Install the remaining targets when one fails, then summarize"),
+            )
+            .arg(
+                clap::Arg::new("host")
+                    .long("host")
+                    .value_name("USER@MACHINE")
+                    .action(clap::ArgAction::Append)
+                    .help("Install on this machine over SSH instead of locally; repeatable"),
+            )
+            .arg(
+                clap::Arg::new("hosts-file")
+                    .long("hosts-file")
+                    .value_name("PATH")
+                    .help("Install on every `user@machine` listed in this file, one per line"),
+            )
+    }
+
+    fn install(&self, ctx: &Context) -> anyhow::Result<()> {
+        let only = ctx.values("only");
+        let keep_going = ctx.flag("keep-going");
+        let mut hosts: Vec<String> = ctx.values("host").into_iter().map(String::from).collect();
+        if let Some(path) = ctx.value("hosts-file") {
+            hosts.extend(remote::read_hosts_file(std::path::Path::new(path))?);
+        }
+        if !hosts.is_empty() {
+            return install_remote(ctx, &hosts, &only, keep_going);
+        }
+
+        let installed = run(ctx.config()?, &only, keep_going)?;
+        if keep_going {
+            say!("\n{}", render_summary(&installed).trim_end());
+        }
+        ctx.report(&installed)?;
+
+        let failed: Vec<&str> = installed
+            .iter()
+            .filter(|target| !target.ok)
+            .map(|target| target.target)
+            .collect();
+        if !failed.is_empty() {
+            bail!(
+                "{} of {} foundation targets failed: {}",
+                failed.len(),
+                installed.len(),
+                failed.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    fn summary(&self, ctx: &Context) -> anyhow::Result<TargetStatus> {
+        let config = ctx.config()?;
+        let mut drift = Vec::new();
+        for step in plan(&config.foundation.install)? {
+            let target = step.target;
+            let matches = super::default_matches(target, Action::Status)?;
+            let member = target.summary(&Context::with_config(&matches, config.clone()))?;
+            if member.enabled && !member.installed {
+                drift.push(format!("`{}` is not installed", target.name()));
             }
-            other => {
-                eprintln!("warning: unsupported foundation target `{other}` in config; skipping");
+        }
+        let mut status = TargetStatus::new(self.name(), None);
+        status.installed = drift.is_empty();
+        status.drift = drift;
+        Ok(status)
+    }
+}
+
+/// Runs `install foundation` with the same arguments on each of `hosts` over SSH.
+fn install_remote(
+    ctx: &Context,
+    hosts: &[String],
+    only: &[&str],
+    keep_going: bool,
+) -> anyhow::Result<()> {
+    // Creates the config on first use, so there is always one to copy.
+    ctx.config()?;
+    let mut args = vec!["install", "foundation"];
+    args.extend(only);
+    if keep_going {
+        args.push("--keep-going");
+    }
+
+    let results = remote::run(hosts, &config::path()?, &args)?;
+    say!("");
+    for result in &results {
+        match &result.error {
+            Some(error) => say!("{}: failed: {error}", result.host),
+            None => say!("{}: ok", result.host),
+        }
+    }
+    ctx.report(&results)?;
+
+    let failed: Vec<&str> = results
+        .iter()
+        .filter(|result| !result.ok)
+        .map(|result| result.host.as_str())
+        .collect();
+    if !failed.is_empty() {
+        bail!(
+            "{} of {} hosts failed: {}",
+            failed.len(),
+            results.len(),
+            failed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct InstalledTarget {
+    pub target: &'static str,
+    pub ok: bool,
+    pub changed: bool,
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Installs the `foundation.install` entries, or just those named in `only`, after
+/// the targets they depend on. The first failure is returned as an error unless
+/// `keep_going` is set, in which case it is recorded and targets depending on the
+/// failed one are skipped.
+pub fn run(
+    config: &DebkitConfig,
+    only: &[&str],
+    keep_going: bool,
+) -> anyhow::Result<Vec<InstalledTarget>> {
+    let mut installed = Vec::new();
+    if config.foundation.install.is_empty() {
+        say!("No foundation install targets configured (`foundation.install` is empty).");
+        return Ok(installed);
+    }
+    for name in only {
+        if !config.foundation.install.iter().any(|entry| entry == name) {
+            bail!("`{name}` is not listed in `foundation.install`");
+        }
+    }
+
+    let names: Vec<&str> = config
+        .foundation
+        .install
+        .iter()
+        .map(String::as_str)
+        .filter(|name| only.is_empty() || only.contains(name))
+        .collect();
+    for step in plan(&names)? {
+        let target = step.target;
+        match step.required_by {
+            Some(dependent) => say!(
+                "Installing foundation target: {} (required by {dependent})",
+                target.name()
+            ),
+            None => say!("Installing foundation target: {}", target.name()),
+        }
+        let changes = exec::changes();
+        let result = match target
+            .dependencies()
+            .iter()
+            .find(|dependency| failed(&installed, dependency))
+        {
+            Some(dependency) => Err(anyhow!("skipped because `{dependency}` failed")),
+            None => install(target, config),
+        }
+        .with_context(|| format!("failed to install foundation target `{}`", target.name()));
+        let error = match result {
+            Ok(()) => None,
+            Err(err) if keep_going => {
+                error!("{err:#}");
+                Some(format!("{err:#}"))
             }
+            Err(err) => return Err(err),
+        };
+        installed.push(InstalledTarget {
+            target: target.name(),
+            ok: error.is_none(),
+            changed: exec::changes() > changes,
+            version: target.version(),
+            error,
+        });
+    }
+
+    Ok(installed)
+}
+
+fn install(target: &dyn Target, config: &DebkitConfig) -> anyhow::Result<()> {
+    let matches = super::default_matches(target, Action::Install)?;
+    super::run_tracked(
+        target,
+        Action::Install,
+        &Context::with_config(&matches, config.clone()),
+    )
+}
+
+fn failed(installed: &[InstalledTarget], name: &str) -> bool {
+    installed
+        .iter()
+        .any(|target| target.target == name && !target.ok)
+}
+
+fn render_summary(installed: &[InstalledTarget]) -> String {
+    let rows = installed
+        .iter()
+        .map(|target| {
+            let result = match (target.ok, target.changed) {
+                (false, _) => "failed",
+                (true, true) => "changed",
+                (true, false) => "ok",
+            };
+            [
+                target.target.to_string(),
+                result.to_string(),
+                target.version.clone().unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = ["TARGET", "RESULT", "VERSION"].map(String::from);
+
+    let mut widths = header.clone().map(|cell| cell.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
         }
     }
 
+    let mut out = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+
+    let errors = installed
+        .iter()
+        .filter_map(|target| Some((target.target, target.error.as_ref()?)))
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        out.push_str("\nFailures:\n");
+        for (target, error) in errors {
+            out.push_str(&format!("- {target}: {error}\n"));
+        }
+    }
+    out
+}
+
+pub struct Step<'a> {
+    pub target: &'a dyn Target,
+    /// The target that pulled this one in when it was not listed itself.
+    pub required_by: Option<&'static str>,
+}
+
+/// Resolves foundation entries to install targets, each once and after its
+/// dependencies, otherwise keeping the listed order. Unknown entries are skipped
+/// with a warning; unknown dependencies and dependency cycles are errors.
+pub fn plan<S: AsRef<str>>(names: &[S]) -> anyhow::Result<Vec<Step<'static>>> {
+    order(names, |name| {
+        super::find(Action::Install, name).filter(|target| target.name() != FoundationTarget.name())
+    })
+}
+
+fn order<'a, S: AsRef<str>>(
+    names: &[S],
+    lookup: impl Fn(&str) -> Option<&'a dyn Target>,
+) -> anyhow::Result<Vec<Step<'a>>> {
+    let mut steps = Vec::new();
+    for name in names {
+        let name = name.as_ref();
+        let Some(target) = lookup(name) else {
+            warn!("unsupported foundation target `{name}` in config; skipping");
+            continue;
+        };
+        visit(target, None, &lookup, &mut Vec::new(), &mut steps)?;
+    }
+    Ok(steps)
+}
+
+fn visit<'a>(
+    target: &'a dyn Target,
+    required_by: Option<&'static str>,
+    lookup: &impl Fn(&str) -> Option<&'a dyn Target>,
+    path: &mut Vec<&'static str>,
+    steps: &mut Vec<Step<'a>>,
+) -> anyhow::Result<()> {
+    let name = target.name();
+    if steps.iter().any(|step| step.target.name() == name) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|entry| *entry == name) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(name);
+        bail!(
+            "foundation targets depend on each other: {}",
+            cycle.join(" -> ")
+        );
+    }
+
+    path.push(name);
+    for dependency in target.dependencies() {
+        let Some(resolved) = lookup(dependency) else {
+            bail!("target `{name}` depends on unknown target `{dependency}`");
+        };
+        visit(resolved, Some(name), lookup, path, steps)?;
+    }
+    path.pop();
+    steps.push(Step {
+        target,
+        required_by,
+    });
     Ok(())
 }
+
+/// Completion candidates for `install foundation`, read from the existing config so
+/// pressing <TAB> never creates one.
+fn configured_entries() -> Vec<CompletionCandidate> {
+    config::path()
+        .and_then(|path| config::load(&path))
+        .map(|config| {
+            config
+                .foundation
+                .install
+                .into_iter()
+                .map(CompletionCandidate::new)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fake(&'static str, &'static [&'static str]);
+
+    impl Target for Fake {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn description(&self) -> &'static str {
+            "test target"
+        }
+
+        fn dependencies(&self) -> &'static [&'static str] {
+            self.1
+        }
+    }
+
+    const FAKES: &[Fake] = &[
+        Fake("rust", &[]),
+        Fake("cargo-tools", &["rust"]),
+        Fake("npm", &[]),
+        Fake("codex", &["npm"]),
+        Fake("a", &["b"]),
+        Fake("b", &["c"]),
+        Fake("c", &["a"]),
+        Fake("broken", &["missing"]),
+    ];
+
+    fn names(list: &[&str]) -> anyhow::Result<Vec<(&'static str, Option<&'static str>)>> {
+        let steps = order(list, |name| {
+            FAKES
+                .iter()
+                .find(|fake| fake.0 == name)
+                .map(|fake| fake as &dyn Target)
+        })?;
+        Ok(steps
+            .iter()
+            .map(|step| (step.target.name(), step.required_by))
+            .collect())
+    }
+
+    #[test]
+    fn installs_dependencies_first_and_once() {
+        assert_eq!(
+            names(&["cargo-tools", "codex", "rust", "npm", "codex"]).unwrap(),
+            [
+                ("rust", Some("cargo-tools")),
+                ("cargo-tools", None),
+                ("npm", Some("codex")),
+                ("codex", None),
+            ]
+        );
+        assert_eq!(
+            names(&["npm", "unknown", "rust"]).unwrap(),
+            [("npm", None), ("rust", None)]
+        );
+    }
+
+    #[test]
+    fn reports_cycles_and_unknown_dependencies() {
+        let err = names(&["rust", "a"]).unwrap_err().to_string();
+        assert!(err.contains("a -> b -> c -> a"), "{err}");
+        let err = names(&["broken"]).unwrap_err().to_string();
+        assert!(err.contains("unknown target `missing`"), "{err}");
+    }
+
+    #[test]
+    fn renders_summary_with_failures() {
+        let installed = [
+            InstalledTarget {
+                target: "git",
+                ok: true,
+                changed: false,
+                version: Some("git version 2.47.3".to_string()),
+                error: None,
+            },
+            InstalledTarget {
+                target: "npm",
+                ok: false,
+                changed: true,
+                version: None,
+                error: Some("failed to install foundation target `npm`: offline".to_string()),
+            },
+            InstalledTarget {
+                target: "rust",
+                ok: true,
+                changed: true,
+                version: None,
+                error: None,
+            },
+        ];
+        assert_eq!(
+            render_summary(&installed),
+            "\
+TARGET  RESULT   VERSION
+git     ok       git version 2.47.3
+npm     failed   -
+rust    changed  -
+
+Failures:
+- npm: failed to install foundation target `npm`: offline
+"
+        );
+    }
+
+    #[test]
+    fn registered_dependencies_resolve() {
+        for target in super::super::targets() {
+            for dependency in target.dependencies() {
+                assert!(
+                    super::super::find(Action::Install, dependency).is_some(),
+                    "`{}` depends on unknown target `{dependency}`",
+                    target.name()
+                );
+            }
+        }
+    }
+}
@@ -2,49 +2,70 @@ use std::process::Command;
 
 use anyhow::{Context, bail};
 
+use super::Target;
+use crate::output::say;
+use crate::{exec, system};
+
 pub fn run() -> anyhow::Result<()> {
-    if command_available("git") {
-        println!("git already installed:");
+    if system::command_available("git") {
+        say!("git already installed:");
         run_command("git", &["--version"])?;
         return Ok(());
     }
 
     install_git_package()?;
+    if exec::is_dry_run() {
+        return Ok(());
+    }
 
-    if !command_available("git") {
+    if !system::command_available("git") {
         bail!("`git` was not found on PATH after installation");
     }
 
-    println!("git installation complete:");
+    say!("git installation complete:");
     run_command("git", &["--version"])?;
 
     Ok(())
 }
 
 fn install_git_package() -> anyhow::Result<()> {
-    super::apt::install_missing(&["git"])?;
+    crate::apt::install_missing(&["git"])?;
     Ok(())
 }
 
 fn run_command(program: &str, args: &[&str]) -> anyhow::Result<()> {
-    let status = Command::new(program)
-        .args(args)
-        .status()
+    let status = exec::probe(Command::new(program).args(args))
         .with_context(|| format!("failed to start `{program}`"))?;
     if !status.success() {
         bail!(
-            "command `{}` failed with status {}",
-            format!("{program} {}", args.join(" ")),
+            "command `{program} {}` failed with status {}",
+            args.join(" "),
             status
         );
     }
     Ok(())
 }
 
-fn command_available(program: &str) -> bool {
-    Command::new(program)
-        .arg("--version")
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false)
+pub struct GitTarget;
+
+impl Target for GitTarget {
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn description(&self) -> &'static str {
+        "Git version control via apt"
+    }
+
+    fn install(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        run()
+    }
+
+    fn version(&self) -> Option<String> {
+        super::program_version("git", &["--version"])
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(&["git"])
+    }
 }
@@ -1,10 +1,13 @@
 use std::env;
-use std::fs::{self, OpenOptions};
-use std::io::Write;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use anyhow::Context;
 
+use super::{Action, Target, shell_init};
+use crate::output::say;
+use crate::{backup, exec};
+
 const PROMPT_FILENAME: &str = ".git-prompt.sh";
 const PROMPT_CONTENT: &str = r#"# Enable git prompt
 if [ -f /usr/lib/git-core/git-sh-prompt ]; then
@@ -23,7 +26,7 @@ pub fn run() -> anyhow::Result<()> {
     let bashrc_path = home.join(".bashrc");
 
     if let Some(parent) = prompt_path.parent() {
-        fs::create_dir_all(parent)
+        exec::create_dir_all(parent)
             .with_context(|| format!("failed to create {}", parent.display()))?;
     }
 
@@ -31,54 +34,124 @@ pub fn run() -> anyhow::Result<()> {
 
     let existing_prompt = fs::read_to_string(&prompt_path).unwrap_or_default();
     if existing_prompt != PROMPT_CONTENT {
-        fs::write(&prompt_path, PROMPT_CONTENT)
+        exec::write(&prompt_path, PROMPT_CONTENT)
             .with_context(|| format!("failed to write {}", prompt_path.display()))?;
         changed = true;
     }
 
-    if !bashrc_path.exists() {
-        fs::write(&bashrc_path, "")
-            .with_context(|| format!("failed to create {}", bashrc_path.display()))?;
-    }
-
-    let bashrc_content = fs::read_to_string(&bashrc_path)
-        .with_context(|| format!("failed to read {}", bashrc_path.display()))?;
-    let source_block = format!(
-        "# Load git prompt configuration\nif [ -f \"{}\" ]; then\n  . \"{}\"\nfi\n",
-        prompt_path.display(),
-        prompt_path.display()
-    );
+    let bashrc_content = if bashrc_path.exists() {
+        fs::read_to_string(&bashrc_path)
+            .with_context(|| format!("failed to read {}", bashrc_path.display()))?
+    } else {
+        String::new()
+    };
+    let source_block = source_block(&prompt_path);
 
     if !bashrc_content.contains(prompt_path.to_string_lossy().as_ref()) {
-        let mut handle = OpenOptions::new()
-            .append(true)
-            .open(&bashrc_path)
-            .with_context(|| format!("failed to open {} for append", bashrc_path.display()))?;
+        let mut addition = String::new();
         if !bashrc_content.is_empty() && !bashrc_content.ends_with('\n') {
-            writeln!(handle)?;
+            addition.push('\n');
         }
         if !bashrc_content.is_empty() {
-            writeln!(handle)?;
+            addition.push('\n');
         }
-        write!(handle, "{source_block}")?;
+        addition.push_str(&source_block);
+        backup::save(&bashrc_path)?;
+        exec::append(&bashrc_path, &addition)
+            .with_context(|| format!("failed to append to {}", bashrc_path.display()))?;
         changed = true;
     }
 
     if changed {
-        println!(
+        say!(
             "Configured git prompt: file={}, sourced via {}",
             prompt_path.display(),
             bashrc_path.display()
         );
     } else {
-        println!("Git prompt already configured");
+        say!("Git prompt already configured");
+    }
+
+    Ok(())
+}
+
+/// Deletes `~/.git-prompt.sh` and the block that sources it from `.bashrc`.
+pub fn uninstall() -> anyhow::Result<()> {
+    let home = home_dir()?;
+    let prompt_path = home.join(PROMPT_FILENAME);
+    let block = source_block(&prompt_path);
+    let block = block.lines().collect::<Vec<_>>();
+
+    let mut removed_any = !shell_init::remove_block(&[home.join(".bashrc")], &block)?.is_empty();
+    if prompt_path.exists() {
+        exec::remove_file(&prompt_path)
+            .with_context(|| format!("failed to remove {}", prompt_path.display()))?;
+        removed_any = true;
     }
 
+    if removed_any {
+        say!("Removed git prompt configuration");
+    } else {
+        say!("Git prompt is not configured");
+    }
     Ok(())
 }
 
+fn source_block(prompt_path: &Path) -> String {
+    format!(
+        "# Load git prompt configuration\nif [ -f \"{}\" ]; then\n  . \"{}\"\nfi\n",
+        prompt_path.display(),
+        prompt_path.display()
+    )
+}
+
 fn home_dir() -> anyhow::Result<PathBuf> {
     env::var_os("HOME")
         .map(PathBuf::from)
         .context("HOME environment variable is not set")
 }
+
+pub struct GitPromptTarget;
+
+impl Target for GitPromptTarget {
+    fn name(&self) -> &'static str {
+        "git-prompt"
+    }
+
+    fn description(&self) -> &'static str {
+        "Git-aware Bash prompt for the current user"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Configure | Action::Uninstall)
+    }
+
+    fn configure(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        run()
+    }
+
+    fn uninstall(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall()
+    }
+
+    fn summary(&self, _ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let home = home_dir()?;
+        let prompt_path = home.join(PROMPT_FILENAME);
+        let bashrc_path = home.join(".bashrc");
+        let mut status = super::TargetStatus::new(self.name(), None);
+        status.installed = prompt_path.exists();
+        if !status.installed {
+            return Ok(status);
+        }
+
+        let mut drift = Vec::new();
+        if fs::read_to_string(&prompt_path).unwrap_or_default() != PROMPT_CONTENT {
+            drift.push(format!("{} is missing or modified", prompt_path.display()));
+        }
+        let bashrc = fs::read_to_string(&bashrc_path).unwrap_or_default();
+        if !bashrc.contains(prompt_path.to_string_lossy().as_ref()) {
+            drift.push(format!("{} does not source it", bashrc_path.display()));
+        }
+        Ok(status.with_drift(drift))
+    }
+}
@@ -0,0 +1,318 @@
+//! `configure gnome-settings`: the gsettings keys in `[gnome_settings.settings]`.
+//!
+//! Each key is set with `gsettings set` as the target user, which checks it against
+//! the schema, and only when its value differs, so running it again changes nothing.
+//! Before the first change the user's whole dconf database is dumped to
+//! `~/.local/state/debkit/gnome-settings.dconf`; `uninstall gnome-settings` resets the
+//! configured keys and loads their values from that dump back with `dconf load`.
+//! `status gnome-settings` lists each key with its current and configured value.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+
+use super::user::{UserContext, target_user_context};
+use super::{Action, Target};
+use crate::config::GnomeSettingsConfig;
+use crate::desktop::{dconf, gsettings};
+use crate::output::{self, say};
+use crate::{exec, state, system};
+
+#[derive(Debug, Serialize)]
+pub struct GnomeSettingsStatus {
+    pub settings: Vec<SettingStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SettingStatus {
+    pub schema: String,
+    pub key: String,
+    pub wanted: String,
+    /// The value `gsettings get` prints, or `None` when the schema or key does not exist.
+    pub current: Option<String>,
+}
+
+impl SettingStatus {
+    fn matches(&self) -> bool {
+        self.current
+            .as_deref()
+            .is_some_and(|current| gsettings::same_value(current, &self.wanted))
+    }
+}
+
+impl GnomeSettingsStatus {
+    fn drift(&self) -> Vec<String> {
+        self.settings
+            .iter()
+            .filter(|setting| !setting.matches())
+            .map(|setting| match &setting.current {
+                Some(current) => format!(
+                    "{} {} is {current}, not {}",
+                    setting.schema, setting.key, setting.wanted
+                ),
+                None => format!("{} has no key {}", setting.schema, setting.key),
+            })
+            .collect()
+    }
+}
+
+pub fn run(config: &GnomeSettingsConfig) -> anyhow::Result<GnomeSettingsStatus> {
+    if !gsettings::available() {
+        bail!("gsettings was not found; gnome-settings needs a GNOME desktop");
+    }
+    let user = target_user_context()?;
+    let status = collect_status(config, &user)?;
+    let mut changed = 0;
+    for setting in &status.settings {
+        if setting.matches() {
+            continue;
+        }
+        if setting.current.is_none() {
+            bail!(
+                "{} has no key {}; check `gnome_settings.settings` and that the schema is installed",
+                setting.schema,
+                setting.key
+            );
+        }
+        if changed == 0 {
+            save_snapshot(&user)?;
+        }
+        gsettings::set(&user, &setting.schema, &setting.key, &setting.wanted)?;
+        changed += 1;
+    }
+    if config.settings.is_empty() {
+        say!("No settings to apply; list them in `gnome_settings.settings`.");
+    } else if changed == 0 {
+        say!("All {} settings are applied.", status.settings.len());
+    } else {
+        say!("Changed {changed} of {} settings.", status.settings.len());
+    }
+    collect_status(config, &user)
+}
+
+/// Resets the configured keys, then loads the values they had before DebKit first
+/// changed them from the snapshot.
+pub fn uninstall(config: &GnomeSettingsConfig) -> anyhow::Result<()> {
+    if !gsettings::available() {
+        say!("gsettings was not found; there is nothing to reset.");
+        return Ok(());
+    }
+    let user = target_user_context()?;
+    for (schema, key, _) in settings(config) {
+        if gsettings::has_key(&user, schema, key) {
+            gsettings::reset(&user, schema, key)?;
+        }
+    }
+    let path = snapshot_path()?;
+    let Ok(snapshot) = fs::read_to_string(&path) else {
+        say!("Reset the configured settings to their defaults.");
+        return Ok(());
+    };
+    let previous = previous_values(config, &snapshot);
+    if !previous.is_empty() {
+        dconf::load_as(&user, "/", &previous)?;
+    }
+    if !exec::is_dry_run() {
+        fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+    }
+    say!("Restored the configured settings from {}", path.display());
+    Ok(())
+}
+
+pub fn collect_status(
+    config: &GnomeSettingsConfig,
+    user: &UserContext,
+) -> anyhow::Result<GnomeSettingsStatus> {
+    let settings = settings(config)
+        .map(|(schema, key, wanted)| {
+            Ok(SettingStatus {
+                schema: schema.to_string(),
+                key: key.to_string(),
+                wanted: wanted.to_string(),
+                current: gsettings::get(user, schema, key)?,
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok(GnomeSettingsStatus { settings })
+}
+
+fn settings(config: &GnomeSettingsConfig) -> impl Iterator<Item = (&str, &str, &str)> {
+    config.settings.iter().flat_map(|(schema, keys)| {
+        keys.iter()
+            .map(move |(key, value)| (schema.as_str(), key.as_str(), value.as_str()))
+    })
+}
+
+fn snapshot_path() -> anyhow::Result<PathBuf> {
+    Ok(state::dir()?.join("gnome-settings.dconf"))
+}
+
+/// Dumps the user's dconf database before the first change. Like backups this is
+/// DebKit's own bookkeeping, so it bypasses [`crate::exec`] and dry runs skip it.
+fn save_snapshot(user: &UserContext) -> anyhow::Result<()> {
+    let path = snapshot_path()?;
+    if exec::is_dry_run() || path.exists() || !system::command_available("dconf") {
+        return Ok(());
+    }
+    let snapshot = dconf::dump_as(user, "/")?;
+    if let Some(dir) = path.parent() {
+        state::create_dir(dir)?;
+    }
+    fs::write(&path, snapshot).with_context(|| format!("failed to write {}", path.display()))?;
+    state::hand_over(&path)
+}
+
+/// The entries of a `dconf dump /` snapshot for the configured keys, as a profile to
+/// load back into `/`. Keys that were at their default are not in the dump and stay
+/// reset.
+fn previous_values(config: &GnomeSettingsConfig, snapshot: &str) -> String {
+    let mut profile = String::new();
+    let mut last_section = None;
+    for (section, key, value) in dconf::entries(snapshot) {
+        let configured = settings(config).any(|(schema, configured_key, _)| {
+            configured_key == key && gsettings::dconf_dir(schema).trim_matches('/') == section
+        });
+        if !configured {
+            continue;
+        }
+        if last_section.as_ref() != Some(&section) {
+            profile.push_str(&format!("[{section}]\n"));
+            last_section = Some(section);
+        }
+        profile.push_str(&format!("{key}={value}\n"));
+    }
+    profile
+}
+
+fn print_status_report(status: &GnomeSettingsStatus) {
+    if status.settings.is_empty() {
+        say!("No settings listed in `gnome_settings.settings`.");
+        return;
+    }
+    for setting in &status.settings {
+        let current = setting.current.as_deref().unwrap_or("missing");
+        if setting.matches() {
+            say!("{} {}: {current}", setting.schema, setting.key);
+        } else {
+            say!(
+                "{} {}: {current} (wanted {})",
+                setting.schema,
+                setting.key,
+                setting.wanted
+            );
+        }
+    }
+}
+
+pub struct GnomeSettingsTarget;
+
+impl Target for GnomeSettingsTarget {
+    fn name(&self) -> &'static str {
+        "gnome-settings"
+    }
+
+    fn description(&self) -> &'static str {
+        "GNOME desktop settings from gnome_settings.settings, applied with gsettings"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["gsettings"]
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(
+            action,
+            Action::Configure | Action::Uninstall | Action::Status
+        )
+    }
+
+    fn configure(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let status = run(&ctx.config()?.gnome_settings)?;
+        ctx.report(&status)
+    }
+
+    fn uninstall(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall(&ctx.config()?.gnome_settings)
+    }
+
+    fn status(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let status = collect_status(&ctx.config()?.gnome_settings, &target_user_context()?)?;
+        if !output::is_json() {
+            print_status_report(&status);
+        }
+        ctx.report(&status)?;
+        if !status.drift().is_empty() {
+            return Err(output::Drift.into());
+        }
+        Ok(())
+    }
+
+    fn version(&self) -> Option<String> {
+        super::program_version("gsettings", &["--version"])
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let config = &ctx.config()?.gnome_settings;
+        let summary = super::TargetStatus::new(self.name(), self.version());
+        if !summary.installed || config.settings.is_empty() {
+            return Ok(summary);
+        }
+        let status = collect_status(config, &target_user_context()?)?;
+        Ok(summary.with_drift(status.drift()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_settings_and_restores_the_snapshot() {
+        let mut config = GnomeSettingsConfig::default();
+        config
+            .settings
+            .entry("org.gnome.desktop.interface".to_string())
+            .or_default()
+            .extend([
+                ("color-scheme".to_string(), "'prefer-dark'".to_string()),
+                ("clock-show-seconds".to_string(), "true".to_string()),
+            ]);
+        config
+            .settings
+            .entry("org.gnome.desktop.session".to_string())
+            .or_default()
+            .insert("idle-delay".to_string(), "600".to_string());
+
+        let status = GnomeSettingsStatus {
+            settings: settings(&config)
+                .zip([Some("false"), Some("'prefer-dark'"), Some("uint32 600")])
+                .map(|((schema, key, wanted), current)| SettingStatus {
+                    schema: schema.to_string(),
+                    key: key.to_string(),
+                    wanted: wanted.to_string(),
+                    current: current.map(str::to_string),
+                })
+                .collect(),
+        };
+        assert_eq!(
+            status.drift(),
+            ["org.gnome.desktop.interface clock-show-seconds is false, not true"]
+        );
+
+        let snapshot = "[org/gnome/desktop/interface]\n\
+                        color-scheme='default'\n\
+                        gtk-theme='Adwaita'\n\
+                        \n\
+                        [org/gnome/desktop/session]\n\
+                        idle-delay=uint32 300\n";
+        assert_eq!(
+            previous_values(&config, snapshot),
+            "[org/gnome/desktop/interface]\n\
+             color-scheme='default'\n\
+             [org/gnome/desktop/session]\n\
+             idle-delay=uint32 300\n"
+        );
+    }
+}
@@ -1,22 +1,259 @@
-pub fn run() {
-    println!("Available install/configure targets:");
+use serde::Serialize;
+use tracing::warn;
+
+use super::{Action, Context, Update};
+use crate::output::{self, say};
+use crate::{config, state};
+
+#[derive(Debug, Serialize)]
+pub struct TargetInfo {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub description: &'static str,
+    pub actions: Vec<&'static str>,
+    /// Installed or configured by DebKit according to the state file.
+    pub managed: bool,
+    /// Whether the target's software is on this machine; only checked by
+    /// `list --status`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installed: Option<bool>,
+    /// The detected version; always `None` without `list --status`.
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ListReport {
+    targets: Vec<TargetInfo>,
+}
+
+/// The updates `list --available-updates` found for one target.
+#[derive(Debug, Serialize)]
+pub struct TargetUpdates {
+    pub target: &'static str,
+    pub updates: Vec<Update>,
+}
+
+#[derive(Debug, Serialize)]
+struct UpdatesReport {
+    targets: Vec<TargetUpdates>,
+}
+
+/// Describes every registered target; with `check_installed`, each one is also asked
+/// for the version it finds installed.
+pub fn collect(check_installed: bool) -> Vec<TargetInfo> {
+    let state = state::current();
+    super::targets()
+        .iter()
+        .map(|target| {
+            let version = check_installed.then(|| target.version()).flatten();
+            TargetInfo {
+                name: target.name(),
+                aliases: target.aliases(),
+                description: target.description(),
+                actions: Action::ALL
+                    .into_iter()
+                    .filter(|action| target.supports(*action))
+                    .map(Action::label)
+                    .collect(),
+                managed: state.is_managed(target.name()),
+                installed: check_installed.then_some(version.is_some()),
+                version,
+            }
+        })
+        .collect()
+}
+
+pub fn run(check_installed: bool) -> anyhow::Result<()> {
+    let targets = collect(check_installed);
+    if output::is_json() {
+        return output::emit(&ListReport { targets });
+    }
+    if check_installed {
+        say!("{}", render_table(&targets).trim_end());
+        return Ok(());
+    }
+
+    say!("Available install/configure targets:");
+    for target in targets {
+        say!(
+            "- {} [{}]: {}{}",
+            target.name,
+            target.actions.join(", "),
+            target.description,
+            if target.managed {
+                " (installed by DebKit)"
+            } else {
+                ""
+            }
+        );
+    }
+    Ok(())
+}
+
+/// Asks every target for newer versions of what it installed, leaving out those with
+/// none. A target whose check fails is warned about and skipped.
+pub fn collect_updates() -> anyhow::Result<Vec<TargetUpdates>> {
+    let config = config::load_or_init(&config::path()?)?;
+    let mut found = Vec::new();
     for target in super::targets() {
-        let mut capabilities = Vec::new();
-        if target.supports_install {
-            capabilities.push("install");
-        }
-        if target.supports_uninstall {
-            capabilities.push("uninstall");
+        let matches = super::default_matches(*target, Action::Status)?;
+        let ctx = Context::with_config(&matches, config.clone());
+        match target.updates(&ctx) {
+            Ok(updates) if updates.is_empty() => {}
+            Ok(updates) => found.push(TargetUpdates {
+                target: target.name(),
+                updates,
+            }),
+            Err(err) => warn!("could not check {} for updates: {err:#}", target.name()),
         }
-        if target.supports_configure {
-            capabilities.push("configure");
+    }
+    Ok(found)
+}
+
+pub fn run_updates() -> anyhow::Result<()> {
+    let targets = collect_updates()?;
+    if output::is_json() {
+        return output::emit(&UpdatesReport { targets });
+    }
+    let count = targets
+        .iter()
+        .map(|target| target.updates.len())
+        .sum::<usize>();
+    if count == 0 {
+        say!("No updates available for the installed targets.");
+    } else {
+        say!("{}", render_updates(&targets).trim_end());
+    }
+    output::outcome(format!(
+        "{count} updates available for {} targets",
+        targets.len()
+    ));
+    Ok(())
+}
+
+/// The `list --available-updates` table: a row per package or toolchain.
+fn render_updates(targets: &[TargetUpdates]) -> String {
+    let rows = targets
+        .iter()
+        .flat_map(|target| {
+            target.updates.iter().map(|update| {
+                [
+                    target.target.to_string(),
+                    update.name.clone(),
+                    update.installed.clone(),
+                    update.available.clone(),
+                ]
+            })
+        })
+        .collect::<Vec<_>>();
+    render_rows(
+        ["TARGET", "NAME", "INSTALLED", "AVAILABLE"].map(String::from),
+        &rows,
+    )
+}
+
+/// The `list --status` table: one row per target with whether it is installed, its
+/// version, whether DebKit manages it and the actions it supports.
+fn render_table(targets: &[TargetInfo]) -> String {
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+    let rows = targets
+        .iter()
+        .map(|target| {
+            [
+                target.name.to_string(),
+                target.installed.map_or("-", yes_no).to_string(),
+                target.version.clone().unwrap_or_else(|| "-".to_string()),
+                yes_no(target.managed).to_string(),
+                target.actions.join(","),
+            ]
+        })
+        .collect::<Vec<_>>();
+    render_rows(
+        ["TARGET", "INSTALLED", "VERSION", "MANAGED", "ACTIONS"].map(String::from),
+        &rows,
+    )
+}
+
+/// Lines up `rows` under `header` in columns two spaces apart.
+fn render_rows<const N: usize>(header: [String; N], rows: &[[String; N]]) -> String {
+    let mut widths = header.clone().map(|cell| cell.len());
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
         }
+    }
 
-        println!(
-            "- {} [{}]: {}",
-            target.name,
-            capabilities.join(", "),
-            target.description
-        );
+    let mut out = String::new();
+    for row in std::iter::once(&header).chain(rows) {
+        let cells = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_installed_state_table() {
+        let targets = vec![
+            TargetInfo {
+                name: "git",
+                aliases: &[],
+                description: "Git",
+                actions: vec!["install"],
+                managed: true,
+                installed: Some(true),
+                version: Some("git version 2.47.3".to_string()),
+            },
+            TargetInfo {
+                name: "tailscale",
+                aliases: &[],
+                description: "Tailscale",
+                actions: vec!["install", "uninstall", "status"],
+                managed: false,
+                installed: Some(false),
+                version: None,
+            },
+        ];
+        let expected = [
+            "TARGET     INSTALLED  VERSION             MANAGED  ACTIONS",
+            "git        yes        git version 2.47.3  yes      install",
+            "tailscale  no         -                   no       install,uninstall,status",
+            "",
+        ];
+        assert_eq!(render_table(&targets), expected.join("\n"));
+    }
+
+    #[test]
+    fn renders_available_updates() {
+        let targets = vec![TargetUpdates {
+            target: "rust",
+            updates: vec![
+                Update {
+                    name: "stable-x86_64-unknown-linux-gnu".to_string(),
+                    installed: "1.80.0".to_string(),
+                    available: "1.81.0".to_string(),
+                },
+                Update {
+                    name: "rustup".to_string(),
+                    installed: "1.27.0".to_string(),
+                    available: "1.27.1".to_string(),
+                },
+            ],
+        }];
+        let expected = [
+            "TARGET  NAME                             INSTALLED  AVAILABLE",
+            "rust    stable-x86_64-unknown-linux-gnu  1.80.0     1.81.0",
+            "rust    rustup                           1.27.0     1.27.1",
+            "",
+        ];
+        assert_eq!(render_updates(&targets), expected.join("\n"));
     }
 }
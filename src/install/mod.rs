@@ -1,126 +1,663 @@
-pub mod apt;
+pub mod appindicator;
+pub mod auto_updates;
+pub mod cargo_tools;
 pub mod codex;
+pub mod docker;
 pub mod essentials;
+pub mod firewall;
+pub mod flatpak;
+pub mod fonts;
 pub mod foundation;
 pub mod git;
 pub mod git_prompt;
+pub mod gnome_settings;
 pub mod list;
+pub mod neovim;
+pub mod nfs_mounts;
 pub mod nis;
 pub mod npm;
+pub mod python;
 pub mod ripgrep;
 pub mod rust;
+pub mod shell_init;
+pub mod ssh;
+pub mod status;
 pub mod sudo_nopass;
+pub mod tailscale;
+pub mod user;
 pub mod variety;
 pub mod wake_on_lan;
+pub mod wallpapers_sync;
+pub mod zsh;
 
-#[derive(Debug, Clone, Copy)]
-pub struct InstallTarget {
-    pub name: &'static str,
-    pub supports_install: bool,
-    pub supports_uninstall: bool,
-    pub supports_configure: bool,
-    pub description: &'static str,
-}
-
-pub fn targets() -> &'static [InstallTarget] {
-    &[
-        InstallTarget {
-            name: "essentials",
-            supports_install: true,
-            supports_uninstall: false,
-            supports_configure: false,
-            description: "Baseline CLI packages required for provisioning",
-        },
-        InstallTarget {
-            name: "git",
-            supports_install: true,
-            supports_uninstall: false,
-            supports_configure: false,
-            description: "Git version control via apt",
-        },
-        InstallTarget {
-            name: "git-prompt",
-            supports_install: false,
-            supports_uninstall: false,
-            supports_configure: true,
-            description: "Git-aware Bash prompt for the current user",
-        },
-        InstallTarget {
-            name: "npm",
-            supports_install: true,
-            supports_uninstall: true,
-            supports_configure: false,
-            description: "Node.js and npm from official Node.js binaries",
-        },
-        InstallTarget {
-            name: "nis",
-            supports_install: true,
-            supports_uninstall: false,
-            supports_configure: true,
-            description: "NIS client and server packages",
-        },
-        InstallTarget {
-            name: "nis-client",
-            supports_install: true,
-            supports_uninstall: false,
-            supports_configure: true,
-            description: "NIS client packages",
-        },
-        InstallTarget {
-            name: "nis-server",
-            supports_install: true,
-            supports_uninstall: false,
-            supports_configure: true,
-            description: "NIS server packages",
-        },
-        InstallTarget {
-            name: "codex",
-            supports_install: true,
-            supports_uninstall: true,
-            supports_configure: false,
-            description: "OpenAI Codex CLI via npm",
-        },
-        InstallTarget {
-            name: "ripgrep",
-            supports_install: true,
-            supports_uninstall: true,
-            supports_configure: false,
-            description: "ripgrep recursive search tool",
-        },
-        InstallTarget {
-            name: "rust",
-            supports_install: true,
-            supports_uninstall: false,
-            supports_configure: true,
-            description: "Rust toolchain via rustup",
-        },
-        InstallTarget {
-            name: "sudo-nopass",
-            supports_install: true,
-            supports_uninstall: false,
-            supports_configure: false,
-            description: "Passwordless sudo for configured users",
-        },
-        InstallTarget {
-            name: "variety",
-            supports_install: true,
-            supports_uninstall: false,
-            supports_configure: true,
-            description: "Variety wallpaper rotator for GNOME",
-        },
-        InstallTarget {
-            name: "foundation",
-            supports_install: true,
-            supports_uninstall: false,
-            supports_configure: false,
-            description: "Installs configured base targets from debkit config",
-        },
-        InstallTarget {
-            name: "wake-on-lan",
-            supports_install: true,
-            supports_uninstall: false,
-            supports_configure: false,
-            description: "Inspect and enable wired Ethernet Wake-on-LAN",
-        },
-    ]
+use std::cell::{OnceCell, RefCell};
+use std::fmt;
+use std::marker::PhantomData;
+use std::process::Command;
+
+use anyhow::bail;
+use clap::error::ErrorKind;
+use clap::{ArgMatches, FromArgMatches, Subcommand};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::config::{self, DebkitConfig};
+use crate::output::say;
+use crate::{confirm, exec, output, state};
+
+static TARGETS: &[&dyn Target] = &[
+    &essentials::EssentialsTarget,
+    &auto_updates::AutoUpdatesTarget,
+    &git::GitTarget,
+    &git_prompt::GitPromptTarget,
+    &ssh::SshTarget,
+    &firewall::FirewallTarget,
+    &npm::NpmTarget,
+    &python::PythonTarget,
+    &nis::NisTarget(nis::Role::Configured),
+    &nis::NisTarget(nis::Role::Client),
+    &nis::NisTarget(nis::Role::Server),
+    &codex::CodexTarget,
+    &ripgrep::RipgrepTarget,
+    &neovim::NeovimTarget,
+    &rust::RustTarget,
+    &cargo_tools::CargoToolsTarget,
+    &docker::DockerTarget,
+    &flatpak::FlatpakTarget,
+    &tailscale::TailscaleTarget,
+    &fonts::FontsTarget,
+    &zsh::ZshTarget,
+    &sudo_nopass::SudoNopassTarget,
+    &nfs_mounts::NfsMountsTarget,
+    &gnome_settings::GnomeSettingsTarget,
+    &appindicator::AppindicatorTarget,
+    &variety::VarietyTarget,
+    &wallpapers_sync::WallpapersSyncTarget,
+    &foundation::FoundationTarget,
+    &wake_on_lan::WakeOnLanTarget,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Install,
+    Uninstall,
+    Configure,
+    Status,
+}
+
+impl Action {
+    pub const ALL: [Action; 4] = [
+        Action::Install,
+        Action::Uninstall,
+        Action::Configure,
+        Action::Status,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Install => "install",
+            Action::Uninstall => "uninstall",
+            Action::Configure => "configure",
+            Action::Status => "status",
+        }
+    }
+}
+
+/// A provisioning target. Registering an implementation in [`targets`] is all it
+/// takes for it to show up in `install`/`uninstall`/`configure`/`status`, in
+/// `list`, and in `foundation.install`.
+pub trait Target: Sync {
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// Extra names accepted on the command line and in `foundation.install`.
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Targets that `install foundation` installs before this one, pulling them in
+    /// when `foundation.install` does not list them.
+    fn dependencies(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        action == Action::Install
+    }
+
+    /// Adds target-specific arguments to the subcommand generated for `action`.
+    fn args(&self, _action: Action, command: clap::Command) -> clap::Command {
+        command
+    }
+
+    fn install(&self, _ctx: &Context) -> anyhow::Result<()> {
+        unsupported(self.name(), Action::Install)
+    }
+
+    fn uninstall(&self, _ctx: &Context) -> anyhow::Result<()> {
+        unsupported(self.name(), Action::Uninstall)
+    }
+
+    fn configure(&self, _ctx: &Context) -> anyhow::Result<()> {
+        unsupported(self.name(), Action::Configure)
+    }
+
+    fn status(&self, _ctx: &Context) -> anyhow::Result<()> {
+        unsupported(self.name(), Action::Status)
+    }
+
+    /// Version of the software this target manages, if it is installed.
+    fn version(&self) -> Option<String> {
+        None
+    }
+
+    /// Installed/configured state for `status all`. Targets with settings beyond
+    /// being installed override this to report drift from the config.
+    fn summary(&self, _ctx: &Context) -> anyhow::Result<TargetStatus> {
+        Ok(TargetStatus::new(self.name(), self.version()))
+    }
+
+    /// Newer versions of what the target installed, for `list --available-updates`.
+    /// Targets that install APT packages return [`apt_updates`] for them.
+    fn updates(&self, _ctx: &Context) -> anyhow::Result<Vec<Update>> {
+        Ok(Vec::new())
+    }
+}
+
+/// A package or toolchain with a newer version available than the installed one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Update {
+    pub name: String,
+    pub installed: String,
+    pub available: String,
+}
+
+/// The installed `packages` with another candidate version in APT's package lists.
+pub fn apt_updates(packages: &[&str]) -> anyhow::Result<Vec<Update>> {
+    Ok(crate::apt::upgradable(packages)?
+        .into_iter()
+        .map(|upgrade| Update {
+            name: upgrade.package,
+            installed: upgrade.installed,
+            available: upgrade.candidate,
+        })
+        .collect())
+}
+
+/// One row of `status all`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetStatus {
+    pub target: &'static str,
+    /// `false` when the config switches the target off, e.g. `nis.enabled = false`.
+    pub enabled: bool,
+    pub installed: bool,
+    pub version: Option<String>,
+    /// `None` when the target has nothing to configure beyond being installed.
+    pub configured: Option<bool>,
+    /// Whether the state file records DebKit installing or configuring the target.
+    pub managed: bool,
+    /// Ways the system differs from what the config asks for.
+    pub drift: Vec<String>,
+}
+
+impl TargetStatus {
+    /// A status that counts the target as installed when `version` was detected.
+    pub fn new(target: &'static str, version: Option<String>) -> Self {
+        Self {
+            target,
+            enabled: true,
+            installed: version.is_some(),
+            version,
+            configured: None,
+            managed: false,
+            drift: Vec::new(),
+        }
+    }
+
+    pub fn disabled(target: &'static str) -> Self {
+        Self {
+            enabled: false,
+            ..Self::new(target, None)
+        }
+    }
+
+    /// Records `drift`; the target counts as configured when there is none.
+    pub fn with_drift(mut self, drift: Vec<String>) -> Self {
+        self.configured = Some(drift.is_empty());
+        self.drift = drift;
+        self
+    }
+}
+
+fn unsupported(name: &str, action: Action) -> anyhow::Result<()> {
+    bail!("target `{name}` does not support `{}`", action.label())
+}
+
+/// First line of `program args...` output, for [`Target::version`] implementations.
+pub fn program_version(program: &str, args: &[&str]) -> Option<String> {
+    let output = exec::query(Command::new(program).args(args)).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().next()?.trim();
+    (!line.is_empty()).then(|| line.to_string())
+}
+
+pub fn targets() -> &'static [&'static dyn Target] {
+    TARGETS
+}
+
+/// Looks up a target by name or alias among those supporting `action`.
+pub fn find(action: Action, name: &str) -> Option<&'static dyn Target> {
+    targets().iter().copied().find(|target| {
+        target.supports(action) && (target.name() == name || target.aliases().contains(&name))
+    })
+}
+
+pub fn run(target: &dyn Target, action: Action, ctx: &Context) -> anyhow::Result<()> {
+    match action {
+        Action::Install => target.install(ctx),
+        Action::Uninstall => target.uninstall(ctx),
+        Action::Configure => target.configure(ctx),
+        Action::Status => target.status(ctx),
+    }
+}
+
+/// Runs `action` and keeps the state file in step: installs and configures are
+/// recorded along with the files they created, uninstalls drop the record.
+pub fn run_tracked(target: &dyn Target, action: Action, ctx: &Context) -> anyhow::Result<()> {
+    let tracked = target.name() != foundation::FoundationTarget.name() && !exec::is_dry_run();
+    if tracked
+        && action == Action::Uninstall
+        && !state::current().is_managed(target.name())
+        && target.summary(ctx).is_ok_and(|status| status.installed)
+    {
+        say!(
+            "Note: `{}` was not installed by DebKit; removing it anyway.",
+            target.name()
+        );
+    }
+
+    let created = exec::created().len();
+    run(target, action, ctx)?;
+    if !tracked {
+        return Ok(());
+    }
+    let updated = match action {
+        Action::Install | Action::Configure => {
+            let files = exec::created()[created..].to_vec();
+            let version = target.version();
+            state::update(|state| state.record(target.name(), version, &files, state::now()))
+        }
+        Action::Uninstall => state::update(|state| state.forget(target.name())).map(|record| {
+            let files = record.map(|record| record.files).unwrap_or_default();
+            for file in files.iter().filter(|file| file.exists()) {
+                say!("Left in place (created by DebKit): {}", file.display());
+            }
+        }),
+        Action::Status => Ok(()),
+    };
+    if let Err(err) = updated {
+        warn!("failed to update the DebKit state file: {err:#}");
+    }
+    Ok(())
+}
+
+/// Runs `action` on the target called `name` as `debkit <action> <name> <args>...`
+/// would, for programs embedding DebKit: `args` are the target's own options, such as
+/// `["--reinstall"]`. There is no confirmation prompt and no `--format json` report.
+pub fn run_by_name(action: Action, name: &str, args: &[&str]) -> anyhow::Result<()> {
+    let Some(target) = find(action, name) else {
+        bail!("no target `{name}` supports `{}`", action.label());
+    };
+    let matches = command_for(target, action)
+        .try_get_matches_from(std::iter::once(name).chain(args.iter().copied()))?;
+    run_tracked(target, action, &Context::new(&matches))
+}
+
+fn command_for(target: &dyn Target, action: Action) -> clap::Command {
+    let command = clap::Command::new(target.name())
+        .about(target.description())
+        .aliases(target.aliases().iter().copied());
+    target.args(action, command)
+}
+
+/// State shared with a target while it runs: its parsed arguments and the DebKit
+/// config, which is only loaded (and created on first use) when a target asks for it.
+pub struct Context<'a> {
+    matches: &'a ArgMatches,
+    config: OnceCell<DebkitConfig>,
+    details: RefCell<Option<serde_json::Value>>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(matches: &'a ArgMatches) -> Self {
+        Self {
+            matches,
+            config: OnceCell::new(),
+            details: RefCell::new(None),
+        }
+    }
+
+    pub fn with_config(matches: &'a ArgMatches, config: DebkitConfig) -> Self {
+        Self {
+            matches,
+            config: OnceCell::from(config),
+            details: RefCell::new(None),
+        }
+    }
+
+    /// Attaches target-specific data to the `--format json` result.
+    pub fn report(&self, details: &impl Serialize) -> anyhow::Result<()> {
+        *self.details.borrow_mut() = Some(serde_json::to_value(details)?);
+        Ok(())
+    }
+
+    pub fn take_details(&self) -> Option<serde_json::Value> {
+        self.details.take()
+    }
+
+    pub fn config(&self) -> anyhow::Result<&DebkitConfig> {
+        if let Some(config) = self.config.get() {
+            return Ok(config);
+        }
+        let config = config::load_or_init(&config::path()?)?;
+        Ok(self.config.get_or_init(|| config))
+    }
+
+    pub fn flag(&self, id: &str) -> bool {
+        self.matches
+            .try_get_one::<bool>(id)
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false)
+    }
+
+    pub fn value(&self, id: &str) -> Option<&str> {
+        self.matches
+            .try_get_one::<String>(id)
+            .ok()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    pub fn values(&self, id: &str) -> Vec<&str> {
+        self.matches
+            .try_get_many::<String>(id)
+            .ok()
+            .flatten()
+            .map(|values| values.map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn subcommand(&self) -> Option<(&str, &ArgMatches)> {
+        self.matches.subcommand()
+    }
+}
+
+pub trait ActionKind {
+    const ACTION: Action;
+}
+
+#[derive(Debug)]
+pub struct InstallAction;
+#[derive(Debug)]
+pub struct UninstallAction;
+#[derive(Debug)]
+pub struct ConfigureAction;
+#[derive(Debug)]
+pub struct StatusAction;
+
+impl ActionKind for InstallAction {
+    const ACTION: Action = Action::Install;
+}
+impl ActionKind for UninstallAction {
+    const ACTION: Action = Action::Uninstall;
+}
+impl ActionKind for ConfigureAction {
+    const ACTION: Action = Action::Configure;
+}
+impl ActionKind for StatusAction {
+    const ACTION: Action = Action::Status;
+}
+
+/// A `debkit <action> <target> [args]` invocation. Its clap subcommands are generated
+/// from the registry, so targets never have to be listed in `main.rs`.
+pub struct TargetCommand<A> {
+    target: &'static dyn Target,
+    matches: ArgMatches,
+    action: PhantomData<A>,
+}
+
+impl<A: ActionKind> TargetCommand<A> {
+    pub fn target(&self) -> &'static dyn Target {
+        self.target
+    }
+
+    pub fn context(&self) -> Context<'_> {
+        Context::new(&self.matches)
+    }
+
+    pub fn run(&self) -> anyhow::Result<()> {
+        if A::ACTION != Action::Status {
+            let what = format!("{} {}", A::ACTION.label(), self.target().name());
+            confirm::ask(&what, || {
+                run_tracked(self.target(), A::ACTION, &self.context())
+            })?;
+        }
+        let ctx = self.context();
+        let changes = exec::changes();
+        let result = run_tracked(self.target(), A::ACTION, &ctx);
+        // Drift is a finding, not a failure: the report is still `ok`.
+        let drifted = result.as_ref().is_err_and(|err| err.is::<output::Drift>());
+        if !output::is_json() {
+            if (result.is_ok() || drifted) && output::is_quiet() {
+                let changed = exec::changes() > changes;
+                output::outcome(match A::ACTION {
+                    Action::Status => status_outcome(&self.target().summary(&ctx)?),
+                    action => outcome(action, self.target().name(), changed, exec::is_dry_run()),
+                });
+            }
+            return result;
+        }
+        let details = ctx.take_details();
+        // A failure without details is reported by `main` as a plain error document.
+        if result.is_err() && !drifted && details.is_none() {
+            return result;
+        }
+        let error = result
+            .err()
+            .filter(|_| !drifted)
+            .map(|err| format!("{err:#}"));
+        output::emit(&ActionReport {
+            ok: error.is_none(),
+            target: self.target().name(),
+            action: A::ACTION.label(),
+            dry_run: exec::is_dry_run(),
+            changed: exec::changes() > changes,
+            version: self.target().version(),
+            details,
+            error: error.clone(),
+        })?;
+        match error {
+            Some(error) => Err(output::Reported(error).into()),
+            None if drifted => Err(output::Drift.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The `--quiet` result of running `action` on `target`, depending on whether it
+/// changed (or in a dry run, would change) anything.
+fn outcome(action: Action, target: &str, changed: bool, dry_run: bool) -> String {
+    match (action, changed) {
+        (action, true) if dry_run => format!("Would {} {target}", action.label()),
+        (Action::Install, true) => format!("Installed {target}"),
+        (Action::Install, false) => format!("{target} is already installed"),
+        (Action::Configure, true) => format!("Configured {target}"),
+        (Action::Configure, false) => format!("{target} is already configured"),
+        (Action::Uninstall, true) => format!("Uninstalled {target}"),
+        (Action::Uninstall, false) => format!("{target} is not installed"),
+        (Action::Status, _) => format!("Checked {target}"),
+    }
+}
+
+/// The `--quiet` result of `status`, e.g. `variety is installed, with 1 drift`.
+fn status_outcome(status: &TargetStatus) -> String {
+    let installed = match (status.enabled, status.installed) {
+        (false, _) => "is disabled",
+        (true, true) => "is installed",
+        (true, false) => "is not installed",
+    };
+    match status.drift.len() {
+        0 => format!("{} {installed}", status.target),
+        drift => format!("{} {installed}, with {drift} drift", status.target),
+    }
+}
+
+/// The `--format json` result of running a target action.
+#[derive(Debug, Serialize)]
+pub struct ActionReport {
+    pub ok: bool,
+    pub target: &'static str,
+    pub action: &'static str,
+    pub dry_run: bool,
+    pub changed: bool,
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl<A> fmt::Debug for TargetCommand<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TargetCommand")
+            .field("target", &self.target.name())
+            .finish()
+    }
+}
+
+impl<A: ActionKind> FromArgMatches for TargetCommand<A> {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        let Some((name, sub_matches)) = matches.subcommand() else {
+            return Err(clap::Error::raw(
+                ErrorKind::MissingSubcommand,
+                "a target is required\n",
+            ));
+        };
+        let target = find(A::ACTION, name).ok_or_else(|| {
+            clap::Error::raw(
+                ErrorKind::InvalidSubcommand,
+                format!("unknown {} target `{name}`\n", A::ACTION.label()),
+            )
+        })?;
+        Ok(Self {
+            target,
+            matches: sub_matches.clone(),
+            action: PhantomData,
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl<A: ActionKind> Subcommand for TargetCommand<A> {
+    fn augment_subcommands(command: clap::Command) -> clap::Command {
+        targets()
+            .iter()
+            .filter(|target| target.supports(A::ACTION))
+            .fold(command, |command, target| {
+                command.subcommand(command_for(*target, A::ACTION))
+            })
+    }
+
+    fn augment_subcommands_for_update(command: clap::Command) -> clap::Command {
+        Self::augment_subcommands(command)
+    }
+
+    fn has_subcommand(name: &str) -> bool {
+        find(A::ACTION, name).is_some()
+    }
+}
+
+/// Builds the argument matches a target would get from `debkit <action> <name>` with
+/// no extra arguments, for callers such as `foundation` that run targets directly.
+pub fn default_matches(target: &dyn Target, action: Action) -> anyhow::Result<ArgMatches> {
+    command_for(target, action)
+        .try_get_matches_from([target.name()])
+        .map_err(|err| anyhow::anyhow!("failed to build default arguments: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_names_and_aliases_are_unique_per_action() {
+        for action in Action::ALL {
+            let mut seen = std::collections::BTreeSet::new();
+            for target in targets().iter().filter(|target| target.supports(action)) {
+                for name in std::iter::once(target.name()).chain(target.aliases().iter().copied()) {
+                    assert!(
+                        seen.insert(name),
+                        "duplicate {} name `{name}`",
+                        action.label()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn finds_targets_by_alias() {
+        assert_eq!(
+            find(Action::Install, "dev_base").unwrap().name(),
+            "essentials"
+        );
+        assert_eq!(find(Action::Install, "wol").unwrap().name(), "wake-on-lan");
+        assert!(find(Action::Uninstall, "git").is_none());
+    }
+
+    #[test]
+    fn run_by_name_rejects_unknown_targets_and_args() {
+        let err = run_by_name(Action::Uninstall, "git", &[]).unwrap_err();
+        assert_eq!(err.to_string(), "no target `git` supports `uninstall`");
+        assert!(run_by_name(Action::Install, "rust", &["--no-such-flag"]).is_err());
+    }
+
+    #[test]
+    fn quiet_outcome_says_whether_anything_changed() {
+        assert_eq!(
+            outcome(Action::Install, "variety", true, false),
+            "Installed variety"
+        );
+        assert_eq!(
+            outcome(Action::Install, "variety", false, false),
+            "variety is already installed"
+        );
+        assert_eq!(
+            outcome(Action::Uninstall, "variety", true, true),
+            "Would uninstall variety"
+        );
+        let status = TargetStatus {
+            target: "variety",
+            enabled: true,
+            installed: true,
+            version: Some("0.8.4-1".to_string()),
+            configured: Some(true),
+            managed: true,
+            drift: vec!["variety is not held".to_string()],
+        };
+        assert_eq!(
+            status_outcome(&status),
+            "variety is installed, with 1 drift"
+        );
+    }
 }
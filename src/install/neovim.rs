@@ -0,0 +1,395 @@
+//! `install neovim`: Neovim at `neovim.min_version` or newer, and optionally the
+//! user's config from a git repository.
+//!
+//! The APT package is used when its candidate is new enough. Otherwise the upstream
+//! release archive from GitHub, checked against the release's `shasum.txt`, is
+//! unpacked under `/opt` and linked as `/usr/local/bin/nvim`, which comes before
+//! `/usr/bin` on PATH. `neovim.config_repo` is cloned into `~/.config/nvim`, or pulled
+//! when that is already its clone; any other `~/.config/nvim` is left alone.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+
+use super::user::{UserContext, command_as, ensure_owned_writable_dir, target_user_context};
+use super::{Action, Target};
+use crate::config::NeovimConfig;
+use crate::output::say;
+use crate::{apt, audit, download, exec, network, offline, privileged, scratch};
+
+const UPSTREAM_LINK: &str = "/usr/local/bin/nvim";
+const RELEASES_URL: &str = "https://github.com/neovim/neovim/releases/download";
+
+#[derive(Debug, Serialize)]
+pub struct NeovimReport {
+    pub version: Option<String>,
+    /// `apt`, `upstream`, or `existing` when the installed nvim was new enough.
+    pub source: &'static str,
+    pub config_repo: Option<String>,
+}
+
+pub fn run(config: &NeovimConfig) -> anyhow::Result<NeovimReport> {
+    let source = match nvim_version() {
+        Some(version) if version_at_least(&version, &config.min_version) => {
+            say!("nvim {version} is already installed.");
+            "existing"
+        }
+        _ => install_nvim(config)?,
+    };
+
+    let version = nvim_version();
+    if !exec::is_dry_run() {
+        match &version {
+            Some(version) if version_at_least(version, &config.min_version) => {
+                say!(
+                    "nvim {version} meets `neovim.min_version` {}",
+                    config.min_version
+                );
+            }
+            Some(version) => bail!(
+                "nvim {version} is older than `neovim.min_version` {}; is another nvim first on PATH?",
+                config.min_version
+            ),
+            None => bail!("nvim was installed but `nvim --version` does not run"),
+        }
+    }
+
+    let config_repo = Some(config.config_repo.trim()).filter(|repo| !repo.is_empty());
+    if let Some(repo) = config_repo {
+        checkout_config(&target_user_context()?, repo)?;
+    }
+    Ok(NeovimReport {
+        version,
+        source,
+        config_repo: config_repo.map(str::to_string),
+    })
+}
+
+/// Installs nvim from APT when its candidate is at least `min_version`, and from the
+/// upstream release otherwise. Returns where it came from.
+fn install_nvim(config: &NeovimConfig) -> anyhow::Result<&'static str> {
+    if let Some(candidate) = apt_candidate()
+        && version_at_least(&candidate, &config.min_version)
+    {
+        apt::install(&["neovim"])?;
+        return Ok("apt");
+    }
+    say!(
+        "APT's neovim is older than {}; installing the upstream {} release.",
+        config.min_version,
+        config.release
+    );
+    install_upstream(&config.release)?;
+    Ok("upstream")
+}
+
+/// Unpacks the upstream `release` into `/opt`, owned by root, and links `nvim` to it.
+/// The archive is downloaded into a private scratch directory; a dry run only
+/// announces the download and the commands that would unpack it.
+fn install_upstream(release: &str) -> anyhow::Result<()> {
+    if offline::is_enabled() {
+        bail!("the upstream Neovim release needs network access");
+    }
+    let name = archive_name()?;
+    let url = format!("{RELEASES_URL}/{release}/{name}.tar.gz");
+    let dir = if exec::is_dry_run() {
+        env::temp_dir()
+    } else {
+        scratch::create("neovim")?
+    };
+    let archive = dir.join(format!("{name}.tar.gz"));
+    let result = download::save(&url, &archive).and_then(|()| {
+        if !exec::is_dry_run() {
+            verify_archive(release, name, &url, &archive)?;
+        }
+        let target = Path::new("/opt").join(name);
+        if target.exists() {
+            privileged::run("rm", &["-rf", &target.to_string_lossy()])?;
+        }
+        privileged::run(
+            "tar",
+            &[
+                "--no-same-owner",
+                "--no-same-permissions",
+                "-xzf",
+                &archive.to_string_lossy(),
+                "-C",
+                "/opt",
+            ],
+        )?;
+        privileged::run(
+            "ln",
+            &[
+                "-sfn",
+                &target.join("bin/nvim").to_string_lossy(),
+                UPSTREAM_LINK,
+            ],
+        )
+    });
+    if !exec::is_dry_run() {
+        let _ = fs::remove_dir_all(&dir);
+    }
+    result
+}
+
+/// Checks the downloaded `archive` against the release's `shasum.txt`.
+fn verify_archive(release: &str, name: &str, url: &str, archive: &Path) -> anyhow::Result<()> {
+    let sums = download::fetch(&format!("{RELEASES_URL}/{release}/shasum.txt"))?;
+    let expected = checksum(&String::from_utf8_lossy(&sums), &format!("{name}.tar.gz"))
+        .with_context(|| format!("shasum.txt of {release} has no entry for {name}.tar.gz"))?;
+    let actual = audit::hash_file(archive).unwrap_or_default();
+    if actual != expected {
+        bail!("{url} has SHA-256 {actual}, but shasum.txt says {expected}");
+    }
+    Ok(())
+}
+
+/// Removes the upstream release and the APT package. `~/.config/nvim` stays.
+pub fn uninstall() -> anyhow::Result<()> {
+    let mut removed = false;
+    if let Ok(target) = fs::read_link(UPSTREAM_LINK)
+        && target.starts_with("/opt/nvim-")
+    {
+        privileged::remove_file(Path::new(UPSTREAM_LINK))?;
+        if let Some(dir) = target.ancestors().nth(2) {
+            privileged::run("rm", &["-rf", &dir.to_string_lossy()])?;
+        }
+        removed = true;
+    }
+    if apt::package_installed("neovim")? {
+        apt::remove(&["neovim"])?;
+        removed = true;
+    }
+    if !removed {
+        say!("Neovim is not installed.");
+    }
+    say!("Kept ~/.config/nvim.");
+    Ok(())
+}
+
+/// Clones `repo` into the user's `~/.config/nvim`, or pulls it when the directory is
+/// already a clone of it.
+fn checkout_config(user: &UserContext, repo: &str) -> anyhow::Result<()> {
+    apt::install_missing(&["git"])?;
+    let dir = config_dir(user);
+    if config_origin(user).as_deref() == Some(repo) {
+        if offline::is_enabled() {
+            say!("Offline: keeping the current clone of {repo}");
+            return Ok(());
+        }
+        return run_git(
+            user,
+            &["-C", &dir.display().to_string(), "pull", "--ff-only"],
+            &format!("Updating {repo}"),
+        );
+    }
+    if fs::read_dir(&dir).is_ok_and(|mut entries| entries.next().is_some()) {
+        bail!(
+            "{} already holds a config that is not a clone of {repo}; move it aside first",
+            dir.display()
+        );
+    }
+    if offline::is_enabled() {
+        bail!("{repo} has not been cloned yet, which needs network access");
+    }
+    if let Some(parent) = dir.parent().filter(|parent| !parent.exists()) {
+        exec::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+        ensure_owned_writable_dir(parent, user)?;
+    }
+    run_git(
+        user,
+        &["clone", repo, &dir.display().to_string()],
+        &format!("Cloning {repo}"),
+    )
+}
+
+fn run_git(user: &UserContext, args: &[&str], step: &str) -> anyhow::Result<()> {
+    let status = exec::status_step(
+        network::apply(&mut command_as(user, "git")?).args(args),
+        step,
+    )
+    .context("failed to start `git`")?;
+    if !status.success() {
+        bail!("`git {}` failed with status {status}", args.join(" "));
+    }
+    Ok(())
+}
+
+fn config_dir(user: &UserContext) -> PathBuf {
+    user.home.join(".config").join("nvim")
+}
+
+/// The `origin` of the git clone in `~/.config/nvim`, if it is one.
+fn config_origin(user: &UserContext) -> Option<String> {
+    let output = exec::query(
+        command_as(user, "git")
+            .ok()?
+            .arg("-C")
+            .arg(config_dir(user))
+            .args(["remote", "get-url", "origin"]),
+    )
+    .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The upstream archive for this machine, without `.tar.gz`.
+fn archive_name() -> anyhow::Result<&'static str> {
+    Ok(match env::consts::ARCH {
+        "x86_64" => "nvim-linux-x86_64",
+        "aarch64" => "nvim-linux-arm64",
+        arch => bail!("Neovim publishes no Linux release for {arch}"),
+    })
+}
+
+/// The SHA-256 for `file` in `sha256sum`-style `sums`.
+fn checksum(sums: &str, file: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        (name.trim().trim_start_matches('*') == file).then(|| hash.to_ascii_lowercase())
+    })
+}
+
+/// The version `nvim --version` reports, such as `0.10.4` for `NVIM v0.10.4`.
+fn nvim_version() -> Option<String> {
+    let line = super::program_version("nvim", &["--version"])?;
+    let version = line.strip_prefix("NVIM v")?;
+    Some(version.split_whitespace().next()?.to_string())
+}
+
+/// The `Candidate:` of `apt-cache policy neovim`, if there is one.
+fn apt_candidate() -> Option<String> {
+    let output = exec::query(Command::new("apt-cache").args(["policy", "neovim"])).ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Candidate:"))
+        .map(str::trim)
+        .filter(|version| !version.is_empty() && *version != "(none)")
+        .map(str::to_string)
+}
+
+/// Whether `version` is `min` or newer, comparing the leading numbers of each dotted
+/// part. A Debian epoch and revision, as in `1:0.7.2-7`, are ignored, and a dev
+/// build like `0.11.0-dev` counts as its release.
+fn version_at_least(version: &str, min: &str) -> bool {
+    fn parts(version: &str) -> Vec<u32> {
+        let version = version.split_once(':').map_or(version, |(_, rest)| rest);
+        let version = version
+            .split_once(['-', '+', '~'])
+            .map_or(version, |(upstream, _)| upstream);
+        version
+            .split('.')
+            .map(|part| {
+                part.chars()
+                    .take_while(char::is_ascii_digit)
+                    .collect::<String>()
+                    .parse()
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+    let (mut version, mut min) = (parts(version), parts(min));
+    let len = version.len().max(min.len());
+    version.resize(len, 0);
+    min.resize(len, 0);
+    version >= min
+}
+
+pub struct NeovimTarget;
+
+impl Target for NeovimTarget {
+    fn name(&self) -> &'static str {
+        "neovim"
+    }
+
+    fn description(&self) -> &'static str {
+        "Neovim at neovim.min_version or newer, with an optional config repository"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["nvim"]
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall)
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let report = run(&ctx.config()?.neovim)?;
+        ctx.report(&report)
+    }
+
+    fn uninstall(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall()
+    }
+
+    fn version(&self) -> Option<String> {
+        nvim_version()
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(&["neovim"])
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let config = &ctx.config()?.neovim;
+        let status = super::TargetStatus::new(self.name(), self.version());
+        let Some(version) = status.version.clone() else {
+            return Ok(status);
+        };
+        let mut drift = Vec::new();
+        if !version_at_least(&version, &config.min_version) {
+            drift.push(format!(
+                "nvim {version} is older than {}",
+                config.min_version
+            ));
+        }
+        let repo = config.config_repo.trim();
+        if !repo.is_empty() && config_origin(&target_user_context()?).as_deref() != Some(repo) {
+            drift.push(format!("~/.config/nvim is not a clone of {repo}"));
+        }
+        Ok(status.with_drift(drift))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn compares_versions_and_reads_checksums() {
+        assert!(version_at_least("0.10.4", "0.9.0"));
+        assert!(version_at_least("0.9", "0.9.0"));
+        assert!(!version_at_least("0.7.2-7", "0.9.0"));
+        assert!(version_at_least("1:0.9.5-6ubuntu2", "0.9.0"));
+        assert!(version_at_least("0.11.0-dev-1234+gabc", "0.11.0"));
+        assert!(!version_at_least("0.8.3", "0.10"));
+
+        let sums = "0123abcd  nvim-linux-arm64.tar.gz\nABCDEF01  nvim-linux-x86_64.tar.gz\n";
+        assert_eq!(
+            checksum(sums, "nvim-linux-x86_64.tar.gz").as_deref(),
+            Some("abcdef01")
+        );
+        assert_eq!(checksum(sums, "nvim-win64.zip"), None);
+    }
+
+    #[test]
+    fn dry_run_announces_the_release_without_downloading_it() {
+        let recorder = Arc::new(Recorder::new());
+        runner::with(recorder.clone(), || {
+            exec::dry_run(|| install_upstream("v0.10.4"))
+        })
+        .unwrap();
+        assert!(recorder.calls().is_empty());
+    }
+}
@@ -0,0 +1,372 @@
+//! `install nfs-mounts`: NFS shares from `[nfs_mounts]`, through autofs or fstab.
+//!
+//! With autofs, `/etc/auto.master.d/debkit.autofs` maps `/net` to every server's
+//! exports (`net = true`) and points at `/etc/auto.debkit`, a direct map of the
+//! configured mounts, which autofs mounts on first access. With fstab, the mounts go
+//! in a managed block of `/etc/fstab` and are mounted right away. Either way the
+//! shares are then checked, along with `wallpapers.folder` and `wallpapers.source`
+//! when they live on one, so `install foundation` stops here rather than configuring
+//! Variety on a folder that is not there.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::Serialize;
+
+use super::{Action, Target, shell_init};
+use crate::config::{DebkitConfig, NfsMountMethod, NfsMountsConfig};
+use crate::output::say;
+use crate::{apt, backup, exec, privileged, system};
+
+const AUTO_MASTER_PATH: &str = "/etc/auto.master";
+const AUTO_MASTER_DIR: &str = "/etc/auto.master.d";
+const AUTO_MASTER_INCLUDE: &str = "+dir:/etc/auto.master.d";
+const MASTER_DROPIN_PATH: &str = "/etc/auto.master.d/debkit.autofs";
+const DIRECT_MAP_PATH: &str = "/etc/auto.debkit";
+const FSTAB_PATH: &str = "/etc/fstab";
+const FSTAB_BLOCK: &str = "nfs-mounts";
+const AUTOFS_SERVICE: &str = "autofs.service";
+const MARKER: &str = "# Managed by debkit; `debkit install nfs-mounts` rewrites this file.";
+/// Seconds a share stays mounted after its last use.
+const AUTOFS_TIMEOUT: u32 = 300;
+
+#[derive(Debug, Serialize)]
+pub struct NfsMountsReport {
+    pub method: NfsMountMethod,
+    pub mounts: Vec<String>,
+    /// The wallpapers paths on a share that were checked.
+    pub verified: Vec<String>,
+}
+
+pub fn run(config: &DebkitConfig) -> anyhow::Result<NfsMountsReport> {
+    let nfs = &config.nfs_mounts;
+    if nfs.method == NfsMountMethod::Fstab {
+        apt::install_missing(&["nfs-common"])?;
+        remove_autofs_maps()?;
+        write_fstab(nfs)?;
+        mount_fstab_entries(nfs)?;
+    } else {
+        apt::install_missing(&["nfs-common", "autofs"])?;
+        remove_fstab_block()?;
+        write_autofs_maps(nfs)?;
+    }
+    if !nfs.net && nfs.mounts.is_empty() {
+        say!("No NFS mounts configured; add them under `[nfs_mounts.mounts]`.");
+    }
+
+    let mut paths = nfs.mounts.keys().cloned().collect::<Vec<_>>();
+    let wallpapers = [&config.wallpapers.folder, &config.wallpapers.source]
+        .into_iter()
+        .filter(|path| on_share(nfs, path))
+        .cloned()
+        .collect::<Vec<_>>();
+    paths.extend(wallpapers.iter().cloned());
+    if exec::is_dry_run() {
+        for path in &paths {
+            say!("Would check that {path} resolves once mounted.");
+        }
+    } else {
+        verify(&paths)?;
+    }
+    Ok(NfsMountsReport {
+        method: nfs.method,
+        mounts: nfs.mounts.keys().cloned().collect(),
+        verified: wallpapers,
+    })
+}
+
+/// Removes the autofs maps and the fstab block and unmounts the configured shares.
+/// nfs-common stays for mounts made by hand.
+pub fn uninstall(config: &NfsMountsConfig) -> anyhow::Result<()> {
+    remove_autofs_maps()?;
+    remove_fstab_block()?;
+    for mount_point in config.mounts.keys() {
+        if mounted(mount_point) {
+            privileged::run("umount", &[mount_point])?;
+            say!("Unmounted {mount_point}");
+        }
+    }
+    if apt::package_installed("autofs")? {
+        apt::remove(&["autofs"])?;
+    }
+    say!("Kept nfs-common.");
+    Ok(())
+}
+
+/// Whether `path` is on `/net` or under a configured mount point.
+fn on_share(config: &NfsMountsConfig, path: &str) -> bool {
+    let path = Path::new(path);
+    path.is_absolute()
+        && ((config.net && path.starts_with("/net"))
+            || config
+                .mounts
+                .keys()
+                .any(|mount_point| path.starts_with(mount_point)))
+}
+
+/// Lists each of `paths`, which makes autofs mount the share under it, and fails on
+/// the first that is not a readable directory.
+fn verify(paths: &[String]) -> anyhow::Result<()> {
+    for path in paths {
+        fs::read_dir(path).with_context(|| {
+            format!("{path} does not resolve; check that the NFS server is up and exports it")
+        })?;
+        say!("{path} resolves.");
+    }
+    Ok(())
+}
+
+fn write_autofs_maps(config: &NfsMountsConfig) -> anyhow::Result<()> {
+    let mut changed = false;
+    // The autofs package ships auto.master with the include; a dry run may not have it.
+    if let Ok(master) = fs::read_to_string(AUTO_MASTER_PATH)
+        && !master
+            .lines()
+            .any(|line| line.trim() == AUTO_MASTER_INCLUDE)
+    {
+        let separator = if master.is_empty() || master.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+        changed |= write_if_changed(
+            Path::new(AUTO_MASTER_PATH),
+            &format!("{master}{separator}{AUTO_MASTER_INCLUDE}\n"),
+        )?;
+    }
+    privileged::create_dir_all(Path::new(AUTO_MASTER_DIR))?;
+    changed |= write_if_changed(Path::new(MASTER_DROPIN_PATH), &render_master(config))?;
+    if config.mounts.is_empty() {
+        changed |= remove_if_managed(Path::new(DIRECT_MAP_PATH))?;
+    } else {
+        changed |= write_if_changed(Path::new(DIRECT_MAP_PATH), &render_direct_map(config))?;
+    }
+
+    if !system::systemctl_succeeds("is-enabled", AUTOFS_SERVICE)
+        || !system::systemctl_succeeds("is-active", AUTOFS_SERVICE)
+    {
+        privileged::run("systemctl", &["enable", "--now", AUTOFS_SERVICE])?;
+    } else if changed {
+        privileged::run("systemctl", &["reload", AUTOFS_SERVICE])?;
+    }
+    Ok(())
+}
+
+fn remove_autofs_maps() -> anyhow::Result<()> {
+    let removed = remove_if_managed(Path::new(MASTER_DROPIN_PATH))?
+        | remove_if_managed(Path::new(DIRECT_MAP_PATH))?;
+    if removed && system::systemctl_succeeds("is-active", AUTOFS_SERVICE) {
+        privileged::run("systemctl", &["reload", AUTOFS_SERVICE])?;
+    }
+    Ok(())
+}
+
+fn write_fstab(config: &NfsMountsConfig) -> anyhow::Result<()> {
+    let fstab = fs::read_to_string(FSTAB_PATH).unwrap_or_default();
+    let lines = fstab_lines(config);
+    let updated = if lines.is_empty() {
+        shell_init::without_managed_block(&fstab, FSTAB_BLOCK).unwrap_or(fstab)
+    } else {
+        let lines = lines.iter().map(String::as_str).collect::<Vec<_>>();
+        shell_init::with_managed_block(&fstab, FSTAB_BLOCK, &lines)
+    };
+    if write_if_changed(Path::new(FSTAB_PATH), &updated)? {
+        // systemd generates mount units from fstab.
+        privileged::run("systemctl", &["daemon-reload"])?;
+    }
+    Ok(())
+}
+
+fn remove_fstab_block() -> anyhow::Result<()> {
+    let fstab = fs::read_to_string(FSTAB_PATH).unwrap_or_default();
+    if let Some(updated) = shell_init::without_managed_block(&fstab, FSTAB_BLOCK) {
+        write_if_changed(Path::new(FSTAB_PATH), &updated)?;
+        privileged::run("systemctl", &["daemon-reload"])?;
+    }
+    Ok(())
+}
+
+fn mount_fstab_entries(config: &NfsMountsConfig) -> anyhow::Result<()> {
+    for mount_point in config.mounts.keys() {
+        if mounted(mount_point) {
+            continue;
+        }
+        privileged::create_dir_all(Path::new(mount_point))?;
+        privileged::run("mount", &[mount_point])?;
+        say!("Mounted {mount_point}");
+    }
+    Ok(())
+}
+
+fn render_master(config: &NfsMountsConfig) -> String {
+    let mut lines = vec![MARKER.to_string()];
+    if config.net {
+        lines.push(format!("/net -hosts --timeout={AUTOFS_TIMEOUT}"));
+    }
+    if !config.mounts.is_empty() {
+        lines.push(format!("/- {DIRECT_MAP_PATH} --timeout={AUTOFS_TIMEOUT}"));
+    }
+    lines.join("\n") + "\n"
+}
+
+fn render_direct_map(config: &NfsMountsConfig) -> String {
+    let mut lines = vec![MARKER.to_string()];
+    lines.extend(config.mounts.iter().map(|(mount_point, source)| {
+        format!("{mount_point} -fstype=nfs,{} {source}", config.options)
+    }));
+    lines.join("\n") + "\n"
+}
+
+/// One fstab line per mount. `_netdev` holds the mount back until the network is up.
+fn fstab_lines(config: &NfsMountsConfig) -> Vec<String> {
+    let mut options = config.options.clone();
+    if !options.split(',').any(|option| option == "_netdev") {
+        options.push_str(",_netdev");
+    }
+    config
+        .mounts
+        .iter()
+        .map(|(mount_point, source)| format!("{source} {mount_point} nfs {options} 0 0"))
+        .collect()
+}
+
+/// Whether something is mounted on `mount_point`, other than autofs's own trigger.
+fn mounted(mount_point: &str) -> bool {
+    fs::read_to_string("/proc/mounts").is_ok_and(|mounts| {
+        mounts.lines().any(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            fields.get(1) == Some(&mount_point) && fields.get(2) != Some(&"autofs")
+        })
+    })
+}
+
+fn write_if_changed(path: &Path, contents: &str) -> anyhow::Result<bool> {
+    if fs::read_to_string(path).is_ok_and(|existing| existing == contents) {
+        return Ok(false);
+    }
+    backup::save(path)?;
+    privileged::write(path, contents.as_bytes())?;
+    say!("Wrote {}", path.display());
+    Ok(true)
+}
+
+fn remove_if_managed(path: &Path) -> anyhow::Result<bool> {
+    if !fs::read_to_string(path).is_ok_and(|existing| existing.starts_with(MARKER)) {
+        return Ok(false);
+    }
+    privileged::remove_file(path)?;
+    say!("Removed {}", path.display());
+    Ok(true)
+}
+
+pub struct NfsMountsTarget;
+
+impl Target for NfsMountsTarget {
+    fn name(&self) -> &'static str {
+        "nfs-mounts"
+    }
+
+    fn description(&self) -> &'static str {
+        "NFS shares through autofs or fstab, checked before Variety uses them"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall)
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let report = run(ctx.config()?)?;
+        ctx.report(&report)
+    }
+
+    fn uninstall(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall(&ctx.config()?.nfs_mounts)
+    }
+
+    fn version(&self) -> Option<String> {
+        apt::package_version("nfs-common")
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(&["nfs-common", "autofs"])
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let config = &ctx.config()?.nfs_mounts;
+        let status = super::TargetStatus::new(self.name(), self.version());
+        if !status.installed {
+            return Ok(status);
+        }
+        let mut drift = Vec::new();
+        if config.method == NfsMountMethod::Fstab {
+            let lines = fstab_lines(config);
+            let lines = lines.iter().map(String::as_str).collect::<Vec<_>>();
+            if !lines.is_empty()
+                && !shell_init::has_managed_block(Path::new(FSTAB_PATH), FSTAB_BLOCK, &lines)
+            {
+                drift.push(format!("{FSTAB_PATH} does not have the configured mounts"));
+            }
+            drift.extend(
+                config
+                    .mounts
+                    .keys()
+                    .filter(|mount_point| !mounted(mount_point))
+                    .map(|mount_point| format!("{mount_point} is not mounted")),
+            );
+        } else {
+            let mut files = vec![(MASTER_DROPIN_PATH, render_master(config))];
+            if !config.mounts.is_empty() {
+                files.push((DIRECT_MAP_PATH, render_direct_map(config)));
+            }
+            drift.extend(
+                files
+                    .into_iter()
+                    .filter(|(path, contents)| {
+                        fs::read_to_string(path).ok().as_ref() != Some(contents)
+                    })
+                    .map(|(path, _)| format!("{path} does not match the config")),
+            );
+            if !system::systemctl_succeeds("is-active", AUTOFS_SERVICE) {
+                drift.push(format!("{AUTOFS_SERVICE} is not running"));
+            }
+        }
+        Ok(status.with_drift(drift))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn renders_autofs_maps_and_fstab_lines() {
+        let config = NfsMountsConfig {
+            net: true,
+            mounts: BTreeMap::from([(
+                "/srv/media".to_string(),
+                "spitfire:/export/media".to_string(),
+            )]),
+            ..NfsMountsConfig::default()
+        };
+        assert_eq!(
+            render_master(&config),
+            format!("{MARKER}\n/net -hosts --timeout=300\n/- /etc/auto.debkit --timeout=300\n")
+        );
+        assert_eq!(
+            render_direct_map(&config),
+            format!("{MARKER}\n/srv/media -fstype=nfs,rw,soft spitfire:/export/media\n")
+        );
+        assert_eq!(
+            fstab_lines(&config),
+            ["spitfire:/export/media /srv/media nfs rw,soft,_netdev 0 0"]
+        );
+
+        assert!(on_share(&config, "/net/spitfire/pictures/wallpapers"));
+        assert!(on_share(&config, "/srv/media/wallpapers"));
+        assert!(!on_share(&config, "/srv/mediawiki"));
+        assert!(!on_share(&config, "spitfire:/srv/wallpapers"));
+    }
+}
@@ -1,10 +1,15 @@
 use std::collections::BTreeSet;
+use std::fs;
 use std::path::Path;
 use std::process::Command;
 
 use anyhow::{Context, bail};
+use tracing::warn;
 
-use crate::config::NisConfig;
+use super::{Action, Target};
+use crate::config::{self, NisConfig};
+use crate::output::say;
+use crate::{exec, system};
 
 const DEFAULTDOMAIN_PATH: &str = "/etc/defaultdomain";
 const YP_CONF_PATH: &str = "/etc/yp.conf";
@@ -74,7 +79,7 @@ struct NisPlan {
 
 pub fn run(role: Role, config: &NisConfig) -> anyhow::Result<()> {
     if !config.enabled {
-        println!("NIS configuration is disabled (`nis.enabled = false`).");
+        say!("NIS configuration is disabled (`nis.enabled = false`).");
         return Ok(());
     }
 
@@ -98,16 +103,16 @@ pub fn run(role: Role, config: &NisConfig) -> anyhow::Result<()> {
         validate_nis_admin_group_membership(&plan);
     }
 
-    println!("NIS configured:");
-    println!("  Role: {}", plan.role.config_value());
-    println!("  Domain: {}", plan.domain);
+    say!("NIS configured:");
+    say!("  Role: {}", plan.role.config_value());
+    say!("  Domain: {}", plan.domain);
     if let Some(master) = &plan.master {
-        println!("  Master: {master}");
+        say!("  Master: {master}");
     }
     if plan.role.includes_client() {
-        println!("  NSS: local files are kept before NIS (`files nis`).");
+        say!("  NSS: local files are kept before NIS (`files nis`).");
     }
-    println!(
+    say!(
         "  Services: {}",
         if plan.services.is_empty() {
             "none".to_string()
@@ -115,7 +120,7 @@ pub fn run(role: Role, config: &NisConfig) -> anyhow::Result<()> {
             plan.services.join(", ")
         }
     );
-    println!(
+    say!(
         "Keep a local sudo-capable account on every machine for recovery; DebKit does not remove or rewrite local account databases."
     );
 
@@ -130,7 +135,7 @@ pub fn rebuild_and_push_maps(config: &NisConfig) -> anyhow::Result<()> {
     if domain.is_empty() {
         return Ok(());
     }
-    println!("Rebuilding NIS maps after group changes...");
+    say!("Rebuilding NIS maps after group changes...");
     rebuild_master_maps(domain)?;
     let plan = build_plan(Role::Configured, config)?;
     push_maps_to_slaves_if_requested(&plan)?;
@@ -144,7 +149,7 @@ pub fn configure(config: &NisConfig) -> anyhow::Result<()> {
     let plan = build_plan(Role::Configured, config)?;
     if plan.role == NisRole::Master {
         configure_master(&plan)?;
-        println!("NIS master maps rebuilt.");
+        say!("NIS master maps rebuilt.");
         return Ok(());
     }
     if plan.role != NisRole::Slave {
@@ -179,7 +184,7 @@ pub fn configure(config: &NisConfig) -> anyhow::Result<()> {
 
     register_slave_with_master(&plan);
 
-    println!("NIS slave maps force-refreshed from {master}.");
+    say!("NIS slave maps force-refreshed from {master}.");
     Ok(())
 }
 
@@ -194,7 +199,9 @@ fn configure_master(plan: &NisPlan) -> anyhow::Result<()> {
     initialize_master_maps_if_needed(&plan.domain)?;
     ensure_ypservers_source(&master, &plan.slaves)?;
     rebuild_ypservers_map(&plan.domain)?;
-    validate_ypservers_map(&plan.domain, &master, &plan.slaves)?;
+    if !exec::is_dry_run() {
+        validate_ypservers_map(&plan.domain, &master, &plan.slaves)?;
+    }
     rebuild_master_maps(&plan.domain)?;
     push_maps_to_slaves_if_requested(plan)?;
 
@@ -244,12 +251,10 @@ fn register_slave_with_master(plan: &NisPlan) {
     let slave_fqdn = match current_fqdn(&plan.domain) {
         Ok(fqdn) if !fqdn.is_empty() => fqdn,
         _ => {
-            eprintln!(
-                "warning: could not determine local FQDN; skipping registration with master {master}."
+            warn!(
+                "could not determine local FQDN; skipping registration with master {master}.\n{}",
+                register_manually(master, "<this-host-fqdn>")
             );
-            eprintln!("  To register manually, run on {master}:");
-            eprintln!("    debkit configure nis add-slave --host \"$(hostname)\" <this-host-fqdn>");
-            eprintln!("    sudo debkit configure nis");
             return;
         }
     };
@@ -264,35 +269,39 @@ fn register_slave_with_master(plan: &NisPlan) {
         "debkit configure nis add-slave --host \"$(hostname)\" {slave_fqdn} && sudo debkit configure nis"
     );
 
-    println!("Registering slave {slave_fqdn} with NIS master {master}...");
+    say!("Registering slave {slave_fqdn} with NIS master {master}...");
 
-    let status = Command::new("ssh")
-        .args([&ssh_target, &remote_cmd])
-        .status();
+    let status = exec::status(Command::new("ssh").args([&ssh_target, &remote_cmd]));
 
     match status {
         Ok(s) if s.success() => {
-            println!("Registered with master {master}.");
+            say!("Registered with master {master}.");
         }
         Ok(s) => {
-            eprintln!(
-                "warning: SSH to {master} exited with status {}; slave was not registered.",
+            warn!(
+                "SSH to {master} exited with status {}; slave was not registered.\n{}",
                 s.code()
-                    .map_or_else(|| "unknown".to_string(), |c| c.to_string())
+                    .map_or_else(|| "unknown".to_string(), |c| c.to_string()),
+                register_manually(master, &slave_fqdn)
             );
-            eprintln!("  To register manually, run on {master}:");
-            eprintln!("    debkit configure nis add-slave --host \"$(hostname)\" {slave_fqdn}");
-            eprintln!("    sudo debkit configure nis");
         }
         Err(e) => {
-            eprintln!("warning: could not SSH to {master}: {e}; slave was not registered.");
-            eprintln!("  To register manually, run on {master}:");
-            eprintln!("    debkit configure nis add-slave --host \"$(hostname)\" {slave_fqdn}");
-            eprintln!("    sudo debkit configure nis");
+            warn!(
+                "could not SSH to {master}: {e}; slave was not registered.\n{}",
+                register_manually(master, &slave_fqdn)
+            );
         }
     }
 }
 
+fn register_manually(master: &str, slave_fqdn: &str) -> String {
+    format!(
+        "  To register manually, run on {master}:\n    \
+         debkit configure nis add-slave --host \"$(hostname)\" {slave_fqdn}\n    \
+         sudo debkit configure nis"
+    )
+}
+
 fn configure_client(plan: &NisPlan) -> anyhow::Result<()> {
     ensure_root_file(
         Path::new(YP_CONF_PATH),
@@ -305,7 +314,7 @@ fn configure_client(plan: &NisPlan) -> anyhow::Result<()> {
 fn configure_server_only(plan: &NisPlan) -> anyhow::Result<()> {
     enable_required_services(plan)?;
     enable_optional_services(plan)?;
-    println!(
+    say!(
         "warning: `nis-server` is a compatibility install target only; configure role `master` or `slave` for normal DebKit NIS use."
     );
     Ok(())
@@ -534,6 +543,9 @@ fn ensure_ypservers_source(master: &str, slaves: &[String]) -> anyhow::Result<()
 fn rebuild_ypservers_map(domain: &str) -> anyhow::Result<()> {
     let domain_dir = Path::new(YP_MAP_ROOT).join(domain);
     ensure_root_dir(&domain_dir)?;
+    if exec::is_dry_run() && !Path::new(YPSERVERS_SOURCE_PATH).exists() {
+        return Ok(());
+    }
     let source = std::fs::read_to_string(YPSERVERS_SOURCE_PATH)
         .with_context(|| format!("failed to read {YPSERVERS_SOURCE_PATH}"))?;
     let hosts = source
@@ -547,13 +559,13 @@ fn rebuild_ypservers_map(domain: &str) -> anyhow::Result<()> {
         .with_context(|| format!("{YPSERVERS_SOURCE_PATH} contains no NIS servers"))?;
     let input = render_ypservers_makedbm_input(master, slaves);
     let tmp = format!("/tmp/debkit-ypservers-{}.map", std::process::id());
-    std::fs::write(&tmp, input).with_context(|| format!("failed to write {tmp}"))?;
+    exec::write(&tmp, input).with_context(|| format!("failed to write {tmp}"))?;
     let result = run_privileged_command(
         MAKEDBM_PATH,
         &[&tmp, &format!("{}/{domain}/ypservers", YP_MAP_ROOT)],
         "rebuilding NIS ypservers map",
     );
-    let _ = std::fs::remove_file(&tmp);
+    let _ = exec::remove_file(&tmp);
     result
 }
 
@@ -596,7 +608,7 @@ fn initialize_or_refresh_slave_maps(domain: &str, master: &str) -> anyhow::Resul
     ) {
         Ok(()) => Ok(()),
         Err(err) => {
-            println!(
+            say!(
                 "warning: `/usr/lib/yp/ypinit -s {master}` failed ({err:#}); falling back to direct ypxfr map transfer"
             );
             transfer_maps_manually(domain, master)
@@ -617,7 +629,7 @@ fn refresh_slave_maps_if_possible(domain: &str) -> anyhow::Result<()> {
         }
     }
     if !refreshed {
-        println!(
+        say!(
             "NIS slave maps for `{domain}` already exist; no Debian ypxfr refresh scripts were found, so existing local maps were kept."
         );
     }
@@ -655,7 +667,7 @@ fn push_maps_to_slaves_if_requested(plan: &NisPlan) -> anyhow::Result<()> {
         return Ok(());
     }
     if plan.slaves.is_empty() {
-        println!(
+        say!(
             "warning: `nis.push_to_slaves = true` but `nis.slaves` is empty; no slave pushes attempted"
         );
         return Ok(());
@@ -734,7 +746,7 @@ fn enable_and_start_service_if_available(service: &str) -> anyhow::Result<()> {
     if systemd_unit_exists(service) {
         enable_and_start_service(service)?;
     } else {
-        println!("warning: optional service `{service}` is not available; skipping");
+        say!("warning: optional service `{service}` is not available; skipping");
     }
     Ok(())
 }
@@ -753,44 +765,19 @@ fn systemd_unit_exists(service: &str) -> bool {
 }
 
 fn install_packages(packages: &[&str], label: &str) -> anyhow::Result<()> {
-    run_apt_command(&["update"])?;
-    let mut args = vec!["install", "-y"];
-    args.extend(packages);
-    run_apt_command(&args)?;
+    crate::apt::install(packages)?;
+    if exec::is_dry_run() {
+        return Ok(());
+    }
 
     for package in packages {
-        if !package_installed(package)? {
+        if !crate::apt::package_installed(package)? {
             bail!("`{package}` was not installed after installing {label}");
         }
     }
     Ok(())
 }
 
-fn run_apt_command(args: &[&str]) -> anyhow::Result<()> {
-    let euid = current_euid()?;
-
-    let mut command;
-    if euid == 0 {
-        command = Command::new("apt");
-        command.args(args);
-    } else if command_available("sudo") {
-        command = Command::new("sudo");
-        command.arg("apt").args(args);
-    } else {
-        bail!("installing NIS requires root privileges; run as root or install `sudo` and retry");
-    }
-
-    let status = command
-        .env("DEBIAN_FRONTEND", "noninteractive")
-        .status()
-        .context("failed to launch apt")?;
-    if !status.success() {
-        bail!("apt {} failed with status {}", args.join(" "), status);
-    }
-
-    Ok(())
-}
-
 fn run_privileged_command(program: &str, args: &[&str], label: &str) -> anyhow::Result<()> {
     run_privileged_command_with_input(program, args, "", label)
 }
@@ -801,36 +788,18 @@ fn run_privileged_command_with_input(
     stdin: &str,
     label: &str,
 ) -> anyhow::Result<()> {
-    let euid = current_euid()?;
-
     let mut command;
-    if euid == 0 {
+    if system::is_root() {
         command = Command::new(program);
         command.args(args);
-    } else if command_available("sudo") {
+    } else if system::command_available("sudo") {
         command = Command::new("sudo");
         command.arg(program).args(args);
     } else {
         bail!("{label} requires root privileges; run as root or install `sudo` and retry");
     }
 
-    let mut child = command
-        .stdin(std::process::Stdio::piped())
-        .spawn()
-        .with_context(|| format!("failed while {label}"))?;
-    if !stdin.is_empty() {
-        use std::io::Write;
-        child
-            .stdin
-            .as_mut()
-            .expect("piped stdin")
-            .write_all(stdin.as_bytes())
-            .with_context(|| format!("failed to write stdin while {label}"))?;
-    }
-    drop(child.stdin.take());
-
-    let status = child
-        .wait()
+    let status = exec::status_with_input(&mut command, stdin.as_bytes())
         .with_context(|| format!("failed while {label}"))?;
     if !status.success() {
         bail!("{program} {} failed with status {}", args.join(" "), status);
@@ -838,34 +807,22 @@ fn run_privileged_command_with_input(
     Ok(())
 }
 
-fn package_installed(package: &str) -> anyhow::Result<bool> {
-    let status = Command::new("dpkg-query")
-        .args(["-W", "-f=${Status}", package])
-        .status()
-        .with_context(|| format!("failed to query package `{package}`"))?;
-    Ok(status.success())
-}
-
 fn ensure_root_dir(path: &Path) -> anyhow::Result<bool> {
     if path.is_dir() {
         return Ok(false);
     }
-    if current_euid()? == 0 {
-        std::fs::create_dir_all(path)
+    if system::is_root() {
+        exec::create_dir_all(path)
             .with_context(|| format!("failed to create {}", path.display()))?;
         return Ok(true);
     }
-    if !command_available("sudo") {
+    if !system::command_available("sudo") {
         bail!(
             "creating {} requires root privileges; run as root or install `sudo` and retry",
             path.display()
         );
     }
-    let status = Command::new("sudo")
-        .arg("mkdir")
-        .arg("-p")
-        .arg(path)
-        .status()
+    let status = exec::status(Command::new("sudo").arg("mkdir").arg("-p").arg(path))
         .with_context(|| format!("failed to create {}", path.display()))?;
     if !status.success() {
         bail!(
@@ -886,35 +843,28 @@ fn ensure_root_file(path: &Path, content: &str) -> anyhow::Result<bool> {
         }
     }
 
-    if current_euid()? == 0 {
-        std::fs::write(path, content)
+    if system::is_root() {
+        exec::write(path, content)
             .with_context(|| format!("failed to write {}", path.display()))?;
         return Ok(true);
     }
 
-    if !command_available("sudo") {
+    if !system::command_available("sudo") {
         bail!(
             "writing {} requires root privileges; run as root or install `sudo` and retry",
             path.display()
         );
     }
 
-    let status = Command::new("sudo")
-        .arg("tee")
-        .arg(path)
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::null())
-        .spawn()
-        .and_then(|mut child| {
-            use std::io::Write;
-            child
-                .stdin
-                .as_mut()
-                .expect("piped stdin")
-                .write_all(content.as_bytes())?;
-            child.wait()
-        })
-        .with_context(|| format!("failed to write {}", path.display()))?;
+    let status = exec::write_via(
+        Command::new("sudo")
+            .arg("tee")
+            .arg(path)
+            .stdout(std::process::Stdio::null()),
+        path,
+        content.as_bytes(),
+    )
+    .with_context(|| format!("failed to write {}", path.display()))?;
     if !status.success() {
         bail!("sudo tee {} failed with status {}", path.display(), status);
     }
@@ -936,7 +886,7 @@ fn warn_if_no_local_admin(groups: &[String]) {
         })
         .unwrap_or(false);
     if !has_admin_member {
-        println!(
+        say!(
             "warning: no local admin group member was detected in /etc/group; keep at least one local admin account for recovery before relying on NIS."
         );
     }
@@ -958,29 +908,29 @@ fn validate_nis_admin_group_membership(plan: &NisPlan) {
         let initgroups_result = capture_status("getent", &["initgroups", user]);
         let id_result = capture_status("id", &[user]);
 
-        println!("Validated NIS group lookup commands:");
-        println!("  getent group {group}");
-        println!("  getent initgroups {user}");
-        println!("  id {user}");
+        say!("Validated NIS group lookup commands:");
+        say!("  getent group {group}");
+        say!("  getent initgroups {user}");
+        say!("  id {user}");
 
         let group_output = match group_result {
             Ok(output) => output,
             Err(err) => {
-                println!("warning: `getent group {group}` did not succeed: {err:#}");
+                say!("warning: `getent group {group}` did not succeed: {err:#}");
                 continue;
             }
         };
         let initgroups_output = match initgroups_result {
             Ok(output) => output,
             Err(err) => {
-                println!("warning: `getent initgroups {user}` did not succeed: {err:#}");
+                say!("warning: `getent initgroups {user}` did not succeed: {err:#}");
                 String::new()
             }
         };
         let id_output = match id_result {
             Ok(output) => output,
             Err(err) => {
-                println!("warning: `id {user}` did not succeed: {err:#}");
+                say!("warning: `id {user}` did not succeed: {err:#}");
                 String::new()
             }
         };
@@ -991,11 +941,11 @@ fn validate_nis_admin_group_membership(plan: &NisPlan) {
         if group_lists_user && (!initgroups_lists_group || !id_lists_group) {
             let nsswitch = std::fs::read_to_string(NSSWITCH_PATH).unwrap_or_default();
             if nsswitch_has_active_initgroups(&nsswitch) {
-                println!(
+                say!(
                     "warning: NIS group `{group}` lists `{user}`, but supplementary group lookup did not include it; /etc/nsswitch.conf has an active `initgroups:` line, so inspect that line before relying on NIS group membership."
                 );
             } else {
-                println!(
+                say!(
                     "warning: NIS group `{group}` lists `{user}`, but supplementary group lookup did not include it."
                 );
             }
@@ -1025,23 +975,12 @@ fn id_output_contains_group(raw: &str, group: &str) -> bool {
         .any(|field| field == group)
 }
 
-fn command_available(program: &str) -> bool {
-    Command::new("sh")
-        .arg("-c")
-        .arg(format!("command -v {program} >/dev/null 2>&1"))
-        .status()
-        .map(|status| status.success())
-        .unwrap_or(false)
-}
-
 fn capture(program: &str, args: &[&str]) -> anyhow::Result<String> {
     capture_status(program, args)
 }
 
 fn capture_status(program: &str, args: &[&str]) -> anyhow::Result<String> {
-    let output = Command::new(program)
-        .args(args)
-        .output()
+    let output = exec::query(Command::new(program).args(args))
         .with_context(|| format!("failed to run {program} {}", args.join(" ")))?;
     if !output.status.success() {
         bail!(
@@ -1073,22 +1012,6 @@ fn current_fqdn(domain: &str) -> anyhow::Result<String> {
     }
 }
 
-fn current_euid() -> anyhow::Result<u32> {
-    let output = Command::new("id")
-        .arg("-u")
-        .output()
-        .context("failed to run `id -u`")?;
-    if !output.status.success() {
-        bail!("`id -u` failed with status {}", output.status);
-    }
-
-    let stdout = String::from_utf8(output.stdout).context("`id -u` returned non-UTF-8 output")?;
-    let trimmed = stdout.trim();
-    trimmed
-        .parse::<u32>()
-        .with_context(|| format!("failed to parse `id -u` output `{trimmed}`"))
-}
-
 fn nonempty_unique<'a>(items: impl IntoIterator<Item = &'a str>) -> Vec<String> {
     let mut seen = BTreeSet::new();
     let mut out = Vec::new();
@@ -1135,6 +1058,121 @@ impl NisRole {
     }
 }
 
+pub struct NisTarget(pub Role);
+
+impl Target for NisTarget {
+    fn name(&self) -> &'static str {
+        match self.0 {
+            Role::Configured => "nis",
+            Role::Client => "nis-client",
+            Role::Server => "nis-server",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self.0 {
+            Role::Configured => "NIS client and server packages",
+            Role::Client => "NIS client packages",
+            Role::Server => "NIS server packages",
+        }
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        match self.0 {
+            Role::Configured => &[],
+            Role::Client => &["nis_client"],
+            Role::Server => &["nis_server"],
+        }
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        match self.0 {
+            Role::Configured => matches!(action, Action::Install | Action::Configure),
+            Role::Client | Role::Server => action == Action::Install,
+        }
+    }
+
+    fn args(&self, action: Action, command: clap::Command) -> clap::Command {
+        if action != Action::Configure {
+            return command;
+        }
+        command
+            .about("Force-refresh NIS maps on a configured slave")
+            .subcommand(
+                clap::Command::new("add-slave")
+                    .about("Add a slave FQDN to a master host config")
+                    .arg(clap::Arg::new("slave").required(true))
+                    .arg(clap::Arg::new("host").long("host").required(true)),
+            )
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        run(self.0, &ctx.config()?.nis)
+    }
+
+    fn configure(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let Some(("add-slave", add)) = ctx.subcommand() else {
+            return configure(&ctx.config()?.nis);
+        };
+        let slave = add
+            .get_one::<String>("slave")
+            .context("missing slave FQDN")?;
+        let host = add.get_one::<String>("host").context("missing --host")?;
+        add_slave(host, slave)
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let config = &ctx.config()?.nis;
+        if !config.enabled {
+            return Ok(super::TargetStatus::disabled(self.name()));
+        }
+        let mut status = super::TargetStatus::new(self.name(), None);
+
+        let plan = build_plan(self.0, config)?;
+        let mut drift = Vec::new();
+        for package in &plan.packages {
+            if !crate::apt::package_installed(package)? {
+                drift.push(format!("package `{package}` is not installed"));
+            }
+        }
+        status.installed = drift.is_empty();
+
+        let domain = fs::read_to_string(DEFAULTDOMAIN_PATH).unwrap_or_default();
+        if domain.trim() != plan.domain {
+            drift.push(format!(
+                "{DEFAULTDOMAIN_PATH} does not name domain `{}`",
+                plan.domain
+            ));
+        }
+        if plan.role.includes_client() {
+            let nsswitch = fs::read_to_string(NSSWITCH_PATH).unwrap_or_default();
+            if render_nsswitch_with_files_then_nis(&nsswitch) != nsswitch {
+                drift.push(format!("{NSSWITCH_PATH} does not use `files nis`"));
+            }
+        }
+        Ok(status.with_drift(drift))
+    }
+}
+
+fn add_slave(host: &str, slave: &str) -> anyhow::Result<()> {
+    let result = config::add_nis_slave_to_host(&config::path()?, host, slave)?;
+    if result.added {
+        say!("Added {slave} to {host} NIS slave list.");
+    } else {
+        say!("{slave} is already present in {host} NIS slave list.");
+    }
+    say!("\nNext:");
+    say!(
+        "  1. Run `debkit configure nis` on {host} to rebuild ypservers and push maps if enabled."
+    );
+    say!(
+        "  2. Run `debkit install nis` on {slave} if it has not already been configured as a slave."
+    );
+    say!("  3. Run `debkit configure nis` on {slave} to force-refresh stale slave maps.");
+    say!("\nUpdated: {}", result.path.display());
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
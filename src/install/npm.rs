@@ -1,12 +1,15 @@
 use std::env;
-use std::fs::{self, OpenOptions};
-use std::io::Write;
-use std::os::unix::fs::symlink;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use anyhow::{Context, bail};
 
+use super::{Action, Target, shell_init};
+use crate::exec;
+use crate::network;
+use crate::output::say;
+
 const NODE_BASE_URL: &str = "https://nodejs.org/dist";
 const LOCAL_BIN_PATH_LINE: &str = r#"export PATH="$HOME/.local/bin:$PATH""#;
 
@@ -22,7 +25,7 @@ pub fn run(options: Options) -> anyhow::Result<()> {
     let home = home_dir()?;
 
     if managed_install_exists_for(&home, &install_spec.version_dir_name) {
-        println!(
+        say!(
             "npm already installed from upstream Node.js binaries ({}):",
             install_spec.display_version
         );
@@ -42,6 +45,9 @@ pub fn run(options: Options) -> anyhow::Result<()> {
     }
 
     install_nodejs_from_upstream(&home, &install_spec)?;
+    if exec::is_dry_run() {
+        return Ok(());
+    }
 
     if !managed_install_exists_for(&home, &install_spec.version_dir_name) {
         bail!(
@@ -50,7 +56,7 @@ pub fn run(options: Options) -> anyhow::Result<()> {
         );
     }
 
-    println!("npm installation complete:");
+    say!("npm installation complete:");
     run_command(
         managed_program_path_for_home(&home, "node")
             .to_string_lossy()
@@ -84,35 +90,42 @@ pub fn uninstall() -> anyhow::Result<()> {
 
     for bin in managed_bins {
         if bin.exists() || fs::symlink_metadata(&bin).is_ok() {
-            fs::remove_file(&bin).with_context(|| format!("failed to remove {}", bin.display()))?;
+            exec::remove_file(&bin)
+                .with_context(|| format!("failed to remove {}", bin.display()))?;
             removed_any = true;
         }
     }
 
     if fs::symlink_metadata(&current_link).is_ok() {
-        fs::remove_file(&current_link)
+        exec::remove_file(&current_link)
             .with_context(|| format!("failed to remove {}", current_link.display()))?;
         removed_any = true;
     }
 
     if install_root.exists() {
-        fs::remove_dir_all(&install_root)
+        exec::remove_dir_all(&install_root)
             .with_context(|| format!("failed to remove {}", install_root.display()))?;
         removed_any = true;
     }
 
     if local_node_modules.exists() {
-        fs::remove_dir_all(&local_node_modules)
+        exec::remove_dir_all(&local_node_modules)
             .with_context(|| format!("failed to remove {}", local_node_modules.display()))?;
         removed_any = true;
     }
 
+    let shell_files = shell_init::files(&home);
+    for file in shell_init::remove_block(&shell_files, &[LOCAL_BIN_PATH_LINE])? {
+        say!("Removed ~/.local/bin PATH entry from {}", file.display());
+        removed_any = true;
+    }
+
     if !removed_any {
-        println!("npm is not installed.");
+        say!("npm is not installed.");
         return Ok(());
     }
 
-    println!("npm uninstalled.");
+    say!("npm uninstalled.");
     Ok(())
 }
 
@@ -173,7 +186,7 @@ fn install_nodejs_from_upstream(home: &Path, install_spec: &InstallSpec) -> anyh
     let archive_name = parse_archive_name(&shasums, arch)?;
 
     let tmp_dir = managed_tmp_dir_for_home(home);
-    fs::create_dir_all(&tmp_dir)
+    exec::create_dir_all(&tmp_dir)
         .with_context(|| format!("failed to create {}", tmp_dir.display()))?;
 
     let archive_path = tmp_dir.join(&archive_name);
@@ -181,7 +194,7 @@ fn install_nodejs_from_upstream(home: &Path, install_spec: &InstallSpec) -> anyh
         "{NODE_BASE_URL}/{}/{archive_name}",
         install_spec.release_path
     );
-    run_command(
+    run_step(
         "curl",
         &[
             "-fsSL",
@@ -194,13 +207,13 @@ fn install_nodejs_from_upstream(home: &Path, install_spec: &InstallSpec) -> anyh
 
     let extract_dir = tmp_dir.join("extract");
     if extract_dir.exists() {
-        fs::remove_dir_all(&extract_dir)
+        exec::remove_dir_all(&extract_dir)
             .with_context(|| format!("failed to reset {}", extract_dir.display()))?;
     }
-    fs::create_dir_all(&extract_dir)
+    exec::create_dir_all(&extract_dir)
         .with_context(|| format!("failed to create {}", extract_dir.display()))?;
 
-    run_command(
+    run_step(
         "tar",
         &[
             "-xJf",
@@ -212,7 +225,7 @@ fn install_nodejs_from_upstream(home: &Path, install_spec: &InstallSpec) -> anyh
     .context("failed to extract Node.js archive")?;
 
     let version_dir = extract_dir.join(&install_spec.version_dir_name);
-    if !version_dir.exists() {
+    if !version_dir.exists() && !exec::is_dry_run() {
         bail!(
             "expected extracted Node.js directory `{}` was not found",
             version_dir.display()
@@ -224,16 +237,16 @@ fn install_nodejs_from_upstream(home: &Path, install_spec: &InstallSpec) -> anyh
     let current_link = managed_current_link_for_home(home);
     let local_bin_dir = managed_bin_dir_for_home(home);
 
-    fs::create_dir_all(&install_root)
+    exec::create_dir_all(&install_root)
         .with_context(|| format!("failed to create {}", install_root.display()))?;
-    fs::create_dir_all(&local_bin_dir)
+    exec::create_dir_all(&local_bin_dir)
         .with_context(|| format!("failed to create {}", local_bin_dir.display()))?;
 
     if installed_version_dir.exists() {
-        fs::remove_dir_all(&installed_version_dir)
+        exec::remove_dir_all(&installed_version_dir)
             .with_context(|| format!("failed to reset {}", installed_version_dir.display()))?;
     }
-    run_command(
+    run_step(
         "cp",
         &[
             "-a",
@@ -256,39 +269,14 @@ fn install_nodejs_from_upstream(home: &Path, install_spec: &InstallSpec) -> anyh
         &current_link.join("bin").join("npx"),
     )?;
 
-    fs::remove_dir_all(&tmp_dir)
+    exec::remove_dir_all(&tmp_dir)
         .with_context(|| format!("failed to clean up {}", tmp_dir.display()))?;
 
     Ok(())
 }
 
 fn ensure_shell_init_sources_local_bin() -> anyhow::Result<()> {
-    let home = home_dir()?;
-    let files = [home.join(".bashrc"), home.join(".profile")];
-
-    for file in files {
-        if !file.exists() {
-            fs::write(&file, "").with_context(|| format!("failed to create {}", file.display()))?;
-        }
-
-        let content = fs::read_to_string(&file)
-            .with_context(|| format!("failed to read {}", file.display()))?;
-        if content
-            .lines()
-            .any(|existing| existing.trim() == LOCAL_BIN_PATH_LINE)
-        {
-            continue;
-        }
-
-        let mut handle = OpenOptions::new()
-            .append(true)
-            .open(&file)
-            .with_context(|| format!("failed to open {} for append", file.display()))?;
-        writeln!(handle)?;
-        writeln!(handle, "{LOCAL_BIN_PATH_LINE}")?;
-    }
-
-    Ok(())
+    shell_init::ensure_line(&shell_init::files(&home_dir()?), LOCAL_BIN_PATH_LINE)
 }
 
 fn ensure_symlink(link: &Path, target: &Path) -> anyhow::Result<()> {
@@ -296,7 +284,7 @@ fn ensure_symlink(link: &Path, target: &Path) -> anyhow::Result<()> {
         if existing_target == target {
             return Ok(());
         }
-        fs::remove_file(link).with_context(|| format!("failed to remove {}", link.display()))?;
+        exec::remove_file(link).with_context(|| format!("failed to remove {}", link.display()))?;
     } else if link.exists() {
         bail!(
             "refusing to overwrite existing non-symlink path `{}`",
@@ -304,7 +292,7 @@ fn ensure_symlink(link: &Path, target: &Path) -> anyhow::Result<()> {
         );
     }
 
-    symlink(target, link).with_context(|| {
+    exec::symlink(target, link).with_context(|| {
         format!(
             "failed to create symlink {} -> {}",
             link.display(),
@@ -383,14 +371,25 @@ fn parse_archive_name(shasums: &str, arch: &str) -> anyhow::Result<String> {
 }
 
 fn run_command(program: &str, args: &[&str]) -> anyhow::Result<()> {
-    let status = Command::new(program)
-        .args(args)
-        .status()
+    let status = exec::probe(Command::new(program).args(args))
         .with_context(|| format!("failed to start `{program}`"))?;
     if !status.success() {
         bail!(
-            "command `{}` failed with status {}",
-            format!("{program} {}", args.join(" ")),
+            "command `{program} {}` failed with status {}",
+            args.join(" "),
+            status
+        );
+    }
+    Ok(())
+}
+
+fn run_step(program: &str, args: &[&str]) -> anyhow::Result<()> {
+    let status = exec::status(network::apply(&mut Command::new(program)).args(args))
+        .with_context(|| format!("failed to start `{program}`"))?;
+    if !status.success() {
+        bail!(
+            "command `{program} {}` failed with status {}",
+            args.join(" "),
             status
         );
     }
@@ -398,14 +397,12 @@ fn run_command(program: &str, args: &[&str]) -> anyhow::Result<()> {
 }
 
 fn run_capture_command(program: &str, args: &[&str]) -> anyhow::Result<String> {
-    let output = Command::new(program)
-        .args(args)
-        .output()
+    let output = exec::query(network::apply(&mut Command::new(program)).args(args))
         .with_context(|| format!("failed to start `{program}`"))?;
     if !output.status.success() {
         bail!(
-            "command `{}` failed with status {}",
-            format!("{program} {}", args.join(" ")),
+            "command `{program} {}` failed with status {}",
+            args.join(" "),
             output.status
         );
     }
@@ -418,3 +415,71 @@ fn home_dir() -> anyhow::Result<PathBuf> {
         .map(PathBuf::from)
         .context("HOME environment variable is not set")
 }
+
+pub struct NpmTarget;
+
+impl Target for NpmTarget {
+    fn name(&self) -> &'static str {
+        "npm"
+    }
+
+    fn description(&self) -> &'static str {
+        "Node.js and npm from official Node.js binaries"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall)
+    }
+
+    fn args(&self, action: Action, command: clap::Command) -> clap::Command {
+        if action != Action::Install {
+            return command;
+        }
+        command.arg(
+            clap::Arg::new("version")
+                .long("version")
+                .value_name("VERSION")
+                .help("Node.js version to install [default: `npm.version` from config]"),
+        )
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let version = match ctx.value("version") {
+            Some(version) => version.to_string(),
+            None => ctx.config()?.npm.version.clone(),
+        };
+        run(Options { version })
+    }
+
+    fn uninstall(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall()
+    }
+
+    fn version(&self) -> Option<String> {
+        super::program_version("node", &["--version"])
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let status = super::TargetStatus::new(self.name(), self.version());
+        if !status.installed {
+            return Ok(status);
+        }
+        let mut drift =
+            shell_init::missing_line(&shell_init::files(&home_dir()?), LOCAL_BIN_PATH_LINE)
+                .into_iter()
+                .map(|file| format!("{} does not add ~/.local/bin to PATH", file.display()))
+                .collect::<Vec<_>>();
+
+        let wanted = ctx.config()?.npm.version.trim().trim_start_matches('v');
+        let pinned = wanted.split('.').all(|part| part.parse::<u32>().is_ok());
+        if let Some(installed) = &status.version
+            && pinned
+            && installed.trim_start_matches('v') != wanted
+        {
+            drift.push(format!(
+                "Node.js {installed} is installed but config wants {wanted}"
+            ));
+        }
+        Ok(status.with_drift(drift))
+    }
+}
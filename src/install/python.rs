@@ -0,0 +1,330 @@
+//! `install python`: Python 3 with venv, pip and pipx from APT, and the command-line
+//! tools in `python.tools` installed with pipx.
+//!
+//! pipx gives each tool its own virtual environment under the target user's home and
+//! links its commands into `~/.local/bin`. An entry is a package name, or
+//! `name==version` to pin it; a pinned tool at another version is installed again
+//! with `--force`. `status python` lists each tool with its installed version.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+
+use super::user::{UserContext, command_as, target_user_context};
+use super::{Action, Target};
+use crate::config::PythonConfig;
+use crate::output::{self, say};
+use crate::{apt, exec, network, offline};
+
+const PACKAGES: &[&str] = &["python3", "python3-venv", "python3-pip", "pipx"];
+
+#[derive(Debug, Serialize)]
+pub struct PythonStatus {
+    pub python: Option<String>,
+    pub pipx: Option<String>,
+    pub tools: Vec<ToolStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ToolStatus {
+    pub name: String,
+    /// The pinned version, if any.
+    pub wanted: Option<String>,
+    pub version: Option<String>,
+}
+
+impl ToolStatus {
+    fn up_to_date(&self) -> bool {
+        match (&self.wanted, &self.version) {
+            (_, None) => false,
+            (Some(wanted), Some(version)) => wanted == version,
+            (None, Some(_)) => true,
+        }
+    }
+}
+
+impl PythonStatus {
+    fn drift(&self) -> Vec<String> {
+        self.tools
+            .iter()
+            .filter(|tool| !tool.up_to_date())
+            .map(|tool| match (&tool.wanted, &tool.version) {
+                (Some(wanted), Some(version)) => {
+                    format!("{} is {version}, not {wanted}", tool.name)
+                }
+                _ => format!("{} is not installed", tool.name),
+            })
+            .collect()
+    }
+}
+
+pub fn run(config: &PythonConfig) -> anyhow::Result<PythonStatus> {
+    apt::install_missing(PACKAGES)?;
+    let user = target_user_context()?;
+    // A dry run on a fresh machine has no pipx yet to ask.
+    let installed = match installed_tools(&user) {
+        Err(_) if exec::is_dry_run() => BTreeMap::new(),
+        installed => installed?,
+    };
+    for entry in &config.tools {
+        let tool = tool_status(entry, &installed);
+        if tool.up_to_date() {
+            say!(
+                "{} {} is installed",
+                tool.name,
+                tool.version.as_deref().unwrap_or_default()
+            );
+            continue;
+        }
+        if offline::is_enabled() {
+            bail!("installing {entry} with pipx needs network access");
+        }
+        let mut args = vec!["install"];
+        if tool.version.is_some() {
+            args.push("--force");
+        }
+        args.push(entry);
+        run_pipx(&user, &args)?;
+    }
+    if config.tools.is_empty() {
+        say!("No pipx tools to install; list them in `python.tools`.");
+    } else if !on_path(&user.home.join(".local/bin")) {
+        say!("Add ~/.local/bin to PATH to run the pipx tools, or run `pipx ensurepath`.");
+    }
+    collect_status(config, &user)
+}
+
+/// Uninstalls the configured tools with pipx. Python itself stays, since much of the
+/// system depends on it.
+pub fn uninstall(config: &PythonConfig) -> anyhow::Result<()> {
+    let user = target_user_context()?;
+    let installed = installed_tools(&user).unwrap_or_default();
+    let mut removed_any = false;
+    for entry in &config.tools {
+        let tool = tool_status(entry, &installed);
+        if tool.version.is_some() {
+            run_pipx(&user, &["uninstall", &tool.name])?;
+            removed_any = true;
+        }
+    }
+    if !removed_any {
+        say!("No configured pipx tools are installed.");
+    }
+    say!("Kept python3 and pipx.");
+    Ok(())
+}
+
+pub fn collect_status(config: &PythonConfig, user: &UserContext) -> anyhow::Result<PythonStatus> {
+    let installed = if apt::package_version("pipx").is_some() {
+        installed_tools(user)?
+    } else {
+        BTreeMap::new()
+    };
+    Ok(PythonStatus {
+        python: apt::package_version("python3"),
+        pipx: apt::package_version("pipx"),
+        tools: config
+            .tools
+            .iter()
+            .map(|entry| tool_status(entry, &installed))
+            .collect(),
+    })
+}
+
+fn tool_status(entry: &str, installed: &BTreeMap<String, String>) -> ToolStatus {
+    let (name, wanted) = match entry.split_once("==") {
+        Some((name, version)) => (name, Some(version.to_string())),
+        None => (entry, None),
+    };
+    ToolStatus {
+        name: name.to_string(),
+        wanted,
+        version: installed.get(&name.to_ascii_lowercase()).cloned(),
+    }
+}
+
+fn print_status_report(status: &PythonStatus) {
+    say!(
+        "python3: {}",
+        status.python.as_deref().unwrap_or("not installed")
+    );
+    say!(
+        "pipx: {}",
+        status.pipx.as_deref().unwrap_or("not installed")
+    );
+    if status.tools.is_empty() {
+        say!("No tools listed in `python.tools`.");
+        return;
+    }
+    let width = status
+        .tools
+        .iter()
+        .map(|tool| tool.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("TOOL".len());
+    say!("{:<width$}  {:<12} WANTED", "TOOL", "VERSION");
+    for tool in &status.tools {
+        say!(
+            "{:<width$}  {:<12} {}",
+            tool.name,
+            tool.version.as_deref().unwrap_or("missing"),
+            tool.wanted.as_deref().unwrap_or("any")
+        );
+    }
+}
+
+#[derive(Deserialize)]
+struct PipxList {
+    venvs: BTreeMap<String, PipxVenv>,
+}
+
+#[derive(Deserialize)]
+struct PipxVenv {
+    metadata: PipxMetadata,
+}
+
+#[derive(Deserialize)]
+struct PipxMetadata {
+    main_package: PipxPackage,
+}
+
+#[derive(Deserialize)]
+struct PipxPackage {
+    package: String,
+    package_version: String,
+}
+
+/// The version of each package pipx installed for the user, by lowercase name.
+fn installed_tools(user: &UserContext) -> anyhow::Result<BTreeMap<String, String>> {
+    let output = exec::query(command_as(user, "pipx")?.args(["list", "--json"]))
+        .context("failed to start `pipx list`")?;
+    if !output.status.success() {
+        bail!(
+            "`pipx list --json` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    parse_pipx_list(&output.stdout)
+}
+
+fn parse_pipx_list(json: &[u8]) -> anyhow::Result<BTreeMap<String, String>> {
+    let list: PipxList =
+        serde_json::from_slice(json).context("could not read `pipx list --json`")?;
+    Ok(list
+        .venvs
+        .into_values()
+        .map(|venv| {
+            let package = venv.metadata.main_package;
+            (
+                package.package.to_ascii_lowercase(),
+                package.package_version,
+            )
+        })
+        .collect())
+}
+
+fn on_path(dir: &Path) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|entry| entry == dir))
+}
+
+fn run_pipx(user: &UserContext, args: &[&str]) -> anyhow::Result<()> {
+    let step = format!("pipx {}", args.join(" "));
+    let status = exec::status_step(
+        network::apply(&mut command_as(user, "pipx")?).args(args),
+        &step,
+    )
+    .context("failed to start `pipx`")?;
+    if !status.success() {
+        bail!("command `{step}` failed with status {status}");
+    }
+    Ok(())
+}
+
+pub struct PythonTarget;
+
+impl Target for PythonTarget {
+    fn name(&self) -> &'static str {
+        "python"
+    }
+
+    fn description(&self) -> &'static str {
+        "Python 3 with venv, pip and pipx, and tools installed with pipx"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall | Action::Status)
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let status = run(&ctx.config()?.python)?;
+        ctx.report(&status)
+    }
+
+    fn uninstall(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall(&ctx.config()?.python)
+    }
+
+    fn status(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let status = collect_status(&ctx.config()?.python, &target_user_context()?)?;
+        if !output::is_json() {
+            print_status_report(&status);
+        }
+        ctx.report(&status)?;
+        if !status.drift().is_empty() {
+            return Err(output::Drift.into());
+        }
+        Ok(())
+    }
+
+    fn version(&self) -> Option<String> {
+        apt::package_version("pipx")
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(PACKAGES)
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let status = collect_status(&ctx.config()?.python, &target_user_context()?)?;
+        let summary = super::TargetStatus::new(self.name(), status.pipx.clone());
+        if !summary.installed {
+            return Ok(summary);
+        }
+        Ok(summary.with_drift(status.drift()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_pipx_versions_and_pins() {
+        let json = br#"{"pipx_spec_version": "0.1", "venvs": {
+            "httpie": {"metadata": {"main_package": {"package": "httpie", "package_version": "3.2.4"}}},
+            "ansible-core": {"metadata": {"main_package": {"package": "ansible-core", "package_version": "2.17.5"}}}
+        }}"#;
+        let installed = parse_pipx_list(json).unwrap();
+        assert_eq!(installed.get("httpie").map(String::as_str), Some("3.2.4"));
+
+        assert!(tool_status("httpie", &installed).up_to_date());
+        let pinned = tool_status("ansible-core==2.18.0", &installed);
+        assert!(!pinned.up_to_date());
+        let status = PythonStatus {
+            python: None,
+            pipx: None,
+            tools: vec![pinned, tool_status("black", &installed)],
+        };
+        assert_eq!(
+            status.drift(),
+            [
+                "ansible-core is 2.17.5, not 2.18.0",
+                "black is not installed"
+            ]
+        );
+    }
+}
@@ -2,64 +2,96 @@ use std::process::Command;
 
 use anyhow::{Context, bail};
 
+use super::{Action, Target};
+use crate::output::say;
+use crate::{exec, system};
+
 pub fn run() -> anyhow::Result<()> {
-    if command_available("rg") {
+    if system::command_available("rg") {
         run_command("rg", &["--version"])?;
         return Ok(());
     }
 
     install_ripgrep_package()?;
+    if exec::is_dry_run() {
+        return Ok(());
+    }
 
-    if !command_available("rg") {
+    if !system::command_available("rg") {
         bail!("`rg` was not found on PATH after installation");
     }
 
-    println!("ripgrep installation complete:");
+    say!("ripgrep installation complete:");
     run_command("rg", &["--version"])?;
 
     Ok(())
 }
 
 pub fn uninstall() -> anyhow::Result<()> {
-    if !command_available("rg") {
-        println!("ripgrep is not installed.");
+    if !system::command_available("rg") {
+        say!("ripgrep is not installed.");
         return Ok(());
     }
 
-    super::apt::remove(&["ripgrep"])?;
+    crate::apt::remove(&["ripgrep"])?;
+    if exec::is_dry_run() {
+        return Ok(());
+    }
 
-    if command_available("rg") {
+    if system::command_available("rg") {
         bail!("`rg` is still available on PATH after uninstall");
     }
 
-    println!("ripgrep uninstalled.");
+    say!("ripgrep uninstalled.");
     Ok(())
 }
 
 fn install_ripgrep_package() -> anyhow::Result<()> {
-    super::apt::install_missing(&["ripgrep"])?;
+    crate::apt::install_missing(&["ripgrep"])?;
     Ok(())
 }
 
 fn run_command(program: &str, args: &[&str]) -> anyhow::Result<()> {
-    let status = Command::new(program)
-        .args(args)
-        .status()
+    let status = exec::probe(Command::new(program).args(args))
         .with_context(|| format!("failed to start `{program}`"))?;
     if !status.success() {
         bail!(
-            "command `{}` failed with status {}",
-            format!("{program} {}", args.join(" ")),
+            "command `{program} {}` failed with status {}",
+            args.join(" "),
             status
         );
     }
     Ok(())
 }
 
-fn command_available(program: &str) -> bool {
-    Command::new(program)
-        .arg("--version")
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false)
+pub struct RipgrepTarget;
+
+impl Target for RipgrepTarget {
+    fn name(&self) -> &'static str {
+        "ripgrep"
+    }
+
+    fn description(&self) -> &'static str {
+        "ripgrep recursive search tool"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall)
+    }
+
+    fn install(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        run()
+    }
+
+    fn uninstall(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall()
+    }
+
+    fn version(&self) -> Option<String> {
+        super::program_version("rg", &["--version"])
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(&["ripgrep"])
+    }
 }
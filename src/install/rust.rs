@@ -1,92 +1,414 @@
 use std::env;
-use std::fs::{self, OpenOptions};
-use std::io::Write;
-use std::path::PathBuf;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use anyhow::{Context, bail};
+use serde::Serialize;
+
+use super::{Action, Target, shell_init};
+use crate::config::{DEFAULT_RUSTUP_VERSION, RustConfig};
+use crate::output::{self, say};
+use crate::{audit, download, exec, network, offline, scratch, system};
+
+/// The line DebKit appended to `.bashrc` and `.profile` before it used a managed block.
+const LEGACY_CARGO_ENV_LINE: &str = r#"source "$HOME/.cargo/env""#;
+const CARGO_ENV_BLOCK: &str = "cargo-env";
+const POSIX_CARGO_ENV: &[&str] = &[r#". "$HOME/.cargo/env""#];
+const FISH_CARGO_ENV: &[&str] = &[r#"fish_add_path "$HOME/.cargo/bin""#];
+const RUSTUP_INIT_ARGS: &[&str] = &["-y", "--profile", "default", "--default-toolchain"];
+/// The SHA-256 of `rustup-init` for [`DEFAULT_RUSTUP_VERSION`], by host triple.
+const RUSTUP_INIT_SHA256: &[(&str, &str)] = &[
+    (
+        "x86_64-unknown-linux-gnu",
+        "4acc9acc76d5079515b46346a485974457b5a79893cfb01112423c89aeb5aa10",
+    ),
+    (
+        "aarch64-unknown-linux-gnu",
+        "9732d6c5e2a098d3521fca8145d826ae0aaa067ef2385ead08e6feac88fa5792",
+    ),
+];
 
 #[derive(Debug, Clone)]
 pub struct Options {
     pub reinstall: bool,
+    /// A local `rustup-init` to run instead of downloading it from static.rust-lang.org.
+    pub rustup_init: Option<PathBuf>,
+    /// The rustup release whose `rustup-init` is downloaded.
+    pub rustup_version: String,
+    /// The SHA-256 the downloaded `rustup-init` must have, instead of the one pinned
+    /// for [`DEFAULT_RUSTUP_VERSION`]. A local one is checked against it too.
+    pub rustup_init_sha256: Option<String>,
+    /// The default toolchain: a channel such as `stable` or an exact version such as
+    /// `1.79.0`.
+    pub toolchain: String,
+    /// rustup components, such as `rust-src`, to add to every installed toolchain.
+    pub components: Vec<String>,
+    /// Toolchains to install next to the default one, such as `nightly`.
+    pub extra_toolchains: Vec<String>,
+    /// Targets, such as `wasm32-unknown-unknown`, to add to every installed toolchain.
+    pub targets: Vec<String>,
 }
 
 pub fn run(options: Options) -> anyhow::Result<()> {
     ensure_shell_init_sources_cargo_env()?;
 
-    if !options.reinstall && command_available("cargo") && command_available("rustc") {
-        println!("Rust already installed:");
+    // rustup's proxies are on PATH even without a default toolchain, so ask rustc.
+    if !options.reinstall && command_available("cargo") && rustc_version().is_some() {
+        say!("Rust already installed:");
         run_command("cargo", &["--version"])?;
         run_command("rustc", &["--version"])?;
-        return Ok(());
+        if !listed(&rustup_lines(&["default"]), &options.toolchain) {
+            set_default(&options.toolchain)?;
+            if !exec::is_dry_run() {
+                say!("Default toolchain is now {}:", options.toolchain);
+                run_command("rustc", &["--version"])?;
+            }
+        }
+    } else {
+        install_rustup(&options)?;
     }
 
+    for toolchain in &options.extra_toolchains {
+        if !listed(&rustup_lines(&["toolchain", "list"]), toolchain) {
+            run_step("rustup", &["toolchain", "install", toolchain])?;
+        }
+    }
+    add_missing("component", &options.components, &options.extra_toolchains)?;
+    add_missing("target", &options.targets, &options.extra_toolchains)
+}
+
+fn install_rustup(options: &Options) -> anyhow::Result<()> {
     if command_available("rustup") {
         if options.reinstall {
-            run_command("rustup", &["self", "update"])?;
+            run_step("rustup", &["self", "update"])?;
         }
-        run_command("rustup", &["toolchain", "install", "stable"])?;
-        run_command("rustup", &["default", "stable"])?;
+        set_default(&options.toolchain)?;
     } else {
-        run_shell_command(
-            "curl --proto '=https' --tlsv1.2 -sSf https://sh.rustup.rs | \
-             sh -s -- -y --profile default --default-toolchain stable",
-        )?;
+        let mut args = RUSTUP_INIT_ARGS.to_vec();
+        args.push(&options.toolchain);
+        if let Some(rustup_init) = local_rustup_init(options.rustup_init.clone())? {
+            if let Some(expected) = &options.rustup_init_sha256 {
+                verify_sha256(&rustup_init, expected)?;
+            }
+            run_step(&rustup_init.to_string_lossy(), &args)?;
+        } else {
+            let rustup_init = download_rustup_init(options)?;
+            let result = run_step(&rustup_init.to_string_lossy(), &args);
+            if !exec::is_dry_run()
+                && let Some(dir) = rustup_init.parent()
+            {
+                let _ = fs::remove_dir_all(dir);
+            }
+            result?;
+        }
     }
 
     ensure_shell_init_sources_cargo_env()?;
-    println!("Rust installation complete:");
+    if exec::is_dry_run() {
+        return Ok(());
+    }
+    say!("Rust installation complete:");
     run_command("cargo", &["--version"])?;
     run_command("rustc", &["--version"])?;
-
     Ok(())
 }
 
-fn ensure_shell_init_sources_cargo_env() -> anyhow::Result<()> {
-    let home = home_dir()?;
-    let line = r#"source "$HOME/.cargo/env""#;
-    let files = [home.join(".bashrc"), home.join(".profile")];
+fn set_default(toolchain: &str) -> anyhow::Result<()> {
+    run_step("rustup", &["toolchain", "install", toolchain])?;
+    run_step("rustup", &["default", toolchain])
+}
 
-    for file in files {
-        if !file.exists() {
-            fs::write(&file, "").with_context(|| format!("failed to create {}", file.display()))?;
+/// Adds the missing rustup `kind`s, `component` or `target`, to the default toolchain
+/// and each extra one, then checks with `rustup <kind> list --installed` that they are
+/// there.
+fn add_missing(kind: &str, names: &[String], extra_toolchains: &[String]) -> anyhow::Result<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    let toolchains = std::iter::once(None).chain(extra_toolchains.iter().map(Some));
+    for toolchain in toolchains {
+        let label = toolchain.map_or("the default toolchain", String::as_str);
+        let missing = not_installed(kind, names, toolchain);
+        if missing.is_empty() {
+            continue;
         }
-
-        let content = fs::read_to_string(&file)
-            .with_context(|| format!("failed to read {}", file.display()))?;
-        if content.lines().any(|existing| existing.trim() == line) {
+        let mut args = vec![kind, "add"];
+        if let Some(toolchain) = toolchain {
+            args.extend(["--toolchain", toolchain.as_str()]);
+        }
+        args.extend(missing.iter().map(String::as_str));
+        run_step("rustup", &args)?;
+        if exec::is_dry_run() {
             continue;
         }
+        let still_missing = not_installed(kind, names, toolchain);
+        if !still_missing.is_empty() {
+            bail!(
+                "rustup did not install {} for {label}",
+                still_missing.join(", ")
+            );
+        }
+        say!("Added {} to {label}", missing.join(", "));
+    }
+    Ok(())
+}
+
+/// The `names` of rustup `kind`s that `toolchain`, or the default one, does not have.
+fn not_installed(kind: &str, names: &[String], toolchain: Option<&String>) -> Vec<String> {
+    let mut args = vec![kind, "list", "--installed"];
+    if let Some(toolchain) = toolchain {
+        args.extend(["--toolchain", toolchain.as_str()]);
+    }
+    let installed = rustup_lines(&args);
+    names
+        .iter()
+        .filter(|name| !listed(&installed, name))
+        .cloned()
+        .collect()
+}
+
+/// Whether rustup's listing `lines` has `name`, on its own or followed by the host
+/// triple as in `clippy-x86_64-unknown-linux-gnu`. A dated toolchain such as
+/// `nightly-2024-05-01` is not `nightly`.
+fn listed(lines: &[String], name: &str) -> bool {
+    lines.iter().any(|line| {
+        let entry = line.split_whitespace().next().unwrap_or_default();
+        entry == name
+            || entry
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('-'))
+                .is_some_and(|rest| !rest.starts_with(|c: char| c.is_ascii_digit()))
+    })
+}
+
+/// The lines `rustup args...` prints, or none when rustup is missing or fails.
+fn rustup_lines(args: &[&str]) -> Vec<String> {
+    let Some(rustup) = resolve_program("rustup") else {
+        return Vec::new();
+    };
+    match exec::query(Command::new(rustup).args(args)) {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The toolchains and the rustup release `rustup check` finds newer versions of.
+/// rustup asks static.rust-lang.org, so offline this finds none.
+fn available_updates() -> anyhow::Result<Vec<super::Update>> {
+    let Some(rustup) = resolve_program("rustup") else {
+        return Ok(Vec::new());
+    };
+    if offline::is_enabled() {
+        return Ok(Vec::new());
+    }
+    let output = exec::query(network::apply(&mut Command::new(rustup)).arg("check"))
+        .context("failed to run `rustup check`")?;
+    Ok(parse_rustup_check(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Reads the `name - Update available : old (hash date) -> new (hash date)` lines of
+/// `rustup check`.
+fn parse_rustup_check(check: &str) -> Vec<super::Update> {
+    check
+        .lines()
+        .filter_map(|line| {
+            let (name, rest) = line.split_once(" - ")?;
+            let (installed, available) = rest
+                .strip_prefix("Update available")?
+                .trim_start_matches([' ', ':'])
+                .split_once(" -> ")?;
+            let version = |text: &str| text.split_whitespace().next().map(str::to_string);
+            Some(super::Update {
+                name: name.trim().to_string(),
+                installed: version(installed)?,
+                available: version(available)?,
+            })
+        })
+        .collect()
+}
 
-        let mut handle = OpenOptions::new()
-            .append(true)
-            .open(&file)
-            .with_context(|| format!("failed to open {} for append", file.display()))?;
-        writeln!(handle)?;
-        writeln!(handle, "{line}")?;
+/// Stops shell startup files from sourcing the cargo env and, with `self_uninstall`,
+/// removes rustup together with every toolchain, `~/.cargo` and `~/.rustup`.
+pub fn uninstall(self_uninstall: bool) -> anyhow::Result<()> {
+    let home = home_dir()?;
+    let files = cargo_env_files(&home, true)
+        .into_iter()
+        .map(|(file, _)| file)
+        .collect::<Vec<_>>();
+    let mut changed =
+        shell_init::remove_block(&shell_init::files(&home), &[LEGACY_CARGO_ENV_LINE])?;
+    changed.extend(shell_init::remove_managed_block(&files, CARGO_ENV_BLOCK)?);
+    changed.sort();
+    changed.dedup();
+    for file in changed {
+        say!("Removed cargo env from {}", file.display());
     }
 
+    if !self_uninstall {
+        say!("Kept rustup and installed toolchains; pass --self-uninstall to remove them.");
+        return Ok(());
+    }
+    if !command_available("rustup") {
+        say!("rustup is not installed.");
+        return Ok(());
+    }
+    run_step("rustup", &["self", "uninstall", "-y"])?;
+    say!("Rust uninstalled.");
     Ok(())
 }
 
+/// Downloads `rustup-init` for this machine into a private scratch directory and checks
+/// it against the pinned or configured SHA-256 before it may run. A dry run only
+/// announces the download and returns a path under `$TMPDIR`, creating nothing.
+fn download_rustup_init(options: &Options) -> anyhow::Result<PathBuf> {
+    let triple = host_triple()?;
+    let expected = expected_sha256(
+        &options.rustup_version,
+        triple,
+        options.rustup_init_sha256.as_deref(),
+    )?;
+    let url = format!(
+        "https://static.rust-lang.org/rustup/archive/{}/{triple}/rustup-init",
+        options.rustup_version
+    );
+    if exec::is_dry_run() {
+        let path = env::temp_dir().join("rustup-init");
+        download::save(&url, &path)?;
+        return Ok(path);
+    }
+    let dir = scratch::create("rustup")?;
+    let path = dir.join("rustup-init");
+    let downloaded = download::save(&url, &path).and_then(|()| {
+        verify_sha256(&path, &expected)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("failed to make {} executable", path.display()))
+    });
+    if let Err(err) = downloaded {
+        let _ = fs::remove_dir_all(&dir);
+        return Err(err);
+    }
+    Ok(path)
+}
+
+/// The SHA-256 `rustup-init` `version` for `triple` must have: `configured`, or the
+/// one pinned for [`DEFAULT_RUSTUP_VERSION`].
+fn expected_sha256(
+    version: &str,
+    triple: &str,
+    configured: Option<&str>,
+) -> anyhow::Result<String> {
+    if let Some(configured) = configured {
+        return Ok(configured.to_ascii_lowercase());
+    }
+    RUSTUP_INIT_SHA256
+        .iter()
+        .find(|(pinned, _)| version == DEFAULT_RUSTUP_VERSION && *pinned == triple)
+        .map(|(_, sha256)| sha256.to_string())
+        .with_context(|| {
+            format!(
+                "no SHA-256 is pinned for rustup-init {version} on {triple}; set `rust.rustup_init_sha256`"
+            )
+        })
+}
+
+fn verify_sha256(path: &Path, expected: &str) -> anyhow::Result<()> {
+    let actual = audit::hash_file(path).unwrap_or_default();
+    if actual != expected {
+        bail!(
+            "refusing to run {}: its SHA-256 is {actual}, expected {expected}",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// The rustup host triple of this machine.
+fn host_triple() -> anyhow::Result<&'static str> {
+    Ok(match env::consts::ARCH {
+        "x86_64" => "x86_64-unknown-linux-gnu",
+        "aarch64" => "aarch64-unknown-linux-gnu",
+        "arm" => "armv7-unknown-linux-gnueabihf",
+        "x86" => "i686-unknown-linux-gnu",
+        "powerpc64" => "powerpc64le-unknown-linux-gnu",
+        "riscv64" => "riscv64gc-unknown-linux-gnu",
+        "s390x" => "s390x-unknown-linux-gnu",
+        arch => bail!("rustup-init is not available for {arch}"),
+    })
+}
+
+/// The `rustup-init` to run: the one passed with `--rustup-init`, or under
+/// `--offline` one found on PATH. `None` means download the installer.
+fn local_rustup_init(path: Option<PathBuf>) -> anyhow::Result<Option<PathBuf>> {
+    if let Some(path) = path {
+        if !path.is_file() {
+            bail!("rustup-init was not found at {}", path.display());
+        }
+        return Ok(Some(path));
+    }
+    if !offline::is_enabled() {
+        return Ok(None);
+    }
+    match resolve_program("rustup-init") {
+        Some(path) => Ok(Some(path)),
+        None => bail!(
+            "offline mode needs a local rustup-init; pass --rustup-init <PATH> or put it on PATH"
+        ),
+    }
+}
+
+fn ensure_shell_init_sources_cargo_env() -> anyhow::Result<()> {
+    let home = home_dir()?;
+    shell_init::remove_block(&shell_init::files(&home), &[LEGACY_CARGO_ENV_LINE])?;
+    for (file, lines) in cargo_env_files(&home, false) {
+        shell_init::ensure_managed_block(&file, CARGO_ENV_BLOCK, lines)?;
+    }
+    Ok(())
+}
+
+/// The startup files that set up the cargo env, with the lines that do it:
+/// `.bashrc` and `.profile`, then `.zshenv` and fish's `conf.d/cargo.fish` for users
+/// of zsh and fish, or for everyone with `all`.
+fn cargo_env_files(home: &Path, all: bool) -> Vec<(PathBuf, &'static [&'static str])> {
+    let mut files = shell_init::files(home)
+        .into_iter()
+        .map(|file| (file, POSIX_CARGO_ENV))
+        .collect::<Vec<_>>();
+    if all || shell_init::uses(home, "zsh") {
+        files.push((home.join(".zshenv"), POSIX_CARGO_ENV));
+    }
+    if all || shell_init::uses(home, "fish") {
+        files.push((home.join(".config/fish/conf.d/cargo.fish"), FISH_CARGO_ENV));
+    }
+    files
+}
+
 fn command_available(program: &str) -> bool {
     resolve_program(program).is_some()
 }
 
+fn rustc_version() -> Option<String> {
+    let rustc = resolve_program("rustc")?;
+    super::program_version(rustc.to_str()?, &["--version"])
+}
+
 fn run_command(program: &str, args: &[&str]) -> anyhow::Result<()> {
     let Some(program_path) = resolve_program(program) else {
         bail!("`{program}` executable was not found in PATH or ~/.cargo/bin");
     };
 
-    let status = Command::new(&program_path)
-        .args(args)
-        .status()
+    let status = exec::probe(Command::new(&program_path).args(args))
         .with_context(|| format!("failed to start `{}`", program_path.display()))?;
 
     if !status.success() {
         bail!(
-            "command `{}` failed with status {}",
-            format!("{} {}", program_path.display(), args.join(" ")),
+            "command `{} {}` failed with status {}",
+            program_path.display(),
+            args.join(" "),
             status
         );
     }
@@ -94,28 +416,32 @@ fn run_command(program: &str, args: &[&str]) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn run_shell_command(cmd: &str) -> anyhow::Result<()> {
-    let status = Command::new("sh")
-        .args(["-c", cmd])
-        .status()
-        .context("failed to start shell command")?;
+fn run_step(program: &str, args: &[&str]) -> anyhow::Result<()> {
+    let program_path = resolve_program(program).unwrap_or_else(|| PathBuf::from(program));
+    let step = format!("{program} {}", args.join(" "));
+    let status = exec::status_step(
+        network::apply(&mut Command::new(&program_path)).args(args),
+        &step,
+    )
+    .with_context(|| format!("failed to start `{}`", program_path.display()))?;
     if !status.success() {
-        bail!("shell command failed with status {}", status);
+        bail!(
+            "command `{} {}` failed with status {}",
+            program_path.display(),
+            args.join(" "),
+            status
+        );
     }
     Ok(())
 }
 
-fn resolve_program(program: &str) -> Option<PathBuf> {
-    if Command::new(program)
-        .arg("--version")
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false)
-    {
-        return Some(PathBuf::from(program));
+/// `program` on PATH or, before the cargo env is sourced, in `~/.cargo/bin`.
+pub fn resolve_program(program: &str) -> Option<PathBuf> {
+    if let Some(path) = system::find_program(program) {
+        return Some(path);
     }
 
-    let cargo_bin = home_dir().ok()?.join(".cargo").join("bin").join(program);
+    let cargo_bin = cargo_bin().ok()?.join(program);
     if cargo_bin.exists() {
         return Some(cargo_bin);
     }
@@ -123,8 +449,448 @@ fn resolve_program(program: &str) -> Option<PathBuf> {
     None
 }
 
+/// Where rustup puts cargo and its proxies: `$CARGO_HOME/bin`, or `~/.cargo/bin`.
+fn cargo_bin() -> anyhow::Result<PathBuf> {
+    let cargo_home = match env::var_os("CARGO_HOME").filter(|home| !home.is_empty()) {
+        Some(cargo_home) => PathBuf::from(cargo_home),
+        None => home_dir()?.join(".cargo"),
+    };
+    Ok(cargo_home.join("bin"))
+}
+
 fn home_dir() -> anyhow::Result<PathBuf> {
     env::var_os("HOME")
         .map(PathBuf::from)
         .context("HOME environment variable is not set")
 }
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RustStatus {
+    /// `rustup --version`, when rustup is installed.
+    pub rustup: Option<String>,
+    /// `rustc --version` of the default toolchain.
+    pub rustc: Option<String>,
+    pub default_toolchain: Option<String>,
+    pub pinned_toolchain: String,
+    /// The pinned toolchain and each of `rust.extra_toolchains`.
+    pub toolchains: Vec<ToolchainStatus>,
+    /// Where `cargo` and `rustc` resolve on PATH, if they do.
+    pub cargo_path: Option<PathBuf>,
+    pub rustc_path: Option<PathBuf>,
+    /// `$CARGO_HOME/bin` or `~/.cargo/bin`, where rustup puts its proxies.
+    pub cargo_bin: PathBuf,
+    pub shell_init: Vec<ShellInitStatus>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolchainStatus {
+    pub name: String,
+    pub installed: bool,
+    /// Configured components and targets this toolchain lacks.
+    pub missing_components: Vec<String>,
+    pub missing_targets: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShellInitStatus {
+    pub file: PathBuf,
+    pub sets_up_cargo_env: bool,
+}
+
+impl RustStatus {
+    /// Ways the Rust setup differs from `[rust]`.
+    pub fn drift(&self) -> Vec<String> {
+        let mut drift = self
+            .shell_init
+            .iter()
+            .filter(|file| !file.sets_up_cargo_env)
+            .map(|file| format!("{} does not set up the cargo env", file.file.display()))
+            .collect::<Vec<_>>();
+        if let Some(default) = &self.default_toolchain
+            && !listed(std::slice::from_ref(default), &self.pinned_toolchain)
+        {
+            drift.push(format!(
+                "the default toolchain is {default} but config pins {}",
+                self.pinned_toolchain
+            ));
+        }
+        for toolchain in &self.toolchains {
+            if !toolchain.installed {
+                drift.push(format!("toolchain {} is not installed", toolchain.name));
+                continue;
+            }
+            if !toolchain.missing_components.is_empty() {
+                drift.push(format!(
+                    "toolchain {} lacks {}",
+                    toolchain.name,
+                    toolchain.missing_components.join(", ")
+                ));
+            }
+            if !toolchain.missing_targets.is_empty() {
+                drift.push(format!(
+                    "toolchain {} cannot build for {}",
+                    toolchain.name,
+                    toolchain.missing_targets.join(", ")
+                ));
+            }
+        }
+        for (program, path) in [("cargo", &self.cargo_path), ("rustc", &self.rustc_path)] {
+            if let Some(path) = path
+                && self.rustup.is_some()
+                && !path.starts_with(&self.cargo_bin)
+            {
+                drift.push(format!(
+                    "`{program}` on PATH is {}, not rustup's",
+                    path.display()
+                ));
+            }
+        }
+        drift
+    }
+}
+
+pub fn collect_status(config: &RustConfig) -> anyhow::Result<RustStatus> {
+    let home = home_dir()?;
+    let installed = rustup_lines(&["toolchain", "list"]);
+    let toolchains = std::iter::once(&config.toolchain)
+        .chain(&config.extra_toolchains)
+        .map(|name| {
+            let installed = listed(&installed, name);
+            let toolchain = Some(name);
+            ToolchainStatus {
+                name: name.clone(),
+                installed,
+                missing_components: if installed {
+                    not_installed("component", &config.components, toolchain)
+                } else {
+                    Vec::new()
+                },
+                missing_targets: if installed {
+                    not_installed("target", &config.targets, toolchain)
+                } else {
+                    Vec::new()
+                },
+            }
+        })
+        .collect();
+    let shell_init = cargo_env_files(&home, false)
+        .into_iter()
+        .map(|(file, lines)| ShellInitStatus {
+            sets_up_cargo_env: shell_init::has_managed_block(&file, CARGO_ENV_BLOCK, lines),
+            file,
+        })
+        .collect();
+    Ok(RustStatus {
+        rustup: resolve_program("rustup")
+            .and_then(|rustup| super::program_version(rustup.to_str()?, &["--version"])),
+        rustc: rustc_version(),
+        default_toolchain: rustup_lines(&["default"])
+            .first()
+            .and_then(|line| line.split_whitespace().next())
+            .map(str::to_string),
+        pinned_toolchain: config.toolchain.clone(),
+        toolchains,
+        cargo_path: system::find_program("cargo"),
+        rustc_path: system::find_program("rustc"),
+        cargo_bin: cargo_bin()?,
+        shell_init,
+    })
+}
+
+fn print_status_report(status: &RustStatus) {
+    let path = |path: &Option<PathBuf>| {
+        path.as_ref()
+            .map_or("not on PATH".to_string(), |path| path.display().to_string())
+    };
+    say!("Rust status:");
+    say!(
+        "- rustup: {}",
+        status.rustup.as_deref().unwrap_or("not installed")
+    );
+    say!(
+        "- rustc: {}",
+        status.rustc.as_deref().unwrap_or("not installed")
+    );
+    say!(
+        "- default toolchain: {} (config pins {})",
+        status.default_toolchain.as_deref().unwrap_or("none"),
+        status.pinned_toolchain
+    );
+    say!("- cargo on PATH: {}", path(&status.cargo_path));
+    say!("- rustc on PATH: {}", path(&status.rustc_path));
+    say!("- toolchains (installed / missing components / missing targets):");
+    for toolchain in &status.toolchains {
+        let missing = |names: &[String]| {
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        };
+        say!(
+            "  - {}: {} / {} / {}",
+            toolchain.name,
+            toolchain.installed,
+            missing(&toolchain.missing_components),
+            missing(&toolchain.missing_targets)
+        );
+    }
+    say!("- shell startup files setting up the cargo env:");
+    for file in &status.shell_init {
+        say!("  - {}: {}", file.file.display(), file.sets_up_cargo_env);
+    }
+}
+
+pub struct RustTarget;
+
+impl Target for RustTarget {
+    fn name(&self) -> &'static str {
+        "rust"
+    }
+
+    fn description(&self) -> &'static str {
+        "Rust toolchain via rustup"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall | Action::Status)
+    }
+
+    fn args(&self, action: Action, command: clap::Command) -> clap::Command {
+        match action {
+            Action::Install => command
+                .arg(
+                    clap::Arg::new("reinstall")
+                        .long("reinstall")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    clap::Arg::new("rustup-init")
+                        .long("rustup-init")
+                        .value_name("PATH")
+                        .help("Run this rustup-init instead of downloading the installer"),
+                )
+                .arg(
+                    clap::Arg::new("toolchain")
+                        .long("toolchain")
+                        .value_name("TOOLCHAIN")
+                        .help("Default toolchain, such as 1.79.0 [default: `rust.toolchain` from config]"),
+                )
+                .arg(
+                    clap::Arg::new("component")
+                        .long("component")
+                        .value_name("NAME")
+                        .action(clap::ArgAction::Append)
+                        .help("rustup component to add as well as `rust.components` (repeatable)"),
+                )
+                .arg(
+                    clap::Arg::new("extra-toolchain")
+                        .long("extra-toolchain")
+                        .value_name("TOOLCHAIN")
+                        .action(clap::ArgAction::Append)
+                        .help(
+                            "Toolchain to install as well as `rust.extra_toolchains` (repeatable)",
+                        ),
+                )
+                .arg(
+                    clap::Arg::new("target")
+                        .long("target")
+                        .value_name("TRIPLE")
+                        .action(clap::ArgAction::Append)
+                        .help("Target to add as well as `rust.targets` (repeatable)"),
+                ),
+            Action::Uninstall => command.arg(
+                clap::Arg::new("self-uninstall")
+                    .long("self-uninstall")
+                    .action(clap::ArgAction::SetTrue)
+                    .help("Also run `rustup self uninstall`, removing ~/.cargo and ~/.rustup"),
+            ),
+            _ => command,
+        }
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let config = &ctx.config()?.rust;
+        let merge = |configured: &[String], id| {
+            let mut names = configured.to_vec();
+            for name in ctx.values(id) {
+                if !names.iter().any(|existing| existing == name) {
+                    names.push(name.to_string());
+                }
+            }
+            names
+        };
+        run(Options {
+            reinstall: ctx.flag("reinstall"),
+            rustup_init: ctx.value("rustup-init").map(PathBuf::from),
+            rustup_version: config.rustup_version.clone(),
+            rustup_init_sha256: Some(config.rustup_init_sha256.clone())
+                .filter(|sha256| !sha256.is_empty()),
+            toolchain: ctx
+                .value("toolchain")
+                .map_or_else(|| config.toolchain.clone(), str::to_string),
+            components: merge(&config.components, "component"),
+            extra_toolchains: merge(&config.extra_toolchains, "extra-toolchain"),
+            targets: merge(&config.targets, "target"),
+        })
+    }
+
+    fn uninstall(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall(ctx.flag("self-uninstall"))
+    }
+
+    fn status(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let status = collect_status(&ctx.config()?.rust)?;
+        if !output::is_json() {
+            print_status_report(&status);
+        }
+        ctx.report(&status)
+    }
+
+    fn version(&self) -> Option<String> {
+        rustc_version()
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let status = collect_status(&ctx.config()?.rust)?;
+        let summary = super::TargetStatus::new(self.name(), status.rustc.clone());
+        if !summary.installed {
+            return Ok(summary);
+        }
+        Ok(summary.with_drift(status.drift()))
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        available_updates()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn finds_components_toolchains_and_targets_in_rustup_listings() {
+        let lines = [
+            "cargo-x86_64-unknown-linux-gnu",
+            "clippy-x86_64-unknown-linux-gnu",
+            "rust-src",
+            "stable-x86_64-unknown-linux-gnu (default)",
+            "nightly-2024-05-01-x86_64-unknown-linux-gnu",
+            "1.79.0-x86_64-unknown-linux-gnu",
+        ]
+        .map(str::to_string);
+        assert!(listed(&lines, "clippy"));
+        assert!(listed(&lines, "rust-src"));
+        assert!(!listed(&lines, "rust-analyzer"));
+        assert!(listed(&lines, "stable"));
+        assert!(listed(&lines, "1.79.0"));
+        assert!(!listed(&lines, "nightly"));
+        assert!(!listed(&lines, "1.79"));
+
+        let targets = ["thumbv7em-none-eabihf", "x86_64-unknown-linux-gnu"].map(str::to_string);
+        assert!(listed(&targets, "x86_64-unknown-linux-gnu"));
+        assert!(!listed(&targets, "thumbv7em-none-eabi"));
+        assert!(!listed(&targets, "x86_64-unknown-linux-musl"));
+    }
+
+    #[test]
+    fn reads_updates_from_rustup_check() {
+        let check = "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 (051478957 2024-07-21) -> 1.81.0 (eeb90cda1 2024-09-04)\n\
+                     nightly-x86_64-unknown-linux-gnu - Up to date : 1.83.0-nightly (1bc403daa 2024-10-11)\n\
+                     rustup - Update available : 1.27.0 -> 1.27.1\n";
+        assert_eq!(
+            parse_rustup_check(check),
+            [
+                super::super::Update {
+                    name: "stable-x86_64-unknown-linux-gnu".to_string(),
+                    installed: "1.80.0".to_string(),
+                    available: "1.81.0".to_string(),
+                },
+                super::super::Update {
+                    name: "rustup".to_string(),
+                    installed: "1.27.0".to_string(),
+                    available: "1.27.1".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_drift_from_the_rust_config() {
+        let status = RustStatus {
+            rustup: Some("rustup 1.29.0".to_string()),
+            rustc: Some("rustc 1.80.0".to_string()),
+            default_toolchain: Some("1.80.0-x86_64-unknown-linux-gnu".to_string()),
+            pinned_toolchain: "1.79.0".to_string(),
+            toolchains: vec![
+                ToolchainStatus {
+                    name: "1.79.0".to_string(),
+                    installed: true,
+                    missing_components: vec!["rust-src".to_string()],
+                    missing_targets: vec!["wasm32-unknown-unknown".to_string()],
+                },
+                ToolchainStatus {
+                    name: "nightly".to_string(),
+                    installed: false,
+                    missing_components: Vec::new(),
+                    missing_targets: Vec::new(),
+                },
+            ],
+            cargo_path: Some(PathBuf::from("/usr/bin/cargo")),
+            rustc_path: Some(PathBuf::from("/home/me/.cargo/bin/rustc")),
+            cargo_bin: PathBuf::from("/home/me/.cargo/bin"),
+            shell_init: vec![ShellInitStatus {
+                file: PathBuf::from("/home/me/.zshenv"),
+                sets_up_cargo_env: false,
+            }],
+        };
+        assert_eq!(
+            status.drift(),
+            [
+                "/home/me/.zshenv does not set up the cargo env",
+                "the default toolchain is 1.80.0-x86_64-unknown-linux-gnu but config pins 1.79.0",
+                "toolchain 1.79.0 lacks rust-src",
+                "toolchain 1.79.0 cannot build for wasm32-unknown-unknown",
+                "toolchain nightly is not installed",
+                "`cargo` on PATH is /usr/bin/cargo, not rustup's",
+            ]
+        );
+    }
+
+    #[test]
+    fn pins_the_rustup_init_checksum() {
+        let pinned = expected_sha256(DEFAULT_RUSTUP_VERSION, "aarch64-unknown-linux-gnu", None);
+        assert!(pinned.unwrap().starts_with("9732d6c5"));
+        assert_eq!(
+            expected_sha256("1.27.1", "x86_64-unknown-linux-gnu", Some("ABCD")).unwrap(),
+            "abcd"
+        );
+        let err = expected_sha256("1.27.1", "x86_64-unknown-linux-gnu", None).unwrap_err();
+        assert!(err.to_string().contains("rust.rustup_init_sha256"), "{err}");
+    }
+
+    #[test]
+    fn dry_run_announces_rustup_init_without_downloading_it() {
+        let options = Options {
+            reinstall: false,
+            rustup_init: None,
+            rustup_version: DEFAULT_RUSTUP_VERSION.to_string(),
+            rustup_init_sha256: Some("0".repeat(64)),
+            toolchain: "stable".to_string(),
+            components: Vec::new(),
+            extra_toolchains: Vec::new(),
+            targets: Vec::new(),
+        };
+        let recorder = Arc::new(Recorder::new());
+        let path = runner::with(recorder.clone(), || {
+            exec::dry_run(|| download_rustup_init(&options))
+        })
+        .unwrap();
+        assert!(recorder.calls().is_empty());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,291 @@
+//! Lines DebKit adds to the user's shell startup files, such as
+//! `export PATH="$HOME/.local/bin:$PATH"`, and their removal on uninstall.
+//!
+//! Newer additions go in a managed block between `# >>> debkit <name> >>>` and
+//! `# <<< debkit <name> <<<` markers, which DebKit rewrites in place when its
+//! contents change and removes whole.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::{backup, exec};
+
+/// The startup files DebKit edits in `home`.
+pub fn files(home: &Path) -> [PathBuf; 2] {
+    [home.join(".bashrc"), home.join(".profile")]
+}
+
+/// Whether the user runs `shell` (`zsh` or `fish`): it is their login shell, or they
+/// have its startup files in `home`.
+pub fn uses(home: &Path, shell: &str) -> bool {
+    let login = env::var_os("SHELL")
+        .is_some_and(|login| Path::new(&login).file_name() == Some(shell.as_ref()));
+    login
+        || match shell {
+            "zsh" => home.join(".zshrc").exists() || home.join(".zshenv").exists(),
+            "fish" => home.join(".config/fish").is_dir(),
+            _ => false,
+        }
+}
+
+/// Writes `lines` as the `name` block of `file`, replacing the block already there or
+/// appending it, and creates the file and its directory when missing.
+pub fn ensure_managed_block(file: &Path, name: &str, lines: &[&str]) -> anyhow::Result<()> {
+    let content = if file.exists() {
+        fs::read_to_string(file).with_context(|| format!("failed to read {}", file.display()))?
+    } else {
+        String::new()
+    };
+    let updated = with_managed_block(&content, name, lines);
+    if updated == content {
+        return Ok(());
+    }
+    if let Some(dir) = file.parent().filter(|dir| !dir.exists()) {
+        exec::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    backup::save(file)?;
+    exec::write(file, updated).with_context(|| format!("failed to write {}", file.display()))
+}
+
+/// Whether `file` has the `name` block with exactly `lines`.
+pub fn has_managed_block(file: &Path, name: &str, lines: &[&str]) -> bool {
+    let content = fs::read_to_string(file).unwrap_or_default();
+    content.contains(&managed_block(name, lines))
+}
+
+/// Removes the `name` block from each of `files`, together with the blank separator
+/// line DebKit writes before it. Returns the files that changed.
+pub fn remove_managed_block(files: &[PathBuf], name: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let mut changed = Vec::new();
+    for file in files {
+        if !file.exists() {
+            continue;
+        }
+        let content = fs::read_to_string(file)
+            .with_context(|| format!("failed to read {}", file.display()))?;
+        let Some(updated) = without_managed_block(&content, name) else {
+            continue;
+        };
+        backup::save(file)?;
+        exec::write(file, updated)
+            .with_context(|| format!("failed to write {}", file.display()))?;
+        changed.push(file.clone());
+    }
+    Ok(changed)
+}
+
+fn markers(name: &str) -> (String, String) {
+    (
+        format!("# >>> debkit {name} >>>"),
+        format!("# <<< debkit {name} <<<"),
+    )
+}
+
+fn managed_block(name: &str, lines: &[&str]) -> String {
+    let (begin, end) = markers(name);
+    let mut block = format!("{begin}\n");
+    for line in lines {
+        block.push_str(line);
+        block.push('\n');
+    }
+    block.push_str(&end);
+    block.push('\n');
+    block
+}
+
+/// The line range of the `name` block in `lines`, markers included.
+fn managed_range(lines: &[&str], name: &str) -> Option<(usize, usize)> {
+    let (begin, end) = markers(name);
+    let start = lines.iter().position(|line| line.trim() == begin)?;
+    let stop = start + lines[start..].iter().position(|line| line.trim() == end)?;
+    Some((start, stop))
+}
+
+/// `content` with `lines` as its `name` block, for files DebKit writes some other
+/// way, such as root-owned ones.
+pub fn with_managed_block(content: &str, name: &str, lines: &[&str]) -> String {
+    let block = managed_block(name, lines);
+    let existing = content.lines().collect::<Vec<_>>();
+    let Some((start, stop)) = managed_range(&existing, name) else {
+        let separator = if content.is_empty() { "" } else { "\n" };
+        let newline = if content.is_empty() || content.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+        return format!("{content}{newline}{separator}{block}");
+    };
+    let mut updated = String::new();
+    for line in &existing[..start] {
+        updated.push_str(line);
+        updated.push('\n');
+    }
+    updated.push_str(&block);
+    for line in &existing[stop + 1..] {
+        updated.push_str(line);
+        updated.push('\n');
+    }
+    updated
+}
+
+/// `content` without its `name` block, or `None` when it has none.
+pub fn without_managed_block(content: &str, name: &str) -> Option<String> {
+    let mut lines = content.lines().collect::<Vec<_>>();
+    let (start, stop) = managed_range(&lines, name)?;
+    let start = if start > 0 && lines[start - 1].trim().is_empty() {
+        start - 1
+    } else {
+        start
+    };
+    lines.drain(start..=stop);
+    let mut updated = lines.join("\n");
+    if !updated.is_empty() {
+        updated.push('\n');
+    }
+    Some(updated)
+}
+
+/// Appends `line` to each file that does not already contain it.
+pub fn ensure_line(files: &[PathBuf], line: &str) -> anyhow::Result<()> {
+    for file in files {
+        let content = if file.exists() {
+            fs::read_to_string(file)
+                .with_context(|| format!("failed to read {}", file.display()))?
+        } else {
+            String::new()
+        };
+        if content.lines().any(|existing| existing.trim() == line) {
+            continue;
+        }
+
+        backup::save(file)?;
+        exec::append(file, &format!("\n{line}\n"))
+            .with_context(|| format!("failed to append to {}", file.display()))?;
+    }
+
+    Ok(())
+}
+
+/// The files among `files` that do not contain `line`.
+pub fn missing_line(files: &[PathBuf], line: &str) -> Vec<PathBuf> {
+    files
+        .iter()
+        .filter(|file| {
+            !fs::read_to_string(file)
+                .unwrap_or_default()
+                .lines()
+                .any(|existing| existing.trim() == line)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Removes `block` (compared line by line, ignoring surrounding whitespace) from each
+/// file, together with the blank separator line DebKit writes before it. Returns the
+/// files that changed.
+pub fn remove_block(files: &[PathBuf], block: &[&str]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut changed = Vec::new();
+    for file in files {
+        if !file.exists() {
+            continue;
+        }
+        let content = fs::read_to_string(file)
+            .with_context(|| format!("failed to read {}", file.display()))?;
+        let Some(updated) = without_block(&content, block) else {
+            continue;
+        };
+        backup::save(file)?;
+        exec::write(file, updated)
+            .with_context(|| format!("failed to write {}", file.display()))?;
+        changed.push(file.clone());
+    }
+    Ok(changed)
+}
+
+fn without_block(content: &str, block: &[&str]) -> Option<String> {
+    if block.is_empty() {
+        return None;
+    }
+    let mut lines = content.lines().collect::<Vec<_>>();
+    let mut removed = false;
+    let mut index = 0;
+    while index + block.len() <= lines.len() {
+        let matches = lines[index..index + block.len()]
+            .iter()
+            .zip(block)
+            .all(|(line, expected)| line.trim() == expected.trim());
+        if !matches {
+            index += 1;
+            continue;
+        }
+        let start = if index > 0 && lines[index - 1].trim().is_empty() {
+            index - 1
+        } else {
+            index
+        };
+        lines.drain(start..index + block.len());
+        index = start;
+        removed = true;
+    }
+    if !removed {
+        return None;
+    }
+
+    let mut updated = lines.join("\n");
+    if !updated.is_empty() {
+        updated.push('\n');
+    }
+    Some(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_appended_line_and_separator() {
+        let content = "alias ll='ls -l'\n\nsource \"$HOME/.cargo/env\"\nexport EDITOR=vi\n";
+        assert_eq!(
+            without_block(content, &[r#"source "$HOME/.cargo/env""#]).as_deref(),
+            Some("alias ll='ls -l'\nexport EDITOR=vi\n")
+        );
+        assert_eq!(without_block("export EDITOR=vi\n", &["missing"]), None);
+    }
+
+    #[test]
+    fn removes_every_copy_of_a_multi_line_block() {
+        let block = ["# Load x", "if [ -f x ]; then", "  . x", "fi"];
+        let content = "a\n\n# Load x\nif [ -f x ]; then\n  . x\nfi\nb\n# Load x\nif [ -f x ]; then\n  . x\nfi\n";
+        assert_eq!(without_block(content, &block).as_deref(), Some("a\nb\n"));
+    }
+
+    #[test]
+    fn rewrites_and_removes_a_managed_block() {
+        let block =
+            "# >>> debkit cargo-env >>>\n. \"$HOME/.cargo/env\"\n# <<< debkit cargo-env <<<\n";
+        let added = with_managed_block(
+            "export EDITOR=vi",
+            "cargo-env",
+            &[r#". "$HOME/.cargo/env""#],
+        );
+        assert_eq!(added, format!("export EDITOR=vi\n\n{block}"));
+        assert_eq!(
+            with_managed_block(&added, "cargo-env", &[r#". "$HOME/.cargo/env""#]),
+            added
+        );
+
+        let stale = format!("{added}alias ll='ls -l'\n").replace(".cargo/env", ".cargo/old");
+        assert_eq!(
+            with_managed_block(&stale, "cargo-env", &[r#". "$HOME/.cargo/env""#]),
+            format!("export EDITOR=vi\n\n{block}alias ll='ls -l'\n")
+        );
+        assert_eq!(
+            without_managed_block(&stale, "cargo-env").as_deref(),
+            Some("export EDITOR=vi\nalias ll='ls -l'\n")
+        );
+        assert_eq!(without_managed_block(&stale, "local-bin"), None);
+    }
+}
@@ -0,0 +1,304 @@
+//! `install ssh`: the target user's SSH client setup, and optionally an SSH server.
+//!
+//! An ed25519 key is generated as `~/.ssh/id_ed25519` when the user has none, and
+//! the `[ssh.hosts]` entries are written as `Host` blocks in a managed block of
+//! `~/.ssh/config`. `~/.ssh` is made private to the user (mode 700), as are the
+//! config, `authorized_keys` and the private key (mode 600). With `server = true`
+//! openssh-server is installed and `ssh.service` enabled and started.
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+
+use super::user::{
+    UserContext, command_as, ensure_owned_private_dir, ensure_owned_private_file,
+    target_user_context,
+};
+use super::{Action, Target, shell_init};
+use crate::config::{SshConfig, SshHost};
+use crate::output::say;
+use crate::{apt, exec, privileged, system};
+
+const HOSTS_BLOCK: &str = "ssh-hosts";
+const KEY_NAME: &str = "id_ed25519";
+const SSH_SERVICE: &str = "ssh.service";
+
+#[derive(Debug, Serialize)]
+pub struct SshReport {
+    pub key: PathBuf,
+    pub key_created: bool,
+    pub public_key: Option<String>,
+    pub hosts: Vec<String>,
+    pub server: bool,
+}
+
+pub fn run(config: &SshConfig) -> anyhow::Result<SshReport> {
+    let user = target_user_context()?;
+    apt::install_missing(&["openssh-client"])?;
+    let dir = ssh_dir(&user);
+    exec::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    ensure_owned_private_dir(&dir, &user)?;
+
+    let key = dir.join(KEY_NAME);
+    let key_created = config.generate_key && !key.exists();
+    if key_created {
+        generate_key(&user, &key, &config.key_comment)?;
+    }
+
+    let config_file = dir.join("config");
+    if config.hosts.is_empty() {
+        shell_init::remove_managed_block(std::slice::from_ref(&config_file), HOSTS_BLOCK)?;
+    } else {
+        let lines = host_lines(config);
+        let lines = lines.iter().map(String::as_str).collect::<Vec<_>>();
+        shell_init::ensure_managed_block(&config_file, HOSTS_BLOCK, &lines)?;
+    }
+    for file in private_files(&dir) {
+        if file.exists() || (file == config_file && !config.hosts.is_empty()) {
+            ensure_owned_private_file(&file, &user)?;
+        }
+    }
+
+    if config.server {
+        apt::install_missing(&["openssh-server"])?;
+        if !system::systemctl_succeeds("is-enabled", SSH_SERVICE)
+            || !system::systemctl_succeeds("is-active", SSH_SERVICE)
+        {
+            privileged::run("systemctl", &["enable", "--now", SSH_SERVICE])?;
+        }
+        say!("SSH server enabled; this machine accepts SSH logins.");
+    }
+
+    let public_key = fs::read_to_string(key.with_extension("pub"))
+        .ok()
+        .map(|key| key.trim().to_string());
+    if let Some(public_key) = &public_key {
+        say!("Public key {}:", key.with_extension("pub").display());
+        say!("{public_key}");
+    }
+    Ok(SshReport {
+        key,
+        key_created,
+        public_key,
+        hosts: config.hosts.keys().cloned().collect(),
+        server: config.server,
+    })
+}
+
+/// Removes the DebKit `Host` blocks from `~/.ssh/config`. Keys stay, since other
+/// machines may trust them, and so does openssh-server, which a remote session may
+/// be running through.
+pub fn uninstall() -> anyhow::Result<()> {
+    let user = target_user_context()?;
+    let config_file = ssh_dir(&user).join("config");
+    if shell_init::remove_managed_block(std::slice::from_ref(&config_file), HOSTS_BLOCK)?.is_empty()
+    {
+        say!("{} has no DebKit host block.", config_file.display());
+    } else {
+        say!(
+            "Removed the DebKit host block from {}",
+            config_file.display()
+        );
+    }
+    say!("Kept the SSH keys in {}.", ssh_dir(&user).display());
+    Ok(())
+}
+
+fn ssh_dir(user: &UserContext) -> PathBuf {
+    user.home.join(".ssh")
+}
+
+/// The files in `~/.ssh` that must be readable by the user alone.
+fn private_files(dir: &Path) -> [PathBuf; 3] {
+    [
+        dir.join("config"),
+        dir.join("authorized_keys"),
+        dir.join(KEY_NAME),
+    ]
+}
+
+/// Runs ssh-keygen as the target user, so the key is theirs, without a passphrase.
+fn generate_key(user: &UserContext, key: &Path, comment: &str) -> anyhow::Result<()> {
+    let mut command = command_as(user, "ssh-keygen")?;
+    command
+        .args(["-q", "-t", "ed25519", "-N", ""])
+        .arg("-f")
+        .arg(key);
+    if !comment.trim().is_empty() {
+        command.args(["-C", comment.trim()]);
+    }
+    let status = exec::status_step(&mut command, "Generating an ed25519 SSH key")
+        .context("failed to start `ssh-keygen`")?;
+    if !status.success() {
+        bail!("ssh-keygen failed with status {status}");
+    }
+    say!("Generated {}", key.display());
+    Ok(())
+}
+
+/// The `Host` blocks for `[ssh.hosts]`, separated by blank lines.
+fn host_lines(config: &SshConfig) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (alias, host) in &config.hosts {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(format!("Host {alias}"));
+        lines.extend(
+            host_options(host)
+                .into_iter()
+                .map(|(key, value)| format!("    {key} {value}")),
+        );
+    }
+    lines
+}
+
+fn host_options(host: &SshHost) -> Vec<(String, String)> {
+    let mut options = [
+        ("HostName", host.hostname.clone()),
+        ("User", host.user.clone()),
+        (
+            "Port",
+            if host.port == 0 {
+                String::new()
+            } else {
+                host.port.to_string()
+            },
+        ),
+        ("IdentityFile", host.identity_file.clone()),
+        (
+            "ForwardAgent",
+            if host.forward_agent { "yes" } else { "" }.to_string(),
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, value)| !value.trim().is_empty())
+    .map(|(key, value)| (key.to_string(), value))
+    .collect::<Vec<_>>();
+    options.extend(
+        host.options
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone())),
+    );
+    options
+}
+
+/// Whether group or others may use `path`.
+fn too_open(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o077 != 0)
+}
+
+pub struct SshTarget;
+
+impl Target for SshTarget {
+    fn name(&self) -> &'static str {
+        "ssh"
+    }
+
+    fn description(&self) -> &'static str {
+        "SSH key, ~/.ssh/config host blocks, and optionally openssh-server"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall)
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let report = run(&ctx.config()?.ssh)?;
+        ctx.report(&report)
+    }
+
+    fn uninstall(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall()
+    }
+
+    fn version(&self) -> Option<String> {
+        apt::package_version("openssh-client")
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(&["openssh-client", "openssh-server"])
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let config = &ctx.config()?.ssh;
+        let user = target_user_context()?;
+        let dir = ssh_dir(&user);
+        let mut status = super::TargetStatus::new(self.name(), self.version());
+        let mut drift = Vec::new();
+        if config.generate_key && !dir.join(KEY_NAME).exists() {
+            drift.push(format!("{} is missing", dir.join(KEY_NAME).display()));
+        }
+        if !config.hosts.is_empty() {
+            let lines = host_lines(config);
+            let lines = lines.iter().map(String::as_str).collect::<Vec<_>>();
+            if !shell_init::has_managed_block(&dir.join("config"), HOSTS_BLOCK, &lines) {
+                drift.push(format!(
+                    "{} does not have the configured hosts",
+                    dir.join("config").display()
+                ));
+            }
+        }
+        for path in std::iter::once(dir.clone()).chain(private_files(&dir)) {
+            if too_open(&path) {
+                drift.push(format!("{} is accessible to other users", path.display()));
+            }
+        }
+        if config.server {
+            if apt::package_version("openssh-server").is_none() {
+                drift.push("openssh-server is not installed".to_string());
+            } else if !system::systemctl_succeeds("is-active", SSH_SERVICE) {
+                drift.push(format!("{SSH_SERVICE} is not running"));
+            }
+        }
+        status.installed = status.installed && dir.join(KEY_NAME).exists();
+        Ok(status.with_drift(drift))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn renders_host_blocks() {
+        let mut config = SshConfig::default();
+        config.hosts.insert(
+            "spitfire".to_string(),
+            SshHost {
+                hostname: "spitfire.lan".to_string(),
+                user: "john".to_string(),
+                port: 2222,
+                forward_agent: true,
+                options: BTreeMap::from([("ProxyJump".to_string(), "bastion".to_string())]),
+                ..SshHost::default()
+            },
+        );
+        config.hosts.insert(
+            "bastion".to_string(),
+            SshHost {
+                hostname: "203.0.113.7".to_string(),
+                ..SshHost::default()
+            },
+        );
+        assert_eq!(
+            host_lines(&config),
+            [
+                "Host bastion",
+                "    HostName 203.0.113.7",
+                "",
+                "Host spitfire",
+                "    HostName spitfire.lan",
+                "    User john",
+                "    Port 2222",
+                "    ForwardAgent yes",
+                "    ProxyJump bastion",
+            ]
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use serde::Serialize;
+
+use super::{Action, Context, TargetStatus};
+use crate::output::{self, say};
+use crate::{config, state};
+
+#[derive(Debug, Serialize)]
+struct StatusAllReport {
+    ok: bool,
+    targets: Vec<TargetStatus>,
+}
+
+/// Collects the state of every registered target. A target whose checks fail is
+/// reported as not installed with the error as drift instead of aborting the run.
+pub fn collect_all() -> anyhow::Result<Vec<TargetStatus>> {
+    let config = config::load_or_init(&config::path()?)?;
+    let state = state::current();
+    let mut statuses = Vec::new();
+    for target in super::targets() {
+        let matches = super::default_matches(*target, Action::Status)?;
+        let ctx = Context::with_config(&matches, config.clone());
+        let status = target.summary(&ctx).unwrap_or_else(|err| {
+            TargetStatus::new(target.name(), None)
+                .with_drift(vec![format!("status check failed: {err:#}")])
+        });
+        statuses.push(TargetStatus {
+            managed: state.is_managed(target.name()),
+            ..status
+        });
+    }
+    Ok(statuses)
+}
+
+pub fn run_all() -> anyhow::Result<()> {
+    let targets = collect_all()?;
+    if output::is_json() {
+        return output::emit(&StatusAllReport { ok: true, targets });
+    }
+    say!("{}", render_table(&targets).trim_end());
+    let installed = targets.iter().filter(|status| status.installed).count();
+    let drifted = targets
+        .iter()
+        .filter(|status| !status.drift.is_empty())
+        .count();
+    output::outcome(format!(
+        "{installed} of {} targets installed, {drifted} with drift",
+        targets.len()
+    ));
+    Ok(())
+}
+
+fn render_table(statuses: &[TargetStatus]) -> String {
+    let rows = statuses
+        .iter()
+        .map(|status| {
+            [
+                status.target.to_string(),
+                if status.enabled {
+                    yes_no(status.installed).to_string()
+                } else {
+                    "disabled".to_string()
+                },
+                status.version.clone().unwrap_or_else(|| "-".to_string()),
+                status.configured.map_or("-", yes_no).to_string(),
+                yes_no(status.managed).to_string(),
+                status.drift.len().to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = [
+        "TARGET",
+        "INSTALLED",
+        "VERSION",
+        "CONFIGURED",
+        "MANAGED",
+        "DRIFT",
+    ]
+    .map(String::from);
+
+    let mut widths = header.clone().map(|cell| cell.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+
+    let drifted = statuses
+        .iter()
+        .filter(|status| !status.drift.is_empty())
+        .collect::<Vec<_>>();
+    if !drifted.is_empty() {
+        out.push_str("\nDrift:\n");
+        for status in drifted {
+            for item in &status.drift {
+                out.push_str(&format!("- {}: {item}\n", status.target));
+            }
+        }
+    }
+    out
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "yes" } else { "no" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_aligned_table_with_drift_details() {
+        let statuses = vec![
+            TargetStatus {
+                managed: true,
+                ..TargetStatus::new("git", Some("git version 2.47.3".to_string()))
+            },
+            TargetStatus::new("variety", None)
+                .with_drift(vec!["autostart entry is missing".to_string()]),
+        ];
+        let expected = [
+            "TARGET   INSTALLED  VERSION             CONFIGURED  MANAGED  DRIFT",
+            "git      yes        git version 2.47.3  -           yes      0",
+            "variety  no         -                   no          no       1",
+            "",
+            "Drift:",
+            "- variety: autostart entry is missing",
+            "",
+        ];
+        assert_eq!(render_table(&statuses), expected.join("\n"));
+    }
+}
@@ -4,7 +4,10 @@ use std::process::Command;
 
 use anyhow::{Context, bail};
 
+use super::Target;
 use crate::config::SudoNopassConfig;
+use crate::output::say;
+use crate::{exec, system};
 
 const SUDOERS_MAIN_PATH: &str = "/etc/sudoers";
 const SUDOERS_DROPIN_DIR: &str = "/etc/sudoers.d";
@@ -13,7 +16,7 @@ const LEGACY_NOPASS_PATH: &str = "/etc/sudoers.d/nopass";
 
 pub fn run(config: &SudoNopassConfig) -> anyhow::Result<()> {
     if !config.enabled {
-        println!("Passwordless sudo is disabled (`sudo_nopass.enabled = false`).");
+        say!("Passwordless sudo is disabled (`sudo_nopass.enabled = false`).");
         return Ok(());
     }
 
@@ -33,14 +36,14 @@ pub fn run(config: &SudoNopassConfig) -> anyhow::Result<()> {
     validate_sudoers()?;
     validate_passwordless_sudo(config);
 
-    println!("Passwordless sudo configured:");
-    println!("  Group: {}", config.group);
+    say!("Passwordless sudo configured:");
+    say!("  Group: {}", config.group);
     if config.nis_managed {
-        println!("  Mode: NIS-managed (group membership controlled via NIS)");
+        say!("  Mode: NIS-managed (group membership controlled via NIS)");
     } else {
-        println!("  Users: {}", effective_users(config).join(", "));
+        say!("  Users: {}", effective_users(config).join(", "));
     }
-    println!("  Drop-in: {SUDOERS_DROPIN_DIR}/99-{}-nopass", config.group);
+    say!("  Drop-in: {SUDOERS_DROPIN_DIR}/99-{}-nopass", config.group);
     Ok(())
 }
 
@@ -48,7 +51,7 @@ fn ensure_group_exists(group: &str) -> anyhow::Result<()> {
     if local_group_exists(group) {
         return Ok(());
     }
-    if group_exists(group) {
+    if system::group_exists(group) {
         bail!(
             "`sudo_nopass.group = \"{group}\"` resolves through NSS but is not a local /etc/group entry; set `sudo_nopass.nis_managed = true` for NIS-managed group membership or choose a local group"
         );
@@ -93,7 +96,7 @@ fn ensure_group_nopass_rule(group: &str) -> anyhow::Result<()> {
 
 fn secure_sudoers_dropin(path: &str) -> anyhow::Result<()> {
     run_root_command("chown", &["root:root", path])?;
-    run_root_command("chmod", &["0440", &path])
+    run_root_command("chmod", &["0440", path])
 }
 
 fn render_group_nopass_rule(group: &str) -> String {
@@ -107,7 +110,7 @@ fn add_users_to_group(config: &SudoNopassConfig) -> anyhow::Result<()> {
 
     for user in users {
         if !user_exists(&user) {
-            println!("Skipping user {user}; account not found.");
+            say!("Skipping user {user}; account not found.");
             continue;
         }
         if user_is_in_group(&user, &config.group)? {
@@ -120,10 +123,7 @@ fn add_users_to_group(config: &SudoNopassConfig) -> anyhow::Result<()> {
 }
 
 fn validate_sudoers() -> anyhow::Result<()> {
-    let status = Command::new("visudo")
-        .arg("-c")
-        .status()
-        .context("failed to run visudo")?;
+    let status = exec::probe(Command::new("visudo").arg("-c")).context("failed to run visudo")?;
     if !status.success() {
         bail!("visudo -c failed with status {}", status);
     }
@@ -136,26 +136,26 @@ fn validate_passwordless_sudo(config: &SudoNopassConfig) {
         return;
     }
 
-    match Command::new("getent").args(["group", group]).output() {
+    match exec::query(Command::new("getent").args(["group", group])) {
         Ok(output) if output.status.success() => {
             let raw = String::from_utf8_lossy(&output.stdout);
-            println!("Validated sudo group lookup:");
-            println!("  getent group {group}");
+            say!("Validated sudo group lookup:");
+            say!("  getent group {group}");
             if !raw
                 .lines()
                 .any(|line| line.starts_with(&format!("{group}:")))
             {
-                println!("warning: `getent group {group}` succeeded but did not return `{group}`.");
+                say!("warning: `getent group {group}` succeeded but did not return `{group}`.");
             }
         }
         Ok(output) => {
-            println!(
+            say!(
                 "warning: `getent group {group}` failed with status {}.",
                 output.status
             );
         }
         Err(err) => {
-            println!("warning: failed to run `getent group {group}`: {err}.");
+            say!("warning: failed to run `getent group {group}`: {err}.");
         }
     }
 
@@ -163,35 +163,35 @@ fn validate_passwordless_sudo(config: &SudoNopassConfig) {
         match user_is_in_group(&user, group) {
             Ok(true) => {}
             Ok(false) => {
-                println!(
-                    "warning: `{user}` is not currently reported as a member of `{group}` by `id -nG {user}`; sudo will ask for a password until NSS reports that membership."
+                say!(
+                    "warning: `{user}` is not currently a member of `{group}` according to NSS; sudo will ask for a password until NSS reports that membership."
                 );
             }
             Err(err) => {
-                println!("warning: failed to check `id -nG {user}`: {err:#}");
+                say!("warning: failed to check the groups of `{user}`: {err:#}");
             }
         }
 
         if current_user().as_deref() == Some(user.as_str()) && !current_process_is_in_group(group) {
-            println!(
-                "warning: the current login session is not in `{group}` according to plain `id`; start a new login session before testing passwordless sudo."
+            say!(
+                "warning: the current login session is not in `{group}`; start a new login session before testing passwordless sudo."
             );
         }
 
-        if current_euid().ok() == Some(0) {
+        if system::is_root() {
             match sudo_policy_allows_nopass(&user) {
                 Ok(true) => {}
                 Ok(false) => {
-                    println!(
+                    say!(
                         "warning: `sudo -n -l -U {user}` does not show a NOPASSWD rule; `sudo -i` will still ask for a password."
                     );
                 }
                 Err(err) => {
-                    println!("warning: failed to validate sudo policy for `{user}`: {err:#}");
+                    say!("warning: failed to validate sudo policy for `{user}`: {err:#}");
                 }
             }
         } else {
-            println!(
+            say!(
                 "warning: skipping exact sudo policy validation for `{user}` because DebKit is not running as root."
             );
         }
@@ -199,9 +199,7 @@ fn validate_passwordless_sudo(config: &SudoNopassConfig) {
 }
 
 fn sudo_policy_allows_nopass(user: &str) -> anyhow::Result<bool> {
-    let output = Command::new("sudo")
-        .args(["-n", "-l", "-U", user])
-        .output()
+    let output = exec::query(Command::new("sudo").args(["-n", "-l", "-U", user]))
         .with_context(|| format!("failed to run `sudo -n -l -U {user}`"))?;
     if !output.status.success() {
         return Ok(false);
@@ -221,12 +219,11 @@ fn effective_users(config: &SudoNopassConfig) -> Vec<String> {
         })
         .collect();
 
-    if config.add_current_user {
-        if let Some(user) = current_user() {
-            if user != "root" {
-                users.push(user);
-            }
-        }
+    if config.add_current_user
+        && let Some(user) = current_user()
+        && user != "root"
+    {
+        users.push(user);
     }
 
     users
@@ -240,14 +237,6 @@ fn current_user() -> Option<String> {
         .map(|value| value.trim().to_string())
 }
 
-fn group_exists(group: &str) -> bool {
-    Command::new("getent")
-        .args(["group", group])
-        .status()
-        .map(|status| status.success())
-        .unwrap_or(false)
-}
-
 fn local_group_exists(group: &str) -> bool {
     fs::read_to_string("/etc/group")
         .ok()
@@ -259,44 +248,20 @@ fn local_group_exists(group: &str) -> bool {
 }
 
 fn user_exists(user: &str) -> bool {
-    Command::new("id")
-        .args(["-u", user])
-        .status()
-        .map(|status| status.success())
-        .unwrap_or(false)
+    system::uid(user).is_ok_and(|uid| uid.is_some())
 }
 
 fn user_is_in_group(user: &str, group: &str) -> anyhow::Result<bool> {
-    let output = Command::new("id")
-        .args(["-nG", user])
-        .output()
-        .with_context(|| format!("failed to check group membership for {user}"))?;
-    if !output.status.success() {
-        return Ok(false);
-    }
-    let groups = String::from_utf8_lossy(&output.stdout);
-    Ok(groups.split_whitespace().any(|item| item == group))
+    Ok(system::user_groups(user)?.iter().any(|item| item == group))
 }
 
 fn current_process_is_in_group(group: &str) -> bool {
-    Command::new("id")
-        .arg("-nG")
-        .output()
-        .ok()
-        .filter(|output| output.status.success())
-        .map(|output| {
-            String::from_utf8_lossy(&output.stdout)
-                .split_whitespace()
-                .any(|item| item == group)
-        })
-        .unwrap_or(false)
+    system::current_groups().iter().any(|item| item == group)
 }
 
 fn run_root_command(program: &str, args: &[&str]) -> anyhow::Result<()> {
-    if current_euid()? == 0 {
-        let status = Command::new(program)
-            .args(args)
-            .status()
+    if system::is_root() {
+        let status = exec::status(Command::new(program).args(args))
             .with_context(|| format!("failed to start `{program}`"))?;
         if !status.success() {
             bail!("{program} {} failed with status {}", args.join(" "), status);
@@ -304,14 +269,11 @@ fn run_root_command(program: &str, args: &[&str]) -> anyhow::Result<()> {
         return Ok(());
     }
 
-    if !command_available("sudo") {
+    if !system::command_available("sudo") {
         bail!("`{program}` requires root privileges; run as root or install `sudo` and retry");
     }
 
-    let status = Command::new("sudo")
-        .arg(program)
-        .args(args)
-        .status()
+    let status = exec::status(Command::new("sudo").arg(program).args(args))
         .with_context(|| format!("failed to start `sudo {program}`"))?;
     if !status.success() {
         bail!(
@@ -331,60 +293,74 @@ fn ensure_root_file(path: &str, content: &str) -> anyhow::Result<()> {
         }
     }
 
-    if current_euid()? == 0 {
-        fs::write(path, content).with_context(|| format!("failed to write {path}"))?;
+    if system::is_root() {
+        exec::write(path, content).with_context(|| format!("failed to write {path}"))?;
         return Ok(());
     }
 
-    if !command_available("sudo") {
+    if !system::command_available("sudo") {
         bail!("writing {path} requires root privileges; run as root or install `sudo` and retry");
     }
 
-    let status = Command::new("sudo")
-        .arg("tee")
-        .arg(path)
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::null())
-        .spawn()
-        .and_then(|mut child| {
-            use std::io::Write;
-            child
-                .stdin
-                .as_mut()
-                .expect("piped stdin")
-                .write_all(content.as_bytes())?;
-            child.wait()
-        })
-        .with_context(|| format!("failed to write {path}"))?;
+    let status = exec::write_via(
+        Command::new("sudo")
+            .arg("tee")
+            .arg(path)
+            .stdout(std::process::Stdio::null()),
+        Path::new(path),
+        content.as_bytes(),
+    )
+    .with_context(|| format!("failed to write {path}"))?;
     if !status.success() {
         bail!("sudo tee {path} failed with status {status}");
     }
     Ok(())
 }
 
-fn command_available(program: &str) -> bool {
-    Command::new("sh")
-        .arg("-c")
-        .arg(format!("command -v {program} >/dev/null 2>&1"))
-        .status()
-        .map(|status| status.success())
-        .unwrap_or(false)
-}
+pub struct SudoNopassTarget;
 
-fn current_euid() -> anyhow::Result<u32> {
-    let output = Command::new("id")
-        .arg("-u")
-        .output()
-        .context("failed to run `id -u`")?;
-    if !output.status.success() {
-        bail!("`id -u` failed with status {}", output.status);
+impl Target for SudoNopassTarget {
+    fn name(&self) -> &'static str {
+        "sudo-nopass"
+    }
+
+    fn description(&self) -> &'static str {
+        "Passwordless sudo for configured users"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["sudo_nopass", "admin-group-nopass", "admin_group_nopass"]
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let config = ctx.config()?;
+        run(&config.sudo_nopass)?;
+        super::nis::rebuild_and_push_maps(&config.nis)
     }
 
-    let stdout = String::from_utf8(output.stdout).context("`id -u` returned non-UTF-8 output")?;
-    stdout
-        .trim()
-        .parse::<u32>()
-        .with_context(|| format!("failed to parse `id -u` output `{}`", stdout.trim()))
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let config = &ctx.config()?.sudo_nopass;
+        if !config.enabled {
+            return Ok(super::TargetStatus::disabled(self.name()));
+        }
+        let mut status = super::TargetStatus::new(self.name(), None);
+
+        let path = format!("{SUDOERS_DROPIN_DIR}/99-{}-nopass", config.group);
+        let installed = fs::read_to_string(&path).ok();
+        status.installed = installed.is_some();
+        let mut drift = Vec::new();
+        if installed.as_deref() != Some(render_group_nopass_rule(&config.group).as_str()) {
+            drift.push(format!("{path} is missing or modified"));
+        }
+        if !config.nis_managed {
+            for user in effective_users(config) {
+                if user_exists(&user) && !user_is_in_group(&user, &config.group)? {
+                    drift.push(format!("{user} is not in group `{}`", config.group));
+                }
+            }
+        }
+        Ok(status.with_drift(drift))
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,341 @@
+//! `install tailscale`: the Tailscale VPN from its upstream repository.
+//!
+//! `pkgs.tailscale.com` is added as the `tailscale` APT source with its signing key,
+//! the `tailscale` package is installed and `tailscaled.service` enabled and started.
+//! With `up = true`, `tailscale up` then joins the tailnet with an auth key from the
+//! environment or a file. The key goes to tailscale on stdin or as a `file:` path, so
+//! it never shows in the process list, dry-run output or the audit log.
+//! `status tailscale` reports the connection state.
+
+use std::fs;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+
+use super::{Action, Target};
+use crate::config::TailscaleConfig;
+use crate::output::{self, say};
+use crate::{apt, exec, keyring, offline, privileged, repo, system};
+
+const REPO_NAME: &str = "tailscale";
+const TAILSCALE_SERVICE: &str = "tailscaled.service";
+/// The key Tailscale signs its package repositories with.
+const TAILSCALE_KEY_FINGERPRINT: &str = "2596A99EAAB33821893C0A79458CA832957F5868";
+/// The backend state of a node that is connected to its tailnet.
+const RUNNING: &str = "Running";
+
+#[derive(Debug, Serialize)]
+pub struct TailscaleStatus {
+    pub version: Option<String>,
+    pub service: bool,
+    /// The backend state, such as `Running`, `NeedsLogin` or `Stopped`.
+    pub state: Option<String>,
+    /// The node's MagicDNS name.
+    pub name: Option<String>,
+    pub addresses: Vec<String>,
+}
+
+impl TailscaleStatus {
+    fn connected(&self) -> bool {
+        self.state.as_deref() == Some(RUNNING)
+    }
+
+    fn drift(&self, config: &TailscaleConfig) -> Vec<String> {
+        let mut drift = Vec::new();
+        if !repo::path(REPO_NAME).exists() {
+            drift.push(format!("{} is missing", repo::path(REPO_NAME).display()));
+        }
+        if !self.service {
+            drift.push(format!("{TAILSCALE_SERVICE} is not running"));
+        } else if config.up && !self.connected() {
+            drift.push(format!(
+                "not connected to the tailnet ({})",
+                self.state.as_deref().unwrap_or("unknown state")
+            ));
+        }
+        drift
+    }
+}
+
+pub fn run(config: &TailscaleConfig) -> anyhow::Result<TailscaleStatus> {
+    if !repo::path(REPO_NAME).exists() {
+        add_tailscale_repo()?;
+    }
+    apt::install_missing(&["tailscale"])?;
+    if !system::systemctl_succeeds("is-enabled", TAILSCALE_SERVICE)
+        || !system::systemctl_succeeds("is-active", TAILSCALE_SERVICE)
+    {
+        privileged::run("systemctl", &["enable", "--now", TAILSCALE_SERVICE])?;
+    }
+
+    if config.up {
+        if collect_status().connected() {
+            say!("Already connected to the tailnet.");
+        } else {
+            tailscale_up(config)?;
+        }
+    }
+    let status = collect_status();
+    if let Some(name) = &status.name {
+        say!("Connected as {name} ({})", status.addresses.join(", "));
+    }
+    Ok(status)
+}
+
+/// Removes the package and the source. The node's identity in `/var/lib/tailscale`
+/// stays, so reinstalling rejoins the tailnet as the same machine.
+pub fn uninstall() -> anyhow::Result<()> {
+    if apt::package_installed("tailscale")? {
+        apt::remove(&["tailscale"])?;
+    } else {
+        say!("tailscale is not installed.");
+    }
+    if repo::path(REPO_NAME).exists() {
+        repo::remove(REPO_NAME)?;
+    }
+    say!("Kept the node state in /var/lib/tailscale.");
+    Ok(())
+}
+
+/// Runs `tailscale up` with the auth key from `auth_key_env`, or else from
+/// `auth_key_file`. Without either it leaves logging in to the user, since an
+/// interactive login would wait for a browser.
+fn tailscale_up(config: &TailscaleConfig) -> anyhow::Result<()> {
+    if offline::is_enabled() {
+        bail!("joining the tailnet needs network access");
+    }
+    let key = std::env::var(&config.auth_key_env)
+        .ok()
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty());
+    let mut command = privileged::command("tailscale")?;
+    command.arg("up");
+    let status = if let Some(key) = key {
+        command
+            .arg("--auth-key=file:/dev/stdin")
+            .args(&config.up_args);
+        exec::status_with_input(&mut command, key.as_bytes())
+    } else if !config.auth_key_file.is_empty() {
+        command
+            .arg(format!("--auth-key=file:{}", config.auth_key_file))
+            .args(&config.up_args);
+        exec::status_step(&mut command, "Joining the tailnet")
+    } else {
+        say!(
+            "No auth key in {} or `tailscale.auth_key_file`; run `sudo tailscale up` to log in.",
+            config.auth_key_env
+        );
+        return Ok(());
+    }
+    .context("failed to start `tailscale up`")?;
+    if !status.success() {
+        bail!("`tailscale up` failed with status {status}");
+    }
+    Ok(())
+}
+
+pub fn collect_status() -> TailscaleStatus {
+    let mut status = exec::query(Command::new("tailscale").args(["status", "--json"]))
+        .ok()
+        .and_then(|output| parse_status(&output.stdout))
+        .unwrap_or(TailscaleStatus {
+            version: None,
+            service: false,
+            state: None,
+            name: None,
+            addresses: Vec::new(),
+        });
+    status.version = apt::package_version("tailscale");
+    status.service = system::systemctl_succeeds("is-active", TAILSCALE_SERVICE);
+    status
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StatusJson {
+    backend_state: String,
+    #[serde(rename = "Self")]
+    node: Option<NodeJson>,
+}
+
+#[derive(Deserialize)]
+struct NodeJson {
+    #[serde(rename = "DNSName", default)]
+    dns_name: String,
+    #[serde(rename = "TailscaleIPs", default)]
+    addresses: Option<Vec<String>>,
+}
+
+/// Reads `tailscale status --json`, which exits non-zero but still prints the state
+/// when the node is logged out.
+fn parse_status(json: &[u8]) -> Option<TailscaleStatus> {
+    let status: StatusJson = serde_json::from_slice(json).ok()?;
+    let connected = status.backend_state == RUNNING;
+    let node = status.node.filter(|_| connected);
+    Some(TailscaleStatus {
+        version: None,
+        service: false,
+        state: Some(status.backend_state),
+        name: node
+            .as_ref()
+            .map(|node| node.dns_name.trim_end_matches('.').to_string())
+            .filter(|name| !name.is_empty()),
+        addresses: node.and_then(|node| node.addresses).unwrap_or_default(),
+    })
+}
+
+fn print_status_report(status: &TailscaleStatus) {
+    say!(
+        "tailscale: {}",
+        status.version.as_deref().unwrap_or("not installed")
+    );
+    say!(
+        "{TAILSCALE_SERVICE}: {}",
+        if status.service { "running" } else { "stopped" }
+    );
+    say!("state: {}", status.state.as_deref().unwrap_or("unknown"));
+    if let Some(name) = &status.name {
+        say!("name: {name}");
+    }
+    if !status.addresses.is_empty() {
+        say!("addresses: {}", status.addresses.join(", "));
+    }
+}
+
+/// Adds `pkgs.tailscale.com` for this distribution and release as the `tailscale`
+/// source. A fingerprint under `[keys] tailscale` replaces the built-in one.
+fn add_tailscale_repo() -> anyhow::Result<()> {
+    let os_release =
+        fs::read_to_string("/etc/os-release").context("failed to read /etc/os-release")?;
+    let Some((distro, codename)) = tailscale_distro(&os_release) else {
+        bail!("Tailscale publishes packages for Debian, Ubuntu and Raspbian; this system is none");
+    };
+    let fingerprint = keyring::declared_fingerprint(REPO_NAME)?
+        .unwrap_or_else(|| TAILSCALE_KEY_FINGERPRINT.to_string());
+    repo::add(
+        &format!("deb https://pkgs.tailscale.com/stable/{distro} {codename} main"),
+        repo::AddOptions {
+            name: Some(REPO_NAME),
+            key_url: Some(&format!(
+                "https://pkgs.tailscale.com/stable/{distro}/{codename}.noarmor.gpg"
+            )),
+            fingerprint: Some(&fingerprint),
+            suite: None,
+        },
+    )?;
+    Ok(())
+}
+
+/// The `stable/<distro>` directory of pkgs.tailscale.com and the release codename to
+/// use from `/etc/os-release`. Derivatives use the release they are based on.
+fn tailscale_distro(os_release: &str) -> Option<(&'static str, String)> {
+    let value = |key: &str| {
+        os_release
+            .lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+            .map(|value| value.trim_matches('"').to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let id = value("ID").unwrap_or_default();
+    let like = value("ID_LIKE").unwrap_or_default();
+    let family = |name: &str| id == name || like.split_whitespace().any(|like| like == name);
+    if id == "raspbian" {
+        Some(("raspbian", value("VERSION_CODENAME")?))
+    } else if family("ubuntu") {
+        Some((
+            "ubuntu",
+            value("UBUNTU_CODENAME").or(value("VERSION_CODENAME"))?,
+        ))
+    } else if family("debian") {
+        Some((
+            "debian",
+            value("DEBIAN_CODENAME").or(value("VERSION_CODENAME"))?,
+        ))
+    } else {
+        None
+    }
+}
+
+pub struct TailscaleTarget;
+
+impl Target for TailscaleTarget {
+    fn name(&self) -> &'static str {
+        "tailscale"
+    }
+
+    fn description(&self) -> &'static str {
+        "Tailscale VPN from pkgs.tailscale.com, optionally joined to a tailnet"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall | Action::Status)
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let status = run(&ctx.config()?.tailscale)?;
+        ctx.report(&status)
+    }
+
+    fn uninstall(&self, _ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall()
+    }
+
+    fn status(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let status = collect_status();
+        if !output::is_json() {
+            print_status_report(&status);
+        }
+        ctx.report(&status)?;
+        if !status.drift(&ctx.config()?.tailscale).is_empty() {
+            return Err(output::Drift.into());
+        }
+        Ok(())
+    }
+
+    fn version(&self) -> Option<String> {
+        apt::package_version("tailscale")
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(&["tailscale"])
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let status = collect_status();
+        let summary = super::TargetStatus::new(self.name(), status.version.clone());
+        if !summary.installed {
+            return Ok(summary);
+        }
+        Ok(summary.with_drift(status.drift(&ctx.config()?.tailscale)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_connection_state() {
+        let running = br#"{"Version": "1.76.1", "BackendState": "Running", "Self": {
+            "HostName": "spitfire", "DNSName": "spitfire.tail1234.ts.net.",
+            "TailscaleIPs": ["100.101.102.103", "fd7a:115c:a1e0::1"]}}"#;
+        let status = parse_status(running).unwrap();
+        assert!(status.connected());
+        assert_eq!(status.name.as_deref(), Some("spitfire.tail1234.ts.net"));
+        assert_eq!(status.addresses, ["100.101.102.103", "fd7a:115c:a1e0::1"]);
+
+        let logged_out =
+            br#"{"BackendState": "NeedsLogin", "Self": {"DNSName": "", "TailscaleIPs": null}}"#;
+        let status = parse_status(logged_out).unwrap();
+        assert_eq!(status.state.as_deref(), Some("NeedsLogin"));
+        assert_eq!(status.name, None);
+        assert!(status.addresses.is_empty());
+
+        let raspbian = "ID=raspbian\nID_LIKE=debian\nVERSION_CODENAME=bookworm\n";
+        assert_eq!(
+            tailscale_distro(raspbian),
+            Some(("raspbian", "bookworm".to_string()))
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+
+use crate::{exec, system};
+
+#[derive(Debug, Clone)]
+pub struct UserContext {
+    pub name: Option<String>,
+    pub home: PathBuf,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+pub fn target_user_context() -> anyhow::Result<UserContext> {
+    if system::is_root()
+        && let Some(sudo_user) = env::var_os("SUDO_USER")
+    {
+        let sudo_user = sudo_user.to_string_lossy().trim().to_string();
+        if !sudo_user.is_empty() {
+            if let Some(entry) = passwd_entry_for_user(&sudo_user) {
+                return Ok(UserContext {
+                    name: Some(sudo_user),
+                    home: entry.home,
+                    uid: Some(entry.uid),
+                    gid: Some(entry.gid),
+                });
+            }
+            return Ok(UserContext {
+                home: PathBuf::from(format!("/home/{sudo_user}")),
+                name: Some(sudo_user),
+                uid: None,
+                gid: None,
+            });
+        }
+    }
+
+    let home = env::var_os("HOME")
+        .map(PathBuf::from)
+        .context("HOME environment variable is not set")?;
+    Ok(UserContext {
+        name: env::var("USER").ok().filter(|user| !user.trim().is_empty()),
+        home,
+        uid: None,
+        gid: None,
+    })
+}
+
+/// Builds a command that runs as the target user, dropping root privileges when
+/// DebKit was started through sudo, with the user's session bus in the environment.
+pub fn command_as(user: &UserContext, program: &str) -> anyhow::Result<Command> {
+    let (Some(name), Some(uid)) = (user.name.as_deref(), user.uid) else {
+        return Ok(Command::new(program));
+    };
+    if !system::is_root() || uid == 0 {
+        return Ok(Command::new(program));
+    }
+
+    let runtime_dir = PathBuf::from(format!("/run/user/{uid}"));
+    let mut command = Command::new("runuser");
+    command
+        .args(["-u", name, "--", program])
+        .env("HOME", &user.home)
+        .env("USER", name)
+        .env("LOGNAME", name)
+        .env("XDG_RUNTIME_DIR", &runtime_dir)
+        .env(
+            "DBUS_SESSION_BUS_ADDRESS",
+            format!("unix:path={}", runtime_dir.join("bus").display()),
+        );
+    Ok(command)
+}
+
+/// Builds `systemctl --user <args>` for the target user. Through sudo it goes
+/// through the user's running systemd instance with [`command_as`], or with
+/// `machinectl shell` when the user has no session bus, which starts one.
+pub fn systemctl_as(user: &UserContext, args: &[&str]) -> anyhow::Result<Command> {
+    let (Some(name), Some(uid)) = (user.name.as_deref(), user.uid) else {
+        return Ok(user_systemctl(Command::new("systemctl"), args));
+    };
+    if !system::is_root() || uid == 0 || Path::new(&format!("/run/user/{uid}/bus")).exists() {
+        return Ok(user_systemctl(command_as(user, "systemctl")?, args));
+    }
+    let (Some(_), Some(systemctl)) = (
+        system::find_program("machinectl"),
+        system::find_program("systemctl"),
+    ) else {
+        bail!(
+            "{name} has no systemd user session; log in as {name} once, or install systemd-container for machinectl"
+        );
+    };
+    let mut command = Command::new("machinectl");
+    command
+        .args(["--quiet", "shell", &format!("{name}@.host")])
+        .arg(systemctl);
+    Ok(user_systemctl(command, args))
+}
+
+fn user_systemctl(mut command: Command, args: &[&str]) -> Command {
+    command.arg("--user").args(args);
+    command
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswdEntry {
+    pub uid: u32,
+    pub gid: u32,
+    pub home: PathBuf,
+    /// The login shell, such as `/bin/bash`.
+    pub shell: String,
+}
+
+pub fn passwd_entry_for_user(user: &str) -> Option<PasswdEntry> {
+    let passwd = fs::read_to_string("/etc/passwd").ok()?;
+    passwd_entry_for_user_from_passwd(user, &passwd)
+}
+
+fn passwd_entry_for_user_from_passwd(user: &str, passwd: &str) -> Option<PasswdEntry> {
+    for line in passwd.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+
+        let mut parts = line.split(':');
+        let name = parts.next()?;
+        if name != user {
+            continue;
+        }
+
+        let _password = parts.next()?;
+        let uid = parts.next()?.parse::<u32>().ok()?;
+        let gid = parts.next()?.parse::<u32>().ok()?;
+        let _gecos = parts.next()?;
+        let home = PathBuf::from(parts.next()?);
+        let shell = parts.next().unwrap_or_default().to_string();
+        return Some(PasswdEntry {
+            uid,
+            gid,
+            home,
+            shell,
+        });
+    }
+
+    None
+}
+
+/// The people with accounts on this machine: users with a uid from 1000 up, a login
+/// shell and a home directory that exists. System accounts and `nobody` are left out.
+pub fn human_users() -> anyhow::Result<Vec<UserContext>> {
+    let passwd = fs::read_to_string("/etc/passwd").context("failed to read /etc/passwd")?;
+    Ok(human_users_from_passwd(&passwd)
+        .into_iter()
+        .filter(|user| user.home.is_dir())
+        .collect())
+}
+
+fn human_users_from_passwd(passwd: &str) -> Vec<UserContext> {
+    let mut users = Vec::new();
+    for line in passwd.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(':').collect();
+        let [name, _password, uid, gid, _gecos, home, shell] = fields[..] else {
+            continue;
+        };
+        let (Ok(uid), Ok(gid)) = (uid.parse::<u32>(), gid.parse::<u32>()) else {
+            continue;
+        };
+        let no_login = shell.ends_with("/nologin") || shell.ends_with("/false");
+        if uid < 1000 || uid == 65534 || no_login || !home.starts_with('/') || home == "/" {
+            continue;
+        }
+        users.push(UserContext {
+            name: Some(name.to_string()),
+            home: PathBuf::from(home),
+            uid: Some(uid),
+            gid: Some(gid),
+        });
+    }
+    users
+}
+
+pub fn ensure_owned_writable_dir(path: &Path, user: &UserContext) -> anyhow::Result<()> {
+    set_mode(path, 0o755)?;
+    if let (Some(uid), Some(gid)) = (user.uid, user.gid) {
+        chown_path(path, uid, gid)?;
+    }
+    Ok(())
+}
+
+pub fn ensure_owned_writable_file(path: &Path, user: &UserContext) -> anyhow::Result<()> {
+    set_mode(path, 0o644)?;
+    if let (Some(uid), Some(gid)) = (user.uid, user.gid) {
+        chown_path(path, uid, gid)?;
+    }
+    Ok(())
+}
+
+/// Like [`ensure_owned_writable_dir`], but only the user may enter it, as ssh wants
+/// for `~/.ssh`.
+pub fn ensure_owned_private_dir(path: &Path, user: &UserContext) -> anyhow::Result<()> {
+    set_mode(path, 0o700)?;
+    if let (Some(uid), Some(gid)) = (user.uid, user.gid) {
+        chown_path(path, uid, gid)?;
+    }
+    Ok(())
+}
+
+/// Like [`ensure_owned_writable_file`], but only the user may read it.
+pub fn ensure_owned_private_file(path: &Path, user: &UserContext) -> anyhow::Result<()> {
+    set_mode(path, 0o600)?;
+    if let (Some(uid), Some(gid)) = (user.uid, user.gid) {
+        chown_path(path, uid, gid)?;
+    }
+    Ok(())
+}
+
+fn set_mode(path: &Path, mode: u32) -> anyhow::Result<()> {
+    if exec::is_dry_run() && !path.exists() {
+        return Ok(());
+    }
+    let mut perms = fs::metadata(path)
+        .with_context(|| format!("failed to read metadata for {}", path.display()))?
+        .permissions();
+    perms.set_mode(mode);
+    exec::set_permissions(path, perms)
+        .with_context(|| format!("failed to set permissions on {}", path.display()))?;
+    Ok(())
+}
+
+fn chown_path(path: &Path, uid: u32, gid: u32) -> anyhow::Result<()> {
+    exec::chown(path, uid, gid).with_context(|| {
+        format!(
+            "failed to set ownership on {} to {uid}:{gid}",
+            path.display()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_passwd_entry() {
+        let passwd = "root:x:0:0:root:/root:/bin/bash\nuser1:x:1000:1000::/home/user1:/bin/bash\n";
+        assert_eq!(
+            passwd_entry_for_user_from_passwd("user1", passwd),
+            Some(PasswdEntry {
+                uid: 1000,
+                gid: 1000,
+                home: PathBuf::from("/home/user1"),
+                shell: "/bin/bash".to_string(),
+            })
+        );
+        assert_eq!(passwd_entry_for_user_from_passwd("missing", passwd), None);
+    }
+
+    #[test]
+    fn lists_human_users_only() {
+        let passwd = "\
+root:x:0:0:root:/root:/bin/bash
+daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin
+nobody:x:65534:65534:nobody:/nonexistent:/usr/sbin/nologin
+alice:x:1000:1000:Alice,,,:/home/alice:/bin/bash
+builder:x:1001:1001::/var/lib/builder:/bin/false
+bob:x:1002:100::/home/bob:/usr/bin/zsh
+";
+        let users = human_users_from_passwd(passwd);
+        let names: Vec<_> = users
+            .iter()
+            .filter_map(|user| user.name.as_deref())
+            .collect();
+        assert_eq!(names, ["alice", "bob"]);
+        assert_eq!(users[1].home, PathBuf::from("/home/bob"));
+        assert_eq!((users[1].uid, users[1].gid), (Some(1002), Some(100)));
+    }
+}
@@ -1,119 +1,664 @@
-use std::env;
+use std::ffi::OsStr;
 use std::fs;
-use std::os::unix::fs::PermissionsExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, bail};
-
-use crate::config::DebkitConfig;
-
-#[derive(Debug, Clone)]
+use serde::Serialize;
+use tracing::warn;
+
+use super::user::{
+    UserContext, command_as, ensure_owned_writable_dir, ensure_owned_writable_file, human_users,
+    systemctl_as, target_user_context,
+};
+use super::{Action, Target, appindicator};
+use crate::backup;
+use crate::config::{DebkitConfig, VarietyAutostart, VarietyConfig, VarietyMode};
+use crate::desktop;
+use crate::desktop::environment::{self, BackgroundSchema, Environment};
+use crate::desktop::gsettings;
+use crate::desktop::slideshow::{self, Inventory};
+use crate::exec;
+use crate::output::{self, say};
+use crate::pin;
+use crate::system;
+
+#[derive(Debug, Clone, Serialize)]
 pub struct VarietyStatus {
+    pub mode: VarietyMode,
+    pub desktop: Environment,
+    pub slideshow_exists: bool,
     pub installed_version: Option<String>,
     pub wallpapers_folder: String,
     pub wallpapers_folder_exists: bool,
+    /// The images in the wallpapers folder, when it could be read.
+    pub wallpapers: Option<Inventory>,
+    /// From `variety.autostart`.
+    pub autostart: VarietyAutostart,
+    /// The XDG autostart entry exists, or in `systemd` mode the user unit is enabled.
     pub autostart_exists: bool,
+    /// variety.conf holds exactly what `configure variety` would write.
+    pub conf_matches: bool,
+    /// A `variety` process runs as the target user.
+    pub running: bool,
+    /// `change_interval` in variety.conf, which is what Variety goes by.
+    pub change_interval_seconds: Option<u32>,
+    /// The `change_interval` `interval_minutes` asks for.
+    pub configured_interval_seconds: u32,
+    pub privacy_settings: Vec<PrivacySetting>,
+    /// Differences from the config; `status variety` exits with status 2 when there
+    /// are any.
+    pub drift: Vec<String>,
 }
 
-pub fn run(config: &DebkitConfig) -> anyhow::Result<()> {
-    install_variety_package()?;
+#[derive(Debug, Clone, Serialize)]
+pub struct PrivacySetting {
+    pub key: &'static str,
+    pub configured: bool,
+    pub current: Option<String>,
+}
 
-    if !command_available("variety") {
+/// Installs and configures Variety; `reset` first regenerates variety.conf from the
+/// packaged default, dropping changes made in Variety's preferences, and `with_tray`
+/// adds the GNOME extension that shows its tray icon.
+pub fn run(config: &DebkitConfig, reset: bool, with_tray: bool) -> anyhow::Result<()> {
+    if config.variety.mode == VarietyMode::GnomeSlideshow {
+        return run_gnome_slideshow(config, &target_user_context()?);
+    }
+
+    install_variety_package(&config.variety)?;
+
+    if !system::command_available("variety") && !exec::is_dry_run() {
         bail!("`variety` was not found on PATH after installation");
     }
 
     let user = target_user_context()?;
-    configure_variety(&user, config)?;
+    configure_variety(&user, config, reset)?;
 
     let status = collect_status_for_user(config, &user)?;
     print_status_report(&status);
+    warn_about_wallpapers(&status);
 
-    if is_gnome_desktop() {
-        println!(
-            "Note: If the tray icon is missing on GNOME, AppIndicator extension may be absent. Wallpaper rotation still works without tray support."
+    if with_tray {
+        appindicator::run()?;
+    } else if status.desktop == Environment::Gnome {
+        say!(
+            "Note: If the tray icon is missing on GNOME, AppIndicator extension may be absent; `install variety --with-tray` adds it. Wallpaper rotation still works without tray support."
         );
     }
 
     Ok(())
 }
 
-pub fn print_status(config: &DebkitConfig) -> anyhow::Result<()> {
+/// Re-applies the Variety (or GNOME slideshow) settings without touching packages.
+pub fn configure(config: &DebkitConfig, reset: bool) -> anyhow::Result<()> {
+    if config.variety.mode == VarietyMode::GnomeSlideshow {
+        return run_gnome_slideshow(config, &target_user_context()?);
+    }
     let user = target_user_context()?;
-    let status = collect_status_for_user(config, &user)?;
-    print_status_report(&status);
+    configure_variety(&user, config, reset)?;
+    say!("Variety configuration applied.");
     Ok(())
 }
 
-fn configure_variety(user: &UserContext, config: &DebkitConfig) -> anyhow::Result<()> {
+/// Re-applies the configuration when Variety or the GNOME slideshow is set up, so it
+/// sees the images `wallpapers-sync` just copied.
+pub fn refresh(config: &DebkitConfig) -> anyhow::Result<()> {
+    let user = target_user_context()?;
+    let set_up = if config.variety.mode == VarietyMode::GnomeSlideshow {
+        slideshow_path(&user).exists()
+    } else {
+        variety_conf_path(&user).exists()
+    };
+    if set_up {
+        configure(config, false)?;
+    }
+    Ok(())
+}
+
+/// How `install variety --all-users` went for one user.
+#[derive(Debug, Clone, Serialize)]
+pub struct UserOutcome {
+    pub user: String,
+    pub ok: bool,
+    /// Why configuring failed.
+    pub error: Option<String>,
+    pub drift: Vec<String>,
+}
+
+/// `install variety --all-users`: installs the package once, then applies the per-user
+/// configuration for every account [`human_users`] finds, carrying on past failures.
+pub fn run_all_users(
+    config: &DebkitConfig,
+    reset: bool,
+    with_tray: bool,
+) -> anyhow::Result<Vec<UserOutcome>> {
+    if !system::is_root() {
+        bail!("`--all-users` writes into other users' homes; run it with sudo");
+    }
+    let users = human_users()?;
+    if users.is_empty() {
+        bail!("no local users with a uid from 1000 up and an existing home directory");
+    }
+    let slideshow = config.variety.mode == VarietyMode::GnomeSlideshow;
+    if !slideshow {
+        install_variety_package(&config.variety)?;
+        if !system::command_available("variety") && !exec::is_dry_run() {
+            bail!("`variety` was not found on PATH after installation");
+        }
+    }
+
+    let mut outcomes = Vec::new();
+    for user in &users {
+        let name = user.name.clone().unwrap_or_default();
+        say!("Configuring {} for {name}", config.variety.mode);
+        let configured = if slideshow {
+            run_gnome_slideshow(config, user)
+        } else {
+            configure_variety(user, config, reset).and_then(|()| {
+                if with_tray {
+                    appindicator::run_for(user)
+                } else {
+                    Ok(())
+                }
+            })
+        };
+        let outcome = match configured.and_then(|()| collect_status_for_user(config, user)) {
+            Ok(status) => UserOutcome {
+                user: name,
+                ok: true,
+                error: None,
+                drift: status.drift,
+            },
+            Err(err) => {
+                warn!(
+                    "failed to configure {} for {name}: {err:#}",
+                    config.variety.mode
+                );
+                UserOutcome {
+                    user: name,
+                    ok: false,
+                    error: Some(format!("{err:#}")),
+                    drift: Vec::new(),
+                }
+            }
+        };
+        outcomes.push(outcome);
+    }
+    Ok(outcomes)
+}
+
+/// One row per user: `ok`, `drift` with what differs, or `failed` with the error.
+pub fn print_user_outcomes(outcomes: &[UserOutcome]) {
+    let width = outcomes
+        .iter()
+        .map(|outcome| outcome.user.len())
+        .max()
+        .unwrap_or(0)
+        .max("USER".len());
+    say!("{:<width$}  {:<7} DETAILS", "USER", "RESULT");
+    for outcome in outcomes {
+        let (result, details) = match &outcome.error {
+            Some(error) => ("failed", error.clone()),
+            None if outcome.drift.is_empty() => ("ok", String::new()),
+            None => ("drift", outcome.drift.join("; ")),
+        };
+        say!("{:<width$}  {result:<7} {details}", outcome.user);
+    }
+}
+
+pub fn collect_status(config: &DebkitConfig) -> anyhow::Result<VarietyStatus> {
+    let user = target_user_context()?;
+    collect_status_for_user(config, &user)
+}
+
+/// Rotates wallpapers with GNOME's built-in slideshow support instead of the Variety
+/// package: the images in `wallpapers.folder` are written into a background XML and
+/// the desktop's background schema, `org.gnome.desktop.background` or its Cinnamon and
+/// MATE counterparts, is pointed at it.
+fn run_gnome_slideshow(config: &DebkitConfig, user: &UserContext) -> anyhow::Result<()> {
+    let folder = Path::new(&config.wallpapers.folder);
+    if config.wallpapers.folder.trim().is_empty() || !folder.is_dir() {
+        bail!(
+            "`wallpapers.folder` must be an existing directory for gnome_slideshow mode (got `{}`)",
+            config.wallpapers.folder
+        );
+    }
+    let images = slideshow::collect_images(folder, slideshow::INVENTORY_DEPTH)?;
+    if images.is_empty() {
+        bail!("no images found in {}", folder.display());
+    }
+
+    let desktop = environment::detect(user);
+    let background = slideshow_background(desktop)?;
+    let xml_path = slideshow_path(user);
+    let backgrounds_dir = xml_path
+        .parent()
+        .context("slideshow path has no parent directory")?;
+    exec::create_dir_all(backgrounds_dir)
+        .with_context(|| format!("failed to create {}", backgrounds_dir.display()))?;
+    ensure_owned_writable_dir(backgrounds_dir, user)?;
+
+    let interval_seconds = u64::from(config.variety.interval_minutes).saturating_mul(60);
+    let desired = desktop::slideshow::render(&images, interval_seconds);
+    let existing = fs::read_to_string(&xml_path).unwrap_or_default();
+    if existing != desired {
+        exec::write(&xml_path, desired)
+            .with_context(|| format!("failed to write {}", xml_path.display()))?;
+    }
+    ensure_owned_writable_file(&xml_path, user)?;
+
+    let picture = if background.picture_is_uri {
+        environment::file_uri(&xml_path)
+    } else {
+        xml_path.display().to_string()
+    };
+    for (key, value) in [
+        (background.picture_key, picture.as_str()),
+        ("picture-uri-dark", picture.as_str()),
+        ("picture-options", "zoom"),
+    ] {
+        set_background_key(user, background.schema, key, value)?;
+    }
+
+    say!(
+        "GNOME slideshow configured with {} images from {} ({} min per image): {}",
+        images.len(),
+        folder.display(),
+        config.variety.interval_minutes,
+        xml_path.display()
+    );
+    for autostart in [autostart_path(user), systemd_unit_path(user)] {
+        if autostart.exists() {
+            warn!(
+                "{} still exists; Variety will override the slideshow when it starts",
+                autostart.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the Variety package and the files `install variety` wrote for the target
+/// user. Downloaded wallpapers and Variety's other state are left alone unless
+/// `purge_config` deletes all of `~/.config/variety`.
+pub fn uninstall(purge_config: bool) -> anyhow::Result<()> {
+    let user = target_user_context()?;
+    let mut removed_any = remove_systemd_unit(&user)?;
+
+    for path in [autostart_path(&user), variety_conf_path(&user)] {
+        if path.exists() {
+            backup::save(&path)?;
+            exec::remove_file(&path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+            say!("Removed {}", path.display());
+            removed_any = true;
+        }
+    }
+    let config_dir = variety_config_dir(&user);
+    if purge_config && config_dir.exists() {
+        exec::remove_dir_all(&config_dir)
+            .with_context(|| format!("failed to remove {}", config_dir.display()))?;
+        say!("Removed {}", config_dir.display());
+        removed_any = true;
+    }
+
+    pin::remove("variety")?;
+    if crate::apt::package_installed("variety")? {
+        pin::set_held("variety", false)?;
+        crate::apt::remove(&["variety"])?;
+        removed_any = true;
+    }
+
+    if !removed_any {
+        say!("Variety is not installed.");
+        return Ok(());
+    }
+
+    say!("Variety uninstalled.");
+    Ok(())
+}
+
+fn autostart_path(user: &UserContext) -> PathBuf {
+    user.home
+        .join(".config")
+        .join("autostart")
+        .join("variety.desktop")
+}
+
+fn systemd_unit_path(user: &UserContext) -> PathBuf {
+    user.home
+        .join(".config")
+        .join("systemd")
+        .join("user")
+        .join(VARIETY_UNIT)
+}
+
+/// The link `systemctl --user enable` makes for the unit's `WantedBy=`.
+fn systemd_unit_link(user: &UserContext) -> PathBuf {
+    user.home
+        .join(".config")
+        .join("systemd")
+        .join("user")
+        .join("graphical-session.target.wants")
+        .join(VARIETY_UNIT)
+}
+
+fn variety_config_dir(user: &UserContext) -> PathBuf {
+    user.home.join(".config").join("variety")
+}
+
+fn variety_conf_path(user: &UserContext) -> PathBuf {
+    variety_config_dir(user).join("variety.conf")
+}
+
+fn slideshow_path(user: &UserContext) -> PathBuf {
+    user.home
+        .join(".local")
+        .join("share")
+        .join("backgrounds")
+        .join("debkit-slideshow.xml")
+}
+
+/// The schema a slideshow XML can be set in. A desktop that was not recognized, as is
+/// common through sudo without a login session, is taken for GNOME.
+fn slideshow_background(desktop: Environment) -> anyhow::Result<BackgroundSchema> {
+    match desktop {
+        Environment::Unknown => Ok(Environment::Gnome
+            .background_schema()
+            .context("GNOME has a background schema")?),
+        desktop => desktop.background_schema().with_context(|| {
+            format!(
+                "gnome_slideshow mode needs GNOME, Cinnamon or MATE, but this is {}; use `mode = \"variety\"`",
+                desktop.name()
+            )
+        }),
+    }
+}
+
+fn set_background_key(
+    user: &UserContext,
+    schema: &str,
+    key: &str,
+    value: &str,
+) -> anyhow::Result<()> {
+    if key == "picture-uri-dark" && !gsettings::has_key(user, schema, key) {
+        return Ok(());
+    }
+    gsettings::set(user, schema, key, value)
+}
+
+fn configure_variety(user: &UserContext, config: &DebkitConfig, reset: bool) -> anyhow::Result<()> {
     let wallpapers_dir = Path::new(&config.wallpapers.folder);
     if !wallpapers_dir.exists() {
-        eprintln!(
-            "warning: wallpapers folder does not exist: {}",
+        warn!(
+            "wallpapers folder {} not found; check `wallpapers.folder` in the config",
             wallpapers_dir.display()
         );
     }
 
     let config_dir = user.home.join(".config");
-    fs::create_dir_all(&config_dir)
+    exec::create_dir_all(&config_dir)
         .with_context(|| format!("failed to create {}", config_dir.display()))?;
     ensure_owned_writable_dir(&config_dir, user)?;
 
-    let variety_dir = config_dir.join("variety");
-    fs::create_dir_all(&variety_dir)
+    let conf_path = variety_conf_path(user);
+    let variety_dir = conf_path
+        .parent()
+        .context("variety.conf path has no parent directory")?;
+    exec::create_dir_all(variety_dir)
         .with_context(|| format!("failed to create {}", variety_dir.display()))?;
-    ensure_owned_writable_dir(&variety_dir, user)?;
+    ensure_owned_writable_dir(variety_dir, user)?;
 
-    let conf_path = variety_dir.join("variety.conf");
+    let changes = exec::changes();
     ensure_variety_conf(
         &conf_path,
         &config.wallpapers.folder,
-        config.variety.interval_minutes,
+        &config.variety,
+        reset,
     )?;
+    let conf_changed = exec::changes() > changes;
     ensure_owned_writable_file(&conf_path, user)?;
 
-    configure_gsettings_best_effort(config);
+    configure_gsettings_best_effort(user, config);
+    let desktop = environment::detect(user);
+    if let Err(err) = zoom_wallpapers(user, desktop) {
+        warn!(
+            "could not make {} zoom wallpapers to fill the screen: {err:#}",
+            desktop.name()
+        );
+    }
 
-    let autostart_path = user
-        .home
-        .join(".config")
-        .join("autostart")
-        .join("variety.desktop");
-    ensure_autostart_desktop(&autostart_path)?;
-    if let Some(parent) = autostart_path.parent() {
-        ensure_owned_writable_dir(parent, user)?;
+    let autostart_path = autostart_path(user);
+    if config.variety.autostart == VarietyAutostart::Systemd {
+        ensure_systemd_unit(user)?;
+        if autostart_path.exists() {
+            exec::remove_file(&autostart_path)
+                .with_context(|| format!("failed to remove {}", autostart_path.display()))?;
+            say!("Removed {}", autostart_path.display());
+        }
+    } else {
+        remove_systemd_unit(user)?;
+        ensure_autostart_desktop(&autostart_path)?;
+        if let Some(parent) = autostart_path.parent() {
+            ensure_owned_writable_dir(parent, user)?;
+        }
+        ensure_owned_writable_file(&autostart_path, user)?;
     }
-    ensure_owned_writable_file(&autostart_path, user)?;
 
+    if conf_changed && let Err(err) = restart_running_variety(user) {
+        warn!("could not restart variety; the new settings apply from the next login: {err:#}");
+    }
     Ok(())
 }
 
-fn install_variety_package() -> anyhow::Result<()> {
-    super::apt::install_missing(&["variety"])?;
+/// Seconds to wait for `variety --quit` before giving up on the restart.
+const QUIT_TIMEOUT_SECONDS: u64 = 10;
+
+/// Variety reads variety.conf only when it starts, so a running instance is restarted
+/// after the file changed: through systemd when `variety.service` runs it, otherwise
+/// with `variety --quit` and a new `variety` in the same graphical session.
+fn restart_running_variety(user: &UserContext) -> anyhow::Result<()> {
+    let Some(pid) = variety_pids(user).first().copied() else {
+        return Ok(());
+    };
+    let unit_active = systemctl_as(user, &["is-active", "--quiet", VARIETY_UNIT])
+        .ok()
+        .and_then(|mut command| exec::probe(command.stderr(Stdio::null())).ok())
+        .is_some_and(|status| status.success());
+    if unit_active {
+        run_user_systemctl(user, &["restart", VARIETY_UNIT])?;
+        say!("Restarted {VARIETY_UNIT} to apply the new settings");
+        return Ok(());
+    }
+
+    let environ = fs::read(format!("/proc/{pid}/environ")).unwrap_or_default();
+    let session = session_env(&environ);
+    run_checked(
+        command_as(user, "variety")?
+            .arg("--quit")
+            .envs(session.clone()),
+        "variety --quit",
+    )?;
+    if !exec::is_dry_run() {
+        let deadline = Instant::now() + Duration::from_secs(QUIT_TIMEOUT_SECONDS);
+        while !variety_pids(user).is_empty() {
+            if Instant::now() > deadline {
+                bail!("variety did not quit within {QUIT_TIMEOUT_SECONDS} seconds");
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+    }
+    run_checked(
+        command_as(user, "setsid")?
+            .args(["-f", "variety"])
+            .envs(session)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null()),
+        "setsid -f variety",
+    )?;
+    say!("Restarted variety to apply the new settings");
     Ok(())
 }
 
-fn ensure_variety_conf(path: &Path, folder: &str, interval_minutes: u32) -> anyhow::Result<()> {
+/// The variables tying a process to its graphical session, from the NUL-separated
+/// `/proc/<pid>/environ` of one running in it.
+fn session_env(environ: &[u8]) -> Vec<(String, String)> {
+    const SESSION_VARS: &[&str] = &[
+        "DISPLAY",
+        "WAYLAND_DISPLAY",
+        "XAUTHORITY",
+        "XDG_RUNTIME_DIR",
+        "XDG_SESSION_TYPE",
+        "XDG_CURRENT_DESKTOP",
+        "DBUS_SESSION_BUS_ADDRESS",
+    ];
+    environ
+        .split(|byte| *byte == 0)
+        .filter_map(|var| {
+            String::from_utf8_lossy(var)
+                .split_once('=')
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+        })
+        .filter(|(name, _)| SESSION_VARS.contains(&name.as_str()))
+        .collect()
+}
+
+const VARIETY_UNIT: &str = "variety.service";
+
+/// A user unit that starts Variety with the graphical session, for sessions that do
+/// not run XDG autostart entries.
+fn render_systemd_unit(variety: &Path) -> String {
+    format!(
+        "# Managed by debkit (variety.autostart = \"systemd\")\n\
+         [Unit]\n\
+         Description=Variety wallpaper changer\n\
+         PartOf=graphical-session.target\n\
+         After=graphical-session.target\n\
+         \n\
+         [Service]\n\
+         ExecStart={}\n\
+         Restart=on-failure\n\
+         \n\
+         [Install]\n\
+         WantedBy=graphical-session.target\n",
+        variety.display()
+    )
+}
+
+/// Writes `variety.service` for the target user and enables it.
+fn ensure_systemd_unit(user: &UserContext) -> anyhow::Result<()> {
+    let path = systemd_unit_path(user);
+    let unit_dir = path.parent().context("unit path has no parent directory")?;
+    exec::create_dir_all(unit_dir)
+        .with_context(|| format!("failed to create {}", unit_dir.display()))?;
+    for dir in unit_dir.ancestors().take(2) {
+        ensure_owned_writable_dir(dir, user)?;
+    }
+
+    let variety = system::find_program("variety").unwrap_or_else(|| "/usr/bin/variety".into());
+    let desired = render_systemd_unit(&variety);
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+    let changed = existing != desired;
+    if changed {
+        exec::write(&path, desired)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    ensure_owned_writable_file(&path, user)?;
+
+    if changed {
+        run_user_systemctl(user, &["daemon-reload"])?;
+    }
+    if changed || !systemd_unit_link(user).exists() {
+        run_user_systemctl(user, &["enable", VARIETY_UNIT])?;
+    }
+    Ok(())
+}
+
+/// Disables and removes `variety.service`, returning whether there was one.
+fn remove_systemd_unit(user: &UserContext) -> anyhow::Result<bool> {
+    let path = systemd_unit_path(user);
+    if !path.exists() {
+        return Ok(false);
+    }
+    run_user_systemctl(user, &["disable", VARIETY_UNIT])?;
+    exec::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+    run_user_systemctl(user, &["daemon-reload"])?;
+    say!("Removed {}", path.display());
+    Ok(true)
+}
+
+fn run_user_systemctl(user: &UserContext, args: &[&str]) -> anyhow::Result<()> {
+    run_checked(
+        &mut systemctl_as(user, args)?,
+        &format!("systemctl --user {}", args.join(" ")),
+    )
+}
+
+/// Installs the package at the pinned version, if any, and holds it when asked. A
+/// changed pin reinstalls so apt moves to the newly pinned version.
+fn install_variety_package(variety: &VarietyConfig) -> anyhow::Result<()> {
+    let version = variety.version.trim();
+    let repinned = if version.is_empty() {
+        pin::remove("variety")?
+    } else {
+        pin::set("variety", version)?
+    };
+    if repinned || !crate::apt::package_installed("variety")? {
+        pin::set_held("variety", false)?;
+        crate::apt::install(&["variety"])?;
+    }
+    pin::set_held("variety", variety.hold)?;
+    Ok(())
+}
+
+fn ensure_variety_conf(
+    path: &Path,
+    folder: &str,
+    variety: &VarietyConfig,
+    reset: bool,
+) -> anyhow::Result<()> {
     let existing = if path.exists() {
-        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?
+        Some(
+            fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?,
+        )
     } else {
-        default_variety_conf().unwrap_or_default()
+        None
     };
 
-    let updated = configure_variety_conf_text(&existing, folder, interval_minutes);
-    if updated != existing {
-        fs::write(path, updated).with_context(|| format!("failed to write {}", path.display()))?;
+    let base = variety_conf_base(existing.as_deref(), reset);
+    let updated = configure_variety_conf_text(&base, folder, variety);
+    if Some(&updated) != existing.as_ref() {
+        backup::save(path)?;
+        exec::write(path, updated)
+            .with_context(|| format!("failed to write {}", path.display()))?;
     }
 
     Ok(())
 }
 
+/// The text debkit's settings are applied to: the current variety.conf, or the
+/// packaged default when there is none yet or with `reset`.
+fn variety_conf_base(existing: Option<&str>, reset: bool) -> String {
+    match existing {
+        Some(existing) if !reset => existing.to_string(),
+        _ => default_variety_conf().unwrap_or_default(),
+    }
+}
+
 fn default_variety_conf() -> Option<String> {
     fs::read_to_string("/usr/share/variety/config/variety.conf").ok()
 }
 
-fn configure_variety_conf_text(existing: &str, folder: &str, interval_minutes: u32) -> String {
-    let interval_seconds = interval_minutes.saturating_mul(60).max(5);
+fn configure_variety_conf_text(existing: &str, folder: &str, variety: &VarietyConfig) -> String {
+    let interval_seconds = change_interval_seconds(variety);
     let mut lines = existing
         .lines()
         .map(ToString::to_string)
@@ -122,22 +667,178 @@ fn configure_variety_conf_text(existing: &str, folder: &str, interval_minutes: u
     upsert_root_key(&mut lines, "change_enabled", "True");
     upsert_root_key(&mut lines, "change_on_start", "True");
     upsert_root_key(&mut lines, "change_interval", &interval_seconds.to_string());
-    upsert_root_key(&mut lines, "internet_enabled", "False");
     upsert_root_key(&mut lines, "wallpaper_auto_rotate", "True");
+    if !variety.download_folder.trim().is_empty() {
+        upsert_root_key(
+            &mut lines,
+            "download_folder",
+            variety.download_folder.trim(),
+        );
+    }
+    upsert_root_key(&mut lines, "quota_enabled", conf_bool(variety.quota_mb > 0));
+    if variety.quota_mb > 0 {
+        upsert_root_key(&mut lines, "quota_size", &variety.quota_mb.to_string());
+    }
 
-    upsert_root_key(&mut lines, "smart_notice_shown", "True");
-    upsert_root_key(&mut lines, "smart_register_shown", "True");
-    upsert_root_key(&mut lines, "stats_notice_shown", "True");
-
-    set_section(
+    upsert_root_key(
         &mut lines,
-        "sources",
-        &[format!("src1 = True|folder|{folder}")],
+        "clock_enabled",
+        conf_bool(variety.clock_enabled),
+    );
+    upsert_root_key(
+        &mut lines,
+        "quotes_enabled",
+        conf_bool(variety.quotes_enabled),
     );
 
+    for (key, enabled) in privacy_settings(variety) {
+        upsert_root_key(&mut lines, key, conf_bool(enabled));
+    }
+
+    let mut sources = vec![format!("src1 = True|folder|{folder}")];
+    for (index, source) in variety.online_sources.iter().enumerate() {
+        sources.push(format!(
+            "src{} = {}|{}",
+            index + 2,
+            conf_bool(variety.internet_enabled),
+            online_source(source)
+        ));
+    }
+    set_section(&mut lines, "sources", &sources);
+    for filter in set_filters(&mut lines, &variety.filters) {
+        warn!("variety.conf has no filter named `{filter}`; add it to its [filters] section");
+    }
+
     to_text(lines)
 }
 
+/// The `type|location` of an `online_sources` entry such as `wallhaven:nature`. Sources
+/// without a location get the description Variety gives them.
+/// Variety's `change_interval` for `interval_minutes`; it changes at most every 5 seconds.
+fn change_interval_seconds(variety: &VarietyConfig) -> u32 {
+    variety.interval_minutes.saturating_mul(60).max(5)
+}
+
+fn online_source(source: &str) -> String {
+    if let Some((kind, location)) = source.split_once(':') {
+        return format!("{kind}|{}", location.trim());
+    }
+    let description = match source {
+        "unsplash" => "High-resolution photos from Unsplash.com",
+        "bing" => "Bing Photo of the Day",
+        "apod" => "NASA's Astronomy Picture of the Day",
+        "desktoppr" => "Random wallpapers from Desktoppr.co",
+        "chromecast" => "Chromecast Backgrounds",
+        _ => source,
+    };
+    format!("{source}|{description}")
+}
+
+/// The filters in Variety's default variety.conf, with their ImageMagick arguments.
+const FILTERS: &[(&str, &str)] = &[
+    ("Keep original", ""),
+    ("Grayscale", "-type Grayscale"),
+    ("Heavy blur", "-blur 120x40"),
+    ("Soft blur", "-blur 20x7"),
+    ("Oil painting", "-paint 6"),
+    ("Pencil sketch", "-colorspace gray -sketch 0x20+120"),
+    ("Pointilism", "-spread 10 -noise 3"),
+    ("Pixellate", "-scale 3% -scale 3333%"),
+];
+
+/// Turns on the `[filters]` entries named in `enabled` and off the others, adding the
+/// named ones from [`FILTERS`] that are missing. Returns the names that are neither in
+/// the section nor in [`FILTERS`].
+fn set_filters(lines: &mut Vec<String>, enabled: &[String]) -> Vec<String> {
+    let wanted = |name: &str| {
+        enabled
+            .iter()
+            .any(|filter| filter.trim().eq_ignore_ascii_case(name))
+    };
+    let mut filters = section_lines(lines, "filters");
+    let mut present = Vec::new();
+    for line in &mut filters {
+        let Some((key, value)) = parse_key_value(line.trim()) else {
+            continue;
+        };
+        let mut fields = value.splitn(3, '|');
+        let (Some(_), Some(name)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let args = fields.next().unwrap_or_default();
+        let updated = format!("{key} = {}|{name}|{args}", conf_bool(wanted(name)));
+        present.push(name.to_ascii_lowercase());
+        *line = updated;
+    }
+
+    let mut unknown = Vec::new();
+    let mut next = filters.len() + 1;
+    for filter in enabled.iter().map(|filter| filter.trim()) {
+        if filter.is_empty() || present.contains(&filter.to_ascii_lowercase()) {
+            continue;
+        }
+        match FILTERS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(filter))
+        {
+            Some((name, args)) => {
+                filters.push(format!("filter{next} = True|{name}|{args}"));
+                next += 1;
+            }
+            None => unknown.push(filter.to_string()),
+        }
+    }
+    set_section(lines, "filters", &filters);
+    unknown
+}
+
+/// The lines of `section`, without its header.
+fn section_lines(lines: &[String], section: &str) -> Vec<String> {
+    let header = format!("[{section}]");
+    lines
+        .iter()
+        .skip_while(|line| line.trim() != header)
+        .skip(1)
+        .take_while(|line| {
+            let trimmed = line.trim();
+            !(trimmed.starts_with('[') && trimmed.ends_with(']'))
+        })
+        .cloned()
+        .collect()
+}
+
+fn privacy_settings(variety: &VarietyConfig) -> [(&'static str, bool); 8] {
+    [
+        ("internet_enabled", variety.internet_enabled),
+        ("safe_mode", variety.safe_mode),
+        ("smart_enabled", variety.smart_enabled),
+        ("sync_enabled", variety.sync_enabled),
+        ("stats_enabled", variety.stats_enabled),
+        ("smart_notice_shown", variety.smart_notice_shown),
+        ("smart_register_shown", variety.smart_register_shown),
+        ("stats_notice_shown", variety.stats_notice_shown),
+    ]
+}
+
+fn conf_bool(value: bool) -> &'static str {
+    if value { "True" } else { "False" }
+}
+
+fn root_key_value(content: &str, key: &str) -> Option<String> {
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            return None;
+        }
+        if let Some((line_key, value)) = parse_key_value(trimmed)
+            && line_key == key
+        {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
 fn upsert_root_key(lines: &mut Vec<String>, key: &str, value: &str) {
     let mut first_idx = None;
     let mut to_remove = Vec::new();
@@ -227,14 +928,14 @@ fn to_text(lines: Vec<String>) -> String {
     out
 }
 
-fn configure_gsettings_best_effort(config: &DebkitConfig) {
-    if !command_available("gsettings") {
+fn configure_gsettings_best_effort(user: &UserContext, config: &DebkitConfig) {
+    if !gsettings::available() {
         return;
     }
 
     let interval_seconds = config.variety.interval_minutes.saturating_mul(60);
     let folder = config.wallpapers.folder.replace('"', "\\\"");
-    let folder_uri = format!("file://{folder}");
+    let folder_uri = environment::file_uri(Path::new(&config.wallpapers.folder));
 
     let attempts = [
         ("org.variety", "sources", format!("['{folder_uri}']")),
@@ -244,29 +945,157 @@ fn configure_gsettings_best_effort(config: &DebkitConfig) {
             "change-interval",
             interval_seconds.to_string(),
         ),
-        ("org.variety", "download-enabled", "false".to_string()),
+        (
+            "org.variety",
+            "download-enabled",
+            config.variety.internet_enabled.to_string(),
+        ),
     ];
 
     for (schema, key, value) in attempts {
-        let writable = Command::new("gsettings")
-            .args(["writable", schema, key])
-            .output();
-        let Ok(output) = writable else {
+        if gsettings::writable(user, schema, key) {
+            let _ = gsettings::set(user, schema, key, &value);
+        }
+    }
+}
+
+/// Has the desktop scale Variety's wallpapers to fill the screen, cropping what does
+/// not fit, whatever their aspect ratio. Desktops that keep the background in
+/// gsettings get `picture-options` `zoom`, Xfce gets `image-style` 5 on every
+/// backdrop, and Plasma gets `FillMode` 2 on every image wallpaper.
+fn zoom_wallpapers(user: &UserContext, desktop: Environment) -> anyhow::Result<()> {
+    if let Some(background) = desktop.background_schema() {
+        if !gsettings::available() {
+            return Ok(());
+        }
+        if gsettings::get(user, background.schema, "picture-options")?.as_deref() == Some("'zoom'")
+        {
+            return Ok(());
+        }
+        return set_background_key(user, background.schema, "picture-options", "zoom");
+    }
+    match desktop {
+        Environment::Xfce => zoom_xfce_wallpapers(user),
+        Environment::Kde => zoom_kde_wallpapers(user),
+        _ => Ok(()),
+    }
+}
+
+fn zoom_xfce_wallpapers(user: &UserContext) -> anyhow::Result<()> {
+    if !system::command_available("xfconf-query") {
+        return Ok(());
+    }
+    let xfconf = |args: &[&str]| -> anyhow::Result<String> {
+        let output = exec::query(
+            command_as(user, "xfconf-query")?
+                .args(["--channel", "xfce4-desktop"])
+                .args(args),
+        )
+        .context("failed to run `xfconf-query`")?;
+        if !output.status.success() {
+            bail!(
+                "xfconf-query {} failed with status {}",
+                args.join(" "),
+                output.status
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    let properties = xfconf(&["--list"])?;
+    for property in properties
+        .lines()
+        .filter(|property| property.starts_with("/backdrop/") && property.ends_with("/image-style"))
+    {
+        if xfconf(&["--property", property])? == "5" {
             continue;
-        };
-        if !output.status.success() || String::from_utf8_lossy(&output.stdout).trim() != "true" {
+        }
+        run_checked(
+            command_as(user, "xfconf-query")?.args([
+                "--channel",
+                "xfce4-desktop",
+                "--property",
+                property,
+                "--set",
+                "5",
+            ]),
+            &format!("xfconf-query --property {property} --set 5"),
+        )?;
+    }
+    Ok(())
+}
+
+fn zoom_kde_wallpapers(user: &UserContext) -> anyhow::Result<()> {
+    let appletsrc = user
+        .home
+        .join(".config")
+        .join("plasma-org.kde.plasma.desktop-appletsrc");
+    let Ok(content) = fs::read_to_string(&appletsrc) else {
+        return Ok(());
+    };
+    let containments = unzoomed_kde_containments(&content);
+    if containments.is_empty() {
+        return Ok(());
+    }
+    let kwriteconfig = ["kwriteconfig6", "kwriteconfig5"]
+        .into_iter()
+        .find(|program| system::command_available(program))
+        .context("neither kwriteconfig6 nor kwriteconfig5 is installed")?;
+    let file = appletsrc.display().to_string();
+    for containment in &containments {
+        let mut args = vec!["--file", file.as_str()];
+        for group in [
+            "Containments",
+            containment,
+            "Wallpaper",
+            "org.kde.image",
+            "General",
+        ] {
+            args.extend(["--group", group]);
+        }
+        args.extend(["--key", "FillMode", "2"]);
+        run_checked(
+            command_as(user, kwriteconfig)?.args(&args),
+            &format!("{kwriteconfig} {}", args.join(" ")),
+        )?;
+    }
+    say!("Plasma picks up the new wallpaper fill mode at the next login.");
+    Ok(())
+}
+
+/// The Plasma containments whose image wallpaper has a `FillMode` other than 2
+/// (scaled and cropped), which is also Plasma's default.
+fn unzoomed_kde_containments(appletsrc: &str) -> Vec<String> {
+    let mut containments = Vec::new();
+    let mut current = None;
+    for line in appletsrc.lines().map(str::trim) {
+        if line.starts_with('[') {
+            current = line
+                .strip_prefix("[Containments][")
+                .and_then(|rest| rest.strip_suffix("][Wallpaper][org.kde.image][General]"))
+                .map(str::to_string);
             continue;
         }
+        if let (Some(containment), Some((key, value))) = (&current, line.split_once('='))
+            && key.trim() == "FillMode"
+            && value.trim() != "2"
+        {
+            containments.push(containment.clone());
+        }
+    }
+    containments
+}
 
-        let _ = Command::new("gsettings")
-            .args(["set", schema, key, &value])
-            .status();
+fn run_checked(command: &mut Command, step: &str) -> anyhow::Result<()> {
+    let status = exec::status(command).with_context(|| format!("failed to run `{step}`"))?;
+    if !status.success() {
+        bail!("`{step}` failed with status {status}");
     }
+    Ok(())
 }
 
 fn ensure_autostart_desktop(path: &Path) -> anyhow::Result<()> {
     if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
+        exec::create_dir_all(parent)
             .with_context(|| format!("failed to create {}", parent.display()))?;
     }
 
@@ -288,13 +1117,33 @@ fn ensure_autostart_desktop(path: &Path) -> anyhow::Result<()> {
     } else {
         &existing
     });
+    desktop::entry::validate(&desired)
+        .with_context(|| format!("refusing to write autostart entry {}", path.display()))?;
     if existing != desired {
-        fs::write(path, desired).with_context(|| format!("failed to write {}", path.display()))?;
+        backup::save(path)?;
+        exec::write(path, desired)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    if !exec::is_dry_run() {
+        warn_desktop_file_validate(path);
     }
 
     Ok(())
 }
 
+fn warn_desktop_file_validate(path: &Path) {
+    if !system::command_available("desktop-file-validate") {
+        return;
+    }
+    let Ok(output) = exec::query(Command::new("desktop-file-validate").arg(path)) else {
+        return;
+    };
+    let report = String::from_utf8_lossy(&output.stdout);
+    for line in report.lines().filter(|line| !line.trim().is_empty()) {
+        warn!("{line}");
+    }
+}
+
 fn normalize_desktop_entry(content: &str) -> String {
     let mut lines = if content.contains("[Desktop Entry]") {
         content.lines().map(ToString::to_string).collect::<Vec<_>>()
@@ -340,34 +1189,227 @@ fn collect_status_for_user(
     user: &UserContext,
 ) -> anyhow::Result<VarietyStatus> {
     let installed_version = installed_variety_version();
-    let autostart = user
-        .home
-        .join(".config")
-        .join("autostart")
-        .join("variety.desktop");
+    let autostart_exists = if config.variety.autostart == VarietyAutostart::Systemd {
+        systemd_unit_path(user).exists() && systemd_unit_link(user).exists()
+    } else {
+        autostart_path(user).exists()
+    };
+    let conf_path = variety_conf_path(user);
+    let conf = fs::read_to_string(&conf_path).unwrap_or_default();
+    let conf_matches = conf_path.exists()
+        && configure_variety_conf_text(
+            &variety_conf_base(Some(&conf), false),
+            &config.wallpapers.folder,
+            &config.variety,
+        ) == conf;
+    let privacy_settings = privacy_settings(&config.variety)
+        .into_iter()
+        .map(|(key, configured)| PrivacySetting {
+            key,
+            configured,
+            current: root_key_value(&conf, key),
+        })
+        .collect();
 
-    Ok(VarietyStatus {
+    let mut status = VarietyStatus {
+        mode: config.variety.mode,
+        desktop: environment::detect(user),
+        slideshow_exists: slideshow_path(user).exists(),
         installed_version,
         wallpapers_folder: config.wallpapers.folder.clone(),
         wallpapers_folder_exists: Path::new(&config.wallpapers.folder).exists(),
-        autostart_exists: autostart.exists(),
-    })
+        wallpapers: wallpapers_inventory(&config.wallpapers.folder),
+        autostart: config.variety.autostart,
+        autostart_exists,
+        conf_matches,
+        running: variety_running(user),
+        change_interval_seconds: root_key_value(&conf, "change_interval")
+            .and_then(|seconds| seconds.parse().ok()),
+        configured_interval_seconds: change_interval_seconds(&config.variety),
+        privacy_settings,
+        drift: Vec::new(),
+    };
+    status.drift = status_drift(&config.variety, &status)?;
+    Ok(status)
 }
 
-fn installed_variety_version() -> Option<String> {
-    let output = Command::new("dpkg-query")
-        .args(["-W", "-f=${Version}", "variety"])
-        .output()
-        .ok()?;
-    if !output.status.success() {
+/// What differs from the config, in the order `status` lists it.
+fn status_drift(variety: &VarietyConfig, status: &VarietyStatus) -> anyhow::Result<Vec<String>> {
+    let mut drift = Vec::new();
+    if !status.wallpapers_folder_exists {
+        drift.push(format!(
+            "wallpapers folder {} does not exist",
+            status.wallpapers_folder
+        ));
+    } else if status
+        .wallpapers
+        .as_ref()
+        .is_some_and(|inventory| inventory.images == 0)
+    {
+        drift.push(format!(
+            "wallpapers folder {} has no supported images",
+            status.wallpapers_folder
+        ));
+    }
+    if status.mode == VarietyMode::GnomeSlideshow {
+        if !status.slideshow_exists {
+            drift.push("slideshow xml is missing".to_string());
+        }
+        return Ok(drift);
+    }
+
+    let Some(installed) = &status.installed_version else {
+        drift.push("variety is not installed".to_string());
+        return Ok(drift);
+    };
+    if !status.autostart_exists && status.autostart == VarietyAutostart::Systemd {
+        drift.push(format!("{VARIETY_UNIT} user unit is not enabled"));
+    } else if !status.autostart_exists {
+        drift.push("autostart entry is missing".to_string());
+    }
+    let pinned = variety.version.trim();
+    if !pinned.is_empty() && !pin::matches(installed, pinned) {
+        drift.push(format!(
+            "installed version {installed} does not match pinned version {pinned}"
+        ));
+    }
+    if variety.hold && !pin::held()?.iter().any(|held| held == "variety") {
+        drift.push("variety is not held".to_string());
+    }
+    if !status.conf_matches {
+        drift.push("variety.conf differs from what `configure variety` would write".to_string());
+    }
+    if status.change_interval_seconds != Some(status.configured_interval_seconds) {
+        drift.push(format!(
+            "change_interval is {} in variety.conf but config wants {}",
+            status
+                .change_interval_seconds
+                .map_or("unset".to_string(), |seconds| seconds.to_string()),
+            status.configured_interval_seconds
+        ));
+    }
+    for setting in &status.privacy_settings {
+        let wanted = conf_bool(setting.configured);
+        if !setting
+            .current
+            .as_deref()
+            .is_some_and(|current| current.eq_ignore_ascii_case(wanted))
+        {
+            drift.push(format!(
+                "{} is {} in variety.conf but config wants {wanted}",
+                setting.key,
+                setting.current.as_deref().unwrap_or("unset")
+            ));
+        }
+    }
+    Ok(drift)
+}
+
+/// Whether `user` runs Variety, judged by the command lines in /proc.
+fn variety_running(user: &UserContext) -> bool {
+    !variety_pids(user).is_empty()
+}
+
+/// The processes of `user` running Variety.
+fn variety_pids(user: &UserContext) -> Vec<u32> {
+    let uid = user.uid.unwrap_or_else(system::euid);
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse().ok()?;
+            let process = entry.path();
+            let runs = fs::metadata(&process).is_ok_and(|metadata| metadata.uid() == uid)
+                && fs::read(process.join("cmdline")).is_ok_and(|cmdline| runs_variety(&cmdline));
+            runs.then_some(pid)
+        })
+        .collect()
+}
+
+/// Whether a NUL-separated command line starts `variety`, directly or as the script
+/// of a Python interpreter.
+fn runs_variety(cmdline: &[u8]) -> bool {
+    let mut args = cmdline
+        .split(|byte| *byte == 0)
+        .map(|arg| Path::new(OsStr::from_bytes(arg)).file_name());
+    match args.next().flatten() {
+        Some(program) if program == "variety" => true,
+        Some(program) if program.to_string_lossy().starts_with("python") => args
+            .next()
+            .flatten()
+            .is_some_and(|script| script == "variety"),
+        _ => false,
+    }
+}
+
+/// The images in `folder`, or `None` when it is not a readable directory.
+fn wallpapers_inventory(folder: &str) -> Option<Inventory> {
+    let folder = Path::new(folder);
+    if !folder.is_dir() {
         return None;
     }
-    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    if version.is_empty() {
-        None
-    } else {
-        Some(version)
+    slideshow::inventory(folder, slideshow::INVENTORY_DEPTH)
+        .inspect_err(|err| warn!("{err:#}"))
+        .ok()
+}
+
+/// One line on the wallpapers folder, such as `12 images, 34.5 MB (also 3 heic)`.
+fn describe_wallpapers(status: &VarietyStatus) -> String {
+    let Some(inventory) = &status.wallpapers else {
+        return if status.wallpapers_folder_exists {
+            "not a readable folder".to_string()
+        } else {
+            "folder not found; check `wallpapers.folder`".to_string()
+        };
+    };
+    let mut line = format!(
+        "{} images, {:.1} MB",
+        inventory.images,
+        inventory.image_bytes as f64 / (1024.0 * 1024.0)
+    );
+    if inventory.unsupported_files() > 0 {
+        line.push_str(&format!(" (also {})", describe_unsupported(inventory)));
+    }
+    line
+}
+
+/// The unsupported files by extension, such as `3 heic, 1 without extension`.
+fn describe_unsupported(inventory: &Inventory) -> String {
+    inventory
+        .unsupported
+        .iter()
+        .map(|(extension, count)| match extension.as_str() {
+            "" => format!("{count} without extension"),
+            extension => format!("{count} {extension}"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn warn_about_wallpapers(status: &VarietyStatus) {
+    let folder = &status.wallpapers_folder;
+    // `configure_variety` already warned about a missing folder.
+    let Some(inventory) = &status.wallpapers else {
+        return;
+    };
+    if inventory.images == 0 {
+        warn!(
+            "wallpapers folder {folder} has no supported images ({})",
+            slideshow::IMAGE_EXTENSIONS.join(", ")
+        );
     }
+    if inventory.unsupported_files() > 0 {
+        warn!(
+            "wallpapers folder {folder} has files Variety will not show: {}",
+            describe_unsupported(inventory)
+        );
+    }
+}
+
+fn installed_variety_version() -> Option<String> {
+    crate::apt::package_version("variety")
 }
 
 fn print_status_report(status: &VarietyStatus) {
@@ -375,159 +1417,169 @@ fn print_status_report(status: &VarietyStatus) {
         .installed_version
         .as_deref()
         .unwrap_or("not installed");
-    println!("Variety status:");
-    println!("- installed version: {version}");
-    println!("- wallpapers folder: {}", status.wallpapers_folder);
-    println!(
-        "- wallpapers folder exists: {}",
-        status.wallpapers_folder_exists
-    );
-    println!("- autostart entry exists: {}", status.autostart_exists);
-}
-
-fn command_available(program: &str) -> bool {
-    Command::new("sh")
-        .args(["-c", &format!("command -v {program} >/dev/null 2>&1")])
-        .status()
-        .map(|status| status.success())
-        .unwrap_or(false)
-}
-
-fn current_euid() -> anyhow::Result<u32> {
-    let output = Command::new("id")
-        .arg("-u")
-        .output()
-        .context("failed to get current uid")?;
-    if !output.status.success() {
-        bail!("failed to determine current uid");
-    }
-
-    let uid = String::from_utf8_lossy(&output.stdout)
-        .trim()
-        .parse::<u32>()
-        .context("failed to parse uid")?;
-    Ok(uid)
-}
-
-#[derive(Debug, Clone)]
-struct UserContext {
-    home: PathBuf,
-    uid: Option<u32>,
-    gid: Option<u32>,
-}
-
-fn target_user_context() -> anyhow::Result<UserContext> {
-    let euid = current_euid()?;
-    if euid == 0 {
-        if let Some(sudo_user) = env::var_os("SUDO_USER") {
-            let sudo_user = sudo_user.to_string_lossy().trim().to_string();
-            if !sudo_user.is_empty() {
-                if let Some(entry) = passwd_entry_for_user(&sudo_user) {
-                    return Ok(UserContext {
-                        home: entry.home,
-                        uid: Some(entry.uid),
-                        gid: Some(entry.gid),
-                    });
-                }
-                return Ok(UserContext {
-                    home: PathBuf::from(format!("/home/{sudo_user}")),
-                    uid: None,
-                    gid: None,
-                });
-            }
+    say!("Variety status:");
+    say!("- mode: {}", status.mode);
+    say!("- desktop: {}", status.desktop.name());
+    if status.mode == VarietyMode::GnomeSlideshow {
+        say!("- slideshow xml exists: {}", status.slideshow_exists);
+    }
+    say!("- installed version: {version}");
+    say!("- wallpapers folder: {}", status.wallpapers_folder);
+    say!("- wallpapers: {}", describe_wallpapers(status));
+    if status.autostart == VarietyAutostart::Systemd {
+        say!("- {VARIETY_UNIT} enabled: {}", status.autostart_exists);
+    } else {
+        say!("- autostart entry exists: {}", status.autostart_exists);
+    }
+    say!("- variety running: {}", status.running);
+    if status.mode != VarietyMode::GnomeSlideshow {
+        say!(
+            "- change interval: {} (config wants {}s)",
+            status
+                .change_interval_seconds
+                .map_or("unset".to_string(), |seconds| format!("{seconds}s")),
+            status.configured_interval_seconds
+        );
+        say!("- variety.conf matches config: {}", status.conf_matches);
+    }
+    say!("- privacy settings (variety.conf / configured):");
+    for setting in &status.privacy_settings {
+        say!(
+            "  - {}: {} / {}",
+            setting.key,
+            setting.current.as_deref().unwrap_or("unset"),
+            conf_bool(setting.configured)
+        );
+    }
+    if status.drift.is_empty() {
+        say!("- drift: none");
+    } else {
+        say!("- drift:");
+        for drift in &status.drift {
+            say!("  - {drift}");
         }
     }
-
-    let home = env::var_os("HOME")
-        .map(PathBuf::from)
-        .context("HOME environment variable is not set")?;
-    Ok(UserContext {
-        home,
-        uid: None,
-        gid: None,
-    })
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct PasswdEntry {
-    uid: u32,
-    gid: u32,
-    home: PathBuf,
+fn reset_arg(command: clap::Command) -> clap::Command {
+    command.arg(
+        clap::Arg::new("reset")
+            .long("reset")
+            .action(clap::ArgAction::SetTrue)
+            .help("Regenerate variety.conf from the packaged default first"),
+    )
 }
 
-fn passwd_entry_for_user(user: &str) -> Option<PasswdEntry> {
-    let passwd = fs::read_to_string("/etc/passwd").ok()?;
-    passwd_entry_for_user_from_passwd(user, &passwd)
-}
+pub struct VarietyTarget;
 
-fn passwd_entry_for_user_from_passwd(user: &str, passwd: &str) -> Option<PasswdEntry> {
-    for line in passwd.lines() {
-        if line.starts_with('#') || line.trim().is_empty() {
-            continue;
-        }
+impl Target for VarietyTarget {
+    fn name(&self) -> &'static str {
+        "variety"
+    }
 
-        let mut parts = line.split(':');
-        let name = parts.next()?;
-        if name != user {
-            continue;
+    fn description(&self) -> &'static str {
+        "Variety wallpaper rotator"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(
+            action,
+            Action::Install | Action::Uninstall | Action::Configure | Action::Status
+        )
+    }
+
+    fn args(&self, action: Action, command: clap::Command) -> clap::Command {
+        match action {
+            Action::Install => reset_arg(command)
+                .arg(
+                    clap::Arg::new("all-users")
+                        .long("all-users")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Configure every local user with a home directory (needs root)"),
+                )
+                .arg(
+                    clap::Arg::new("with-tray")
+                        .long("with-tray")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Also install the GNOME AppIndicator extension for the tray icon"),
+                ),
+            Action::Configure => reset_arg(command),
+            Action::Uninstall => command.arg(
+                clap::Arg::new("purge-config")
+                    .long("purge-config")
+                    .action(clap::ArgAction::SetTrue)
+                    .help("Also delete ~/.config/variety, including downloaded wallpapers"),
+            ),
+            _ => command,
         }
+    }
 
-        let _password = parts.next()?;
-        let uid = parts.next()?.parse::<u32>().ok()?;
-        let gid = parts.next()?.parse::<u32>().ok()?;
-        let _gecos = parts.next()?;
-        let home = PathBuf::from(parts.next()?);
-        return Some(PasswdEntry { uid, gid, home });
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        if !ctx.flag("all-users") {
+            return run(ctx.config()?, ctx.flag("reset"), ctx.flag("with-tray"));
+        }
+        let outcomes = run_all_users(ctx.config()?, ctx.flag("reset"), ctx.flag("with-tray"))?;
+        print_user_outcomes(&outcomes);
+        ctx.report(&outcomes)?;
+        let failed = outcomes.iter().filter(|outcome| !outcome.ok).count();
+        if failed > 0 {
+            bail!(
+                "failed to configure variety for {failed} of {} users",
+                outcomes.len()
+            );
+        }
+        Ok(())
     }
 
-    None
-}
+    fn uninstall(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall(ctx.flag("purge-config"))
+    }
 
-fn ensure_owned_writable_dir(path: &Path, user: &UserContext) -> anyhow::Result<()> {
-    set_mode(path, 0o755)?;
-    if let (Some(uid), Some(gid)) = (user.uid, user.gid) {
-        chown_path(path, uid, gid)?;
+    fn configure(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        configure(ctx.config()?, ctx.flag("reset"))
     }
-    Ok(())
-}
 
-fn ensure_owned_writable_file(path: &Path, user: &UserContext) -> anyhow::Result<()> {
-    set_mode(path, 0o644)?;
-    if let (Some(uid), Some(gid)) = (user.uid, user.gid) {
-        chown_path(path, uid, gid)?;
+    fn status(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let status = collect_status(ctx.config()?)?;
+        if !output::is_json() {
+            print_status_report(&status);
+        }
+        ctx.report(&status)?;
+        if !status.drift.is_empty() {
+            return Err(output::Drift.into());
+        }
+        Ok(())
     }
-    Ok(())
-}
 
-fn set_mode(path: &Path, mode: u32) -> anyhow::Result<()> {
-    let mut perms = fs::metadata(path)
-        .with_context(|| format!("failed to read metadata for {}", path.display()))?
-        .permissions();
-    perms.set_mode(mode);
-    fs::set_permissions(path, perms)
-        .with_context(|| format!("failed to set permissions on {}", path.display()))?;
-    Ok(())
-}
+    fn version(&self) -> Option<String> {
+        installed_variety_version()
+    }
 
-fn chown_path(path: &Path, uid: u32, gid: u32) -> anyhow::Result<()> {
-    let status = Command::new("chown")
-        .arg(format!("{uid}:{gid}"))
-        .arg(path)
-        .status()
-        .with_context(|| format!("failed to start chown for {}", path.display()))?;
-    if !status.success() {
-        bail!(
-            "failed to set ownership on {} to {uid}:{gid}",
-            path.display()
-        );
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(&["variety"])
     }
-    Ok(())
-}
 
-fn is_gnome_desktop() -> bool {
-    env::var("XDG_CURRENT_DESKTOP")
-        .map(|v| v.contains("GNOME"))
-        .unwrap_or(false)
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let status = collect_status(ctx.config()?)?;
+        let installed = if status.mode == VarietyMode::GnomeSlideshow {
+            status.slideshow_exists
+        } else {
+            status.installed_version.is_some()
+        };
+        if !installed {
+            return Ok(super::TargetStatus::new(self.name(), None));
+        }
+
+        if status.mode == VarietyMode::GnomeSlideshow {
+            let mut summary = super::TargetStatus::new(self.name(), None).with_drift(status.drift);
+            summary.installed = true;
+            return Ok(summary);
+        }
+        Ok(
+            super::TargetStatus::new(self.name(), status.installed_version)
+                .with_drift(status.drift),
+        )
+    }
 }
 
 #[cfg(test)]
@@ -537,12 +1589,179 @@ mod tests {
     #[test]
     fn configure_variety_conf_sets_expected_keys() {
         let existing = "change_interval = 300\ninternet_enabled = True\n[sources]\nsrc1 = True|flickr|foo\n[filters]\nfilter1 = False|Keep original|\n";
-        let updated = configure_variety_conf_text(existing, "/pics", 10);
+        let variety = VarietyConfig {
+            interval_minutes: 10,
+            ..VarietyConfig::default()
+        };
+        let updated = configure_variety_conf_text(existing, "/pics", &variety);
         assert!(updated.contains("change_interval = 600"));
         assert!(updated.contains("internet_enabled = False"));
         assert!(updated.contains("wallpaper_auto_rotate = True"));
         assert!(updated.contains("[sources]\nsrc1 = True|folder|/pics\n"));
         assert!(updated.contains("[filters]"));
+        assert!(updated.contains("quota_enabled = True\nquota_size = 500"));
+    }
+
+    #[test]
+    fn systemd_unit_starts_variety_with_the_graphical_session() {
+        let unit = render_systemd_unit(Path::new("/usr/bin/variety"));
+        assert!(unit.contains("\n[Service]\nExecStart=/usr/bin/variety\nRestart=on-failure\n"));
+        assert!(unit.contains("PartOf=graphical-session.target\n"));
+        assert!(unit.ends_with("[Install]\nWantedBy=graphical-session.target\n"));
+    }
+
+    #[test]
+    fn reset_drops_settings_made_outside_debkit() {
+        let existing = "icon = Dark\n[sources]\n";
+        let variety = VarietyConfig::default();
+
+        let kept = variety_conf_base(Some(existing), false);
+        assert!(configure_variety_conf_text(&kept, "/pics", &variety).contains("icon = Dark"));
+        let reset = configure_variety_conf_text(
+            &variety_conf_base(Some(existing), true),
+            "/pics",
+            &variety,
+        );
+        assert!(!reset.contains("icon = Dark"));
+        assert!(reset.contains("src1 = True|folder|/pics"));
+    }
+
+    #[test]
+    fn restarts_variety_in_its_graphical_session() {
+        assert_eq!(
+            session_env(b"HOME=/home/me\0DISPLAY=:0\0WAYLAND_DISPLAY=wayland-0\0PATH=/usr/bin\0"),
+            [
+                ("DISPLAY".to_string(), ":0".to_string()),
+                ("WAYLAND_DISPLAY".to_string(), "wayland-0".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn status_reports_conf_and_interval_drift() {
+        assert!(runs_variety(b"/usr/bin/python3\0/usr/bin/variety\0"));
+        assert!(runs_variety(b"variety\0--profile\0x\0"));
+        assert!(!runs_variety(b"man\0variety\0"));
+        assert!(!runs_variety(b""));
+
+        let variety = VarietyConfig::default();
+        let mut status = VarietyStatus {
+            mode: VarietyMode::Variety,
+            desktop: Environment::Gnome,
+            slideshow_exists: false,
+            installed_version: Some("0.8.10-1".to_string()),
+            wallpapers_folder: "/pics".to_string(),
+            wallpapers_folder_exists: true,
+            wallpapers: Some(Inventory {
+                images: 3,
+                ..Inventory::default()
+            }),
+            autostart: VarietyAutostart::Desktop,
+            autostart_exists: true,
+            conf_matches: true,
+            running: false,
+            change_interval_seconds: Some(600),
+            configured_interval_seconds: change_interval_seconds(&variety),
+            privacy_settings: Vec::new(),
+            drift: Vec::new(),
+        };
+        assert!(status_drift(&variety, &status).unwrap().is_empty());
+
+        status.conf_matches = false;
+        status.change_interval_seconds = Some(300);
+        assert_eq!(
+            status_drift(&variety, &status).unwrap(),
+            [
+                "variety.conf differs from what `configure variety` would write",
+                "change_interval is 300 in variety.conf but config wants 600",
+            ]
+        );
+        status.installed_version = None;
+        assert_eq!(
+            status_drift(&variety, &status).unwrap(),
+            ["variety is not installed"]
+        );
+
+        status.wallpapers = Some(Inventory {
+            unsupported: [("heic".to_string(), 2), (String::new(), 1)].into(),
+            ..Inventory::default()
+        });
+        assert_eq!(
+            status_drift(&variety, &status).unwrap(),
+            [
+                "wallpapers folder /pics has no supported images",
+                "variety is not installed"
+            ]
+        );
+        assert_eq!(
+            describe_wallpapers(&status),
+            "0 images, 0.0 MB (also 1 without extension, 2 heic)"
+        );
+    }
+
+    #[test]
+    fn finds_plasma_wallpapers_that_are_not_zoomed() {
+        let appletsrc = "[Containments][1][Wallpaper][org.kde.image][General]\nFillMode=1\nImage=file:///a.jpg\n\n[Containments][2][Wallpaper][org.kde.image][General]\nFillMode=2\n\n[Containments][3][General]\nFillMode=0\n";
+        assert_eq!(unzoomed_kde_containments(appletsrc), ["1"]);
+    }
+
+    #[test]
+    fn configure_variety_conf_writes_online_sources() {
+        let variety = VarietyConfig {
+            internet_enabled: true,
+            download_folder: "/srv/walls".to_string(),
+            quota_mb: 0,
+            online_sources: vec!["unsplash".to_string(), "wallhaven:nature".to_string()],
+            ..VarietyConfig::default()
+        };
+        let updated =
+            configure_variety_conf_text("quota_size = 200\n[sources]\n", "/pics", &variety);
+        assert!(updated.contains("internet_enabled = True"));
+        assert!(updated.contains("download_folder = /srv/walls"));
+        assert!(updated.contains("quota_enabled = False"));
+        assert!(updated.contains(
+            "[sources]\nsrc1 = True|folder|/pics\nsrc2 = True|unsplash|High-resolution photos from Unsplash.com\nsrc3 = True|wallhaven|nature\n"
+        ));
+    }
+
+    #[test]
+    fn configure_variety_conf_writes_privacy_toggles() {
+        let variety = VarietyConfig {
+            safe_mode: true,
+            stats_notice_shown: false,
+            ..VarietyConfig::default()
+        };
+        let updated =
+            configure_variety_conf_text("smart_enabled = True\n[sources]\n", "/pics", &variety);
+        assert!(updated.contains("safe_mode = True"));
+        assert!(updated.contains("smart_enabled = False"));
+        assert!(updated.contains("sync_enabled = False"));
+        assert!(updated.contains("stats_enabled = False"));
+        assert!(updated.contains("stats_notice_shown = False"));
+        assert_eq!(
+            root_key_value(&updated, "safe_mode").as_deref(),
+            Some("True")
+        );
+        assert_eq!(root_key_value(&updated, "src1"), None);
+    }
+
+    #[test]
+    fn configure_variety_conf_enforces_filters_clock_and_quotes() {
+        let variety = VarietyConfig {
+            filters: vec!["Keep original".to_string(), "grayscale".to_string()],
+            ..VarietyConfig::default()
+        };
+        let existing = "quotes_enabled = True\n[sources]\n[filters]\nfilter1 = False|Keep original|\nfilter2 = True|Heavy blur|-blur 120x40\n[quotes_sources]\nq1 = True|goodreads|\n";
+        let updated = configure_variety_conf_text(existing, "/pics", &variety);
+        assert!(updated.contains("quotes_enabled = False"));
+        assert!(updated.contains("clock_enabled = False"));
+        assert!(updated.contains(
+            "[filters]\nfilter1 = True|Keep original|\nfilter2 = False|Heavy blur|-blur 120x40\nfilter3 = True|Grayscale|-type Grayscale\n[quotes_sources]"
+        ));
+        assert_eq!(
+            configure_variety_conf_text(&updated, "/pics", &variety),
+            updated
+        );
     }
 
     #[test]
@@ -562,16 +1781,15 @@ mod tests {
     }
 
     #[test]
-    fn parses_passwd_entry() {
-        let passwd = "root:x:0:0:root:/root:/bin/bash\nuser1:x:1000:1000::/home/user1:/bin/bash\n";
-        assert_eq!(
-            passwd_entry_for_user_from_passwd("user1", passwd),
-            Some(PasswdEntry {
-                uid: 1000,
-                gid: 1000,
-                home: PathBuf::from("/home/user1")
-            })
-        );
-        assert_eq!(passwd_entry_for_user_from_passwd("missing", passwd), None);
+    fn ensure_autostart_desktop_rejects_invalid_entry() {
+        let dir = std::env::temp_dir().join(format!("debkit-autostart-{}", std::process::id()));
+        let path = dir.join("variety.desktop");
+        fs::create_dir_all(&dir).unwrap();
+        let broken = "[Desktop Entry]\nType=Application\nName=Variety\nExec=variety \"--profile\n";
+        fs::write(&path, broken).unwrap();
+
+        assert!(ensure_autostart_desktop(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), broken);
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
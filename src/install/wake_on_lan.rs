@@ -4,7 +4,11 @@ use std::process::Command;
 
 use anyhow::{Context, bail};
 
+use super::{Action, Target};
 use crate::config::{DebkitConfig, WakeOnLanConfig};
+use crate::exec;
+use crate::output::say;
+use crate::system;
 
 const WAKE_INFO_DIR: &str = "/var/lib/debkit/wake-on-lan";
 const ETHTOOL_SERVICE_PATH: &str = "/etc/systemd/system/debkit-wol@.service";
@@ -76,8 +80,8 @@ enum SelectedBackend {
 
 pub fn run(config: &DebkitConfig) -> anyhow::Result<()> {
     if !config.wake_on_lan.enabled {
-        println!("Wake-on-LAN is disabled in config (`wake_on_lan.enabled = false`).");
-        println!("Set it to true before running `debkit install wake-on-lan`.");
+        say!("Wake-on-LAN is disabled in config (`wake_on_lan.enabled = false`).");
+        say!("Set it to true before running `debkit install wake-on-lan`.");
         print_status(config)?;
         return Ok(());
     }
@@ -113,37 +117,37 @@ pub fn dry_run(config: &DebkitConfig) -> anyhow::Result<()> {
     let interfaces = selected_interfaces(&report, &config.wake_on_lan)?;
     let backend = select_backend(&report, &interfaces, request);
 
-    println!("Wake-on-LAN dry run");
-    println!("interfaces selected: {}", interfaces.join(", "));
-    println!("backend requested: {}", request.as_str());
-    println!("backend that would be selected: {}", backend.as_str());
-    println!(
+    say!("Wake-on-LAN dry run");
+    say!("interfaces selected: {}", interfaces.join(", "));
+    say!("backend requested: {}", request.as_str());
+    say!("backend that would be selected: {}", backend.as_str());
+    say!(
         "ethtool would be installed: {}",
         yes_no(backend == SelectedBackend::Ethtool && !report.ethtool_installed)
     );
     for iface_name in &interfaces {
         let iface = interface_by_name(&report, iface_name)?;
-        println!(
+        say!(
             "NetworkManager connection for {iface_name}: {}",
             iface.nm_connection.as_deref().unwrap_or("n/a")
         );
         if backend == SelectedBackend::NetworkManager {
             if let Some(connection) = iface.nm_connection.as_deref() {
-                println!(
+                say!(
                     "would run: nmcli connection modify \"{connection}\" 802-3-ethernet.wake-on-lan magic"
                 );
             } else {
-                println!("would fail: NetworkManager has no active connection for {iface_name}");
+                say!("would fail: NetworkManager has no active connection for {iface_name}");
             }
         } else {
-            println!("would run: ethtool -s {iface_name} wol g");
-            println!("would write: {ETHTOOL_SERVICE_PATH}");
-            println!("would enable: debkit-wol@{iface_name}.service");
+            say!("would run: ethtool -s {iface_name} wol g");
+            say!("would write: {ETHTOOL_SERVICE_PATH}");
+            say!("would enable: debkit-wol@{iface_name}.service");
         }
     }
-    println!("wake-info files would be written under: {WAKE_INFO_DIR}");
+    say!("wake-info files would be written under: {WAKE_INFO_DIR}");
     for info in build_wake_infos(&report, &interfaces, request, backend, config)? {
-        println!("TimeVault wake command: wakeonlan {}", info.mac_address);
+        say!("TimeVault wake command: wakeonlan {}", info.mac_address);
     }
     Ok(())
 }
@@ -171,8 +175,8 @@ fn collect_report(config: &DebkitConfig) -> anyhow::Result<HostReport> {
         .unwrap_or_else(|_| "unknown".to_string())
         .trim()
         .to_string();
-    let ethtool_installed = command_available("ethtool");
-    let network_manager_installed = command_available("nmcli");
+    let ethtool_installed = system::command_available("ethtool");
+    let network_manager_installed = system::command_available("nmcli");
     let network_manager_running = network_manager_installed && network_manager_running();
     let interfaces = collect_interfaces(ethtool_installed, network_manager_installed)?;
 
@@ -199,8 +203,8 @@ fn collect_report(config: &DebkitConfig) -> anyhow::Result<HostReport> {
         network_manager_running,
         default_route_interface: default_route_interface(),
         ethtool_installed,
-        wakeonlan_installed: command_available("wakeonlan"),
-        etherwake_installed: command_available("etherwake"),
+        wakeonlan_installed: system::command_available("wakeonlan"),
+        etherwake_installed: system::command_available("etherwake"),
         interfaces,
         warnings,
     })
@@ -274,14 +278,13 @@ fn selected_interfaces(
         return Ok(config.interfaces.clone());
     }
 
-    if let Some(default_iface) = &report.default_route_interface {
-        if report
+    if let Some(default_iface) = &report.default_route_interface
+        && report
             .interfaces
             .iter()
             .any(|iface| iface.name == *default_iface && iface.kind == InterfaceKind::Wired)
-        {
-            return Ok(vec![default_iface.clone()]);
-        }
+    {
+        return Ok(vec![default_iface.clone()]);
     }
 
     let wired = report
@@ -367,7 +370,7 @@ fn configure_network_manager(report: &HostReport, interfaces: &[String]) -> anyh
         })?;
 
         if iface.nm_wake_on_lan.as_deref() == Some("magic") {
-            println!(
+            say!(
                 "NetworkManager connection `{connection}` already configured for Wake-on-LAN magic."
             );
         } else {
@@ -384,7 +387,7 @@ fn configure_network_manager(report: &HostReport, interfaces: &[String]) -> anyh
             .with_context(|| {
                 format!("failed to configure NetworkManager Wake-on-LAN for `{iface_name}`")
             })?;
-            println!(
+            say!(
                 "Configured NetworkManager connection `{connection}` for Wake-on-LAN magic. Reconnect or reboot if the setting is not immediately reflected."
             );
         }
@@ -399,12 +402,12 @@ fn configure_ethtool(report: &HostReport, interfaces: &[String]) -> anyhow::Resu
 
     for iface_name in interfaces {
         let iface = interface_by_name(report, iface_name)?;
-        if let Some(supports) = &iface.supports_wake_on {
-            if !supports.split_whitespace().any(|mode| mode == "g") {
-                bail!(
-                    "`{iface_name}` does not report magic-packet support (`Supports Wake-on: {supports}`)"
-                );
-            }
+        if let Some(supports) = &iface.supports_wake_on
+            && !supports.split_whitespace().any(|mode| mode == "g")
+        {
+            bail!(
+                "`{iface_name}` does not report magic-packet support (`Supports Wake-on: {supports}`)"
+            );
         }
         run_privileged("ethtool", &["-s", iface_name, "wol", "g"])
             .with_context(|| format!("failed to enable runtime Wake-on-LAN on `{iface_name}`"))?;
@@ -421,7 +424,7 @@ fn configure_ethtool(report: &HostReport, interfaces: &[String]) -> anyhow::Resu
         )?;
     }
 
-    let after = collect_interfaces(true, command_available("nmcli"))?;
+    let after = collect_interfaces(true, system::command_available("nmcli"))?;
     for iface_name in interfaces {
         let wake_on = after
             .iter()
@@ -510,31 +513,31 @@ fn print_report(
     selected_interfaces: Option<&[String]>,
     config: &DebkitConfig,
 ) {
-    println!("Wake-on-LAN report");
-    println!("hostname: {}", report.hostname);
-    println!("OS version: {}", report.os_version);
-    println!("requested backend: {}", request.as_str());
-    println!(
+    say!("Wake-on-LAN report");
+    say!("hostname: {}", report.hostname);
+    say!("OS version: {}", report.os_version);
+    say!("requested backend: {}", request.as_str());
+    say!(
         "selected backend: {}",
         selected_backend
             .map(|backend| backend.as_str())
             .unwrap_or("not selected")
     );
-    println!(
+    say!(
         "NetworkManager installed/running: {}/{}",
         yes_no(report.network_manager_installed),
         yes_no(report.network_manager_running)
     );
-    println!("ethtool installed: {}", yes_no(report.ethtool_installed));
-    println!(
+    say!("ethtool installed: {}", yes_no(report.ethtool_installed));
+    say!(
         "wakeonlan installed: {}",
         yes_no(report.wakeonlan_installed)
     );
-    println!(
+    say!(
         "etherwake installed: {}",
         yes_no(report.etherwake_installed)
     );
-    println!(
+    say!(
         "default route interface: {}",
         report
             .default_route_interface
@@ -542,36 +545,36 @@ fn print_report(
             .unwrap_or("unknown")
     );
 
-    println!("\nInterfaces:");
+    say!("\nInterfaces:");
     for iface in &report.interfaces {
         let selected = selected_interfaces
             .map(|items| items.iter().any(|name| name == &iface.name))
             .unwrap_or(false);
-        println!(
+        say!(
             "- {} ({}){}",
             iface.name,
             iface.kind.as_str(),
             if selected { " [target]" } else { "" }
         );
-        println!("  MAC address: {}", opt(&iface.mac_address));
-        println!(
+        say!("  MAC address: {}", opt(&iface.mac_address));
+        say!(
             "  permanent MAC address: {}",
             opt(&iface.permanent_mac_address)
         );
-        println!("  NetworkManager managed: {}", yes_no(iface.nm_managed));
-        println!(
+        say!("  NetworkManager managed: {}", yes_no(iface.nm_managed));
+        say!(
             "  active NetworkManager connection: {}",
             opt(&iface.nm_connection)
         );
-        println!(
+        say!(
             "  NetworkManager wake-on-lan: {}",
             opt(&iface.nm_wake_on_lan)
         );
-        println!("  Supports Wake-on: {}", opt(&iface.supports_wake_on));
-        println!("  ethtool Wake-on: {}", opt(&iface.ethtool_wake_on));
-        println!("  link detected: {}", opt(&iface.link_detected));
+        say!("  Supports Wake-on: {}", opt(&iface.supports_wake_on));
+        say!("  ethtool Wake-on: {}", opt(&iface.ethtool_wake_on));
+        say!("  link detected: {}", opt(&iface.link_detected));
         if iface.kind == InterfaceKind::Wireless {
-            println!("  warning: ignored for standard Wake-on-LAN");
+            say!("  warning: ignored for standard Wake-on-LAN");
         }
     }
 
@@ -584,38 +587,38 @@ fn print_report(
             config,
         ) {
             Ok(infos) => {
-                println!("\nTimeVault wake information:");
+                say!("\nTimeVault wake information:");
                 for info in infos {
-                    println!("- target hostname: {}", info.hostname);
-                    println!("  target wired interface name: {}", info.interface);
-                    println!("  target MAC address: {}", info.mac_address);
-                    println!("  wake command: wakeonlan {}", info.mac_address);
-                    println!("  optional etherwake: {}", etherwake_command(&info));
+                    say!("- target hostname: {}", info.hostname);
+                    say!("  target wired interface name: {}", info.interface);
+                    say!("  target MAC address: {}", info.mac_address);
+                    say!("  wake command: wakeonlan {}", info.mac_address);
+                    say!("  optional etherwake: {}", etherwake_command(&info));
                 }
             }
-            Err(err) => println!("\nTimeVault wake information unavailable: {err:#}"),
+            Err(err) => say!("\nTimeVault wake information unavailable: {err:#}"),
         }
     }
 
-    println!("\nWarnings:");
+    say!("\nWarnings:");
     for warning in &report.warnings {
-        println!("- {warning}");
+        say!("- {warning}");
     }
 }
 
 fn print_configured_summary(infos: &[WakeInfo]) {
     for info in infos {
-        println!("\nWake-on-LAN configured:");
-        println!("  Hostname: {}", info.hostname);
-        println!("  Interface: {}", info.interface);
-        println!("  MAC: {}", info.mac_address);
-        println!("  Requested backend: {}", info.requested_backend.as_str());
-        println!("  Selected backend: {}", info.selected_backend.as_str());
-        println!(
+        say!("\nWake-on-LAN configured:");
+        say!("  Hostname: {}", info.hostname);
+        say!("  Interface: {}", info.interface);
+        say!("  MAC: {}", info.mac_address);
+        say!("  Requested backend: {}", info.requested_backend.as_str());
+        say!("  Selected backend: {}", info.selected_backend.as_str());
+        say!(
             "  NetworkManager connection: {}",
             info.network_manager_connection.as_deref().unwrap_or("n/a")
         );
-        println!(
+        say!(
             "  ethtool verification: {}",
             match info.ethtool_wake_on.as_deref() {
                 Some("g") => "Wake-on: g",
@@ -624,7 +627,7 @@ fn print_configured_summary(infos: &[WakeInfo]) {
                 None => "skipped",
             }
         );
-        println!(
+        say!(
             "\nFrom {}, wake it with:\n  wakeonlan {}\n\nOr:\n  {}",
             info.wake_from,
             info.mac_address,
@@ -733,15 +736,16 @@ fn network_manager_running() -> bool {
 }
 
 fn systemctl_is_active(unit: &str) -> bool {
-    Command::new("systemctl")
-        .arg("is-active")
-        .arg("--quiet")
-        .arg(unit)
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .status()
-        .map(|status| status.success())
-        .unwrap_or(false)
+    exec::probe(
+        Command::new("systemctl")
+            .arg("is-active")
+            .arg("--quiet")
+            .arg(unit)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null()),
+    )
+    .map(|status| status.success())
+    .unwrap_or(false)
 }
 
 fn default_route_interface() -> Option<String> {
@@ -817,42 +821,16 @@ fn ethtool_value(raw: &str, key: &str) -> Option<String> {
 }
 
 fn ensure_ethtool_installed() -> anyhow::Result<()> {
-    if command_available("ethtool") {
+    if system::command_available("ethtool") {
         return Ok(());
     }
-    run_apt_command(&["update"])?;
-    run_apt_command(&["install", "-y", "ethtool"])?;
-    Ok(())
-}
-
-fn run_apt_command(args: &[&str]) -> anyhow::Result<()> {
-    let euid = current_euid()?;
-    let mut command;
-    if euid == 0 {
-        command = Command::new("apt");
-        command.args(args);
-    } else if command_available("sudo") {
-        command = Command::new("sudo");
-        command.arg("apt").args(args);
-    } else {
-        bail!(
-            "installing packages requires root privileges; run as root or install `sudo` and retry"
-        );
-    }
-
-    let status = command
-        .env("DEBIAN_FRONTEND", "noninteractive")
-        .status()
-        .context("failed to launch apt")?;
-    if !status.success() {
-        bail!("apt {} failed with status {}", args.join(" "), status);
-    }
-    Ok(())
+    crate::apt::install(&["ethtool"])
 }
 
 fn ensure_root_dir(path: &Path) -> anyhow::Result<()> {
-    if current_euid()? == 0 {
-        fs::create_dir_all(path).with_context(|| format!("failed to create {}", path.display()))?;
+    if system::is_root() {
+        exec::create_dir_all(path)
+            .with_context(|| format!("failed to create {}", path.display()))?;
         return Ok(());
     }
     run_privileged("mkdir", &["-p", path.to_string_lossy().as_ref()])
@@ -867,34 +845,28 @@ fn ensure_root_file(path: &Path, content: &str) -> anyhow::Result<bool> {
         }
     }
 
-    if current_euid()? == 0 {
-        fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))?;
+    if system::is_root() {
+        exec::write(path, content)
+            .with_context(|| format!("failed to write {}", path.display()))?;
         return Ok(true);
     }
 
-    if !command_available("sudo") {
+    if !system::command_available("sudo") {
         bail!(
             "writing {} requires root privileges; run as root or install `sudo` and retry",
             path.display()
         );
     }
 
-    let status = Command::new("sudo")
-        .arg("tee")
-        .arg(path)
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::null())
-        .spawn()
-        .and_then(|mut child| {
-            use std::io::Write;
-            child
-                .stdin
-                .as_mut()
-                .expect("piped stdin")
-                .write_all(content.as_bytes())?;
-            child.wait()
-        })
-        .with_context(|| format!("failed to write {}", path.display()))?;
+    let status = exec::write_via(
+        Command::new("sudo")
+            .arg("tee")
+            .arg(path)
+            .stdout(std::process::Stdio::null()),
+        path,
+        content.as_bytes(),
+    )
+    .with_context(|| format!("failed to write {}", path.display()))?;
     if !status.success() {
         bail!("sudo tee {} failed with status {}", path.display(), status);
     }
@@ -902,21 +874,19 @@ fn ensure_root_file(path: &Path, content: &str) -> anyhow::Result<bool> {
 }
 
 fn run_privileged(program: &str, args: &[&str]) -> anyhow::Result<()> {
-    let euid = current_euid()?;
     let mut command;
-    if euid == 0 {
+    if system::is_root() {
         command = Command::new(program);
         command.args(args);
-    } else if command_available("sudo") {
+    } else if system::command_available("sudo") {
         command = Command::new("sudo");
         command.arg(program).args(args);
     } else {
         bail!("`{program}` requires root privileges; run as root or install `sudo` and retry");
     }
 
-    let status = command
-        .status()
-        .with_context(|| format!("failed to launch {program}"))?;
+    let status =
+        exec::status(&mut command).with_context(|| format!("failed to launch {program}"))?;
     if !status.success() {
         bail!(
             "{} {} failed with status {}",
@@ -929,11 +899,12 @@ fn run_privileged(program: &str, args: &[&str]) -> anyhow::Result<()> {
 }
 
 fn capture(program: &str, args: &[&str]) -> anyhow::Result<String> {
-    let output = Command::new(program)
-        .args(args)
-        .stderr(std::process::Stdio::null())
-        .output()
-        .with_context(|| format!("failed to run {program}"))?;
+    let output = exec::query(
+        Command::new(program)
+            .args(args)
+            .stderr(std::process::Stdio::null()),
+    )
+    .with_context(|| format!("failed to run {program}"))?;
     if !output.status.success() {
         bail!(
             "{} {} failed with status {}",
@@ -945,38 +916,12 @@ fn capture(program: &str, args: &[&str]) -> anyhow::Result<String> {
     Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
-fn command_available(name: &str) -> bool {
-    Command::new("sh")
-        .arg("-c")
-        .arg(format!("command -v {name} >/dev/null 2>&1"))
-        .status()
-        .map(|status| status.success())
-        .unwrap_or(false)
-}
-
 fn read_trimmed(path: PathBuf) -> anyhow::Result<String> {
     fs::read_to_string(&path)
         .with_context(|| format!("failed to read {}", path.display()))
         .map(|value| value.trim().to_string())
 }
 
-fn current_euid() -> anyhow::Result<u32> {
-    let status = fs::read_to_string("/proc/self/status").context("failed to read effective uid")?;
-    for line in status.lines() {
-        if let Some(rest) = line.strip_prefix("Uid:") {
-            let mut fields = rest.split_whitespace();
-            let _real = fields.next();
-            let Some(effective) = fields.next() else {
-                break;
-            };
-            return effective
-                .parse::<u32>()
-                .context("failed to parse effective uid");
-        }
-    }
-    bail!("failed to determine effective uid")
-}
-
 fn json_string(raw: &str) -> String {
     format!("\"{}\"", raw.replace('\\', "\\\\").replace('"', "\\\""))
 }
@@ -1022,6 +967,65 @@ impl InterfaceKind {
     }
 }
 
+pub struct WakeOnLanTarget;
+
+impl Target for WakeOnLanTarget {
+    fn name(&self) -> &'static str {
+        "wake-on-lan"
+    }
+
+    fn description(&self) -> &'static str {
+        "Inspect and enable wired Ethernet Wake-on-LAN"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["wake_on_lan", "wol"]
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Status)
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let config = ctx.config()?;
+        if exec::is_dry_run() {
+            dry_run(config)
+        } else {
+            run(config)
+        }
+    }
+
+    fn status(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        print_status(ctx.config()?)
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let config = ctx.config()?;
+        if !config.wake_on_lan.enabled {
+            return Ok(super::TargetStatus::disabled(self.name()));
+        }
+
+        let report = collect_report(config)?;
+        let mut drift = Vec::new();
+        for name in selected_interfaces(&report, &config.wake_on_lan)? {
+            let iface = interface_by_name(&report, &name)?;
+            let enabled = iface.nm_wake_on_lan.as_deref() == Some("magic")
+                || iface
+                    .ethtool_wake_on
+                    .as_deref()
+                    .is_some_and(|value| value.contains('g'));
+            if !enabled {
+                drift.push(format!(
+                    "Wake-on-LAN magic packets are not enabled on {name}"
+                ));
+            }
+        }
+        let mut status = super::TargetStatus::new(self.name(), None).with_drift(drift);
+        status.installed = status.configured == Some(true);
+        Ok(status)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,297 @@
+//! `install wallpapers-sync`: a local copy of a wallpaper collection kept elsewhere.
+//!
+//! `wallpapers.source` is mirrored into `wallpapers.folder` with rsync, so Variety
+//! reads local files even when the share behind the source is slow or not mounted.
+//! A git repository is cloned, or pulled, into `~/.cache/debkit/wallpapers-git` first
+//! and mirrored from there. `include`, `exclude` and `max_file_mb` pick the files, and
+//! `max_total_mb` refuses a sync that would copy more than that. Files that left the
+//! source, or no longer match the filters, are deleted from the copy.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+
+use super::user::{UserContext, command_as, ensure_owned_writable_dir, target_user_context};
+use super::{Action, Target, variety};
+use crate::config::{DebkitConfig, WallpapersConfig};
+use crate::output::say;
+use crate::{apt, exec, network, offline, system};
+
+pub fn run(config: &DebkitConfig) -> anyhow::Result<()> {
+    let wallpapers = &config.wallpapers;
+    if wallpapers.source.trim().is_empty() {
+        bail!("set `wallpapers.source` to the collection to mirror into `wallpapers.folder`");
+    }
+    let user = target_user_context()?;
+    let git = is_git_source(&wallpapers.source);
+    apt::install_missing(if git { &["rsync", "git"] } else { &["rsync"] })?;
+
+    let source = if git {
+        format!("{}/", checkout(&user, &wallpapers.source)?.display())
+    } else {
+        rsync_source(wallpapers)?
+    };
+    let folder = Path::new(&wallpapers.folder);
+    exec::create_dir_all(folder)
+        .with_context(|| format!("failed to create {}", folder.display()))?;
+    ensure_owned_writable_dir(folder, &user)?;
+    let destination = format!("{}/", folder.display());
+    let args = rsync_args(wallpapers);
+
+    // A dry run may have neither rsync nor the git clone yet to measure with.
+    if !exec::is_dry_run() || (!git && system::command_available("rsync")) {
+        check_total_size(&user, &args, &source, &destination, wallpapers.max_total_mb)?;
+    }
+    let step = format!("Mirroring {} into {}", wallpapers.source, folder.display());
+    let status = exec::status_step(
+        command_as(&user, "rsync")?
+            .args(&args)
+            .args([&source, &destination]),
+        &step,
+    )
+    .context("failed to start `rsync`")?;
+    if !status.success() {
+        bail!(
+            "rsync from {} failed with status {status}; the copy in {} may be incomplete",
+            wallpapers.source,
+            folder.display()
+        );
+    }
+    say!("Mirrored {} into {}", wallpapers.source, folder.display());
+
+    variety::refresh(config)
+}
+
+/// Whether `source` names a git repository rather than a directory or rsync path.
+fn is_git_source(source: &str) -> bool {
+    source.ends_with(".git")
+        || ["git@", "git://", "ssh://", "https://", "http://"]
+            .iter()
+            .any(|prefix| source.starts_with(prefix))
+}
+
+/// The source as rsync takes it, with a trailing `/` so the contents are copied. A
+/// local directory must be there: an absent mount would otherwise empty the copy.
+fn rsync_source(wallpapers: &WallpapersConfig) -> anyhow::Result<String> {
+    let source = wallpapers.source.trim_end_matches('/');
+    if source.starts_with('/') && !Path::new(source).is_dir() {
+        bail!(
+            "{source} is not available; keeping the current copy in {}",
+            wallpapers.folder
+        );
+    }
+    Ok(format!("{source}/"))
+}
+
+/// Clones `url` into the target user's cache, or brings the clone up to date, and
+/// returns its directory.
+fn checkout(user: &UserContext, url: &str) -> anyhow::Result<PathBuf> {
+    let dir = user
+        .home
+        .join(".cache")
+        .join("debkit")
+        .join("wallpapers-git");
+    let origin = exec::query(
+        command_as(user, "git")?
+            .arg("-C")
+            .arg(&dir)
+            .args(["remote", "get-url", "origin"]),
+    )
+    .ok()
+    .filter(|output| output.status.success())
+    .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+
+    if origin.as_deref() == Some(url) {
+        if offline::is_enabled() {
+            say!("Offline: using the existing clone of {url}");
+            return Ok(dir);
+        }
+        run_git(
+            user,
+            &["-C", &dir.display().to_string(), "pull", "--ff-only"],
+            &format!("Updating {url}"),
+        )?;
+        return Ok(dir);
+    }
+    if offline::is_enabled() {
+        bail!("{url} has not been cloned yet, which needs network access");
+    }
+    if dir.exists() {
+        exec::remove_dir_all(&dir)
+            .with_context(|| format!("failed to remove {}", dir.display()))?;
+    }
+    if let Some(parent) = dir.parent() {
+        exec::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+        ensure_owned_writable_dir(parent, user)?;
+    }
+    run_git(
+        user,
+        &["clone", "--depth", "1", url, &dir.display().to_string()],
+        &format!("Cloning {url}"),
+    )?;
+    Ok(dir)
+}
+
+fn run_git(user: &UserContext, args: &[&str], step: &str) -> anyhow::Result<()> {
+    let status = exec::status_step(
+        network::apply(&mut command_as(user, "git")?).args(args),
+        step,
+    )
+    .context("failed to start `git`")?;
+    if !status.success() {
+        bail!("`git {}` failed with status {status}", args.join(" "));
+    }
+    Ok(())
+}
+
+/// The rsync options mirroring the files `wallpapers` selects. Excludes come first so
+/// they win over includes; with includes, everything else is excluded, while
+/// directories are kept to look inside and dropped again when left empty.
+fn rsync_args(wallpapers: &WallpapersConfig) -> Vec<String> {
+    let mut args = [
+        "--archive",
+        "--delete",
+        "--delete-excluded",
+        "--prune-empty-dirs",
+        "--timeout=60",
+        "--exclude=.git",
+    ]
+    .map(String::from)
+    .to_vec();
+    args.extend(
+        wallpapers
+            .exclude
+            .iter()
+            .map(|pattern| format!("--exclude={pattern}")),
+    );
+    if !wallpapers.include.is_empty() {
+        args.push("--include=*/".to_string());
+        args.extend(
+            wallpapers
+                .include
+                .iter()
+                .map(|pattern| format!("--include={pattern}")),
+        );
+        args.push("--exclude=*".to_string());
+    }
+    if wallpapers.max_file_mb > 0 {
+        args.push(format!("--max-size={}M", wallpapers.max_file_mb));
+    }
+    args
+}
+
+/// Refuses the sync when the selected files add up to more than `max_total_mb`, as
+/// measured by an rsync dry run.
+fn check_total_size(
+    user: &UserContext,
+    args: &[String],
+    source: &str,
+    destination: &str,
+    max_total_mb: u32,
+) -> anyhow::Result<()> {
+    if max_total_mb == 0 {
+        return Ok(());
+    }
+    let output = exec::query(command_as(user, "rsync")?.args(args).args([
+        "--dry-run",
+        "--stats",
+        source,
+        destination,
+    ]))
+    .context("failed to start `rsync`")?;
+    if !output.status.success() {
+        bail!(
+            "could not list {source}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let total = total_file_size(&String::from_utf8_lossy(&output.stdout))
+        .context("rsync did not report the total file size")?;
+    let limit = u64::from(max_total_mb) * 1024 * 1024;
+    if total > limit {
+        bail!(
+            "{source} holds {} MB of wallpapers, more than `wallpapers.max_total_mb` ({max_total_mb} MB)",
+            total.div_ceil(1024 * 1024)
+        );
+    }
+    Ok(())
+}
+
+/// The `Total file size: 1,234 bytes` figure of `rsync --stats`.
+fn total_file_size(stats: &str) -> Option<u64> {
+    let line = stats
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Total file size:"))?;
+    let digits = line
+        .split_whitespace()
+        .next()?
+        .chars()
+        .filter(char::is_ascii_digit)
+        .collect::<String>();
+    digits.parse().ok()
+}
+
+pub struct WallpapersSyncTarget;
+
+impl Target for WallpapersSyncTarget {
+    fn name(&self) -> &'static str {
+        "wallpapers-sync"
+    }
+
+    fn description(&self) -> &'static str {
+        "Local copy of wallpapers.source in wallpapers.folder"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        action == Action::Install
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        run(ctx.config()?)
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let wallpapers = &ctx.config()?.wallpapers;
+        if wallpapers.source.trim().is_empty() {
+            return Ok(super::TargetStatus::disabled(self.name()));
+        }
+        let mut status = super::TargetStatus::new(self.name(), None);
+        status.installed =
+            fs::read_dir(&wallpapers.folder).is_ok_and(|mut entries| entries.next().is_some());
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_and_measures_the_mirrored_files() {
+        let wallpapers = WallpapersConfig {
+            include: vec!["*.jpg".to_string()],
+            exclude: vec!["private/".to_string()],
+            max_file_mb: 20,
+            ..WallpapersConfig::default()
+        };
+        assert_eq!(
+            rsync_args(&wallpapers)[5..],
+            [
+                "--exclude=.git",
+                "--exclude=private/",
+                "--include=*/",
+                "--include=*.jpg",
+                "--exclude=*",
+                "--max-size=20M",
+            ]
+        );
+        assert!(is_git_source("git@github.com:me/walls.git"));
+        assert!(!is_git_source("spitfire:/srv/walls"));
+        assert_eq!(
+            total_file_size("Number of files: 3\nTotal file size: 1,234,567 bytes\n"),
+            Some(1_234_567)
+        );
+    }
+}
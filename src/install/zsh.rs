@@ -0,0 +1,332 @@
+//! `install zsh`: zsh with a plugin manager and the plugins from `[zsh]`.
+//!
+//! zsh comes from APT. The plugin manager, zinit or antidote, is cloned into the
+//! target user's home, and a managed `zsh-plugins` block in `~/.zshrc` loads it with
+//! `zsh.plugins`; antidote reads the plugins from a block in `~/.zsh_plugins.txt`.
+//! With `login_shell = true`, `chsh` makes zsh the user's login shell, through sudo
+//! when DebKit does not run as root.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+
+use super::user::{
+    UserContext, command_as, ensure_owned_writable_file, passwd_entry_for_user, target_user_context,
+};
+use super::{Action, Target, shell_init};
+use crate::config::{ZshConfig, ZshPluginManager};
+use crate::output::say;
+use crate::{apt, exec, network, offline, privileged, system};
+
+const PLUGINS_BLOCK: &str = "zsh-plugins";
+/// The shell `uninstall zsh` switches a zsh user back to.
+const FALLBACK_SHELL: &str = "/bin/bash";
+
+/// A plugin manager DebKit can set up: where it is cloned from and to, relative to
+/// the user's home.
+struct PluginManager {
+    name: &'static str,
+    repository: &'static str,
+    dir: &'static str,
+}
+
+const PLUGIN_MANAGERS: &[PluginManager] = &[
+    PluginManager {
+        name: "zinit",
+        repository: "https://github.com/zdharma-continuum/zinit.git",
+        dir: ".local/share/zinit/zinit.git",
+    },
+    PluginManager {
+        name: "antidote",
+        repository: "https://github.com/mattmc3/antidote.git",
+        dir: ".antidote",
+    },
+];
+
+#[derive(Debug, Serialize)]
+pub struct ZshReport {
+    pub login_shell: Option<String>,
+    pub plugin_manager: String,
+    pub plugins: Vec<String>,
+}
+
+pub fn run(config: &ZshConfig) -> anyhow::Result<ZshReport> {
+    let user = target_user_context()?;
+    let manager = plugin_manager(config);
+    apt::install_missing(&["zsh", "git"])?;
+
+    clone_plugin_manager(&user, manager)?;
+    for (file, lines) in managed_files(&user, manager, &config.plugins) {
+        let lines = lines.iter().map(String::as_str).collect::<Vec<_>>();
+        shell_init::ensure_managed_block(&file, PLUGINS_BLOCK, &lines)?;
+        ensure_owned_writable_file(&file, &user)?;
+    }
+    say!(
+        "zsh loads {} plugins with {} from {}",
+        config.plugins.len(),
+        manager.name,
+        user.home.join(".zshrc").display()
+    );
+
+    if config.login_shell
+        && !user
+            .name
+            .as_deref()
+            .and_then(login_shell)
+            .is_some_and(|shell| is_zsh(&shell))
+    {
+        set_login_shell(&user, &zsh_path())?;
+    }
+    Ok(ZshReport {
+        login_shell: user.name.as_deref().and_then(login_shell),
+        plugin_manager: manager.name.to_string(),
+        plugins: config.plugins.clone(),
+    })
+}
+
+/// Removes the managed blocks and the plugin manager, and with `login_shell = true`
+/// switches the user back to bash. zsh itself is removed once no account uses it as
+/// its login shell.
+pub fn uninstall(config: &ZshConfig) -> anyhow::Result<()> {
+    let user = target_user_context()?;
+    let files = [user.home.join(".zshrc"), user.home.join(".zsh_plugins.txt")];
+    for file in shell_init::remove_managed_block(&files, PLUGINS_BLOCK)? {
+        say!("Removed the DebKit plugin block from {}", file.display());
+    }
+    for manager in PLUGIN_MANAGERS {
+        let dir = user.home.join(manager.dir);
+        if dir.exists() {
+            exec::remove_dir_all(&dir)
+                .with_context(|| format!("failed to remove {}", dir.display()))?;
+            say!("Removed {}", dir.display());
+        }
+    }
+
+    if config.login_shell
+        && let Some(name) = user.name.as_deref()
+        && login_shell(name).is_some_and(|shell| is_zsh(&shell))
+    {
+        set_login_shell(&user, FALLBACK_SHELL)?;
+    }
+    let passwd = fs::read_to_string("/etc/passwd").context("failed to read /etc/passwd")?;
+    let users = zsh_users(&passwd);
+    if !apt::package_installed("zsh")? {
+        say!("zsh is not installed.");
+    } else if users.is_empty() {
+        apt::remove(&["zsh"])?;
+    } else {
+        say!("Kept zsh: it is the login shell of {}.", users.join(", "));
+    }
+    Ok(())
+}
+
+fn plugin_manager(config: &ZshConfig) -> &'static PluginManager {
+    match config.plugin_manager {
+        ZshPluginManager::Zinit => &PLUGIN_MANAGERS[0],
+        ZshPluginManager::Antidote => &PLUGIN_MANAGERS[1],
+    }
+}
+
+/// Clones `manager` as the target user unless it is already there. It updates itself
+/// afterwards, with `zinit self-update` or `antidote update`.
+fn clone_plugin_manager(user: &UserContext, manager: &PluginManager) -> anyhow::Result<()> {
+    let dir = user.home.join(manager.dir);
+    if dir.join(".git").exists() {
+        return Ok(());
+    }
+    if offline::is_enabled() {
+        bail!(
+            "{} has not been cloned yet, which needs network access",
+            manager.name
+        );
+    }
+    let status = exec::status_step(
+        network::apply(&mut command_as(user, "git")?)
+            .args(["clone", "--depth", "1", manager.repository])
+            .arg(&dir),
+        &format!("Cloning {}", manager.name),
+    )
+    .context("failed to start `git`")?;
+    if !status.success() {
+        bail!("cloning {} failed with status {status}", manager.repository);
+    }
+    Ok(())
+}
+
+/// The files with a `zsh-plugins` block for `manager`, and the lines of each block.
+fn managed_files(
+    user: &UserContext,
+    manager: &PluginManager,
+    plugins: &[String],
+) -> Vec<(PathBuf, Vec<String>)> {
+    let zshrc = user.home.join(".zshrc");
+    let source = format!(". \"$HOME/{}/{}.zsh\"", manager.dir, manager.name);
+    if manager.name == "antidote" {
+        let plugins_file = user.home.join(".zsh_plugins.txt");
+        return vec![
+            (
+                zshrc,
+                vec![
+                    source,
+                    "antidote load \"$HOME/.zsh_plugins.txt\"".to_string(),
+                ],
+            ),
+            (plugins_file, plugins.to_vec()),
+        ];
+    }
+    let mut lines = vec![source];
+    lines.extend(plugins.iter().map(|plugin| format!("zinit light {plugin}")));
+    vec![(zshrc, lines)]
+}
+
+fn zsh_path() -> String {
+    system::find_program("zsh")
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| "/usr/bin/zsh".to_string())
+}
+
+fn is_zsh(shell: &str) -> bool {
+    Path::new(shell).file_name() == Some("zsh".as_ref())
+}
+
+/// The login shell of `user` from `/etc/passwd`.
+fn login_shell(user: &str) -> Option<String> {
+    passwd_entry_for_user(user)
+        .map(|entry| entry.shell)
+        .filter(|shell| !shell.is_empty())
+}
+
+/// Runs `chsh -s shell` for the target user as root, so it neither asks for their
+/// password nor changes root's shell when DebKit runs through sudo.
+fn set_login_shell(user: &UserContext, shell: &str) -> anyhow::Result<()> {
+    let Some(name) = user.name.as_deref() else {
+        bail!("cannot tell whose login shell to change; set USER or run through sudo");
+    };
+    let listed = fs::read_to_string("/etc/shells")
+        .is_ok_and(|shells| shells.lines().any(|line| line.trim() == shell));
+    if !listed && !exec::is_dry_run() {
+        bail!("{shell} is not listed in /etc/shells, so chsh refuses it");
+    }
+    privileged::run("chsh", &["-s", shell, name])?;
+    say!("Login shell of {name} is now {shell}; it applies from the next login.");
+    Ok(())
+}
+
+/// The accounts in `passwd` whose login shell is zsh.
+fn zsh_users(passwd: &str) -> Vec<String> {
+    passwd
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields = line.split(':').collect::<Vec<_>>();
+            let [name, .., shell] = fields[..] else {
+                return None;
+            };
+            (fields.len() == 7 && is_zsh(shell)).then(|| name.to_string())
+        })
+        .collect()
+}
+
+pub struct ZshTarget;
+
+impl Target for ZshTarget {
+    fn name(&self) -> &'static str {
+        "zsh"
+    }
+
+    fn description(&self) -> &'static str {
+        "zsh with a plugin manager and plugins"
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(action, Action::Install | Action::Uninstall)
+    }
+
+    fn install(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        let report = run(&ctx.config()?.zsh)?;
+        ctx.report(&report)
+    }
+
+    fn uninstall(&self, ctx: &super::Context) -> anyhow::Result<()> {
+        uninstall(&ctx.config()?.zsh)
+    }
+
+    fn version(&self) -> Option<String> {
+        apt::package_version("zsh")
+    }
+
+    fn updates(&self, _ctx: &super::Context) -> anyhow::Result<Vec<super::Update>> {
+        super::apt_updates(&["zsh"])
+    }
+
+    fn summary(&self, ctx: &super::Context) -> anyhow::Result<super::TargetStatus> {
+        let config = &ctx.config()?.zsh;
+        let status = super::TargetStatus::new(self.name(), self.version());
+        if !status.installed {
+            return Ok(status);
+        }
+        let user = target_user_context()?;
+        let manager = plugin_manager(config);
+        let mut drift = Vec::new();
+        if !user.home.join(manager.dir).exists() {
+            drift.push(format!("{} is not cloned", manager.name));
+        }
+        for (file, lines) in managed_files(&user, manager, &config.plugins) {
+            let lines = lines.iter().map(String::as_str).collect::<Vec<_>>();
+            if !shell_init::has_managed_block(&file, PLUGINS_BLOCK, &lines) {
+                drift.push(format!(
+                    "{} does not load the configured plugins",
+                    file.display()
+                ));
+            }
+        }
+        if config.login_shell
+            && let Some(name) = user.name.as_deref()
+            && !login_shell(name).is_some_and(|shell| is_zsh(&shell))
+        {
+            drift.push(format!("the login shell of {name} is not zsh"));
+        }
+        Ok(status.with_drift(drift))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_plugins_with_the_configured_manager() {
+        let user = UserContext {
+            name: Some("alice".to_string()),
+            home: PathBuf::from("/home/alice"),
+            uid: Some(1000),
+            gid: Some(1000),
+        };
+        let plugins = ["zsh-users/zsh-autosuggestions".to_string()];
+        let zinit = managed_files(&user, &PLUGIN_MANAGERS[0], &plugins);
+        assert_eq!(
+            zinit,
+            [(
+                PathBuf::from("/home/alice/.zshrc"),
+                vec![
+                    r#". "$HOME/.local/share/zinit/zinit.git/zinit.zsh""#.to_string(),
+                    "zinit light zsh-users/zsh-autosuggestions".to_string(),
+                ]
+            )]
+        );
+        let antidote = managed_files(&user, &PLUGIN_MANAGERS[1], &plugins);
+        assert_eq!(antidote[0].1[0], r#". "$HOME/.antidote/antidote.zsh""#);
+        assert_eq!(
+            antidote[1],
+            (
+                PathBuf::from("/home/alice/.zsh_plugins.txt"),
+                plugins.to_vec()
+            )
+        );
+
+        let passwd = "root:x:0:0:root:/root:/bin/bash\nbob:x:1002:100::/home/bob:/usr/bin/zsh\n";
+        assert_eq!(zsh_users(passwd), ["bob"]);
+    }
+}
@@ -0,0 +1,258 @@
+//! Signing keys for third-party APT repositories, kept one per file in
+//! `/etc/apt/keyrings` and referenced from the source with `Signed-By:`. Keys are
+//! never added to apt's global trust store with `apt-key`.
+//!
+//! A key's fingerprint is checked before it is installed: against the one passed by the
+//! caller, or else the one declared for the keyring's name under `[keys]` in the config.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use anyhow::{Context, bail};
+use tracing::warn;
+
+use crate::output::say;
+use crate::{config, download, exec, privileged, scratch};
+
+pub const KEYRINGS_DIR: &str = "/etc/apt/keyrings";
+
+pub fn path(name: &str) -> PathBuf {
+    Path::new(KEYRINGS_DIR).join(format!("{name}.gpg"))
+}
+
+/// Downloads the key at `url`, checks its fingerprint and installs it as
+/// `/etc/apt/keyrings/<name>.gpg`.
+pub fn install(name: &str, url: &str, fingerprint: Option<&str>) -> anyhow::Result<PathBuf> {
+    validate_name(name)?;
+    let expected = match fingerprint {
+        Some(fingerprint) => Some(fingerprint.to_string()),
+        None => declared_fingerprint(name)?,
+    };
+    let expected = expected
+        .map(|fingerprint| {
+            normalize_fingerprint(&fingerprint)
+                .with_context(|| format!("`{fingerprint}` is not a key fingerprint"))
+        })
+        .transpose()?;
+
+    let key = dearmor(&download::fetch(url)?)?;
+    let found = fingerprints(&key)?;
+    match &expected {
+        Some(expected) => verify(&found, expected)
+            .with_context(|| format!("refusing to install the key from {url}"))?,
+        None => warn!(
+            "no fingerprint declared for `{name}`; trusting {} from {url} unverified (add `{name} = \"...\"` under [keys] to pin it)",
+            found.join(", ")
+        ),
+    }
+
+    let path = path(name);
+    privileged::create_dir_all(Path::new(KEYRINGS_DIR))?;
+    privileged::write(&path, &key)?;
+    say!("Installed key {} as {}", found.join(", "), path.display());
+    Ok(path)
+}
+
+/// Checks the detached `signature` over `data` with `gpgv` against the keys in
+/// `keyring`, such as one installed by [`install`].
+pub fn verify_signature(keyring: &Path, signature: &[u8], data: &[u8]) -> anyhow::Result<()> {
+    let dir = scratch::create("gpgv")?;
+    let (signature_path, data_path) = (dir.join("data.sig"), dir.join("data"));
+    let output = fs::write(&signature_path, signature)
+        .and_then(|()| fs::write(&data_path, data))
+        .with_context(|| format!("failed to write to {}", dir.display()))
+        .and_then(|()| {
+            exec::query(
+                Command::new("gpgv")
+                    .arg("--keyring")
+                    .arg(keyring)
+                    .arg(&signature_path)
+                    .arg(&data_path),
+            )
+            .context("failed to launch gpgv; install the `gpgv` package and retry")
+        });
+    let _ = fs::remove_dir_all(&dir);
+    let output = output?;
+    if !output.status.success() {
+        bail!(
+            "the signature does not match a key in {}: {}",
+            keyring.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Strips spaces and a `0x` prefix and upper-cases a fingerprint; `None` unless the
+/// result is a full 40 (v4) or 64 (v5) digit fingerprint.
+pub fn normalize_fingerprint(raw: &str) -> Option<String> {
+    let compact = raw.split_whitespace().collect::<String>();
+    let compact = compact
+        .strip_prefix("0x")
+        .or_else(|| compact.strip_prefix("0X"))
+        .unwrap_or(&compact)
+        .to_ascii_uppercase();
+    let valid = matches!(compact.len(), 40 | 64) && compact.chars().all(|c| c.is_ascii_hexdigit());
+    valid.then_some(compact)
+}
+
+/// The fingerprint declared for `name` under `[keys]`, if the config exists and sets one.
+pub fn declared_fingerprint(name: &str) -> anyhow::Result<Option<String>> {
+    let path = config::path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(config::load(&path)?.keys.remove(name))
+}
+
+/// Every primary key in a keyring must be the expected one, so a compromised download
+/// cannot smuggle extra trusted keys in alongside it.
+fn verify(found: &[String], expected: &str) -> anyhow::Result<()> {
+    if found.is_empty() {
+        bail!("the download contains no public key");
+    }
+    if !found.iter().any(|fingerprint| fingerprint == expected) {
+        bail!("expected key {expected}, got {}", found.join(", "));
+    }
+    if found.len() > 1 {
+        bail!(
+            "expected only key {expected}, but the download also contains {}",
+            found
+                .iter()
+                .filter(|fingerprint| *fingerprint != expected)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn validate_name(name: &str) -> anyhow::Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+    if !valid {
+        bail!("invalid key name `{name}`; use letters, digits, `-`, `_` and `.`");
+    }
+    Ok(())
+}
+
+/// Converts an ASCII-armored key to the binary form apt expects; binary keys are
+/// returned unchanged.
+fn dearmor(key: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if !key.starts_with(b"-----BEGIN PGP") {
+        return Ok(key.to_vec());
+    }
+    gpg(&["--dearmor"], key)
+}
+
+/// Fingerprints of the primary keys in a binary keyring.
+fn fingerprints(key: &[u8]) -> anyhow::Result<Vec<String>> {
+    let listing = gpg(&["--with-colons", "--show-keys"], key)?;
+    Ok(primary_fingerprints(&String::from_utf8_lossy(&listing)))
+}
+
+/// Reads `--with-colons` output, where each `pub` record is followed by the `fpr`
+/// record of its fingerprint and subkeys come as `sub` records with their own `fpr`.
+fn primary_fingerprints(listing: &str) -> Vec<String> {
+    let mut fingerprints = Vec::new();
+    let mut in_primary = false;
+    for line in listing.lines() {
+        let fields = line.split(':').collect::<Vec<_>>();
+        match fields[0] {
+            "pub" | "sec" => in_primary = true,
+            "sub" | "ssb" => in_primary = false,
+            "fpr" if in_primary => {
+                if let Some(fingerprint) = fields.get(9).filter(|value| !value.is_empty()) {
+                    fingerprints.push(fingerprint.to_ascii_uppercase());
+                }
+                in_primary = false;
+            }
+            _ => {}
+        }
+    }
+    fingerprints
+}
+
+/// Runs gpg on `input` with a throwaway private home directory, so the user's own
+/// keyring is neither read nor modified and no one else can plant a `gpg.conf` in it.
+fn gpg(args: &[&str], input: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let home = scratch::create("gpg")?;
+    let output = run_gpg(&home, args, input);
+    let _ = fs::remove_dir_all(&home);
+    let output = output?;
+    if !output.status.success() {
+        bail!(
+            "gpg {} failed with status {}: {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+fn run_gpg(home: &Path, args: &[&str], input: &[u8]) -> anyhow::Result<Output> {
+    exec::query_with_input(
+        Command::new("gpg")
+            .arg("--homedir")
+            .arg(home)
+            .args(["--batch", "--no-tty"])
+            .args(args),
+        input,
+    )
+    .context("failed to launch gpg; install the `gnupg` package and retry")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_fingerprints() {
+        assert_eq!(
+            normalize_fingerprint("0xbc52 8686 b50d 79e3 39d3  721c eb3e 94ad be12 29cf")
+                .as_deref(),
+            Some("BC528686B50D79E339D3721CEB3E94ADBE1229CF")
+        );
+        assert_eq!(normalize_fingerprint("EB3E94ADBE1229CF"), None);
+        assert_eq!(
+            normalize_fingerprint("ZC528686B50D79E339D3721CEB3E94ADBE1229CF"),
+            None
+        );
+    }
+
+    #[test]
+    fn reads_primary_fingerprints_only() {
+        let listing = "\
+pub:-:4096:1:EB3E94ADBE1229CF:1445472556:::-:::scSC::::::23::0:
+fpr:::::::::BC528686B50D79E339D3721CEB3E94ADBE1229CF:
+uid:-::::1445472556::B2ECB6D6DE1A4F81::Microsoft (Release signing) <gpgsecurity@microsoft.com>::::::::::0:
+sub:-:4096:1:1111111111111111:1445472556::::::e::::::23:
+fpr:::::::::AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA:
+pub:-:2048:1:2222222222222222:1445472556:::-:::scSC::::::23::0:
+fpr:::::::::CCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCC:
+";
+        assert_eq!(
+            primary_fingerprints(listing),
+            [
+                "BC528686B50D79E339D3721CEB3E94ADBE1229CF",
+                "CCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCC"
+            ]
+        );
+    }
+
+    #[test]
+    fn verify_requires_exactly_the_expected_key() {
+        let expected = "BC528686B50D79E339D3721CEB3E94ADBE1229CF";
+        let other = "CCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCC";
+        assert!(verify(&[expected.to_string()], expected).is_ok());
+        assert!(verify(&[other.to_string()], expected).is_err());
+        assert!(verify(&[expected.to_string(), other.to_string()], expected).is_err());
+        assert!(verify(&[], expected).is_err());
+    }
+}
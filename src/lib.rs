@@ -0,0 +1,51 @@
+//! DebKit as a library.
+//!
+//! Everything the `debkit` command does is available here for programs that want to
+//! provision a machine themselves, such as a bootstrap binary: the config
+//! ([`config`]), the provisioning targets ([`install`]), APT, repository, key and pin
+//! management ([`apt`], [`repo`], [`keyring`], [`pin`]) and the packaging pipeline
+//! ([`package`], [`publish`]). The `debkit` binary is a thin clap front end over this
+//! crate.
+//!
+//! All changes to the system go through [`exec`], so [`exec::set_dry_run`] turns any of
+//! these calls into a preview. Progress is printed with [`output::say!`];
+//! [`output::set_quiet`] silences it.
+//!
+//! ```no_run
+//! use debkit::install::{self, Action};
+//!
+//! fn main() -> anyhow::Result<()> {
+//!     debkit::exec::set_dry_run(true);
+//!     install::run_by_name(Action::Install, "ripgrep", &[])?;
+//!     install::run_by_name(Action::Install, "rust", &["--reinstall"])?;
+//!     Ok(())
+//! }
+//! ```
+
+pub mod apt;
+pub mod audit;
+pub mod backup;
+pub mod config;
+pub mod confirm;
+pub mod desktop;
+pub mod download;
+pub mod exec;
+pub mod install;
+pub mod keyring;
+pub mod logging;
+pub mod network;
+pub mod offline;
+pub mod output;
+pub mod package;
+pub mod pin;
+pub mod privileged;
+pub mod progress;
+pub mod publish;
+pub mod remote;
+pub mod repo;
+pub mod runner;
+pub mod scratch;
+pub mod self_update;
+pub mod state;
+pub mod system;
+pub mod verify;
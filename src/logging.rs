@@ -0,0 +1,156 @@
+//! Diagnostics through `tracing`.
+//!
+//! The console (stderr) shows warnings and errors as `warning: ...` and `error: ...`.
+//! `-v` adds the commands DebKit runs and the files it changes, `-vv` debugging detail,
+//! and `--log-level` picks the level directly. `--log-file` records everything from
+//! `debug` up with timestamps, including the output of long-running commands, which
+//! never goes to the console through here. Results and progress for the user still go
+//! through [`crate::output::say!`].
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::Context;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::{FilterExt, LevelFilter, filter_fn};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::{Layer, SubscriberExt};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+
+use crate::progress;
+
+/// Target for events that only belong in the log file, such as command output.
+pub const FILE_ONLY: &str = "debkit::log_file";
+
+static CONSOLE_LEVEL: OnceLock<LevelFilter> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// The console level: `--log-level` if given, otherwise `warn` raised by one level
+/// per `-v`.
+pub fn console_level(level: Option<LogLevel>, verbose: u8) -> LevelFilter {
+    match (level, verbose) {
+        (Some(level), _) => level.into(),
+        (None, 0) => LevelFilter::WARN,
+        (None, 1) => LevelFilter::INFO,
+        (None, 2) => LevelFilter::DEBUG,
+        (None, _) => LevelFilter::TRACE,
+    }
+}
+
+/// Installs the console logger at `level` and, with `file`, the log file.
+pub fn init(level: LevelFilter, file: Option<&Path>) -> anyhow::Result<()> {
+    let _ = CONSOLE_LEVEL.set(level);
+    let console = tracing_subscriber::fmt::layer()
+        .event_format(Console)
+        .with_writer(|| ConsoleWriter)
+        .with_filter(level.and(filter_fn(|meta| meta.target() != FILE_ONLY)));
+    let file = match file {
+        Some(path) => Some(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(Mutex::new(open(path)?))
+                .with_filter(level.max(LevelFilter::DEBUG)),
+        ),
+        None => None,
+    };
+    tracing::subscriber::set_global_default(Registry::default().with(console).with(file))
+        .context("failed to install the logger")
+}
+
+/// The console level [`init`] was given, such as `warn`, for passing on to DebKit on
+/// other machines; `None` before [`init`] or when the console is off.
+pub fn console_level_name() -> Option<String> {
+    CONSOLE_LEVEL
+        .get()
+        .and_then(|level| level.into_level())
+        .map(|level| level.to_string().to_ascii_lowercase())
+}
+
+fn open(path: &Path) -> anyhow::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open log file {}", path.display()))
+}
+
+/// `warning: message`, the way DebKit has always printed diagnostics.
+struct Console;
+
+impl<S, N> FormatEvent<S, N> for Console
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        write!(writer, "{}: ", label(*event.metadata().level()))?;
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+fn label(level: Level) -> &'static str {
+    match level {
+        Level::ERROR => "error",
+        Level::WARN => "warning",
+        Level::INFO => "info",
+        Level::DEBUG => "debug",
+        Level::TRACE => "trace",
+    }
+}
+
+/// stderr, with any progress spinner moved out of the way.
+struct ConsoleWriter;
+
+impl Write for ConsoleWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        progress::suspend(|| io::stderr().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbosity_raises_the_console_level() {
+        assert_eq!(console_level(None, 0), LevelFilter::WARN);
+        assert_eq!(console_level(None, 1), LevelFilter::INFO);
+        assert_eq!(console_level(None, 2), LevelFilter::DEBUG);
+        assert_eq!(console_level(None, 5), LevelFilter::TRACE);
+        assert_eq!(console_level(Some(LogLevel::Error), 2), LevelFilter::ERROR);
+    }
+}
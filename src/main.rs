@@ -1,117 +1,492 @@
-mod config;
-mod install;
-mod package;
+mod completions;
+mod manpages;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::{Args, Parser, Subcommand};
+use clap_complete::{ArgValueCandidates, CompletionCandidate};
+use debkit::install::{
+    self, ConfigureAction, InstallAction, StatusAction, TargetCommand, UninstallAction,
+};
+use debkit::output::{self, say};
+use debkit::{
+    apt, audit, backup, config, confirm, desktop, exec, keyring, logging, network, offline,
+    package, pin, repo, self_update, verify,
+};
+use serde::Serialize;
 
 #[derive(Debug, Parser)]
 #[command(name = "debkit", version, about = "DebKit CLI")]
 struct Cli {
+    #[arg(
+        long,
+        global = true,
+        help = "Print the commands and file changes that would be made without making them"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Print the diff of each file DebKit writes before writing it, as --dry-run does"
+    )]
+    diff: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = output::Format::Text,
+        help = "Output format; `json` prints a single JSON document on stdout"
+    )]
+    format: output::Format,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "Config file to use instead of ~/.config/debkit/config.toml [env: DEBKIT_CONFIG]"
+    )]
+    config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Install only from packages and installers already on this machine"
+    )]
+    offline: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "Use pre-downloaded .deb files from DIR/archives as apt's package cache"
+    )]
+    apt_cache: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "URL",
+        help = "HTTP(S) proxy for apt, curl, rustup and npm, instead of `network.proxy`; \"\" for none"
+    )]
+    proxy: Option<String>,
+
+    #[arg(
+        short = 'y',
+        long,
+        global = true,
+        help = "Make privileged changes without asking for confirmation first"
+    )]
+    yes: bool,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        conflicts_with = "verbose",
+        help = "Print only errors and a one-line result, for cron jobs and CI"
+    )]
+    quiet: bool,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count,
+        help = "Log the commands run and files changed (-v), or debugging detail too (-vv)"
+    )]
+    verbose: u8,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_name = "LEVEL",
+        help = "Console log level, instead of -v"
+    )]
+    log_level: Option<logging::LogLevel>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "Append a debug log, including the output of long-running commands, to PATH"
+    )]
+    log_file: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Debug, Subcommand)]
 enum Commands {
+    #[command(about = "Print a shell completion script")]
+    Completions(CompletionsArgs),
+    #[command(about = "Read or change keys in ~/.config/debkit/config.toml")]
+    Config(ConfigCommand),
     #[command(about = "Configure DebKit features")]
     Configure(ConfigureCommand),
+    #[command(about = "Dump or load the target user's dconf desktop settings")]
+    Desktop(DesktopCommand),
     #[command(about = "Create or update the current host override config")]
     HostConfig,
+    #[command(about = "Add cargo-deb packaging metadata to a Rust project's Cargo.toml")]
+    Init(InitArgs),
     #[command(about = "List installable DebKit targets")]
-    List,
+    List(ListArgs),
+    #[command(about = "Show what past DebKit runs changed, from the audit log")]
+    Logs(LogsArgs),
+    #[command(about = "Generate man pages for every subcommand")]
+    Man(ManArgs),
     #[command(about = "Build DebKit packages")]
     Package(PackageCommand),
+    #[command(about = "Add built .deb packages to a local aptly or reprepro repository")]
+    Publish(PublishArgs),
+    #[command(about = "Add, remove or list third-party APT repositories")]
+    Repo(RepoCommand),
+    #[command(about = "Install APT signing keys into /etc/apt/keyrings")]
+    Key(KeyCommand),
+    #[command(about = "Pin packages to a version with apt preferences")]
+    Pin(PinCommand),
+    #[command(about = "Keep packages at their installed version with `apt-mark hold`")]
+    Hold(HoldArgs),
+    #[command(about = "Release packages held with `debkit hold`")]
+    Unhold(HoldArgs),
     #[command(about = "Install a DebKit target")]
     Install(InstallCommand),
     #[command(about = "Uninstall a DebKit target")]
     Uninstall(UninstallCommand),
+    #[command(about = "Remove apt packages")]
+    Remove(RemoveArgs),
+    #[command(about = "Put back the copy of a file DebKit saved before changing it")]
+    Restore(RestoreArgs),
     #[command(about = "Show status for a DebKit target")]
     Status(StatusCommand),
+    #[command(name = "self", about = "Manage DebKit itself")]
+    Itself(SelfCommand),
+    #[command(about = "Try an install in a throwaway docker or podman container")]
+    Verify(VerifyArgs),
 }
 
 #[derive(Debug, Args)]
-struct PackageCommand {
+struct CompletionsArgs {
+    #[arg(value_enum)]
+    shell: completions::Shell,
+}
+
+#[derive(Debug, Args)]
+struct ConfigCommand {
     #[command(subcommand)]
-    command: PackageSubcommand,
+    command: ConfigSubcommand,
 }
 
 #[derive(Debug, Subcommand)]
-enum PackageSubcommand {
-    Deb(PackageDebArgs),
+enum ConfigSubcommand {
+    #[command(about = "Print a key such as `variety.interval_minutes`, or its default")]
+    Get(ConfigKeyArgs),
+    #[command(about = "Set a key, keeping comments and the rest of the file intact")]
+    Set(ConfigSetArgs),
+    #[command(about = "Remove a key so its default applies again")]
+    Unset(ConfigKeyArgs),
+    #[command(about = "Check the config and host override for problems without changing them")]
+    Validate(ConfigValidateArgs),
 }
 
 #[derive(Debug, Args)]
-struct InstallCommand {
+struct ConfigValidateArgs {
+    #[arg(long, help = "Also fail on warnings, for use in CI")]
+    strict: bool,
+}
+
+#[derive(Debug, Args)]
+struct ConfigKeyArgs {
+    #[arg(add = ArgValueCandidates::new(config_key_candidates))]
+    key: String,
+}
+
+#[derive(Debug, Args)]
+struct ConfigSetArgs {
+    #[arg(add = ArgValueCandidates::new(config_key_candidates))]
+    key: String,
+    #[arg(help = "New value; arrays accept `a,b,c` or a TOML array such as '[\"a\", \"b\"]'")]
+    value: String,
+}
+
+fn config_key_candidates() -> Vec<CompletionCandidate> {
+    config::keys()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+#[derive(Debug, Args)]
+struct ListArgs {
+    #[arg(
+        long,
+        help = "Check each target on this machine and show whether it is installed, with its version"
+    )]
+    status: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "status",
+        help = "Compare installed versions with what APT, rustup and crates.io offer, and list the newer ones"
+    )]
+    available_updates: bool,
+
+    #[arg(long, help = "Print the targets as JSON; same as `--format json`")]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct LogsArgs {
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 10,
+        help = "Number of most recent runs to show"
+    )]
+    last: usize,
+
+    #[arg(long, help = "Print the runs as JSON; same as `--format json`")]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct RepoCommand {
     #[command(subcommand)]
-    command: InstallSubcommand,
+    command: RepoSubcommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum RepoSubcommand {
+    #[command(about = "Add a `deb ...` line or `ppa:owner/name` as a deb822 .sources file")]
+    Add(RepoAddArgs),
+    #[command(about = "Remove a repository added by DebKit, with its signing key")]
+    Remove(RepoRemoveArgs),
+    #[command(about = "List the deb822 sources in /etc/apt/sources.list.d")]
+    List,
 }
 
 #[derive(Debug, Args)]
-struct ConfigureCommand {
+struct RepoAddArgs {
+    #[arg(
+        help = "A one-line `deb [options] <uri> <suite> [components...]` entry or `ppa:owner/name`"
+    )]
+    source: String,
+
+    #[arg(long, help = "File name under sources.list.d, without `.sources`")]
+    name: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "HTTPS URL of the signing key to install into /etc/apt/keyrings"
+    )]
+    key: Option<String>,
+
+    #[arg(
+        long,
+        help = "Fingerprint the key must have, instead of the one declared under [keys]"
+    )]
+    fingerprint: Option<String>,
+
+    #[arg(
+        long,
+        help = "Suite for a PPA instead of this system's Ubuntu codename"
+    )]
+    suite: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct RepoRemoveArgs {
+    name: String,
+}
+
+#[derive(Debug, Args)]
+struct KeyCommand {
     #[command(subcommand)]
-    command: ConfigureSubcommand,
+    command: KeySubcommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum KeySubcommand {
+    #[command(about = "Download a key over HTTPS, verify it and install it as <NAME>.gpg")]
+    Add(KeyAddArgs),
 }
 
 #[derive(Debug, Args)]
-struct UninstallCommand {
+struct KeyAddArgs {
+    #[arg(help = "Keyring name; the key is installed as /etc/apt/keyrings/<NAME>.gpg")]
+    name: String,
+
+    #[arg(help = "HTTPS URL of the key, ASCII-armored or binary")]
+    url: String,
+
+    #[arg(
+        long,
+        help = "Fingerprint the key must have, instead of the one declared under [keys]"
+    )]
+    fingerprint: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct PinCommand {
     #[command(subcommand)]
-    command: UninstallSubcommand,
+    command: PinSubcommand,
 }
 
 #[derive(Debug, Subcommand)]
-enum InstallSubcommand {
-    Codex(InstallCodexArgs),
-    Essentials,
-    Git,
-    Nis,
-    NisClient,
-    NisServer,
-    Npm(InstallNpmArgs),
-    Ripgrep,
-    Rust(InstallRustArgs),
-    SudoNopass,
-    Variety,
-    Foundation,
-    WakeOnLan(InstallWakeOnLanArgs),
+enum PinSubcommand {
+    #[command(about = "Pin a package to a version, such as `0.8.4-1` or `0.8.*`")]
+    Set(PinSetArgs),
+    #[command(about = "Remove a pin written by `debkit pin set`")]
+    Remove(PinRemoveArgs),
+    #[command(about = "List DebKit's pins and the packages on hold")]
+    List,
+}
+
+#[derive(Debug, Args)]
+struct PinSetArgs {
+    package: String,
+    version: String,
+}
+
+#[derive(Debug, Args)]
+struct PinRemoveArgs {
+    package: String,
+}
+
+#[derive(Debug, Args)]
+struct HoldArgs {
+    #[arg(required = true)]
+    packages: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+struct RestoreArgs {
+    #[arg(help = "A file DebKit changed, such as ~/.bashrc")]
+    file: PathBuf,
+
+    #[arg(long, help = "List the backups of the file instead, oldest first")]
+    list: bool,
+}
+
+#[derive(Debug, Args)]
+struct ManArgs {
+    #[arg(long, default_value = "./dist/man")]
+    output_dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct PackageCommand {
+    #[command(subcommand)]
+    command: PackageSubcommand,
 }
 
 #[derive(Debug, Subcommand)]
-enum ConfigureSubcommand {
-    #[command(about = "Create or update ~/.config/debkit/hosts/<hostname>.toml")]
-    HostConfig,
-    #[command(about = "Configure the current user's Git-aware shell prompt")]
-    GitPrompt,
-    #[command(about = "Force-refresh NIS maps on a configured slave")]
-    Nis(ConfigureNisArgs),
+enum PackageSubcommand {
+    Deb(Box<PackageDebArgs>),
+    #[command(about = "Build .rpm packages with cargo-generate-rpm")]
+    Rpm(PackageRpmArgs),
+    #[command(about = "Build .tar.gz or .zip archives of the binaries with a SHA256 file")]
+    Archive(PackageArchiveArgs),
+    #[command(about = "Build portable AppImages with appimagetool")]
+    Appimage(PackageAppimageArgs),
+    #[command(about = "Generate a flatpak-builder manifest and optionally build a bundle")]
+    Flatpak(PackageFlatpakArgs),
+    #[command(about = "Write debian/changelog from git tags and commit messages")]
+    Changelog(PackageChangelogArgs),
+    #[command(about = "Build a Debian source package (.dsc) for a PPA or buildd")]
+    Source(PackageSourceArgs),
+    #[command(about = "Remove all but the newest versions of built .deb packages")]
+    Clean(PackageCleanArgs),
 }
 
 #[derive(Debug, Args)]
-struct ConfigureNisArgs {
+struct DesktopCommand {
     #[command(subcommand)]
-    command: Option<ConfigureNisSubcommand>,
+    command: DesktopSubcommand,
 }
 
 #[derive(Debug, Subcommand)]
-enum ConfigureNisSubcommand {
-    #[command(about = "Add a slave FQDN to a master host config")]
-    AddSlave(AddNisSlaveArgs),
+enum DesktopSubcommand {
+    #[command(about = "Print a dconf subtree as a loadable profile")]
+    Dump(DesktopDumpArgs),
+    #[command(about = "Apply a profile written by `debkit desktop dump`")]
+    Load(DesktopLoadArgs),
 }
 
 #[derive(Debug, Args)]
-struct AddNisSlaveArgs {
-    slave: String,
+struct DesktopDumpArgs {
+    #[arg(default_value = "/")]
+    dir: String,
+}
 
-    #[arg(long)]
-    host: String,
+#[derive(Debug, Args)]
+struct DesktopLoadArgs {
+    file: PathBuf,
+
+    #[arg(long, default_value = "/")]
+    dir: String,
+}
+
+#[derive(Debug, Args)]
+struct InstallCommand {
+    #[command(subcommand)]
+    command: InstallSubcommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum InstallSubcommand {
+    #[command(about = "Install a local .deb file, resolving its dependencies with apt")]
+    Deb(InstallDebArgs),
+    #[command(flatten)]
+    Target(TargetCommand<InstallAction>),
+}
+
+#[derive(Debug, Args)]
+struct InstallDebArgs {
+    file: PathBuf,
+
+    #[arg(long, help = "Allow replacing an installed newer version")]
+    allow_downgrade: bool,
+}
+
+#[derive(Debug, Args)]
+struct RemoveArgs {
+    #[arg(required = true)]
+    packages: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Also delete the packages' system-wide configuration files"
+    )]
+    purge: bool,
+
+    #[arg(long, help = "Then remove dependencies that nothing needs any more")]
+    autoremove: bool,
+}
+
+#[derive(Debug, Args)]
+struct ConfigureCommand {
+    #[command(subcommand)]
+    command: ConfigureSubcommand,
+}
+
+#[derive(Debug, Args)]
+struct UninstallCommand {
+    #[command(subcommand)]
+    command: TargetCommand<UninstallAction>,
 }
 
 #[derive(Debug, Subcommand)]
-enum UninstallSubcommand {
-    Codex,
-    Npm,
-    Ripgrep,
+enum ConfigureSubcommand {
+    #[command(about = "Create or update ~/.config/debkit/hosts/<hostname>.toml")]
+    HostConfig,
+    #[command(flatten)]
+    Target(TargetCommand<ConfigureAction>),
 }
 
 #[derive(Debug, Args)]
@@ -122,210 +497,1527 @@ struct StatusCommand {
 
 #[derive(Debug, Subcommand)]
 enum StatusSubcommand {
-    Variety,
-    WakeOnLan,
+    #[command(about = "Summarize installed version, configuration and drift for every target")]
+    All,
+    #[command(flatten)]
+    Target(TargetCommand<StatusAction>),
 }
 
 #[derive(Debug, Args)]
-struct InstallRustArgs {
-    #[arg(long)]
-    reinstall: bool,
+struct SelfCommand {
+    #[command(subcommand)]
+    command: SelfSubcommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum SelfSubcommand {
+    #[command(about = "Install the newest DebKit release from GitHub or the APT repositories")]
+    Update(SelfUpdateArgs),
 }
 
 #[derive(Debug, Args)]
-struct InstallNpmArgs {
-    #[arg(long, default_value = "latest")]
-    version: String,
+struct SelfUpdateArgs {
+    #[arg(long, help = "Only report whether a newer version is available")]
+    check: bool,
 }
 
 #[derive(Debug, Args)]
-struct InstallCodexArgs {
-    #[arg(long = "node-version", default_value = "latest")]
-    node_version: String,
+struct InitArgs {
+    #[arg(long, default_value = "Cargo.toml", help = "The project's Cargo.toml")]
+    manifest_path: PathBuf,
+
+    #[arg(long, help = "Replace an existing [package.metadata.deb] section")]
+    force: bool,
 }
 
 #[derive(Debug, Args)]
-struct InstallWakeOnLanArgs {
-    #[arg(long)]
-    dry_run: bool,
+struct VerifyArgs {
+    #[arg(default_value = "foundation", help = "Target to install")]
+    target: String,
+
+    #[arg(long, default_value = verify::DEFAULT_IMAGE, help = "Debian or Ubuntu image to start")]
+    image: String,
+
+    #[arg(
+        long,
+        value_parser = ["docker", "podman"],
+        help = "Container engine; by default podman, or docker if podman is not installed"
+    )]
+    engine: Option<String>,
+
+    #[arg(long, help = "Leave the container running afterwards")]
+    keep: bool,
+
+    #[arg(last = true, help = "Options for the target, after `--`")]
+    args: Vec<String>,
+}
+
+/// Which crates `package deb` and `package rpm` build.
+#[derive(Debug, Args)]
+struct PackageProjectArgs {
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Cargo.toml of the crate to package [default: ./Cargo.toml]"
+    )]
+    manifest_path: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with = "manifest_path",
+        help = "Directory of the crate to package"
+    )]
+    project_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        short = 'p',
+        value_name = "MEMBER",
+        help = "Workspace member to package; repeat for several"
+    )]
+    package: Vec<String>,
+
+    #[arg(
+        long,
+        conflicts_with = "package",
+        help = "Package every workspace member with a binary"
+    )]
+    all_members: bool,
+}
+
+impl PackageProjectArgs {
+    fn manifest_path(&self) -> PathBuf {
+        match (&self.manifest_path, &self.project_dir) {
+            (Some(path), _) => path.clone(),
+            (None, Some(dir)) => dir.join("Cargo.toml"),
+            (None, None) => PathBuf::from("Cargo.toml"),
+        }
+    }
 }
 
 #[derive(Debug, Args)]
 struct PackageDebArgs {
+    #[command(flatten)]
+    project: PackageProjectArgs,
+
     #[arg(long, default_value_t = true)]
     release: bool,
 
     #[arg(long, default_value = "./dist")]
     output_dir: PathBuf,
 
-    #[arg(long)]
-    arch: Option<String>,
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        help = "File name for each package in the output directory, such as \"{name}_{version}_{arch}_{channel}.deb\"; can also use {profile} and {git-sha}"
+    )]
+    name_template: Option<String>,
 
-    #[arg(long)]
-    verbose: bool,
+    #[arg(
+        long,
+        requires = "name_template",
+        help = "What {channel} stands for in --name-template, such as stable or nightly"
+    )]
+    channel: Option<String>,
+
+    #[arg(
+        long,
+        help = "Debian architecture, such as arm64; cross-compiles when it is not this machine's (repeatable)"
+    )]
+    arch: Vec<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "gcc",
+        help = "What links the binaries when cross-compiling"
+    )]
+    linker: package::cross::Linker,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "cargo-deb",
+        help = "What assembles the .deb; native needs no cargo-deb"
+    )]
+    backend: package::deb::Backend,
 
     #[arg(long)]
     reinstall: bool,
+
+    #[arg(
+        long,
+        help = "Install the built package through apt once it is in the output directory"
+    )]
+    install: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "no_strip",
+        help = "Strip the binaries, even in a dev build"
+    )]
+    strip: bool,
+
+    #[arg(long, help = "Keep the binaries' symbols in the package")]
+    no_strip: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "no_strip",
+        help = "Split debug info into a companion <package>-dbgsym package"
+    )]
+    dbgsym: bool,
+
+    #[arg(
+        long,
+        help = "Generate completions and man pages and package them with assets/*.service units"
+    )]
+    assets: bool,
+
+    #[arg(long, help = "Maintainer to put in the package instead of the crate's")]
+    maintainer: Option<String>,
+
+    #[arg(
+        long,
+        help = "Dependency to put in the package instead of the crate's (repeatable; replaces them all)"
+    )]
+    depends: Vec<String>,
+
+    #[arg(
+        long,
+        help = "One-line description to put in the package instead of the crate's"
+    )]
+    description: Option<String>,
+
+    #[arg(
+        long,
+        help = "Archive section to put in the package, such as net or admin"
+    )]
+    section: Option<String>,
+
+    #[arg(
+        long,
+        value_parser = package::control::PRIORITIES,
+        help = "Priority to put in the package"
+    )]
+    priority: Option<String>,
+
+    #[arg(
+        long,
+        help = "Write debian/changelog from git tags and commits and include it in the package"
+    )]
+    changelog: bool,
+
+    #[arg(
+        long,
+        help = "Build from the last commit's timestamp and check a second build is bit-identical"
+    )]
+    reproducible: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Write a software bill of materials for each .deb from Cargo.lock"
+    )]
+    sbom: Option<package::sbom::Format>,
+
+    #[arg(
+        long,
+        requires = "sbom",
+        help = "Put the SBOM in the .deb under /usr/share/doc/<package>/ instead of next to it"
+    )]
+    embed_sbom: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "report",
+        help = "Check each .deb with lintian; --lint=strict fails on errors"
+    )]
+    lint: Option<package::lint::Mode>,
+
+    #[arg(long, help = "Sign each .deb once it is built")]
+    sign: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "dpkg-sig",
+        help = "Tool that signs with --sign"
+    )]
+    signer: package::sign::Signer,
+
+    #[arg(
+        long,
+        requires = "sign",
+        help = "Key ID or fingerprint to sign with [default: packaging.signing_key]"
+    )]
+    key: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "IMAGE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = package::verify::DEFAULT_IMAGE,
+        help = "Check each .deb once it is built: contents, dependencies and that its binary runs in IMAGE"
+    )]
+    verify: Option<String>,
+
+    #[arg(
+        long,
+        help = "Write SHA256SUMS, a .sha256 per package and manifest.json into the output directory"
+    )]
+    checksums: bool,
+
+    #[arg(
+        long,
+        help = "After a successful build, keep only this many versions of each package"
+    )]
+    keep: Option<usize>,
+    #[arg(
+        long,
+        group = "version_change",
+        conflicts_with = "bump",
+        help = "Build with this version instead of the crate's"
+    )]
+    version: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        group = "version_change",
+        help = "Build with the crate's version raised by one"
+    )]
+    bump: Option<package::version::Bump>,
+
+    #[arg(
+        long,
+        requires = "version_change",
+        help = "Write the new version to Cargo.toml instead of only labelling the package with it"
+    )]
+    write_version: bool,
+
+    #[arg(
+        long,
+        requires = "write_version",
+        help = "Commit the written version and tag it v<version> once the packages are built"
+    )]
+    tag: bool,
+}
+
+#[derive(Debug, Args)]
+struct PublishArgs {
+    #[arg(required = true, help = ".deb files to publish")]
+    packages: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        required_unless_present = "backend",
+        conflicts_with = "backend",
+        help = "Local repository to publish to, a [publish.<name>] table in the config"
+    )]
+    repo: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Hosted service to upload to, configured under [upload]"
+    )]
+    backend: Option<debkit::publish::hosted::Backend>,
+
+    #[arg(
+        long,
+        conflicts_with = "backend",
+        help = "Sign the repository's Release file again afterwards"
+    )]
+    sign: bool,
+
+    #[arg(
+        long,
+        default_value_t = 3,
+        help = "Times to retry a failed upload to a hosted service"
+    )]
+    retries: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct PublishReport<'a> {
+    ok: bool,
+    /// The local repository or hosted service.
+    destination: &'a str,
+    packages: Vec<PublishedReport>,
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct PublishedReport {
+    path: PathBuf,
+    /// Where a hosted service serves the package from.
+    url: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct PackageChangelogArgs {
+    #[command(flatten)]
+    project: PackageProjectArgs,
+}
+
+#[derive(Debug, Args)]
+struct PackageCleanArgs {
+    #[command(flatten)]
+    project: PackageProjectArgs,
+
+    #[arg(long, default_value = "./dist")]
+    output_dir: PathBuf,
+
+    #[arg(
+        long,
+        default_value_t = package::clean::DEFAULT_KEEP,
+        help = "Versions of each package to keep"
+    )]
+    keep: usize,
+}
+
+#[derive(Debug, Args)]
+struct PackageSourceArgs {
+    #[command(flatten)]
+    project: PackageProjectArgs,
+
+    #[arg(long, default_value = "./dist")]
+    output_dir: PathBuf,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "vendored",
+        help = "Build offline from vendored dependencies, or with dh-cargo from packaged ones"
+    )]
+    rules: package::source::Rules,
+
+    #[arg(
+        long,
+        default_value = package::source::DEFAULT_DISTRIBUTION,
+        help = "Distribution the upload targets, such as an Ubuntu series for a PPA"
+    )]
+    distribution: String,
+
+    #[arg(long, help = "Sign the .dsc and .changes with gpg")]
+    sign: bool,
+
+    #[arg(
+        long,
+        requires = "sign",
+        help = "Key ID or fingerprint to sign with [default: packaging.signing_key]"
+    )]
+    key: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct PackageRpmArgs {
+    #[command(flatten)]
+    project: PackageProjectArgs,
+
+    #[arg(long, default_value = "release", help = "Cargo profile to build")]
+    profile: String,
+
+    #[arg(long, default_value = "./dist")]
+    output_dir: PathBuf,
+
+    #[arg(long, help = "RPM architecture, such as x86_64 or aarch64")]
+    arch: Option<String>,
+
+    #[arg(long, help = "Reinstall cargo-generate-rpm first")]
+    reinstall: bool,
+}
+
+#[derive(Debug, Args)]
+struct PackageArchiveArgs {
+    #[command(flatten)]
+    project: PackageProjectArgs,
+
+    // `--format` is the global text/json switch.
+    #[arg(long, value_enum, default_value = "tar.gz", help = "Archive format")]
+    archive_format: package::archive::Format,
+
+    #[arg(long, default_value = "release", help = "Cargo profile to build")]
+    profile: String,
+
+    #[arg(long, default_value = "./dist")]
+    output_dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct PackageAppimageArgs {
+    #[command(flatten)]
+    project: PackageProjectArgs,
+
+    #[arg(long, default_value = "release", help = "Cargo profile to build")]
+    profile: String,
+
+    #[arg(long, default_value = "./dist")]
+    output_dir: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "A .png or .svg icon; by default a placeholder"
+    )]
+    icon: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "appimagetool to use instead of the one on PATH or a downloaded one"
+    )]
+    appimagetool: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct PackageFlatpakArgs {
+    #[command(flatten)]
+    project: PackageProjectArgs,
+
+    #[arg(
+        long,
+        help = "Application ID such as io.github.me.tool [default: from a GitHub repository URL]"
+    )]
+    app_id: Option<String>,
+
+    #[arg(
+        long,
+        default_value = package::flatpak::DEFAULT_RUNTIME_VERSION,
+        help = "org.freedesktop.Platform version"
+    )]
+    runtime_version: String,
+
+    #[arg(long, default_value = "./dist")]
+    output_dir: PathBuf,
+
+    #[arg(long, help = "Run flatpak-builder and export a .flatpak bundle")]
+    build: bool,
 }
 
 fn main() {
+    completions::complete_from_env();
     if let Err(err) = run() {
+        if err.is::<output::Drift>() {
+            std::process::exit(2);
+        }
+        if output::is_json() && !err.is::<output::Reported>() {
+            let _ = output::emit(&output::ErrorReport::new(&err));
+        }
+        tracing::error!(target: logging::FILE_ONLY, "{err:#}");
         eprintln!("error: {err:#}");
         std::process::exit(1);
     }
 }
 
+#[derive(Debug, Serialize)]
+struct PathReport<'a> {
+    ok: bool,
+    path: &'a Path,
+}
+
+#[derive(Debug, Serialize)]
+struct PackageReport<'a> {
+    ok: bool,
+    artifacts: Vec<ArtifactReport<'a>>,
+    arch: Option<&'a str>,
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ArtifactReport<'a> {
+    path: &'a Path,
+    name: &'a str,
+    version: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct LogsReport {
+    ok: bool,
+    runs: Vec<audit::RunLog>,
+}
+
+#[derive(Debug, Serialize)]
+struct BackupListReport {
+    ok: bool,
+    backups: Vec<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct RestoreReport<'a> {
+    ok: bool,
+    file: &'a Path,
+    backup: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+struct RepoAddReport {
+    ok: bool,
+    #[serde(flatten)]
+    repo: repo::AddedRepo,
+}
+
+#[derive(Debug, Serialize)]
+struct RepoListReport {
+    ok: bool,
+    repos: Vec<repo::ListedRepo>,
+}
+
+#[derive(Debug, Serialize)]
+struct PinListReport {
+    ok: bool,
+    pins: Vec<pin::Pin>,
+    held: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RemoveReport<'a> {
+    ok: bool,
+    packages: &'a [String],
+    purged: bool,
+    autoremoved: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ChangedReport {
+    ok: bool,
+    changed: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ManReport<'a> {
+    ok: bool,
+    dir: &'a Path,
+    pages: &'a [PathBuf],
+}
+
 fn run() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    let level = match cli.log_level {
+        None if cli.quiet => logging::LogLevel::Error.into(),
+        level => logging::console_level(level, cli.verbose),
+    };
+    logging::init(level, cli.log_file.as_deref())?;
+    output::set_quiet(cli.quiet);
+    exec::set_dry_run(cli.dry_run);
+    exec::set_show_diff(cli.diff);
+    output::set_format(cli.format);
+    if !cli.dry_run {
+        audit::start(std::env::args().collect());
+    }
+    if let Some(path) = cli.config {
+        config::set_path(path);
+    }
+    offline::set_enabled(cli.offline);
+    if let Some(dir) = cli.apt_cache {
+        offline::set_apt_cache(dir);
+    }
+    if let Some(proxy) = cli.proxy {
+        if !proxy.is_empty() && !network::is_valid_proxy(&proxy) {
+            anyhow::bail!("--proxy must be an http:// or https:// URL, got `{proxy}`");
+        }
+        network::set_proxy(proxy);
+    }
+    confirm::set_assume_yes(cli.yes);
+
+    match cli.command {
+        Commands::Completions(args) => {
+            completions::write_script(args.shell, &mut std::io::stdout().lock())?;
+        }
+        Commands::HostConfig => {
+            write_host_config()?;
+        }
+        Commands::Init(args) => {
+            run_init(args)?;
+        }
+        Commands::Config(config) => {
+            run_config(config.command)?;
+        }
+        Commands::Configure(configure) => match configure.command {
+            ConfigureSubcommand::HostConfig => {
+                write_host_config()?;
+            }
+            ConfigureSubcommand::Target(target) => {
+                target.run()?;
+            }
+        },
+        Commands::Desktop(desktop) => match desktop.command {
+            DesktopSubcommand::Dump(args) => {
+                let profile = desktop::dconf::dump(&args.dir)?;
+                if output::is_json() {
+                    output::emit(&serde_json::json!({
+                        "ok": true,
+                        "dir": args.dir,
+                        "profile": profile,
+                    }))?;
+                } else {
+                    print!("{profile}");
+                }
+            }
+            DesktopSubcommand::Load(args) => {
+                desktop::dconf::load(&args.file, &args.dir)?;
+                if output::is_json() {
+                    output::emit(&PathReport {
+                        ok: true,
+                        path: &args.file,
+                    })?;
+                }
+                output::outcome(format!("Loaded {}", args.file.display()));
+            }
+        },
+        Commands::List(args) => {
+            if args.json {
+                output::set_format(output::Format::Json);
+            }
+            if args.available_updates {
+                install::list::run_updates()?;
+            } else {
+                install::list::run(args.status)?;
+            }
+        }
+        Commands::Logs(args) => {
+            if args.json {
+                output::set_format(output::Format::Json);
+            }
+            let runs = audit::runs(audit::read(&audit::path()?)?, args.last);
+            if output::is_json() {
+                output::emit(&LogsReport { ok: true, runs })?;
+            } else if runs.is_empty() {
+                println!("No DebKit runs have been logged yet.");
+            } else {
+                print!("{}", audit::render(&runs));
+            }
+        }
+        Commands::Repo(repo) => {
+            run_repo(repo.command)?;
+        }
+        Commands::Pin(pin) => {
+            run_pin(pin.command)?;
+        }
+        Commands::Hold(args) => {
+            set_held(&args.packages, true)?;
+        }
+        Commands::Unhold(args) => {
+            set_held(&args.packages, false)?;
+        }
+        Commands::Key(key) => match key.command {
+            KeySubcommand::Add(args) => {
+                let install =
+                    || keyring::install(&args.name, &args.url, args.fingerprint.as_deref());
+                confirm::ask(&format!("key add {}", args.name), install)?;
+                let path = install()?;
+                if output::is_json() {
+                    output::emit(&PathReport {
+                        ok: true,
+                        path: &path,
+                    })?;
+                }
+                output::outcome(format!("Installed key {}", path.display()));
+            }
+        },
+        Commands::Man(args) => {
+            let pages = manpages::write_all(&args.output_dir)?;
+            if output::is_json() {
+                output::emit(&ManReport {
+                    ok: true,
+                    dir: &args.output_dir,
+                    pages: &pages,
+                })?;
+            } else {
+                for page in &pages {
+                    println!("{}", page.display());
+                }
+            }
+        }
+        Commands::Package(pkg) => match pkg.command {
+            PackageSubcommand::Deb(args) => {
+                if args.install && args.arch.len() > 1 {
+                    anyhow::bail!("--install needs a single --arch");
+                }
+                let signing = package_signing(&args)?;
+                let artifacts = package::deb::run(package::deb::Options {
+                    manifest_path: args.project.manifest_path(),
+                    packages: args.project.package,
+                    all_members: args.project.all_members,
+                    release: args.release,
+                    output_dir: args.output_dir,
+                    name_template: args
+                        .name_template
+                        .as_deref()
+                        .map(|template| {
+                            package::naming::NameTemplate::parse(template, args.channel.clone())
+                        })
+                        .transpose()?,
+                    arches: args.arch.clone(),
+                    linker: args.linker,
+                    backend: args.backend,
+                    reinstall: args.reinstall,
+                    strip: match (args.strip, args.no_strip) {
+                        (true, _) => Some(true),
+                        (_, true) => Some(false),
+                        _ => None,
+                    },
+                    dbgsym: args.dbgsym,
+                    control: package::control::Overrides {
+                        maintainer: args.maintainer,
+                        depends: args.depends,
+                        description: args.description,
+                        section: args.section,
+                        priority: args.priority,
+                    },
+                    changelog: args.changelog,
+                    reproducible: args.reproducible,
+                    sbom: args.sbom.map(|format| package::sbom::Sbom {
+                        format,
+                        embed: args.embed_sbom,
+                    }),
+                    lint: args.lint,
+                    signing,
+                    verify: args.verify.map(|image| package::verify::Verify {
+                        image,
+                        engine: None,
+                    }),
+                    checksums: args.checksums,
+                    keep: args.keep,
+                    assets: args.assets,
+                    completions: Some(completions::write_all),
+                    man_pages: Some(manpages::write_all),
+                    version: match (args.version, args.bump) {
+                        (Some(version), _) => Some(package::version::Change::Set(version)),
+                        (_, Some(bump)) => Some(package::version::Change::Bump(bump)),
+                        _ => None,
+                    },
+                    write_version: args.write_version,
+                    tag: args.tag,
+                })?;
+                // Each .deb names its own architecture; the report only carries a
+                // single one.
+                let arch = match args.arch.as_slice() {
+                    [arch] => Some(arch.as_str()),
+                    _ => None,
+                };
+                report_artifacts(&artifacts, arch)?;
+                if args.install {
+                    install_built(&artifacts)?;
+                }
+            }
+            PackageSubcommand::Rpm(args) => {
+                let artifacts = package::rpm::run(package::rpm::Options {
+                    manifest_path: args.project.manifest_path(),
+                    packages: args.project.package,
+                    all_members: args.project.all_members,
+                    profile: args.profile,
+                    output_dir: args.output_dir,
+                    arch: args.arch.clone(),
+                    reinstall: args.reinstall,
+                })?;
+                report_artifacts(&artifacts, args.arch.as_deref())?;
+            }
+            PackageSubcommand::Archive(args) => {
+                let artifacts = package::archive::run(package::archive::Options {
+                    manifest_path: args.project.manifest_path(),
+                    packages: args.project.package,
+                    all_members: args.project.all_members,
+                    format: args.archive_format,
+                    profile: args.profile,
+                    output_dir: args.output_dir,
+                    completions: Some(completions::write_all),
+                    man_pages: Some(manpages::write_all),
+                })?;
+                report_artifacts(&artifacts, None)?;
+            }
+            PackageSubcommand::Appimage(args) => {
+                let artifacts = package::appimage::run(package::appimage::Options {
+                    manifest_path: args.project.manifest_path(),
+                    packages: args.project.package,
+                    all_members: args.project.all_members,
+                    profile: args.profile,
+                    output_dir: args.output_dir,
+                    icon: args.icon,
+                    appimagetool: args.appimagetool,
+                })?;
+                report_artifacts(&artifacts, None)?;
+            }
+            PackageSubcommand::Flatpak(args) => {
+                let artifacts = package::flatpak::run(package::flatpak::Options {
+                    manifest_path: args.project.manifest_path(),
+                    packages: args.project.package,
+                    all_members: args.project.all_members,
+                    app_id: args.app_id,
+                    runtime_version: args.runtime_version,
+                    output_dir: args.output_dir,
+                    build: args.build,
+                })?;
+                report_artifacts(&artifacts, None)?;
+            }
+            PackageSubcommand::Changelog(args) => {
+                let changelogs = package::changelog::run(package::changelog::Options {
+                    manifest_path: args.project.manifest_path(),
+                    packages: args.project.package,
+                    all_members: args.project.all_members,
+                })?;
+                report_artifacts(&changelogs, None)?;
+            }
+            PackageSubcommand::Clean(args) => {
+                let removed = package::clean::run(package::clean::Options {
+                    manifest_path: args.project.manifest_path(),
+                    packages: args.project.package,
+                    all_members: args.project.all_members,
+                    output_dir: args.output_dir,
+                    keep: args.keep,
+                })?;
+                if output::is_json() {
+                    output::emit(&CleanReport {
+                        ok: true,
+                        removed: &removed,
+                        dry_run: exec::is_dry_run(),
+                    })?;
+                } else {
+                    for path in &removed {
+                        println!("{}", path.display());
+                    }
+                }
+                output::outcome(format!("Removed {} files", removed.len()));
+            }
+            PackageSubcommand::Source(args) => {
+                let key = if args.sign {
+                    signing_key(&args.key)?
+                } else {
+                    None
+                };
+                let files = package::source::run(package::source::Options {
+                    manifest_path: args.project.manifest_path(),
+                    packages: args.project.package,
+                    all_members: args.project.all_members,
+                    output_dir: args.output_dir,
+                    rules: args.rules,
+                    distribution: args.distribution,
+                    sign: args.sign,
+                    key,
+                })?;
+                report_artifacts(&files, Some("source"))?;
+            }
+        },
+        Commands::Install(install) => match install.command {
+            InstallSubcommand::Deb(args) => {
+                let install = || apt::install_local(&args.file, args.allow_downgrade);
+                confirm::ask(&format!("install deb {}", args.file.display()), install)?;
+                install()?;
+                if output::is_json() {
+                    output::emit(&PathReport {
+                        ok: true,
+                        path: &args.file,
+                    })?;
+                }
+                output::outcome(format!("Installed {}", args.file.display()));
+            }
+            InstallSubcommand::Target(target) => {
+                target.run()?;
+            }
+        },
+        Commands::Uninstall(uninstall) => {
+            uninstall.command.run()?;
+        }
+        Commands::Remove(args) => {
+            let packages = args.packages.iter().map(String::as_str).collect::<Vec<_>>();
+            let remove = || {
+                if args.purge {
+                    apt::purge(&packages)?;
+                } else {
+                    apt::remove(&packages)?;
+                }
+                if args.autoremove {
+                    apt::autoremove()?;
+                }
+                anyhow::Ok(())
+            };
+            confirm::ask(&format!("remove {}", args.packages.join(" ")), remove)?;
+            remove()?;
+            if output::is_json() {
+                output::emit(&RemoveReport {
+                    ok: true,
+                    packages: &args.packages,
+                    purged: args.purge,
+                    autoremoved: args.autoremove,
+                })?;
+            }
+            output::outcome(format!(
+                "{} {}",
+                if args.purge { "Purged" } else { "Removed" },
+                args.packages.join(" ")
+            ));
+        }
+        Commands::Restore(args) => {
+            run_restore(&args)?;
+        }
+        Commands::Status(status) => match status.command {
+            StatusSubcommand::All => {
+                install::status::run_all()?;
+            }
+            StatusSubcommand::Target(target) => {
+                target.run()?;
+            }
+        },
+        Commands::Itself(itself) => match itself.command {
+            SelfSubcommand::Update(args) => {
+                run_self_update(args)?;
+            }
+        },
+        Commands::Verify(args) => {
+            run_verify(args)?;
+        }
+        Commands::Publish(args) => {
+            run_publish(args)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn run_publish(args: PublishArgs) -> anyhow::Result<()> {
+    let config = config::load_or_init(&config::path()?)?;
+    let (destination, packages) = match (&args.repo, args.backend) {
+        (_, Some(backend)) => {
+            use debkit::publish::hosted;
+            hosted::ensure_ready(backend, &config.upload)?;
+            let mut packages = Vec::new();
+            for path in &args.packages {
+                let path = package::absolute_path(path)?;
+                let url = hosted::upload(backend, &config.upload, &path, args.retries)?;
+                say!("Uploaded {} to {url}", path.display());
+                packages.push(PublishedReport {
+                    path,
+                    url: Some(url),
+                });
+            }
+            (backend.name().to_string(), packages)
+        }
+        (Some(name), None) => {
+            let Some(repo) = config.publish.get(name) else {
+                let known = config.publish.keys().cloned().collect::<Vec<_>>();
+                anyhow::bail!(
+                    "no repository `{name}` in the config; add a [publish.{name}] table{}",
+                    if known.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" or use one of {}", known.join(", "))
+                    }
+                );
+            };
+            let signing_key = [&repo.signing_key, &config.packaging.signing_key]
+                .into_iter()
+                .find(|key| !key.is_empty());
+            let packages = debkit::publish::run(debkit::publish::Options {
+                name,
+                repo,
+                packages: &args.packages,
+                sign: args.sign,
+                signing_key: signing_key.map(String::as_str),
+            })?;
+            let packages = packages
+                .into_iter()
+                .map(|path| PublishedReport { path, url: None })
+                .collect();
+            (name.clone(), packages)
+        }
+        (None, None) => unreachable!("clap requires --repo or --backend"),
+    };
+    if output::is_json() {
+        return output::emit(&PublishReport {
+            ok: true,
+            destination: &destination,
+            packages,
+            dry_run: exec::is_dry_run(),
+        });
+    }
+    Ok(())
+}
+
+/// How `package deb --sign` signs, with the key from the config unless `--key` names
+/// one.
+fn package_signing(args: &PackageDebArgs) -> anyhow::Result<Option<package::sign::Signing>> {
+    if !args.sign {
+        return Ok(None);
+    }
+    Ok(Some(package::sign::Signing {
+        signer: args.signer,
+        key: signing_key(&args.key)?,
+    }))
+}
+
+/// `key`, or else `packaging.signing_key` from the config when it is set.
+fn signing_key(key: &Option<String>) -> anyhow::Result<Option<String>> {
+    if key.is_some() {
+        return Ok(key.clone());
+    }
+    let config = config::load_or_init(&config::path()?)?;
+    Ok(Some(config.packaging.signing_key).filter(|key| !key.is_empty()))
+}
+
+fn report_artifacts(artifacts: &[package::Artifact], arch: Option<&str>) -> anyhow::Result<()> {
+    if output::is_json() {
+        return output::emit(&PackageReport {
+            ok: true,
+            artifacts: artifacts
+                .iter()
+                .map(|artifact| ArtifactReport {
+                    path: &artifact.path,
+                    name: &artifact.name,
+                    version: &artifact.version,
+                })
+                .collect(),
+            arch,
+            dry_run: exec::is_dry_run(),
+        });
+    }
+    for artifact in artifacts {
+        println!("{}", artifact.path.display());
+    }
+    Ok(())
+}
+
+/// Installs the packages `package deb` built, leaving out debug symbols, and says
+/// what dpkg then reports for each.
+fn install_built(artifacts: &[package::Artifact]) -> anyhow::Result<()> {
+    for artifact in artifacts
+        .iter()
+        .filter(|artifact| !artifact.name.ends_with("-dbgsym"))
+    {
+        if exec::is_dry_run() {
+            say!(
+                "Dry run: would install {} {}.",
+                artifact.name,
+                artifact.version
+            );
+            continue;
+        }
+        let install = || apt::reinstall_local(&artifact.path);
+        confirm::ask(&format!("install deb {}", artifact.path.display()), install)?;
+        install()?;
+        let Some(status) = apt::package_status(&artifact.name) else {
+            anyhow::bail!("dpkg does not know {} after installing it", artifact.name);
+        };
+        say!("{} {status}", artifact.name);
+        output::outcome(format!("Installed {} {status}", artifact.name));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct CleanReport<'a> {
+    ok: bool,
+    removed: &'a [PathBuf],
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct InitReport<'a> {
+    ok: bool,
+    path: &'a Path,
+    metadata: &'a package::init::Metadata,
+}
+
+fn run_init(args: InitArgs) -> anyhow::Result<()> {
+    let metadata = package::init::ask(package::init::defaults(&args.manifest_path)?)?;
+    package::init::write(&args.manifest_path, &metadata, args.force)?;
+    if output::is_json() {
+        return output::emit(&InitReport {
+            ok: true,
+            path: &args.manifest_path,
+            metadata: &metadata,
+        });
+    }
+    let summary = format!(
+        "Added [package.metadata.deb] for {} to {}",
+        metadata.name,
+        args.manifest_path.display()
+    );
+    say!("{summary}.");
+    output::outcome(summary);
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct SelfUpdateReport<'a> {
+    ok: bool,
+    current: &'a str,
+    latest: &'a str,
+    available: bool,
+    updated: bool,
+}
+
+fn run_self_update(args: SelfUpdateArgs) -> anyhow::Result<()> {
+    let config = config::load_or_init(&config::path()?)?;
+    let release = self_update::latest(&config.self_update, !args.check)?;
+    let available = self_update::is_newer(&release)?;
+    let (current, latest) = (self_update::CURRENT_VERSION, release.version.as_str());
+    let updated = available && !args.check;
+    if updated {
+        let update = || self_update::update(&release);
+        confirm::ask(&format!("self update to {latest}"), update).and_then(|()| update())?;
+    }
+
+    if output::is_json() {
+        return output::emit(&SelfUpdateReport {
+            ok: true,
+            current,
+            latest,
+            available,
+            updated,
+        });
+    }
+    let summary = if updated {
+        format!("Updated DebKit from {current} to {latest}")
+    } else if available {
+        format!("DebKit {latest} is available (running {current}); run `debkit self update`")
+    } else {
+        format!("DebKit {current} is up to date (latest release: {latest})")
+    };
+    say!("{summary}.");
+    output::outcome(summary);
+    Ok(())
+}
+
+fn run_verify(args: VerifyArgs) -> anyhow::Result<()> {
+    if install::find(install::Action::Install, &args.target).is_none() {
+        anyhow::bail!("unknown target `{}`; see `debkit list`", args.target);
+    }
+    // Creates the config on first use, so there is always one to copy.
+    let path = config::path()?;
+    config::load_or_init(&path)?;
+    let report = verify::run(
+        &verify::Options {
+            target: &args.target,
+            args: &args.args,
+            image: &args.image,
+            engine: args.engine.as_deref(),
+            keep: args.keep,
+        },
+        &path,
+    )?;
+    let summary = match &report.error {
+        Some(error) => format!("install {} failed on {}: {error}", args.target, args.image),
+        None => format!("install {} succeeded on {}", args.target, args.image),
+    };
+    if output::is_json() {
+        output::emit(&report)?;
+        if !report.ok {
+            return Err(output::Reported(summary).into());
+        }
+        return Ok(());
+    }
+    if !report.ok {
+        anyhow::bail!(summary);
+    }
+    say!("Verified: {summary}.");
+    output::outcome(summary);
+    Ok(())
+}
+
+fn write_host_config() -> anyhow::Result<()> {
+    let path = config::configure_complete(&config::path()?)?;
+    if output::is_json() {
+        return output::emit(&PathReport {
+            ok: true,
+            path: &path,
+        });
+    }
+    println!("Wrote host override config: {}", path.display());
+    Ok(())
+}
 
-    match cli.command {
-        Commands::HostConfig => {
-            write_host_config()?;
-        }
-        Commands::Configure(configure) => match configure.command {
-            ConfigureSubcommand::HostConfig => {
-                write_host_config()?;
+fn run_repo(command: RepoSubcommand) -> anyhow::Result<()> {
+    match command {
+        RepoSubcommand::Add(args) => {
+            let add = || {
+                repo::add(
+                    &args.source,
+                    repo::AddOptions {
+                        name: args.name.as_deref(),
+                        key_url: args.key.as_deref(),
+                        fingerprint: args.fingerprint.as_deref(),
+                        suite: args.suite.as_deref(),
+                    },
+                )
+            };
+            confirm::ask(&format!("repo add {}", args.source), add)?;
+            let added = add()?;
+            output::outcome(format!("Added {}", added.path.display()));
+            if output::is_json() {
+                output::emit(&RepoAddReport {
+                    ok: true,
+                    repo: added,
+                })?;
             }
-            ConfigureSubcommand::GitPrompt => {
-                install::git_prompt::run()?;
+        }
+        RepoSubcommand::Remove(args) => {
+            let remove = || repo::remove(&args.name);
+            confirm::ask(&format!("repo remove {}", args.name), remove)?;
+            let path = remove()?;
+            if output::is_json() {
+                output::emit(&PathReport {
+                    ok: true,
+                    path: &path,
+                })?;
             }
-            ConfigureSubcommand::Nis(args) => match args.command {
-                Some(ConfigureNisSubcommand::AddSlave(add)) => {
-                    let result = config::add_nis_slave_to_host(&add.host, &add.slave)?;
-                    if result.added {
-                        println!("Added {} to {} NIS slave list.", add.slave, add.host);
-                    } else {
-                        println!(
-                            "{} is already present in {} NIS slave list.",
-                            add.slave, add.host
-                        );
-                    }
-                    println!("\nNext:");
-                    println!(
-                        "  1. Run `debkit configure nis` on {} to rebuild ypservers and push maps if enabled.",
-                        add.host
-                    );
-                    println!(
-                        "  2. Run `debkit install nis` on {} if it has not already been configured as a slave.",
-                        add.slave
-                    );
+            output::outcome(format!("Removed {}", path.display()));
+        }
+        RepoSubcommand::List => {
+            let repos = repo::list()?;
+            if output::is_json() {
+                output::emit(&RepoListReport { ok: true, repos })?;
+            } else if repos.is_empty() {
+                println!("No sources in {}.", repo::SOURCES_DIR);
+            } else {
+                println!("{:<32} {:<8} {:<24} URIS", "NAME", "MANAGED", "SUITES");
+                for repo in &repos {
                     println!(
-                        "  3. Run `debkit configure nis` on {} to force-refresh stale slave maps.",
-                        add.slave
+                        "{:<32} {:<8} {:<24} {}",
+                        repo.name,
+                        if repo.managed { "yes" } else { "no" },
+                        repo.suites.join(" "),
+                        repo.uris.join(" ")
                     );
-                    println!("\nUpdated: {}", result.path.display());
-                }
-                None => {
-                    let config = config::load_or_init()?;
-                    install::nis::configure(&config.nis)?;
                 }
-            },
-        },
-        Commands::List => {
-            install::list::run();
-        }
-        Commands::Package(pkg) => match pkg.command {
-            PackageSubcommand::Deb(args) => {
-                let output = package::deb::run(package::deb::Options {
-                    release: args.release,
-                    output_dir: args.output_dir,
-                    arch: args.arch,
-                    verbose: args.verbose,
-                    reinstall: args.reinstall,
-                })?;
-                println!("{}", output.display());
-            }
-        },
-        Commands::Install(install) => match install.command {
-            InstallSubcommand::Codex(args) => {
-                install::codex::run(args.node_version)?;
-            }
-            InstallSubcommand::Essentials => {
-                let config = config::load_or_init()?;
-                install::essentials::run(&config.essentials)?;
-            }
-            InstallSubcommand::Git => {
-                install::git::run()?;
-            }
-            InstallSubcommand::Nis => {
-                let config = config::load_or_init()?;
-                install::nis::run(install::nis::Role::Configured, &config.nis)?;
             }
-            InstallSubcommand::NisClient => {
-                let config = config::load_or_init()?;
-                install::nis::run(install::nis::Role::Client, &config.nis)?;
-            }
-            InstallSubcommand::NisServer => {
-                let config = config::load_or_init()?;
-                install::nis::run(install::nis::Role::Server, &config.nis)?;
-            }
-            InstallSubcommand::Npm(args) => {
-                install::npm::run(install::npm::Options {
-                    version: args.version,
-                })?;
-            }
-            InstallSubcommand::Ripgrep => {
-                install::ripgrep::run()?;
+        }
+    }
+    Ok(())
+}
+
+fn run_pin(command: PinSubcommand) -> anyhow::Result<()> {
+    let changed = match command {
+        PinSubcommand::Set(args) => {
+            let set = || pin::set(&args.package, &args.version);
+            confirm::ask(&format!("pin set {}", args.package), set)?;
+            let changed = set()?;
+            output::outcome(if changed {
+                format!("Pinned {} to {}", args.package, args.version)
+            } else {
+                format!("{} is already pinned to {}", args.package, args.version)
+            });
+            changed
+        }
+        PinSubcommand::Remove(args) => {
+            let remove = || pin::remove(&args.package);
+            confirm::ask(&format!("pin remove {}", args.package), remove)?;
+            let removed = remove()?;
+            if !removed {
+                say!("{} is not pinned by DebKit.", args.package);
             }
-            InstallSubcommand::Rust(args) => {
-                install::rust::run(install::rust::Options {
-                    reinstall: args.reinstall,
+            output::outcome(if removed {
+                format!("Unpinned {}", args.package)
+            } else {
+                format!("{} is not pinned by DebKit", args.package)
+            });
+            removed
+        }
+        PinSubcommand::List => {
+            let pins = pin::list()?;
+            let held = pin::held()?;
+            if output::is_json() {
+                output::emit(&PinListReport {
+                    ok: true,
+                    pins,
+                    held,
                 })?;
+                return Ok(());
             }
-            InstallSubcommand::SudoNopass => {
-                let config = config::load_or_init()?;
-                install::sudo_nopass::run(&config.sudo_nopass)?;
-                install::nis::rebuild_and_push_maps(&config.nis)?;
+            if pins.is_empty() {
+                println!("No packages are pinned by DebKit.");
             }
-            InstallSubcommand::Variety => {
-                let config = config::load_or_init()?;
-                install::variety::run(&config)?;
+            for pin in &pins {
+                println!("{} {}", pin.package, pin.version);
             }
-            InstallSubcommand::Foundation => {
-                let config = config::load_or_init()?;
-                install::foundation::run(&config)?;
+            if !held.is_empty() {
+                println!("Held: {}", held.join(" "));
             }
-            InstallSubcommand::WakeOnLan(args) => {
-                let config = config::load_or_init()?;
-                if args.dry_run {
-                    install::wake_on_lan::dry_run(&config)?;
-                } else {
-                    install::wake_on_lan::run(&config)?;
-                }
+            return Ok(());
+        }
+    };
+    if output::is_json() {
+        output::emit(&ChangedReport { ok: true, changed })?;
+    }
+    Ok(())
+}
+
+fn run_restore(args: &RestoreArgs) -> anyhow::Result<()> {
+    if args.list {
+        let backups = backup::list(&args.file)?;
+        if output::is_json() {
+            output::emit(&BackupListReport { ok: true, backups })?;
+        } else if backups.is_empty() {
+            println!("There are no backups of {}.", args.file.display());
+        } else {
+            for backup in &backups {
+                println!("{}", backup.display());
             }
-        },
-        Commands::Uninstall(uninstall) => match uninstall.command {
-            UninstallSubcommand::Codex => {
-                install::codex::uninstall()?;
+        }
+        return Ok(());
+    }
+
+    let backup = backup::restore(&args.file)?;
+    say!("Restored {} from {}", args.file.display(), backup.display());
+    if output::is_json() {
+        output::emit(&RestoreReport {
+            ok: true,
+            file: &args.file,
+            backup,
+        })?;
+    }
+    output::outcome(format!("Restored {}", args.file.display()));
+    Ok(())
+}
+
+fn set_held(packages: &[String], hold: bool) -> anyhow::Result<()> {
+    let verb = if hold { "hold" } else { "unhold" };
+    confirm::ask(&format!("{verb} {}", packages.join(" ")), || {
+        packages
+            .iter()
+            .try_for_each(|package| pin::set_held(package, hold).map(drop))
+    })?;
+    let mut changed = false;
+    for package in packages {
+        if pin::set_held(package, hold)? {
+            changed = true;
+        } else {
+            say!(
+                "{package} is already {}.",
+                if hold { "held" } else { "not held" }
+            );
+        }
+    }
+    if output::is_json() {
+        output::emit(&ChangedReport { ok: true, changed })?;
+    }
+    output::outcome(format!(
+        "{} {}",
+        if hold { "Held" } else { "Released" },
+        packages.join(" ")
+    ));
+    Ok(())
+}
+
+fn run_config(command: ConfigSubcommand) -> anyhow::Result<()> {
+    let path = config::path()?;
+    match command {
+        ConfigSubcommand::Get(args) => {
+            let value = config::get(&path, &args.key)?;
+            if output::is_json() {
+                return output::emit(&serde_json::json!({
+                    "ok": true,
+                    "key": args.key,
+                    "value": toml_to_json(&value),
+                }));
             }
-            UninstallSubcommand::Npm => {
-                install::npm::uninstall()?;
+            match value.as_str() {
+                Some(text) => println!("{text}"),
+                None => println!("{}", value.clone().decorated("", "")),
             }
-            UninstallSubcommand::Ripgrep => {
-                install::ripgrep::uninstall()?;
+        }
+        ConfigSubcommand::Set(args) => {
+            config::set(&path, &args.key, &args.value)?;
+            if output::is_json() {
+                return output::emit(&PathReport {
+                    ok: true,
+                    path: &path,
+                });
             }
-        },
-        Commands::Status(status) => match status.command {
-            StatusSubcommand::Variety => {
-                let config = config::load_or_init()?;
-                install::variety::print_status(&config)?;
+            println!("Set {} in {}", args.key, path.display());
+        }
+        ConfigSubcommand::Validate(args) => {
+            return validate_config(args.strict);
+        }
+        ConfigSubcommand::Unset(args) => {
+            let removed = config::unset(&path, &args.key)?;
+            if output::is_json() {
+                return output::emit(&serde_json::json!({
+                    "ok": true,
+                    "key": args.key,
+                    "removed": removed,
+                }));
             }
-            StatusSubcommand::WakeOnLan => {
-                let config = config::load_or_init()?;
-                install::wake_on_lan::print_status(&config)?;
+            if removed {
+                println!("Unset {}; the default applies again", args.key);
+            } else {
+                println!("{} is not set; the default already applies", args.key);
             }
-        },
+        }
     }
-
     Ok(())
 }
 
-fn write_host_config() -> anyhow::Result<()> {
-    let path = config::configure_complete_for_current_host()?;
-    println!("Wrote host override config: {}", path.display());
+#[derive(Debug, Serialize)]
+struct ValidateReport<'a> {
+    ok: bool,
+    errors: usize,
+    warnings: usize,
+    diagnostics: &'a [config::Diagnostic],
+}
+
+fn validate_config(strict: bool) -> anyhow::Result<()> {
+    let diagnostics = config::check(&config::path()?, |name| {
+        install::find(install::Action::Install, name)
+            .is_some_and(|target| target.name() != "foundation")
+    })?;
+    let count = |severity| {
+        diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    };
+    let (errors, warnings) = (
+        count(config::Severity::Error),
+        count(config::Severity::Warning),
+    );
+    let ok = errors == 0 && (!strict || warnings == 0);
+
+    if output::is_json() {
+        output::emit(&ValidateReport {
+            ok,
+            errors,
+            warnings,
+            diagnostics: &diagnostics,
+        })?;
+    } else {
+        for diagnostic in &diagnostics {
+            println!("{diagnostic}");
+        }
+        if diagnostics.is_empty() {
+            println!("Config is valid.");
+        }
+    }
+    if !ok {
+        return Err(output::Reported(format!(
+            "config has {errors} error(s) and {warnings} warning(s)"
+        ))
+        .into());
+    }
     Ok(())
 }
 
+fn toml_to_json(value: &toml_edit::Value) -> serde_json::Value {
+    use toml_edit::Value;
+    match value {
+        Value::String(text) => text.value().as_str().into(),
+        Value::Integer(number) => (*number.value()).into(),
+        Value::Float(number) => (*number.value()).into(),
+        Value::Boolean(flag) => (*flag.value()).into(),
+        Value::Datetime(datetime) => datetime.value().to_string().into(),
+        Value::Array(array) => array.iter().map(toml_to_json).collect(),
+        Value::InlineTable(table) => table
+            .iter()
+            .map(|(key, value)| (key.to_string(), toml_to_json(value)))
+            .collect(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -333,22 +2025,140 @@ mod tests {
     #[test]
     fn parses_install_variety() {
         let cli = Cli::try_parse_from(["debkit", "install", "variety"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Install(InstallCommand { command: InstallSubcommand::Target(command) }) if command.target().name() == "variety"
+        ));
+    }
+
+    #[test]
+    fn parses_install_deb() {
+        let cli = Cli::try_parse_from([
+            "debkit",
+            "install",
+            "deb",
+            "./dist/debkit_0.2.0_amd64.deb",
+            "--allow-downgrade",
+        ])
+        .unwrap();
         assert!(matches!(
             cli.command,
             Commands::Install(InstallCommand {
-                command: InstallSubcommand::Variety
+                command: InstallSubcommand::Deb(InstallDebArgs { file, allow_downgrade: true })
+            }) if file == Path::new("./dist/debkit_0.2.0_amd64.deb")
+        ));
+    }
+
+    #[test]
+    fn parses_remove_with_purge() {
+        let cli = Cli::try_parse_from([
+            "debkit",
+            "remove",
+            "cowsay",
+            "sl",
+            "--purge",
+            "--autoremove",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Remove(RemoveArgs { packages, purge: true, autoremove: true })
+                if packages == ["cowsay", "sl"]
+        ));
+        assert!(Cli::try_parse_from(["debkit", "remove"]).is_err());
+    }
+
+    #[test]
+    fn parses_restore_with_list() {
+        let cli = Cli::try_parse_from(["debkit", "restore", "/home/me/.bashrc", "--list"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Restore(RestoreArgs { file, list: true }) if file == Path::new("/home/me/.bashrc")
+        ));
+    }
+
+    #[test]
+    fn parses_logging_flags() {
+        let cli = Cli::try_parse_from(["debkit", "install", "variety", "-vv"]).unwrap();
+        assert_eq!(cli.verbose, 2);
+        let cli = Cli::try_parse_from([
+            "debkit",
+            "--log-level",
+            "debug",
+            "--log-file",
+            "/tmp/debkit.log",
+            "status",
+            "all",
+        ])
+        .unwrap();
+        assert_eq!(cli.log_level, Some(logging::LogLevel::Debug));
+        assert_eq!(cli.log_file.as_deref(), Some(Path::new("/tmp/debkit.log")));
+        assert!(Cli::try_parse_from(["debkit", "--log-level", "loud", "status", "all"]).is_err());
+    }
+
+    #[test]
+    fn parses_verify_with_target_options() {
+        let cli = Cli::try_parse_from([
+            "debkit",
+            "verify",
+            "--image",
+            "ubuntu:24.04",
+            "--",
+            "--keep-going",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Verify(VerifyArgs { target, image, engine: None, keep: false, args })
+                if target == "foundation" && image == "ubuntu:24.04" && args == ["--keep-going"]
+        ));
+        assert!(Cli::try_parse_from(["debkit", "verify", "git", "--engine", "lxc"]).is_err());
+    }
+
+    #[test]
+    fn parses_init() {
+        let cli = Cli::try_parse_from(["debkit", "init", "--force"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Init(InitArgs { manifest_path, force: true })
+                if manifest_path == Path::new("Cargo.toml")
+        ));
+    }
+
+    #[test]
+    fn parses_self_update() {
+        let cli = Cli::try_parse_from(["debkit", "self", "update", "--check"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Itself(SelfCommand {
+                command: SelfSubcommand::Update(SelfUpdateArgs { check: true })
             })
         ));
     }
 
+    #[test]
+    fn quiet_conflicts_with_verbose() {
+        let cli = Cli::try_parse_from(["debkit", "install", "variety", "-q"]).unwrap();
+        assert!(cli.quiet);
+        assert!(Cli::try_parse_from(["debkit", "-q", "-v", "install", "variety"]).is_err());
+    }
+
+    #[test]
+    fn parses_yes_anywhere() {
+        let cli = Cli::try_parse_from(["debkit", "-y", "install", "variety"]).unwrap();
+        assert!(cli.yes);
+        let cli = Cli::try_parse_from(["debkit", "remove", "sl", "--yes"]).unwrap();
+        assert!(cli.yes);
+        let cli = Cli::try_parse_from(["debkit", "remove", "sl"]).unwrap();
+        assert!(!cli.yes);
+    }
+
     #[test]
     fn parses_install_codex() {
         let cli = Cli::try_parse_from(["debkit", "install", "codex"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Install(InstallCommand {
-                command: InstallSubcommand::Codex(_)
-            })
+            Commands::Install(InstallCommand { command: InstallSubcommand::Target(command) }) if command.target().name() == "codex"
         ));
     }
 
@@ -357,9 +2167,7 @@ mod tests {
         let cli = Cli::try_parse_from(["debkit", "install", "essentials"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Install(InstallCommand {
-                command: InstallSubcommand::Essentials
-            })
+            Commands::Install(InstallCommand { command: InstallSubcommand::Target(command) }) if command.target().name() == "essentials"
         ));
     }
 
@@ -368,9 +2176,7 @@ mod tests {
         let cli = Cli::try_parse_from(["debkit", "install", "git"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Install(InstallCommand {
-                command: InstallSubcommand::Git
-            })
+            Commands::Install(InstallCommand { command: InstallSubcommand::Target(command) }) if command.target().name() == "git"
         ));
     }
 
@@ -379,9 +2185,7 @@ mod tests {
         let cli = Cli::try_parse_from(["debkit", "install", "npm"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Install(InstallCommand {
-                command: InstallSubcommand::Npm(_)
-            })
+            Commands::Install(InstallCommand { command: InstallSubcommand::Target(command) }) if command.target().name() == "npm"
         ));
     }
 
@@ -390,9 +2194,7 @@ mod tests {
         let cli = Cli::try_parse_from(["debkit", "install", "nis"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Install(InstallCommand {
-                command: InstallSubcommand::Nis
-            })
+            Commands::Install(InstallCommand { command: InstallSubcommand::Target(command) }) if command.target().name() == "nis"
         ));
     }
 
@@ -401,9 +2203,7 @@ mod tests {
         let cli = Cli::try_parse_from(["debkit", "install", "nis-client"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Install(InstallCommand {
-                command: InstallSubcommand::NisClient
-            })
+            Commands::Install(InstallCommand { command: InstallSubcommand::Target(command) }) if command.target().name() == "nis-client"
         ));
     }
 
@@ -412,9 +2212,7 @@ mod tests {
         let cli = Cli::try_parse_from(["debkit", "install", "nis-server"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Install(InstallCommand {
-                command: InstallSubcommand::NisServer
-            })
+            Commands::Install(InstallCommand { command: InstallSubcommand::Target(command) }) if command.target().name() == "nis-server"
         ));
     }
 
@@ -425,8 +2223,9 @@ mod tests {
         assert!(matches!(
             cli.command,
             Commands::Install(InstallCommand {
-                command: InstallSubcommand::Npm(InstallNpmArgs { version })
-            }) if version == "24.12.0"
+                command: InstallSubcommand::Target(command)
+            }) if command.target().name() == "npm"
+                && command.context().value("version") == Some("24.12.0")
         ));
     }
 
@@ -437,8 +2236,9 @@ mod tests {
         assert!(matches!(
             cli.command,
             Commands::Install(InstallCommand {
-                command: InstallSubcommand::Codex(InstallCodexArgs { node_version })
-            }) if node_version == "latest"
+                command: InstallSubcommand::Target(command)
+            }) if command.target().name() == "codex"
+                && command.context().value("node-version") == Some("latest")
         ));
     }
 
@@ -447,9 +2247,7 @@ mod tests {
         let cli = Cli::try_parse_from(["debkit", "install", "ripgrep"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Install(InstallCommand {
-                command: InstallSubcommand::Ripgrep
-            })
+            Commands::Install(InstallCommand { command: InstallSubcommand::Target(command) }) if command.target().name() == "ripgrep"
         ));
     }
 
@@ -458,9 +2256,7 @@ mod tests {
         let cli = Cli::try_parse_from(["debkit", "install", "sudo-nopass"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Install(InstallCommand {
-                command: InstallSubcommand::SudoNopass
-            })
+            Commands::Install(InstallCommand { command: InstallSubcommand::Target(command) }) if command.target().name() == "sudo-nopass"
         ));
     }
 
@@ -469,9 +2265,7 @@ mod tests {
         let cli = Cli::try_parse_from(["debkit", "install", "foundation"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Install(InstallCommand {
-                command: InstallSubcommand::Foundation
-            })
+            Commands::Install(InstallCommand { command: InstallSubcommand::Target(command) }) if command.target().name() == "foundation"
         ));
     }
 
@@ -480,20 +2274,19 @@ mod tests {
         let cli = Cli::try_parse_from(["debkit", "install", "wake-on-lan"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Install(InstallCommand {
-                command: InstallSubcommand::WakeOnLan(_)
-            })
+            Commands::Install(InstallCommand { command: InstallSubcommand::Target(command) }) if command.target().name() == "wake-on-lan"
         ));
     }
 
     #[test]
     fn parses_install_wake_on_lan_dry_run() {
         let cli = Cli::try_parse_from(["debkit", "install", "wake-on-lan", "--dry-run"]).unwrap();
+        assert!(cli.dry_run);
         assert!(matches!(
             cli.command,
             Commands::Install(InstallCommand {
-                command: InstallSubcommand::WakeOnLan(InstallWakeOnLanArgs { dry_run: true })
-            })
+                command: InstallSubcommand::Target(command)
+            }) if command.target().name() == "wake-on-lan"
         ));
     }
 
@@ -503,8 +2296,8 @@ mod tests {
         assert!(matches!(
             cli.command,
             Commands::Configure(ConfigureCommand {
-                command: ConfigureSubcommand::GitPrompt
-            })
+                command: ConfigureSubcommand::Target(target)
+            }) if target.target().name() == "git-prompt"
         ));
     }
 
@@ -514,8 +2307,8 @@ mod tests {
         assert!(matches!(
             cli.command,
             Commands::Configure(ConfigureCommand {
-                command: ConfigureSubcommand::Nis(ConfigureNisArgs { command: None })
-            })
+                command: ConfigureSubcommand::Target(target)
+            }) if target.target().name() == "nis" && target.context().subcommand().is_none()
         ));
     }
 
@@ -534,10 +2327,12 @@ mod tests {
         assert!(matches!(
             cli.command,
             Commands::Configure(ConfigureCommand {
-                command: ConfigureSubcommand::Nis(ConfigureNisArgs {
-                    command: Some(ConfigureNisSubcommand::AddSlave(AddNisSlaveArgs { .. }))
-                })
-            })
+                command: ConfigureSubcommand::Target(target)
+            }) if target.target().name() == "nis"
+                && matches!(
+                    target.context().subcommand(),
+                    Some(("add-slave", add)) if add.get_one::<String>("host").map(String::as_str) == Some("iris")
+                )
         ));
     }
 
@@ -552,6 +2347,28 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn parses_desktop_dump() {
+        let cli = Cli::try_parse_from(["debkit", "desktop", "dump", "/org/gnome/"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Desktop(DesktopCommand {
+                command: DesktopSubcommand::Dump(DesktopDumpArgs { dir })
+            }) if dir == "/org/gnome/"
+        ));
+    }
+
+    #[test]
+    fn parses_desktop_load() {
+        let cli = Cli::try_parse_from(["debkit", "desktop", "load", "profile.dconf"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Desktop(DesktopCommand {
+                command: DesktopSubcommand::Load(DesktopLoadArgs { file, dir })
+            }) if file.as_os_str() == "profile.dconf" && dir == "/"
+        ));
+    }
+
     #[test]
     fn parses_top_level_host_config() {
         let cli = Cli::try_parse_from(["debkit", "host-config"]).unwrap();
@@ -563,9 +2380,7 @@ mod tests {
         let cli = Cli::try_parse_from(["debkit", "uninstall", "codex"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Uninstall(UninstallCommand {
-                command: UninstallSubcommand::Codex
-            })
+            Commands::Uninstall(UninstallCommand { command }) if command.target().name() == "codex"
         ));
     }
 
@@ -574,9 +2389,7 @@ mod tests {
         let cli = Cli::try_parse_from(["debkit", "uninstall", "npm"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Uninstall(UninstallCommand {
-                command: UninstallSubcommand::Npm
-            })
+            Commands::Uninstall(UninstallCommand { command }) if command.target().name() == "npm"
         ));
     }
 
@@ -585,9 +2398,7 @@ mod tests {
         let cli = Cli::try_parse_from(["debkit", "uninstall", "ripgrep"]).unwrap();
         assert!(matches!(
             cli.command,
-            Commands::Uninstall(UninstallCommand {
-                command: UninstallSubcommand::Ripgrep
-            })
+            Commands::Uninstall(UninstallCommand { command }) if command.target().name() == "ripgrep"
         ));
     }
 
@@ -597,7 +2408,18 @@ mod tests {
         assert!(matches!(
             cli.command,
             Commands::Status(StatusCommand {
-                command: StatusSubcommand::Variety
+                command: StatusSubcommand::Target(target)
+            }) if target.target().name() == "variety"
+        ));
+    }
+
+    #[test]
+    fn parses_status_all() {
+        let cli = Cli::try_parse_from(["debkit", "status", "all"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Status(StatusCommand {
+                command: StatusSubcommand::All
             })
         ));
     }
@@ -608,8 +2430,181 @@ mod tests {
         assert!(matches!(
             cli.command,
             Commands::Status(StatusCommand {
-                command: StatusSubcommand::WakeOnLan
+                command: StatusSubcommand::Target(target)
+            }) if target.target().name() == "wake-on-lan"
+        ));
+    }
+
+    #[test]
+    fn parses_install_target_alias() {
+        let cli = Cli::try_parse_from(["debkit", "install", "wol"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Install(InstallCommand { command: InstallSubcommand::Target(command) })
+                if command.target().name() == "wake-on-lan"
+        ));
+    }
+
+    #[test]
+    fn rejects_unsupported_target_action() {
+        assert!(Cli::try_parse_from(["debkit", "install", "git-prompt"]).is_err());
+        assert!(Cli::try_parse_from(["debkit", "uninstall", "git"]).is_err());
+        assert!(Cli::try_parse_from(["debkit", "install", "missing"]).is_err());
+    }
+
+    #[test]
+    fn cli_definition_is_valid() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_global_dry_run_anywhere() {
+        let cli = Cli::try_parse_from(["debkit", "--dry-run", "install", "git"]).unwrap();
+        assert!(cli.dry_run);
+        let cli = Cli::try_parse_from(["debkit", "package", "deb", "--dry-run"]).unwrap();
+        assert!(cli.dry_run);
+        let cli = Cli::try_parse_from(["debkit", "install", "git"]).unwrap();
+        assert!(!cli.dry_run);
+        let cli = Cli::try_parse_from(["debkit", "configure", "variety", "--diff"]).unwrap();
+        assert!(cli.diff && !cli.dry_run);
+    }
+
+    #[test]
+    fn parses_package_deb_project() {
+        let cli =
+            Cli::try_parse_from(["debkit", "package", "deb", "--project-dir", "../tool"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Package(PackageCommand {
+                command: PackageSubcommand::Deb(args)
+            }) if matches!(
+                *args,
+                PackageDebArgs {
+                    project: PackageProjectArgs { manifest_path: None, project_dir: Some(ref dir), .. },
+                    ..
+                } if dir == Path::new("../tool")
+            )
+        ));
+        assert!(
+            Cli::try_parse_from([
+                "debkit",
+                "package",
+                "deb",
+                "--project-dir",
+                "a",
+                "--manifest-path",
+                "b/Cargo.toml",
+            ])
+            .is_err()
+        );
+        let cli = Cli::try_parse_from([
+            "debkit",
+            "package",
+            "deb",
+            "-p",
+            "agent",
+            "--package",
+            "cli",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Package(PackageCommand {
+                command: PackageSubcommand::Deb(args)
+            }) if matches!(
+                *args,
+                PackageDebArgs {
+                    project: PackageProjectArgs { ref package, all_members: false, .. },
+                    ..
+                } if package == &["agent", "cli"]
+            )
+        ));
+        for (args, lint) in [
+            (&["--lint"][..], Some(package::lint::Mode::Report)),
+            (&["--lint=strict"][..], Some(package::lint::Mode::Strict)),
+            (&[][..], None),
+        ] {
+            let cli = Cli::try_parse_from(["debkit", "package", "deb"].iter().chain(args)).unwrap();
+            assert!(matches!(
+                cli.command,
+                Commands::Package(PackageCommand {
+                    command: PackageSubcommand::Deb(args)
+                }) if args.lint == lint
+            ));
+        }
+        let cli = Cli::try_parse_from(["debkit", "package", "rpm", "--profile", "dist"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Package(PackageCommand {
+                command: PackageSubcommand::Rpm(PackageRpmArgs { profile, arch: None, .. })
+            }) if profile == "dist"
+        ));
+        let cli = Cli::try_parse_from(["debkit", "package", "archive", "--archive-format", "zip"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Package(PackageCommand {
+                command: PackageSubcommand::Archive(PackageArchiveArgs {
+                    archive_format: package::archive::Format::Zip,
+                    ..
+                })
+            })
+        ));
+        assert!(
+            Cli::try_parse_from(["debkit", "package", "deb", "-p", "agent", "--all-members"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn parses_global_format() {
+        let cli = Cli::try_parse_from(["debkit", "status", "variety", "--format", "json"]).unwrap();
+        assert_eq!(cli.format, output::Format::Json);
+        let cli = Cli::try_parse_from(["debkit", "list"]).unwrap();
+        assert_eq!(cli.format, output::Format::Text);
+        assert!(Cli::try_parse_from(["debkit", "--format", "yaml", "list"]).is_err());
+    }
+
+    #[test]
+    fn parses_list_status_and_json() {
+        let cli = Cli::try_parse_from(["debkit", "list", "--status", "--json"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::List(ListArgs {
+                status: true,
+                available_updates: false,
+                json: true
+            })
+        ));
+        let cli = Cli::try_parse_from(["debkit", "list", "--available-updates"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::List(ListArgs {
+                available_updates: true,
+                ..
             })
         ));
+        assert!(
+            Cli::try_parse_from(["debkit", "list", "--status", "--available-updates"]).is_err()
+        );
+        let cli = Cli::try_parse_from(["debkit", "list"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::List(ListArgs {
+                status: false,
+                available_updates: false,
+                json: false
+            })
+        ));
+    }
+
+    #[test]
+    fn parses_global_config_path() {
+        let cli =
+            Cli::try_parse_from(["debkit", "install", "git", "--config", "/srv/lab.toml"]).unwrap();
+        assert_eq!(cli.config, Some(PathBuf::from("/srv/lab.toml")));
+        let cli = Cli::try_parse_from(["debkit", "list"]).unwrap();
+        assert_eq!(cli.config, None);
     }
 }
@@ -0,0 +1,84 @@
+//! Man pages.
+//!
+//! One roff page is rendered per visible subcommand, named after its path through the
+//! CLI (`debkit-install-rust.1`), so `man debkit-install-rust` works once they are
+//! installed under `/usr/share/man/man1`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::CommandFactory;
+use clap_mangen::Man;
+
+use debkit::exec;
+
+pub struct Page {
+    pub filename: String,
+    pub contents: Vec<u8>,
+}
+
+pub fn render_all() -> anyhow::Result<Vec<Page>> {
+    let mut command = crate::Cli::command().disable_help_subcommand(true);
+    command.build();
+    let mut pages = Vec::new();
+    render(command, &mut pages)?;
+    Ok(pages)
+}
+
+fn render(command: clap::Command, pages: &mut Vec<Page>) -> anyhow::Result<()> {
+    let man = Man::new(command.clone()).source(concat!("debkit ", env!("CARGO_PKG_VERSION")));
+    let mut contents = Vec::new();
+    man.render(&mut contents)
+        .with_context(|| format!("failed to render man page for `{}`", command.get_name()))?;
+    pages.push(Page {
+        filename: man.get_filename(),
+        contents,
+    });
+
+    for subcommand in command.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        render(subcommand.clone(), pages)?;
+    }
+    Ok(())
+}
+
+/// Writes every page into `dir`, leaving pages whose content is unchanged untouched.
+pub fn write_all(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    exec::create_dir_all(dir)
+        .with_context(|| format!("failed to create man page directory {}", dir.display()))?;
+
+    let mut paths = Vec::new();
+    for page in render_all()? {
+        let path = dir.join(&page.filename);
+        exec::write(&path, &page.contents)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_all;
+
+    #[test]
+    fn renders_a_page_per_subcommand() {
+        let pages = render_all().unwrap();
+        let names = pages
+            .iter()
+            .map(|page| page.filename.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names[0], "debkit.1");
+        assert!(names.contains(&"debkit-install.1"));
+        assert!(names.contains(&"debkit-install-rust.1"));
+        assert!(names.contains(&"debkit-status-all.1"));
+        assert!(!names.contains(&"debkit-help.1"));
+
+        let rust = pages
+            .iter()
+            .find(|page| page.filename == "debkit-install-rust.1")
+            .unwrap();
+        let roff = String::from_utf8_lossy(&rust.contents);
+        assert!(roff.starts_with(".ie"), "{roff}");
+        assert!(roff.contains("reinstall"));
+    }
+}
@@ -0,0 +1,30 @@
+//! `--offline`: provisioning machines without network access.
+//!
+//! In offline mode apt skips `apt-get update` and installs only what is already
+//! downloaded, the Rust target runs a local `rustup-init` instead of fetching the
+//! installer, and cargo tools are built from cargo's local registry cache.
+//! `--apt-cache <DIR>` points apt at a directory of pre-downloaded `.deb` files (laid
+//! out as `DIR/archives/*.deb`), with or without `--offline`.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+static APT_CACHE: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn set_enabled(enabled: bool) {
+    OFFLINE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+pub fn set_apt_cache(dir: PathBuf) {
+    let _ = APT_CACHE.set(dir);
+}
+
+pub fn apt_cache() -> Option<&'static Path> {
+    APT_CACHE.get().map(PathBuf::as_path)
+}
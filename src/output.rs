@@ -0,0 +1,130 @@
+//! Output format selection.
+//!
+//! With `--format json` every command prints exactly one JSON document on stdout.
+//! Progress and human-readable reports written with [`say!`] move to stderr in that
+//! mode, as does the stdout of commands run through [`crate::exec`], so scripts can
+//! parse stdout without screen-scraping. `--quiet` silences [`say!`] altogether and
+//! leaves only errors and the [`outcome`] line.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+pub fn set_format(format: Format) {
+    JSON.store(format == Format::Json, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Silences [`say!`]; warnings and errors still go to stderr.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Prints the one-line result of a command, such as `variety is already installed`,
+/// under `--quiet`. Otherwise the command's regular output already says as much, and
+/// under `--format json` the JSON document does.
+pub fn outcome(line: impl std::fmt::Display) {
+    if is_quiet() && !is_json() {
+        println!("{line}");
+    }
+}
+
+/// Prints `value` as the command's JSON result.
+pub fn emit<T: Serialize>(value: &T) -> anyhow::Result<()> {
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, value)?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub ok: bool,
+    pub error: String,
+}
+
+impl ErrorReport {
+    pub fn new(err: &anyhow::Error) -> Self {
+        Self {
+            ok: false,
+            error: format!("{err:#}"),
+        }
+    }
+}
+
+/// A failure whose JSON document was already emitted; `main` only sets the exit status.
+#[derive(Debug)]
+pub struct Reported(pub String);
+
+impl std::fmt::Display for Reported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Reported {}
+
+/// Returned by a `status` that found drift once its report is out, so `main` exits
+/// with status 2 instead of 1 and prints nothing more.
+#[derive(Debug)]
+pub struct Drift;
+
+impl std::fmt::Display for Drift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("drift detected")
+    }
+}
+
+impl std::error::Error for Drift {}
+
+/// `println!` for human-readable output; goes to stderr under `--format json`.
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::output::is_quiet() {
+            // Silenced by `output::set_quiet`.
+        } else if $crate::output::is_json() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub use say;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_report_includes_context_chain() {
+        let err = anyhow::anyhow!("disk full").context("failed to write /etc/yp.conf");
+        let json = serde_json::to_value(ErrorReport::new(&err)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "ok": false,
+                "error": "failed to write /etc/yp.conf: disk full",
+            })
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! `debkit package appimage`: a portable AppImage of a crate's binaries.
+//!
+//! Each crate is built with cargo and laid out as an AppDir under
+//! `target/appimage/<name>.AppDir/`: the binaries in `usr/bin`, an `AppRun` link to
+//! the main one, a terminal `.desktop` entry and an icon (`--icon`, or a generated
+//! placeholder). appimagetool turns that into `<name>-<version>-<arch>.AppImage`. It
+//! is taken from `PATH`, or downloaded once into DebKit's state directory.
+
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+
+use super::{Artifact, Project, absolute_path, profile_dir, run_command, run_step, select};
+use crate::output::say;
+use crate::{download, exec, state, system};
+
+const TOOL_URL: &str = "https://github.com/AppImage/appimagetool/releases/download/continuous";
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The `Cargo.toml` of the crate to package, or of the workspace to pick members
+    /// of.
+    pub manifest_path: PathBuf,
+    /// Workspace members to package instead of the crate at `manifest_path`.
+    pub packages: Vec<String>,
+    /// Package every workspace member with a binary.
+    pub all_members: bool,
+    /// The cargo profile to build and package, such as `release`.
+    pub profile: String,
+    pub output_dir: PathBuf,
+    /// A `.png` or `.svg` icon; by default a placeholder with the name's initial.
+    pub icon: Option<PathBuf>,
+    /// The appimagetool to run instead of the one on `PATH` or a downloaded one.
+    pub appimagetool: Option<PathBuf>,
+}
+
+/// Builds an AppImage for each selected crate, in order, in the output directory.
+pub fn run(options: Options) -> anyhow::Result<Vec<Artifact>> {
+    let projects = select(
+        &options.manifest_path,
+        &options.packages,
+        options.all_members,
+    )?;
+    let arch = arch()?;
+    let tool = match &options.appimagetool {
+        Some(tool) => tool.clone(),
+        None => appimagetool(arch)?,
+    };
+
+    exec::create_dir_all(&options.output_dir).with_context(|| {
+        format!(
+            "failed to create output directory {}",
+            options.output_dir.display()
+        )
+    })?;
+    let output_dir = absolute_path(&options.output_dir)?;
+
+    projects
+        .iter()
+        .map(|project| build(project, &options, arch, &tool, &output_dir))
+        .collect()
+}
+
+fn build(
+    project: &Project,
+    options: &Options,
+    arch: &str,
+    tool: &Path,
+    output_dir: &Path,
+) -> anyhow::Result<Artifact> {
+    let step = format!("Building {} ({})", project.name, options.profile);
+    let args = ["build", "--profile", &options.profile].map(str::to_string);
+    run_command(&step, "cargo", &args, &project.dir)
+        .with_context(|| format!("failed to build {}", project.name))?;
+
+    let staging = project.target_dir.join("appimage");
+    let app_dir = staging.join(format!("{}.AppDir", project.name));
+    stage(project, options, &app_dir)?;
+
+    let image = output_dir.join(format!(
+        "{}-{}-{arch}.AppImage",
+        project.name, project.version
+    ));
+    let mut command = Command::new(tool);
+    command
+        .arg("--no-appstream")
+        .arg(&app_dir)
+        .arg(&image)
+        .current_dir(&staging)
+        .env("ARCH", arch)
+        // appimagetool is itself an AppImage; this lets it run without FUSE.
+        .env("APPIMAGE_EXTRACT_AND_RUN", "1");
+    run_step(
+        &format!("Building the AppImage for {}", project.name),
+        &mut command,
+    )
+    .with_context(|| format!("failed to create {}", image.display()))?;
+
+    Ok(Artifact {
+        path: image,
+        name: project.name.clone(),
+        version: project.version.clone(),
+    })
+}
+
+/// Lays out `app_dir` from scratch.
+fn stage(project: &Project, options: &Options, app_dir: &Path) -> anyhow::Result<()> {
+    let Some(main) = project
+        .binaries
+        .iter()
+        .find(|binary| **binary == project.name)
+        .or(project.binaries.first())
+    else {
+        bail!("`{}` has no binary to package", project.name);
+    };
+    if app_dir.exists() {
+        exec::remove_dir_all(app_dir)
+            .with_context(|| format!("failed to clear {}", app_dir.display()))?;
+    }
+    let bin_dir = app_dir.join("usr/bin");
+    exec::create_dir_all(&bin_dir)
+        .with_context(|| format!("failed to create {}", bin_dir.display()))?;
+
+    let built = project.target_dir.join(profile_dir(&options.profile));
+    for binary in &project.binaries {
+        let (from, to) = (built.join(binary), bin_dir.join(binary));
+        exec::copy(&from, &to)
+            .with_context(|| format!("failed to copy {} to {}", from.display(), to.display()))?;
+    }
+    exec::symlink(format!("usr/bin/{main}"), app_dir.join("AppRun"))
+        .context("failed to link AppRun")?;
+
+    let desktop = app_dir.join(format!("{}.desktop", project.name));
+    exec::write(&desktop, desktop_entry(&project.name, main))
+        .with_context(|| format!("failed to write {}", desktop.display()))?;
+
+    match &options.icon {
+        Some(icon) => {
+            let extension = icon.extension().unwrap_or_default().to_string_lossy();
+            if extension != "png" && extension != "svg" {
+                bail!("the icon must be a .png or .svg file: {}", icon.display());
+            }
+            let to = app_dir.join(format!("{}.{extension}", project.name));
+            exec::copy(icon, &to).with_context(|| format!("failed to copy {}", icon.display()))?;
+        }
+        None => {
+            let to = app_dir.join(format!("{}.svg", project.name));
+            exec::write(&to, placeholder_icon(&project.name))
+                .with_context(|| format!("failed to write {}", to.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn desktop_entry(name: &str, exec: &str) -> String {
+    format!(
+        "[Desktop Entry]\nType=Application\nName={name}\nExec={exec}\nIcon={name}\nTerminal=true\nCategories=Utility;\n"
+    )
+}
+
+/// A plain square with the first letter of `name`, for crates without an icon.
+fn placeholder_icon(name: &str) -> String {
+    let initial = name.chars().next().unwrap_or('?').to_ascii_uppercase();
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="256" height="256" viewBox="0 0 256 256">
+  <rect width="256" height="256" rx="32" fill="#33518c"/>
+  <text x="128" y="172" font-family="sans-serif" font-size="144" text-anchor="middle" fill="#ffffff">{initial}</text>
+</svg>
+"##
+    )
+}
+
+/// The AppImage name for this machine's architecture.
+fn arch() -> anyhow::Result<&'static str> {
+    Ok(match std::env::consts::ARCH {
+        "x86_64" => "x86_64",
+        "aarch64" => "aarch64",
+        "arm" => "armhf",
+        "x86" => "i686",
+        other => bail!("AppImages are not built for `{other}`"),
+    })
+}
+
+/// appimagetool from `PATH`, or the release for `arch`, downloaded on first use.
+fn appimagetool(arch: &str) -> anyhow::Result<PathBuf> {
+    if let Some(tool) = system::find_program("appimagetool") {
+        return Ok(tool);
+    }
+    let name = format!("appimagetool-{arch}.AppImage");
+    let path = state::dir()?.join("tools").join(&name);
+    if path.is_file() {
+        return Ok(path);
+    }
+    say!("Downloading {name}");
+    let tool = download::fetch(&format!("{TOOL_URL}/{name}"))?;
+    if let Some(dir) = path.parent() {
+        exec::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    exec::write(&path, tool).with_context(|| format!("failed to write {}", path.display()))?;
+    exec::set_permissions(&path, Permissions::from_mode(0o755))
+        .with_context(|| format!("failed to make {} executable", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::package::single_crate_metadata;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn lays_out_the_app_dir_and_runs_appimagetool() {
+        let dir = std::env::temp_dir().join(format!("debkit-appimage-{}", std::process::id()));
+        fs::create_dir_all(dir.join("target/release")).unwrap();
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        fs::write(dir.join("target/release/tool"), "binary").unwrap();
+        let metadata = single_crate_metadata(&dir, "tool", "2.0.0");
+        let recorder = Arc::new(Recorder::new().reply("cargo metadata", 0, &metadata));
+        let options = Options {
+            manifest_path: dir.join("Cargo.toml"),
+            packages: Vec::new(),
+            all_members: false,
+            profile: "release".to_string(),
+            output_dir: dir.join("dist"),
+            icon: None,
+            appimagetool: Some(PathBuf::from("/opt/appimagetool")),
+        };
+
+        let artifacts = runner::with(recorder.clone(), || run(options)).unwrap();
+        let arch = arch().unwrap();
+        let image = dir.join(format!("dist/tool-2.0.0-{arch}.AppImage"));
+        assert_eq!(artifacts[0].path, image);
+
+        let app_dir = dir.join("target/appimage/tool.AppDir");
+        assert_eq!(
+            fs::read_link(app_dir.join("AppRun")).unwrap(),
+            Path::new("usr/bin/tool")
+        );
+        assert!(app_dir.join("usr/bin/tool").is_file());
+        assert!(app_dir.join("tool.svg").is_file());
+        assert!(
+            fs::read_to_string(app_dir.join("tool.desktop"))
+                .unwrap()
+                .contains("\nExec=tool\nIcon=tool\n")
+        );
+        assert_eq!(
+            recorder.calls()[1..],
+            [
+                format!("cargo build --profile release (in {})", dir.display()),
+                format!(
+                    "/opt/appimagetool --no-appstream {} {} (in {}/target/appimage)",
+                    app_dir.display(),
+                    image.display(),
+                    dir.display()
+                ),
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,274 @@
+//! `debkit package archive`: a versioned `.tar.gz` or `.zip` of the built binaries,
+//! for distributions where a `.deb` does not fit.
+//!
+//! Each crate is built with cargo and staged under `target/archive/<name>/` with its
+//! binaries, `LICENSE*`, `COPYING*` and `README*` files and, for DebKit itself, shell
+//! completions and man pages. The staging directory is archived as
+//! `<name>-<version>-<host triple>` and a `sha256sum`-style `.sha256` file is written
+//! next to the archive.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+
+use super::deb::WriteManPages;
+use super::{Artifact, Project, absolute_path, profile_dir, run_command, select};
+use crate::{audit, exec, system};
+
+/// Writes shell completion scripts into the directory it is given and returns their
+/// paths.
+pub type WriteCompletions = fn(&Path) -> anyhow::Result<Vec<PathBuf>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    #[value(name = "tar.gz")]
+    TarGz,
+    Zip,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::TarGz => "tar.gz",
+            Format::Zip => "zip",
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Format::TarGz => "tar",
+            Format::Zip => "zip",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The `Cargo.toml` of the crate to package, or of the workspace to pick members
+    /// of.
+    pub manifest_path: PathBuf,
+    /// Workspace members to package instead of the crate at `manifest_path`.
+    pub packages: Vec<String>,
+    /// Package every workspace member with a binary.
+    pub all_members: bool,
+    pub format: Format,
+    /// The cargo profile to build and package, such as `release`.
+    pub profile: String,
+    pub output_dir: PathBuf,
+    /// Generates the shell completions to ship in the archive. Only used when the
+    /// crate is DebKit itself.
+    pub completions: Option<WriteCompletions>,
+    /// Generates the man pages to ship in the archive. Only used when the crate is
+    /// DebKit itself.
+    pub man_pages: Option<WriteManPages>,
+}
+
+/// Builds an archive for each selected crate, in order, in the output directory.
+pub fn run(options: Options) -> anyhow::Result<Vec<Artifact>> {
+    let projects = select(
+        &options.manifest_path,
+        &options.packages,
+        options.all_members,
+    )?;
+    let tool = options.format.program();
+    if !system::command_available(tool) {
+        bail!(
+            "`{tool}` is required to build a .{} archive; install it with apt",
+            options.format.extension()
+        );
+    }
+    let host = host_triple()?;
+
+    exec::create_dir_all(&options.output_dir).with_context(|| {
+        format!(
+            "failed to create output directory {}",
+            options.output_dir.display()
+        )
+    })?;
+    let output_dir = absolute_path(&options.output_dir)?;
+
+    projects
+        .iter()
+        .map(|project| build(project, &options, &host, &output_dir))
+        .collect()
+}
+
+fn build(
+    project: &Project,
+    options: &Options,
+    host: &str,
+    output_dir: &Path,
+) -> anyhow::Result<Artifact> {
+    let step = format!("Building {} ({})", project.name, options.profile);
+    let args = ["build", "--profile", &options.profile].map(str::to_string);
+    run_command(&step, "cargo", &args, &project.dir)
+        .with_context(|| format!("failed to build {}", project.name))?;
+
+    let stem = format!("{}-{}-{host}", project.name, project.version);
+    let staging_root = project.target_dir.join("archive");
+    let staging = staging_root.join(&stem);
+    stage(project, options, &staging)?;
+
+    let archive = output_dir.join(format!("{stem}.{}", options.format.extension()));
+    let args = match options.format {
+        Format::TarGz => vec![
+            "-czf".to_string(),
+            archive.display().to_string(),
+            stem.clone(),
+        ],
+        Format::Zip => vec![
+            "-qr".to_string(),
+            archive.display().to_string(),
+            stem.clone(),
+        ],
+    };
+    if archive.exists() {
+        // zip adds to an existing archive instead of replacing it.
+        exec::remove_file(&archive)
+            .with_context(|| format!("failed to remove {}", archive.display()))?;
+    }
+    let step = format!("Archiving {stem}");
+    run_command(&step, options.format.program(), &args, &staging_root)
+        .with_context(|| format!("failed to create {}", archive.display()))?;
+
+    if !exec::is_dry_run() {
+        let hash = audit::hash_file(&archive)
+            .with_context(|| format!("failed to read {}", archive.display()))?;
+        let file_name = archive.file_name().unwrap_or_default().to_string_lossy();
+        let sums = archive.with_file_name(format!("{file_name}.sha256"));
+        exec::write(&sums, format!("{hash}  {file_name}\n"))
+            .with_context(|| format!("failed to write {}", sums.display()))?;
+    }
+
+    Ok(Artifact {
+        path: archive,
+        name: project.name.clone(),
+        version: project.version.clone(),
+    })
+}
+
+/// Fills `staging` with what goes into the archive, starting from an empty directory.
+fn stage(project: &Project, options: &Options, staging: &Path) -> anyhow::Result<()> {
+    if staging.exists() {
+        exec::remove_dir_all(staging)
+            .with_context(|| format!("failed to clear {}", staging.display()))?;
+    }
+    exec::create_dir_all(staging)
+        .with_context(|| format!("failed to create {}", staging.display()))?;
+
+    let built = project.target_dir.join(profile_dir(&options.profile));
+    for binary in &project.binaries {
+        copy(&built.join(binary), &staging.join(binary))?;
+    }
+    let mut docs = fs::read_dir(&project.dir)
+        .with_context(|| format!("failed to read {}", project.dir.display()))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_doc(path))
+        .collect::<Vec<_>>();
+    docs.sort();
+    for doc in docs {
+        copy(&doc, &staging.join(doc.file_name().unwrap_or_default()))?;
+    }
+
+    if project.name == env!("CARGO_PKG_NAME") {
+        if let Some(write_completions) = options.completions {
+            write_completions(&staging.join("completions"))?;
+        }
+        if let Some(write_man_pages) = options.man_pages {
+            write_man_pages(&staging.join("man"))?;
+        }
+    }
+    Ok(())
+}
+
+fn is_doc(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_ascii_uppercase();
+    ["LICENSE", "LICENCE", "COPYING", "README"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+fn copy(from: &Path, to: &Path) -> anyhow::Result<()> {
+    exec::copy(from, to)
+        .map(drop)
+        .with_context(|| format!("failed to copy {} to {}", from.display(), to.display()))
+}
+
+/// The target triple rustc builds for by default, such as `x86_64-unknown-linux-gnu`.
+fn host_triple() -> anyhow::Result<String> {
+    let output = exec::query(Command::new("rustc").arg("-vV"))
+        .context("`rustc` executable was not found in PATH")?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("host:"))
+        .map(|host| host.trim().to_string())
+        .context("`rustc -vV` did not report a host triple")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::package::single_crate_metadata;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn stages_and_archives_the_release_binary() {
+        let dir = std::env::temp_dir().join(format!("debkit-archive-{}", std::process::id()));
+        fs::create_dir_all(dir.join("target/release")).unwrap();
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        fs::write(dir.join("LICENSE-MIT"), "MIT").unwrap();
+        fs::write(dir.join("README.md"), "# tool").unwrap();
+        fs::write(dir.join("build.rs"), "fn main() {}").unwrap();
+        fs::write(dir.join("target/release/tool"), "binary").unwrap();
+        let metadata = single_crate_metadata(&dir, "tool", "2.0.0");
+        let recorder = Arc::new(Recorder::new().reply("cargo metadata", 0, &metadata).reply(
+            "rustc -vV",
+            0,
+            "release: 1.90.0\nhost: x86_64-unknown-linux-gnu\n",
+        ));
+        let options = Options {
+            manifest_path: dir.join("Cargo.toml"),
+            packages: Vec::new(),
+            all_members: false,
+            format: Format::TarGz,
+            profile: "release".to_string(),
+            output_dir: dir.join("dist"),
+            completions: None,
+            man_pages: None,
+        };
+
+        // The recorder stands in for tar, so the archive itself is not created.
+        let err = runner::with(recorder.clone(), || run(options)).unwrap_err();
+        assert!(err.to_string().contains(".tar.gz"), "{err:#}");
+
+        let stem = "tool-2.0.0-x86_64-unknown-linux-gnu";
+        let staging = dir.join("target/archive").join(stem);
+        let mut staged = fs::read_dir(&staging)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        staged.sort();
+        assert_eq!(staged, ["LICENSE-MIT", "README.md", "tool"]);
+        assert_eq!(
+            recorder.calls()[2..],
+            [
+                format!("cargo build --profile release (in {})", dir.display()),
+                format!(
+                    "tar -czf {}/dist/{stem}.tar.gz {stem} (in {}/target/archive)",
+                    dir.display(),
+                    dir.display()
+                ),
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
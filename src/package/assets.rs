@@ -0,0 +1,342 @@
+//! `package deb --assets`: shell completions, man pages and systemd units, wired into
+//! cargo-deb's `[package.metadata.deb]` instead of listed there by hand.
+//!
+//! Completions and man pages are generated under `target/assets/` before cargo-deb
+//! runs. DebKit writes its own completions itself. Other crates say how their binary
+//! produces them under `[package.metadata.debkit]`, as arguments in which `{shell}`
+//! and `{dir}` are filled in:
+//!
+//! ```toml
+//! [package.metadata.debkit]
+//! completions = ["completions", "{shell}"]  # prints the script for bash, zsh or fish
+//! man-pages = ["man", "--out-dir", "{dir}"] # writes section 1 pages into {dir}
+//! ```
+//!
+//! The binary is built and run for the build machine with `cargo run`. Each
+//! `assets/*.service` file becomes a systemd unit, which the maintainer scripts
+//! cargo-deb generates enable and start. The `assets` entries and `systemd-units`
+//! that are missing are added to `Cargo.toml`, after `$auto` (cargo-deb's default
+//! assets) when the crate had no `assets` of its own.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use toml_edit::{Array, DocumentMut, InlineTable, Item, TableLike, Value};
+use tracing::debug;
+
+use super::archive::WriteCompletions;
+use super::{Project, update_deb_metadata};
+use crate::exec;
+
+/// Where the generated files go, relative to the crate.
+const GENERATED: &str = "target/assets";
+/// Where systemd units are picked up from, relative to the crate.
+const UNITS: &str = "assets";
+
+const SHELLS: [Shell; 3] = [
+    Shell {
+        name: "bash",
+        prefix: "",
+        suffix: "",
+        destination: "usr/share/bash-completion/completions/",
+    },
+    Shell {
+        name: "zsh",
+        prefix: "_",
+        suffix: "",
+        destination: "usr/share/zsh/vendor-completions/",
+    },
+    Shell {
+        name: "fish",
+        prefix: "",
+        suffix: ".fish",
+        destination: "usr/share/fish/vendor_completions.d/",
+    },
+];
+
+struct Shell {
+    name: &'static str,
+    /// Around the command name, the file name the shell looks for.
+    prefix: &'static str,
+    suffix: &'static str,
+    destination: &'static str,
+}
+
+/// What `[package.metadata.debkit]` says about generating assets.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Generators {
+    completions: Vec<String>,
+    man_pages: Vec<String>,
+}
+
+/// Generates the assets of `project` and makes sure cargo-deb packages them.
+/// `completions` writes DebKit's own completions.
+pub fn prepare(
+    project: &Project,
+    release: bool,
+    completions: Option<WriteCompletions>,
+) -> anyhow::Result<()> {
+    let generated = project.dir.join(GENERATED);
+    let generators = generators(project)?;
+    let mut assets = Vec::new();
+
+    if let Some(write_completions) = completions
+        && project.name == env!("CARGO_PKG_NAME")
+    {
+        write_completions(&generated.join("completions"))?;
+        assets.extend(completion_assets());
+    } else if !generators.completions.is_empty() {
+        let binary = binary(project)?;
+        for shell in &SHELLS {
+            let args = fill(&generators.completions, "{shell}", shell.name);
+            let output = run_binary(project, binary, release, &args)?;
+            let path = generated
+                .join("completions")
+                .join(shell.name)
+                .join(format!("{}{binary}{}", shell.prefix, shell.suffix));
+            if let Some(dir) = path.parent() {
+                exec::create_dir_all(dir)
+                    .with_context(|| format!("failed to create {}", dir.display()))?;
+            }
+            exec::write(&path, output)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+        }
+        assets.extend(completion_assets());
+    }
+    if !generators.man_pages.is_empty() {
+        let dir = generated.join("man");
+        exec::create_dir_all(&dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        let dir = dir.display().to_string();
+        run_binary(
+            project,
+            binary(project)?,
+            release,
+            &fill(&generators.man_pages, "{dir}", &dir),
+        )?;
+        assets.push((format!("{GENERATED}/man/*"), "usr/share/man/man1/"));
+    }
+    let units = units(&project.dir.join(UNITS))?;
+    debug!(
+        "packaging {} generated asset globs and {} systemd units for {}",
+        assets.len(),
+        units.len(),
+        project.name
+    );
+
+    update_deb_metadata(project, |deb| wire(deb, &assets, &units))
+}
+
+fn completion_assets() -> impl Iterator<Item = (String, &'static str)> {
+    SHELLS.iter().map(|shell| {
+        (
+            format!("{GENERATED}/completions/{}/*", shell.name),
+            shell.destination,
+        )
+    })
+}
+
+/// Adds the missing `assets` entries and, unless the crate sets them itself, the
+/// `systemd-units`. Returns whether anything changed.
+fn wire(deb: &mut dyn TableLike, assets: &[(String, &str)], units: &[String]) -> bool {
+    let mut changed = false;
+    if !assets.is_empty() {
+        let list = deb
+            .entry("assets")
+            .or_insert_with(|| {
+                changed = true;
+                Item::Value(Value::Array(Array::from_iter(["$auto"])))
+            })
+            .as_array_mut();
+        if let Some(list) = list {
+            for (source, destination) in assets {
+                let listed = list.iter().any(|entry| {
+                    entry
+                        .as_array()
+                        .and_then(|entry| entry.get(0))
+                        .and_then(Value::as_str)
+                        == Some(source)
+                });
+                if !listed {
+                    list.push(Array::from_iter([source.as_str(), destination, "644"]));
+                    changed = true;
+                }
+            }
+        }
+    }
+    if !units.is_empty() && !deb.contains_key("systemd-units") {
+        let mut list = Array::new();
+        for unit in units {
+            let mut table = InlineTable::new();
+            table.insert("unit-name", unit.as_str().into());
+            table.insert("unit-scripts", UNITS.into());
+            table.insert("enable", true.into());
+            table.insert("start", true.into());
+            list.push(table);
+        }
+        deb.insert("systemd-units", Item::Value(Value::Array(list)));
+        changed = true;
+    }
+    changed
+}
+
+/// The names of the `.service` files in `dir`, without the extension.
+fn units(dir: &Path) -> anyhow::Result<Vec<String>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut units = fs::read_dir(dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "service")
+        })
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+        .collect::<Vec<_>>();
+    units.sort();
+    Ok(units)
+}
+
+fn generators(project: &Project) -> anyhow::Result<Generators> {
+    let manifest = project.dir.join("Cargo.toml");
+    let raw = fs::read_to_string(&manifest)
+        .with_context(|| format!("failed to read {}", manifest.display()))?;
+    let document = raw
+        .parse::<DocumentMut>()
+        .with_context(|| format!("{} is not valid TOML", manifest.display()))?;
+    let args = |key| {
+        document
+            .get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("debkit"))
+            .and_then(|debkit| debkit.get(key))
+            .and_then(Item::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|arg| arg.as_str().map(str::to_string))
+            .collect()
+    };
+    Ok(Generators {
+        completions: args("completions"),
+        man_pages: args("man-pages"),
+    })
+}
+
+/// The binary that generates the assets: the crate's first.
+fn binary(project: &Project) -> anyhow::Result<&str> {
+    project
+        .binaries
+        .first()
+        .map(String::as_str)
+        .with_context(|| format!("{} has no binary to generate assets with", project.name))
+}
+
+fn fill(args: &[String], placeholder: &str, value: &str) -> Vec<String> {
+    args.iter()
+        .map(|arg| arg.replace(placeholder, value))
+        .collect()
+}
+
+/// Runs `binary` with `args` through `cargo run` and returns its output.
+fn run_binary(
+    project: &Project,
+    binary: &str,
+    release: bool,
+    args: &[String],
+) -> anyhow::Result<Vec<u8>> {
+    let mut command = Command::new("cargo");
+    command
+        .args(["run", "--quiet", "--bin", binary])
+        .current_dir(&project.dir);
+    if release {
+        command.arg("--release");
+    }
+    command.arg("--").args(args);
+    let step = format!("Generating assets with `{binary} {}`", args.join(" "));
+    let output = exec::output_step(&mut command, &step)
+        .context("`cargo` executable was not found in PATH")?;
+    if !output.status.success() {
+        bail!(
+            "`{binary} {}` failed with status {}:\n{}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn generates_completions_and_wires_assets_and_units() {
+        let dir = std::env::temp_dir().join(format!("debkit-assets-{}", std::process::id()));
+        fs::create_dir_all(dir.join(UNITS)).unwrap();
+        fs::write(dir.join("assets/tool.service"), "[Service]\n").unwrap();
+        fs::write(dir.join("assets/tool-worker.service"), "[Service]\n").unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"tool\"\nversion = \"1.0.0\"\n\n\
+             [package.metadata.debkit]\ncompletions = [\"completions\", \"{shell}\"]\n",
+        )
+        .unwrap();
+        let project = Project {
+            name: "tool".to_string(),
+            version: "1.0.0".to_string(),
+            deb_name: "tool".to_string(),
+            rpm_name: "tool".to_string(),
+            binaries: vec!["tool".to_string()],
+            repository: None,
+            description: None,
+            maintainer: None,
+            dir: dir.clone(),
+            workspace_root: dir.clone(),
+            target_dir: dir.join("target"),
+        };
+        let recorder = Arc::new(Recorder::new().reply(
+            "cargo run --quiet --bin tool --release -- completions zsh",
+            0,
+            "#compdef tool\n",
+        ));
+
+        runner::with(recorder.clone(), || prepare(&project, true, None)).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("target/assets/completions/zsh/_tool")).unwrap(),
+            "#compdef tool\n"
+        );
+        assert!(
+            dir.join("target/assets/completions/fish/tool.fish")
+                .exists()
+        );
+        assert_eq!(recorder.calls().len(), 3);
+        let manifest = fs::read_to_string(dir.join("Cargo.toml")).unwrap();
+        assert!(
+            manifest.ends_with(
+                "[package.metadata.deb]\n\
+                 assets = [\"$auto\", [\"target/assets/completions/bash/*\", \"usr/share/bash-completion/completions/\", \"644\"], \
+                 [\"target/assets/completions/zsh/*\", \"usr/share/zsh/vendor-completions/\", \"644\"], \
+                 [\"target/assets/completions/fish/*\", \"usr/share/fish/vendor_completions.d/\", \"644\"]]\n\
+                 systemd-units = [{ unit-name = \"tool\", unit-scripts = \"assets\", enable = true, start = true }, \
+                 { unit-name = \"tool-worker\", unit-scripts = \"assets\", enable = true, start = true }]\n"
+            ),
+            "{manifest}"
+        );
+
+        // A second run finds everything wired already.
+        runner::with(recorder, || prepare(&project, true, None)).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("Cargo.toml")).unwrap(),
+            manifest
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
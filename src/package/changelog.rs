@@ -0,0 +1,276 @@
+//! `debkit package changelog`: a `debian/changelog` written from git history.
+//!
+//! Tags that name a version, such as `v1.2.0` or `1.2.0`, mark the releases. Each
+//! release gets an entry listing the subjects of the commits since the previous one
+//! that touch the crate's directory, signed by the author of its newest commit. When
+//! `Cargo.toml` has been bumped past the newest tag, the commits after it form an entry
+//! for the new version. `package.metadata.deb.changelog` is pointed at the file so
+//! cargo-deb installs it as the package's `changelog.Debian.gz`.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use toml_edit::value;
+
+use super::{Artifact, Project, select, update_deb_metadata};
+use crate::exec;
+
+/// Where the changelog goes, relative to the crate.
+const PATH: &str = "debian/changelog";
+/// The distribution of the entries, as the release a package is uploaded to.
+pub const DISTRIBUTION: &str = "unstable";
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The `Cargo.toml` of the crate, or of the workspace to pick members of.
+    pub manifest_path: PathBuf,
+    /// Workspace members to write changelogs for instead of the crate at
+    /// `manifest_path`.
+    pub packages: Vec<String>,
+    /// Write a changelog for every workspace member with a binary.
+    pub all_members: bool,
+}
+
+/// One release in the changelog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub version: String,
+    /// Commit subjects, newest first.
+    pub changes: Vec<String>,
+    /// `Name <email>` of the author of the newest commit.
+    pub author: String,
+    /// The RFC 2822 date of the newest commit.
+    pub date: String,
+}
+
+/// Writes the changelog of each selected crate and returns them.
+pub fn run(options: Options) -> anyhow::Result<Vec<Artifact>> {
+    select(
+        &options.manifest_path,
+        &options.packages,
+        options.all_members,
+    )?
+    .iter()
+    .map(write)
+    .collect()
+}
+
+/// Writes `debian/changelog` for `project` and makes sure cargo-deb packages it.
+pub fn write(project: &Project) -> anyhow::Result<Artifact> {
+    let changelog = generate(project, DISTRIBUTION)?;
+    let path = project.dir.join(PATH);
+    if let Some(dir) = path.parent() {
+        exec::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    exec::write(&path, changelog).with_context(|| format!("failed to write {}", path.display()))?;
+    point_cargo_deb_at_changelog(project)?;
+    Ok(Artifact {
+        path,
+        name: project.deb_name.clone(),
+        version: project.version.clone(),
+    })
+}
+
+/// The changelog of `project` from its git history, with every release uploaded to
+/// `distribution`.
+pub fn generate(project: &Project, distribution: &str) -> anyhow::Result<String> {
+    let entries = entries(project)?;
+    if entries.is_empty() {
+        bail!(
+            "{} has no commits to write a changelog from",
+            project.dir.display()
+        );
+    }
+    Ok(render(&project.deb_name, distribution, &entries))
+}
+
+/// The releases of `project`, newest first.
+fn entries(project: &Project) -> anyhow::Result<Vec<Entry>> {
+    let tags = git(project, &["tag", "--merged", "HEAD", "--sort=-v:refname"])?;
+    let releases = tags
+        .lines()
+        .filter_map(|tag| Some((tag, tag_version(tag)?)))
+        .collect::<Vec<_>>();
+
+    let mut entries = Vec::new();
+    match releases.first() {
+        Some((newest, version)) if *version != project.version => {
+            push_entry(
+                &mut entries,
+                project,
+                &project.version,
+                &format!("{newest}..HEAD"),
+            )?;
+        }
+        None => push_entry(&mut entries, project, &project.version, "HEAD")?,
+        // Commits after the newest release wait for the next version bump.
+        Some(_) => {}
+    }
+    for (index, (tag, version)) in releases.iter().enumerate() {
+        let range = match releases.get(index + 1) {
+            Some((previous, _)) => format!("{previous}..{tag}"),
+            None => tag.to_string(),
+        };
+        push_entry(&mut entries, project, version, &range)?;
+    }
+    Ok(entries)
+}
+
+fn push_entry(
+    entries: &mut Vec<Entry>,
+    project: &Project,
+    version: &str,
+    range: &str,
+) -> anyhow::Result<()> {
+    let log = git(
+        project,
+        &["log", "--format=%aD%x1f%an%x1f%ae%x1f%s", range, "--", "."],
+    )?;
+    let commits = log
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\x1f');
+            Some((
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+            ))
+        })
+        .collect::<Vec<_>>();
+    let Some(&(date, name, email, _)) = commits.first() else {
+        return Ok(());
+    };
+    entries.push(Entry {
+        version: version.to_string(),
+        changes: commits
+            .iter()
+            .map(|(_, _, _, subject)| subject.to_string())
+            .collect(),
+        author: format!("{name} <{email}>"),
+        date: date.to_string(),
+    });
+    Ok(())
+}
+
+/// The version a release tag names: `v1.2.0` or `1.2.0`.
+fn tag_version(tag: &str) -> Option<String> {
+    let version = tag.strip_prefix('v').unwrap_or(tag);
+    version
+        .starts_with(|c: char| c.is_ascii_digit())
+        .then(|| version.to_string())
+}
+
+/// `entries` in the format of `debian/changelog`. Versions get cargo-deb's default
+/// Debian revision, `-1`.
+pub fn render(package: &str, distribution: &str, entries: &[Entry]) -> String {
+    let mut changelog = String::new();
+    for entry in entries {
+        changelog.push_str(&format!(
+            "{package} ({}-1) {distribution}; urgency=medium\n\n",
+            entry.version
+        ));
+        for change in &entry.changes {
+            changelog.push_str(&format!("  * {change}\n"));
+        }
+        changelog.push_str(&format!("\n -- {}  {}\n\n", entry.author, entry.date));
+    }
+    changelog
+}
+
+/// Sets `package.metadata.deb.changelog` unless the crate already names a changelog.
+fn point_cargo_deb_at_changelog(project: &Project) -> anyhow::Result<()> {
+    update_deb_metadata(project, |deb| {
+        if deb.contains_key("changelog") {
+            return false;
+        }
+        deb.insert("changelog", value(PATH));
+        true
+    })
+}
+
+fn git(project: &Project, args: &[&str]) -> anyhow::Result<String> {
+    let output = exec::query(Command::new("git").args(args).current_dir(&project.dir))
+        .context("`git` executable was not found in PATH")?;
+    if !output.status.success() {
+        bail!(
+            "`git {}` failed in {}:\n{}",
+            args.join(" "),
+            project.dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn writes_an_entry_per_release_and_points_cargo_deb_at_it() {
+        let dir = std::env::temp_dir().join(format!("debkit-changelog-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"my_tool\"\nversion = \"1.1.0\"\n",
+        )
+        .unwrap();
+        let commit = |date: &str, subject: &str| {
+            format!("{date}\x1fAda Lovelace\x1fada@example.com\x1f{subject}\n")
+        };
+        let recorder = Arc::new(
+            Recorder::new()
+                .reply("git tag", 0, "v1.0.0\nv0.9.0\nnightly\n")
+                .reply(
+                    "git log --format=%aD%x1f%an%x1f%ae%x1f%s v1.0.0..HEAD",
+                    0,
+                    &(commit("Fri, 2 Oct 2026 10:00:00 +0200", "Bump to 1.1.0")
+                        + &commit("Thu, 1 Oct 2026 10:00:00 +0200", "Add --quiet")),
+                )
+                .reply(
+                    "git log --format=%aD%x1f%an%x1f%ae%x1f%s v0.9.0..v1.0.0",
+                    0,
+                    &commit("Mon, 1 Jun 2026 09:30:00 +0000", "Stabilise the CLI"),
+                )
+                .reply(
+                    "git log --format=%aD%x1f%an%x1f%ae%x1f%s v0.9.0",
+                    0,
+                    &commit("Sun, 1 Mar 2026 08:00:00 +0000", "Initial commit"),
+                ),
+        );
+        let project = Project {
+            name: "my_tool".to_string(),
+            version: "1.1.0".to_string(),
+            deb_name: "my-tool".to_string(),
+            rpm_name: "my_tool".to_string(),
+            binaries: vec!["my_tool".to_string()],
+            repository: None,
+            description: None,
+            maintainer: None,
+            dir: dir.clone(),
+            workspace_root: dir.clone(),
+            target_dir: dir.join("target"),
+        };
+
+        let artifact = runner::with(recorder, || write(&project)).unwrap();
+        assert_eq!(artifact.path, dir.join("debian/changelog"));
+        assert_eq!(
+            fs::read_to_string(&artifact.path).unwrap(),
+            "my-tool (1.1.0-1) unstable; urgency=medium\n\n  * Bump to 1.1.0\n  * Add --quiet\n\n -- Ada Lovelace <ada@example.com>  Fri, 2 Oct 2026 10:00:00 +0200\n\n\
+             my-tool (1.0.0-1) unstable; urgency=medium\n\n  * Stabilise the CLI\n\n -- Ada Lovelace <ada@example.com>  Mon, 1 Jun 2026 09:30:00 +0000\n\n\
+             my-tool (0.9.0-1) unstable; urgency=medium\n\n  * Initial commit\n\n -- Ada Lovelace <ada@example.com>  Sun, 1 Mar 2026 08:00:00 +0000\n\n"
+        );
+        assert!(
+            fs::read_to_string(dir.join("Cargo.toml"))
+                .unwrap()
+                .ends_with("[package.metadata.deb]\nchangelog = \"debian/changelog\"\n")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+//! `SHA256SUMS`, a `.sha256` next to each package and a `manifest.json` describing
+//! what a build put in its output directory, for release automation to pick up.
+//!
+//! `SHA256SUMS` and the `.sha256` files are in the format `sha256sum --check` reads.
+//! Both they and the manifest only cover the packages of the build that wrote them.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::Serialize;
+
+use super::Artifact;
+use crate::{audit, exec, state};
+
+pub const SUMS: &str = "SHA256SUMS";
+pub const MANIFEST: &str = "manifest.json";
+
+/// One package in the manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Entry {
+    /// The file name, relative to the output directory.
+    pub file: String,
+    pub name: String,
+    pub version: String,
+    /// The Debian architecture from a `name_version_arch.deb` (or `.ddeb`) file name.
+    pub arch: Option<String>,
+    pub size: u64,
+    pub sha256: String,
+    /// Seconds since the Unix epoch.
+    pub built_at: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Manifest<'a> {
+    artifacts: &'a [Entry],
+}
+
+/// Writes the checksum files and the manifest for `artifacts` into `output_dir`.
+pub fn write(output_dir: &Path, artifacts: &[Artifact]) -> anyhow::Result<Vec<Entry>> {
+    let built_at = state::now();
+    let mut entries = Vec::new();
+    let mut sums = String::new();
+    for artifact in artifacts {
+        let path = &artifact.path;
+        let file = path
+            .file_name()
+            .context("package path has no file name")?
+            .to_string_lossy()
+            .into_owned();
+        let sha256 =
+            audit::hash_file(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let size = fs::metadata(path)
+            .with_context(|| format!("failed to read {}", path.display()))?
+            .len();
+        let line = format!("{sha256}  {file}\n");
+        let own = path.with_file_name(format!("{file}.sha256"));
+        exec::write(&own, &line).with_context(|| format!("failed to write {}", own.display()))?;
+        sums.push_str(&line);
+        entries.push(Entry {
+            arch: deb_arch(&file),
+            file,
+            name: artifact.name.clone(),
+            version: artifact.version.clone(),
+            size,
+            sha256,
+            built_at,
+        });
+    }
+
+    let path = output_dir.join(SUMS);
+    exec::write(&path, sums).with_context(|| format!("failed to write {}", path.display()))?;
+    let path = output_dir.join(MANIFEST);
+    let mut manifest = serde_json::to_string_pretty(&Manifest {
+        artifacts: &entries,
+    })?;
+    manifest.push('\n');
+    exec::write(&path, manifest).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(entries)
+}
+
+fn deb_arch(file: &str) -> Option<String> {
+    let stem = file
+        .strip_suffix(".deb")
+        .or_else(|| file.strip_suffix(".ddeb"))?;
+    let (_, arch) = stem.rsplit_once('_')?;
+    Some(arch.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_sums_and_a_manifest_for_each_package() {
+        let dir = std::env::temp_dir().join(format!("debkit-checksums-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let artifacts = [
+            ("tool_1.0.0-1_amd64.deb", "deb"),
+            ("tool_1.0.0-1_arm64.deb", "deb2"),
+        ]
+        .map(|(file, contents)| {
+            fs::write(dir.join(file), contents).unwrap();
+            Artifact {
+                path: dir.join(file),
+                name: "tool".to_string(),
+                version: "1.0.0".to_string(),
+            }
+        });
+
+        let entries = write(&dir, &artifacts).unwrap();
+        let amd64 = "9cfa1468c93fc18652e34a000f0c6614b0fa18f6f4887477ad9b0d36ca6a7eaa  tool_1.0.0-1_amd64.deb\n";
+        let arm64 = "5d84d5d8a3987d3a6f188f5fff1e5fcc77c692e65b0bb9a755ce5787a3084c63  tool_1.0.0-1_arm64.deb\n";
+        assert_eq!(
+            fs::read_to_string(dir.join(SUMS)).unwrap(),
+            format!("{amd64}{arm64}")
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("tool_1.0.0-1_arm64.deb.sha256")).unwrap(),
+            arm64
+        );
+        assert_eq!(entries[1].arch.as_deref(), Some("arm64"));
+        assert_eq!(entries[1].size, 4);
+
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.join(MANIFEST)).unwrap()).unwrap();
+        assert_eq!(manifest["artifacts"][0]["file"], "tool_1.0.0-1_amd64.deb");
+        assert_eq!(manifest["artifacts"][0]["arch"], "amd64");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+//! `debkit package clean`: pruning old packages.
+//!
+//! cargo-deb leaves every `.deb` it built in `target/debian/` (or
+//! `target/<triple>/debian/`), and each `package deb` adds its own to the output
+//! directory. For each package name and architecture, only the `keep` newest versions
+//! are kept, ordered the way dpkg orders versions. Files next to a removed package that
+//! share its name, such as its `.sha256`, `.asc` or SBOM, go with it. Only the packages
+//! of the selected crates, and their `-dbgsym` packages, are touched.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+
+use super::deb::DebFile;
+use super::{Project, select, version};
+use crate::exec;
+
+pub const DEFAULT_KEEP: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The `Cargo.toml` of the crate, or of the workspace to pick members of.
+    pub manifest_path: PathBuf,
+    pub packages: Vec<String>,
+    pub all_members: bool,
+    pub output_dir: PathBuf,
+    /// How many versions of each package to keep.
+    pub keep: usize,
+}
+
+/// Prunes the packages of the selected crates and returns the files removed.
+pub fn run(options: Options) -> anyhow::Result<Vec<PathBuf>> {
+    let projects = select(
+        &options.manifest_path,
+        &options.packages,
+        options.all_members,
+    )?;
+    prune(&projects, &options.output_dir, options.keep)
+}
+
+/// Keeps the `keep` newest versions of each package of `projects` in their cargo-deb
+/// directories and in `output_dir`, and returns the files removed.
+pub fn prune(projects: &[Project], output_dir: &Path, keep: usize) -> anyhow::Result<Vec<PathBuf>> {
+    if keep == 0 {
+        bail!("keeping no versions would remove every package; keep at least 1");
+    }
+    let names = projects
+        .iter()
+        .flat_map(|project| {
+            [
+                project.deb_name.clone(),
+                format!("{}-dbgsym", project.deb_name),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let mut dirs = vec![output_dir.to_path_buf()];
+    for project in projects {
+        dirs.extend(debian_dirs(&project.target_dir)?);
+    }
+    dirs.sort();
+    dirs.dedup();
+
+    let mut removed = Vec::new();
+    for dir in dirs.iter().filter(|dir| dir.is_dir()) {
+        let files = files(dir)?;
+        let mut groups: BTreeMap<(String, String), Vec<DebFile>> = BTreeMap::new();
+        for package in files.iter().filter_map(|file| DebFile::parse(file)) {
+            if names.contains(&package.name) {
+                groups
+                    .entry((package.name.clone(), package.arch.clone()))
+                    .or_default()
+                    .push(package);
+            }
+        }
+        for mut packages in groups.into_values() {
+            packages.sort_by(newest_first);
+            for package in packages.iter().skip(keep) {
+                let stem = package
+                    .path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy();
+                let prefix = format!("{stem}.");
+                for file in files.iter().filter(|file| {
+                    file.file_name()
+                        .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+                }) {
+                    exec::remove_file(file)
+                        .with_context(|| format!("failed to remove {}", file.display()))?;
+                    removed.push(file.clone());
+                }
+            }
+        }
+    }
+    Ok(removed)
+}
+
+fn newest_first(a: &DebFile, b: &DebFile) -> Ordering {
+    version::compare_debian(&b.version, &a.version)
+}
+
+/// `target_dir/debian` and `target_dir/<triple>/debian`, where they exist.
+fn debian_dirs(target_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut dirs = vec![target_dir.join("debian")];
+    if target_dir.is_dir() {
+        for entry in fs::read_dir(target_dir)
+            .with_context(|| format!("failed to read {}", target_dir.display()))?
+        {
+            let dir = entry?.path().join("debian");
+            if dir.is_dir() {
+                dirs.push(dir);
+            }
+        }
+    }
+    Ok(dirs)
+}
+
+fn files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = fs::read_dir(dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    files.retain(|file| file.is_file());
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_newest_versions_of_each_package() {
+        assert_eq!(
+            version::compare_debian("1.10.0-1", "1.9.0-1"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            version::compare_debian("1.0.0~rc1-1", "1.0.0-1"),
+            Ordering::Less
+        );
+        assert_eq!(
+            version::compare_debian("1:0.1-1", "2.0-1"),
+            Ordering::Greater
+        );
+        assert_eq!(version::compare_debian("1.0-1", "1.0-1"), Ordering::Equal);
+
+        let root = std::env::temp_dir().join(format!("debkit-clean-{}", std::process::id()));
+        let dist = root.join("dist");
+        fs::create_dir_all(&dist).unwrap();
+        fs::create_dir_all(root.join("target/aarch64-unknown-linux-gnu/debian")).unwrap();
+        for file in [
+            "dist/tool_1.9.0-1_amd64.deb",
+            "dist/tool_1.9.0-1_amd64.deb.sha256",
+            "dist/tool_1.10.0~rc1-1_amd64.deb",
+            "dist/tool_1.10.0-1_amd64.deb",
+            "dist/tool_1.9.0-1_arm64.deb",
+            "dist/other_0.1.0-1_amd64.deb",
+            "dist/other_0.2.0-1_amd64.deb",
+            "target/aarch64-unknown-linux-gnu/debian/tool_1.9.0-1_arm64.deb",
+            "target/aarch64-unknown-linux-gnu/debian/tool_1.10.0-1_arm64.deb",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        let project = Project {
+            name: "tool".to_string(),
+            version: "1.10.0".to_string(),
+            deb_name: "tool".to_string(),
+            rpm_name: "tool".to_string(),
+            binaries: vec!["tool".to_string()],
+            repository: None,
+            description: None,
+            maintainer: None,
+            dir: root.clone(),
+            workspace_root: root.clone(),
+            target_dir: root.join("target"),
+        };
+
+        let removed = prune(&[project], &dist, 1).unwrap();
+        assert_eq!(
+            removed,
+            [
+                "dist/tool_1.10.0~rc1-1_amd64.deb",
+                "dist/tool_1.9.0-1_amd64.deb",
+                "dist/tool_1.9.0-1_amd64.deb.sha256",
+                "target/aarch64-unknown-linux-gnu/debian/tool_1.9.0-1_arm64.deb",
+            ]
+            .map(|file| root.join(file))
+        );
+        assert!(dist.join("tool_1.9.0-1_arm64.deb").exists());
+        assert!(dist.join("other_0.1.0-1_amd64.deb").exists());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+//! Overrides for fields of a built package's `DEBIAN/control`, for variants of a
+//! package that should not need their own `Cargo.toml`.
+//!
+//! cargo-deb only reads these fields from `[package.metadata.deb]`, so the `.deb` it
+//! built is unpacked with `dpkg-deb`, its control file rewritten, and packed again.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, bail};
+
+use super::{Project, repack_deb};
+use crate::{exec, system};
+
+/// The values a package's `Priority` can take.
+pub const PRIORITIES: [&str; 5] = ["required", "important", "standard", "optional", "extra"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overrides {
+    pub maintainer: Option<String>,
+    /// Replaces the whole `Depends` field, including what cargo-deb worked out.
+    pub depends: Vec<String>,
+    /// Replaces the one-line synopsis; the extended description is kept.
+    pub description: Option<String>,
+    pub section: Option<String>,
+    pub priority: Option<String>,
+}
+
+impl Overrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Overrides::default()
+    }
+
+    pub fn ensure_available(&self) -> anyhow::Result<()> {
+        if !self.is_empty() && !system::command_available("dpkg-deb") {
+            bail!(
+                "dpkg-deb is required to override control fields; install it with: sudo apt install dpkg"
+            );
+        }
+        Ok(())
+    }
+
+    /// Rewrites the control file of the built package `deb` of `project`.
+    pub fn apply(&self, project: &Project, deb: &Path) -> anyhow::Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        repack_deb(project, deb, "its control fields overridden", &[], |root| {
+            let path = root.join("DEBIAN").join("control");
+            let control = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            exec::write(&path, self.rewrite(&control)?)
+                .with_context(|| format!("failed to write {}", path.display()))
+        })
+    }
+
+    /// `control` with the overridden fields replaced, or added when it lacks them.
+    fn rewrite(&self, control: &str) -> anyhow::Result<String> {
+        // Each field with its continuation lines.
+        let mut fields: Vec<(String, Vec<String>)> = Vec::new();
+        for line in control.lines().filter(|line| !line.trim().is_empty()) {
+            match fields.last_mut() {
+                Some((_, lines)) if line.starts_with([' ', '\t']) => lines.push(line.to_string()),
+                _ => {
+                    let (name, _) = line
+                        .split_once(':')
+                        .with_context(|| format!("malformed control line `{line}`"))?;
+                    fields.push((name.to_string(), vec![line.to_string()]));
+                }
+            }
+        }
+
+        let depends = (!self.depends.is_empty()).then(|| self.depends.join(", "));
+        let overrides = [
+            ("Maintainer", self.maintainer.as_ref()),
+            ("Depends", depends.as_ref()),
+            ("Description", self.description.as_ref()),
+            ("Section", self.section.as_ref()),
+            ("Priority", self.priority.as_ref()),
+        ];
+        for (name, value) in overrides {
+            let Some(value) = value else { continue };
+            if value.contains('\n') {
+                bail!("the {name} override must be a single line");
+            }
+            let line = format!("{name}: {value}");
+            let field = fields
+                .iter_mut()
+                .find(|(field, _)| field.eq_ignore_ascii_case(name));
+            match field {
+                // The extended description stays.
+                Some((_, lines)) if name == "Description" => lines[0] = line,
+                Some((_, lines)) => *lines = vec![line],
+                None => {
+                    // Description comes last by convention.
+                    let at = fields
+                        .iter()
+                        .position(|(field, _)| field.eq_ignore_ascii_case("Description"))
+                        .unwrap_or(fields.len());
+                    fields.insert(at, (name.to_string(), vec![line]));
+                }
+            }
+        }
+        Ok(fields
+            .iter()
+            .flat_map(|(_, lines)| lines.iter().map(|line| format!("{line}\n")))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_and_adds_control_fields() {
+        let control = "Package: tool\nVersion: 1.0.0-1\nArchitecture: amd64\n\
+                       Maintainer: Ada <ada@example.com>\nDepends: libc6 (>= 2.34)\n\
+                       Description: Does things\n Longer text.\n .\n More.\n";
+        let overrides = Overrides {
+            maintainer: Some("Ops <ops@example.com>".to_string()),
+            depends: vec!["libc6 (>= 2.34)".to_string(), "curl".to_string()],
+            description: Some("Does things, internally".to_string()),
+            section: Some("net".to_string()),
+            ..Overrides::default()
+        };
+        assert_eq!(
+            overrides.rewrite(control).unwrap(),
+            "Package: tool\nVersion: 1.0.0-1\nArchitecture: amd64\n\
+             Maintainer: Ops <ops@example.com>\nDepends: libc6 (>= 2.34), curl\n\
+             Section: net\nDescription: Does things, internally\n Longer text.\n .\n More.\n"
+        );
+        assert!(Overrides::default().is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+//! Cross-compiling a crate for another Debian architecture.
+//!
+//! Each supported architecture maps to a Rust target triple. With the default
+//! [`Linker::Gcc`] the target's standard library is added with rustup and cargo links
+//! with the distribution's cross gcc, such as `aarch64-linux-gnu-gcc` from the
+//! `gcc-aarch64-linux-gnu` package. [`Linker::Zig`] links with cargo-zigbuild instead
+//! and [`Linker::Cross`] builds inside the `cross` containers, which bring their own
+//! toolchains.
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use tracing::debug;
+
+use super::{run_command, run_step};
+use crate::exec;
+
+/// What links the binaries for a foreign architecture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Linker {
+    /// The distribution's cross gcc, through cargo.
+    Gcc,
+    /// cargo-zigbuild, which needs no cross gcc.
+    Zig,
+    /// The `cross` tool and its containers.
+    Cross,
+}
+
+/// An architecture to build for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    /// The Debian architecture, such as `arm64`.
+    pub arch: &'static str,
+    /// The Rust target triple, such as `aarch64-unknown-linux-gnu`.
+    pub triple: &'static str,
+    /// The GNU triple that prefixes the cross gcc, such as `aarch64-linux-gnu`.
+    gnu: &'static str,
+    /// `std::env::consts::ARCH` on a machine of this architecture.
+    host: &'static str,
+}
+
+const TARGETS: &[Target] = &[
+    Target {
+        arch: "amd64",
+        triple: "x86_64-unknown-linux-gnu",
+        gnu: "x86_64-linux-gnu",
+        host: "x86_64",
+    },
+    Target {
+        arch: "arm64",
+        triple: "aarch64-unknown-linux-gnu",
+        gnu: "aarch64-linux-gnu",
+        host: "aarch64",
+    },
+    Target {
+        arch: "armhf",
+        triple: "armv7-unknown-linux-gnueabihf",
+        gnu: "arm-linux-gnueabihf",
+        host: "arm",
+    },
+    Target {
+        arch: "armel",
+        triple: "arm-unknown-linux-gnueabi",
+        gnu: "arm-linux-gnueabi",
+        host: "",
+    },
+    Target {
+        arch: "i386",
+        triple: "i686-unknown-linux-gnu",
+        gnu: "i686-linux-gnu",
+        host: "x86",
+    },
+    Target {
+        arch: "ppc64el",
+        triple: "powerpc64le-unknown-linux-gnu",
+        gnu: "powerpc64le-linux-gnu",
+        host: "powerpc64",
+    },
+    Target {
+        arch: "riscv64",
+        triple: "riscv64gc-unknown-linux-gnu",
+        gnu: "riscv64-linux-gnu",
+        host: "riscv64",
+    },
+    Target {
+        arch: "s390x",
+        triple: "s390x-unknown-linux-gnu",
+        gnu: "s390x-linux-gnu",
+        host: "s390x",
+    },
+];
+
+impl Target {
+    /// The target for the Debian architecture `arch`.
+    pub fn for_arch(arch: &str) -> anyhow::Result<Target> {
+        TARGETS
+            .iter()
+            .find(|target| target.arch == arch)
+            .copied()
+            .with_context(|| {
+                let known = TARGETS
+                    .iter()
+                    .map(|target| target.arch)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("cannot cross-compile for `{arch}`; supported architectures are {known}")
+            })
+    }
+
+    /// The architecture of the machine DebKit runs on, when it is a supported one.
+    pub fn host() -> Option<Target> {
+        TARGETS.iter().find(|target| target.is_host()).copied()
+    }
+
+    /// Whether this is the architecture of the machine DebKit runs on, so no
+    /// cross-compiling is needed.
+    pub fn is_host(&self) -> bool {
+        self.host == std::env::consts::ARCH
+    }
+
+    fn gcc(&self) -> String {
+        format!("{}-gcc", self.gnu)
+    }
+
+    /// The variable that points cargo at the linker for this target.
+    fn linker_variable(&self) -> String {
+        format!(
+            "CARGO_TARGET_{}_LINKER",
+            self.triple.to_ascii_uppercase().replace('-', "_")
+        )
+    }
+}
+
+/// Checks that `linker` can build for `target` and installs the Rust target where it
+/// is needed.
+pub fn prepare(target: Target, linker: Linker, dir: &Path) -> anyhow::Result<()> {
+    match linker {
+        Linker::Gcc => {
+            let gcc = target.gcc();
+            if exec::query(Command::new(&gcc).arg("--version")).is_err() {
+                bail!(
+                    "`{gcc}` is required to link for {}; install it with: sudo apt install gcc-{}",
+                    target.arch,
+                    target.gnu
+                );
+            }
+        }
+        Linker::Zig => {
+            let output = exec::query(Command::new("cargo").args(["zigbuild", "--version"]))
+                .context("`cargo` executable was not found in PATH")?;
+            if !output.status.success() {
+                bail!(
+                    "cargo-zigbuild is required for --linker zig. Install it with: cargo install --locked cargo-zigbuild"
+                );
+            }
+        }
+        Linker::Cross => {
+            if exec::query(Command::new("cross").arg("--version")).is_err() {
+                bail!(
+                    "cross is required for --linker cross. Install it with: cargo install --locked cross"
+                );
+            }
+            // cross brings the target's standard library in its container.
+            return Ok(());
+        }
+    }
+    add_rust_target(target, dir)
+}
+
+/// Adds `target`'s standard library with rustup unless it is installed. Toolchains
+/// not managed by rustup are left to provide it themselves.
+fn add_rust_target(target: Target, dir: &Path) -> anyhow::Result<()> {
+    let output = match exec::query(Command::new("rustup").args(["target", "list", "--installed"])) {
+        Ok(output) if output.status.success() => output,
+        _ => {
+            debug!(
+                "rustup is unavailable; assuming {} is installed",
+                target.triple
+            );
+            return Ok(());
+        }
+    };
+    if String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .any(|line| line.trim() == target.triple)
+    {
+        return Ok(());
+    }
+    let args = ["target", "add", target.triple].map(str::to_string);
+    run_command(
+        &format!("Adding the Rust target {}", target.triple),
+        "rustup",
+        &args,
+        dir,
+    )
+    .with_context(|| format!("failed to add the Rust target {}", target.triple))
+}
+
+/// Builds the crate in `dir` for `target` when `linker` builds outside cargo-deb,
+/// which then packages the result with `--no-build`, with `envs` added to the build's
+/// environment. Returns whether it did.
+pub fn build(
+    target: Target,
+    linker: Linker,
+    release: bool,
+    envs: &[(&str, &str)],
+    dir: &Path,
+) -> anyhow::Result<bool> {
+    let mut command = match linker {
+        Linker::Gcc => return Ok(false),
+        Linker::Zig => {
+            let mut command = Command::new("cargo");
+            command.arg("zigbuild");
+            command
+        }
+        Linker::Cross => {
+            let mut command = Command::new("cross");
+            command.arg("build");
+            command
+        }
+    };
+    command
+        .args(["--target", target.triple])
+        .envs(envs.iter().copied())
+        .current_dir(dir);
+    if release {
+        command.arg("--release");
+    }
+    run_step(&format!("Building for {}", target.arch), &mut command)?;
+    Ok(true)
+}
+
+/// Points cargo, when it builds for `target` itself, at the cross gcc.
+pub fn configure(command: &mut Command, target: Target, linker: Linker) {
+    if linker == Linker::Gcc {
+        command.env(target.linker_variable(), target.gcc());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_debian_architectures_to_rust_targets() {
+        let arm64 = Target::for_arch("arm64").unwrap();
+        assert_eq!(arm64.triple, "aarch64-unknown-linux-gnu");
+        assert_eq!(arm64.gcc(), "aarch64-linux-gnu-gcc");
+        assert_eq!(
+            arm64.linker_variable(),
+            "CARGO_TARGET_AARCH64_UNKNOWN_LINUX_GNU_LINKER"
+        );
+        assert_eq!(
+            Target::for_arch("armhf").unwrap().triple,
+            "armv7-unknown-linux-gnueabihf"
+        );
+        let err = Target::for_arch("sparc").unwrap_err();
+        assert!(err.to_string().contains("arm64"), "{err:#}");
+    }
+}
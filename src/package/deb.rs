@@ -1,210 +1,516 @@
-use std::ffi::OsStr;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use anyhow::{Context, bail};
+use tracing::debug;
+
+use super::archive::WriteCompletions;
+use super::control::Overrides;
+use super::cross::{self, Linker, Target};
+use super::naming::{self, NameTemplate};
+use super::sbom::Sbom;
+use super::sign::Signing;
+use super::verify::Verify;
+use super::{
+    Artifact, Project, absolute_path, assets, changelog, checksums, clean, lint, native,
+    reproducible, run_step, scripts, select, version,
+};
+use crate::exec;
+use crate::output::say;
+
+/// What turns the built crate into a `.deb`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// cargo-deb, which has to be installed.
+    CargoDeb,
+    /// DebKit's own packaging, from the same `[package.metadata.deb]`.
+    Native,
+}
+
+/// Writes man pages into the directory it is given and returns their paths.
+pub type WriteManPages = fn(&Path) -> anyhow::Result<Vec<PathBuf>>;
 
 #[derive(Debug, Clone)]
 pub struct Options {
+    /// The `Cargo.toml` of the crate to package, or of the workspace to pick members
+    /// of.
+    pub manifest_path: PathBuf,
+    /// Workspace members to package instead of the crate at `manifest_path`.
+    pub packages: Vec<String>,
+    /// Package every workspace member with a binary.
+    pub all_members: bool,
     pub release: bool,
     pub output_dir: PathBuf,
-    pub arch: Option<String>,
-    pub verbose: bool,
+    /// Names the packages in the output directory instead of the names they were
+    /// built with.
+    pub name_template: Option<NameTemplate>,
+    /// The Debian architectures, such as `arm64`, to build for in turn; by default the
+    /// build machine's. The crate is cross-compiled for the others.
+    pub arches: Vec<String>,
+    /// What links the binaries when cross-compiling.
+    pub linker: Linker,
+    pub backend: Backend,
     pub reinstall: bool,
+    /// Strip the binaries, or keep their symbols; by default cargo-deb strips release
+    /// builds.
+    pub strip: Option<bool>,
+    /// Split the debug info off into a companion `<package>-dbgsym` package.
+    pub dbgsym: bool,
+    /// Control fields to set in each `.deb` instead of those from `Cargo.toml`.
+    pub control: Overrides,
+    /// Write `debian/changelog` from git history first, for cargo-deb to include.
+    pub changelog: bool,
+    /// Build from the last commit's timestamp, normalize each `.deb` and check that a
+    /// second build from scratch comes out identical.
+    pub reproducible: bool,
+    /// Write a software bill of materials for each `.deb`, next to it or inside it.
+    pub sbom: Option<Sbom>,
+    /// Check each `.deb` with lintian once it is built.
+    pub lint: Option<lint::Mode>,
+    /// Sign each `.deb` once it is built and checked.
+    pub signing: Option<Signing>,
+    /// Check each `.deb` once it is built: its contents, that its dependencies
+    /// resolve and that its binary runs.
+    pub verify: Option<Verify>,
+    /// Write `SHA256SUMS`, a `.sha256` per package and `manifest.json` into the output
+    /// directory.
+    pub checksums: bool,
+    /// Once everything is built, keep only this many versions of each package.
+    pub keep: Option<usize>,
+    /// Generate completions and man pages and package them and any systemd units,
+    /// adding what is missing to `[package.metadata.deb]`.
+    pub assets: bool,
+    /// Generates the shell completions to ship with `assets`. Only used when the crate
+    /// is DebKit itself.
+    pub completions: Option<WriteCompletions>,
+    /// Generates the man pages to ship in the package. Only used when the crate is
+    /// DebKit itself.
+    pub man_pages: Option<WriteManPages>,
+    /// Build with a new version instead of the crate's own.
+    pub version: Option<version::Change>,
+    /// Write the new version back to `Cargo.toml`; otherwise it only labels the package.
+    pub write_version: bool,
+    /// Commit the written-back version and tag it `v<version>` once everything is
+    /// built.
+    pub tag: bool,
 }
 
-pub fn run(options: Options) -> anyhow::Result<PathBuf> {
-    let project_root = Path::new(env!("CARGO_MANIFEST_DIR"));
-
-    ensure_cargo_deb_available(options.reinstall, options.verbose)?;
-
-    let mut args = vec!["deb".to_string()];
-    if let Some(arch) = &options.arch {
-        args.push("--deb-arch".to_string());
-        args.push(arch.clone());
+/// Builds a `.deb` for each selected crate and architecture, in order, and copies them
+/// all into the output directory.
+pub fn run(options: Options) -> anyhow::Result<Vec<Artifact>> {
+    let mut projects = select(
+        &options.manifest_path,
+        &options.packages,
+        options.all_members,
+    )?;
+    if options.tag && projects.len() > 1 {
+        bail!("--tag names a single version; pick one crate with --package");
     }
-    if !options.release {
-        args.push("--profile".to_string());
-        args.push("dev".to_string());
+    if let Some(template) = &options.name_template {
+        // Otherwise one package would overwrite another in the output directory.
+        if (projects.len() > 1 || options.dbgsym) && !template.uses("name") {
+            bail!("--name-template needs {{name}} to tell the packages apart");
+        }
+        if options.arches.len() > 1 && !template.uses("arch") {
+            bail!("--name-template needs {{arch}} to tell the architectures apart");
+        }
     }
 
-    run_command("cargo", &args, project_root, options.verbose)
-        .with_context(|| "failed to run cargo-deb package build")?;
-
-    let debian_dir = project_root.join("target").join("debian");
-    let newest = newest_matching_deb(&debian_dir, "debkit_")?;
-
-    fs::create_dir_all(&options.output_dir).with_context(|| {
+    match options.backend {
+        Backend::CargoDeb => ensure_cargo_deb_available(options.reinstall)?,
+        Backend::Native if options.dbgsym => {
+            bail!("--dbgsym needs cargo-deb; use --backend cargo-deb")
+        }
+        Backend::Native => {}
+    }
+    options.control.ensure_available()?;
+    if let Some(sbom) = &options.sbom {
+        sbom.ensure_available()?;
+    }
+    if options.lint.is_some() {
+        lint::ensure_available()?;
+    }
+    if let Some(signing) = &options.signing {
+        signing.ensure_available()?;
+    }
+    let mut arches = Vec::new();
+    for arch in &options.arches {
+        let target = Some(Target::for_arch(arch)?).filter(|target| !target.is_host());
+        if let Some(target) = target {
+            cross::prepare(target, options.linker, &projects[0].dir)?;
+        }
+        arches.push((Some(arch.as_str()), target));
+    }
+    if arches.is_empty() {
+        arches.push((None, None));
+    }
+    exec::create_dir_all(&options.output_dir).with_context(|| {
         format!(
             "failed to create output directory {}",
             options.output_dir.display()
         )
     })?;
+    let mut manifests = Vec::new();
+    if let Some(change) = &options.version {
+        for project in &mut projects {
+            let version = version::next(&project.version, change)?;
+            if options.write_version {
+                manifests.push(version::write(project, &version)?);
+            }
+            project.version = version;
+        }
+    }
+    if options.changelog {
+        for project in &projects {
+            changelog::write(project)?;
+        }
+    }
 
-    let filename = newest
-        .file_name()
-        .context("newest .deb path does not include a filename")?;
-    let output_path = options.output_dir.join(filename);
+    let artifacts = arches
+        .iter()
+        .flat_map(|&(arch, target)| projects.iter().map(move |project| (project, arch, target)))
+        .map(|(project, arch, target)| build(project, &options, arch, target))
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .concat();
+    if options.checksums && !exec::is_dry_run() {
+        checksums::write(&options.output_dir, &artifacts)?;
+    }
+    if let Some(keep) = options.keep {
+        let removed = clean::prune(&projects, &options.output_dir, keep)?;
+        if !removed.is_empty() {
+            say!(
+                "Pruned {} old files, keeping {keep} versions",
+                removed.len()
+            );
+        }
+    }
+    if options.tag {
+        version::tag(
+            &projects[0].workspace_root,
+            &projects[0].version,
+            &manifests,
+        )?;
+    }
+    Ok(artifacts)
+}
 
-    if options.verbose {
-        eprintln!("copy {} -> {}", newest.display(), output_path.display());
+fn build(
+    project: &Project,
+    options: &Options,
+    arch: Option<&str>,
+    target: Option<Target>,
+) -> anyhow::Result<Vec<Artifact>> {
+    if let Some(write_man_pages) = options.man_pages
+        && project.name == env!("CARGO_PKG_NAME")
+    {
+        // Picked up by the `target/man/*` asset in Cargo.toml.
+        let man_dir = project.dir.join("target").join("man");
+        let pages = write_man_pages(&man_dir)?;
+        debug!(
+            "generated {} man pages in {}",
+            pages.len(),
+            man_dir.display()
+        );
+    }
+
+    if options.assets {
+        assets::prepare(project, options.release, options.completions)?;
+    }
+    scripts::prepare(project)?;
+
+    let epoch = if options.reproducible {
+        Some(reproducible::source_date_epoch(project)?)
+    } else {
+        None
+    };
+    let mut envs = Vec::new();
+    // Without debug info in the release profile there are no symbols to split off.
+    if options.dbgsym && options.release {
+        envs.push(("CARGO_PROFILE_RELEASE_DEBUG", "true"));
     }
+    if let Some(epoch) = &epoch {
+        envs.push(("SOURCE_DATE_EPOCH", epoch.as_str()));
+    }
+    package(project, options, arch, target, &envs)?;
+
+    if exec::is_dry_run() {
+        return Ok(vec![Artifact {
+            path: absolute_path(&options.output_dir)?,
+            name: project.deb_name.clone(),
+            version: project.version.clone(),
+        }]);
+    }
+
+    let debian_dir = debian_dir(&project.target_dir, target);
+    let deb_arch = target.map(|target| target.arch).or(arch);
+    let newest = newest_matching_deb(&debian_dir, &project.deb_name, deb_arch)?;
+    let git_sha = match &options.name_template {
+        Some(template) if template.uses("git-sha") => {
+            Some(naming::git_sha(&project.workspace_root)?)
+        }
+        _ => None,
+    };
+    let output_path = copy_to_output(&newest, options, git_sha.as_deref())?;
+    if let Some(epoch) = &epoch {
+        reproducible::normalize(project, &output_path, epoch)?;
+        let rebuild_dir = project.target_dir.join(reproducible::REBUILD_DIR);
+        if rebuild_dir.exists() {
+            exec::remove_dir_all(&rebuild_dir)
+                .with_context(|| format!("failed to remove {}", rebuild_dir.display()))?;
+        }
+        let rebuild_target_dir = rebuild_dir.to_string_lossy();
+        let mut rebuild_envs = envs.clone();
+        rebuild_envs.push(("CARGO_TARGET_DIR", &rebuild_target_dir));
+        package(project, options, arch, target, &rebuild_envs)?;
+        let again = newest_matching_deb(
+            &self::debian_dir(&rebuild_dir, target),
+            &project.deb_name,
+            deb_arch,
+        )?;
+        reproducible::normalize(project, &again, epoch)?;
+        reproducible::compare(&output_path, &again)?;
+    }
+    options.control.apply(project, &output_path)?;
+    if let Some(sbom) = &options.sbom {
+        sbom.write(project, &output_path)?;
+    }
+    // Repacking stamped the package with the current time again.
+    let repacked =
+        !options.control.is_empty() || options.sbom.as_ref().is_some_and(|sbom| sbom.embed);
+    if let Some(epoch) = &epoch
+        && repacked
+    {
+        reproducible::normalize(project, &output_path, epoch)?;
+    }
+    if let Some(mode) = options.lint {
+        lint::check(&output_path, mode)?;
+    }
+    if let Some(signing) = &options.signing {
+        signing.sign(&output_path)?;
+    }
+    if let Some(verify) = &options.verify {
+        verify.check(project, &output_path, target.is_none())?;
+    }
+    let mut artifacts = vec![Artifact {
+        path: absolute_path(&output_path)?,
+        name: project.deb_name.clone(),
+        version: project.version.clone(),
+    }];
+
+    if options.dbgsym {
+        let name = format!("{}-dbgsym", project.deb_name);
+        let newest = newest_matching_deb(&debian_dir, &name, deb_arch)?;
+        let output_path = copy_to_output(&newest, options, git_sha.as_deref())?;
+        if let Some(signing) = &options.signing {
+            signing.sign(&output_path)?;
+        }
+        artifacts.push(Artifact {
+            path: absolute_path(&output_path)?,
+            name,
+            version: project.version.clone(),
+        });
+    }
+    Ok(artifacts)
+}
+
+/// Builds the `.deb` for `project` with `envs` set, with the chosen backend.
+fn package(
+    project: &Project,
+    options: &Options,
+    arch: Option<&str>,
+    target: Option<Target>,
+    envs: &[(&str, &str)],
+) -> anyhow::Result<()> {
+    match options.backend {
+        Backend::CargoDeb => cargo_deb(project, options, arch, target, envs),
+        Backend::Native => native::build(project, options, arch, target, envs),
+    }
+}
+
+/// Runs cargo-deb for `project` with `envs` set.
+fn cargo_deb(
+    project: &Project,
+    options: &Options,
+    arch: Option<&str>,
+    target: Option<Target>,
+    envs: &[(&str, &str)],
+) -> anyhow::Result<()> {
+    let mut command = Command::new("cargo");
+    command
+        .arg("deb")
+        .current_dir(&project.dir)
+        .envs(envs.iter().copied());
+    match target {
+        Some(target) => {
+            // cargo-deb derives the package architecture from the target.
+            command.args(["--target", target.triple]);
+            if cross::build(target, options.linker, options.release, envs, &project.dir)
+                .with_context(|| format!("failed to build {} for {}", project.name, target.arch))?
+            {
+                command.arg("--no-build");
+            }
+            cross::configure(&mut command, target, options.linker);
+        }
+        None => {
+            if let Some(arch) = arch {
+                command.args(["--deb-arch", arch]);
+            }
+        }
+    }
+    if !options.release {
+        command.args(["--profile", "dev"]);
+    }
+    match options.strip {
+        Some(true) => command.arg("--strip"),
+        Some(false) => command.arg("--no-strip"),
+        None => &mut command,
+    };
+    if options.dbgsym {
+        command.arg("--dbgsym");
+    }
+    if options.version.is_some() && !options.write_version {
+        command.args(["--deb-version", &project.version]);
+    }
+
+    let step = format!("Building the .deb for {} with cargo-deb", project.name);
+    run_step(&step, &mut command)
+        .with_context(|| format!("failed to build the .deb for {}", project.name))
+}
+
+/// Where the packages built in `target_dir` go, as cargo-deb lays it out.
+pub fn debian_dir(target_dir: &Path, target: Option<Target>) -> PathBuf {
+    match target {
+        Some(target) => target_dir.join(target.triple).join("debian"),
+        None => target_dir.join("debian"),
+    }
+}
 
-    fs::copy(&newest, &output_path).with_context(|| {
+/// Copies the package built at `built` into the output directory, named after the
+/// name template if there is one.
+fn copy_to_output(
+    built: &Path,
+    options: &Options,
+    git_sha: Option<&str>,
+) -> anyhow::Result<PathBuf> {
+    let filename = built
+        .file_name()
+        .context("newest .deb path does not include a filename")?;
+    let output_path = match (&options.name_template, DebFile::parse(built)) {
+        (Some(template), Some(deb)) => {
+            let profile = if options.release { "release" } else { "dev" };
+            options
+                .output_dir
+                .join(template.render(&deb, profile, git_sha))
+        }
+        _ => options.output_dir.join(filename),
+    };
+
+    exec::copy(built, &output_path).with_context(|| {
         format!(
             "failed to copy artifact from {} to {}",
-            newest.display(),
+            built.display(),
             output_path.display()
         )
     })?;
-
-    Ok(absolute_path(&output_path)?)
+    Ok(output_path)
 }
 
-fn ensure_cargo_deb_available(reinstall: bool, verbose: bool) -> anyhow::Result<()> {
+fn ensure_cargo_deb_available(reinstall: bool) -> anyhow::Result<()> {
     if reinstall {
-        let install_args = vec![
-            "install".to_string(),
-            "--locked".to_string(),
-            "--force".to_string(),
-            "cargo-deb".to_string(),
-        ];
-        run_command(
-            "cargo",
-            &install_args,
-            Path::new(env!("CARGO_MANIFEST_DIR")),
-            verbose,
-        )
-        .with_context(|| "failed to reinstall cargo-deb")?;
-        return Ok(());
+        return crate::install::cargo_tools::install(&["cargo-deb".to_string()], true)
+            .context("failed to reinstall cargo-deb");
     }
 
-    let output = Command::new("cargo")
-        .args(["deb", "--version"])
-        .output()
+    let output = exec::query(Command::new("cargo").args(["deb", "--version"]))
         .context("`cargo` executable was not found in PATH")?;
 
     if output.status.success() {
         return Ok(());
     }
 
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    if verbose {
-        eprintln!("cargo deb --version stdout: {stdout}");
-        eprintln!("cargo deb --version stderr: {stderr}");
-    }
+    debug!(
+        "cargo deb --version failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
 
     bail!(
-        "cargo-deb is required but not installed. Install it with: cargo install --locked cargo-deb"
+        "cargo-deb is required but not installed. Install it with `debkit package deb --reinstall`, or list it in `cargo_tools.install` and run `debkit install cargo-tools`"
     );
 }
 
-fn run_command(program: &str, args: &[String], cwd: &Path, verbose: bool) -> anyhow::Result<()> {
-    if verbose {
-        eprintln!(
-            "run (cwd: {}): {} {}",
-            cwd.display(),
-            program,
-            args.join(" ")
-        );
-    }
-
-    let output = Command::new(program)
-        .args(args)
-        .current_dir(cwd)
-        .output()
-        .with_context(|| format!("failed to start `{program}`"))?;
+/// A package file named `<name>_<version>_<arch>.deb`, or `.ddeb` as cargo-deb names
+/// debug symbol packages after Ubuntu's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebFile {
+    pub path: PathBuf,
+    pub name: String,
+    pub version: String,
+    pub arch: String,
+}
 
-    if output.status.success() {
-        if verbose {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            if !stdout.trim().is_empty() {
-                eprintln!("stdout:\n{stdout}");
-            }
-            if !stderr.trim().is_empty() {
-                eprintln!("stderr:\n{stderr}");
-            }
+impl DebFile {
+    pub fn parse(path: &Path) -> Option<Self> {
+        let extension = path.extension()?;
+        if extension != "deb" && extension != "ddeb" {
+            return None;
         }
-        return Ok(());
+        let stem = path.file_stem()?.to_str()?;
+        let mut parts = stem.split('_');
+        let (name, version, arch) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            path: path.to_path_buf(),
+            name: name.to_string(),
+            version: version.to_string(),
+            arch: arch.to_string(),
+        })
     }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    bail!(
-        "command `{}` failed with status {}\nstdout:\n{}\nstderr:\n{}",
-        format!("{} {}", program, args.join(" ")),
-        output.status,
-        stdout,
-        stderr,
-    );
 }
 
-pub fn newest_matching_deb(dir: &Path, prefix: &str) -> anyhow::Result<PathBuf> {
-    let mut candidates = Vec::new();
-
+/// The package named `name` in `dir` with the highest Debian version, for `arch` when
+/// it is given. File times play no part, as a cache may have restored them.
+pub fn newest_matching_deb(dir: &Path, name: &str, arch: Option<&str>) -> anyhow::Result<PathBuf> {
     let entries = fs::read_dir(dir)
         .with_context(|| format!("failed to read artifact directory {}", dir.display()))?;
-
+    let mut newest: Option<DebFile> = None;
     for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
-        if !path.is_file() {
+        let Some(deb) = DebFile::parse(&entry?.path()) else {
             continue;
-        }
-
-        let filename = match path.file_name().and_then(OsStr::to_str) {
-            Some(name) => name,
-            None => continue,
         };
-        if !filename.starts_with(prefix) {
+        if deb.name != name || arch.is_some_and(|arch| deb.arch != arch) {
             continue;
         }
-        if path.extension().and_then(OsStr::to_str) != Some("deb") {
-            continue;
+        let newer = newest.as_ref().is_none_or(|newest| {
+            version::compare_debian(&deb.version, &newest.version)
+                .then_with(|| deb.path.cmp(&newest.path))
+                .is_gt()
+        });
+        if newer {
+            newest = Some(deb);
         }
-
-        let modified = entry
-            .metadata()
-            .and_then(|m| m.modified())
-            .with_context(|| format!("failed to read mtime for {}", path.display()))?;
-        candidates.push((modified, path));
     }
-
-    candidates.sort_by(|(a_time, a_path), (b_time, b_path)| {
-        a_time
-            .cmp(b_time)
-            .then_with(|| a_path.file_name().cmp(&b_path.file_name()))
-    });
-
-    candidates.pop().map(|(_, path)| path).with_context(|| {
+    newest.map(|deb| deb.path).with_context(|| {
         format!(
-            "no .deb artifacts found in {} matching prefix {}",
-            dir.display(),
-            prefix
+            "no {name}_<version>_{}.deb found in {}",
+            arch.unwrap_or("<arch>"),
+            dir.display()
         )
     })
 }
 
-fn absolute_path(path: &Path) -> anyhow::Result<PathBuf> {
-    if path.is_absolute() {
-        return Ok(path.to_path_buf());
-    }
-
-    Ok(std::env::current_dir()
-        .context("failed to read current working directory")?
-        .join(path))
-}
-
 #[cfg(test)]
 mod tests {
-    use super::newest_matching_deb;
+    use super::{
+        Backend, Linker, Options, Overrides, ensure_cargo_deb_available, newest_matching_deb, run,
+    };
+    use crate::package::single_crate_metadata;
+    use crate::runner::{self, Recorder};
     use std::fs;
-    use std::path::PathBuf;
-    use std::thread;
-    use std::time::Duration;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
 
     fn temp_test_dir() -> PathBuf {
         let pid = std::process::id();
@@ -215,22 +521,214 @@ mod tests {
         std::env::temp_dir().join(format!("debkit-test-{pid}-{nanos}"))
     }
 
+    /// Options for building the crate at `dir` into `dir/dist` with cargo-deb and
+    /// nothing else turned on.
+    fn options(dir: &Path) -> Options {
+        Options {
+            manifest_path: dir.join("Cargo.toml"),
+            packages: Vec::new(),
+            all_members: false,
+            release: true,
+            output_dir: dir.join("dist"),
+            name_template: None,
+            arches: Vec::new(),
+            linker: Linker::Gcc,
+            backend: Backend::CargoDeb,
+            reinstall: false,
+            strip: None,
+            dbgsym: false,
+            control: Overrides::default(),
+            changelog: false,
+            sbom: None,
+            lint: None,
+            signing: None,
+            reproducible: false,
+            verify: None,
+            checksums: false,
+            keep: None,
+            assets: false,
+            completions: None,
+            man_pages: None,
+            version: None,
+            write_version: false,
+            tag: false,
+        }
+    }
+
     #[test]
     fn picks_newest_matching_deb_file() {
         let dir = temp_test_dir();
         fs::create_dir_all(&dir).expect("create temp test dir");
 
-        let first = dir.join("debkit_0.1.0_amd64.deb");
-        fs::write(&first, b"a").expect("write first");
+        // Written newest first, so file times would pick the wrong one.
+        for name in [
+            "debkit_0.10.0-1_amd64.deb",
+            "debkit_0.10.0~rc1-1_amd64.deb",
+            "debkit_0.9.0-1_amd64.deb",
+            "debkit_0.11.0-1_arm64.deb",
+            "debkit-dbgsym_0.12.0-1_amd64.ddeb",
+        ] {
+            fs::write(dir.join(name), b"").expect("write package");
+        }
+
+        let selected = newest_matching_deb(&dir, "debkit", Some("amd64")).expect("pick newest");
+        assert_eq!(selected, dir.join("debkit_0.10.0-1_amd64.deb"));
+        let selected = newest_matching_deb(&dir, "debkit", None).expect("pick newest");
+        assert_eq!(selected, dir.join("debkit_0.11.0-1_arm64.deb"));
+        let selected = newest_matching_deb(&dir, "debkit-dbgsym", None).expect("pick newest");
+        assert_eq!(selected, dir.join("debkit-dbgsym_0.12.0-1_amd64.ddeb"));
+        assert!(newest_matching_deb(&dir, "debkit", Some("i386")).is_err());
 
-        thread::sleep(Duration::from_millis(1100));
+        fs::remove_dir_all(&dir).expect("cleanup temp test dir");
+    }
 
-        let second = dir.join("debkit_0.2.0_amd64.deb");
-        fs::write(&second, b"b").expect("write second");
+    #[test]
+    fn requires_or_reinstalls_cargo_deb() {
+        let recorder = Arc::new(Recorder::new().reply("cargo deb --version", 101, "").reply(
+            "cargo install --list",
+            0,
+            "cargo-deb v2.7.0:\n    cargo-deb\n",
+        ));
+        let err = runner::with(recorder.clone(), || ensure_cargo_deb_available(false)).unwrap_err();
+        assert!(err.to_string().contains("cargo-deb is required"), "{err:#}");
+
+        runner::with(recorder.clone(), || ensure_cargo_deb_available(true)).unwrap();
+        assert_eq!(
+            recorder.calls(),
+            [
+                "cargo deb --version",
+                "cargo install --list",
+                "cargo install --locked --force cargo-deb",
+                "cargo install --list",
+            ]
+        );
+    }
 
-        let selected = newest_matching_deb(&dir, "debkit_").expect("pick newest");
-        assert_eq!(selected, second);
+    #[test]
+    fn cross_compiles_for_a_foreign_architecture() {
+        let dir = temp_test_dir();
+        let built = dir.join("target/s390x-unknown-linux-gnu/debian");
+        fs::create_dir_all(&built).unwrap();
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        fs::write(built.join("tool_2.0.0-1_s390x.deb"), "deb").unwrap();
+        let metadata = single_crate_metadata(&dir, "tool", "2.0.0");
+        let recorder = Arc::new(Recorder::new().reply("cargo metadata", 0, &metadata).reply(
+            "rustup target list",
+            0,
+            "x86_64-unknown-linux-gnu\n",
+        ));
+        let options = Options {
+            arches: vec!["s390x".to_string()],
+            ..options(&dir)
+        };
 
-        fs::remove_dir_all(&dir).expect("cleanup temp test dir");
+        let artifacts = runner::with(recorder.clone(), || run(options)).unwrap();
+        assert_eq!(artifacts[0].path, dir.join("dist/tool_2.0.0-1_s390x.deb"));
+        assert_eq!(
+            recorder.calls()[1..],
+            [
+                "cargo deb --version".to_string(),
+                "s390x-linux-gnu-gcc --version".to_string(),
+                "rustup target list --installed".to_string(),
+                format!(
+                    "rustup target add s390x-unknown-linux-gnu (in {})",
+                    dir.display()
+                ),
+                format!(
+                    "cargo deb --target s390x-unknown-linux-gnu (in {})",
+                    dir.display()
+                ),
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn collects_the_dbgsym_package_next_to_the_deb() {
+        let dir = temp_test_dir();
+        let built = dir.join("target/debian");
+        fs::create_dir_all(&built).unwrap();
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        fs::write(built.join("tool_2.0.0-1_amd64.deb"), "deb").unwrap();
+        fs::write(built.join("tool-dbgsym_2.0.0-1_amd64.ddeb"), "ddeb").unwrap();
+        let metadata = single_crate_metadata(&dir, "tool", "2.0.0");
+        let recorder = Arc::new(Recorder::new().reply("cargo metadata", 0, &metadata));
+        let options = Options {
+            dbgsym: true,
+            ..options(&dir)
+        };
+
+        let artifacts = runner::with(recorder.clone(), || run(options)).unwrap();
+        assert_eq!(
+            artifacts
+                .iter()
+                .map(|artifact| (artifact.path.clone(), artifact.name.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (dir.join("dist/tool_2.0.0-1_amd64.deb"), "tool"),
+                (
+                    dir.join("dist/tool-dbgsym_2.0.0-1_amd64.ddeb"),
+                    "tool-dbgsym"
+                ),
+            ]
+        );
+        assert_eq!(
+            recorder.calls().last().unwrap(),
+            &format!("cargo deb --dbgsym (in {})", dir.display())
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn builds_every_architecture_in_turn() {
+        let dir = temp_test_dir();
+        for (triple, arch) in [
+            ("s390x-unknown-linux-gnu", "s390x"),
+            ("powerpc64le-unknown-linux-gnu", "ppc64el"),
+        ] {
+            let built = dir.join("target").join(triple).join("debian");
+            fs::create_dir_all(&built).unwrap();
+            fs::write(built.join(format!("tool_2.0.0-1_{arch}.deb")), "deb").unwrap();
+        }
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        let metadata = single_crate_metadata(&dir, "tool", "2.0.0");
+        let recorder = Arc::new(Recorder::new().reply("cargo metadata", 0, &metadata).reply(
+            "rustup target list",
+            0,
+            "s390x-unknown-linux-gnu\npowerpc64le-unknown-linux-gnu\n",
+        ));
+        let options = Options {
+            arches: vec!["s390x".to_string(), "ppc64el".to_string()],
+            linker: Linker::Zig,
+            ..options(&dir)
+        };
+
+        let artifacts = runner::with(recorder.clone(), || run(options)).unwrap();
+        assert_eq!(
+            artifacts
+                .iter()
+                .map(|artifact| artifact.path.clone())
+                .collect::<Vec<_>>(),
+            [
+                dir.join("dist/tool_2.0.0-1_s390x.deb"),
+                dir.join("dist/tool_2.0.0-1_ppc64el.deb"),
+            ]
+        );
+        let builds = recorder
+            .calls()
+            .into_iter()
+            .filter(|call| call.contains("--target"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            builds,
+            [
+                "cargo zigbuild --target s390x-unknown-linux-gnu --release",
+                "cargo deb --target s390x-unknown-linux-gnu --no-build",
+                "cargo zigbuild --target powerpc64le-unknown-linux-gnu --release",
+                "cargo deb --target powerpc64le-unknown-linux-gnu --no-build",
+            ]
+            .map(|call| format!("{call} (in {})", dir.display()))
+        );
+        fs::remove_dir_all(dir).unwrap();
     }
 }
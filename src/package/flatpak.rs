@@ -0,0 +1,323 @@
+//! `debkit package flatpak`: a flatpak-builder manifest for a crate, and optionally
+//! a `.flatpak` bundle built from it.
+//!
+//! The manifest builds the crate offline with the `rust-stable` SDK extension. Its
+//! dependencies come from `cargo-sources.json`, generated from `Cargo.lock` in the
+//! format of flatpak-cargo-generator: each crates.io package as an archive source
+//! vendored under `cargo/vendor`, plus a cargo config that uses that directory.
+//! Git dependencies are not supported. With `--build`, flatpak-builder builds the
+//! manifest into a repository under `target/flatpak/` and `flatpak build-bundle`
+//! exports it into the output directory.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use serde_json::json;
+use toml_edit::{DocumentMut, Item};
+
+use super::{Artifact, CRATES_IO, Project, absolute_path, run_step, select};
+use crate::{exec, system};
+
+pub const DEFAULT_RUNTIME_VERSION: &str = "24.08";
+const SOURCES: &str = "cargo-sources.json";
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The `Cargo.toml` of the crate to package, or of the workspace to pick members
+    /// of.
+    pub manifest_path: PathBuf,
+    /// Workspace members to package instead of the crate at `manifest_path`.
+    pub packages: Vec<String>,
+    /// Package every workspace member with a binary.
+    pub all_members: bool,
+    /// The application ID, such as `io.github.me.tool`; by default derived from a
+    /// GitHub `repository` URL.
+    pub app_id: Option<String>,
+    /// The `org.freedesktop.Platform` version to build against.
+    pub runtime_version: String,
+    pub output_dir: PathBuf,
+    /// Run flatpak-builder and export a `.flatpak` bundle, not just the manifest.
+    pub build: bool,
+}
+
+/// Writes a manifest for each selected crate into the output directory and, with
+/// `build`, bundles it. Returns the bundles, or the manifests without `build`.
+pub fn run(options: Options) -> anyhow::Result<Vec<Artifact>> {
+    let projects = select(
+        &options.manifest_path,
+        &options.packages,
+        options.all_members,
+    )?;
+    if options.app_id.is_some() && projects.len() > 1 {
+        bail!("--app-id names a single application; package one crate at a time with it");
+    }
+    if options.build {
+        for tool in ["flatpak-builder", "flatpak"] {
+            if !system::command_available(tool) {
+                bail!("`{tool}` is required for --build; install it with apt");
+            }
+        }
+    }
+
+    exec::create_dir_all(&options.output_dir).with_context(|| {
+        format!(
+            "failed to create output directory {}",
+            options.output_dir.display()
+        )
+    })?;
+    let output_dir = absolute_path(&options.output_dir)?;
+
+    projects
+        .iter()
+        .map(|project| package(project, &options, &output_dir))
+        .collect()
+}
+
+fn package(project: &Project, options: &Options, output_dir: &Path) -> anyhow::Result<Artifact> {
+    let app_id = match &options.app_id {
+        Some(app_id) => app_id.clone(),
+        None => default_app_id(project)?,
+    };
+    let lock = project.workspace_root.join("Cargo.lock");
+    let raw = fs::read_to_string(&lock).with_context(|| {
+        format!(
+            "failed to read {}; run `cargo generate-lockfile` first",
+            lock.display()
+        )
+    })?;
+    let sources = output_dir.join(SOURCES);
+    exec::write(&sources, to_json(&cargo_sources(&raw)?)?)
+        .with_context(|| format!("failed to write {}", sources.display()))?;
+    let manifest_path = output_dir.join(format!("{app_id}.json"));
+    exec::write(
+        &manifest_path,
+        to_json(&manifest(project, &app_id, &options.runtime_version)?)?,
+    )
+    .with_context(|| format!("failed to write {}", manifest_path.display()))?;
+
+    let artifact = |path| Artifact {
+        path,
+        name: app_id.clone(),
+        version: project.version.clone(),
+    };
+    if !options.build {
+        return Ok(artifact(manifest_path));
+    }
+
+    let work = project.target_dir.join("flatpak");
+    let repo = work.join("repo");
+    let step = format!("Building the flatpak for {}", project.name);
+    run_step(
+        &step,
+        Command::new("flatpak-builder")
+            .args(["--user", "--force-clean", "--install-deps-from=flathub"])
+            .arg(format!("--repo={}", repo.display()))
+            .arg(work.join("build"))
+            .arg(&manifest_path)
+            .current_dir(output_dir),
+    )?;
+    let bundle = output_dir.join(format!("{}-{}.flatpak", project.name, project.version));
+    run_step(
+        &format!("Bundling {}", bundle.display()),
+        Command::new("flatpak")
+            .arg("build-bundle")
+            .arg(&repo)
+            .arg(&bundle)
+            .arg(&app_id),
+    )?;
+    Ok(artifact(bundle))
+}
+
+/// `io.github.<owner>.<name>` for crates hosted on GitHub.
+fn default_app_id(project: &Project) -> anyhow::Result<String> {
+    let owner = project
+        .repository
+        .as_deref()
+        .and_then(|url| url.strip_prefix("https://github.com/"))
+        .and_then(|path| path.split('/').next())
+        .filter(|owner| !owner.is_empty());
+    let Some(owner) = owner else {
+        bail!(
+            "cannot derive an application ID for `{}` without a GitHub `repository`; pass --app-id",
+            project.name
+        );
+    };
+    let part = |value: &str| value.replace('-', "_");
+    Ok(format!("io.github.{}.{}", part(owner), part(&project.name)))
+}
+
+fn manifest(
+    project: &Project,
+    app_id: &str,
+    runtime_version: &str,
+) -> anyhow::Result<serde_json::Value> {
+    let Some(command) = project
+        .binaries
+        .iter()
+        .find(|binary| **binary == project.name)
+        .or(project.binaries.first())
+    else {
+        bail!("`{}` has no binary to package", project.name);
+    };
+    let mut build_commands = vec![
+        "cargo --offline fetch --verbose".to_string(),
+        format!(
+            "cargo --offline build --release --verbose --package {}",
+            project.name
+        ),
+    ];
+    build_commands.extend(
+        project
+            .binaries
+            .iter()
+            .map(|binary| format!("install -Dm755 target/release/{binary} -t /app/bin/")),
+    );
+    Ok(json!({
+        "id": app_id,
+        "runtime": "org.freedesktop.Platform",
+        "runtime-version": runtime_version,
+        "sdk": "org.freedesktop.Sdk",
+        "sdk-extensions": ["org.freedesktop.Sdk.Extension.rust-stable"],
+        "command": command,
+        "build-options": {
+            "append-path": "/usr/lib/sdk/rust-stable/bin",
+            "env": {"CARGO_HOME": format!("/run/build/{}/cargo", project.name)},
+        },
+        "modules": [{
+            "name": project.name,
+            "buildsystem": "simple",
+            "build-commands": build_commands,
+            "sources": [
+                {"type": "dir", "path": project.workspace_root},
+                SOURCES,
+            ],
+        }],
+    }))
+}
+
+/// The flatpak sources that vendor every crates.io package in `lock`.
+fn cargo_sources(lock: &str) -> anyhow::Result<Vec<serde_json::Value>> {
+    let document = lock.parse::<DocumentMut>().context("invalid Cargo.lock")?;
+    let mut sources = Vec::new();
+    for package in document
+        .get("package")
+        .and_then(Item::as_array_of_tables)
+        .into_iter()
+        .flatten()
+    {
+        let field = |key| package.get(key).and_then(Item::as_str).unwrap_or_default();
+        let (name, version, source) = (field("name"), field("version"), field("source"));
+        if source.is_empty() {
+            // A path dependency, built from the project directory itself.
+            continue;
+        }
+        if !CRATES_IO.contains(&source) {
+            bail!(
+                "{name} {version} comes from {source}; only crates.io dependencies can be vendored"
+            );
+        }
+        let checksum = field("checksum");
+        let dest = format!("cargo/vendor/{name}-{version}");
+        sources.push(json!({
+            "type": "archive",
+            "archive-type": "tar-gzip",
+            "url": format!("https://static.crates.io/crates/{name}/{name}-{version}.crate"),
+            "sha256": checksum,
+            "dest": dest,
+        }));
+        sources.push(json!({
+            "type": "inline",
+            "contents": json!({"package": checksum, "files": {}}).to_string(),
+            "dest": dest,
+            "dest-filename": ".cargo-checksum.json",
+        }));
+    }
+    sources.push(json!({
+        "type": "inline",
+        "contents": "[source.vendored-sources]\ndirectory = \"cargo/vendor\"\n\n[source.crates-io]\nreplace-with = \"vendored-sources\"\n",
+        "dest": "cargo",
+        "dest-filename": "config",
+    }));
+    Ok(sources)
+}
+
+fn to_json(value: &impl serde::Serialize) -> anyhow::Result<String> {
+    let mut json = serde_json::to_string_pretty(value)?;
+    json.push('\n');
+    Ok(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vendors_crates_io_packages_from_the_lockfile() {
+        let lock = r#"
+version = 4
+
+[[package]]
+name = "anyhow"
+version = "1.0.100"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "a23eb6b1614318a8071c9b2521f36b424b2c83db5eb3a0fead4a6c0809af6e61"
+
+[[package]]
+name = "tool"
+version = "2.0.0"
+dependencies = ["anyhow"]
+"#;
+        let sources = cargo_sources(lock).unwrap();
+        assert_eq!(sources.len(), 3);
+        assert_eq!(
+            sources[0]["url"],
+            "https://static.crates.io/crates/anyhow/anyhow-1.0.100.crate"
+        );
+        assert_eq!(sources[0]["dest"], "cargo/vendor/anyhow-1.0.100");
+        assert_eq!(
+            sources[1]["contents"],
+            r#"{"package":"a23eb6b1614318a8071c9b2521f36b424b2c83db5eb3a0fead4a6c0809af6e61","files":{}}"#
+        );
+        assert_eq!(sources[2]["dest-filename"], "config");
+
+        let git = "[[package]]\nname = \"fork\"\nversion = \"0.1.0\"\nsource = \"git+https://example.com/fork#abc\"\n";
+        let err = cargo_sources(git).unwrap_err();
+        assert!(err.to_string().contains("only crates.io"), "{err:#}");
+    }
+
+    #[test]
+    fn derives_the_app_id_and_build_commands() {
+        let project = Project {
+            name: "my-tool".to_string(),
+            version: "2.0.0".to_string(),
+            deb_name: "my-tool".to_string(),
+            rpm_name: "my-tool".to_string(),
+            binaries: vec!["my-tool".to_string(), "helper".to_string()],
+            repository: Some("https://github.com/some-one/my-tool".to_string()),
+            description: None,
+            maintainer: None,
+            dir: PathBuf::from("/work/tool"),
+            workspace_root: PathBuf::from("/work"),
+            target_dir: PathBuf::from("/work/target"),
+        };
+        assert_eq!(
+            default_app_id(&project).unwrap(),
+            "io.github.some_one.my_tool"
+        );
+        let manifest = manifest(&project, "io.github.some_one.my_tool", "24.08").unwrap();
+        assert_eq!(manifest["command"], "my-tool");
+        assert_eq!(
+            manifest["modules"][0]["build-commands"][3],
+            "install -Dm755 target/release/helper -t /app/bin/"
+        );
+        assert_eq!(manifest["modules"][0]["sources"][0]["path"], "/work");
+
+        let without_repository = Project {
+            repository: None,
+            ..project
+        };
+        assert!(default_app_id(&without_repository).is_err());
+    }
+}
@@ -0,0 +1,322 @@
+//! `debkit init`: adding cargo-deb metadata to any Rust project.
+//!
+//! [`defaults`] reads the project's Cargo.toml and directory for what a
+//! `[package.metadata.deb]` section needs: the package name, a maintainer from
+//! `package.authors` or git's `user.name` and `user.email`, the binaries and README as
+//! assets, and any systemd units in `debian/`. [`ask`] lets the user change those on a
+//! terminal and [`write`] adds the section, leaving the rest of the file as it was.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Table, Value};
+
+use crate::{confirm, exec};
+
+/// Where cargo-deb looks for maintainer scripts and systemd units.
+const SCRIPTS_DIR: &str = "debian/";
+
+/// The `[package.metadata.deb]` section `debkit init` writes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Metadata {
+    pub name: String,
+    /// `Name <email>`.
+    pub maintainer: String,
+    pub section: String,
+    pub priority: String,
+    /// Package relations; cargo-deb replaces `$auto` with the shared libraries the
+    /// binaries link against.
+    pub depends: Vec<String>,
+    /// `[source, target, mode]` triples, as cargo-deb expects them.
+    pub assets: Vec<[String; 3]>,
+    /// Whether to install, enable and start the `*.service` units in `debian/`.
+    pub systemd_units: bool,
+}
+
+/// What the project at `manifest` would get without any answers.
+pub fn defaults(manifest: &Path) -> anyhow::Result<Metadata> {
+    let document = read(manifest)?;
+    let dir = manifest.parent().unwrap_or(Path::new(""));
+    let package = document
+        .get("package")
+        .and_then(Item::as_table_like)
+        .with_context(|| format!("{} has no [package] table", manifest.display()))?;
+    let name = package
+        .get("name")
+        .and_then(Item::as_str)
+        .with_context(|| format!("{} has no package name", manifest.display()))?
+        .to_string();
+
+    let binaries = binaries(&document, &name, dir);
+    if binaries.is_empty() {
+        bail!("`{name}` has no binary to package");
+    }
+    let mut assets = binaries
+        .iter()
+        .map(|binary| {
+            [
+                format!("target/release/{binary}"),
+                format!("usr/bin/{binary}"),
+                "755".to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let readme = match package.get("readme").and_then(Item::as_str) {
+        Some(readme) => Some(readme.to_string()),
+        None => dir
+            .join("README.md")
+            .is_file()
+            .then(|| "README.md".to_string()),
+    };
+    if let Some(readme) = readme {
+        let file = Path::new(&readme).file_name().unwrap_or_default();
+        assets.push([
+            readme.clone(),
+            format!("usr/share/doc/{name}/{}", file.to_string_lossy()),
+            "644".to_string(),
+        ]);
+    }
+
+    let maintainer = match package
+        .get("authors")
+        .and_then(Item::as_array)
+        .and_then(|authors| authors.get(0))
+        .and_then(Value::as_str)
+    {
+        Some(author) => author.to_string(),
+        None => git_maintainer(dir),
+    };
+
+    Ok(Metadata {
+        name,
+        maintainer,
+        section: "utils".to_string(),
+        priority: "optional".to_string(),
+        depends: vec!["$auto".to_string()],
+        assets,
+        systemd_units: !units(&dir.join(SCRIPTS_DIR)).is_empty(),
+    })
+}
+
+/// Asks for the maintainer, section, dependencies and whether to enable the systemd
+/// units, offering `defaults` for each.
+pub fn ask(defaults: Metadata) -> anyhow::Result<Metadata> {
+    let maintainer = confirm::prompt("Maintainer (Name <email>)", &defaults.maintainer)?;
+    if maintainer.is_empty() {
+        bail!(
+            "the package needs a maintainer; set `package.authors` or git's user.name and user.email"
+        );
+    }
+    let section = confirm::prompt("Section", &defaults.section)?;
+    let depends = confirm::prompt("Depends (comma-separated)", &defaults.depends.join(", "))?;
+    let systemd_units = defaults.systemd_units && {
+        let answer = confirm::prompt("Install and enable the systemd units in debian/", "yes")?;
+        matches!(answer.to_ascii_lowercase().as_str(), "y" | "yes")
+    };
+    Ok(Metadata {
+        maintainer,
+        section,
+        depends: depends
+            .split(',')
+            .map(str::trim)
+            .filter(|depend| !depend.is_empty())
+            .map(str::to_string)
+            .collect(),
+        systemd_units,
+        ..defaults
+    })
+}
+
+/// Adds `metadata` to `manifest` as `[package.metadata.deb]`. An existing section is
+/// only replaced with `force`.
+pub fn write(manifest: &Path, metadata: &Metadata, force: bool) -> anyhow::Result<()> {
+    let mut document = read(manifest)?;
+    let package = document
+        .get_mut("package")
+        .and_then(Item::as_table_mut)
+        .with_context(|| format!("{} has no [package] table", manifest.display()))?;
+    let parent = package
+        .entry("metadata")
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        })
+        .as_table_mut()
+        .context("`package.metadata` is not a table")?;
+    if parent.contains_key("deb") && !force {
+        bail!(
+            "{} already has [package.metadata.deb]; pass --force to replace it",
+            manifest.display()
+        );
+    }
+    let mut deb = table(metadata);
+    // After every table already in the file, rather than straight after [package].
+    deb.set_position(usize::MAX);
+    parent.insert("deb", Item::Table(deb));
+    exec::write(manifest, document.to_string())
+        .with_context(|| format!("failed to write {}", manifest.display()))
+}
+
+fn read(manifest: &Path) -> anyhow::Result<DocumentMut> {
+    let raw = fs::read_to_string(manifest)
+        .with_context(|| format!("failed to read {}", manifest.display()))?;
+    raw.parse::<DocumentMut>()
+        .with_context(|| format!("{} is not valid TOML", manifest.display()))
+}
+
+/// The binaries cargo builds for the package: its `[[bin]]` targets, or the default
+/// ones from `src/main.rs` and `src/bin/`.
+fn binaries(document: &DocumentMut, name: &str, dir: &Path) -> Vec<String> {
+    if let Some(bins) = document.get("bin").and_then(Item::as_array_of_tables) {
+        return bins
+            .iter()
+            .filter_map(|bin| bin.get("name").and_then(Item::as_str))
+            .map(str::to_string)
+            .collect();
+    }
+    let mut binaries = Vec::new();
+    if dir.join("src/main.rs").is_file() {
+        binaries.push(name.to_string());
+    }
+    let mut extra = fs::read_dir(dir.join("src/bin"))
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rs"))
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+        .collect::<Vec<_>>();
+    extra.sort();
+    binaries.extend(extra);
+    binaries
+}
+
+fn units(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "service"))
+        .collect()
+}
+
+/// `Name <email>` from git's config for the project, or empty when it has neither.
+fn git_maintainer(dir: &Path) -> String {
+    let get = |key: &str| {
+        exec::query(
+            Command::new("git")
+                .args(["config", "--get", key])
+                .current_dir(dir),
+        )
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default()
+    };
+    match (get("user.name"), get("user.email")) {
+        (name, email) if name.is_empty() || email.is_empty() => String::new(),
+        (name, email) => format!("{name} <{email}>"),
+    }
+}
+
+fn table(metadata: &Metadata) -> Table {
+    let mut table = Table::new();
+    table.insert("name", toml_edit::value(&metadata.name));
+    table.insert("maintainer", toml_edit::value(&metadata.maintainer));
+    table.insert("section", toml_edit::value(&metadata.section));
+    table.insert("priority", toml_edit::value(&metadata.priority));
+    table.insert(
+        "depends",
+        toml_edit::value(metadata.depends.iter().collect::<Array>()),
+    );
+    let mut assets = metadata
+        .assets
+        .iter()
+        .map(|asset| {
+            let mut value = Value::Array(asset.iter().collect());
+            value.decor_mut().set_prefix("\n    ");
+            value
+        })
+        .collect::<Array>();
+    assets.set_trailing("\n");
+    assets.set_trailing_comma(true);
+    table.insert("assets", toml_edit::value(assets));
+    if metadata.systemd_units {
+        table.insert("maintainer-scripts", toml_edit::value(SCRIPTS_DIR));
+        let mut units = InlineTable::new();
+        units.insert("enable", true.into());
+        units.insert("start", true.into());
+        table.insert("systemd-units", toml_edit::value(units));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(manifest: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "debkit-init-{}-{}",
+            std::process::id(),
+            crate::state::now()
+        ));
+        fs::create_dir_all(dir.join("src/bin")).unwrap();
+        fs::create_dir_all(dir.join("debian")).unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.join("src/bin/helper.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.join("README.md"), "# demo\n").unwrap();
+        fs::write(dir.join("debian/demo.service"), "[Service]\n").unwrap();
+        fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+        dir.join("Cargo.toml")
+    }
+
+    #[test]
+    fn scaffolds_metadata_from_the_project() {
+        let manifest = project(
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nauthors = [\"Ada <ada@example.com>\"]\n\n# kept\n[dependencies]\nanyhow = \"1\"\n",
+        );
+        let metadata = defaults(&manifest).unwrap();
+        assert_eq!(metadata.maintainer, "Ada <ada@example.com>");
+        assert!(metadata.systemd_units);
+        write(&manifest, &metadata, false).unwrap();
+
+        let written = fs::read_to_string(&manifest).unwrap();
+        assert_eq!(
+            written,
+            r#"[package]
+name = "demo"
+version = "0.1.0"
+authors = ["Ada <ada@example.com>"]
+
+# kept
+[dependencies]
+anyhow = "1"
+
+[package.metadata.deb]
+name = "demo"
+maintainer = "Ada <ada@example.com>"
+section = "utils"
+priority = "optional"
+depends = ["$auto"]
+assets = [
+    ["target/release/demo", "usr/bin/demo", "755"],
+    ["target/release/helper", "usr/bin/helper", "755"],
+    ["README.md", "usr/share/doc/demo/README.md", "644"],
+]
+maintainer-scripts = "debian/"
+systemd-units = { enable = true, start = true }
+"#
+        );
+        let err = write(&manifest, &metadata, false).unwrap_err();
+        assert!(err.to_string().contains("--force"), "{err:#}");
+        write(&manifest, &metadata, true).unwrap();
+        assert_eq!(fs::read_to_string(&manifest).unwrap(), written);
+        fs::remove_dir_all(manifest.parent().unwrap()).unwrap();
+    }
+}
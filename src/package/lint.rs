@@ -0,0 +1,171 @@
+//! Checking built `.deb`s with lintian.
+//!
+//! lintian's tags are read from its output, grouped by severity and printed. In
+//! [`Mode::Strict`] an error tag fails the build; otherwise findings are only
+//! reported.
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+
+use crate::output::say;
+use crate::{exec, system};
+
+/// What lintian's findings do to the build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Mode {
+    /// Print them.
+    Report,
+    /// Print them and fail on errors.
+    Strict,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Pedantic,
+    Experimental,
+    Overridden,
+}
+
+impl Severity {
+    fn from_code(code: &str) -> Option<Severity> {
+        Some(match code {
+            "E" => Severity::Error,
+            "W" => Severity::Warning,
+            "I" => Severity::Info,
+            "P" => Severity::Pedantic,
+            "X" => Severity::Experimental,
+            "O" => Severity::Overridden,
+            _ => return None,
+        })
+    }
+
+    fn heading(self) -> &'static str {
+        match self {
+            Severity::Error => "errors",
+            Severity::Warning => "warnings",
+            Severity::Info => "info",
+            Severity::Pedantic => "pedantic",
+            Severity::Experimental => "experimental",
+            Severity::Overridden => "overridden",
+        }
+    }
+}
+
+/// One tag lintian emitted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub tag: String,
+    /// What the tag is about, such as the file; often empty.
+    pub detail: String,
+}
+
+pub fn ensure_available() -> anyhow::Result<()> {
+    if !system::command_available("lintian") {
+        bail!("lintian is required for --lint; install it with: sudo apt install lintian");
+    }
+    Ok(())
+}
+
+/// Runs lintian on `deb`, prints its findings and, in [`Mode::Strict`], fails when
+/// any is an error.
+pub fn check(deb: &Path, mode: Mode) -> anyhow::Result<Vec<Finding>> {
+    let output = exec::query(Command::new("lintian").arg(deb))
+        .context("`lintian` executable was not found in PATH")?;
+    // lintian exits with 1 when it found errors and 2 when it could not check.
+    if output.status.code().is_none_or(|code| code > 1) {
+        bail!(
+            "lintian failed on {}:\n{}",
+            deb.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let findings = parse(&String::from_utf8_lossy(&output.stdout));
+    let name = deb.file_name().unwrap_or_default().to_string_lossy();
+    print(&name, &findings);
+
+    let errors = findings
+        .iter()
+        .filter(|finding| finding.severity == Severity::Error)
+        .count();
+    if mode == Mode::Strict && errors > 0 {
+        bail!("lintian found {errors} error(s) in {name}");
+    }
+    Ok(findings)
+}
+
+/// lintian's `E: package: tag detail` lines, most severe first.
+fn parse(output: &str) -> Vec<Finding> {
+    let mut findings = output
+        .lines()
+        .filter_map(|line| {
+            let (code, rest) = line.split_once(": ")?;
+            let severity = Severity::from_code(code)?;
+            let (_package, rest) = rest.split_once(": ")?;
+            let (tag, detail) = rest.split_once(' ').unwrap_or((rest, ""));
+            Some(Finding {
+                severity,
+                tag: tag.to_string(),
+                detail: detail.trim().to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+    findings.sort_by_key(|finding| finding.severity);
+    findings
+}
+
+fn print(name: &str, findings: &[Finding]) {
+    if findings.is_empty() {
+        say!("lintian: {name} is clean");
+        return;
+    }
+    say!("lintian: {name}");
+    for (index, finding) in findings.iter().enumerate() {
+        if index == 0 || findings[index - 1].severity != finding.severity {
+            say!("  {}:", finding.severity.heading());
+        }
+        if finding.detail.is_empty() {
+            say!("    {}", finding.tag);
+        } else {
+            say!("    {} {}", finding.tag, finding.detail);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_lintian_tags_by_severity() {
+        let output = "W: tool: no-manual-page [usr/bin/tool]\n\
+                      E: tool: no-copyright-file\n\
+                      N: 1 tag overridden\n\
+                      I: tool: extended-description-is-probably-too-short\n";
+        assert_eq!(
+            parse(output),
+            [
+                Finding {
+                    severity: Severity::Error,
+                    tag: "no-copyright-file".to_string(),
+                    detail: String::new(),
+                },
+                Finding {
+                    severity: Severity::Warning,
+                    tag: "no-manual-page".to_string(),
+                    detail: "[usr/bin/tool]".to_string(),
+                },
+                Finding {
+                    severity: Severity::Info,
+                    tag: "extended-description-is-probably-too-short".to_string(),
+                    detail: String::new(),
+                },
+            ]
+        );
+    }
+}
@@ -1 +1,499 @@
+//! Building distribution packages of Rust crates.
+//!
+//! [`select`] reads the crates to package from `cargo metadata`; [`deb`] and [`rpm`]
+//! build them with cargo-deb, or [`native`] packaging, and cargo-generate-rpm,
+//! [`archive`] as plain `.tar.gz` or `.zip` archives, [`appimage`] as AppImages and
+//! [`flatpak`] as flatpak bundles, collecting the results in an output directory.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+use toml_edit::{DocumentMut, Item, Table, TableLike};
+
+use crate::exec;
+
+pub mod appimage;
+pub mod archive;
+pub mod assets;
+pub mod changelog;
+pub mod checksums;
+pub mod clean;
+pub mod control;
+pub mod cross;
 pub mod deb;
+pub mod flatpak;
+pub mod init;
+pub mod lint;
+pub mod naming;
+pub mod native;
+pub mod reproducible;
+pub mod rpm;
+pub mod sbom;
+pub mod scripts;
+pub mod sign;
+pub mod source;
+pub mod verify;
+pub mod version;
+
+/// The `source` of crates.io packages in `Cargo.lock`, through the git and the sparse
+/// index.
+pub const CRATES_IO: &[&str] = &[
+    "registry+https://github.com/rust-lang/crates.io-index",
+    "sparse+https://index.crates.io/",
+];
+
+/// A crate as `cargo metadata` describes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Project {
+    pub name: String,
+    pub version: String,
+    /// The name of its Debian package, which starts the `.deb` file name.
+    pub deb_name: String,
+    /// The name of its RPM package, which starts the `.rpm` file name.
+    pub rpm_name: String,
+    /// The names of its binary targets.
+    pub binaries: Vec<String>,
+    /// The `repository` URL from its manifest.
+    pub repository: Option<String>,
+    /// The `description` from its manifest.
+    pub description: Option<String>,
+    /// Who maintains its Debian package: `package.metadata.deb.maintainer`, or else
+    /// the first of its authors.
+    pub maintainer: Option<String>,
+    pub dir: PathBuf,
+    /// The directory of the workspace's `Cargo.toml` and `Cargo.lock`; the crate's own
+    /// directory when it is not in a workspace.
+    pub workspace_root: PathBuf,
+    /// Where cargo builds it, shared by every member of a workspace.
+    pub target_dir: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    packages: Vec<MetadataPackage>,
+    workspace_root: PathBuf,
+    target_directory: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataPackage {
+    name: String,
+    version: String,
+    manifest_path: PathBuf,
+    #[serde(default)]
+    repository: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    targets: Vec<MetadataTarget>,
+    #[serde(default)]
+    metadata: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataTarget {
+    name: String,
+    kind: Vec<String>,
+}
+
+impl MetadataPackage {
+    fn binaries(&self) -> impl Iterator<Item = &str> {
+        self.targets
+            .iter()
+            .filter(|target| target.kind.iter().any(|kind| kind == "bin"))
+            .map(|target| target.name.as_str())
+    }
+}
+
+/// A built package in the output directory.
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub path: PathBuf,
+    pub name: String,
+    pub version: String,
+}
+
+/// The crates to package from the workspace `manifest_path` belongs to: the members
+/// named in `packages`, every member with a binary for `all_members`, or otherwise the
+/// crate at `manifest_path` itself. Read with `cargo metadata`, which also resolves
+/// versions inherited from the workspace.
+pub fn select(
+    manifest_path: &Path,
+    packages: &[String],
+    all_members: bool,
+) -> anyhow::Result<Vec<Project>> {
+    let manifest_path = fs::canonicalize(manifest_path)
+        .with_context(|| format!("no Cargo.toml at {}", manifest_path.display()))?;
+    let output = exec::query(
+        Command::new("cargo")
+            .args([
+                "metadata",
+                "--no-deps",
+                "--format-version",
+                "1",
+                "--manifest-path",
+            ])
+            .arg(&manifest_path),
+    )
+    .context("`cargo` executable was not found in PATH")?;
+    if !output.status.success() {
+        bail!(
+            "`cargo metadata` failed for {}:\n{}",
+            manifest_path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let metadata: Metadata =
+        serde_json::from_slice(&output.stdout).context("unexpected `cargo metadata` output")?;
+    let members = || {
+        metadata
+            .packages
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let selected = if all_members {
+        let binaries = metadata
+            .packages
+            .iter()
+            .filter(|package| package.binaries().next().is_some())
+            .collect::<Vec<_>>();
+        if binaries.is_empty() {
+            bail!("no workspace member has a binary to package");
+        }
+        binaries
+    } else if !packages.is_empty() {
+        packages
+            .iter()
+            .map(|name| {
+                metadata
+                    .packages
+                    .iter()
+                    .find(|package| package.name == *name)
+                    .with_context(|| {
+                        format!(
+                            "the workspace has no member `{name}`; its members are {}",
+                            members()
+                        )
+                    })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+    } else {
+        let Some(package) = metadata
+            .packages
+            .iter()
+            .find(|package| package.manifest_path == manifest_path)
+        else {
+            bail!(
+                "{} is a workspace without a package of its own; pick members ({}) with --package or use --all-members",
+                manifest_path.display(),
+                members()
+            );
+        };
+        vec![package]
+    };
+
+    Ok(selected
+        .into_iter()
+        .map(|package| Project {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            // cargo-deb's default, as Debian package names cannot contain underscores.
+            deb_name: package.metadata["deb"]["name"]
+                .as_str()
+                .map_or_else(|| package.name.replace('_', "-"), str::to_string),
+            rpm_name: package.metadata["generate-rpm"]["name"]
+                .as_str()
+                .map_or_else(|| package.name.clone(), str::to_string),
+            binaries: package.binaries().map(str::to_string).collect(),
+            repository: package.repository.clone(),
+            description: package.description.clone(),
+            // cargo-deb's default too.
+            maintainer: package.metadata["deb"]["maintainer"]
+                .as_str()
+                .or_else(|| package.authors.first().map(String::as_str))
+                .map(str::to_string),
+            dir: package
+                .manifest_path
+                .parent()
+                .unwrap_or(Path::new("/"))
+                .to_path_buf(),
+            workspace_root: metadata.workspace_root.clone(),
+            target_dir: metadata.target_directory.clone(),
+        })
+        .collect())
+}
+
+/// Lets `change` edit the `[package.metadata.deb]` table of `project`'s `Cargo.toml`,
+/// creating it if needed, and writes the manifest back when `change` returns true.
+/// Comments and formatting elsewhere are kept.
+pub fn update_deb_metadata(
+    project: &Project,
+    change: impl FnOnce(&mut dyn TableLike) -> bool,
+) -> anyhow::Result<()> {
+    let manifest = project.dir.join("Cargo.toml");
+    let raw = fs::read_to_string(&manifest)
+        .with_context(|| format!("failed to read {}", manifest.display()))?;
+    let mut document = raw
+        .parse::<DocumentMut>()
+        .with_context(|| format!("{} is not valid TOML", manifest.display()))?;
+    let deb = document
+        .get_mut("package")
+        .and_then(Item::as_table_mut)
+        .with_context(|| format!("{} has no [package] table", manifest.display()))?
+        .entry("metadata")
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        })
+        .as_table_like_mut()
+        .context("`package.metadata` is not a table")?
+        .entry("deb")
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_position(usize::MAX);
+            Item::Table(table)
+        })
+        .as_table_like_mut()
+        .context("`package.metadata.deb` is not a table")?;
+    if !change(deb) {
+        return Ok(());
+    }
+    exec::write(&manifest, document.to_string())
+        .with_context(|| format!("failed to write {}", manifest.display()))
+}
+
+/// Unpacks the built package `deb` with `dpkg-deb`, lets `change` edit the unpacked
+/// tree, and packs it again in place with `envs` set. `what` names the change in the
+/// progress step.
+pub fn repack_deb(
+    project: &Project,
+    deb: &Path,
+    what: &str,
+    envs: &[(&str, &str)],
+    change: impl FnOnce(&Path) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let name = deb.file_name().unwrap_or_default().to_string_lossy();
+    let root = project.target_dir.join("debian").join("repack-root");
+    if root.exists() {
+        exec::remove_dir_all(&root)
+            .with_context(|| format!("failed to remove {}", root.display()))?;
+    }
+    run_step(
+        &format!("Unpacking {name}"),
+        Command::new("dpkg-deb").arg("-R").arg(deb).arg(&root),
+    )?;
+    change(&root)?;
+    run_step(
+        &format!("Repacking {name} with {what}"),
+        Command::new("dpkg-deb")
+            .args(["--root-owner-group", "--build"])
+            .arg(&root)
+            .arg(deb)
+            .envs(envs.iter().copied()),
+    )?;
+    exec::remove_dir_all(&root).with_context(|| format!("failed to remove {}", root.display()))
+}
+
+/// Runs `program` in `cwd` as the progress step `step`.
+pub fn run_command(step: &str, program: &str, args: &[String], cwd: &Path) -> anyhow::Result<()> {
+    run_step(step, Command::new(program).args(args).current_dir(cwd))
+}
+
+/// Runs `command` as the progress step `step`, failing with its output unless it
+/// succeeds.
+pub fn run_step(step: &str, command: &mut Command) -> anyhow::Result<()> {
+    let output = exec::output_step(command, step).with_context(|| {
+        format!(
+            "failed to start `{}`",
+            command.get_program().to_string_lossy()
+        )
+    })?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    bail!(
+        "command `{}` failed with status {}\nstdout:\n{}\nstderr:\n{}",
+        exec::describe(command),
+        output.status,
+        stdout,
+        stderr,
+    );
+}
+
+/// The most recently modified `.<extension>` file in `dir` whose name starts with
+/// `prefix`.
+pub fn newest_matching(dir: &Path, prefix: &str, extension: &str) -> anyhow::Result<PathBuf> {
+    let mut candidates = Vec::new();
+
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("failed to read artifact directory {}", dir.display()))?;
+
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+
+        let filename = match path.file_name().and_then(OsStr::to_str) {
+            Some(name) => name,
+            None => continue,
+        };
+        if !filename.starts_with(prefix) {
+            continue;
+        }
+        if path.extension().and_then(OsStr::to_str) != Some(extension) {
+            continue;
+        }
+
+        let modified = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .with_context(|| format!("failed to read mtime for {}", path.display()))?;
+        candidates.push((modified, path));
+    }
+
+    candidates.sort_by(|(a_time, a_path), (b_time, b_path)| {
+        a_time
+            .cmp(b_time)
+            .then_with(|| a_path.file_name().cmp(&b_path.file_name()))
+    });
+
+    candidates.pop().map(|(_, path)| path).with_context(|| {
+        format!(
+            "no .{} artifacts found in {} matching prefix {}",
+            extension,
+            dir.display(),
+            prefix
+        )
+    })
+}
+
+/// The directory under `target/` cargo builds `profile` into.
+pub fn profile_dir(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        profile => profile,
+    }
+}
+
+/// `path` relative to the current directory made absolute.
+pub fn absolute_path(path: &Path) -> anyhow::Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+
+    Ok(std::env::current_dir()
+        .context("failed to read current working directory")?
+        .join(path))
+}
+
+/// `cargo metadata` output for a workspace at `dir` holding the one crate `name` with
+/// a binary of the same name, for tests that stand in for cargo with a
+/// [`crate::runner::Recorder`].
+#[cfg(test)]
+pub(crate) fn single_crate_metadata(dir: &Path, name: &str, version: &str) -> String {
+    format!(
+        r#"{{"packages": [{{"name": "{name}", "version": "{version}", "manifest_path": "{0}/Cargo.toml",
+            "targets": [{{"name": "{name}", "kind": ["bin"]}}], "metadata": null}}], "workspace_root": "{0}", "target_directory": "{0}/target"}}"#,
+        dir.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn selects_crates_from_cargo_metadata() {
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let metadata = format!(
+            r#"{{"packages": [
+                {{"name": "my_tool", "version": "1.2.0", "manifest_path": "/work/tool/Cargo.toml",
+                  "targets": [{{"name": "my_tool", "kind": ["bin"]}}], "metadata": null}},
+                {{"name": "shared", "version": "1.2.0", "manifest_path": "{}",
+                  "targets": [{{"name": "shared", "kind": ["lib"]}}], "metadata": null}},
+                {{"name": "agent", "version": "0.3.0", "manifest_path": "/work/agent/Cargo.toml",
+                  "targets": [{{"name": "agent", "kind": ["lib"]}}, {{"name": "agentd", "kind": ["bin"]}}],
+                  "metadata": {{"deb": {{"name": "acme-agent"}}}}}}
+            ], "workspace_root": "/work", "target_directory": "/work/target"}}"#,
+            manifest.display()
+        );
+        let recorder = Arc::new(Recorder::new().reply("cargo metadata", 0, &metadata));
+        let pick = |packages: &[&str], all_members| {
+            let packages = packages
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>();
+            runner::with(recorder.clone(), || {
+                select(&manifest, &packages, all_members)
+            })
+        };
+        let names = |projects: Vec<Project>| {
+            projects
+                .into_iter()
+                .map(|project| project.deb_name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(pick(&[], false).unwrap()), ["shared"]);
+        assert_eq!(names(pick(&[], true).unwrap()), ["my-tool", "acme-agent"]);
+        let agent = pick(&["agent"], false).unwrap().remove(0);
+        assert_eq!(
+            agent,
+            Project {
+                name: "agent".to_string(),
+                version: "0.3.0".to_string(),
+                deb_name: "acme-agent".to_string(),
+                rpm_name: "agent".to_string(),
+                binaries: vec!["agentd".to_string()],
+                repository: None,
+                description: None,
+                maintainer: None,
+                dir: PathBuf::from("/work/agent"),
+                workspace_root: PathBuf::from("/work"),
+                target_dir: PathBuf::from("/work/target"),
+            }
+        );
+        let err = pick(&["missing"], false).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("its members are my_tool, shared, agent"),
+            "{err:#}"
+        );
+        assert_eq!(
+            recorder.calls()[0],
+            format!(
+                "cargo metadata --no-deps --format-version 1 --manifest-path {}",
+                manifest.display()
+            )
+        );
+
+        // The real thing, for DebKit's own manifest.
+        let own = select(&manifest, &[], false).unwrap().remove(0);
+        assert_eq!(own.deb_name, "debkit");
+        assert_eq!(
+            own.maintainer.as_deref(),
+            Some("John Allen <john@example.com>")
+        );
+        assert_eq!(own.version, env!("CARGO_PKG_VERSION"));
+    }
+}
@@ -0,0 +1,145 @@
+//! `package deb --name-template`: the file names packages get in the output directory.
+//!
+//! By default each `.deb` keeps the `<name>_<version>_<arch>.deb` name it was built
+//! with. A template such as `{name}_{version}_{arch}_{channel}.deb` names it instead,
+//! from the package's name, version and architecture, the `--channel` it is built
+//! for, the cargo profile, and the short hash of the commit it was built from.
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+
+use super::deb::DebFile;
+use crate::exec;
+
+/// The variables a template can use, in braces.
+pub const VARIABLES: [&str; 6] = ["name", "version", "arch", "channel", "profile", "git-sha"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    template: String,
+    /// What `{channel}` stands for, such as `stable` or `nightly`.
+    channel: Option<String>,
+}
+
+impl NameTemplate {
+    /// Checks that `template` only uses known variables, and has a `channel` to fill
+    /// in if it uses `{channel}`.
+    pub fn parse(template: &str, channel: Option<String>) -> anyhow::Result<Self> {
+        if template.contains('/') {
+            bail!("--name-template names a file, so it cannot contain `/`");
+        }
+        let template = Self {
+            template: template.to_string(),
+            channel,
+        };
+        for variable in template.variables()? {
+            if !VARIABLES.contains(&variable) {
+                bail!(
+                    "--name-template has an unknown variable {{{variable}}}; it can use {}",
+                    VARIABLES
+                        .map(|variable| format!("{{{variable}}}"))
+                        .join(", ")
+                );
+            }
+            if variable == "channel" && template.channel.is_none() {
+                bail!("--name-template uses {{channel}}, so --channel must say what it is");
+            }
+        }
+        Ok(template)
+    }
+
+    /// Whether the template uses `{variable}`.
+    pub fn uses(&self, variable: &str) -> bool {
+        self.variables()
+            .is_ok_and(|variables| variables.contains(&variable))
+    }
+
+    /// The file name of the package `deb` built with `profile` from the commit
+    /// `git_sha`, which is only needed when the template uses `{git-sha}`.
+    pub fn render(&self, deb: &DebFile, profile: &str, git_sha: Option<&str>) -> String {
+        let mut name = String::new();
+        let mut rest = self.template.as_str();
+        while let Some((before, after)) = rest.split_once('{') {
+            let Some((variable, after)) = after.split_once('}') else {
+                break;
+            };
+            name.push_str(before);
+            name.push_str(match variable {
+                "name" => &deb.name,
+                "version" => &deb.version,
+                "arch" => &deb.arch,
+                "channel" => self.channel.as_deref().unwrap_or_default(),
+                "profile" => profile,
+                "git-sha" => git_sha.unwrap_or_default(),
+                _ => "",
+            });
+            rest = after;
+        }
+        name.push_str(rest);
+        name
+    }
+
+    fn variables(&self) -> anyhow::Result<Vec<&str>> {
+        let mut variables = Vec::new();
+        let mut rest = self.template.as_str();
+        while let Some((_, after)) = rest.split_once('{') {
+            let (variable, after) = after.split_once('}').with_context(|| {
+                format!("--name-template `{}` has an unclosed `{{`", self.template)
+            })?;
+            variables.push(variable);
+            rest = after;
+        }
+        Ok(variables)
+    }
+}
+
+/// The short hash of the commit checked out in `dir`.
+pub fn git_sha(dir: &Path) -> anyhow::Result<String> {
+    let output = exec::query(
+        Command::new("git")
+            .args(["rev-parse", "--short", "HEAD"])
+            .current_dir(dir),
+    )
+    .context("`git` executable was not found in PATH")?;
+    let sha = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || sha.is_empty() {
+        bail!(
+            "--name-template uses {{git-sha}}, but {} has no commit",
+            dir.display()
+        );
+    }
+    Ok(sha)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_in_the_variables() {
+        let template = NameTemplate::parse(
+            "{name}_{version}_{arch}_{channel}-{profile}+{git-sha}.deb",
+            Some("nightly".to_string()),
+        )
+        .unwrap();
+        let deb = DebFile::parse(Path::new("/target/debian/tool_1.0.0-1_arm64.deb")).unwrap();
+        assert_eq!(
+            template.render(&deb, "release", Some("abc1234")),
+            "tool_1.0.0-1_arm64_nightly-release+abc1234.deb"
+        );
+        assert!(template.uses("git-sha"));
+        assert!(
+            !NameTemplate::parse("{name}.deb", None)
+                .unwrap()
+                .uses("arch")
+        );
+
+        let err = NameTemplate::parse("{name}_{sha}.deb", None).unwrap_err();
+        assert!(err.to_string().contains("unknown variable {sha}"), "{err}");
+        assert!(NameTemplate::parse("{name}_{channel}.deb", None).is_err());
+        assert!(NameTemplate::parse("{name.deb", None).is_err());
+        assert!(NameTemplate::parse("dist/{name}.deb", None).is_err());
+    }
+}
@@ -0,0 +1,696 @@
+//! `package deb --backend native`: `.deb` files assembled by DebKit itself, so build
+//! machines need no cargo-deb.
+//!
+//! The crate is built with `cargo build`, then the package is written directly as an
+//! `ar` archive of `debian-binary`, `control.tar.xz` and `data.tar.xz`. What goes in it
+//! comes from the `[package.metadata.deb]` cargo-deb reads: `assets`, as `$auto` or
+//! `[source, destination, mode]` entries whose file name may hold a `*`, and
+//! `depends`, `section`, `priority`, `revision`, `extended-description`,
+//! `conf-files`, `maintainer-scripts` and `changelog`. Files under `/etc` are
+//! conffiles. Every entry is owned by root, dated `SOURCE_DATE_EPOCH` when it is set,
+//! and written in name order, so the same inputs give the same bytes.
+//!
+//! Working out shared library dependencies needs dpkg-shlibdeps, so `$auto` in
+//! `depends` is left out, and `systemd-units` are only handled by cargo-deb.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, bail};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use lzma_rust2::{XzOptions, XzWriter};
+use md5::{Digest, Md5};
+use toml_edit::{DocumentMut, Item, Value};
+use tracing::{debug, warn};
+
+use super::cross::{self, Target};
+use super::deb::{self, Options};
+use super::{Project, profile_dir, run_step};
+use crate::exec;
+
+/// The maintainer scripts picked up from `maintainer-scripts`.
+const SCRIPTS: [&str; 4] = ["preinst", "postinst", "prerm", "postrm"];
+
+/// What `[package.metadata.deb]` says about the package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Metadata {
+    assets: Vec<Asset>,
+    depends: Vec<String>,
+    section: Option<String>,
+    priority: String,
+    revision: String,
+    extended_description: Option<String>,
+    conf_files: Vec<String>,
+    maintainer_scripts: Option<String>,
+    changelog: Option<String>,
+}
+
+/// An `assets` entry: files to install, with their mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Asset {
+    /// Relative to the crate; `target/release/` stands for where cargo put the build.
+    source: String,
+    /// A directory when it ends with `/`.
+    destination: String,
+    mode: u32,
+}
+
+/// A file in one of the package's tar archives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct File {
+    /// Relative to the root, without a leading `/`.
+    path: String,
+    mode: u32,
+    contents: Vec<u8>,
+}
+
+/// Builds `project` for `target`, or the build machine, and writes its `.deb` where
+/// cargo-deb would, with `envs` set for the build.
+pub fn build(
+    project: &Project,
+    options: &Options,
+    arch: Option<&str>,
+    target: Option<Target>,
+    envs: &[(&str, &str)],
+) -> anyhow::Result<()> {
+    let metadata = read_metadata(project)?;
+    let deb_arch = match target.map(|target| target.arch).or(arch) {
+        Some(arch) => arch,
+        None => Target::host().map(|target| target.arch).with_context(|| {
+            format!(
+                "no Debian architecture is known for {}; pick one with --arch",
+                std::env::consts::ARCH
+            )
+        })?,
+    };
+
+    // cargo-deb strips release builds unless told otherwise; rustc can do it too.
+    let profile = if options.release { "RELEASE" } else { "DEV" };
+    let strip_variable = format!("CARGO_PROFILE_{profile}_STRIP");
+    let mut envs = envs.to_vec();
+    match options.strip {
+        Some(true) => envs.push((&strip_variable, "symbols")),
+        Some(false) => envs.push((&strip_variable, "false")),
+        None if options.release => envs.push((&strip_variable, "symbols")),
+        None => {}
+    }
+    let built_already = match target {
+        Some(target) => cross::build(target, options.linker, options.release, &envs, &project.dir)
+            .with_context(|| format!("failed to build {} for {}", project.name, target.arch))?,
+        None => false,
+    };
+    if !built_already {
+        let mut command = Command::new("cargo");
+        command
+            .arg("build")
+            .current_dir(&project.dir)
+            .envs(envs.iter().copied());
+        if let Some(target) = target {
+            command.args(["--target", target.triple]);
+            cross::configure(&mut command, target, options.linker);
+        }
+        if options.release {
+            command.arg("--release");
+        }
+        run_step(&format!("Building {}", project.name), &mut command)
+            .with_context(|| format!("failed to build {}", project.name))?;
+    }
+    if exec::is_dry_run() {
+        return Ok(());
+    }
+
+    // The reproducible rebuild moves the whole target directory.
+    let target_dir =
+        env(&envs, "CARGO_TARGET_DIR").map_or(project.target_dir.clone(), PathBuf::from);
+    let mut built = target_dir.clone();
+    if let Some(target) = target {
+        built.push(target.triple);
+    }
+    built.push(profile_dir(if options.release { "release" } else { "dev" }));
+    let epoch = match env(&envs, "SOURCE_DATE_EPOCH") {
+        Some(epoch) => epoch
+            .parse()
+            .with_context(|| format!("SOURCE_DATE_EPOCH `{epoch}` is not a number"))?,
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .context("the clock is before 1970")?
+            .as_secs(),
+    };
+    // `--version` without `--write-version` labels the package as cargo-deb's
+    // `--deb-version` does, revision and all.
+    let version = if options.version.is_some() && !options.write_version {
+        project.version.clone()
+    } else {
+        debian_version(&project.version, &metadata.revision)
+    };
+
+    let package = assemble(project, &metadata, &built, deb_arch, &version, epoch)?;
+    let dir = deb::debian_dir(&target_dir, target);
+    exec::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let path = dir.join(format!("{}_{version}_{deb_arch}.deb", project.deb_name));
+    exec::write(&path, package).with_context(|| format!("failed to write {}", path.display()))?;
+    debug!("assembled {}", path.display());
+    Ok(())
+}
+
+/// The package's bytes, with the binaries cargo built in `built`.
+fn assemble(
+    project: &Project,
+    metadata: &Metadata,
+    built: &Path,
+    arch: &str,
+    version: &str,
+    epoch: u64,
+) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    for asset in &metadata.assets {
+        let sources = sources(project, built, &asset.source)?;
+        let destination = asset.destination.trim_start_matches('/');
+        for source in &sources {
+            let path = if destination.ends_with('/') || sources.len() > 1 {
+                let name = source.file_name().unwrap_or_default().to_string_lossy();
+                format!("{}/{name}", destination.trim_end_matches('/'))
+            } else {
+                destination.to_string()
+            };
+            let contents =
+                fs::read(source).with_context(|| format!("failed to read {}", source.display()))?;
+            data.push(File {
+                path,
+                mode: asset.mode,
+                contents,
+            });
+        }
+    }
+    if let Some(changelog) = &metadata.changelog {
+        let path = project.dir.join(changelog);
+        let text = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        // gzip -9n: no name or time in the header.
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&text)?;
+        data.push(File {
+            path: format!("usr/share/doc/{}/changelog.Debian.gz", project.deb_name),
+            mode: 0o644,
+            contents: encoder.finish()?,
+        });
+    }
+
+    let mut control = vec![File {
+        path: "control".to_string(),
+        mode: 0o644,
+        contents: control_file(project, metadata, arch, version, &data)?.into_bytes(),
+    }];
+    let md5sums = data
+        .iter()
+        .map(|file| format!("{:x}  {}\n", Md5::digest(&file.contents), file.path))
+        .collect::<String>();
+    control.push(File {
+        path: "md5sums".to_string(),
+        mode: 0o644,
+        contents: md5sums.into_bytes(),
+    });
+    let mut conffiles = data
+        .iter()
+        .filter(|file| file.path.starts_with("etc/"))
+        .map(|file| format!("/{}", file.path))
+        .chain(
+            metadata
+                .conf_files
+                .iter()
+                .map(|file| format!("/{}", file.trim_start_matches('/'))),
+        )
+        .collect::<Vec<_>>();
+    conffiles.sort();
+    conffiles.dedup();
+    if !conffiles.is_empty() {
+        control.push(File {
+            path: "conffiles".to_string(),
+            mode: 0o644,
+            contents: conffiles
+                .iter()
+                .map(|file| format!("{file}\n"))
+                .collect::<String>()
+                .into_bytes(),
+        });
+    }
+    if let Some(dir) = &metadata.maintainer_scripts {
+        let dir = project.dir.join(dir);
+        for name in SCRIPTS {
+            let path = dir.join(name);
+            if !path.is_file() {
+                continue;
+            }
+            let script = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            // Nothing is generated to fill in, unlike with cargo-deb's systemd units.
+            control.push(File {
+                path: name.to_string(),
+                mode: 0o755,
+                contents: script.replace("#DEBHELPER#", "").into_bytes(),
+            });
+        }
+    }
+
+    Ok(ar(
+        &[
+            ("debian-binary", b"2.0\n".to_vec()),
+            ("control.tar.xz", xz(&tar(&control, epoch)?)?),
+            ("data.tar.xz", xz(&tar(&data, epoch)?)?),
+        ],
+        epoch,
+    ))
+}
+
+fn control_file(
+    project: &Project,
+    metadata: &Metadata,
+    arch: &str,
+    version: &str,
+    data: &[File],
+) -> anyhow::Result<String> {
+    let maintainer = project.maintainer.as_deref().with_context(|| {
+        format!(
+            "{} has no maintainer; set `maintainer` under [package.metadata.deb] or `authors`",
+            project.name
+        )
+    })?;
+    let description = project.description.as_deref().with_context(|| {
+        format!(
+            "{} has no `description` in Cargo.toml for the package",
+            project.name
+        )
+    })?;
+    let installed_size = data
+        .iter()
+        .map(|file| file.contents.len().div_ceil(1024))
+        .sum::<usize>();
+
+    let mut control = format!(
+        "Package: {}\nVersion: {version}\nArchitecture: {arch}\nMaintainer: {maintainer}\n\
+         Installed-Size: {installed_size}\n",
+        project.deb_name
+    );
+    if !metadata.depends.is_empty() {
+        control.push_str(&format!("Depends: {}\n", metadata.depends.join(", ")));
+    }
+    if let Some(section) = &metadata.section {
+        control.push_str(&format!("Section: {section}\n"));
+    }
+    control.push_str(&format!("Priority: {}\n", metadata.priority));
+    if let Some(repository) = &project.repository {
+        control.push_str(&format!("Homepage: {repository}\n"));
+    }
+    control.push_str(&format!("Description: {}\n", description.trim()));
+    if let Some(extended) = &metadata.extended_description {
+        for line in extended.trim().lines() {
+            match line.trim_end() {
+                "" => control.push_str(" .\n"),
+                line => control.push_str(&format!(" {line}\n")),
+            }
+        }
+    }
+    Ok(control)
+}
+
+fn read_metadata(project: &Project) -> anyhow::Result<Metadata> {
+    let manifest = project.dir.join("Cargo.toml");
+    let raw = fs::read_to_string(&manifest)
+        .with_context(|| format!("failed to read {}", manifest.display()))?;
+    let document = raw
+        .parse::<DocumentMut>()
+        .with_context(|| format!("{} is not valid TOML", manifest.display()))?;
+    metadata(project, &document)
+}
+
+fn metadata(project: &Project, document: &DocumentMut) -> anyhow::Result<Metadata> {
+    let deb = document
+        .get("package")
+        .and_then(|package| package.get("metadata"))
+        .and_then(|metadata| metadata.get("deb"));
+    let get = |key| deb.and_then(|deb| deb.get(key));
+    let string = |key| get(key).and_then(Item::as_str).map(str::to_string);
+    let strings = |key| {
+        get(key)
+            .and_then(Item::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.as_str().map(str::to_string))
+            .collect::<Vec<_>>()
+    };
+    if get("systemd-units").is_some() {
+        bail!(
+            "{} has `systemd-units`, which only cargo-deb packages; use --backend cargo-deb",
+            project.name
+        );
+    }
+
+    let auto = || {
+        let mut assets = project
+            .binaries
+            .iter()
+            .map(|binary| Asset {
+                source: format!("target/release/{binary}"),
+                destination: format!("usr/bin/{binary}"),
+                mode: 0o755,
+            })
+            .collect::<Vec<_>>();
+        if project.dir.join("README.md").is_file() {
+            assets.push(Asset {
+                source: "README.md".to_string(),
+                destination: format!("usr/share/doc/{}/README", project.deb_name),
+                mode: 0o644,
+            });
+        }
+        assets
+    };
+    let assets = match get("assets") {
+        None => auto(),
+        Some(item) => {
+            let entries = item
+                .as_array()
+                .context("`package.metadata.deb.assets` is not an array")?;
+            let mut assets = Vec::new();
+            for entry in entries {
+                if entry.as_str() == Some("$auto") {
+                    assets.extend(auto());
+                    continue;
+                }
+                let fields = entry
+                    .as_array()
+                    .map(|fields| fields.iter().filter_map(Value::as_str).collect::<Vec<_>>());
+                let Some([source, destination, mode]) = fields.as_deref() else {
+                    bail!(
+                        "`{}` in `package.metadata.deb.assets` is not `$auto` or [source, destination, mode]",
+                        entry.to_string().trim()
+                    );
+                };
+                assets.push(Asset {
+                    source: source.to_string(),
+                    destination: destination.to_string(),
+                    mode: u32::from_str_radix(mode, 8)
+                        .with_context(|| format!("`{mode}` is not an octal file mode"))?,
+                });
+            }
+            assets
+        }
+    };
+
+    let mut depends = match get("depends") {
+        Some(item) if item.is_str() => item
+            .as_str()
+            .unwrap_or_default()
+            .split(',')
+            .map(|depend| depend.trim().to_string())
+            .filter(|depend| !depend.is_empty())
+            .collect(),
+        _ => strings("depends"),
+    };
+    if depends.iter().any(|depend| depend == "$auto") {
+        warn!(
+            "the native backend leaves `$auto` out of {}'s Depends; list the libraries it needs in `depends`",
+            project.name
+        );
+        depends.retain(|depend| depend != "$auto");
+    }
+
+    Ok(Metadata {
+        assets,
+        depends,
+        section: string("section"),
+        priority: string("priority").unwrap_or_else(|| "optional".to_string()),
+        revision: string("revision").unwrap_or_else(|| "1".to_string()),
+        extended_description: string("extended-description"),
+        conf_files: strings("conf-files"),
+        maintainer_scripts: string("maintainer-scripts"),
+        changelog: string("changelog"),
+    })
+}
+
+/// The files an asset's `source` names, where a `*` in the file name matches any run
+/// of characters.
+fn sources(project: &Project, built: &Path, source: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let path = match source.strip_prefix("target/release/") {
+        Some(rest) => built.join(rest),
+        None => project.dir.join(source),
+    };
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let Some((prefix, suffix)) = name.split_once('*') else {
+        if !path.is_file() {
+            bail!("the asset {source} is not at {}", path.display());
+        }
+        return Ok(vec![path]);
+    };
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut matches = Vec::new();
+    if dir.is_dir() {
+        for entry in
+            fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?
+        {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if path.is_file()
+                && name.len() >= prefix.len() + suffix.len()
+                && name.starts_with(prefix)
+                && name.ends_with(suffix)
+            {
+                matches.push(path);
+            }
+        }
+    }
+    if matches.is_empty() {
+        bail!("the asset {source} matches no files");
+    }
+    matches.sort();
+    Ok(matches)
+}
+
+/// `version` as Debian orders it, with the revision: a semver pre-release sorts before
+/// its release, as `1.0.0~rc.1-1`.
+fn debian_version(version: &str, revision: &str) -> String {
+    let version = version.replacen('-', "~", 1);
+    if revision.is_empty() {
+        version
+    } else {
+        format!("{version}-{revision}")
+    }
+}
+
+fn env<'a>(envs: &[(&str, &'a str)], name: &str) -> Option<&'a str> {
+    envs.iter()
+        .rev()
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| *value)
+}
+
+/// A ustar archive of `files` and the directories above them, sorted by name.
+fn tar(files: &[File], mtime: u64) -> anyhow::Result<Vec<u8>> {
+    let mut entries: BTreeMap<String, Option<&File>> = BTreeMap::new();
+    entries.insert("./".to_string(), None);
+    for file in files {
+        let mut dir = "./".to_string();
+        let mut parts = file.path.split('/').collect::<Vec<_>>();
+        parts.pop();
+        for part in parts {
+            dir.push_str(part);
+            dir.push('/');
+            entries.entry(dir.clone()).or_insert(None);
+        }
+        if entries
+            .insert(format!("./{}", file.path), Some(file))
+            .is_some()
+        {
+            bail!("/{} is in the package twice", file.path);
+        }
+    }
+
+    let mut tar = Vec::new();
+    for (path, file) in entries {
+        match file {
+            None => header(&mut tar, &path, 0o755, 0, b'5', mtime)?,
+            Some(file) => {
+                header(
+                    &mut tar,
+                    &path,
+                    file.mode,
+                    file.contents.len() as u64,
+                    b'0',
+                    mtime,
+                )?;
+                tar.extend_from_slice(&file.contents);
+                tar.resize(tar.len().next_multiple_of(512), 0);
+            }
+        }
+    }
+    // Two empty blocks end the archive.
+    tar.resize(tar.len() + 1024, 0);
+    Ok(tar)
+}
+
+fn header(
+    tar: &mut Vec<u8>,
+    path: &str,
+    mode: u32,
+    size: u64,
+    kind: u8,
+    mtime: u64,
+) -> anyhow::Result<()> {
+    // Names over 100 bytes go partly in the prefix field, split at a `/`.
+    let (prefix, name) = if path.len() <= 100 {
+        ("", path)
+    } else {
+        path.char_indices()
+            .filter(|&(at, c)| c == '/' && at <= 155 && path.len() - at - 1 <= 100)
+            .map(|(at, _)| (&path[..at], &path[at + 1..]))
+            .find(|(_, name)| !name.is_empty())
+            .with_context(|| format!("{path} is too long for a tar archive"))?
+    };
+    let mut block = [0u8; 512];
+    block[..name.len()].copy_from_slice(name.as_bytes());
+    octal(&mut block[100..108], mode.into());
+    octal(&mut block[108..116], 0);
+    octal(&mut block[116..124], 0);
+    octal(&mut block[124..136], size);
+    octal(&mut block[136..148], mtime);
+    block[156] = kind;
+    block[257..263].copy_from_slice(b"ustar\0");
+    block[263..265].copy_from_slice(b"00");
+    block[265..269].copy_from_slice(b"root");
+    block[297..301].copy_from_slice(b"root");
+    block[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+    // The checksum counts its own field as spaces.
+    block[148..156].copy_from_slice(b"        ");
+    let checksum = block.iter().map(|&byte| u64::from(byte)).sum::<u64>();
+    block[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+    tar.extend_from_slice(&block);
+    Ok(())
+}
+
+/// `value` in octal, zero-padded to fill `field` but for its closing NUL.
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{value:0width$o}", width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+}
+
+fn xz(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut writer = XzWriter::new(Vec::new(), XzOptions::with_preset(6))?;
+    writer.write_all(data)?;
+    Ok(writer.finish()?)
+}
+
+/// An `ar` archive of `members`, laid out as dpkg-deb writes it.
+fn ar(members: &[(&str, Vec<u8>)], mtime: u64) -> Vec<u8> {
+    let mut ar = b"!<arch>\n".to_vec();
+    for (name, data) in members {
+        ar.extend_from_slice(
+            format!(
+                "{name:<16}{mtime:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+                0,
+                0,
+                100644,
+                data.len()
+            )
+            .as_bytes(),
+        );
+        ar.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            ar.push(b'\n');
+        }
+    }
+    ar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assembles_a_deb_from_the_metadata() {
+        let dir = std::env::temp_dir().join(format!("debkit-native-{}", std::process::id()));
+        fs::create_dir_all(dir.join("target/release")).unwrap();
+        fs::create_dir_all(dir.join("config")).unwrap();
+        fs::write(dir.join("target/release/tool"), "binary").unwrap();
+        fs::write(dir.join("config/tool.toml"), "port = 1\n").unwrap();
+        fs::write(dir.join("README.md"), "# tool\n").unwrap();
+        let project = Project {
+            name: "tool".to_string(),
+            version: "1.0.0-rc.1".to_string(),
+            deb_name: "tool".to_string(),
+            rpm_name: "tool".to_string(),
+            binaries: vec!["tool".to_string()],
+            repository: Some("https://example.com/tool".to_string()),
+            description: Some("Does things".to_string()),
+            maintainer: Some("Ada <ada@example.com>".to_string()),
+            dir: dir.clone(),
+            workspace_root: dir.clone(),
+            target_dir: dir.join("target"),
+        };
+        let document = "[package]\nname = \"tool\"\n\n[package.metadata.deb]\n\
+                        depends = \"$auto, libssl3\"\nsection = \"net\"\n\
+                        extended-description = \"Longer text.\\n\\nMore.\"\n\
+                        assets = [\"$auto\", [\"config/*.toml\", \"/etc/tool/\", \"640\"]]\n"
+            .parse::<DocumentMut>()
+            .unwrap();
+        let metadata = metadata(&project, &document).unwrap();
+        assert_eq!(metadata.depends, ["libssl3"]);
+        assert_eq!(metadata.assets.len(), 3);
+        let version = debian_version(&project.version, &metadata.revision);
+        assert_eq!(version, "1.0.0~rc.1-1");
+
+        let package = assemble(
+            &project,
+            &metadata,
+            &dir.join("target/release"),
+            "amd64",
+            &version,
+            1700000000,
+        )
+        .unwrap();
+        assert!(package.starts_with(
+            b"!<arch>\ndebian-binary   1700000000  0     0     100644  4         `\n2.0\n"
+        ));
+        let again = assemble(
+            &project,
+            &metadata,
+            &dir.join("target/release"),
+            "amd64",
+            &version,
+            1700000000,
+        )
+        .unwrap();
+        assert_eq!(package, again);
+
+        let data = [File {
+            path: "usr/bin/tool".to_string(),
+            mode: 0o755,
+            contents: b"binary".to_vec(),
+        }];
+        assert_eq!(
+            control_file(&project, &metadata, "amd64", &version, &data).unwrap(),
+            "Package: tool\nVersion: 1.0.0~rc.1-1\nArchitecture: amd64\n\
+             Maintainer: Ada <ada@example.com>\nInstalled-Size: 1\nDepends: libssl3\n\
+             Section: net\nPriority: optional\nHomepage: https://example.com/tool\n\
+             Description: Does things\n Longer text.\n .\n More.\n"
+        );
+        let archive = tar(&data, 1700000000).unwrap();
+        let names = archive
+            .chunks(512)
+            .filter(|block| block[257..262] == *b"ustar")
+            .map(|block| {
+                String::from_utf8_lossy(&block[..100])
+                    .trim_end_matches('\0')
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["./", "./usr/", "./usr/bin/", "./usr/bin/tool"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+//! `package deb --reproducible`: packages that come out bit for bit the same from the
+//! same commit.
+//!
+//! `SOURCE_DATE_EPOCH` is set to the time of the last commit for cargo and cargo-deb.
+//! Each `.deb` is then unpacked and packed again with every file's mtime set to that
+//! time, which `dpkg-deb` also uses for the archive headers while it sorts the entries
+//! by name. To show the result does not depend on the machine's state, the package is
+//! built a second time from scratch in its own target directory, and the two must
+//! match.
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+
+use super::{Project, repack_deb, run_step};
+use crate::output::say;
+use crate::{audit, exec};
+
+/// The target directory of the second build, inside the crate's.
+pub const REBUILD_DIR: &str = "reproducible";
+
+/// The time of the last commit of the workspace, in seconds since the Unix epoch.
+pub fn source_date_epoch(project: &Project) -> anyhow::Result<String> {
+    let output = exec::query(
+        Command::new("git")
+            .args(["log", "-1", "--format=%ct"])
+            .current_dir(&project.workspace_root),
+    )
+    .context("`git` executable was not found in PATH")?;
+    let epoch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || epoch.parse::<u64>().is_err() {
+        bail!(
+            "--reproducible takes its timestamp from the last commit, but {} has none",
+            project.workspace_root.display()
+        );
+    }
+    Ok(epoch)
+}
+
+/// Packs `deb` again with every mtime at `epoch` and the entries sorted.
+pub fn normalize(project: &Project, deb: &Path, epoch: &str) -> anyhow::Result<()> {
+    repack_deb(
+        project,
+        deb,
+        "normalized timestamps",
+        &[("SOURCE_DATE_EPOCH", epoch)],
+        |root| {
+            run_step(
+                "Normalizing timestamps",
+                Command::new("find").arg(root).args([
+                    "-exec",
+                    "touch",
+                    "--no-dereference",
+                    "--date",
+                    &format!("@{epoch}"),
+                    "{}",
+                    "+",
+                ]),
+            )
+        },
+    )
+}
+
+/// Fails unless the packages `first` and `second` are identical.
+pub fn compare(first: &Path, second: &Path) -> anyhow::Result<()> {
+    let hash = |path: &Path| {
+        audit::hash_file(path).with_context(|| format!("failed to read {}", path.display()))
+    };
+    let (expected, actual) = (hash(first)?, hash(second)?);
+    let name = first.file_name().unwrap_or_default().to_string_lossy();
+    if expected != actual {
+        bail!(
+            "{name} is not reproducible: the second build differs\n  {expected}  {}\n  {actual}  {}\n\
+             compare them with: diffoscope {} {}",
+            first.display(),
+            second.display(),
+            first.display(),
+            second.display()
+        );
+    }
+    say!("Reproducible: both builds of {name} are {expected}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn takes_the_commit_time_and_compares_builds() {
+        let dir = std::env::temp_dir().join(format!("debkit-reproducible-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let project = Project {
+            name: "tool".to_string(),
+            version: "1.0.0".to_string(),
+            deb_name: "tool".to_string(),
+            rpm_name: "tool".to_string(),
+            binaries: vec!["tool".to_string()],
+            repository: None,
+            description: None,
+            maintainer: None,
+            dir: dir.clone(),
+            workspace_root: dir.clone(),
+            target_dir: PathBuf::from("/unused"),
+        };
+        let recorder = Arc::new(Recorder::new().reply("git log", 0, "1700000000\n"));
+        assert_eq!(
+            runner::with(recorder, || source_date_epoch(&project)).unwrap(),
+            "1700000000"
+        );
+        let recorder = Arc::new(Recorder::new().reply("git log", 128, ""));
+        assert!(runner::with(recorder, || source_date_epoch(&project)).is_err());
+
+        let (first, second) = (dir.join("first.deb"), dir.join("second.deb"));
+        fs::write(&first, "same").unwrap();
+        fs::write(&second, "same").unwrap();
+        compare(&first, &second).unwrap();
+        fs::write(&second, "different").unwrap();
+        let err = compare(&first, &second).unwrap_err().to_string();
+        assert!(err.starts_with("first.deb is not reproducible"), "{err}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+//! `debkit package rpm`: the same flow as [`super::deb`] with cargo-generate-rpm, for
+//! Fedora and RHEL. The crate needs a `[package.metadata.generate-rpm]` section
+//! listing its assets. cargo-generate-rpm is installed with `cargo install` when it
+//! is missing.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::Context;
+use tracing::debug;
+
+use super::{Artifact, Project, absolute_path, newest_matching, run_command, select};
+use crate::exec;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The `Cargo.toml` of the crate to package, or of the workspace to pick members
+    /// of.
+    pub manifest_path: PathBuf,
+    /// Workspace members to package instead of the crate at `manifest_path`.
+    pub packages: Vec<String>,
+    /// Package every workspace member with a binary.
+    pub all_members: bool,
+    /// The cargo profile to build and package, such as `release`.
+    pub profile: String,
+    pub output_dir: PathBuf,
+    /// The RPM architecture, such as `x86_64`; by default the build machine's.
+    pub arch: Option<String>,
+    pub reinstall: bool,
+}
+
+/// Builds an `.rpm` for each selected crate, in order, and copies them all into the
+/// output directory.
+pub fn run(options: Options) -> anyhow::Result<Vec<Artifact>> {
+    let projects = select(
+        &options.manifest_path,
+        &options.packages,
+        options.all_members,
+    )?;
+
+    ensure_cargo_generate_rpm(options.reinstall, &projects[0].dir)?;
+    exec::create_dir_all(&options.output_dir).with_context(|| {
+        format!(
+            "failed to create output directory {}",
+            options.output_dir.display()
+        )
+    })?;
+
+    projects
+        .iter()
+        .map(|project| build(project, &options))
+        .collect()
+}
+
+fn build(project: &Project, options: &Options) -> anyhow::Result<Artifact> {
+    // Unlike cargo-deb, cargo-generate-rpm only packages what is already built.
+    let profile = ["--profile".to_string(), options.profile.clone()];
+    let mut build_args = vec!["build".to_string()];
+    build_args.extend(profile.clone());
+    let step = format!("Building {} ({})", project.name, options.profile);
+    run_command(&step, "cargo", &build_args, &project.dir)
+        .with_context(|| format!("failed to build {}", project.name))?;
+
+    let mut args = vec!["generate-rpm".to_string()];
+    args.extend(profile);
+    if let Some(arch) = &options.arch {
+        args.push("--arch".to_string());
+        args.push(arch.clone());
+    }
+    let step = format!(
+        "Building the .rpm for {} with cargo-generate-rpm",
+        project.name
+    );
+    run_command(&step, "cargo", &args, &project.dir)
+        .with_context(|| format!("failed to build the .rpm for {}", project.name))?;
+
+    let artifact = |path| Artifact {
+        path,
+        name: project.rpm_name.clone(),
+        version: project.version.clone(),
+    };
+    if exec::is_dry_run() {
+        return Ok(artifact(absolute_path(&options.output_dir)?));
+    }
+
+    let rpm_dir = project.target_dir.join("generate-rpm");
+    let newest = newest_matching(&rpm_dir, &format!("{}-", project.rpm_name), "rpm")?;
+    let filename = newest
+        .file_name()
+        .context("newest .rpm path does not include a filename")?;
+    let output_path = options.output_dir.join(filename);
+    exec::copy(&newest, &output_path).with_context(|| {
+        format!(
+            "failed to copy artifact from {} to {}",
+            newest.display(),
+            output_path.display()
+        )
+    })?;
+
+    Ok(artifact(absolute_path(&output_path)?))
+}
+
+fn ensure_cargo_generate_rpm(reinstall: bool, dir: &Path) -> anyhow::Result<()> {
+    if !reinstall {
+        let output = exec::query(Command::new("cargo").args(["generate-rpm", "--version"]))
+            .context("`cargo` executable was not found in PATH")?;
+        if output.status.success() {
+            return Ok(());
+        }
+        debug!(
+            "cargo generate-rpm --version failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let mut args = vec!["install".to_string(), "--locked".to_string()];
+    if reinstall {
+        args.push("--force".to_string());
+    }
+    args.push("cargo-generate-rpm".to_string());
+    run_command("Installing cargo-generate-rpm", "cargo", &args, dir)
+        .context("failed to install cargo-generate-rpm")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::package::single_crate_metadata;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn installs_the_tool_builds_and_collects_the_rpm() {
+        let dir = std::env::temp_dir().join(format!("debkit-rpm-{}", std::process::id()));
+        let built = dir.join("target/generate-rpm");
+        fs::create_dir_all(&built).unwrap();
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        fs::write(built.join("my_tool-1.2.0-1.x86_64.rpm"), "rpm").unwrap();
+        let metadata = single_crate_metadata(&dir, "my_tool", "1.2.0");
+        let recorder = Arc::new(Recorder::new().reply("cargo metadata", 0, &metadata).reply(
+            "cargo generate-rpm --version",
+            101,
+            "",
+        ));
+        let options = Options {
+            manifest_path: dir.join("Cargo.toml"),
+            packages: Vec::new(),
+            all_members: false,
+            profile: "release".to_string(),
+            output_dir: dir.join("dist"),
+            arch: Some("x86_64".to_string()),
+            reinstall: false,
+        };
+
+        let artifacts = runner::with(recorder.clone(), || run(options)).unwrap();
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(
+            artifacts[0].path,
+            dir.join("dist/my_tool-1.2.0-1.x86_64.rpm")
+        );
+        assert_eq!(artifacts[0].name, "my_tool");
+        assert_eq!(
+            recorder.calls()[1..],
+            [
+                "cargo generate-rpm --version".to_string(),
+                format!(
+                    "cargo install --locked cargo-generate-rpm (in {})",
+                    dir.display()
+                ),
+                format!("cargo build --profile release (in {})", dir.display()),
+                format!(
+                    "cargo generate-rpm --profile release --arch x86_64 (in {})",
+                    dir.display()
+                ),
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,411 @@
+//! Software bills of materials for built packages, in CycloneDX or SPDX JSON.
+//!
+//! The components are the packages in `Cargo.lock` that the crate reaches through its
+//! dependencies. `Cargo.lock` does not tell normal dependencies from build and dev
+//! dependencies, so those are listed too. The SBOM goes next to the `.deb` as
+//! `<name>_<version>_<arch>.cdx.json` (or `.spdx.json`), or with `embed` into the
+//! package itself under `/usr/share/doc/<package>/`. Embedding unpacks the `.deb` with
+//! `dpkg-deb`, adds the file and its md5sum, and packs it again.
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use serde_json::json;
+use toml_edit::{DocumentMut, Item};
+
+use super::{CRATES_IO, Project, repack_deb};
+use crate::{exec, state, system};
+
+/// The SBOM format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Cyclonedx,
+    Spdx,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Cyclonedx => "cdx.json",
+            Format::Spdx => "spdx.json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sbom {
+    pub format: Format,
+    /// Put the SBOM inside the `.deb` instead of next to it.
+    pub embed: bool,
+}
+
+/// A package from `Cargo.lock`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Component {
+    name: String,
+    version: String,
+    /// Where cargo got it; empty for path dependencies.
+    source: String,
+    checksum: String,
+}
+
+impl Component {
+    fn purl(&self) -> String {
+        format!("pkg:cargo/{}@{}", self.name, self.version)
+    }
+}
+
+impl Sbom {
+    pub fn ensure_available(&self) -> anyhow::Result<()> {
+        if self.embed && !system::command_available("dpkg-deb") {
+            bail!("dpkg-deb is required to embed the SBOM; install it with: sudo apt install dpkg");
+        }
+        Ok(())
+    }
+
+    /// Writes the SBOM of `project` for the package `deb`. Returns where it went: a
+    /// file next to `deb`, or `deb` itself when it is embedded.
+    pub fn write(&self, project: &Project, deb: &Path) -> anyhow::Result<PathBuf> {
+        let lock = project.workspace_root.join("Cargo.lock");
+        let raw = fs::read_to_string(&lock).with_context(|| {
+            format!(
+                "failed to read {}; run `cargo generate-lockfile` first",
+                lock.display()
+            )
+        })?;
+        let contents = render(self.format, project, &raw, state::now())?;
+        let name = deb.file_name().unwrap_or_default().to_string_lossy();
+        let stem = name.strip_suffix(".deb").unwrap_or(&name);
+        let file_name = format!("{stem}.{}", self.format.extension());
+        if self.embed {
+            embed(project, deb, &file_name, &contents)?;
+            return Ok(deb.to_path_buf());
+        }
+        let path = deb.with_file_name(file_name);
+        exec::write(&path, contents)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// The SBOM of `project` from the `Cargo.lock` in `lock`, created at `now` (in
+/// seconds since the Unix epoch).
+fn render(format: Format, project: &Project, lock: &str, now: u64) -> anyhow::Result<String> {
+    let components = components(lock, &project.name)?;
+    let root = Component {
+        name: project.name.clone(),
+        version: project.version.clone(),
+        source: String::new(),
+        checksum: String::new(),
+    };
+    let tool = format!("debkit-{}", env!("CARGO_PKG_VERSION"));
+    let document = match format {
+        Format::Cyclonedx => {
+            let component = |component: &Component, kind| {
+                let mut value = json!({
+                    "type": kind,
+                    "bom-ref": component.purl(),
+                    "name": component.name,
+                    "version": component.version,
+                    "purl": component.purl(),
+                });
+                if !component.checksum.is_empty() {
+                    value["hashes"] = json!([{"alg": "SHA-256", "content": component.checksum}]);
+                }
+                value
+            };
+            json!({
+                "bomFormat": "CycloneDX",
+                "specVersion": "1.5",
+                "version": 1,
+                "metadata": {
+                    "timestamp": rfc3339(now),
+                    "tools": {"components": [{"type": "application", "name": tool}]},
+                    "component": component(&root, "application"),
+                },
+                "components": components
+                    .iter()
+                    .map(|dependency| component(dependency, "library"))
+                    .collect::<Vec<_>>(),
+                "dependencies": [{
+                    "ref": root.purl(),
+                    "dependsOn": components.iter().map(Component::purl).collect::<Vec<_>>(),
+                }],
+            })
+        }
+        Format::Spdx => {
+            let package = |index: usize, component: &Component| {
+                let download = if CRATES_IO.contains(&component.source.as_str()) {
+                    format!(
+                        "https://crates.io/api/v1/crates/{}/{}/download",
+                        component.name, component.version
+                    )
+                } else {
+                    "NOASSERTION".to_string()
+                };
+                let mut value = json!({
+                    "SPDXID": format!("SPDXRef-Package-{index}"),
+                    "name": component.name,
+                    "versionInfo": component.version,
+                    "downloadLocation": download,
+                    "filesAnalyzed": false,
+                    "externalRefs": [{
+                        "referenceCategory": "PACKAGE-MANAGER",
+                        "referenceType": "purl",
+                        "referenceLocator": component.purl(),
+                    }],
+                });
+                if !component.checksum.is_empty() {
+                    value["checksums"] =
+                        json!([{"algorithm": "SHA256", "checksumValue": component.checksum}]);
+                }
+                value
+            };
+            let mut relationships = vec![json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": "SPDXRef-Package-0",
+            })];
+            relationships.extend((1..=components.len()).map(|index| {
+                json!({
+                    "spdxElementId": "SPDXRef-Package-0",
+                    "relationshipType": "DEPENDS_ON",
+                    "relatedSpdxElement": format!("SPDXRef-Package-{index}"),
+                })
+            }));
+            json!({
+                "spdxVersion": "SPDX-2.3",
+                "dataLicense": "CC0-1.0",
+                "SPDXID": "SPDXRef-DOCUMENT",
+                "name": format!("{}-{}", root.name, root.version),
+                "documentNamespace": format!(
+                    "https://spdx.org/spdxdocs/{}-{}-{now}",
+                    root.name, root.version
+                ),
+                "creationInfo": {
+                    "created": rfc3339(now),
+                    "creators": [format!("Tool: {tool}")],
+                },
+                "packages": std::iter::once(&root)
+                    .chain(&components)
+                    .enumerate()
+                    .map(|(index, component)| package(index, component))
+                    .collect::<Vec<_>>(),
+                "relationships": relationships,
+            })
+        }
+    };
+    let mut json = serde_json::to_string_pretty(&document)?;
+    json.push('\n');
+    Ok(json)
+}
+
+/// The packages in `lock` that `root` depends on, directly or not, sorted by name.
+fn components(lock: &str, root: &str) -> anyhow::Result<Vec<Component>> {
+    let document = lock.parse::<DocumentMut>().context("invalid Cargo.lock")?;
+    let mut packages = Vec::new();
+    for package in document
+        .get("package")
+        .and_then(Item::as_array_of_tables)
+        .into_iter()
+        .flatten()
+    {
+        let field = |key| {
+            package
+                .get(key)
+                .and_then(Item::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let dependencies = package
+            .get("dependencies")
+            .and_then(Item::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|dependency| dependency.as_str().map(str::to_string))
+            .collect::<Vec<_>>();
+        packages.push((
+            Component {
+                name: field("name"),
+                version: field("version"),
+                source: field("source"),
+                checksum: field("checksum"),
+            },
+            dependencies,
+        ));
+    }
+    let mut by_name = HashMap::<&str, Vec<usize>>::new();
+    for (index, (component, _)) in packages.iter().enumerate() {
+        by_name.entry(&component.name).or_default().push(index);
+    }
+    // A dependency is `name`, or `name version` when several versions are locked.
+    let resolve = |dependency: &str| {
+        let mut parts = dependency.split_whitespace();
+        let name = parts.next()?;
+        let version = parts.next();
+        by_name
+            .get(name)?
+            .iter()
+            .copied()
+            .find(|&index| version.is_none_or(|version| packages[index].0.version == version))
+    };
+
+    let start = packages
+        .iter()
+        .position(|(component, _)| component.name == root && component.source.is_empty())
+        .with_context(|| format!("{root} is not in Cargo.lock"))?;
+    let mut seen = BTreeSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(index) = queue.pop_front() {
+        for dependency in &packages[index].1 {
+            if let Some(next) = resolve(dependency)
+                && seen.insert(next)
+            {
+                queue.push_back(next);
+            }
+        }
+    }
+    let mut components = seen
+        .into_iter()
+        .filter(|&index| index != start)
+        .map(|index| packages[index].0.clone())
+        .collect::<Vec<_>>();
+    components.sort();
+    Ok(components)
+}
+
+/// Adds `contents` to the `.deb` at `deb` as `/usr/share/doc/<package>/<file_name>`.
+fn embed(project: &Project, deb: &Path, file_name: &str, contents: &str) -> anyhow::Result<()> {
+    repack_deb(project, deb, "its SBOM", &[], |root| {
+        let relative = format!("usr/share/doc/{}/{file_name}", project.deb_name);
+        let path = root.join(&relative);
+        if let Some(dir) = path.parent() {
+            exec::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        exec::write(&path, contents)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        let output = exec::query(Command::new("md5sum").arg(&relative).current_dir(root))
+            .context("`md5sum` executable was not found in PATH")?;
+        if !output.status.success() {
+            bail!("md5sum failed on {}", path.display());
+        }
+        let md5sums = root.join("DEBIAN").join("md5sums");
+        exec::append(&md5sums, &String::from_utf8_lossy(&output.stdout))
+            .with_context(|| format!("failed to update {}", md5sums.display()))
+    })
+}
+
+/// `seconds` since the Unix epoch as an RFC 3339 UTC timestamp.
+fn rfc3339(seconds: u64) -> String {
+    // Howard Hinnant's `civil_from_days`.
+    let days = (seconds / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    let time = seconds % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3_600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_what_the_crate_depends_on() {
+        let lock = r#"
+version = 4
+
+[[package]]
+name = "tool"
+version = "1.0.0"
+dependencies = ["anyhow", "syn 2.0.0"]
+
+[[package]]
+name = "other-member"
+version = "0.1.0"
+dependencies = ["syn 1.0.0"]
+
+[[package]]
+name = "anyhow"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "aaaa"
+
+[[package]]
+name = "syn"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "bbbb"
+
+[[package]]
+name = "syn"
+version = "2.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "cccc"
+dependencies = ["unicode-ident"]
+
+[[package]]
+name = "unicode-ident"
+version = "1.0.0"
+source = "sparse+https://index.crates.io/"
+checksum = "dddd"
+"#;
+        let project = Project {
+            name: "tool".to_string(),
+            version: "1.0.0".to_string(),
+            deb_name: "tool".to_string(),
+            rpm_name: "tool".to_string(),
+            binaries: vec!["tool".to_string()],
+            repository: None,
+            description: None,
+            maintainer: None,
+            dir: PathBuf::from("/src/tool"),
+            workspace_root: PathBuf::from("/src"),
+            target_dir: PathBuf::from("/src/target"),
+        };
+
+        let bom: serde_json::Value = serde_json::from_str(
+            &render(Format::Cyclonedx, &project, lock, 1_790_000_000).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(bom["metadata"]["timestamp"], "2026-09-21T14:13:20Z");
+        assert_eq!(bom["metadata"]["component"]["purl"], "pkg:cargo/tool@1.0.0");
+        assert_eq!(
+            bom["dependencies"][0]["dependsOn"],
+            json!([
+                "pkg:cargo/anyhow@1.0.0",
+                "pkg:cargo/syn@2.0.0",
+                "pkg:cargo/unicode-ident@1.0.0"
+            ])
+        );
+        assert_eq!(bom["components"][1]["hashes"][0]["content"], "cccc");
+
+        let spdx: serde_json::Value =
+            serde_json::from_str(&render(Format::Spdx, &project, lock, 0).unwrap()).unwrap();
+        assert_eq!(spdx["creationInfo"]["created"], "1970-01-01T00:00:00Z");
+        assert_eq!(spdx["packages"].as_array().unwrap().len(), 4);
+        assert_eq!(
+            spdx["packages"][3]["downloadLocation"],
+            "https://crates.io/api/v1/crates/unicode-ident/1.0.0/download"
+        );
+    }
+}
@@ -0,0 +1,292 @@
+//! Maintainer scripts rendered from `[package.metadata.debkit.scripts]`.
+//!
+//! The usual chores of a service package are declared instead of written as shell:
+//!
+//! ```toml
+//! [package.metadata.debkit.scripts]
+//! user = "myservice"                                    # system user and group
+//! directories = ["/var/lib/myservice"]                  # owned by the user
+//! migrate-config = [{ from = "/etc/myservice.conf", to = "/etc/myservice/config.toml" }]
+//! restart = ["myservice.service"]                       # after an upgrade
+//! postinst = "myservice --check-config"                 # anything else, as shell
+//! ```
+//!
+//! `postinst` and, when they have something to do, `prerm` and `postrm` are written
+//! into `target/maintainer-scripts/`, checked with `sh -n`, and handed to cargo-deb
+//! through `package.metadata.deb.maintainer-scripts`. cargo-deb fills in
+//! `#DEBHELPER#` with what its `systemd-units` need.
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+use toml_edit::value;
+
+use super::{Project, update_deb_metadata};
+use crate::exec;
+
+/// Where the scripts go, relative to the crate.
+const DIR: &str = "target/maintainer-scripts";
+
+#[derive(Debug, Default, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    package: ManifestPackage,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ManifestPackage {
+    #[serde(default)]
+    metadata: ManifestMetadata,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ManifestMetadata {
+    #[serde(default)]
+    debkit: ManifestDebkit,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ManifestDebkit {
+    scripts: Option<Scripts>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Scripts {
+    /// The system user, with a group of the same name, created on configure.
+    pub user: Option<String>,
+    /// Directories created on configure, owned by `user` when there is one.
+    pub directories: Vec<String>,
+    /// Config files moved to a new path on upgrade, while only the old one exists.
+    pub migrate_config: Vec<Migration>,
+    /// systemd units restarted once the package is configured.
+    pub restart: Vec<String>,
+    /// Shell run at the end of `postinst configure`.
+    pub postinst: String,
+    /// Shell run at the start of `prerm remove`.
+    pub prerm: String,
+    /// Shell run at the end of `postrm purge`.
+    pub postrm: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Migration {
+    pub from: String,
+    pub to: String,
+}
+
+/// Renders, checks and wires up the scripts of `project`, if it declares any.
+pub fn prepare(project: &Project) -> anyhow::Result<()> {
+    let manifest = project.dir.join("Cargo.toml");
+    let raw = fs::read_to_string(&manifest)
+        .with_context(|| format!("failed to read {}", manifest.display()))?;
+    let parsed: Manifest = toml_edit::de::from_str(&raw).map_err(|err| {
+        anyhow::anyhow!(
+            "invalid [package.metadata.debkit.scripts] in {}: {}",
+            manifest.display(),
+            err.to_string().trim_end()
+        )
+    })?;
+    let Some(scripts) = parsed.package.metadata.debkit.scripts else {
+        return Ok(());
+    };
+    validate(&scripts)?;
+
+    let dir = project.dir.join(DIR);
+    if dir.exists() {
+        exec::remove_dir_all(&dir).with_context(|| format!("failed to clear {}", dir.display()))?;
+    }
+    exec::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    for (name, script) in render(&scripts) {
+        let path = dir.join(name);
+        check_syntax(&path, &script)?;
+        exec::write(&path, script)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        exec::set_permissions(&path, fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("failed to make {} executable", path.display()))?;
+    }
+
+    let mut conflict = None;
+    update_deb_metadata(project, |deb| {
+        match deb.get("maintainer-scripts").and_then(|item| item.as_str()) {
+            Some(DIR) => false,
+            Some(other) => {
+                conflict = Some(other.to_string());
+                false
+            }
+            None => {
+                deb.insert("maintainer-scripts", value(DIR));
+                true
+            }
+        }
+    })?;
+    if let Some(other) = conflict {
+        bail!(
+            "`package.metadata.deb.maintainer-scripts` already points at {other}; remove it to use [package.metadata.debkit.scripts]"
+        );
+    }
+    Ok(())
+}
+
+fn validate(scripts: &Scripts) -> anyhow::Result<()> {
+    if let Some(user) = &scripts.user {
+        let valid = user.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+            && user
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+        if !valid {
+            bail!("`{user}` is not a valid system user name");
+        }
+    }
+    let paths = scripts.directories.iter().chain(
+        scripts
+            .migrate_config
+            .iter()
+            .flat_map(|migration| [&migration.from, &migration.to]),
+    );
+    for path in paths {
+        if !path.starts_with('/') {
+            bail!("`{path}` in [package.metadata.debkit.scripts] must be an absolute path");
+        }
+    }
+    Ok(())
+}
+
+/// The scripts to write, by name. `postinst` is always there so `#DEBHELPER#` has a
+/// home.
+fn render(scripts: &Scripts) -> Vec<(&'static str, String)> {
+    let mut configure = String::new();
+    if let Some(user) = &scripts.user {
+        let user = quote(user);
+        configure.push_str(&format!(
+            "    if ! getent passwd {user} >/dev/null; then\n        \
+             adduser --system --group --no-create-home --home /nonexistent {user}\n    fi\n"
+        ));
+    }
+    for dir in &scripts.directories {
+        let owner = scripts.user.as_deref().map_or(String::new(), |user| {
+            format!("-o {user} -g {user} ", user = quote(user))
+        });
+        configure.push_str(&format!("    install -d {owner}-m 0755 {}\n", quote(dir)));
+    }
+    for migration in &scripts.migrate_config {
+        let (from, to) = (quote(&migration.from), quote(&migration.to));
+        configure.push_str(&format!(
+            "    if [ -e {from} ] && [ ! -e {to} ]; then\n        \
+             mkdir -p \"$(dirname {to})\"\n        mv {from} {to}\n    fi\n"
+        ));
+    }
+    configure.push_str(&indent(&scripts.postinst));
+    if !scripts.restart.is_empty() {
+        let units = scripts
+            .restart
+            .iter()
+            .map(|unit| quote(unit))
+            .collect::<Vec<_>>()
+            .join(" ");
+        configure.push_str(&format!(
+            "    if [ -d /run/systemd/system ]; then\n        \
+             systemctl try-restart {units} || true\n    fi\n"
+        ));
+    }
+
+    let mut rendered = vec![("postinst", script(&[("configure", &configure)]))];
+    if !scripts.prerm.trim().is_empty() {
+        rendered.push(("prerm", script(&[("remove", &indent(&scripts.prerm))])));
+    }
+    if !scripts.postrm.trim().is_empty() {
+        rendered.push(("postrm", script(&[("purge", &indent(&scripts.postrm))])));
+    }
+    rendered
+}
+
+/// A maintainer script running `body` for each action.
+fn script(cases: &[(&str, &str)]) -> String {
+    let mut script = "#!/bin/sh\nset -e\n\ncase \"$1\" in\n".to_string();
+    for (action, body) in cases {
+        if !body.is_empty() {
+            script.push_str(&format!("{action})\n{body}    ;;\n"));
+        }
+    }
+    script.push_str("esac\n\n#DEBHELPER#\n\nexit 0\n");
+    script
+}
+
+fn indent(snippet: &str) -> String {
+    snippet
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| format!("    {line}\n"))
+        .collect()
+}
+
+/// `value` as a single-quoted shell word.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Fails unless `sh -n` accepts `script`, which is about to go to `path`.
+fn check_syntax(path: &Path, script: &str) -> anyhow::Result<()> {
+    let output = exec::query_with_input(Command::new("sh").arg("-n"), script.as_bytes())
+        .context("`sh` executable was not found in PATH")?;
+    if !output.status.success() {
+        bail!(
+            "{} is not valid shell:\n{}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_declared_chores_as_a_postinst() {
+        let scripts = Scripts {
+            user: Some("myservice".to_string()),
+            directories: vec!["/var/lib/myservice".to_string()],
+            migrate_config: vec![Migration {
+                from: "/etc/myservice.conf".to_string(),
+                to: "/etc/myservice/config.toml".to_string(),
+            }],
+            restart: vec!["myservice.service".to_string()],
+            postinst: "myservice --check-config\n".to_string(),
+            ..Scripts::default()
+        };
+        let rendered = render(&scripts);
+        assert_eq!(rendered.len(), 1);
+        assert_eq!(
+            rendered[0].1,
+            "#!/bin/sh\nset -e\n\ncase \"$1\" in\nconfigure)\n\
+             \x20   if ! getent passwd 'myservice' >/dev/null; then\n\
+             \x20       adduser --system --group --no-create-home --home /nonexistent 'myservice'\n\
+             \x20   fi\n\
+             \x20   install -d -o 'myservice' -g 'myservice' -m 0755 '/var/lib/myservice'\n\
+             \x20   if [ -e '/etc/myservice.conf' ] && [ ! -e '/etc/myservice/config.toml' ]; then\n\
+             \x20       mkdir -p \"$(dirname '/etc/myservice/config.toml')\"\n\
+             \x20       mv '/etc/myservice.conf' '/etc/myservice/config.toml'\n\
+             \x20   fi\n\
+             \x20   myservice --check-config\n\
+             \x20   if [ -d /run/systemd/system ]; then\n\
+             \x20       systemctl try-restart 'myservice.service' || true\n\
+             \x20   fi\n\
+             \x20   ;;\nesac\n\n#DEBHELPER#\n\nexit 0\n"
+        );
+        check_syntax(Path::new("postinst"), &rendered[0].1).unwrap();
+        assert!(check_syntax(Path::new("prerm"), "if then\n").is_err());
+
+        let invalid = Scripts {
+            directories: vec!["var/lib/myservice".to_string()],
+            ..Scripts::default()
+        };
+        assert!(validate(&invalid).is_err());
+    }
+}
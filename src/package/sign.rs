@@ -0,0 +1,144 @@
+//! Signing built packages with OpenPGP.
+//!
+//! [`Signer::DpkgSig`] embeds the signature in the `.deb` itself. [`Signer::Gpg`]
+//! and [`Signer::Sq`] (Sequoia) write a detached, ASCII-armoured `<file>.asc` next
+//! to it instead.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+
+use super::run_command;
+use crate::system;
+
+/// The tool that signs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Signer {
+    #[value(name = "dpkg-sig")]
+    DpkgSig,
+    Gpg,
+    Sq,
+}
+
+impl Signer {
+    fn program(self) -> &'static str {
+        match self {
+            Signer::DpkgSig => "dpkg-sig",
+            Signer::Gpg => "gpg",
+            Signer::Sq => "sq",
+        }
+    }
+
+    fn package(self) -> &'static str {
+        match self {
+            Signer::DpkgSig => "dpkg-sig",
+            Signer::Gpg => "gnupg",
+            Signer::Sq => "sq",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signing {
+    pub signer: Signer,
+    /// The key ID or fingerprint to sign with; by default the signer's default key.
+    pub key: Option<String>,
+}
+
+impl Signing {
+    /// Checks that the signer is installed and has what it needs.
+    pub fn ensure_available(&self) -> anyhow::Result<()> {
+        let program = self.signer.program();
+        if !system::command_available(program) {
+            bail!(
+                "`{program}` is required to sign with it; install it with: sudo apt install {}",
+                self.signer.package()
+            );
+        }
+        if self.signer == Signer::Sq && self.key.is_none() {
+            bail!("sq has no default key; pass --key or set `packaging.signing_key`");
+        }
+        Ok(())
+    }
+
+    /// Signs `file` and returns where the signature is: `file` itself or a detached
+    /// `.asc` next to it.
+    pub fn sign(&self, file: &Path) -> anyhow::Result<PathBuf> {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        let path = file.display().to_string();
+        let asc = PathBuf::from(format!("{path}.asc"));
+        let mut args = Vec::new();
+        match self.signer {
+            Signer::DpkgSig => {
+                args.extend(["--sign", "builder"].map(str::to_string));
+                if let Some(key) = &self.key {
+                    args.extend(["-k".to_string(), key.clone()]);
+                }
+                args.push(path);
+            }
+            Signer::Gpg => {
+                args.extend(["--batch", "--yes", "--armor", "--detach-sign"].map(str::to_string));
+                if let Some(key) = &self.key {
+                    args.extend(["--local-user".to_string(), key.clone()]);
+                }
+                args.extend(["--output".to_string(), asc.display().to_string(), path]);
+            }
+            Signer::Sq => {
+                args.extend(["sign", "--signature-file"].map(str::to_string));
+                args.push(asc.display().to_string());
+                if let Some(key) = &self.key {
+                    args.extend(["--signer".to_string(), key.clone()]);
+                }
+                args.push(path);
+            }
+        }
+        let dir = file.parent().unwrap_or(Path::new("."));
+        run_command(
+            &format!("Signing {name}"),
+            self.signer.program(),
+            &args,
+            dir,
+        )
+        .with_context(|| format!("failed to sign {}", file.display()))?;
+        Ok(match self.signer {
+            Signer::DpkgSig => file.to_path_buf(),
+            Signer::Gpg | Signer::Sq => asc,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn signs_in_place_or_detached() {
+        let deb = Path::new("/dist/tool_1.0.0-1_amd64.deb");
+        let recorder = Arc::new(Recorder::new());
+        let sign = |signer, key: Option<&str>| {
+            let signing = Signing {
+                signer,
+                key: key.map(str::to_string),
+            };
+            runner::with(recorder.clone(), || signing.sign(deb)).unwrap()
+        };
+
+        assert_eq!(sign(Signer::DpkgSig, Some("ABCD1234")), deb);
+        assert_eq!(
+            sign(Signer::Gpg, None),
+            Path::new("/dist/tool_1.0.0-1_amd64.deb.asc")
+        );
+        sign(Signer::Sq, Some("ABCD1234"));
+        assert_eq!(
+            recorder.calls(),
+            [
+                "dpkg-sig --sign builder -k ABCD1234 /dist/tool_1.0.0-1_amd64.deb (in /dist)",
+                "gpg --batch --yes --armor --detach-sign --output /dist/tool_1.0.0-1_amd64.deb.asc /dist/tool_1.0.0-1_amd64.deb (in /dist)",
+                "sq sign --signature-file /dist/tool_1.0.0-1_amd64.deb.asc --signer ABCD1234 /dist/tool_1.0.0-1_amd64.deb (in /dist)",
+            ]
+        );
+    }
+}
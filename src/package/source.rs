@@ -0,0 +1,311 @@
+//! `debkit package source`: a Debian source package (`.dsc`) for a PPA or buildd.
+//!
+//! The committed tree at `HEAD` of the workspace is exported with `git archive` into
+//! `target/debian-source/` and becomes the `.orig.tar.gz`. Each file of a minimal
+//! `debian/` directory is added unless the tree already has one: `control`, `rules`,
+//! `source/format` and a `changelog` written from git history. `dpkg-buildpackage -S`
+//! then builds the source package, and its files are copied into the output directory.
+//!
+//! With [`Rules::Vendored`] the dependencies are vendored into the tarball with
+//! `cargo vendor`, and `debian/rules` builds offline with cargo, as buildds have no
+//! network. With [`Rules::DhCargo`] they are left to dh-cargo, which needs each one
+//! packaged in the distribution as a `librust-*-dev` build dependency.
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+
+use super::{Artifact, Project, absolute_path, changelog, run_step, select};
+use crate::{exec, network, system};
+
+pub const DEFAULT_DISTRIBUTION: &str = changelog::DISTRIBUTION;
+
+/// How `debian/rules` builds the crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Rules {
+    /// cargo, offline, from dependencies vendored into the source.
+    Vendored,
+    /// dh-cargo, from dependencies packaged in the distribution.
+    #[value(name = "dh-cargo")]
+    DhCargo,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The `Cargo.toml` of the crate, or of the workspace to pick a member of.
+    pub manifest_path: PathBuf,
+    /// The workspace member to package instead of the crate at `manifest_path`.
+    pub packages: Vec<String>,
+    pub all_members: bool,
+    pub output_dir: PathBuf,
+    pub rules: Rules,
+    /// The release the upload targets, such as `unstable` or an Ubuntu series.
+    pub distribution: String,
+    /// Sign the `.dsc` and `.changes`.
+    pub sign: bool,
+    /// The key to sign with; by default gpg's default key.
+    pub key: Option<String>,
+}
+
+/// Builds the source package and returns its files in the output directory.
+pub fn run(options: Options) -> anyhow::Result<Vec<Artifact>> {
+    let projects = select(
+        &options.manifest_path,
+        &options.packages,
+        options.all_members,
+    )?;
+    let [project] = projects.as_slice() else {
+        bail!("a source package holds a single crate; pick one with --package");
+    };
+    for tool in ["git", "dpkg-buildpackage", "dh"] {
+        if !system::command_available(tool) {
+            bail!(
+                "`{tool}` is required to build a source package; install it with: sudo apt install git dpkg-dev debhelper"
+            );
+        }
+    }
+
+    let name = &project.deb_name;
+    let work = project.target_dir.join("debian-source");
+    let tree = work.join(format!("{name}-{}", project.version));
+    if work.exists() {
+        exec::remove_dir_all(&work)
+            .with_context(|| format!("failed to remove {}", work.display()))?;
+    }
+    exec::create_dir_all(&work).with_context(|| format!("failed to create {}", work.display()))?;
+    // The changelog comes from the real repository, before anything is exported.
+    let changelog = changelog::generate(project, &options.distribution)?;
+
+    let export = work.join(format!("{name}.tar"));
+    run_step(
+        "Exporting the committed tree",
+        Command::new("git")
+            .arg("archive")
+            .arg(format!("--prefix={name}-{}/", project.version))
+            .arg("--output")
+            .arg(&export)
+            .arg("HEAD")
+            .current_dir(&project.workspace_root),
+    )?;
+    run_step(
+        "Unpacking the tree",
+        Command::new("tar")
+            .arg("-xf")
+            .arg(&export)
+            .current_dir(&work),
+    )?;
+    exec::remove_file(&export).with_context(|| format!("failed to remove {}", export.display()))?;
+    if options.rules == Rules::Vendored {
+        run_step(
+            "Vendoring dependencies",
+            network::apply(&mut Command::new("cargo"))
+                .args(["vendor", "--locked", "vendor"])
+                .current_dir(&tree),
+        )?;
+    }
+    let orig = format!("{name}_{}.orig.tar.gz", project.version);
+    run_step(
+        &format!("Creating {orig}"),
+        Command::new("tar")
+            .args(["-czf", &orig])
+            .arg(tree.file_name().unwrap_or_default())
+            .current_dir(&work),
+    )?;
+
+    write_debian_dir(project, &tree, options.rules, &changelog)?;
+    let mut command = Command::new("dpkg-buildpackage");
+    // Build dependencies only have to be installed where the package is built.
+    command.args(["-S", "-d"]).current_dir(&tree);
+    if !options.sign {
+        command.args(["-us", "-uc"]);
+    } else if let Some(key) = &options.key {
+        command.arg(format!("--sign-key={key}"));
+    }
+    run_step(
+        &format!("Building the source package for {name}"),
+        &mut command,
+    )?;
+
+    exec::create_dir_all(&options.output_dir).with_context(|| {
+        format!(
+            "failed to create output directory {}",
+            options.output_dir.display()
+        )
+    })?;
+    if exec::is_dry_run() {
+        return Ok(vec![Artifact {
+            path: absolute_path(&options.output_dir)?,
+            name: name.clone(),
+            version: project.version.clone(),
+        }]);
+    }
+    let mut artifacts = Vec::new();
+    let mut files = fs::read_dir(&work)
+        .with_context(|| format!("failed to read {}", work.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    files.sort();
+    for file in files {
+        let file_name = file.file_name().unwrap_or_default();
+        if !file.is_file() || !file_name.to_string_lossy().starts_with(&format!("{name}_")) {
+            continue;
+        }
+        let output_path = options.output_dir.join(file_name);
+        exec::copy(&file, &output_path).with_context(|| {
+            format!(
+                "failed to copy {} to {}",
+                file.display(),
+                output_path.display()
+            )
+        })?;
+        artifacts.push(Artifact {
+            path: absolute_path(&output_path)?,
+            name: name.clone(),
+            version: project.version.clone(),
+        });
+    }
+    Ok(artifacts)
+}
+
+/// Adds the files of `debian/` that the tree at `tree` does not have yet.
+fn write_debian_dir(
+    project: &Project,
+    tree: &Path,
+    rules: Rules,
+    changelog: &str,
+) -> anyhow::Result<()> {
+    let maintainer = project.maintainer.as_deref().with_context(|| {
+        format!(
+            "{} names no maintainer; set `package.authors` or `package.metadata.deb.maintainer`",
+            project.name
+        )
+    })?;
+    let files = [
+        ("debian/control", control(project, maintainer, rules)),
+        ("debian/rules", self::rules(project, rules)),
+        ("debian/source/format", "3.0 (quilt)\n".to_string()),
+        ("debian/changelog", changelog.to_string()),
+    ];
+    for (relative, contents) in files {
+        let path = tree.join(relative);
+        if path.exists() {
+            continue;
+        }
+        if let Some(dir) = path.parent() {
+            exec::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        exec::write(&path, contents)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    let rules = tree.join("debian/rules");
+    exec::set_permissions(&rules, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("failed to make {} executable", rules.display()))
+}
+
+fn control(project: &Project, maintainer: &str, rules: Rules) -> String {
+    let build_depends = match rules {
+        Rules::Vendored => "debhelper-compat (= 13), cargo, rustc",
+        Rules::DhCargo => "debhelper-compat (= 13), dh-cargo, cargo",
+    };
+    let description = project
+        .description
+        .as_deref()
+        .unwrap_or(&project.name)
+        .lines()
+        .next()
+        .unwrap_or_default();
+    let mut control = format!(
+        "Source: {name}\n\
+         Section: utils\n\
+         Priority: optional\n\
+         Maintainer: {maintainer}\n\
+         Build-Depends: {build_depends}\n\
+         Standards-Version: 4.7.0\n\
+         Rules-Requires-Root: no\n",
+        name = project.deb_name,
+    );
+    if let Some(repository) = &project.repository {
+        control.push_str(&format!("Homepage: {repository}\n"));
+    }
+    control.push_str(&format!(
+        "\nPackage: {}\n\
+         Architecture: any\n\
+         Depends: ${{shlibs:Depends}}, ${{misc:Depends}}\n\
+         Description: {description}\n",
+        project.deb_name
+    ));
+    control
+}
+
+fn rules(project: &Project, rules: Rules) -> String {
+    match rules {
+        Rules::DhCargo => "#!/usr/bin/make -f\n\n%:\n\tdh $@ --buildsystem cargo\n".to_string(),
+        Rules::Vendored => {
+            let install = project
+                .binaries
+                .iter()
+                .map(|binary| {
+                    format!(
+                        "\tinstall -D -m 0755 target/release/{binary} debian/{}/usr/bin/{binary}\n",
+                        project.deb_name
+                    )
+                })
+                .collect::<String>();
+            format!(
+                "#!/usr/bin/make -f\n\n\
+                 export CARGO_HOME = $(CURDIR)/debian/cargo-home\n\
+                 CARGO_FLAGS = --release --offline --frozen -p {name} \\\n\
+                 \t--config 'source.crates-io.replace-with=\"vendored-sources\"' \\\n\
+                 \t--config 'source.vendored-sources.directory=\"vendor\"'\n\n\
+                 %:\n\tdh $@\n\n\
+                 override_dh_auto_build:\n\tcargo build $(CARGO_FLAGS)\n\n\
+                 override_dh_auto_test:\n\n\
+                 override_dh_auto_install:\n{install}\n\
+                 override_dh_auto_clean:\n\trm -rf target debian/cargo-home\n",
+                name = project.name,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_control_and_rules_for_a_vendored_build() {
+        let project = Project {
+            name: "my_tool".to_string(),
+            version: "1.2.0".to_string(),
+            deb_name: "my-tool".to_string(),
+            rpm_name: "my_tool".to_string(),
+            binaries: vec!["my-tool".to_string()],
+            repository: Some("https://github.com/acme/my-tool".to_string()),
+            description: Some("Does things".to_string()),
+            maintainer: Some("Ada Lovelace <ada@example.com>".to_string()),
+            dir: PathBuf::from("/src"),
+            workspace_root: PathBuf::from("/src"),
+            target_dir: PathBuf::from("/src/target"),
+        };
+        assert_eq!(
+            control(&project, "Ada Lovelace <ada@example.com>", Rules::Vendored),
+            "Source: my-tool\nSection: utils\nPriority: optional\nMaintainer: Ada Lovelace <ada@example.com>\n\
+             Build-Depends: debhelper-compat (= 13), cargo, rustc\nStandards-Version: 4.7.0\n\
+             Rules-Requires-Root: no\nHomepage: https://github.com/acme/my-tool\n\n\
+             Package: my-tool\nArchitecture: any\nDepends: ${shlibs:Depends}, ${misc:Depends}\n\
+             Description: Does things\n"
+        );
+        let rules = rules(&project, Rules::Vendored);
+        assert!(rules.contains("\tcargo build $(CARGO_FLAGS)\n"), "{rules}");
+        assert!(
+            rules.contains(
+                "\tinstall -D -m 0755 target/release/my-tool debian/my-tool/usr/bin/my-tool\n"
+            ),
+            "{rules}"
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! `package deb --verify`: checks on each `.deb` once it is built.
+//!
+//! - `dpkg-deb --info` can read the control archive.
+//! - `dpkg-deb --contents` lists the crate's binaries.
+//! - `apt-get --simulate install` resolves the declared `Depends` against the current
+//!   apt cache.
+//! - The first binary runs `--version` in a throwaway container of `image` with the
+//!   package installed.
+//!
+//! The last two need a package for the build machine's architecture, and the container
+//! needs podman or docker; otherwise they are skipped. A summary is printed, and the
+//! build fails if any check did.
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+
+use super::Project;
+use crate::exec;
+use crate::output::say;
+
+pub const DEFAULT_IMAGE: &str = crate::verify::DEFAULT_IMAGE;
+/// Where the package's directory is mounted in the container.
+const MOUNT: &str = "/debkit-package";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verify {
+    /// The image the binary runs in.
+    pub image: String,
+    /// `docker` or `podman`; by default whichever is installed, podman first.
+    pub engine: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    Passed(String),
+    Failed(String),
+    Skipped(String),
+}
+
+impl Verify {
+    /// Checks the package `deb` built from `project`. `host` says whether it is for the
+    /// build machine's architecture.
+    pub fn check(&self, project: &Project, deb: &Path, host: bool) -> anyhow::Result<()> {
+        let name = deb.file_name().unwrap_or_default().to_string_lossy();
+        let contents = dpkg_deb(deb, "--contents");
+        let binary = contents.as_ref().ok().and_then(|listing| {
+            let binary = project.binaries.first()?;
+            listing
+                .lines()
+                .filter_map(|line| line.split_whitespace().last())
+                .map(|path| path.trim_start_matches('.'))
+                .find(|path| path.ends_with(&format!("/bin/{binary}")))
+                .map(str::to_string)
+        });
+        let checks = [
+            ("info", info(deb)),
+            ("contents", self::contents(project, contents)),
+            (
+                "depends",
+                if host {
+                    depends(deb)
+                } else {
+                    Outcome::Skipped("not for this machine's architecture".to_string())
+                },
+            ),
+            (
+                "runs",
+                match (host, &binary) {
+                    (false, _) => {
+                        Outcome::Skipped("not for this machine's architecture".to_string())
+                    }
+                    (_, None) => Outcome::Skipped("the package has no binary".to_string()),
+                    (true, Some(binary)) => self.runs(deb, binary),
+                },
+            ),
+        ];
+
+        say!("verify: {name}");
+        for (check, outcome) in &checks {
+            match outcome {
+                Outcome::Passed(detail) => say!("  ok    {check}: {detail}"),
+                Outcome::Failed(detail) => say!("  FAIL  {check}: {detail}"),
+                Outcome::Skipped(reason) => say!("  skip  {check}: {reason}"),
+            }
+        }
+        let failed = checks
+            .iter()
+            .filter(|(_, outcome)| matches!(outcome, Outcome::Failed(_)))
+            .map(|(check, _)| *check)
+            .collect::<Vec<_>>();
+        if !failed.is_empty() {
+            bail!("{name} failed verification: {}", failed.join(", "));
+        }
+        Ok(())
+    }
+
+    fn runs(&self, deb: &Path, binary: &str) -> Outcome {
+        let engine = match &self.engine {
+            Some(engine) => engine.clone(),
+            None => match crate::verify::find_engine() {
+                Ok(engine) => engine,
+                Err(_) => {
+                    return Outcome::Skipped("neither podman nor docker is installed".to_string());
+                }
+            },
+        };
+        let Some(dir) = deb.parent() else {
+            return Outcome::Failed(format!("{} has no directory", deb.display()));
+        };
+        let file = deb.file_name().unwrap_or_default().to_string_lossy();
+        let script = format!(
+            "apt-get update -qq && apt-get install -y -qq {MOUNT}/{file} >/dev/null && {binary} --version"
+        );
+        let output = exec::output_step(
+            Command::new(&engine)
+                .args([
+                    "run",
+                    "--rm",
+                    "--env",
+                    "DEBIAN_FRONTEND=noninteractive",
+                    "--volume",
+                ])
+                .arg(format!("{}:{MOUNT}:ro", dir.display()))
+                .args([self.image.as_str(), "sh", "-c", &script]),
+            &format!("Running {binary} --version in {}", self.image),
+        );
+        match output {
+            Ok(output) if output.status.success() => {
+                let version = String::from_utf8_lossy(&output.stdout);
+                Outcome::Passed(
+                    version
+                        .lines()
+                        .last()
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                )
+            }
+            Ok(output) => Outcome::Failed(last_line(&output.stderr, &output.stdout)),
+            Err(err) => Outcome::Failed(format!("failed to start `{engine}`: {err}")),
+        }
+    }
+}
+
+fn info(deb: &Path) -> Outcome {
+    match dpkg_deb(deb, "--info") {
+        Ok(_) => match dpkg_deb(deb, "--field").map(|fields| fields_line(&fields)) {
+            Ok(fields) => Outcome::Passed(fields),
+            Err(err) => Outcome::Failed(format!("{err:#}")),
+        },
+        Err(err) => Outcome::Failed(format!("{err:#}")),
+    }
+}
+
+fn contents(project: &Project, listing: anyhow::Result<String>) -> Outcome {
+    let listing = match listing {
+        Ok(listing) => listing,
+        Err(err) => return Outcome::Failed(format!("{err:#}")),
+    };
+    let files = listing
+        .lines()
+        .filter(|line| !line.starts_with('d'))
+        .count();
+    let missing = project
+        .binaries
+        .iter()
+        .filter(|binary| {
+            !listing
+                .lines()
+                .any(|line| line.ends_with(&format!("/bin/{binary}")))
+        })
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        Outcome::Passed(format!(
+            "{files} files, including {}",
+            project.binaries.join(", ")
+        ))
+    } else {
+        Outcome::Failed(format!("{} not in the package", missing.join(", ")))
+    }
+}
+
+fn depends(deb: &Path) -> Outcome {
+    // The path has a slash, so apt takes it for a file rather than a package name.
+    let output = exec::query(
+        Command::new("apt-get")
+            .args(["--simulate", "install"])
+            .arg(deb),
+    );
+    match output {
+        Ok(output) if output.status.success() => {
+            Outcome::Passed("resolve against the apt cache".to_string())
+        }
+        Ok(output) => Outcome::Failed(last_line(&output.stdout, &output.stderr)),
+        Err(err) => Outcome::Failed(format!("failed to start `apt-get`: {err}")),
+    }
+}
+
+/// `dpkg-deb <flag> <deb>`'s output.
+fn dpkg_deb(deb: &Path, flag: &str) -> anyhow::Result<String> {
+    let output = exec::query(Command::new("dpkg-deb").arg(flag).arg(deb))
+        .context("`dpkg-deb` executable was not found in PATH")?;
+    if !output.status.success() {
+        bail!(
+            "dpkg-deb {flag} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `Package version (arch)` from the control fields.
+fn fields_line(fields: &str) -> String {
+    let field = |name: &str| {
+        fields
+            .lines()
+            .find_map(|line| line.strip_prefix(&format!("{name}: ")))
+            .unwrap_or("?")
+            .to_string()
+    };
+    format!(
+        "{} {} ({})",
+        field("Package"),
+        field("Version"),
+        field("Architecture")
+    )
+}
+
+/// The last line that says something, from `first` or else `second`.
+fn last_line(first: &[u8], second: &[u8]) -> String {
+    [first, second]
+        .iter()
+        .filter_map(|output| {
+            String::from_utf8_lossy(output)
+                .lines()
+                .rev()
+                .find(|line| !line.trim().is_empty())
+                .map(|line| line.trim().to_string())
+        })
+        .next()
+        .unwrap_or_else(|| "no output".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn summarizes_the_checks_and_fails_on_unresolved_depends() {
+        let project = Project {
+            name: "tool".to_string(),
+            version: "1.0.0".to_string(),
+            deb_name: "tool".to_string(),
+            rpm_name: "tool".to_string(),
+            binaries: vec!["tool".to_string()],
+            repository: None,
+            description: None,
+            maintainer: None,
+            dir: PathBuf::from("/src"),
+            workspace_root: PathBuf::from("/src"),
+            target_dir: PathBuf::from("/src/target"),
+        };
+        let verify = Verify {
+            image: DEFAULT_IMAGE.to_string(),
+            engine: Some("podman".to_string()),
+        };
+        let deb = Path::new("/dist/tool_1.0.0-1_amd64.deb");
+        let recorder = || {
+            Recorder::new()
+                .reply(
+                    "dpkg-deb --field",
+                    0,
+                    "Package: tool\nVersion: 1.0.0-1\nArchitecture: amd64\n",
+                )
+                .reply(
+                    "dpkg-deb --contents",
+                    0,
+                    "drwxr-xr-x root/root 0 ./usr/bin/\n-rwxr-xr-x root/root 10 ./usr/bin/tool\n",
+                )
+                .reply("podman run", 0, "tool 1.0.0\n")
+        };
+
+        let passing = Arc::new(recorder());
+        runner::with(passing.clone(), || verify.check(&project, deb, true)).unwrap();
+        assert_eq!(
+            passing.calls().last().unwrap(),
+            "podman run --rm --env DEBIAN_FRONTEND=noninteractive --volume /dist:/debkit-package:ro \
+             debian:stable sh -c 'apt-get update -qq && apt-get install -y -qq \
+             /debkit-package/tool_1.0.0-1_amd64.deb >/dev/null && /usr/bin/tool --version'"
+        );
+        assert_eq!(
+            fields_line("Package: tool\nVersion: 1.0.0-1\nArchitecture: amd64\n"),
+            "tool 1.0.0-1 (amd64)"
+        );
+
+        let failing = Arc::new(recorder().reply(
+            "apt-get --simulate",
+            100,
+            "E: Unable to correct problems\n",
+        ));
+        let err = runner::with(failing, || verify.check(&project, deb, true)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "tool_1.0.0-1_amd64.deb failed verification: depends"
+        );
+    }
+}
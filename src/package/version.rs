@@ -0,0 +1,263 @@
+//! Changing the version a package is built with: `package deb --version` and
+//! `--bump`.
+//!
+//! The new version either only labels the package, through cargo-deb's
+//! `--deb-version`, or is written back to `Cargo.toml`: to `[workspace.package]` of
+//! the workspace when the crate inherits its version from there. Once the package is
+//! built, a written-back version can be committed and tagged `v<version>`.
+//!
+//! Built packages are ordered by their Debian version with [`compare_debian`].
+
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use toml_edit::{DocumentMut, Item, TableLike, value};
+
+use super::{Project, run_step};
+use crate::exec;
+
+/// Which part of `major.minor.patch` to raise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Set(String),
+    Bump(Bump),
+}
+
+/// The version `change` makes of `current`. Bumping drops pre-release and build
+/// suffixes.
+pub fn next(current: &str, change: &Change) -> anyhow::Result<String> {
+    let bump = match change {
+        Change::Set(version) => {
+            if !version.starts_with(|c: char| c.is_ascii_digit())
+                || version.contains(char::is_whitespace)
+            {
+                bail!("`{version}` is not a version; use something like 1.2.3");
+            }
+            return Ok(version.clone());
+        }
+        Change::Bump(bump) => bump,
+    };
+    let core = current.split(['-', '+']).next().unwrap_or_default();
+    let parts = core
+        .split('.')
+        .map(str::parse::<u64>)
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .filter(|parts| parts.len() == 3)
+        .with_context(|| format!("cannot bump `{current}`; it is not major.minor.patch"))?;
+    let (major, minor, patch) = (parts[0], parts[1], parts[2]);
+    Ok(match bump {
+        Bump::Patch => format!("{major}.{minor}.{}", patch + 1),
+        Bump::Minor => format!("{major}.{}.0", minor + 1),
+        Bump::Major => format!("{}.0.0", major + 1),
+    })
+}
+
+/// Orders two Debian package versions, `[epoch:]upstream[-revision]`, the way dpkg
+/// does: `~` sorts before anything, even the end, and digit runs compare as numbers.
+pub fn compare_debian(a: &str, b: &str) -> Ordering {
+    let split = |version: &str| {
+        let (epoch, rest) = match version.split_once(':') {
+            Some((epoch, rest)) => (epoch.parse::<u64>().unwrap_or(0), rest),
+            None => (0, version),
+        };
+        let (upstream, revision) = rest.rsplit_once('-').unwrap_or((rest, ""));
+        (epoch, upstream.to_string(), revision.to_string())
+    };
+    let (a, b) = (split(a), split(b));
+    a.0.cmp(&b.0)
+        .then_with(|| compare_part(a.1.as_bytes(), b.1.as_bytes()))
+        .then_with(|| compare_part(a.2.as_bytes(), b.2.as_bytes()))
+}
+
+/// dpkg's `verrevcmp`: alternating runs of non-digits, compared by [`order`], and
+/// digits, compared as numbers.
+fn compare_part(a: &[u8], b: &[u8]) -> Ordering {
+    let digit = |byte: Option<&u8>| byte.is_some_and(u8::is_ascii_digit);
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !a[i].is_ascii_digit()) || (j < b.len() && !b[j].is_ascii_digit()) {
+            let (left, right) = (order(a.get(i)), order(b.get(j)));
+            if left != right {
+                return left.cmp(&right);
+            }
+            i += 1;
+            j += 1;
+        }
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+        let mut first_difference = Ordering::Equal;
+        while digit(a.get(i)) && digit(b.get(j)) {
+            if first_difference == Ordering::Equal {
+                first_difference = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if digit(a.get(i)) {
+            return Ordering::Greater;
+        }
+        if digit(b.get(j)) {
+            return Ordering::Less;
+        }
+        if first_difference != Ordering::Equal {
+            return first_difference;
+        }
+    }
+    Ordering::Equal
+}
+
+fn order(byte: Option<&u8>) -> i32 {
+    match byte {
+        None => 0,
+        Some(b'~') => -1,
+        Some(byte) if byte.is_ascii_digit() => 0,
+        Some(byte) if byte.is_ascii_alphabetic() => i32::from(*byte),
+        Some(byte) => i32::from(*byte) + 256,
+    }
+}
+
+/// Writes `version` into the manifest `project` takes its version from, and returns
+/// that manifest's path.
+pub fn write(project: &Project, version: &str) -> anyhow::Result<PathBuf> {
+    let manifest = project.dir.join("Cargo.toml");
+    let mut document = read(&manifest)?;
+    let package = document
+        .get_mut("package")
+        .and_then(Item::as_table_like_mut)
+        .with_context(|| format!("{} has no [package] table", manifest.display()))?;
+    let inherited = package
+        .get("version")
+        .and_then(|version| version.get("workspace"))
+        .and_then(Item::as_bool)
+        .unwrap_or(false);
+    if !inherited {
+        set(package, version);
+        exec::write(&manifest, document.to_string())
+            .with_context(|| format!("failed to write {}", manifest.display()))?;
+        return Ok(manifest);
+    }
+
+    let manifest = project.workspace_root.join("Cargo.toml");
+    let mut document = read(&manifest)?;
+    let package = document
+        .get_mut("workspace")
+        .and_then(|workspace| workspace.get_mut("package"))
+        .and_then(Item::as_table_like_mut)
+        .with_context(|| format!("{} has no [workspace.package] table", manifest.display()))?;
+    set(package, version);
+    exec::write(&manifest, document.to_string())
+        .with_context(|| format!("failed to write {}", manifest.display()))?;
+    Ok(manifest)
+}
+
+/// Commits the `manifests` the version was written to, with the workspace's
+/// `Cargo.lock`, and tags the commit.
+pub fn tag(workspace_root: &Path, version: &str, manifests: &[PathBuf]) -> anyhow::Result<()> {
+    let tag = format!("v{version}");
+    let lock = workspace_root.join("Cargo.lock");
+    run_step(
+        &format!("Committing version {version}"),
+        Command::new("git")
+            .args(["commit", "--message", &format!("Release {version}"), "--"])
+            .args(manifests)
+            .args(lock.exists().then_some(&lock))
+            .current_dir(workspace_root),
+    )?;
+    run_step(
+        &format!("Tagging {tag}"),
+        Command::new("git")
+            .args([
+                "tag",
+                "--annotate",
+                &tag,
+                "--message",
+                &format!("Release {version}"),
+            ])
+            .current_dir(workspace_root),
+    )
+}
+
+/// Sets `version` in `table`, keeping the comments around the old one.
+fn set(table: &mut dyn TableLike, version: &str) {
+    match table.get_mut("version").and_then(Item::as_value_mut) {
+        Some(old) => {
+            let decor = old.decor().clone();
+            *old = version.into();
+            *old.decor_mut() = decor;
+        }
+        None => {
+            table.insert("version", value(version));
+        }
+    }
+}
+
+fn read(manifest: &Path) -> anyhow::Result<DocumentMut> {
+    fs::read_to_string(manifest)
+        .with_context(|| format!("failed to read {}", manifest.display()))?
+        .parse::<DocumentMut>()
+        .with_context(|| format!("{} is not valid TOML", manifest.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bumps_and_writes_inherited_versions_to_the_workspace() {
+        let bump = |current, bump| next(current, &Change::Bump(bump)).unwrap();
+        assert_eq!(bump("1.2.3", Bump::Patch), "1.2.4");
+        assert_eq!(bump("1.2.3-rc.1", Bump::Minor), "1.3.0");
+        assert_eq!(bump("1.2.3", Bump::Major), "2.0.0");
+        assert!(next("1.2", &Change::Bump(Bump::Patch)).is_err());
+        assert!(next("latest", &Change::Set("latest".to_string())).is_err());
+
+        let root = std::env::temp_dir().join(format!("debkit-version-{}", std::process::id()));
+        fs::create_dir_all(root.join("agent")).unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"agent\"]\n\n[workspace.package]\nversion = \"0.3.0\" # released\n",
+        )
+        .unwrap();
+        let member = "[package]\nname = \"agent\"\nversion.workspace = true\n";
+        fs::write(root.join("agent/Cargo.toml"), member).unwrap();
+        let project = Project {
+            name: "agent".to_string(),
+            version: "0.3.0".to_string(),
+            deb_name: "agent".to_string(),
+            rpm_name: "agent".to_string(),
+            binaries: vec!["agent".to_string()],
+            repository: None,
+            description: None,
+            maintainer: None,
+            dir: root.join("agent"),
+            workspace_root: root.clone(),
+            target_dir: PathBuf::from("/unused"),
+        };
+
+        assert_eq!(write(&project, "0.4.0").unwrap(), root.join("Cargo.toml"));
+        assert_eq!(
+            fs::read_to_string(root.join("Cargo.toml")).unwrap(),
+            "[workspace]\nmembers = [\"agent\"]\n\n[workspace.package]\nversion = \"0.4.0\" # released\n"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("agent/Cargo.toml")).unwrap(),
+            member
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+//! Package version pins and holds.
+//!
+//! A pin is an apt preferences file, `/etc/apt/preferences.d/debkit-<package>.pref`, that
+//! gives the requested version priority 1001 so apt installs it even when that means a
+//! downgrade. A hold is `apt-mark hold`, which keeps apt (and unattended-upgrades) from
+//! changing the installed version at all.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+
+use crate::output::say;
+use crate::{exec, privileged};
+
+pub const PREFERENCES_DIR: &str = "/etc/apt/preferences.d";
+const MARKER: &str = "# Managed by debkit";
+const PRIORITY: u32 = 1001;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Pin {
+    pub package: String,
+    pub version: String,
+    pub path: PathBuf,
+}
+
+pub fn path(package: &str) -> PathBuf {
+    Path::new(PREFERENCES_DIR).join(format!("debkit-{package}.pref"))
+}
+
+/// Pins `package` to `version`, which may end in `*` to allow any matching release.
+/// Returns whether the pin changed.
+pub fn set(package: &str, version: &str) -> anyhow::Result<bool> {
+    validate_package(package)?;
+    validate_version(version)?;
+    let path = path(package);
+    let desired = render(package, version);
+    if fs::read_to_string(&path).is_ok_and(|existing| existing == desired) {
+        return Ok(false);
+    }
+    privileged::create_dir_all(Path::new(PREFERENCES_DIR))?;
+    privileged::write(&path, desired.as_bytes())?;
+    say!("Pinned {package} to {version} in {}", path.display());
+    Ok(true)
+}
+
+/// Removes the pin for `package`. Returns whether there was one.
+pub fn remove(package: &str) -> anyhow::Result<bool> {
+    validate_package(package)?;
+    let path = path(package);
+    if !path.exists() {
+        return Ok(false);
+    }
+    privileged::remove_file(&path)?;
+    say!("Unpinned {package}");
+    Ok(true)
+}
+
+/// Every pin DebKit wrote, sorted by package.
+pub fn list() -> anyhow::Result<Vec<Pin>> {
+    let entries = match fs::read_dir(PREFERENCES_DIR) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {PREFERENCES_DIR}")),
+    };
+    let mut pins = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let is_ours = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("debkit-") && name.ends_with(".pref"));
+        if !is_ours {
+            continue;
+        }
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        if let Some((package, version)) = parse(&raw) {
+            pins.push(Pin {
+                package,
+                version,
+                path,
+            });
+        }
+    }
+    pins.sort_by(|a, b| a.package.cmp(&b.package));
+    Ok(pins)
+}
+
+/// Holds or releases `package` with `apt-mark`, doing nothing when it is already in
+/// that state. Returns whether anything changed.
+pub fn set_held(package: &str, hold: bool) -> anyhow::Result<bool> {
+    validate_package(package)?;
+    if held()?.iter().any(|held| held == package) == hold {
+        return Ok(false);
+    }
+    let verb = if hold { "hold" } else { "unhold" };
+    privileged::run("apt-mark", &[verb, package])?;
+    say!(
+        "{} {package}",
+        if hold { "Held" } else { "Released hold on" }
+    );
+    Ok(true)
+}
+
+/// Packages currently on hold, from `apt-mark showhold`.
+pub fn held() -> anyhow::Result<Vec<String>> {
+    let output = exec::query(Command::new("apt-mark").arg("showhold"))
+        .context("failed to run `apt-mark showhold`")?;
+    if !output.status.success() {
+        bail!("`apt-mark showhold` failed with status {}", output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Whether `version` satisfies a pin, where a trailing `*` matches any suffix.
+pub fn matches(version: &str, pin: &str) -> bool {
+    match pin.strip_suffix('*') {
+        Some(prefix) => version.starts_with(prefix),
+        None => version == pin,
+    }
+}
+
+fn render(package: &str, version: &str) -> String {
+    format!("{MARKER}\nPackage: {package}\nPin: version {version}\nPin-Priority: {PRIORITY}\n")
+}
+
+fn parse(raw: &str) -> Option<(String, String)> {
+    let field = |name: &str| {
+        raw.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .map(str::trim)
+    };
+    let package = field("Package")?;
+    let version = field("Pin")?.strip_prefix("version ")?.trim();
+    Some((package.to_string(), version.to_string()))
+}
+
+/// Debian package names: lower-case letters, digits, `+`, `-` and `.`, starting with a
+/// letter or digit.
+fn validate_package(package: &str) -> anyhow::Result<()> {
+    let valid = package.len() >= 2
+        && package.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && package
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+-.".contains(c));
+    if !valid {
+        bail!("invalid package name `{package}`");
+    }
+    Ok(())
+}
+
+fn validate_version(version: &str) -> anyhow::Result<()> {
+    if version.is_empty() || version.contains(char::is_whitespace) {
+        bail!("invalid version `{version}`; expected something like `0.8.4-1` or `0.8.*`");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_and_parses_preferences() {
+        let raw = render("variety", "0.8.*");
+        assert_eq!(
+            raw,
+            "# Managed by debkit\nPackage: variety\nPin: version 0.8.*\nPin-Priority: 1001\n"
+        );
+        assert_eq!(
+            parse(&raw),
+            Some(("variety".to_string(), "0.8.*".to_string()))
+        );
+        assert_eq!(parse("Package: variety\nPin: release a=stable\n"), None);
+    }
+
+    #[test]
+    fn matches_exact_and_wildcard_versions() {
+        assert!(matches("0.8.4-1", "0.8.4-1"));
+        assert!(matches("0.8.4-1", "0.8.*"));
+        assert!(!matches("0.9.0-1", "0.8.*"));
+        assert!(!matches("0.8.4-2", "0.8.4-1"));
+    }
+
+    #[test]
+    fn rejects_unsafe_names_and_versions() {
+        assert!(validate_package("variety").is_ok());
+        assert!(validate_package("libstdc++6").is_ok());
+        assert!(validate_package("../etc").is_err());
+        assert!(validate_package("Variety").is_err());
+        assert!(validate_version("0.8.4-1").is_ok());
+        assert!(validate_version("1.0\nPin-Priority: -1").is_err());
+    }
+}
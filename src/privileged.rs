@@ -0,0 +1,69 @@
+//! Commands and file changes that need root, run directly when DebKit already runs
+//! as root and through `sudo` otherwise. Everything goes through [`crate::exec`].
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, bail};
+
+use crate::{exec, system};
+
+/// `program` prepared to run as root.
+pub fn command(program: &str) -> anyhow::Result<Command> {
+    if system::is_root() {
+        return Ok(Command::new(program));
+    }
+    if !system::command_available("sudo") {
+        bail!("`{program}` requires root privileges; run as root or install `sudo` and retry");
+    }
+    let mut command = Command::new("sudo");
+    command.arg(program);
+    Ok(command)
+}
+
+/// Runs `program args...` as root and fails on a non-zero exit status.
+pub fn run(program: &str, args: &[&str]) -> anyhow::Result<()> {
+    let mut command = command(program)?;
+    command.args(args);
+    let status =
+        exec::status(&mut command).with_context(|| format!("failed to launch {program}"))?;
+    if !status.success() {
+        bail!("{program} {} failed with status {}", args.join(" "), status);
+    }
+    Ok(())
+}
+
+/// Writes `contents` to a root-owned file, showing a diff in dry runs.
+pub fn write(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    if system::is_root() {
+        return exec::write(path, contents)
+            .with_context(|| format!("failed to write {}", path.display()));
+    }
+    let mut tee = command("tee")?;
+    tee.arg(path).stdout(Stdio::null());
+    let status = exec::write_via(&mut tee, path, contents)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    if !status.success() {
+        bail!("writing {} failed with status {}", path.display(), status);
+    }
+    Ok(())
+}
+
+pub fn remove_file(path: &Path) -> anyhow::Result<()> {
+    if system::is_root() {
+        return exec::remove_file(path)
+            .with_context(|| format!("failed to remove {}", path.display()));
+    }
+    run("rm", &["-f", &path.to_string_lossy()])
+}
+
+pub fn create_dir_all(path: &Path) -> anyhow::Result<()> {
+    if path.is_dir() {
+        return Ok(());
+    }
+    if system::is_root() {
+        return exec::create_dir_all(path)
+            .with_context(|| format!("failed to create {}", path.display()));
+    }
+    run("mkdir", &["-p", &path.to_string_lossy()])
+}
@@ -0,0 +1,117 @@
+//! Progress display for long-running steps such as `apt-get install`, rustup toolchain
+//! downloads and cargo-deb builds.
+//!
+//! On a terminal a [`Step`] is a spinner with the step name, the elapsed time and the
+//! latest line of output from the command behind it; the full output is only printed
+//! if the command fails. When stdout or stderr is not a terminal, or under
+//! `--format json`, the step is announced with a plain line instead and the command's
+//! output is passed through line by line, so logs stay readable.
+
+use std::io::{self, IsTerminal};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::output::{self, say};
+
+const TICK: Duration = Duration::from_millis(100);
+
+static ACTIVE: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Runs `print` with the current spinner, if any, hidden so whatever it writes to the
+/// terminal is not drawn over.
+pub fn suspend<T>(print: impl FnOnce() -> T) -> T {
+    let active = ACTIVE.lock().unwrap().clone();
+    match active {
+        Some(spinner) => spinner.suspend(print),
+        None => print(),
+    }
+}
+
+/// Whether steps are shown as spinners rather than plain lines.
+pub fn is_animated() -> bool {
+    io::stdout().is_terminal()
+        && io::stderr().is_terminal()
+        && !output::is_json()
+        && !output::is_quiet()
+}
+
+pub struct Step {
+    name: String,
+    started: Instant,
+    spinner: Option<ProgressBar>,
+}
+
+impl Step {
+    pub fn start(name: &str) -> Self {
+        let spinner = is_animated().then(|| {
+            let style = ProgressStyle::with_template("{spinner} {prefix} ({elapsed}) {wide_msg}")
+                .expect("progress template is valid");
+            let spinner = ProgressBar::new_spinner().with_style(style);
+            spinner.set_prefix(name.to_string());
+            spinner.enable_steady_tick(TICK);
+            *ACTIVE.lock().unwrap() = Some(spinner.clone());
+            spinner
+        });
+        if spinner.is_none() {
+            say!("==> {name}");
+        }
+        Self {
+            name: name.to_string(),
+            started: Instant::now(),
+            spinner,
+        }
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.spinner.is_some()
+    }
+
+    /// Shows `line`, a line of output from the command behind the step, next to the
+    /// spinner.
+    pub fn update(&self, line: &str) {
+        if let Some(spinner) = &self.spinner {
+            // Download meters redraw themselves with `\r`; only the last frame matters.
+            let line = line.rsplit('\r').next().unwrap_or_default().trim();
+            if !line.is_empty() {
+                spinner.set_message(line.to_string());
+            }
+        }
+    }
+
+    pub fn finish(self, ok: bool) {
+        if let Some(spinner) = &self.spinner {
+            spinner.finish_and_clear();
+            ACTIVE.lock().unwrap().take();
+        }
+        let elapsed = format_elapsed(self.started.elapsed());
+        if ok {
+            say!("==> {}: done in {elapsed}", self.name);
+        } else {
+            say!("==> {}: failed after {elapsed}", self.name);
+        }
+    }
+}
+
+/// `42s`, or `3m05s` from a minute on.
+fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    if seconds < 60 {
+        format!("{seconds}s")
+    } else {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_elapsed_time() {
+        assert_eq!(format_elapsed(Duration::from_millis(900)), "0s");
+        assert_eq!(format_elapsed(Duration::from_secs(42)), "42s");
+        assert_eq!(format_elapsed(Duration::from_secs(185)), "3m05s");
+    }
+}
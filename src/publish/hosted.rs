@@ -0,0 +1,270 @@
+//! Uploading packages to hosted package services, for CI.
+//!
+//! S3 uploads go through the AWS CLI and Cloudsmith's through the cloudsmith CLI, which
+//! both read their own credentials. packagecloud and Gemfury take a plain HTTP upload
+//! with curl; their tokens are handed to curl on stdin so they never show up in a
+//! command line or the log. Each upload is retried with a growing pause in between.
+
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, bail};
+use tracing::warn;
+
+use crate::config::UploadConfig;
+use crate::package::run_step;
+use crate::{exec, network, offline, system};
+
+/// A hosted service to upload to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    S3,
+    Packagecloud,
+    Cloudsmith,
+    Gemfury,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::S3 => "s3",
+            Backend::Packagecloud => "packagecloud",
+            Backend::Cloudsmith => "cloudsmith",
+            Backend::Gemfury => "gemfury",
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Backend::S3 => "aws",
+            Backend::Cloudsmith => "cloudsmith",
+            Backend::Packagecloud | Backend::Gemfury => "curl",
+        }
+    }
+}
+
+/// Checks that `backend` is configured and its tool is installed.
+pub fn ensure_ready(backend: Backend, config: &UploadConfig) -> anyhow::Result<()> {
+    if offline::is_enabled() {
+        bail!("cannot upload with --offline");
+    }
+    let required: &[(&str, &str)] = match backend {
+        Backend::S3 => &[("s3_bucket", &config.s3_bucket)],
+        Backend::Packagecloud => &[
+            ("packagecloud_repo", &config.packagecloud_repo),
+            (
+                "packagecloud_distro_version_id",
+                &config.packagecloud_distro_version_id,
+            ),
+        ],
+        Backend::Cloudsmith => &[
+            ("cloudsmith_repo", &config.cloudsmith_repo),
+            ("cloudsmith_distribution", &config.cloudsmith_distribution),
+        ],
+        Backend::Gemfury => &[("gemfury_account", &config.gemfury_account)],
+    };
+    for (key, value) in required {
+        if value.trim().is_empty() {
+            bail!("set `upload.{key}` in the config to upload there");
+        }
+    }
+    let program = backend.program();
+    if !system::command_available(program) {
+        bail!("`{program}` is required to upload there; install it first");
+    }
+    Ok(())
+}
+
+/// Uploads `package`, trying again up to `retries` times, and returns the URL it can
+/// be found at.
+pub fn upload(
+    backend: Backend,
+    config: &UploadConfig,
+    package: &Path,
+    retries: u32,
+) -> anyhow::Result<String> {
+    let name = package
+        .file_name()
+        .context("package path has no file name")?
+        .to_string_lossy()
+        .into_owned();
+    let mut attempt = 0;
+    loop {
+        match upload_once(backend, config, package, &name) {
+            Ok(url) => return Ok(url),
+            Err(err) if attempt < retries => {
+                attempt += 1;
+                let pause = Duration::from_secs(2u64.pow(attempt));
+                warn!(
+                    "uploading {name} failed, retrying in {}s ({attempt}/{retries}): {err:#}",
+                    pause.as_secs()
+                );
+                thread::sleep(pause);
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to upload {name}"));
+            }
+        }
+    }
+}
+
+fn upload_once(
+    backend: Backend,
+    config: &UploadConfig,
+    package: &Path,
+    name: &str,
+) -> anyhow::Result<String> {
+    let step = format!("Uploading {name}");
+    match backend {
+        Backend::S3 => {
+            let key = match config.s3_prefix.trim_matches('/') {
+                "" => name.to_string(),
+                prefix => format!("{prefix}/{name}"),
+            };
+            run_step(
+                &step,
+                network::apply(&mut Command::new("aws"))
+                    .args(["s3", "cp"])
+                    .arg(package)
+                    .arg(format!("s3://{}/{key}", config.s3_bucket)),
+            )?;
+            Ok(format!(
+                "https://{}.s3.amazonaws.com/{key}",
+                config.s3_bucket
+            ))
+        }
+        Backend::Packagecloud => {
+            let token = token(
+                "PACKAGECLOUD_TOKEN",
+                &config.packagecloud_token,
+                "packagecloud_token",
+            )?;
+            curl(
+                &token,
+                &[
+                    format!(
+                        "package[distro_version_id]={}",
+                        config.packagecloud_distro_version_id
+                    ),
+                    format!("package[package_file]=@{}", package.display()),
+                ],
+                &format!(
+                    "https://packagecloud.io/api/v1/repos/{}/packages.json",
+                    config.packagecloud_repo
+                ),
+            )?;
+            Ok(format!(
+                "https://packagecloud.io/{}",
+                config.packagecloud_repo
+            ))
+        }
+        Backend::Cloudsmith => {
+            let target = format!(
+                "{}/{}",
+                config.cloudsmith_repo, config.cloudsmith_distribution
+            );
+            run_step(
+                &step,
+                network::apply(&mut Command::new("cloudsmith"))
+                    .args(["push", "deb", &target])
+                    .arg(package),
+            )?;
+            let (owner, repo) = config
+                .cloudsmith_repo
+                .split_once('/')
+                .context("`upload.cloudsmith_repo` must look like `owner/repo`")?;
+            Ok(format!(
+                "https://cloudsmith.io/~{owner}/repos/{repo}/packages/"
+            ))
+        }
+        Backend::Gemfury => {
+            let token = token("FURY_PUSH_TOKEN", &config.gemfury_token, "gemfury_token")?;
+            curl(
+                &token,
+                &[format!("package=@{}", package.display())],
+                &format!("https://push.fury.io/{}/", config.gemfury_account),
+            )?;
+            Ok(format!("https://apt.fury.io/{}/", config.gemfury_account))
+        }
+    }
+}
+
+/// The token from the environment variable `variable`, or else from the config.
+fn token(variable: &str, configured: &str, key: &str) -> anyhow::Result<String> {
+    std::env::var(variable)
+        .ok()
+        .filter(|token| !token.is_empty())
+        .or_else(|| Some(configured.to_string()).filter(|token| !token.is_empty()))
+        .with_context(|| format!("set {variable} or `upload.{key}` to upload there"))
+}
+
+/// POSTs `forms` to `url`, authenticated with `token` as the user name.
+fn curl(token: &str, forms: &[String], url: &str) -> anyhow::Result<()> {
+    let mut command = Command::new("curl");
+    network::apply(&mut command).args([
+        "-fsS",
+        "--proto",
+        "=https",
+        "--output",
+        "/dev/null",
+        "--config",
+        "-",
+    ]);
+    for form in forms {
+        command.arg("--form").arg(form);
+    }
+    command.arg(url);
+    let config = format!("user = \"{}:\"\n", token.replace(['\\', '"'], ""));
+    let status = exec::status_with_input(&mut command, config.as_bytes())
+        .context("failed to launch curl")?;
+    if !status.success() {
+        bail!("uploading to {url} failed with status {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn uploads_and_reports_where_the_package_went() {
+        let config = UploadConfig {
+            s3_bucket: "releases".to_string(),
+            s3_prefix: "/pool/main/".to_string(),
+            gemfury_account: "acme".to_string(),
+            gemfury_token: "secret".to_string(),
+            ..UploadConfig::default()
+        };
+        let deb = Path::new("/dist/tool_1.0.0-1_amd64.deb");
+        let recorder = Arc::new(Recorder::new());
+        let url = runner::with(recorder.clone(), || upload(Backend::S3, &config, deb, 0)).unwrap();
+        assert_eq!(
+            url,
+            "https://releases.s3.amazonaws.com/pool/main/tool_1.0.0-1_amd64.deb"
+        );
+        let url = runner::with(recorder.clone(), || {
+            upload(Backend::Gemfury, &config, deb, 0)
+        })
+        .unwrap();
+        assert_eq!(url, "https://apt.fury.io/acme/");
+
+        let calls = recorder.calls();
+        assert_eq!(
+            calls[0],
+            "aws s3 cp /dist/tool_1.0.0-1_amd64.deb s3://releases/pool/main/tool_1.0.0-1_amd64.deb"
+        );
+        // The token goes to curl on stdin, never on its command line.
+        assert!(!calls[1].contains("secret"), "{}", calls[1]);
+        assert!(
+            calls[1].ends_with("https://push.fury.io/acme/"),
+            "{}",
+            calls[1]
+        );
+    }
+}
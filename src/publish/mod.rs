@@ -0,0 +1,230 @@
+//! `debkit publish`: adding built `.deb`s to a local APT repository, or uploading them
+//! to a hosted service with [`hosted`].
+//!
+//! The repositories are `[publish.<name>]` tables in the config, each managed by
+//! reprepro or aptly. reprepro updates its indexes as packages are added; aptly's
+//! local repository is published, or its publication updated, afterwards. With
+//! `sign`, the `Release` file is signed again, by reprepro with gpg and by aptly
+//! itself; otherwise aptly publishes unsigned and reprepro follows its own
+//! `SignWith` setting.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+
+use crate::config::PublishRepo;
+use crate::output::say;
+use crate::package::{absolute_path, run_step};
+use crate::{exec, system};
+
+pub mod hosted;
+
+pub struct Options<'a> {
+    /// The repository's name in the config.
+    pub name: &'a str,
+    pub repo: &'a PublishRepo,
+    /// The `.deb` files to add.
+    pub packages: &'a [PathBuf],
+    /// Sign the `Release` file after adding them.
+    pub sign: bool,
+    /// The key to sign with; by default gpg's or aptly's default key.
+    pub signing_key: Option<&'a str>,
+}
+
+/// Adds the packages to the repository and refreshes its indexes. Returns the
+/// packages' absolute paths.
+pub fn run(options: Options) -> anyhow::Result<Vec<PathBuf>> {
+    let mut packages = Vec::new();
+    for package in options.packages {
+        if package
+            .extension()
+            .is_none_or(|extension| extension != "deb")
+        {
+            bail!("{} is not a .deb package", package.display());
+        }
+        let package = absolute_path(package)?;
+        if !package.is_file() {
+            bail!("{} does not exist", package.display());
+        }
+        packages.push(package);
+    }
+    if packages.is_empty() {
+        bail!("nothing to publish; name at least one .deb");
+    }
+    let backend = options.repo.backend.as_str();
+    if !system::command_available(backend) {
+        bail!(
+            "`{backend}` is required to publish to `{}`; install it with apt",
+            options.name
+        );
+    }
+
+    match backend {
+        "reprepro" => reprepro(&options, &packages)?,
+        "aptly" => aptly(&options, &packages)?,
+        other => bail!(
+            "`publish.{}.backend` must be `aptly` or `reprepro`, got `{other}`",
+            options.name
+        ),
+    }
+    say!(
+        "Published {} package(s) to {} ({} {})",
+        packages.len(),
+        options.name,
+        options.repo.distribution,
+        options.repo.component
+    );
+    Ok(packages)
+}
+
+fn reprepro(options: &Options, packages: &[PathBuf]) -> anyhow::Result<()> {
+    let repo = options.repo;
+    if repo.path.is_empty() {
+        bail!(
+            "`publish.{}.path` must name the reprepro base directory",
+            options.name
+        );
+    }
+    let base = Path::new(&repo.path);
+    for package in packages {
+        let name = package.file_name().unwrap_or_default().to_string_lossy();
+        run_step(
+            &format!("Adding {name} to {}", options.name),
+            Command::new("reprepro")
+                .arg("--basedir")
+                .arg(base)
+                .args([
+                    "--component",
+                    &repo.component,
+                    "includedeb",
+                    &repo.distribution,
+                ])
+                .arg(package),
+        )?;
+    }
+    if !options.sign {
+        return Ok(());
+    }
+
+    let dists = base.join("dists").join(&repo.distribution);
+    let release = dists.join("Release");
+    for (flag, signature) in [
+        ("--clearsign", "InRelease"),
+        ("--detach-sign", "Release.gpg"),
+    ] {
+        let mut command = Command::new("gpg");
+        command.args(["--batch", "--yes", "--armor", flag]);
+        if let Some(key) = options.signing_key {
+            command.args(["--local-user", key]);
+        }
+        command
+            .arg("--output")
+            .arg(dists.join(signature))
+            .arg(&release);
+        run_step(&format!("Signing {signature}"), &mut command)
+            .with_context(|| format!("failed to sign {}", release.display()))?;
+    }
+    Ok(())
+}
+
+fn aptly(options: &Options, packages: &[PathBuf]) -> anyhow::Result<()> {
+    let repo = options.repo;
+    let local = if repo.path.is_empty() {
+        options.name
+    } else {
+        repo.path.as_str()
+    };
+    run_step(
+        &format!("Adding {} package(s) to {local}", packages.len()),
+        Command::new("aptly")
+            .args(["repo", "add", local])
+            .args(packages),
+    )?;
+
+    let signing = match (options.sign, options.signing_key) {
+        (false, _) => vec!["-skip-signing".to_string()],
+        (true, Some(key)) => vec![format!("-gpg-key={key}")],
+        (true, None) => Vec::new(),
+    };
+    let mut command = Command::new("aptly");
+    if is_published(&repo.distribution)? {
+        command
+            .args(["publish", "update"])
+            .args(&signing)
+            .arg(&repo.distribution);
+    } else {
+        command
+            .args(["publish", "repo"])
+            .arg(format!("-distribution={}", repo.distribution))
+            .arg(format!("-component={}", repo.component))
+            .args(&signing)
+            .arg(local);
+    }
+    run_step(&format!("Publishing {local}"), &mut command)
+}
+
+/// Whether aptly already publishes `distribution` at the root prefix.
+fn is_published(distribution: &str) -> anyhow::Result<bool> {
+    let output = exec::query(Command::new("aptly").args(["publish", "list", "-raw"]))
+        .context("`aptly` executable was not found in PATH")?;
+    if !output.status.success() {
+        bail!(
+            "`aptly publish list` failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .any(|line| line.split_whitespace().eq([".", distribution])))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn adds_to_aptly_and_updates_an_existing_publication() {
+        let dir = std::env::temp_dir().join(format!("debkit-publish-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let deb = dir.join("tool_1.0.0-1_amd64.deb");
+        fs::write(&deb, "deb").unwrap();
+        let repo = PublishRepo {
+            backend: "aptly".to_string(),
+            ..PublishRepo::default()
+        };
+        let recorder = Arc::new(Recorder::new().reply("aptly publish list", 0, ". stable\n"));
+        let options = Options {
+            name: "internal",
+            repo: &repo,
+            packages: std::slice::from_ref(&deb),
+            sign: true,
+            signing_key: Some("ABCD1234"),
+        };
+
+        runner::with(recorder.clone(), || {
+            aptly(&options, std::slice::from_ref(&deb))
+        })
+        .unwrap();
+        assert_eq!(
+            recorder.calls(),
+            [
+                format!("aptly repo add internal {}", deb.display()),
+                "aptly publish list -raw".to_string(),
+                "aptly publish update -gpg-key=ABCD1234 stable".to_string(),
+            ]
+        );
+
+        let err = run(Options {
+            packages: &[dir.join("notes.txt")],
+            ..options
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("not a .deb"), "{err:#}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
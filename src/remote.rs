@@ -0,0 +1,202 @@
+//! Running DebKit on other machines over SSH.
+//!
+//! `install foundation --host user@machine` copies the running `debkit` binary and the
+//! local config (with its `hosts/` overrides) into a temporary directory on each host,
+//! runs the same command there and removes the directory again. Hosts run in
+//! parallel and every line of their output is prefixed with the host. The global
+//! `--offline`, `--proxy`, `--log-level` and `--quiet` flags are passed on to the remote
+//! run. There is no terminal to answer a sudo prompt, so the remote user needs
+//! passwordless sudo; each host is checked for it before anything is copied.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{exec, logging, network, offline, output, state};
+
+#[derive(Debug, Serialize)]
+pub struct HostResult {
+    pub host: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Reads one `user@machine` per line; blank lines and `#` comments are ignored.
+pub fn read_hosts_file(path: &Path) -> anyhow::Result<Vec<String>> {
+    let raw =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(parse_hosts(&raw))
+}
+
+fn parse_hosts(raw: &str) -> Vec<String> {
+    raw.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Runs `debkit <args>` on every host with the config at `config_path`.
+pub fn run(hosts: &[String], config_path: &Path, args: &[&str]) -> anyhow::Result<Vec<HostResult>> {
+    let exe = std::env::current_exe().context("failed to locate the debkit binary")?;
+    let dir = format!("/tmp/debkit-{}-{}", state::now(), std::process::id());
+    let results = std::thread::scope(|scope| {
+        let handles = hosts
+            .iter()
+            .map(|host| scope.spawn(|| run_on(host, &exe, config_path, &dir, args)))
+            .collect::<Vec<_>>();
+        hosts
+            .iter()
+            .zip(handles)
+            .map(|(host, handle)| {
+                let result = handle
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("remote run panicked")));
+                HostResult {
+                    host: host.clone(),
+                    ok: result.is_ok(),
+                    error: result.err().map(|err| format!("{err:#}")),
+                }
+            })
+            .collect()
+    });
+    Ok(results)
+}
+
+fn run_on(
+    host: &str,
+    exe: &Path,
+    config_path: &Path,
+    dir: &str,
+    args: &[&str],
+) -> anyhow::Result<()> {
+    let prefix = format!("[{host}] ");
+    step(&prefix, ssh(host, &[SUDO_CHECK])).with_context(|| {
+        format!(
+            "{host} needs root or passwordless sudo, since there is no terminal to type a \
+             password on; `debkit install sudo-nopass` there sets it up"
+        )
+    })?;
+    step(&prefix, ssh(host, &["mkdir", "-m", "700", dir]))?;
+    let result = copy_and_run(host, &prefix, exe, config_path, dir, args);
+    if let Err(err) = step(&prefix, ssh(host, &["rm", "-rf", dir])) {
+        warn!("{prefix}failed to remove {dir}: {err:#}");
+    }
+    result
+}
+
+fn copy_and_run(
+    host: &str,
+    prefix: &str,
+    exe: &Path,
+    config_path: &Path,
+    dir: &str,
+    args: &[&str],
+) -> anyhow::Result<()> {
+    step(prefix, scp(exe, &format!("{host}:{dir}/debkit")))?;
+    step(
+        prefix,
+        scp(config_path, &format!("{host}:{dir}/config.toml")),
+    )?;
+    let hosts_dir = config_path.parent().unwrap_or(Path::new("")).join("hosts");
+    if hosts_dir.is_dir() {
+        step(prefix, scp(&hosts_dir, &format!("{host}:{dir}/")))?;
+    }
+
+    let mut remote = Command::new(format!("{dir}/debkit"));
+    remote
+        .args(["--config", &format!("{dir}/config.toml")])
+        .args(global_flags(
+            offline::is_enabled(),
+            network::proxy_flag(),
+            logging::console_level_name().as_deref(),
+            output::is_quiet(),
+        ))
+        .args(args);
+    step(prefix, ssh(host, &[&exec::describe(&remote)]))
+}
+
+/// Succeeds when the remote user is root or may sudo without a password.
+const SUDO_CHECK: &str = "[ \"$(id -u)\" -eq 0 ] || sudo -n true";
+
+/// The global flags of this run, in the form the remote `debkit` takes them.
+fn global_flags(
+    offline: bool,
+    proxy: Option<&str>,
+    log_level: Option<&str>,
+    quiet: bool,
+) -> Vec<String> {
+    let mut flags = Vec::new();
+    if offline {
+        flags.push("--offline".to_string());
+    }
+    if let Some(proxy) = proxy {
+        flags.extend(["--proxy".to_string(), proxy.to_string()]);
+    }
+    if let Some(level) = log_level {
+        flags.extend(["--log-level".to_string(), level.to_string()]);
+    }
+    if quiet {
+        flags.push("--quiet".to_string());
+    }
+    flags
+}
+
+fn ssh(host: &str, command: &[&str]) -> Command {
+    let mut ssh = Command::new("ssh");
+    ssh.args(["-o", "BatchMode=yes", host, "--"]).args(command);
+    ssh
+}
+
+fn scp(from: &Path, to: &str) -> Command {
+    let mut scp = Command::new("scp");
+    scp.args(["-q", "-r", "-o", "BatchMode=yes"])
+        .arg(from)
+        .arg(to);
+    scp
+}
+
+fn step(prefix: &str, mut command: Command) -> anyhow::Result<()> {
+    let status = exec::status_prefixed(&mut command, prefix)
+        .with_context(|| format!("failed to start `{}`", exec::describe(&command)))?;
+    if !status.success() {
+        bail!(
+            "command `{}` failed with status {}",
+            exec::describe(&command),
+            status
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hosts_file() {
+        let raw = "# lab machines\nme@ws1\n\n  me@ws2  # desk\nws3\n";
+        assert_eq!(parse_hosts(raw), ["me@ws1", "me@ws2", "ws3"]);
+    }
+
+    #[test]
+    fn passes_global_flags_on() {
+        assert!(global_flags(false, None, None, false).is_empty());
+        assert_eq!(
+            global_flags(true, Some(""), Some("error"), true),
+            [
+                "--offline",
+                "--proxy",
+                "",
+                "--log-level",
+                "error",
+                "--quiet"
+            ]
+        );
+    }
+}
@@ -0,0 +1,505 @@
+//! Third-party APT repositories as deb822 `.sources` files in
+//! `/etc/apt/sources.list.d`.
+//!
+//! `debkit repo add` accepts a one-line `deb ...` entry or a Launchpad `ppa:owner/name`,
+//! writes `<name>.sources` with a marker comment, installs the signing key through
+//! [`crate::keyring`] and refreshes the lists of just that source. `repo remove` only
+//! touches files carrying the marker.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::output::say;
+use crate::{apt, download, keyring, privileged};
+
+pub const SOURCES_DIR: &str = "/etc/apt/sources.list.d";
+const MARKER: &str = "# Managed by debkit";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub types: String,
+    pub uri: String,
+    pub suite: String,
+    pub components: Vec<String>,
+    pub architectures: Vec<String>,
+    pub signed_by: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Spec {
+    Line(Source),
+    Ppa { owner: String, archive: String },
+}
+
+#[derive(Debug, Serialize)]
+pub struct AddedRepo {
+    pub name: String,
+    pub path: PathBuf,
+    pub signed_by: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListedRepo {
+    pub name: String,
+    pub path: PathBuf,
+    pub uris: Vec<String>,
+    pub suites: Vec<String>,
+    pub components: Vec<String>,
+    pub signed_by: Option<String>,
+    pub managed: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct AddOptions<'a> {
+    /// File name without `.sources`; derived from the source when `None`.
+    pub name: Option<&'a str>,
+    /// HTTPS URL of the signing key to install for the source.
+    pub key_url: Option<&'a str>,
+    /// Expected key fingerprint, instead of the one declared under `[keys]`.
+    pub fingerprint: Option<&'a str>,
+    /// Suite for a PPA instead of this system's Ubuntu codename.
+    pub suite: Option<&'a str>,
+}
+
+pub fn path(name: &str) -> PathBuf {
+    Path::new(SOURCES_DIR).join(format!("{name}.sources"))
+}
+
+pub fn add(spec: &str, options: AddOptions) -> anyhow::Result<AddedRepo> {
+    let spec = parse_spec(spec)?;
+    let name = match options.name {
+        Some(name) => name.to_string(),
+        None => default_name(&spec),
+    };
+    validate_name(&name)?;
+    let path = path(&name);
+    if path.exists() && !is_managed(&path) {
+        bail!(
+            "{} already exists and is not managed by DebKit",
+            path.display()
+        );
+    }
+
+    let mut fingerprint = options.fingerprint.map(str::to_string);
+    let (mut source, key_url) = match spec {
+        Spec::Line(source) => (source, options.key_url.map(str::to_string)),
+        Spec::Ppa { owner, archive } => {
+            let suite = match options.suite {
+                Some(suite) => suite.to_string(),
+                None => ubuntu_codename()?,
+            };
+            let key_url = match options.key_url {
+                Some(url) => url.to_string(),
+                None => {
+                    let launchpad = ppa_fingerprint(&owner, &archive)?;
+                    let url = format!(
+                        "https://keyserver.ubuntu.com/pks/lookup?op=get&search=0x{launchpad}"
+                    );
+                    if fingerprint.is_none() {
+                        fingerprint = Some(match keyring::declared_fingerprint(&name)? {
+                            Some(declared) => declared,
+                            None => launchpad,
+                        });
+                    }
+                    url
+                }
+            };
+            let source = Source {
+                types: "deb".to_string(),
+                uri: format!("https://ppa.launchpadcontent.net/{owner}/{archive}/ubuntu"),
+                suite,
+                components: vec!["main".to_string()],
+                architectures: Vec::new(),
+                signed_by: None,
+            };
+            (source, Some(key_url))
+        }
+    };
+    if let Some(url) = key_url {
+        if source.signed_by.is_some() {
+            bail!("the source already names its key with `signed-by`; drop --key");
+        }
+        let keyring = keyring::install(&name, &url, fingerprint.as_deref())?;
+        source.signed_by = Some(keyring.display().to_string());
+    }
+    if source.signed_by.is_none() {
+        warn!("`{name}` has no signing key; apt will only accept it if the key is already trusted");
+    }
+
+    privileged::write(&path, render(&source).as_bytes())?;
+    say!("Wrote {}", path.display());
+    apt::update_source(&path)?;
+    Ok(AddedRepo {
+        name,
+        path,
+        signed_by: source.signed_by,
+    })
+}
+
+/// Removes a DebKit-managed source and the keyring installed for it.
+pub fn remove(name: &str) -> anyhow::Result<PathBuf> {
+    validate_name(name)?;
+    let path = path(name);
+    if !path.exists() {
+        bail!("no repository named `{name}` in {SOURCES_DIR}");
+    }
+    if !is_managed(&path) {
+        bail!(
+            "{} is not managed by DebKit; remove it by hand",
+            path.display()
+        );
+    }
+    let keyring = keyring::path(name);
+    let raw = fs::read_to_string(&path).unwrap_or_default();
+    let uses_keyring = stanzas(&raw)
+        .iter()
+        .any(|stanza| field(stanza, "Signed-By").is_some_and(|value| Path::new(value) == keyring));
+
+    privileged::remove_file(&path)?;
+    say!("Removed {}", path.display());
+    if uses_keyring && keyring.exists() {
+        privileged::remove_file(&keyring)?;
+        say!("Removed {}", keyring.display());
+    }
+    Ok(path)
+}
+
+/// Every deb822 source in `/etc/apt/sources.list.d`, sorted by name.
+pub fn list() -> anyhow::Result<Vec<ListedRepo>> {
+    let entries = match fs::read_dir(SOURCES_DIR) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {SOURCES_DIR}")),
+    };
+    let mut repos = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("sources") {
+            continue;
+        }
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        repos.push(listed(name, path, &raw));
+    }
+    repos.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(repos)
+}
+
+fn listed(name: String, path: PathBuf, raw: &str) -> ListedRepo {
+    let stanzas = stanzas(raw);
+    let values = |key: &str| -> Vec<String> {
+        let mut values = Vec::new();
+        for value in stanzas.iter().filter_map(|stanza| field(stanza, key)) {
+            for word in value.split_whitespace() {
+                if !values.iter().any(|existing| existing == word) {
+                    values.push(word.to_string());
+                }
+            }
+        }
+        values
+    };
+    ListedRepo {
+        uris: values("URIs"),
+        suites: values("Suites"),
+        components: values("Components"),
+        signed_by: stanzas
+            .iter()
+            .find_map(|stanza| field(stanza, "Signed-By"))
+            .map(str::to_string),
+        managed: raw.lines().next() == Some(MARKER),
+        name,
+        path,
+    }
+}
+
+fn is_managed(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|raw| raw.lines().next() == Some(MARKER))
+}
+
+fn parse_spec(input: &str) -> anyhow::Result<Spec> {
+    let input = input.trim();
+    if let Some(ppa) = input.strip_prefix("ppa:") {
+        let Some((owner, archive)) = ppa.split_once('/') else {
+            bail!("expected `ppa:owner/name`, got `{input}`");
+        };
+        if owner.is_empty() || archive.is_empty() || archive.contains('/') {
+            bail!("expected `ppa:owner/name`, got `{input}`");
+        }
+        return Ok(Spec::Ppa {
+            owner: owner.to_string(),
+            archive: archive.to_string(),
+        });
+    }
+    parse_line(input).map(Spec::Line)
+}
+
+/// Parses a one-line entry such as
+/// `deb [arch=amd64 signed-by=/etc/apt/keyrings/x.gpg] https://example.com/apt stable main`.
+fn parse_line(line: &str) -> anyhow::Result<Source> {
+    let Some((types, rest)) = line.split_once(char::is_whitespace) else {
+        bail!("expected a `deb <uri> <suite> [components...]` line or `ppa:owner/name`");
+    };
+    if !matches!(types, "deb" | "deb-src") {
+        bail!("expected the line to start with `deb` or `deb-src`, got `{types}`");
+    }
+
+    let mut rest = rest.trim_start();
+    let mut architectures = Vec::new();
+    let mut signed_by = None;
+    if let Some(options) = rest.strip_prefix('[') {
+        let Some((options, after)) = options.split_once(']') else {
+            bail!("unterminated `[` in `{line}`");
+        };
+        for option in options.split_whitespace() {
+            match option.split_once('=') {
+                Some(("arch", value)) => {
+                    architectures.extend(value.split(',').map(str::to_string));
+                }
+                Some(("signed-by", value)) => signed_by = Some(value.to_string()),
+                _ => bail!("unsupported option `{option}`; use `arch=` or `signed-by=`"),
+            }
+        }
+        rest = after.trim_start();
+    }
+
+    let mut words = rest.split_whitespace();
+    let (Some(uri), Some(suite)) = (words.next(), words.next()) else {
+        bail!("expected a URI and a suite in `{line}`");
+    };
+    let components = words.map(str::to_string).collect::<Vec<_>>();
+    if components.is_empty() && !suite.ends_with('/') {
+        bail!(
+            "`{line}` has no components; only flat repositories (suite ending in `/`) may omit them"
+        );
+    }
+    Ok(Source {
+        types: types.to_string(),
+        uri: uri.to_string(),
+        suite: suite.to_string(),
+        components,
+        architectures,
+        signed_by,
+    })
+}
+
+fn render(source: &Source) -> String {
+    let mut out = format!(
+        "{MARKER}\nTypes: {}\nURIs: {}\nSuites: {}\n",
+        source.types, source.uri, source.suite
+    );
+    if !source.components.is_empty() {
+        out.push_str(&format!("Components: {}\n", source.components.join(" ")));
+    }
+    if !source.architectures.is_empty() {
+        out.push_str(&format!(
+            "Architectures: {}\n",
+            source.architectures.join(" ")
+        ));
+    }
+    if let Some(signed_by) = &source.signed_by {
+        out.push_str(&format!("Signed-By: {signed_by}\n"));
+    }
+    out
+}
+
+/// Name for a source added without `--name`: `ppa-owner-name` for PPAs, otherwise
+/// the repository host and path, e.g. `packages-microsoft-com-repos-code`.
+fn default_name(spec: &Spec) -> String {
+    let raw = match spec {
+        Spec::Ppa { owner, archive } => format!("ppa-{owner}-{archive}"),
+        Spec::Line(source) => {
+            let uri = source
+                .uri
+                .split_once("://")
+                .map_or(&*source.uri, |(_, rest)| rest);
+            uri.to_string()
+        }
+    };
+    let mut name = String::new();
+    for c in raw.to_ascii_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c);
+        } else if !name.ends_with('-') {
+            name.push('-');
+        }
+    }
+    name.trim_matches('-').to_string()
+}
+
+fn validate_name(name: &str) -> anyhow::Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+    if !valid {
+        bail!("invalid repository name `{name}`; use letters, digits, `-`, `_` and `.`");
+    }
+    Ok(())
+}
+
+/// Splits a deb822 file into stanzas of `(field, value)` pairs, joining
+/// continuation lines and skipping comments.
+fn stanzas(raw: &str) -> Vec<Vec<(String, String)>> {
+    let mut stanzas = Vec::new();
+    let mut current: Vec<(String, String)> = Vec::new();
+    for line in raw.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                stanzas.push(std::mem::take(&mut current));
+            }
+        } else if line.starts_with('#') {
+            continue;
+        } else if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = current.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            current.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    if !current.is_empty() {
+        stanzas.push(current);
+    }
+    stanzas
+}
+
+fn field<'a>(stanza: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    stanza
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value.as_str())
+}
+
+fn ubuntu_codename() -> anyhow::Result<String> {
+    let raw = fs::read_to_string("/etc/os-release").context("failed to read /etc/os-release")?;
+    for key in ["UBUNTU_CODENAME", "VERSION_CODENAME"] {
+        let value = raw
+            .lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+            .map(|value| value.trim_matches('"'))
+            .filter(|value| !value.is_empty());
+        if let Some(value) = value {
+            return Ok(value.to_string());
+        }
+    }
+    bail!("could not determine the release codename; pass --suite")
+}
+
+/// Looks up the PPA's signing key fingerprint on Launchpad.
+fn ppa_fingerprint(owner: &str, archive: &str) -> anyhow::Result<String> {
+    let url = format!("https://api.launchpad.net/1.0/~{owner}/+archive/ubuntu/{archive}");
+    let body = download::fetch(&url)?;
+    let info: serde_json::Value =
+        serde_json::from_slice(&body).with_context(|| format!("unexpected response from {url}"))?;
+    let Some(fingerprint) = info
+        .get("signing_key_fingerprint")
+        .and_then(|value| value.as_str())
+        .filter(|value| !value.is_empty())
+    else {
+        bail!("Launchpad did not report a signing key for ppa:{owner}/{archive}");
+    };
+    Ok(fingerprint.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_one_line_entries_with_options() {
+        let source = parse_line(
+            "deb [arch=amd64,arm64 signed-by=/etc/apt/keyrings/code.gpg] https://packages.microsoft.com/repos/code stable main",
+        )
+        .unwrap();
+        assert_eq!(
+            source,
+            Source {
+                types: "deb".to_string(),
+                uri: "https://packages.microsoft.com/repos/code".to_string(),
+                suite: "stable".to_string(),
+                components: vec!["main".to_string()],
+                architectures: vec!["amd64".to_string(), "arm64".to_string()],
+                signed_by: Some("/etc/apt/keyrings/code.gpg".to_string()),
+            }
+        );
+        assert_eq!(
+            default_name(&Spec::Line(source.clone())),
+            "packages-microsoft-com-repos-code"
+        );
+        assert_eq!(
+            render(&source),
+            "\
+# Managed by debkit
+Types: deb
+URIs: https://packages.microsoft.com/repos/code
+Suites: stable
+Components: main
+Architectures: amd64 arm64
+Signed-By: /etc/apt/keyrings/code.gpg
+"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        assert!(parse_line("deb https://example.com/apt stable").is_err());
+        assert!(parse_line("deb [trusted=yes] https://example.com/apt stable main").is_err());
+        assert!(parse_line("rpm https://example.com stable main").is_err());
+        assert!(parse_line("deb https://example.com/apt ./").is_ok());
+        assert!(parse_spec("ppa:missing-slash").is_err());
+        assert!(validate_name("../etc").is_err());
+    }
+
+    #[test]
+    fn parses_ppas() {
+        let spec = parse_spec("ppa:peek-developers/stable").unwrap();
+        assert_eq!(
+            spec,
+            Spec::Ppa {
+                owner: "peek-developers".to_string(),
+                archive: "stable".to_string(),
+            }
+        );
+        assert_eq!(default_name(&spec), "ppa-peek-developers-stable");
+    }
+
+    #[test]
+    fn lists_fields_of_deb822_files() {
+        let raw = "\
+# Managed by debkit
+Types: deb deb-src
+URIs: https://deb.example.com/apt
+Suites: bookworm
+  bookworm-updates
+Components: main
+
+Types: deb
+URIs: https://deb.example.com/apt
+Suites: bookworm-backports
+Components: main contrib
+Signed-By: /etc/apt/keyrings/example.gpg
+";
+        let repo = listed("example".to_string(), path("example"), raw);
+        assert!(repo.managed);
+        assert_eq!(repo.uris, ["https://deb.example.com/apt"]);
+        assert_eq!(
+            repo.suites,
+            ["bookworm", "bookworm-updates", "bookworm-backports"]
+        );
+        assert_eq!(repo.components, ["main", "contrib"]);
+        assert_eq!(
+            repo.signed_by.as_deref(),
+            Some("/etc/apt/keyrings/example.gpg")
+        );
+    }
+}
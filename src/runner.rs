@@ -0,0 +1,242 @@
+//! The one place DebKit starts processes.
+//!
+//! [`crate::exec`] decides whether and how a command runs (dry runs, logging, the
+//! audit log, progress); the [`CommandRunner`] underneath only runs it. The real
+//! runner is [`System`]. [`with`] swaps in another one for the current thread, such
+//! as a [`Recorder`], so install and package flows can be exercised in tests without
+//! touching apt, rustup or cargo.
+
+use std::cell::RefCell;
+use std::io::{self, BufRead, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex};
+
+use crate::exec;
+
+/// Which output stream a line passed to [`CommandRunner::stream`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+pub trait CommandRunner {
+    /// Runs `command` with the stdio it was set up with.
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus>;
+
+    /// Runs `command` with `input` on its stdin.
+    fn pipe(&self, command: &mut Command, input: &[u8]) -> io::Result<ExitStatus>;
+
+    /// Runs `command` with `input` on its stdin and captures what it prints.
+    fn output(&self, command: &mut Command, input: &[u8]) -> io::Result<Output>;
+
+    /// Runs `command` with its stdout and stderr captured, handing each line to
+    /// `line` as it arrives.
+    fn stream(
+        &self,
+        command: &mut Command,
+        line: &(dyn Fn(Stream, &str) + Sync),
+    ) -> io::Result<Output>;
+}
+
+thread_local! {
+    static RUNNER: RefCell<Option<Arc<dyn CommandRunner>>> = const { RefCell::new(None) };
+}
+
+/// Runs `action` with every command on this thread going to `runner`.
+pub fn with<T>(runner: Arc<dyn CommandRunner>, action: impl FnOnce() -> T) -> T {
+    let previous = RUNNER.with(|current| current.replace(Some(runner)));
+    let result = action();
+    RUNNER.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// The runner for this thread: the one installed with [`with`], or [`System`].
+pub fn current() -> Arc<dyn CommandRunner> {
+    RUNNER
+        .with(|current| current.borrow().clone())
+        .unwrap_or_else(|| Arc::new(System))
+}
+
+/// Runs commands for real.
+pub struct System;
+
+impl CommandRunner for System {
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        command.status()
+    }
+
+    fn pipe(&self, command: &mut Command, input: &[u8]) -> io::Result<ExitStatus> {
+        let mut child = command.stdin(Stdio::piped()).spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input)?;
+        }
+        child.wait()
+    }
+
+    fn output(&self, command: &mut Command, input: &[u8]) -> io::Result<Output> {
+        if input.is_empty() {
+            return command.output();
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input)?;
+        }
+        child.wait_with_output()
+    }
+
+    fn stream(
+        &self,
+        command: &mut Command,
+        line: &(dyn Fn(Stream, &str) + Sync),
+    ) -> io::Result<Output> {
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let (stdout, stderr) = std::thread::scope(|scope| {
+            let stderr =
+                scope.spawn(|| stderr.map(|stderr| read_lines(stderr, Stream::Stderr, line)));
+            let stdout = stdout.map(|stdout| read_lines(stdout, Stream::Stdout, line));
+            (stdout, stderr.join().ok().flatten())
+        });
+        Ok(Output {
+            status: child.wait()?,
+            stdout: stdout.unwrap_or_default(),
+            stderr: stderr.unwrap_or_default(),
+        })
+    }
+}
+
+fn read_lines(
+    reader: impl io::Read,
+    stream: Stream,
+    line: &(dyn Fn(Stream, &str) + Sync),
+) -> Vec<u8> {
+    let mut captured = Vec::new();
+    for bytes in io::BufReader::new(reader)
+        .split(b'\n')
+        .map_while(Result::ok)
+    {
+        line(stream, &String::from_utf8_lossy(&bytes));
+        captured.extend_from_slice(&bytes);
+        captured.push(b'\n');
+    }
+    captured
+}
+
+/// Runs nothing. Records each command line, as [`exec::describe`] renders it, and
+/// answers with the first reply whose prefix matches, or success with no output.
+#[derive(Default)]
+pub struct Recorder {
+    calls: Mutex<Vec<String>>,
+    replies: Vec<(String, i32, String)>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers commands whose line starts with `prefix` with exit code `code` and
+    /// `stdout`.
+    pub fn reply(mut self, prefix: &str, code: i32, stdout: &str) -> Self {
+        self.replies
+            .push((prefix.to_string(), code, stdout.to_string()));
+        self
+    }
+
+    /// The command lines run so far, in order.
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn answer(&self, command: &Command) -> Output {
+        let line = exec::describe(command);
+        let (code, stdout) = self
+            .replies
+            .iter()
+            .find(|(prefix, _, _)| line.starts_with(prefix.as_str()))
+            .map_or((0, ""), |(_, code, stdout)| (*code, stdout.as_str()));
+        self.calls.lock().unwrap().push(line);
+        Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: Vec::new(),
+        }
+    }
+}
+
+impl CommandRunner for Recorder {
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        Ok(self.answer(command).status)
+    }
+
+    fn pipe(&self, command: &mut Command, _input: &[u8]) -> io::Result<ExitStatus> {
+        Ok(self.answer(command).status)
+    }
+
+    fn output(&self, command: &mut Command, _input: &[u8]) -> io::Result<Output> {
+        Ok(self.answer(command))
+    }
+
+    fn stream(
+        &self,
+        command: &mut Command,
+        line: &(dyn Fn(Stream, &str) + Sync),
+    ) -> io::Result<Output> {
+        let output = self.answer(command);
+        for text in String::from_utf8_lossy(&output.stdout).lines() {
+            line(Stream::Stdout, text);
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorder_answers_in_place_of_the_system() {
+        let recorder = Arc::new(Recorder::new().reply("dpkg-query", 1, "unknown\n"));
+        let output = with(recorder.clone(), || {
+            current().status(Command::new("apt-get").arg("update"))?;
+            current().output(Command::new("dpkg-query").args(["-W", "git"]), &[])
+        })
+        .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(output.stdout, b"unknown\n");
+        assert_eq!(recorder.calls(), ["apt-get update", "dpkg-query -W git"]);
+    }
+
+    #[test]
+    fn system_streams_lines_from_both_outputs() {
+        let lines = Mutex::new(Vec::new());
+        let output = System
+            .stream(
+                Command::new("sh").args(["-c", "echo out; echo err >&2"]),
+                &|stream, line| lines.lock().unwrap().push((stream, line.to_string())),
+            )
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"out\n");
+        let mut lines = lines.into_inner().unwrap();
+        lines.sort_by_key(|(stream, _)| *stream == Stream::Stderr);
+        assert_eq!(
+            lines,
+            [
+                (Stream::Stdout, "out".to_string()),
+                (Stream::Stderr, "err".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,45 @@
+//! Private scratch directories for files DebKit checks and then uses, such as a
+//! downloaded installer or a throwaway gpg home.
+//!
+//! A fixed name under `/tmp` could be created first by another local user, who would
+//! then own it and could swap a file between the check and its use. [`create`] makes a
+//! fresh directory readable by this user alone and fails if the name is taken, so
+//! nothing in it comes from anyone else.
+
+use std::fs::DirBuilder;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+
+/// Creates `$TMPDIR/debkit-<purpose>-<pid>-<nanos>` with mode 700. The caller removes
+/// it when done.
+pub fn create(purpose: &str) -> anyhow::Result<PathBuf> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    let dir = std::env::temp_dir().join(format!("debkit-{purpose}-{}-{nanos}", std::process::id()));
+    DirBuilder::new()
+        .mode(0o700)
+        .create(&dir)
+        .with_context(|| format!("failed to create the private directory {}", dir.display()))?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[test]
+    fn creates_a_fresh_private_directory() {
+        let dir = create("scratch-test").unwrap();
+        let mode = fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        assert!(DirBuilder::new().mode(0o700).create(&dir).is_err());
+        fs::remove_dir(dir).unwrap();
+    }
+}
@@ -0,0 +1,292 @@
+//! `debkit self update`: replacing the running DebKit with a newer release.
+//!
+//! With `self_update.source = "github"` the latest release of `self_update.github_repo`
+//! is read from the GitHub API. Its `.deb` for this architecture is checked against the
+//! release's `SHA256SUMS` and, when the `debkit` signing key is installed (see
+//! `debkit key add`), `SHA256SUMS.asc` must carry a valid signature by it. The package
+//! is then installed like `debkit install deb`. With `source = "apt"` the version comes
+//! from the configured APT repositories, which apt already checks signatures for.
+//!
+//! Nothing is downloaded until the update is confirmed: [`latest`] only reads the
+//! release listing, and [`update`] fetches the `.deb` into a private scratch directory
+//! before installing it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::config::SelfUpdateConfig;
+use crate::{apt, audit, download, exec, keyring, scratch};
+
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+const PACKAGE: &str = "debkit";
+const CHECKSUMS: &str = "SHA256SUMS";
+const SIGNATURE: &str = "SHA256SUMS.asc";
+
+/// The newest DebKit a source offers.
+#[derive(Debug, Clone, Serialize)]
+pub struct Release {
+    /// The package version, such as `0.2.0-1`.
+    pub version: String,
+    /// The files of a GitHub release; `None` for a version from APT.
+    #[serde(skip)]
+    assets: Option<Vec<Asset>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+/// Looks up the newest release in the configured source. With `refresh`, the APT
+/// package lists are updated first; without it, as for `--check`, they are read as
+/// they are.
+pub fn latest(config: &SelfUpdateConfig, refresh: bool) -> anyhow::Result<Release> {
+    match config.source.as_str() {
+        "github" => latest_on_github(&config.github_repo),
+        "apt" => latest_in_apt(refresh),
+        source => bail!("unknown `self_update.source` `{source}`; use `github` or `apt`"),
+    }
+}
+
+fn latest_on_github(repo: &str) -> anyhow::Result<Release> {
+    let url = format!("https://api.github.com/repos/{repo}/releases/latest");
+    let raw = download::fetch(&url)?;
+    let release: GithubRelease =
+        serde_json::from_slice(&raw).with_context(|| format!("unexpected response from {url}"))?;
+    let version = release.tag_name.trim_start_matches('v').to_string();
+    let deb = release
+        .assets
+        .iter()
+        .filter(|asset| asset.name.ends_with(".deb"))
+        .find_map(|asset| deb_version(&asset.name));
+    Ok(Release {
+        version: deb.unwrap_or(version),
+        assets: Some(release.assets),
+    })
+}
+
+/// `0.2.0-1` from `debkit_0.2.0-1_amd64.deb`.
+fn deb_version(file_name: &str) -> Option<String> {
+    let rest = file_name.strip_prefix(&format!("{PACKAGE}_"))?;
+    let (version, _arch) = rest.strip_suffix(".deb")?.rsplit_once('_')?;
+    Some(version.to_string())
+}
+
+fn latest_in_apt(refresh: bool) -> anyhow::Result<Release> {
+    if refresh {
+        apt::update()?;
+    }
+    let output = exec::query(Command::new("apt-cache").args(["policy", PACKAGE]))
+        .context("failed to run `apt-cache policy`")?;
+    let policy = String::from_utf8_lossy(&output.stdout);
+    let Some(version) = candidate(&policy) else {
+        bail!("no APT repository offers `{PACKAGE}`; add one with `debkit repo add` first");
+    };
+    Ok(Release {
+        version,
+        assets: None,
+    })
+}
+
+/// The `Candidate:` line of `apt-cache policy` output.
+fn candidate(policy: &str) -> Option<String> {
+    policy
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Candidate:"))
+        .map(str::trim)
+        .filter(|version| !version.is_empty() && *version != "(none)")
+        .map(str::to_string)
+}
+
+/// Whether `release` is newer than the running DebKit. Only the upstream part of its
+/// version counts, so `0.2.0-1` is not newer than a running `0.2.0`.
+pub fn is_newer(release: &Release) -> anyhow::Result<bool> {
+    let upstream = release
+        .version
+        .rsplit_once('-')
+        .map_or(release.version.as_str(), |(upstream, _)| upstream);
+    let output = exec::query(Command::new("dpkg").args([
+        "--compare-versions",
+        upstream,
+        "gt",
+        CURRENT_VERSION,
+    ]))
+    .context("failed to run `dpkg --compare-versions`")?;
+    Ok(output.status.success())
+}
+
+/// Installs `release`: the candidate in the APT repositories, or the release's `.deb`
+/// once it is downloaded and verified. A dry run announces the download and the
+/// install instead.
+pub fn update(release: &Release) -> anyhow::Result<()> {
+    let Some(assets) = &release.assets else {
+        return apt::install(&[PACKAGE]);
+    };
+    let arch = architecture()?;
+    let deb_name = format!("{PACKAGE}_{}_{arch}.deb", release.version);
+    let deb = asset(release, assets, &deb_name)?;
+    if exec::is_dry_run() {
+        let path = std::env::temp_dir().join(&deb.name);
+        download::save(&deb.browser_download_url, &path)?;
+        exec::announce_change(&format!("would install {}", deb.name), true);
+        return Ok(());
+    }
+
+    let dir = scratch::create("update")?;
+    let result =
+        fetch(release, assets, deb, &dir).and_then(|path| apt::install_local(&path, false));
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+/// Downloads `deb` into `dir` and checks it against the release's checksums.
+fn fetch(release: &Release, assets: &[Asset], deb: &Asset, dir: &Path) -> anyhow::Result<PathBuf> {
+    let sums = download::fetch(&asset(release, assets, CHECKSUMS)?.browser_download_url)?;
+    verify_signature(release, assets, &sums)?;
+
+    let path = dir.join(&deb.name);
+    download::save(&deb.browser_download_url, &path)?;
+    let expected = checksum(&String::from_utf8_lossy(&sums), &deb.name)
+        .with_context(|| format!("{CHECKSUMS} has no entry for {}", deb.name))?;
+    let actual = audit::hash_file(&path).unwrap_or_default();
+    if actual != expected {
+        bail!(
+            "{} does not match {CHECKSUMS}: expected {expected}, got {actual}",
+            deb.name
+        );
+    }
+    Ok(path)
+}
+
+fn asset<'a>(release: &Release, assets: &'a [Asset], name: &str) -> anyhow::Result<&'a Asset> {
+    assets
+        .iter()
+        .find(|asset| asset.name == name)
+        .with_context(|| format!("release {} has no {name}", release.version))
+}
+
+/// With the `debkit` key installed the checksums must be signed by it; without it
+/// the signature cannot be checked.
+fn verify_signature(release: &Release, assets: &[Asset], sums: &[u8]) -> anyhow::Result<()> {
+    let key = keyring::path(PACKAGE);
+    if !key.is_file() {
+        warn!(
+            "not checking the release signature: install the signing key with `debkit key add {PACKAGE} <url>`"
+        );
+        return Ok(());
+    }
+    let signature = download::fetch(&asset(release, assets, SIGNATURE)?.browser_download_url)?;
+    keyring::verify_signature(&key, &signature, sums)
+        .with_context(|| format!("refusing release {}", release.version))
+}
+
+/// The SHA-256 for `file` in `sha256sum` output, whose lines look like
+/// `<hash>  <file>` or `<hash> *<file>`.
+fn checksum(sums: &str, file: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        let name = name.trim_start().trim_start_matches('*');
+        (name == file).then(|| hash.to_ascii_lowercase())
+    })
+}
+
+fn architecture() -> anyhow::Result<String> {
+    let output = exec::query(Command::new("dpkg").arg("--print-architecture"))
+        .context("failed to run `dpkg --print-architecture`")?;
+    if !output.status.success() {
+        bail!(
+            "`dpkg --print-architecture` failed with status {}",
+            output.status
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn reads_versions_and_checksums() {
+        assert_eq!(
+            deb_version("debkit_0.2.0-1_amd64.deb").as_deref(),
+            Some("0.2.0-1")
+        );
+        assert_eq!(deb_version("other_0.2.0-1_amd64.deb"), None);
+        let policy = "debkit:\n  Installed: 0.1.2-1\n  Candidate: 0.2.0-1\n  Version table:\n";
+        assert_eq!(candidate(policy).as_deref(), Some("0.2.0-1"));
+        assert_eq!(candidate("debkit:\n  Candidate: (none)\n"), None);
+        let sums = "ABC123  debkit_0.2.0-1_amd64.deb\ndef456 *debkit_0.2.0-1_arm64.deb\n";
+        assert_eq!(
+            checksum(sums, "debkit_0.2.0-1_amd64.deb").as_deref(),
+            Some("abc123")
+        );
+        assert_eq!(
+            checksum(sums, "debkit_0.2.0-1_arm64.deb").as_deref(),
+            Some("def456")
+        );
+        assert_eq!(checksum(sums, "debkit_0.2.0-1_i386.deb"), None);
+    }
+
+    #[test]
+    fn compares_only_the_upstream_version() {
+        let recorder = Arc::new(Recorder::new());
+        let release = Release {
+            version: "0.2.0-1".to_string(),
+            assets: None,
+        };
+        assert!(runner::with(recorder.clone(), || is_newer(&release)).unwrap());
+        assert_eq!(
+            recorder.calls(),
+            [format!(
+                "dpkg --compare-versions 0.2.0 gt {CURRENT_VERSION}"
+            )]
+        );
+    }
+
+    #[test]
+    fn dry_run_announces_the_update_without_downloading_it() {
+        let recorder = Arc::new(Recorder::new().reply("dpkg --print-architecture", 0, "amd64\n"));
+        let release = Release {
+            version: "0.2.0-1".to_string(),
+            assets: Some(vec![Asset {
+                name: "debkit_0.2.0-1_amd64.deb".to_string(),
+                browser_download_url: "https://127.0.0.1:9/debkit_0.2.0-1_amd64.deb".to_string(),
+            }]),
+        };
+        runner::with(recorder.clone(), || exec::dry_run(|| update(&release))).unwrap();
+        assert_eq!(recorder.calls(), ["dpkg --print-architecture"]);
+    }
+
+    #[test]
+    fn checks_the_apt_candidate_without_refreshing_the_lists() {
+        let recorder = Arc::new(Recorder::new().reply(
+            "apt-cache policy",
+            0,
+            "debkit:\n  Candidate: 0.2.0-1\n",
+        ));
+        let config = SelfUpdateConfig {
+            source: "apt".to_string(),
+            ..SelfUpdateConfig::default()
+        };
+        let release = runner::with(recorder.clone(), || latest(&config, false)).unwrap();
+        assert_eq!(release.version, "0.2.0-1");
+        assert_eq!(recorder.calls(), ["apt-cache policy debkit"]);
+    }
+}
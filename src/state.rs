@@ -0,0 +1,203 @@
+//! Record of what DebKit itself installed.
+//!
+//! `~/.local/state/debkit/state.json` (or `$XDG_STATE_HOME/debkit/state.json`) lists
+//! each target DebKit installed or configured, when, at which version, and the files
+//! it created along the way. `list` and `status all` use it to tell targets DebKit
+//! manages apart from software that was already there, and `uninstall` consults it
+//! before removing anything. Dry runs never touch it.
+//!
+//! Under sudo the state belongs to the user who ran sudo, like the rest of what
+//! DebKit sets up for them: it lives in their home, and what DebKit creates there is
+//! handed to them so later runs without sudo can still write it.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::install::user::target_user_context;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    pub targets: BTreeMap<String, TargetRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetRecord {
+    /// Seconds since the Unix epoch of the first install or configure.
+    pub installed_at: u64,
+    /// Seconds since the Unix epoch of the latest install or configure.
+    pub updated_at: u64,
+    pub version: Option<String>,
+    /// Files and directories that did not exist before DebKit created them.
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+}
+
+impl State {
+    pub fn is_managed(&self, target: &str) -> bool {
+        self.targets.contains_key(target)
+    }
+
+    /// Records a successful install or configure, keeping the original install time
+    /// and every file created by earlier runs.
+    pub fn record(&mut self, target: &str, version: Option<String>, files: &[PathBuf], now: u64) {
+        let record = self
+            .targets
+            .entry(target.to_string())
+            .or_insert_with(|| TargetRecord {
+                installed_at: now,
+                updated_at: now,
+                version: None,
+                files: Vec::new(),
+            });
+        record.updated_at = now;
+        record.version = version;
+        for file in files {
+            if !record.files.contains(file) {
+                record.files.push(file.clone());
+            }
+        }
+    }
+
+    pub fn forget(&mut self, target: &str) -> Option<TargetRecord> {
+        self.targets.remove(target)
+    }
+}
+
+/// DebKit's state directory, shared with the audit log. `$XDG_STATE_HOME` is only
+/// read without sudo, since under sudo it would be root's.
+pub fn dir() -> anyhow::Result<PathBuf> {
+    let user = target_user_context()?;
+    let base = match std::env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) if user.uid.is_none() => PathBuf::from(dir),
+        _ => user.home.join(".local").join("state"),
+    };
+    Ok(base.join("debkit"))
+}
+
+/// Creates `dir` and its missing parents, handing each new one to the target user.
+pub fn create_dir(dir: &Path) -> anyhow::Result<()> {
+    let missing: Vec<&Path> = dir.ancestors().take_while(|dir| !dir.exists()).collect();
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    missing.into_iter().rev().try_for_each(hand_over)
+}
+
+/// Gives `path`, which DebKit just created under [`dir`], to the user who ran sudo.
+/// Without sudo it is theirs already.
+pub fn hand_over(path: &Path) -> anyhow::Result<()> {
+    let user = target_user_context()?;
+    if let (Some(uid), Some(gid)) = (user.uid, user.gid) {
+        std::os::unix::fs::chown(path, Some(uid), Some(gid))
+            .with_context(|| format!("failed to hand {} over to uid {uid}", path.display()))?;
+    }
+    Ok(())
+}
+
+pub fn path() -> anyhow::Result<PathBuf> {
+    Ok(dir()?.join("state.json"))
+}
+
+/// Reads the state file; a missing file is an empty state.
+pub fn load(path: &Path) -> anyhow::Result<State> {
+    let raw = match fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(State::default()),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", path.display())),
+    };
+    serde_json::from_str(&raw).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Writes the state file through a temporary file so a crash never leaves it
+/// half-written. This is DebKit's own bookkeeping, so it bypasses [`crate::exec`].
+pub fn save(path: &Path, state: &State) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        create_dir(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let mut raw = serde_json::to_string_pretty(state)?;
+    raw.push('\n');
+    fs::write(&tmp, raw).with_context(|| format!("failed to write {}", tmp.display()))?;
+    hand_over(&tmp)?;
+    fs::rename(&tmp, path).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// The current state for read-only views such as `list`; an unreadable file is
+/// reported as a warning and treated as empty.
+pub fn current() -> State {
+    path().and_then(|path| load(&path)).unwrap_or_else(|err| {
+        warn!("{err:#}");
+        State::default()
+    })
+}
+
+/// Loads the state file, applies `change` and writes it back.
+pub fn update<T>(change: impl FnOnce(&mut State) -> T) -> anyhow::Result<T> {
+    let path = path()?;
+    let mut state = load(&path)?;
+    let result = change(&mut state);
+    save(&path, &state)?;
+    Ok(result)
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_keeps_install_time_and_merges_files() {
+        let mut state = State::default();
+        state.record("rust", None, &[PathBuf::from("/a")], 10);
+        state.record(
+            "rust",
+            Some("rustc 1.90.0".to_string()),
+            &[PathBuf::from("/a"), PathBuf::from("/b")],
+            20,
+        );
+        assert_eq!(
+            state.targets["rust"],
+            TargetRecord {
+                installed_at: 10,
+                updated_at: 20,
+                version: Some("rustc 1.90.0".to_string()),
+                files: vec![PathBuf::from("/a"), PathBuf::from("/b")],
+            }
+        );
+        assert!(state.forget("rust").is_some());
+        assert!(!state.is_managed("rust"));
+    }
+
+    #[test]
+    fn saves_and_loads_round_trip() {
+        let dir = std::env::temp_dir().join(format!("debkit-state-{}", std::process::id()));
+        let path = dir.join("debkit").join("state.json");
+        assert_eq!(load(&path).unwrap(), State::default());
+
+        let mut state = State::default();
+        state.record("git", Some("git version 2.47.3".to_string()), &[], 1);
+        save(&path, &state).unwrap();
+        assert_eq!(load(&path).unwrap(), state);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn creates_missing_parents() {
+        let dir = std::env::temp_dir().join(format!("debkit-state-dirs-{}", std::process::id()));
+        create_dir(&dir.join("debkit").join("backups")).unwrap();
+        assert!(dir.join("debkit").join("backups").is_dir());
+        create_dir(&dir.join("debkit")).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+//! Facts about the running system, read through libc instead of spawning `id`,
+//! `getent` or `sh -c "command -v ..."` for each question. That is faster, works in
+//! minimal containers without a shell, and keeps the underlying error.
+
+use std::env;
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+use nix::unistd::{Gid, Group, User, getegid, geteuid, getgrouplist, getgroups};
+
+use crate::exec;
+
+pub fn euid() -> u32 {
+    geteuid().as_raw()
+}
+
+pub fn is_root() -> bool {
+    geteuid().is_root()
+}
+
+/// The uid of `user`, or `None` if there is no such user.
+pub fn uid(user: &str) -> anyhow::Result<Option<u32>> {
+    let user = User::from_name(user).with_context(|| format!("failed to look up user `{user}`"))?;
+    Ok(user.map(|user| user.uid.as_raw()))
+}
+
+pub fn group_exists(group: &str) -> bool {
+    Group::from_name(group).is_ok_and(|group| group.is_some())
+}
+
+/// Names of the groups `user` belongs to, including its primary group; empty if there
+/// is no such user.
+pub fn user_groups(user: &str) -> anyhow::Result<Vec<String>> {
+    let Some(entry) =
+        User::from_name(user).with_context(|| format!("failed to look up user `{user}`"))?
+    else {
+        return Ok(Vec::new());
+    };
+    let name = CString::new(user).context("user name contains a NUL byte")?;
+    let gids = getgrouplist(&name, entry.gid)
+        .with_context(|| format!("failed to list the groups of `{user}`"))?;
+    Ok(group_names(gids))
+}
+
+/// Names of the groups this process runs with.
+pub fn current_groups() -> Vec<String> {
+    let mut gids = getgroups().unwrap_or_default();
+    gids.push(getegid());
+    group_names(gids)
+}
+
+fn group_names(gids: Vec<Gid>) -> Vec<String> {
+    let mut names = gids
+        .into_iter()
+        .filter_map(|gid| Group::from_gid(gid).ok().flatten())
+        .map(|group| group.name)
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Where `program` would be run from: the path itself if it contains a `/`, otherwise
+/// the first executable file of that name in `PATH`.
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return is_executable(&path).then_some(path);
+    }
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| is_executable(candidate))
+}
+
+pub fn command_available(program: &str) -> bool {
+    find_program(program).is_some()
+}
+
+/// Whether `systemctl <check> --quiet <unit>` succeeds, for checks such as
+/// `is-active` and `is-enabled`. It runs in dry runs too.
+pub fn systemctl_succeeds(check: &str, unit: &str) -> bool {
+    exec::probe(
+        Command::new("systemctl")
+            .args([check, "--quiet", unit])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null()),
+    )
+    .is_ok_and(|status| status.success())
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_programs_on_path() {
+        let sh = find_program("sh").expect("sh is on PATH");
+        assert!(sh.is_absolute(), "{}", sh.display());
+        assert_eq!(find_program(&sh.to_string_lossy()), Some(sh));
+        assert!(!command_available("definitely-not-a-debkit-program"));
+
+        let file = env::temp_dir().join(format!("debkit-not-executable-{}", std::process::id()));
+        fs::write(&file, "").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(!command_available(&file.to_string_lossy()));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn looks_up_users_and_groups() {
+        assert_eq!(uid("root").unwrap(), Some(0));
+        assert_eq!(uid("definitely-not-a-debkit-user").unwrap(), None);
+        assert!(group_exists("root"));
+        assert!(user_groups("root").unwrap().contains(&"root".to_string()));
+        assert!(
+            user_groups("definitely-not-a-debkit-user")
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
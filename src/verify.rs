@@ -0,0 +1,247 @@
+//! Trying an install in a throwaway container before running it for real.
+//!
+//! `debkit verify foundation` starts a Debian or Ubuntu container with docker or
+//! podman, copies the running `debkit` binary and the local config (with its `hosts/`
+//! overrides) into it, runs `install foundation --yes` there as root and shows the
+//! resulting `status all`. The container is removed afterwards unless `--keep` is
+//! given. The binary runs inside the image as is, so the image needs a C library at
+//! least as new as the one it was built against.
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::output::{self, say};
+use crate::{exec, state, system};
+
+pub const DEFAULT_IMAGE: &str = "debian:stable";
+const ENGINES: &[&str] = &["podman", "docker"];
+const DIR: &str = "/tmp/debkit";
+const EXE: &str = "/tmp/debkit/debkit";
+const CONFIG: &str = "/tmp/debkit/config.toml";
+
+pub struct Options<'a> {
+    pub target: &'a str,
+    /// Extra arguments for the target, such as `--keep-going`.
+    pub args: &'a [String],
+    pub image: &'a str,
+    /// `docker` or `podman`; by default whichever is installed, podman first.
+    pub engine: Option<&'a str>,
+    /// Leave the container running for a look around afterwards.
+    pub keep: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerifyReport {
+    pub ok: bool,
+    pub target: String,
+    pub image: String,
+    pub engine: String,
+    /// The container's name when it was kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The `targets` of `status all` inside the container after the install, under
+    /// `--format json`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<serde_json::Value>,
+}
+
+/// Runs `install <target>` inside a new container of `options.image` with the config
+/// at `config_path`. A failed install is reported in the result, not as an error.
+pub fn run(options: &Options, config_path: &Path) -> anyhow::Result<VerifyReport> {
+    let engine = match options.engine {
+        Some(engine) => engine.to_string(),
+        None => find_engine()?,
+    };
+    let exe = std::env::current_exe().context("failed to locate the debkit binary")?;
+    let name = format!("debkit-verify-{}-{}", state::now(), std::process::id());
+
+    say!("Starting {} in {name}", options.image);
+    step(Command::new(&engine).args([
+        "run",
+        "--detach",
+        "--name",
+        &name,
+        options.image,
+        "sleep",
+        "infinity",
+    ]))?;
+    let container = Container {
+        engine: &engine,
+        name: &name,
+    };
+    let result = container.install(&exe, config_path, options);
+    let status = container.status();
+
+    let kept = options.keep.then(|| name.clone());
+    if options.keep {
+        say!("Kept {name}; enter it with `{engine} exec -it {name} bash`.");
+    } else if let Err(err) = step(Command::new(&engine).args(["rm", "--force", &name])) {
+        warn!("failed to remove container {name}: {err:#}");
+    }
+
+    Ok(VerifyReport {
+        ok: result.is_ok(),
+        target: options.target.to_string(),
+        image: options.image.to_string(),
+        engine,
+        container: kept,
+        error: result.err().map(|err| format!("{err:#}")),
+        status,
+    })
+}
+
+/// The first container engine that is installed.
+pub fn find_engine() -> anyhow::Result<String> {
+    ENGINES
+        .iter()
+        .find(|engine| system::command_available(engine))
+        .map(|engine| engine.to_string())
+        .context("verify needs podman or docker; install one of them and retry")
+}
+
+struct Container<'a> {
+    engine: &'a str,
+    name: &'a str,
+}
+
+impl Container<'_> {
+    fn install(&self, exe: &Path, config_path: &Path, options: &Options) -> anyhow::Result<()> {
+        step(&mut self.exec(&["mkdir", "-p", DIR]))?;
+        self.copy(exe, EXE)?;
+        self.copy(config_path, CONFIG)?;
+        let hosts_dir = config_path.parent().unwrap_or(Path::new("")).join("hosts");
+        if hosts_dir.is_dir() {
+            self.copy(&hosts_dir, &format!("{DIR}/hosts"))?;
+        }
+
+        let mut args = debkit_args(&["--yes", "install", options.target]);
+        args.extend(options.args.iter().map(String::as_str));
+        step(&mut self.exec(&args))
+    }
+
+    /// Shows `status all` from inside the container, or returns it as JSON under
+    /// `--format json`. Failing to get it is only a warning.
+    fn status(&self) -> Option<serde_json::Value> {
+        if exec::is_dry_run() {
+            return None;
+        }
+        if !output::is_json() {
+            if let Err(err) = step(&mut self.exec(&debkit_args(&["status", "all"]))) {
+                warn!("failed to read the status inside {}: {err:#}", self.name);
+            }
+            return None;
+        }
+        let report =
+            exec::query(&mut self.exec(&debkit_args(&["--format", "json", "status", "all"])))
+                .context("failed to start `status all`")
+                .and_then(|output| {
+                    serde_json::from_slice::<serde_json::Value>(&output.stdout)
+                        .context("`status all` did not print JSON")
+                });
+        match report {
+            Ok(mut report) => Some(report["targets"].take()),
+            Err(err) => {
+                warn!("failed to read the status inside {}: {err:#}", self.name);
+                None
+            }
+        }
+    }
+
+    fn exec(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(self.engine);
+        command
+            .args([
+                "exec",
+                "--env",
+                "HOME=/root",
+                "--env",
+                "USER=root",
+                self.name,
+            ])
+            .args(args);
+        command
+    }
+
+    fn copy(&self, from: &Path, to: &str) -> anyhow::Result<()> {
+        step(
+            Command::new(self.engine)
+                .arg("cp")
+                .arg(from)
+                .arg(format!("{}:{to}", self.name)),
+        )
+    }
+}
+
+/// `debkit --config <copied config> <args>...` inside the container.
+fn debkit_args<'a>(args: &[&'a str]) -> Vec<&'a str> {
+    let mut full = vec![EXE, "--config", CONFIG];
+    full.extend(args);
+    full
+}
+
+fn step(command: &mut Command) -> anyhow::Result<()> {
+    let status = exec::status_prefixed(command, "  ")
+        .with_context(|| format!("failed to start `{}`", exec::describe(command)))?;
+    if !status.success() {
+        bail!(
+            "command `{}` failed with status {}",
+            exec::describe(command),
+            status
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runner::{self, Recorder};
+
+    #[test]
+    fn installs_in_a_container_and_removes_it() {
+        let recorder = Arc::new(Recorder::new());
+        let options = Options {
+            target: "git",
+            args: &["--reinstall".to_string()],
+            image: "ubuntu:24.04",
+            engine: Some("docker"),
+            keep: false,
+        };
+        let config = Path::new("/nonexistent/debkit/config.toml");
+        let report = runner::with(recorder.clone(), || run(&options, config)).unwrap();
+        assert!(report.ok, "{:?}", report.error);
+        assert_eq!(report.container, None);
+
+        let calls = recorder.calls();
+        let name = calls[0]
+            .strip_prefix("docker run --detach --name ")
+            .and_then(|rest| rest.strip_suffix(" ubuntu:24.04 sleep infinity"))
+            .unwrap();
+        let exe = std::env::current_exe().unwrap();
+        let exec = format!("docker exec --env HOME=/root --env USER=root {name}");
+        assert_eq!(
+            calls[1..],
+            [
+                format!("{exec} mkdir -p /tmp/debkit"),
+                format!("docker cp {} {name}:/tmp/debkit/debkit", exe.display()),
+                format!(
+                    "docker cp {} {name}:/tmp/debkit/config.toml",
+                    config.display()
+                ),
+                format!(
+                    "{exec} /tmp/debkit/debkit --config /tmp/debkit/config.toml --yes install git --reinstall"
+                ),
+                format!("{exec} /tmp/debkit/debkit --config /tmp/debkit/config.toml status all"),
+                format!("docker rm --force {name}"),
+            ]
+        );
+    }
+}